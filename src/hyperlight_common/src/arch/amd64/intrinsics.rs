@@ -0,0 +1,81 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+ */
+
+use core::arch::asm;
+
+use crate::intrinsics::CpuidResult;
+
+/// Exits to the host on `port` with `val` in `eax`.
+///
+/// # Safety
+/// The host must be prepared to handle an exit on `port`.
+#[inline(always)]
+pub unsafe fn out32(port: u16, val: u32) {
+    unsafe {
+        asm!("out dx, eax", in("dx") port, in("eax") val, options(preserves_flags, nomem, nostack));
+    }
+}
+
+/// Exits to the host on `port` with `val` in `eax` and `args` in
+/// `r8`, `r9` and `r10`.
+///
+/// # Safety
+/// The host must be prepared to handle an exit on `port`, and any
+/// pointers passed in `args` must be valid for the host to read.
+#[inline(always)]
+pub unsafe fn out32_with_args(port: u16, val: u32, args: [u64; 3]) {
+    unsafe {
+        asm!("out dx, eax",
+            in("dx") port,
+            in("eax") val,
+            in("r8") args[0],
+            in("r9") args[1],
+            in("r10") args[2],
+            options(preserves_flags, nomem, nostack)
+        );
+    }
+}
+
+/// Exits to the host on `port` with `val` in `rax` and `arg` in `rcx`.
+///
+/// # Safety
+/// The host must be prepared to handle an exit on `port`.
+#[inline(always)]
+pub unsafe fn out64_with_arg(port: u16, val: u64, arg: u64) {
+    unsafe {
+        asm!("out dx, al", in("dx") port, in("rax") val, in("rcx") arg, options(preserves_flags, nomem, nostack));
+    }
+}
+
+/// Reads the timestamp counter.
+#[inline(always)]
+pub fn read_timestamp() -> u64 {
+    // Safety: rdtsc is available on every x86_64 processor
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Executes CPUID for `leaf` with a subleaf of 0.
+#[inline(always)]
+pub fn cpuid(leaf: u32) -> CpuidResult {
+    // Safety: cpuid is available on every x86_64 processor
+    let r = unsafe { core::arch::x86_64::__cpuid(leaf) };
+    CpuidResult {
+        eax: r.eax,
+        ebx: r.ebx,
+        ecx: r.ecx,
+        edx: r.edx,
+    }
+}
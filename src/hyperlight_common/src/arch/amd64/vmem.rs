@@ -24,6 +24,9 @@ limitations under the License.
 //!
 //! The code uses an iterator-based approach to walk the page table hierarchy,
 //! allocating intermediate tables as needed and setting appropriate flags on leaf PTEs
+//!
+//! A PD entry may also map a 2MB page directly (see [`map_with_large_pages`]),
+//! in which case there is no PT below it.
 
 use crate::vmem::{
     BasicMapping, CowMapping, Mapping, MappingKind, TableMovabilityBase, TableOps, TableReadOps,
@@ -64,6 +67,10 @@ const PAGE_ACCESSED_SET: u64 = 1 << 5; // A - accessed bit
 const PAGE_CACHE_ENABLED: u64 = 0 << 4; // PCD - page cache disable bit not set (caching enabled)
 const PAGE_WRITE_BACK: u64 = 0 << 3; // PWT - page write-through bit not set (write-back caching)
 const PAGE_PAT_WB: u64 = 0 << 7; // PAT - page attribute table index bit (0 for write-back memory when PCD=0, PWT=0)
+/// Page Size (in a PD entry, if this bit is set then the entry maps a 2MB page instead of pointing to a PT)
+const PAGE_LARGE: u64 = 1 << 7;
+/// PAT index bit of a 2MB page entry, which moves to bit 12 since bit 7 is used by [`PAGE_LARGE`]
+const PAGE_LARGE_PAT: u64 = 1 << 12;
 
 // We use various patterns of the available-for-software-use bits to
 // represent certain special mappings.
@@ -382,7 +389,10 @@ where
     P::ChildType: UpdateParent<Op>,
 {
     let new_update_parent = x.update_parent.for_child_at_entry(x.entry_ptr);
-    if let Some(pte) = unsafe { read_pte_if_present(op, x.entry_ptr) } {
+    let existing = unsafe { read_pte_if_present(op, x.entry_ptr) };
+    if let Some(pte) = existing
+        && (pte & PAGE_LARGE) == 0
+    {
         return MapRequest {
             table_base: Op::from_phys(pte & PTE_ADDR_MASK),
             vmin: x.vmin,
@@ -392,6 +402,12 @@ where
     }
 
     let page_addr = unsafe { op.alloc_table() };
+    if let Some(pde) = existing {
+        // The entry maps a 2MB page, part of which is about to be
+        // remapped, so it needs to be split into 4KB pages first.
+        // (We never create 1GB pages, so this can only be a PD entry.)
+        unsafe { split_large_page(op, page_addr, pde) };
+    }
 
     let pte = pte_for_table::<Op>(page_addr);
     unsafe {
@@ -405,6 +421,27 @@ where
     }
 }
 
+/// Fill a freshly allocated PT with 4KB entries that map exactly the
+/// same memory, with the same permissions, as the 2MB page entry `pde`
+/// # Safety
+/// This function modifies page table data structures, and should not be called concurrently
+/// with any other operations that modify the page tables.
+unsafe fn split_large_page<Op: TableOps>(op: &Op, table: Op::TableAddr, pde: u64) {
+    // Bit 7 of a PTE is the PAT index bit, which must be clear (like
+    // bit 12 of the PDE) for write-back memory
+    let pte_base = pde & !(PAGE_LARGE | PAGE_LARGE_PAT);
+    for i in 0..(LARGE_PAGE_SIZE / PAGE_SIZE) as u64 {
+        let moved = unsafe {
+            op.write_entry(
+                Op::entry_addr(table, i << 3),
+                pte_base + i * PAGE_SIZE as u64,
+            )
+        };
+        // A table that was just allocated is never relocated
+        debug_assert!(moved.is_none());
+    }
+}
+
 /// Map a normal memory page
 /// # Safety
 /// This function modifies page table data structures, and should not be called concurrently
@@ -495,6 +532,94 @@ pub unsafe fn map<Op: TableOps>(op: &Op, mapping: Mapping) {
     .for_each(drop);
 }
 
+/// Whether the PD entry described by `r` can map a whole 2MB page for
+/// `mapping`, rather than pointing to a PT of 4KB pages.
+///
+/// This requires the entry to be fully covered by the mapping, the
+/// physical address to be 2MB aligned as well, and the mapping to be a
+/// basic one (copy-on-write mappings are resolved one 4KB page at a
+/// time by the guest). An entry that already points to a PT is left
+/// alone, so that the PT is never orphaned.
+/// # Safety
+/// This function traverses page table data structures, and should not
+/// be called concurrently with any other operations that modify the
+/// page table.
+unsafe fn can_map_large_page<Op: TableReadOps, P: UpdateParent<Op>>(
+    op: &Op,
+    mapping: &Mapping,
+    r: &MapResponse<Op, P>,
+) -> bool {
+    let phys = mapping.phys_base + (r.vmin - mapping.virt_base);
+    matches!(mapping.kind, MappingKind::Basic(_))
+        && r.len == LARGE_PAGE_SIZE as u64
+        && phys.is_multiple_of(LARGE_PAGE_SIZE as u64)
+        && unsafe { read_pte_if_present(op, r.entry_ptr) }.is_none_or(|pde| (pde & PAGE_LARGE) != 0)
+}
+
+/// Map a 2MB page of normal memory directly from a PD entry
+/// # Safety
+/// This function modifies page table data structures, and should not be called concurrently
+/// with any other operations that modify the page tables.
+#[allow(clippy::identity_op)]
+#[allow(clippy::precedence)]
+unsafe fn map_large_page<
+    Op: TableOps,
+    P: UpdateParent<
+            Op,
+            TableMoveInfo = <Op::TableMovability as TableMovabilityBase<Op>>::TableMoveInfo,
+        >,
+>(
+    op: &Op,
+    bm: &BasicMapping,
+    phys: u64,
+    r: MapResponse<Op, P>,
+) {
+    let pde = phys |
+        page_nx_flag(bm.executable) | // NX - no execute unless allowed
+        PAGE_DIRTY_SET | // prevent the CPU writing to the dirty bit
+        PAGE_ACCESSED_SET | // prevent the CPU writing to the access flag
+        PAGE_CACHE_ENABLED | // leave caching enabled
+        PAGE_WRITE_BACK | // use write-back caching
+        PAGE_USER_ACCESS_DISABLED | // dont allow user access (no code runs in user mode for now)
+        page_rw_flag(bm.writable) | // R/W - set if writable
+        PAGE_LARGE | // PS - this entry maps a 2MB page (PAT bit 12 is left clear for write-back memory)
+        PAGE_PRESENT; // P   - this entry is present
+    unsafe {
+        write_entry_updating(op, r.update_parent, r.entry_ptr, pde);
+    }
+}
+
+// There are no notable architecture-specific safety considerations
+// here, and the general conditions are documented in the
+// architecture-independent re-export in vmem.rs
+
+/// Maps a contiguous virtual address range to physical memory, like
+/// [`map`], but using 2MB pages wherever [`can_map_large_page`] allows
+/// and falling back to 4KB pages for the remainder of the range.
+#[allow(clippy::missing_safety_doc)]
+pub unsafe fn map_with_large_pages<Op: TableOps>(op: &Op, mapping: Mapping) {
+    modify_ptes::<47, 39, Op, _>(MapRequest {
+        table_base: op.root_table(),
+        vmin: mapping.virt_base,
+        len: mapping.len,
+        update_parent: Op::TableMovability::root_update_parent(),
+    })
+    .map(|r| unsafe { alloc_pte_if_needed(op, r) })
+    .flat_map(modify_ptes::<38, 30, Op, _>)
+    .map(|r| unsafe { alloc_pte_if_needed(op, r) })
+    .flat_map(modify_ptes::<29, 21, Op, _>)
+    .for_each(|r| unsafe {
+        match &mapping.kind {
+            MappingKind::Basic(bm) if can_map_large_page(op, &mapping, &r) => {
+                let phys = mapping.phys_base + (r.vmin - mapping.virt_base);
+                map_large_page(op, bm, phys, r)
+            }
+            _ => modify_ptes::<20, 12, Op, _>(alloc_pte_if_needed(op, r))
+                .for_each(|r| map_page(op, &mapping, r)),
+        }
+    });
+}
+
 /// # Safety
 /// This function traverses page table data structures, and should not
 /// be called concurrently with any other operations that modify the
@@ -554,10 +679,27 @@ pub unsafe fn virt_to_phys<'a, Op: TableReadOps + 'a>(
     .flat_map(modify_ptes::<38, 30, Op, _>)
     .filter_map(move |r| unsafe { require_pte_exist(op.as_ref(), r) })
     .flat_map(modify_ptes::<29, 21, Op, _>)
-    .filter_map(move |r| unsafe { require_pte_exist(op.as_ref(), r) })
-    .flat_map(modify_ptes::<20, 12, Op, _>)
     .filter_map(move |r| {
-        let pte = unsafe { read_pte_if_present(op.as_ref(), r.entry_ptr) }?;
+        let pde = unsafe { read_pte_if_present(op.as_ref(), r.entry_ptr) }?;
+        // A 2MB page has no PT below it, so remember its entry in
+        // order to report it as the 4KB pages that it is made of
+        let large_pde = ((pde & PAGE_LARGE) != 0).then_some(pde);
+        let pt = modify_ptes::<20, 12, Op, _>(MapRequest {
+            table_base: Op::from_phys(pde & PTE_ADDR_MASK),
+            vmin: r.vmin,
+            len: r.len,
+            update_parent: UpdateParentNone {},
+        });
+        Some(pt.map(move |r| (large_pde, r)))
+    })
+    .flatten()
+    .filter_map(move |(large_pde, r)| {
+        let pte = match large_pde {
+            Some(pde) => {
+                (pde & !(PAGE_LARGE | PAGE_LARGE_PAT)) + (r.vmin & (LARGE_PAGE_SIZE as u64 - 1))
+            }
+            None => unsafe { read_pte_if_present(op.as_ref(), r.entry_ptr) }?,
+        };
         let phys_addr = pte & PTE_ADDR_MASK;
         // Re-do the sign extension
         let sgn_bit = r.vmin >> (VA_BITS - 1);
@@ -590,6 +732,7 @@ pub unsafe fn virt_to_phys<'a, Op: TableReadOps + 'a>(
 const VA_BITS: usize = 48; // We use 48-bit virtual addresses at the moment.
 
 pub const PAGE_SIZE: usize = 4096;
+pub const LARGE_PAGE_SIZE: usize = 0x200000;
 pub const PAGE_TABLE_SIZE: usize = 4096;
 pub type PageTableEntry = u64;
 pub type VirtAddr = u64;
@@ -1048,6 +1191,196 @@ mod tests {
         );
     }
 
+    // ==================== map_with_large_pages() tests ====================
+
+    fn rw_data() -> MappingKind {
+        MappingKind::Basic(BasicMapping {
+            readable: true,
+            writable: true,
+            executable: false,
+        })
+    }
+
+    #[test]
+    fn test_map_large_page() {
+        let ops = MockTableOps::new();
+        let mapping = Mapping {
+            phys_base: 0x400000,
+            virt_base: 0x200000,
+            len: LARGE_PAGE_SIZE as u64,
+            kind: rw_data(),
+        };
+
+        unsafe { map_with_large_pages(&ops, mapping) };
+
+        // Should have allocated: PML4(exists) + PDPT + PD = 3 tables, no PT
+        assert_eq!(ops.table_count(), 3);
+
+        // PD is table 2, entry 1 (for virt_base 0x200000)
+        let pde = ops.get_entry(2, 1);
+        assert_ne!(pde & PAGE_PRESENT, 0, "PDE should be present");
+        assert_ne!(pde & PAGE_LARGE, 0, "PDE should map a large page");
+        assert_ne!(pde & PAGE_RW, 0, "PDE should be writable");
+        assert_ne!(pde & PAGE_NX, 0, "PDE should have NX set (not executable)");
+        assert_eq!(
+            pde & PTE_ADDR_MASK,
+            0x400000,
+            "PDE should map to phys 0x400000"
+        );
+    }
+
+    #[test]
+    fn test_map_large_page_falls_back_when_phys_unaligned() {
+        let ops = MockTableOps::new();
+        let mapping = Mapping {
+            phys_base: 0x401000,
+            virt_base: 0x200000,
+            len: LARGE_PAGE_SIZE as u64,
+            kind: rw_data(),
+        };
+
+        unsafe { map_with_large_pages(&ops, mapping) };
+
+        // A PT is needed since the physical address is not 2MB aligned
+        assert_eq!(ops.table_count(), 4);
+        assert_eq!(ops.get_entry(2, 1) & PAGE_LARGE, 0);
+        for i in 0..PAGE_TABLE_ENTRIES_PER_TABLE {
+            let pte = ops.get_entry(3, i);
+            assert_ne!(pte & PAGE_PRESENT, 0, "PTE {} should be present", i);
+            assert_eq!(pte & PTE_ADDR_MASK, 0x401000 + (i * PAGE_SIZE) as u64);
+        }
+    }
+
+    #[test]
+    fn test_map_large_page_falls_back_for_cow() {
+        let ops = MockTableOps::new();
+        let mapping = Mapping {
+            phys_base: 0x200000,
+            virt_base: 0x200000,
+            len: LARGE_PAGE_SIZE as u64,
+            kind: MappingKind::Cow(CowMapping {
+                readable: true,
+                executable: false,
+            }),
+        };
+
+        unsafe { map_with_large_pages(&ops, mapping) };
+
+        assert_eq!(ops.table_count(), 4);
+        assert_eq!(ops.get_entry(2, 1) & PAGE_LARGE, 0);
+        assert_eq!(ops.get_entry(3, 0) & PTE_AVL_MASK, PAGE_AVL_COW);
+    }
+
+    #[test]
+    fn test_map_large_page_unaligned_edges() {
+        let ops = MockTableOps::new();
+        // Covers the last 4KB of the first 2MB, the whole second 2MB
+        // and the first 4KB of the third 2MB
+        let mapping = Mapping {
+            phys_base: 0x1ff000,
+            virt_base: 0x1ff000,
+            len: LARGE_PAGE_SIZE as u64 + 2 * PAGE_SIZE as u64,
+            kind: rw_data(),
+        };
+
+        unsafe { map_with_large_pages(&ops, mapping) };
+
+        // PML4 + PDPT + PD + a PT for each of the edges
+        assert_eq!(ops.table_count(), 5);
+        assert_eq!(ops.get_entry(2, 0) & PAGE_LARGE, 0);
+        assert_ne!(ops.get_entry(2, 1) & PAGE_LARGE, 0);
+        assert_eq!(ops.get_entry(2, 2) & PAGE_LARGE, 0);
+
+        let mappings: Vec<_> =
+            unsafe { virt_to_phys(&ops, 0x1ff000, LARGE_PAGE_SIZE as u64 + 0x2000) }.collect();
+        assert_eq!(mappings.len(), PAGE_TABLE_ENTRIES_PER_TABLE + 2);
+        for m in mappings {
+            assert_eq!(m.phys_base, m.virt_base, "mapping should be identity");
+        }
+    }
+
+    #[test]
+    fn test_virt_to_phys_large_page() {
+        let ops = MockTableOps::new();
+        let mapping = Mapping {
+            phys_base: 0x600000,
+            virt_base: 0x200000,
+            len: LARGE_PAGE_SIZE as u64,
+            kind: MappingKind::Basic(BasicMapping {
+                readable: true,
+                writable: false,
+                executable: true,
+            }),
+        };
+
+        unsafe { map_with_large_pages(&ops, mapping) };
+
+        // Large pages are reported as the 4KB pages they are made of
+        let mappings: Vec<_> =
+            unsafe { virt_to_phys(&ops, 0x200000, LARGE_PAGE_SIZE as u64) }.collect();
+        assert_eq!(mappings.len(), PAGE_TABLE_ENTRIES_PER_TABLE);
+
+        let result = unsafe { virt_to_phys(&ops, 0x205123, 1).next() }.unwrap();
+        assert_eq!(result.virt_base, 0x205000);
+        assert_eq!(result.phys_base, 0x605000);
+        assert_eq!(result.len, PAGE_SIZE as u64);
+        assert_eq!(
+            result.kind,
+            MappingKind::Basic(BasicMapping {
+                readable: true,
+                writable: false,
+                executable: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_map_splits_large_page() {
+        let ops = MockTableOps::new();
+        let mapping = Mapping {
+            phys_base: 0x200000,
+            virt_base: 0x200000,
+            len: LARGE_PAGE_SIZE as u64,
+            kind: rw_data(),
+        };
+        unsafe { map_with_large_pages(&ops, mapping) };
+        assert_eq!(ops.table_count(), 3);
+
+        // Remap a single page inside the large page
+        let mapping = Mapping {
+            phys_base: 0x900000,
+            virt_base: 0x203000,
+            len: PAGE_SIZE as u64,
+            kind: MappingKind::Cow(CowMapping {
+                readable: true,
+                executable: false,
+            }),
+        };
+        unsafe { map(&ops, mapping) };
+
+        // The PD entry now points to a PT
+        assert_eq!(ops.table_count(), 4);
+        let pde = ops.get_entry(2, 1);
+        assert_eq!(pde & PAGE_LARGE, 0);
+        assert_eq!(pde & PTE_ADDR_MASK, 3 * PAGE_TABLE_SIZE as u64);
+
+        // Every other page keeps the original mapping
+        let mappings: Vec<_> =
+            unsafe { virt_to_phys(&ops, 0x200000, LARGE_PAGE_SIZE as u64) }.collect();
+        assert_eq!(mappings.len(), PAGE_TABLE_ENTRIES_PER_TABLE);
+        for m in mappings {
+            if m.virt_base == 0x203000 {
+                assert_eq!(m.phys_base, 0x900000);
+                assert!(matches!(m.kind, MappingKind::Cow(_)));
+            } else {
+                assert_eq!(m.phys_base, m.virt_base);
+                assert_eq!(m.kind, rw_data());
+            }
+        }
+        let pte = ops.get_entry(3, 0);
+        assert_eq!(pte & (PAGE_LARGE | PAGE_LARGE_PAT), 0);
+    }
+
     // ==================== ModifyPteIterator tests ====================
 
     #[test]
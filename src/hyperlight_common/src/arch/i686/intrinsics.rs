@@ -0,0 +1,52 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+ */
+
+use core::arch::asm;
+
+use crate::intrinsics::CpuidResult;
+
+/// Exits to the host on `port` with `val` in `eax`.
+///
+/// # Safety
+/// The host must be prepared to handle an exit on `port`.
+#[inline(always)]
+pub unsafe fn out32(port: u16, val: u32) {
+    unsafe {
+        asm!("out dx, eax", in("dx") port, in("eax") val, options(preserves_flags, nomem, nostack));
+    }
+}
+
+/// Reads the timestamp counter.
+#[inline(always)]
+pub fn read_timestamp() -> u64 {
+    // Safety: rdtsc is available on every processor hyperlight runs
+    // 32-bit guests on
+    unsafe { core::arch::x86::_rdtsc() }
+}
+
+/// Executes CPUID for `leaf` with a subleaf of 0.
+#[inline(always)]
+pub fn cpuid(leaf: u32) -> CpuidResult {
+    // Safety: cpuid is available on every processor hyperlight runs
+    // 32-bit guests on
+    let r = unsafe { core::arch::x86::__cpuid(leaf) };
+    CpuidResult {
+        eax: r.eax,
+        ebx: r.ebx,
+        ecx: r.ecx,
+        edx: r.edx,
+    }
+}
@@ -20,6 +20,7 @@ limitations under the License.
 use crate::vmem::{Mapping, TableOps, TableReadOps, Void};
 
 pub const PAGE_SIZE: usize = 4096;
+pub const LARGE_PAGE_SIZE: usize = 0x200000;
 pub const PAGE_TABLE_SIZE: usize = 4096;
 pub type PageTableEntry = u32;
 pub type VirtAddr = u32;
@@ -30,6 +31,13 @@ pub unsafe fn map<Op: TableOps>(_op: &Op, _mapping: Mapping) {
     panic!("vmem::map: i686 guests do not support booting the full hyperlight guest kernel");
 }
 
+#[allow(clippy::missing_safety_doc)]
+pub unsafe fn map_with_large_pages<Op: TableOps>(_op: &Op, _mapping: Mapping) {
+    panic!(
+        "vmem::map_with_large_pages: i686 guests do not support booting the full hyperlight guest kernel"
+    );
+}
+
 #[allow(clippy::missing_safety_doc)]
 pub unsafe fn virt_to_phys<Op: TableOps>(_op: &Op, _address: u64) -> impl Iterator<Item = Mapping> {
     panic!(
@@ -0,0 +1,68 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+ */
+
+//! Fallback for architectures without native intrinsics. A guest cannot
+//! reach the host on these architectures, so every intrinsic panics
+//! rather than letting the guest run on past a lost exit.
+
+use crate::intrinsics::CpuidResult;
+
+/// Panics, there is no port I/O to the host.
+///
+/// # Safety
+/// Always safe, `unsafe` to match the native implementations.
+#[inline(always)]
+pub unsafe fn out32(port: u16, _val: u32) {
+    unsupported("out32", port)
+}
+
+/// Panics, there is no port I/O to the host.
+///
+/// # Safety
+/// Always safe, `unsafe` to match the native implementations.
+#[inline(always)]
+pub unsafe fn out32_with_args(port: u16, _val: u32, _args: [u64; 3]) {
+    unsupported("out32_with_args", port)
+}
+
+/// Panics, there is no port I/O to the host.
+///
+/// # Safety
+/// Always safe, `unsafe` to match the native implementations.
+#[inline(always)]
+pub unsafe fn out64_with_arg(port: u16, _val: u64, _arg: u64) {
+    unsupported("out64_with_arg", port)
+}
+
+/// Panics, there is no timestamp counter.
+#[inline(always)]
+pub fn read_timestamp() -> u64 {
+    unsupported("read_timestamp", 0)
+}
+
+/// Panics, there is no CPUID.
+#[inline(always)]
+pub fn cpuid(leaf: u32) -> CpuidResult {
+    unsupported("cpuid", leaf)
+}
+
+#[cold]
+fn unsupported(intrinsic: &str, arg: impl core::fmt::LowerHex) -> ! {
+    panic!(
+        "{}({:#x}) is not supported on this architecture",
+        intrinsic, arg
+    )
+}
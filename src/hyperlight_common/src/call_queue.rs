@@ -0,0 +1,28 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The guest function every guest built with `hyperlight_guest_bin`
+//! provides to run several guest function calls queued by the host
+//! without exiting to the host between them.
+
+/// The guest function that runs the calls queued by the host. It takes
+/// the number of queued calls as a `u32` and returns nothing.
+///
+/// The host pushes the queued calls onto the input buffer below the call
+/// of this function, the first call on top, and the guest pushes the
+/// result of each call onto the output buffer in order, whether the call
+/// failed or not, below the result of this function.
+pub const CALL_QUEUE_FUNCTION: &str = "__hl_call_queue";
@@ -0,0 +1,99 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The functions every guest built with `hyperlight_guest_bin` provides
+//! so that the host can check a deployment end to end, and the host
+//! function they call back.
+
+use alloc::vec::Vec;
+
+/// The guest function that checks the guest allocator and a host
+/// function call round trip. It takes no argument and returns a `String`
+/// describing the checks, or an error if one failed.
+pub const SELF_TEST_FUNCTION: &str = "__hl_selftest";
+
+/// The guest function that times the guest allocator and host function
+/// calls. It takes the number of iterations as a `u32` and returns a
+/// [`GuestBenchTimings`] encoded with [`GuestBenchTimings::to_bytes`].
+pub const BENCH_FUNCTION: &str = "__hl_bench";
+
+/// The guest function that lists the functions registered by the guest.
+/// It takes no argument and returns their names and signatures, encoded
+/// as a
+/// [`HostFunctionDetails`](crate::flatbuffer_wrappers::host_function_details::HostFunctionDetails)
+/// in a `Vec<u8>`.
+pub const LIST_FUNCTIONS_FUNCTION: &str = "__hl_functions";
+
+/// The host function, registered in every sandbox, that returns the
+/// `Vec<u8>` it is given
+pub const HOST_ECHO_FUNCTION: &str = "__hl_echo";
+
+/// The timings measured by [`BENCH_FUNCTION`], in TSC cycles of the guest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GuestBenchTimings {
+    /// The number of times each operation was run
+    pub iterations: u32,
+    /// The cycles spent allocating, filling and freeing buffers
+    pub alloc_cycles: u64,
+    /// The cycles spent calling [`HOST_ECHO_FUNCTION`]
+    pub host_call_cycles: u64,
+}
+
+impl GuestBenchTimings {
+    const ENCODED_SIZE: usize = 4 + 8 + 8;
+
+    /// Encodes the timings as little-endian integers
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::ENCODED_SIZE);
+        bytes.extend_from_slice(&self.iterations.to_le_bytes());
+        bytes.extend_from_slice(&self.alloc_cycles.to_le_bytes());
+        bytes.extend_from_slice(&self.host_call_cycles.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes timings encoded with [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::ENCODED_SIZE {
+            return None;
+        }
+        let (iterations, rest) = bytes.split_at(4);
+        let (alloc_cycles, host_call_cycles) = rest.split_at(8);
+        Some(Self {
+            iterations: u32::from_le_bytes(iterations.try_into().ok()?),
+            alloc_cycles: u64::from_le_bytes(alloc_cycles.try_into().ok()?),
+            host_call_cycles: u64::from_le_bytes(host_call_cycles.try_into().ok()?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GuestBenchTimings;
+
+    #[test]
+    fn bench_timings_round_trip() {
+        let timings = GuestBenchTimings {
+            iterations: 100,
+            alloc_cycles: 123_456,
+            host_call_cycles: u64::MAX,
+        };
+        assert_eq!(
+            GuestBenchTimings::from_bytes(&timings.to_bytes()),
+            Some(timings)
+        );
+        assert_eq!(GuestBenchTimings::from_bytes(&[0; 3]), None);
+    }
+}
@@ -142,6 +142,10 @@ impl FunctionCallResult {
     pub fn into_inner(self) -> core::result::Result<ReturnValue, GuestError> {
         self.0
     }
+
+    pub fn is_err(&self) -> bool {
+        self.0.is_err()
+    }
 }
 
 impl TryFrom<&[u8]> for FunctionCallResult {
@@ -35,6 +35,7 @@ pub enum ErrorCode {
     GispatchFunctionPointerNotSet = 6,
     OutbError = 7,
     UnknownError = 8,
+    StackOverflow = 9,
     GsCheckFailed = 10,
     TooManyGuestFunctions = 11,
     FailureInDlmalloc = 12,
@@ -43,6 +44,7 @@ pub enum ErrorCode {
     GuestError = 15,
     ArrayLengthParamIsMissing = 16,
     HostFunctionError = 17,
+    HostFunctionDenied = 18,
 }
 
 impl From<ErrorCode> for FbErrorCode {
@@ -58,6 +60,7 @@ impl From<ErrorCode> for FbErrorCode {
             ErrorCode::GispatchFunctionPointerNotSet => Self::GispatchFunctionPointerNotSet,
             ErrorCode::OutbError => Self::OutbError,
             ErrorCode::UnknownError => Self::UnknownError,
+            ErrorCode::StackOverflow => Self::StackOverflow,
             ErrorCode::GsCheckFailed => Self::GsCheckFailed,
             ErrorCode::TooManyGuestFunctions => Self::TooManyGuestFunctions,
             ErrorCode::FailureInDlmalloc => Self::FailureInDlmalloc,
@@ -68,6 +71,7 @@ impl From<ErrorCode> for FbErrorCode {
             ErrorCode::GuestError => Self::GuestError,
             ErrorCode::ArrayLengthParamIsMissing => Self::ArrayLengthParamIsMissing,
             ErrorCode::HostFunctionError => Self::HostError,
+            ErrorCode::HostFunctionDenied => Self::HostFunctionDenied,
         }
     }
 }
@@ -84,6 +88,7 @@ impl From<FbErrorCode> for ErrorCode {
             }
             FbErrorCode::GispatchFunctionPointerNotSet => Self::GispatchFunctionPointerNotSet,
             FbErrorCode::OutbError => Self::OutbError,
+            FbErrorCode::StackOverflow => Self::StackOverflow,
             FbErrorCode::GsCheckFailed => Self::GsCheckFailed,
             FbErrorCode::TooManyGuestFunctions => Self::TooManyGuestFunctions,
             FbErrorCode::FailureInDlmalloc => Self::FailureInDlmalloc,
@@ -94,6 +99,7 @@ impl From<FbErrorCode> for ErrorCode {
             FbErrorCode::GuestError => Self::GuestError,
             FbErrorCode::ArrayLengthParamIsMissing => Self::ArrayLengthParamIsMissing,
             FbErrorCode::HostError => Self::HostFunctionError,
+            FbErrorCode::HostFunctionDenied => Self::HostFunctionDenied,
             _ => Self::UnknownError,
         }
     }
@@ -110,6 +116,7 @@ impl From<u64> for ErrorCode {
             6 => Self::GispatchFunctionPointerNotSet,
             7 => Self::OutbError,
             8 => Self::UnknownError,
+            9 => Self::StackOverflow,
             10 => Self::GsCheckFailed,
             11 => Self::TooManyGuestFunctions,
             12 => Self::FailureInDlmalloc,
@@ -118,6 +125,7 @@ impl From<u64> for ErrorCode {
             15 => Self::GuestError,
             16 => Self::ArrayLengthParamIsMissing,
             17 => Self::HostFunctionError,
+            18 => Self::HostFunctionDenied,
             _ => Self::UnknownError,
         }
     }
@@ -134,6 +142,7 @@ impl From<ErrorCode> for u64 {
             ErrorCode::GispatchFunctionPointerNotSet => 6,
             ErrorCode::OutbError => 7,
             ErrorCode::UnknownError => 8,
+            ErrorCode::StackOverflow => 9,
             ErrorCode::GsCheckFailed => 10,
             ErrorCode::TooManyGuestFunctions => 11,
             ErrorCode::FailureInDlmalloc => 12,
@@ -142,6 +151,7 @@ impl From<ErrorCode> for u64 {
             ErrorCode::GuestError => 15,
             ErrorCode::ArrayLengthParamIsMissing => 16,
             ErrorCode::HostFunctionError => 17,
+            ErrorCode::HostFunctionDenied => 18,
         }
     }
 }
@@ -159,6 +169,7 @@ impl From<ErrorCode> for String {
             ErrorCode::GispatchFunctionPointerNotSet => "GispatchFunctionPointerNotSet".to_string(),
             ErrorCode::OutbError => "OutbError".to_string(),
             ErrorCode::UnknownError => "UnknownError".to_string(),
+            ErrorCode::StackOverflow => "StackOverflow".to_string(),
             ErrorCode::GsCheckFailed => "GsCheckFailed".to_string(),
             ErrorCode::TooManyGuestFunctions => "TooManyGuestFunctions".to_string(),
             ErrorCode::FailureInDlmalloc => "FailureInDlmalloc".to_string(),
@@ -169,6 +180,7 @@ impl From<ErrorCode> for String {
             ErrorCode::GuestError => "GuestError".to_string(),
             ErrorCode::ArrayLengthParamIsMissing => "ArrayLengthParamIsMissing".to_string(),
             ErrorCode::HostFunctionError => "HostFunctionError".to_string(),
+            ErrorCode::HostFunctionDenied => "HostFunctionDenied".to_string(),
         }
     }
 }
@@ -156,6 +156,9 @@ pub enum GuestEvent {
     GuestStart {
         /// Timestamp Counter (TSC) value when the guest started.
         tsc: u64,
+        /// Frequency of the TSC in Hz, as reported by the guest CPU, or 0
+        /// if the guest does not know it and the host has to measure it.
+        tsc_freq: u64,
     },
 }
 
@@ -287,9 +290,10 @@ impl TryFrom<&[u8]> for GuestEvent {
 
                 // Extract fields
                 let tsc = gst_fb.tsc();
+                let tsc_freq = gst_fb.tsc_freq();
 
                 // Construct GuestStart event
-                GuestEvent::GuestStart { tsc }
+                GuestEvent::GuestStart { tsc, tsc_freq }
             }
 
             _ => {
@@ -536,8 +540,11 @@ impl<T: Fn(&[u8])> EventsEncoder for EventsBatchEncoderGeneric<T> {
 
                 FbGuestEventEnvelopeType::create(&mut builder, &envelope_args)
             }
-            GuestEvent::GuestStart { tsc } => {
-                let gst_args = FbGuestStartTypeArgs { tsc: *tsc };
+            GuestEvent::GuestStart { tsc, tsc_freq } => {
+                let gst_args = FbGuestStartTypeArgs {
+                    tsc: *tsc,
+                    tsc_freq: *tsc_freq,
+                };
                 let gs_fb = FbGuestStartType::create(&mut builder, &gst_args);
                 // Create the GuestEventEnvelopeType
                 let guest_event_fb = FbGuestEventType::GuestStart;
@@ -599,7 +606,7 @@ mod estimate {
     const CLOSE_TABLE_OVERHEAD: usize = 32;
     const LOG_TABLE_OVERHEAD: usize = 52;
     const EDIT_TABLE_OVERHEAD: usize = 40;
-    const GUEST_START_TABLE_OVERHEAD: usize = 24;
+    const GUEST_START_TABLE_OVERHEAD: usize = 32;
 
     /// Round up to next multiple of 4.
     fn pad4(x: usize) -> usize {
@@ -815,7 +822,10 @@ mod estimate {
 
         #[test]
         fn test_estimate_guest_start_reasonable() {
-            let event = GuestEvent::GuestStart { tsc: 0 };
+            let event = GuestEvent::GuestStart {
+                tsc: 0,
+                tsc_freq: 0,
+            };
             let estimate = estimate_event(&event);
             let actual = encoded_size(&event);
             assert_estimate_bounds(actual, estimate);
@@ -823,8 +833,14 @@ mod estimate {
 
         #[test]
         fn test_estimate_guest_start_corner_cases() {
-            let very_large = GuestEvent::GuestStart { tsc: u64::MAX };
-            let zero = GuestEvent::GuestStart { tsc: 0 };
+            let very_large = GuestEvent::GuestStart {
+                tsc: u64::MAX,
+                tsc_freq: u64::MAX,
+            };
+            let zero = GuestEvent::GuestStart {
+                tsc: 0,
+                tsc_freq: 0,
+            };
 
             for event in [&very_large, &zero] {
                 let estimate = estimate_event(event);
@@ -936,8 +952,18 @@ mod tests {
                     assert_eq!(oid, did);
                     assert_eq!(otsc, dtsc);
                 }
-                (GuestEvent::GuestStart { tsc: otsc }, GuestEvent::GuestStart { tsc: dtsc }) => {
+                (
+                    GuestEvent::GuestStart {
+                        tsc: otsc,
+                        tsc_freq: ofreq,
+                    },
+                    GuestEvent::GuestStart {
+                        tsc: dtsc,
+                        tsc_freq: dfreq,
+                    },
+                ) => {
                     assert_eq!(otsc, dtsc);
+                    assert_eq!(ofreq, dfreq);
                 }
                 (
                     GuestEvent::EditSpan {
@@ -989,7 +1015,10 @@ mod tests {
         };
 
         let events = [
-            GuestEvent::GuestStart { tsc: 50 },
+            GuestEvent::GuestStart {
+                tsc: 50,
+                tsc_freq: 3_000_000_000,
+            },
             GuestEvent::OpenSpan {
                 id: 1,
                 parent_id: None,
@@ -1201,7 +1230,10 @@ mod tests {
             value: "edit_value1".to_string(),
         };
         let events = [
-            GuestEvent::GuestStart { tsc: 50 },
+            GuestEvent::GuestStart {
+                tsc: 50,
+                tsc_freq: 3_000_000_000,
+            },
             GuestEvent::OpenSpan {
                 id: 1,
                 parent_id: None,
@@ -1245,7 +1277,10 @@ mod tests {
         };
 
         let events = [
-            GuestEvent::GuestStart { tsc: 50 },
+            GuestEvent::GuestStart {
+                tsc: 50,
+                tsc_freq: 3_000_000_000,
+            },
             GuestEvent::OpenSpan {
                 id: 1,
                 parent_id: None,
@@ -19,13 +19,13 @@ pub const ENUM_MIN_ERROR_CODE: u64 = 0;
     since = "2.0.0",
     note = "Use associated constants instead. This will no longer be generated in 2021."
 )]
-pub const ENUM_MAX_ERROR_CODE: u64 = 17;
+pub const ENUM_MAX_ERROR_CODE: u64 = 18;
 #[deprecated(
     since = "2.0.0",
     note = "Use associated constants instead. This will no longer be generated in 2021."
 )]
 #[allow(non_camel_case_types)]
-pub const ENUM_VALUES_ERROR_CODE: [ErrorCode; 16] = [
+pub const ENUM_VALUES_ERROR_CODE: [ErrorCode; 18] = [
     ErrorCode::NoError,
     ErrorCode::UnsupportedParameterType,
     ErrorCode::GuestFunctionNameNotProvided,
@@ -34,6 +34,7 @@ pub const ENUM_VALUES_ERROR_CODE: [ErrorCode; 16] = [
     ErrorCode::GispatchFunctionPointerNotSet,
     ErrorCode::OutbError,
     ErrorCode::UnknownError,
+    ErrorCode::StackOverflow,
     ErrorCode::GsCheckFailed,
     ErrorCode::TooManyGuestFunctions,
     ErrorCode::FailureInDlmalloc,
@@ -42,6 +43,7 @@ pub const ENUM_VALUES_ERROR_CODE: [ErrorCode; 16] = [
     ErrorCode::GuestError,
     ErrorCode::ArrayLengthParamIsMissing,
     ErrorCode::HostError,
+    ErrorCode::HostFunctionDenied,
 ];
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -57,6 +59,7 @@ impl ErrorCode {
     pub const GispatchFunctionPointerNotSet: Self = Self(6);
     pub const OutbError: Self = Self(7);
     pub const UnknownError: Self = Self(8);
+    pub const StackOverflow: Self = Self(9);
     pub const GsCheckFailed: Self = Self(10);
     pub const TooManyGuestFunctions: Self = Self(11);
     pub const FailureInDlmalloc: Self = Self(12);
@@ -65,9 +68,10 @@ impl ErrorCode {
     pub const GuestError: Self = Self(15);
     pub const ArrayLengthParamIsMissing: Self = Self(16);
     pub const HostError: Self = Self(17);
+    pub const HostFunctionDenied: Self = Self(18);
 
     pub const ENUM_MIN: u64 = 0;
-    pub const ENUM_MAX: u64 = 17;
+    pub const ENUM_MAX: u64 = 18;
     pub const ENUM_VALUES: &'static [Self] = &[
         Self::NoError,
         Self::UnsupportedParameterType,
@@ -77,6 +81,7 @@ impl ErrorCode {
         Self::GispatchFunctionPointerNotSet,
         Self::OutbError,
         Self::UnknownError,
+        Self::StackOverflow,
         Self::GsCheckFailed,
         Self::TooManyGuestFunctions,
         Self::FailureInDlmalloc,
@@ -85,6 +90,7 @@ impl ErrorCode {
         Self::GuestError,
         Self::ArrayLengthParamIsMissing,
         Self::HostError,
+        Self::HostFunctionDenied,
     ];
     /// Returns the variant's name or "" if unknown.
     pub fn variant_name(self) -> Option<&'static str> {
@@ -99,6 +105,7 @@ impl ErrorCode {
             Self::GispatchFunctionPointerNotSet => Some("GispatchFunctionPointerNotSet"),
             Self::OutbError => Some("OutbError"),
             Self::UnknownError => Some("UnknownError"),
+            Self::StackOverflow => Some("StackOverflow"),
             Self::GsCheckFailed => Some("GsCheckFailed"),
             Self::TooManyGuestFunctions => Some("TooManyGuestFunctions"),
             Self::FailureInDlmalloc => Some("FailureInDlmalloc"),
@@ -107,6 +114,7 @@ impl ErrorCode {
             Self::GuestError => Some("GuestError"),
             Self::ArrayLengthParamIsMissing => Some("ArrayLengthParamIsMissing"),
             Self::HostError => Some("HostError"),
+            Self::HostFunctionDenied => Some("HostFunctionDenied"),
             _ => None,
         }
     }
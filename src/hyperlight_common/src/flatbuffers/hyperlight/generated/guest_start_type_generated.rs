@@ -29,6 +29,7 @@ impl<'a> flatbuffers::Follow<'a> for GuestStartType<'a> {
 
 impl<'a> GuestStartType<'a> {
     pub const VT_TSC: flatbuffers::VOffsetT = 4;
+    pub const VT_TSC_FREQ: flatbuffers::VOffsetT = 6;
 
     #[inline]
     pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -40,6 +41,7 @@ impl<'a> GuestStartType<'a> {
         args: &'args GuestStartTypeArgs,
     ) -> flatbuffers::WIPOffset<GuestStartType<'bldr>> {
         let mut builder = GuestStartTypeBuilder::new(_fbb);
+        builder.add_tsc_freq(args.tsc_freq);
         builder.add_tsc(args.tsc);
         builder.finish()
     }
@@ -55,6 +57,17 @@ impl<'a> GuestStartType<'a> {
                 .unwrap()
         }
     }
+    #[inline]
+    pub fn tsc_freq(&self) -> u64 {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab
+                .get::<u64>(GuestStartType::VT_TSC_FREQ, Some(0))
+                .unwrap()
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for GuestStartType<'_> {
@@ -66,17 +79,22 @@ impl flatbuffers::Verifiable for GuestStartType<'_> {
         use self::flatbuffers::Verifiable;
         v.visit_table(pos)?
             .visit_field::<u64>("tsc", Self::VT_TSC, false)?
+            .visit_field::<u64>("tsc_freq", Self::VT_TSC_FREQ, false)?
             .finish();
         Ok(())
     }
 }
 pub struct GuestStartTypeArgs {
     pub tsc: u64,
+    pub tsc_freq: u64,
 }
 impl<'a> Default for GuestStartTypeArgs {
     #[inline]
     fn default() -> Self {
-        GuestStartTypeArgs { tsc: 0 }
+        GuestStartTypeArgs {
+            tsc: 0,
+            tsc_freq: 0,
+        }
     }
 }
 
@@ -90,6 +108,11 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> GuestStartTypeBuilder<'a, 'b, A
         self.fbb_.push_slot::<u64>(GuestStartType::VT_TSC, tsc, 0);
     }
     #[inline]
+    pub fn add_tsc_freq(&mut self, tsc_freq: u64) {
+        self.fbb_
+            .push_slot::<u64>(GuestStartType::VT_TSC_FREQ, tsc_freq, 0);
+    }
+    #[inline]
     pub fn new(
         _fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
     ) -> GuestStartTypeBuilder<'a, 'b, A> {
@@ -110,6 +133,7 @@ impl core::fmt::Debug for GuestStartType<'_> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut ds = f.debug_struct("GuestStartType");
         ds.field("tsc", &self.tsc());
+        ds.field("tsc_freq", &self.tsc_freq());
         ds.finish()
     }
 }
@@ -0,0 +1,213 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Configuration a guest binary embeds in its [`GUEST_CONFIG_SECTION`]
+//! section, read by the host when it loads the binary. It gives the
+//! memory the guest needs, which the host uses as minimums for the
+//! sandbox configuration, and the host capabilities the guest cannot run
+//! without, so that sandbox creation fails early when they are missing.
+
+/// The ELF section the guest binary configuration is stored in
+pub const GUEST_CONFIG_SECTION: &str = ".hyperlight_config";
+
+/// The version of [`GuestBinaryConfig`] this crate reads and writes. The
+/// host rejects binaries embedding a configuration of another version.
+pub const GUEST_CONFIG_ABI_VERSION: u32 = 1;
+
+/// Identifies the configuration in the section
+const GUEST_CONFIG_MAGIC: u32 = u32::from_le_bytes(*b"HLCF");
+
+/// The host capabilities a guest binary can require
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct GuestCapabilities(u64);
+
+impl GuestCapabilities {
+    /// No capabilities
+    pub const NONE: Self = Self(0);
+    /// The host collects the traces of the guest (`trace_guest`)
+    pub const TRACE_GUEST: Self = Self(1 << 0);
+    /// The host profiles the allocations of the guest (`mem_profile`)
+    pub const MEM_PROFILE: Self = Self(1 << 1);
+    /// The host can debug the guest with gdb (`gdb`)
+    pub const GDB: Self = Self(1 << 2);
+    /// The host writes core dumps when the guest crashes (`crashdump`)
+    pub const CRASHDUMP: Self = Self(1 << 3);
+
+    /// The raw bits of the capabilities
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// The capabilities with the given raw bits
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Both the capabilities in `self` and in `other`
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// The capabilities in `self` that are not in `other`
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Whether `self` has all the capabilities in `other`
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether there are no capabilities
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// The configuration embedded in a guest binary. Sizes of 0 mean that the
+/// guest has no requirement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct GuestBinaryConfig {
+    magic: u32,
+    abi_version: u32,
+    heap_size: u64,
+    stack_size: u64,
+    required_capabilities: GuestCapabilities,
+}
+
+impl GuestBinaryConfig {
+    /// The size of the configuration in the section
+    pub const SIZE: usize = core::mem::size_of::<Self>();
+
+    /// A configuration without requirements
+    pub const fn new() -> Self {
+        Self {
+            magic: GUEST_CONFIG_MAGIC,
+            abi_version: GUEST_CONFIG_ABI_VERSION,
+            heap_size: 0,
+            stack_size: 0,
+            required_capabilities: GuestCapabilities::NONE,
+        }
+    }
+
+    /// Asks for a heap of at least `size` bytes
+    pub const fn with_heap_size(mut self, size: u64) -> Self {
+        self.heap_size = size;
+        self
+    }
+
+    /// Asks for at least `size` bytes of stack
+    pub const fn with_stack_size(mut self, size: u64) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    /// Requires the host to have `capabilities`
+    pub const fn requires(mut self, capabilities: GuestCapabilities) -> Self {
+        self.required_capabilities = self.required_capabilities.union(capabilities);
+        self
+    }
+
+    /// The version of the configuration
+    pub fn abi_version(&self) -> u32 {
+        self.abi_version
+    }
+
+    /// The smallest heap the guest runs with, 0 if it has no requirement
+    pub fn heap_size(&self) -> u64 {
+        self.heap_size
+    }
+
+    /// The smallest stack the guest runs with, 0 if it has no requirement
+    pub fn stack_size(&self) -> u64 {
+        self.stack_size
+    }
+
+    /// The host capabilities the guest cannot run without
+    pub fn required_capabilities(&self) -> GuestCapabilities {
+        self.required_capabilities
+    }
+
+    /// Reads the configuration from the contents of the
+    /// [`GUEST_CONFIG_SECTION`] section, returning `None` if it does not
+    /// hold one. The version is not checked, so that the host can report
+    /// a mismatch.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let u32_at = |at: usize| {
+            bytes
+                .get(at..at + 4)
+                .and_then(|b| b.try_into().ok())
+                .map(u32::from_le_bytes)
+        };
+        let u64_at = |at: usize| {
+            bytes
+                .get(at..at + 8)
+                .and_then(|b| b.try_into().ok())
+                .map(u64::from_le_bytes)
+        };
+        if u32_at(0)? != GUEST_CONFIG_MAGIC {
+            return None;
+        }
+        Some(Self {
+            magic: GUEST_CONFIG_MAGIC,
+            abi_version: u32_at(4)?,
+            heap_size: u64_at(8)?,
+            stack_size: u64_at(16)?,
+            required_capabilities: GuestCapabilities(u64_at(24)?),
+        })
+    }
+}
+
+impl Default for GuestBinaryConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_the_section_bytes() {
+        let config = GuestBinaryConfig::new()
+            .with_heap_size(0x10_0000)
+            .with_stack_size(0x8000)
+            .requires(GuestCapabilities::TRACE_GUEST)
+            .requires(GuestCapabilities::GDB);
+        // The guest stores the struct as is in the section
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                &config as *const GuestBinaryConfig as *const u8,
+                GuestBinaryConfig::SIZE,
+            )
+        };
+        assert_eq!(GuestBinaryConfig::from_bytes(bytes), Some(config));
+        assert!(
+            config
+                .required_capabilities()
+                .contains(GuestCapabilities::TRACE_GUEST.union(GuestCapabilities::GDB))
+        );
+    }
+
+    #[test]
+    fn rejects_other_contents() {
+        assert_eq!(GuestBinaryConfig::from_bytes(&[0; 32]), None);
+        assert_eq!(GuestBinaryConfig::from_bytes(b"HLCF"), None);
+    }
+}
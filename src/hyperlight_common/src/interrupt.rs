@@ -0,0 +1,31 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Interrupt vectors the host can inject into a running guest.
+
+/// The first vector the host can inject; vectors below it are reserved
+/// for CPU exceptions
+pub const FIRST_HOST_INTERRUPT_VECTOR: u8 = 32;
+
+/// The number of vectors, starting at [`FIRST_HOST_INTERRUPT_VECTOR`],
+/// the host can inject
+pub const HOST_INTERRUPT_VECTOR_COUNT: u8 = 16;
+
+/// Whether the host can inject `vector` into the guest
+pub const fn is_host_interrupt_vector(vector: u8) -> bool {
+    vector >= FIRST_HOST_INTERRUPT_VECTOR
+        && vector - FIRST_HOST_INTERRUPT_VECTOR < HOST_INTERRUPT_VECTOR_COUNT
+}
@@ -0,0 +1,45 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+ */
+
+//! The few machine instructions the guest uses to talk to the host:
+//! port I/O exits, reading the timestamp counter and CPUID.
+//!
+//! Guest crates call these instead of writing inline assembly so that
+//! supporting a new architecture only means adding a file under `arch/`.
+//! 32-bit x86 guests, such as Nanvix, only have the intrinsics that do
+//! not need 64-bit registers. Other architectures get a fallback whose
+//! intrinsics panic, since their guests have no way to reach the host.
+
+#[cfg_attr(target_arch = "x86_64", path = "arch/amd64/intrinsics.rs")]
+#[cfg_attr(target_arch = "x86", path = "arch/i686/intrinsics.rs")]
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "x86")),
+    path = "arch/soft/intrinsics.rs"
+)]
+mod arch;
+
+pub use arch::{cpuid, out32, read_timestamp};
+#[cfg(not(target_arch = "x86"))]
+pub use arch::{out32_with_args, out64_with_arg};
+
+/// The registers returned by [`cpuid`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuidResult {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
@@ -24,7 +24,22 @@ pub use arch::{MAX_GPA, MAX_GVA, SNAPSHOT_PT_GVA_MAX, SNAPSHOT_PT_GVA_MIN};
 pub const SCRATCH_TOP_SIZE_OFFSET: u64 = 0x08;
 pub const SCRATCH_TOP_ALLOCATOR_OFFSET: u64 = 0x10;
 pub const SCRATCH_TOP_SNAPSHOT_PT_GPA_BASE_OFFSET: u64 = 0x18;
-pub const SCRATCH_TOP_EXN_STACK_OFFSET: u64 = 0x20;
+/// Set by the host to ask the guest to flush its trace data, and
+/// cleared by the guest once it has done so
+pub const SCRATCH_TOP_TRACE_FLUSH_OFFSET: u64 = 0x20;
+/// Set by the host to the size of the smallest allocation the guest
+/// reports to the memory profiler, `u64::MAX` turning reports off
+pub const SCRATCH_TOP_MEM_PROFILE_OFFSET: u64 = 0x28;
+/// Set by the host to the guest virtual address of the range whose
+/// stores the guest reports with the `mem_trace` feature
+pub const SCRATCH_TOP_MEM_TRACE_BASE_OFFSET: u64 = 0x30;
+/// Set by the host to the size of that range, 0 turning reports off
+pub const SCRATCH_TOP_MEM_TRACE_LEN_OFFSET: u64 = 0x38;
+/// Set by the guest to the page of the traced store it is
+/// single-stepping, 0 when there is none
+pub const SCRATCH_TOP_MEM_TRACE_PENDING_OFFSET: u64 = 0x40;
+// Keeps the exception stack 16-byte aligned
+pub const SCRATCH_TOP_EXN_STACK_OFFSET: u64 = 0x50;
 
 pub fn scratch_base_gpa(size: usize) -> u64 {
     (MAX_GPA - size + 1) as u64
@@ -22,6 +22,12 @@ limitations under the License.
 
 extern crate alloc;
 
+/// cbindgen:ignore
+pub mod call_queue;
+
+/// cbindgen:ignore
+pub mod diagnostics;
+
 pub mod flatbuffer_wrappers;
 /// cbindgen:ignore
 /// FlatBuffers-related utilities and (mostly) generated code
@@ -30,20 +36,41 @@ mod flatbuffers;
 // cbindgen:ignore
 pub mod layout;
 
+/// cbindgen:ignore
+pub mod locale;
+
 // cbindgen:ignore
 pub mod log_level;
 
 /// cbindgen:ignore
 pub mod mem;
 
+/// cbindgen:ignore
+pub mod messaging;
+
 /// cbindgen:ignore
 pub mod outb;
 
 /// cbindgen:ignore
 pub mod resource;
 
+/// cbindgen:ignore
+pub mod secrets;
+
+/// cbindgen:ignore
+pub mod stream;
+
 /// cbindgen:ignore
 pub mod func;
 
+/// cbindgen:ignore
+pub mod guest_config;
+
+/// cbindgen:ignore
+pub mod interrupt;
+
+/// cbindgen:ignore
+pub mod intrinsics;
+
 // cbindgen:ignore
 pub mod vmem;
@@ -0,0 +1,31 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The host functions guests call to get timezone and locale data from
+//! the host, instead of embedding it. They are only registered in the
+//! sandboxes the host provides this data to.
+
+/// The host function that returns the TZif data of a timezone. It takes
+/// the IANA name of the timezone, such as `Europe/Paris`, as a `String`
+/// and returns a `Vec<u8>`, which is empty if the host does not know the
+/// timezone.
+pub const HOST_TIMEZONE_FUNCTION: &str = "__hl_timezone";
+
+/// The host function that returns the data of a locale, in a format
+/// agreed upon by the host and the guest. It takes the BCP 47 tag of the
+/// locale, such as `fr-FR`, as a `String` and returns a `Vec<u8>`, which
+/// is empty if the host does not know the locale.
+pub const HOST_LOCALE_FUNCTION: &str = "__hl_locale";
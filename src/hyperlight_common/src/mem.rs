@@ -18,8 +18,18 @@ pub const PAGE_SHIFT: u64 = 12;
 pub const PAGE_SIZE: u64 = 1 << 12;
 pub const PAGE_SIZE_USIZE: usize = 1 << 12;
 
+/// The guest physical address the host maps the buffers it lends to a
+/// guest function call at, see `MultiUseSandbox::with_borrowed_buffer`.
+/// These buffers are never part of a snapshot.
+pub const BORROWED_BUFFER_BASE: u64 = 0x4_0000_0000;
+
+/// The size of the guest physical address range starting at
+/// [`BORROWED_BUFFER_BASE`], which bounds the total size of the buffers
+/// lent at the same time
+pub const BORROWED_BUFFER_WINDOW_SIZE: u64 = 0x4_0000_0000;
+
 /// A memory region in the guest address space
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 #[repr(C)]
 pub struct GuestMemoryRegion {
     /// The size of the memory region
@@ -28,6 +38,50 @@ pub struct GuestMemoryRegion {
     pub ptr: u64,
 }
 
+/// Allocation failures the host asks the guest allocator to inject,
+/// so that guests can exercise their out of memory paths.
+/// Allocations are counted once the guest is initialised.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct GuestAllocFaults {
+    /// Fail every Nth allocation, 0 disables it
+    pub fail_every: u64,
+    /// Fail the allocations made once the bytes requested in total
+    /// exceed this budget, 0 disables it
+    pub byte_budget: u64,
+}
+
+/// The initialization image of the thread-local storage of the guest,
+/// described by the `PT_TLS` segment of the guest binary. A guest
+/// without thread-local variables has an empty image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct GuestTlsImage {
+    /// The address of the initialised thread-local data (`.tdata`)
+    pub ptr: u64,
+    /// The size of the initialised thread-local data
+    pub file_size: u64,
+    /// The size of the whole thread-local block, the bytes past
+    /// `file_size` (`.tbss`) are zeroed
+    pub mem_size: u64,
+    /// The alignment of the thread-local block
+    pub align: u64,
+}
+
+/// What the guest startup code needs to know about the guest binary,
+/// found by the host when loading it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct GuestImageInfo {
+    /// The thread-local storage of the guest
+    pub tls: GuestTlsImage,
+    /// The `.init_array` section, the functions run before `hyperlight_main`
+    pub init_array: GuestMemoryRegion,
+    /// The `.fini_array` section, the functions run in reverse order
+    /// when the guest finalizes itself
+    pub fini_array: GuestMemoryRegion,
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct HyperlightPEB {
@@ -35,4 +89,13 @@ pub struct HyperlightPEB {
     pub output_stack: GuestMemoryRegion,
     pub init_data: GuestMemoryRegion,
     pub guest_heap: GuestMemoryRegion,
+    pub alloc_faults: GuestAllocFaults,
+    pub image: GuestImageInfo,
+    /// The size the main stack of the guest is limited to, the pages
+    /// below it being guard pages. 0 when the stack is not limited.
+    pub max_stack_size: u64,
+    /// How far below its usual top the main stack of the guest starts,
+    /// a random number of pages when the host randomizes the layout of
+    /// the guest
+    pub stack_offset: u64,
 }
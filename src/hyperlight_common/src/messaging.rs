@@ -0,0 +1,39 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The host functions guests call to exchange messages with the other
+//! sandboxes of the process through named topics. They are only
+//! registered in the sandboxes the host connects to a message bus.
+
+/// The host function that publishes a message. It takes the topic as a
+/// `String` and the message as a `Vec<u8>`, which cannot be empty. It
+/// fails if the sandbox is not allowed to publish to the topic, or if
+/// the message is larger than [`MAX_MESSAGE_SIZE`].
+pub const HOST_PUBLISH_FUNCTION: &str = "__hl_bus_publish";
+
+/// The host function that subscribes the sandbox to a topic. It takes
+/// the topic as a `String` and fails if the sandbox is not allowed to
+/// subscribe to it. Only the messages published after the subscription
+/// are received.
+pub const HOST_SUBSCRIBE_FUNCTION: &str = "__hl_bus_subscribe";
+
+/// The host function that returns the next message of a topic the
+/// sandbox subscribed to. It takes the topic as a `String` and returns
+/// the message as a `Vec<u8>`, which is empty if there is none.
+pub const HOST_POLL_FUNCTION: &str = "__hl_bus_poll";
+
+/// The size of the largest message that can be published
+pub const MAX_MESSAGE_SIZE: usize = 4096;
@@ -85,6 +85,20 @@ impl TryFrom<u8> for Exception {
     }
 }
 
+/// Ends the message the guest sends with [`OutBAction::Abort`]. The
+/// message is the error code, the exception if there was one, and the
+/// text of the error, which being UTF-8 never contains this byte.
+pub const ABORT_TERMINATOR: u8 = 0xFF;
+
+/// Starts the `file:line:column` location of a guest panic, sent in an
+/// abort message after the text of the panic
+pub const ABORT_PANIC_LOCATION: u8 = 0xFE;
+
+/// Starts the backtrace of a guest panic, sent in an abort message after
+/// its location, as the return addresses of the frames of the panic,
+/// innermost first, in hexadecimal and each followed by a space
+pub const ABORT_PANIC_BACKTRACE: u8 = 0xFD;
+
 /// Supported actions when issuing an OUTB actions by Hyperlight.
 /// - Log: for logging,
 /// - CallFunction: makes a call to a host function,
@@ -93,6 +107,8 @@ impl TryFrom<u8> for Exception {
 /// - TraceBatch: reports a batch of spans and events from the guest
 /// - TraceMemoryAlloc: records memory allocation events
 /// - TraceMemoryFree: records memory deallocation events
+/// - Checkpoint: declares a point where the guest state is consistent
+/// - TraceMemoryAccess: reports a store to a traced range of guest memory
 pub enum OutBAction {
     Log = 99,
     CallFunction = 101,
@@ -104,6 +120,33 @@ pub enum OutBAction {
     TraceMemoryAlloc = 105,
     #[cfg(feature = "mem_profile")]
     TraceMemoryFree = 106,
+    Checkpoint = 107,
+    #[cfg(feature = "mem_trace")]
+    TraceMemoryAccess = 108,
+}
+
+/// What an [`OutBAction::TraceMemoryAccess`] exit reports, in `eax`
+#[cfg(feature = "mem_trace")]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryTracePhase {
+    /// The guest is about to single-step a store to the traced address
+    /// in `r8`, made by the instruction at the address in `r9`
+    Store = 0,
+    /// The store reported last is done
+    Done = 1,
+}
+
+#[cfg(feature = "mem_trace")]
+impl TryFrom<u32> for MemoryTracePhase {
+    type Error = anyhow::Error;
+    fn try_from(val: u32) -> anyhow::Result<Self> {
+        match val {
+            0 => Ok(MemoryTracePhase::Store),
+            1 => Ok(MemoryTracePhase::Done),
+            _ => Err(anyhow::anyhow!("Invalid MemoryTracePhase value: {}", val)),
+        }
+    }
 }
 
 impl TryFrom<u16> for OutBAction {
@@ -120,6 +163,9 @@ impl TryFrom<u16> for OutBAction {
             105 => Ok(OutBAction::TraceMemoryAlloc),
             #[cfg(feature = "mem_profile")]
             106 => Ok(OutBAction::TraceMemoryFree),
+            107 => Ok(OutBAction::Checkpoint),
+            #[cfg(feature = "mem_trace")]
+            108 => Ok(OutBAction::TraceMemoryAccess),
             _ => Err(anyhow::anyhow!("Invalid OutBAction value: {}", val)),
         }
     }
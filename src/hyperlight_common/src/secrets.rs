@@ -0,0 +1,25 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The host function guests call to get the secrets the host gives them,
+//! such as credentials, so that they are not embedded in guest binaries.
+//! It is only registered in the sandboxes the host gives secrets to.
+
+/// The host function that returns a secret. It takes the name of the
+/// secret as a `String` and returns its value as a `Vec<u8>`. It fails if
+/// the host does not know the secret, or if the access policy of the
+/// secret does not allow the guest to read it.
+pub const HOST_SECRET_FUNCTION: &str = "__hl_secret_get";
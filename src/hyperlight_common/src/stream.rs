@@ -0,0 +1,35 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The host functions guests call to transfer payloads larger than the
+//! input and output buffers in chunks, through the streams the host
+//! opened. A stream is identified by the `u32` the host gave the guest,
+//! usually as an argument of the guest function call.
+
+/// The host function that reads the next chunk of a stream the host
+/// opened for reading. It takes the stream as a `u32` and the largest
+/// number of bytes to return as a `u32`, and returns the chunk as a
+/// `Vec<u8>`, which is empty once the whole stream was read.
+pub const HOST_STREAM_READ_FUNCTION: &str = "__hl_stream_read";
+
+/// The host function that writes a chunk to a stream the host opened for
+/// writing. It takes the stream as a `u32` and the chunk as a `Vec<u8>`,
+/// which cannot be larger than [`STREAM_CHUNK_SIZE`].
+pub const HOST_STREAM_WRITE_FUNCTION: &str = "__hl_stream_write";
+
+/// The size of the largest chunk transferred by a single host function
+/// call, which fits in the default input and output buffers
+pub const STREAM_CHUNK_SIZE: usize = 4096;
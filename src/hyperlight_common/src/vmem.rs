@@ -18,6 +18,10 @@ limitations under the License.
 #[cfg_attr(target_arch = "x86", path = "arch/i686/vmem.rs")]
 mod arch;
 
+/// The size of a large page, which a single page directory entry can
+/// map when the alignment of both the virtual and physical addresses
+/// allows it.
+pub use arch::LARGE_PAGE_SIZE;
 /// This is always the page size that the /guest/ is being compiled
 /// for, which may or may not be the same as the host page size.
 pub use arch::PAGE_SIZE;
@@ -222,6 +226,17 @@ pub struct Mapping {
 ///   are being remapped, TLB invalidation may need to be performed
 ///   afterwards.
 pub use arch::map;
+/// Like [`map`], but maps each [`LARGE_PAGE_SIZE`]-aligned chunk of
+/// the range with a single large page when the physical address is
+/// equally aligned and the mapping is a [`MappingKind::Basic`] one,
+/// falling back to [`PAGE_SIZE`] pages everywhere else.
+///
+/// Remapping part of a large page later with [`map`] splits it back
+/// into small pages.
+///
+/// # Safety
+/// The same conditions as for [`map`] apply.
+pub use arch::map_with_large_pages;
 /// This function is presently used for reading the tracing data, also
 /// it is useful for debugging
 ///
@@ -14,9 +14,9 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use core::arch::asm;
 use core::ffi::{CStr, c_char};
 
+use hyperlight_common::intrinsics;
 use hyperlight_common::outb::OutBAction;
 
 /// Exits the VM with an Abort OUT action and code 0.
@@ -27,11 +27,8 @@ pub extern "C" fn abort() -> ! {
 
 /// Exits the VM with an Abort OUT action and a specific code.
 pub fn abort_with_code(code: &[u8]) -> ! {
-    // End any ongoing trace before aborting
-    #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
-    hyperlight_guest_tracing::end_trace();
-    outb(OutBAction::Abort as u16, code);
-    outb(OutBAction::Abort as u16, &[0xFF]); // send abort terminator (if not included in code)
+    write_abort(code);
+    write_abort(&[0xFF]); // send abort terminator (if not included in code)
     unreachable!()
 }
 
@@ -40,21 +37,18 @@ pub fn abort_with_code(code: &[u8]) -> ! {
 /// # Safety
 /// This function is unsafe because it dereferences a raw pointer.
 pub unsafe fn abort_with_code_and_message(code: &[u8], message_ptr: *const c_char) -> ! {
-    // End any ongoing trace before aborting
-    #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
-    hyperlight_guest_tracing::end_trace();
     unsafe {
         // Step 1: Send abort code (typically 1 byte, but `code` allows flexibility)
-        outb(OutBAction::Abort as u16, code);
+        write_abort(code);
 
         // Step 2: Convert the C string to bytes
         let message_bytes = CStr::from_ptr(message_ptr).to_bytes(); // excludes null terminator
 
         // Step 3: Send the message itself in chunks
-        outb(OutBAction::Abort as u16, message_bytes);
+        write_abort(message_bytes);
 
         // Step 4: Send abort terminator to signal completion (e.g., 0xFF)
-        outb(OutBAction::Abort as u16, &[0xFF]);
+        write_abort(&[0xFF]);
 
         // This function never returns
         unreachable!()
@@ -65,12 +59,30 @@ pub unsafe fn abort_with_code_and_message(code: &[u8], message_ptr: *const c_cha
 /// over the abort sequence. For example, in `hyperlight_guest_bin`'s panic handler,
 /// we have a message of unknown length that we want to stream
 /// to the host, which requires sending the message in chunks
+///
+/// Any open trace spans are closed first so that the events buffered in the
+/// guest tracing state reach the host together with the abort, instead of
+/// being lost when the sandbox is poisoned.
 pub fn write_abort(code: &[u8]) {
+    #[cfg(feature = "trace_guest")]
+    if !hyperlight_guest_tracing::try_end_trace() {
+        // The tracing state is locked, which means we are aborting from
+        // within the tracing code itself (e.g. an exception raised while
+        // encoding an event). Trying to read the trace data would panic and
+        // re-enter the abort path, so send the abort without it.
+        outb_with(OutBAction::Abort as u16, code, out32_untraced);
+        return;
+    }
     outb(OutBAction::Abort as u16, code);
 }
 
 /// OUT bytes to the host through multiple exits.
 pub(crate) fn outb(port: u16, data: &[u8]) {
+    outb_with(port, data, out32);
+}
+
+/// Splits `data` into chunks that fit in a 32-bit OUT and sends them using `out`.
+fn outb_with(port: u16, data: &[u8], out: unsafe fn(u16, u32)) {
     // Ensure all tracing data is flushed before sending OUT bytes
     unsafe {
         let mut i = 0;
@@ -81,12 +93,19 @@ pub(crate) fn outb(port: u16, data: &[u8]) {
             chunk[0] = chunk_len as u8;
             chunk[1..1 + chunk_len].copy_from_slice(&data[i..i + chunk_len]);
             let val = u32::from_le_bytes(chunk);
-            out32(port, val);
+            out(port, val);
             i += chunk_len;
         }
     }
 }
 
+/// OUT function for sending a 32-bit value to the host without touching the
+/// guest tracing state.
+#[cfg(feature = "trace_guest")]
+unsafe fn out32_untraced(port: u16, val: u32) {
+    unsafe { intrinsics::out32(port, val) }
+}
+
 /// OUT function for sending a 32-bit value to the host.
 /// `out32` can be called from an exception context, so we must be careful
 /// with the tracing state that might be locked at that time.
@@ -95,19 +114,12 @@ pub(crate) fn outb(port: u16, data: &[u8]) {
 /// in exception contexts. Because if the trace state is already locked, trying to create a span
 /// would cause a panic, which is undesirable in exception handling.
 pub(crate) unsafe fn out32(port: u16, val: u32) {
-    #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
+    #[cfg(feature = "trace_guest")]
     {
         if let Some((ptr, len)) = hyperlight_guest_tracing::serialized_data() {
             // If tracing is enabled and there is data to send, send it along with the OUT action
             unsafe {
-                asm!("out dx, eax",
-                    in("dx") port,
-                    in("eax") val,
-                    in("r8") OutBAction::TraceBatch as u64,
-                    in("r9") ptr,
-                    in("r10") len,
-                    options(preserves_flags, nomem, nostack)
-                )
+                intrinsics::out32_with_args(port, val, [OutBAction::TraceBatch as u64, ptr, len])
             };
 
             // Reset the trace state after sending the batch
@@ -116,14 +128,12 @@ pub(crate) unsafe fn out32(port: u16, val: u32) {
             hyperlight_guest_tracing::reset();
         } else {
             // If tracing is not enabled, just send the value
-            unsafe {
-                asm!("out dx, eax", in("dx") port, in("eax") val, options(preserves_flags, nomem, nostack))
-            };
+            unsafe { intrinsics::out32(port, val) };
         }
     }
-    #[cfg(not(all(feature = "trace_guest", target_arch = "x86_64")))]
+    #[cfg(not(feature = "trace_guest"))]
     unsafe {
-        asm!("out dx, eax", in("dx") port, in("eax") val, options(preserves_flags, nomem, nostack));
+        intrinsics::out32(port, val);
     }
 }
 
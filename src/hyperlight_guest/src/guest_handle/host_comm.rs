@@ -152,6 +152,25 @@ impl GuestHandle {
         self.get_host_return_value::<T>()
     }
 
+    /// Declares a point, named `name`, where the state of the guest is
+    /// consistent, so that the host can take a snapshot of the sandbox
+    /// to roll back to if the rest of the call fails.
+    pub fn checkpoint(&self, name: &str) -> Result<()> {
+        // The name is size-prefixed like the other elements of the output
+        // buffer, which are at least 8 bytes long
+        let mut data = Vec::with_capacity(name.len() + 8);
+        data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        data.extend_from_slice(name.as_bytes());
+        data.resize(data.len().max(8), 0);
+        self.push_shared_output_data(&data)?;
+
+        unsafe {
+            out32(OutBAction::Checkpoint as u16, 0);
+        }
+
+        Ok(())
+    }
+
     /// Log a message with the specified log level, source, caller, source file, and line number.
     pub fn log_message(
         &self,
@@ -185,7 +204,7 @@ impl GuestHandle {
             }
         };
 
-        #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
+        #[cfg(feature = "trace_guest")]
         if hyperlight_guest_tracing::is_trace_enabled() {
             // If the "trace_guest" feature is enabled and tracing is initialized, log using tracing
             tracing::trace!(
@@ -199,7 +218,7 @@ impl GuestHandle {
         } else {
             _send_to_host();
         }
-        #[cfg(not(all(feature = "trace_guest", target_arch = "x86_64")))]
+        #[cfg(not(feature = "trace_guest"))]
         {
             _send_to_host();
         }
@@ -0,0 +1,67 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use hyperlight_guest::layout::MAIN_STACK_TOP_GVA;
+
+/// The most return addresses sent to the host when the guest panics
+pub(crate) const MAX_PANIC_FRAMES: usize = 16;
+
+/// Walks the frame pointers of the caller, writing the return addresses
+/// of its frames to `frames`, innermost first, and returns how many were
+/// written.
+///
+/// This is only meaningful for guests built with frame pointers. The
+/// walk never leaves the part of the main stack above the stack pointer,
+/// so on other guests it stops early instead of faulting.
+#[inline(always)]
+pub(crate) fn walk_frames(frames: &mut [u64; MAX_PANIC_FRAMES]) -> usize {
+    let (mut fp, sp): (u64, u64);
+    unsafe {
+        core::arch::asm!(
+            "mov {fp}, rbp",
+            "mov {sp}, rsp",
+            fp = out(reg) fp,
+            sp = out(reg) sp,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+
+    let mut len = 0;
+    while len < frames.len()
+        && fp >= sp
+        && fp % 8 == 0
+        && fp.saturating_add(16) <= MAIN_STACK_TOP_GVA
+    {
+        // The caller's frame pointer is at [fp] and the return address
+        // right above it
+        let (next_fp, ret) = unsafe {
+            let frame = fp as *const u64;
+            (frame.read_volatile(), frame.add(1).read_volatile())
+        };
+        if ret == 0 {
+            break;
+        }
+        frames[len] = ret;
+        len += 1;
+        // Frames grow down, so a frame pointer that does not move up
+        // means the chain ends here
+        if next_fp <= fp {
+            break;
+        }
+        fp = next_fp;
+    }
+    len
+}
@@ -19,6 +19,7 @@ limitations under the License.
 
 use core::arch::{asm, global_asm};
 
+use hyperlight_common::interrupt::{FIRST_HOST_INTERRUPT_VECTOR, HOST_INTERRUPT_VECTOR_COUNT};
 use hyperlight_common::outb::Exception;
 
 use super::super::context;
@@ -48,6 +49,24 @@ unsafe extern "C" {
     fn _do_excp19();
     fn _do_excp20();
     fn _do_excp30();
+
+    // Host interrupt handlers
+    fn _do_excp32();
+    fn _do_excp33();
+    fn _do_excp34();
+    fn _do_excp35();
+    fn _do_excp36();
+    fn _do_excp37();
+    fn _do_excp38();
+    fn _do_excp39();
+    fn _do_excp40();
+    fn _do_excp41();
+    fn _do_excp42();
+    fn _do_excp43();
+    fn _do_excp44();
+    fn _do_excp45();
+    fn _do_excp46();
+    fn _do_excp47();
 }
 
 // Macro to generate exception handlers
@@ -162,6 +181,22 @@ macro_rules! generate_exceptions {
             generate_excp!(19, pusherrcode),
             generate_excp!(20, pusherrcode),
             generate_excp!(30),
+            generate_excp!(32, pusherrcode),
+            generate_excp!(33, pusherrcode),
+            generate_excp!(34, pusherrcode),
+            generate_excp!(35, pusherrcode),
+            generate_excp!(36, pusherrcode),
+            generate_excp!(37, pusherrcode),
+            generate_excp!(38, pusherrcode),
+            generate_excp!(39, pusherrcode),
+            generate_excp!(40, pusherrcode),
+            generate_excp!(41, pusherrcode),
+            generate_excp!(42, pusherrcode),
+            generate_excp!(43, pusherrcode),
+            generate_excp!(44, pusherrcode),
+            generate_excp!(45, pusherrcode),
+            generate_excp!(46, pusherrcode),
+            generate_excp!(47, pusherrcode),
         )
     };
 }
@@ -203,6 +238,20 @@ pub(in super::super) fn init_idt(pc: *mut ProcCtrl) {
     set_idt_entry(Exception::VirtualizationException, _do_excp20); // Virtualization Exception
     set_idt_entry(Exception::SecurityException, _do_excp30); // Security Exception
 
+    // Interrupts injected by the host
+    let host_interrupt_handlers: [unsafe extern "C" fn(); HOST_INTERRUPT_VECTOR_COUNT as usize] = [
+        _do_excp32, _do_excp33, _do_excp34, _do_excp35, _do_excp36, _do_excp37, _do_excp38,
+        _do_excp39, _do_excp40, _do_excp41, _do_excp42, _do_excp43, _do_excp44, _do_excp45,
+        _do_excp46, _do_excp47,
+    ];
+    for (i, handler) in host_interrupt_handlers.into_iter().enumerate() {
+        let handler_addr = handler as *const () as u64;
+        unsafe {
+            (&raw mut (*idt).entries[FIRST_HOST_INTERRUPT_VECTOR as usize + i])
+                .write_volatile(IdtEntry::new(handler_addr));
+        }
+    }
+
     let idtr = IdtPointer {
         limit: (core::mem::size_of::<IDT>() - 1) as u16,
         base: idt as u64,
@@ -15,8 +15,12 @@ limitations under the License.
  */
 
 use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
 
+use hyperlight_common::interrupt::FIRST_HOST_INTERRUPT_VECTOR;
 use hyperlight_common::outb::Exception;
+#[cfg(feature = "mem_trace")]
+use hyperlight_common::vmem;
 use hyperlight_common::vmem::{
     BasicMapping, CowMapping, MappingKind, PAGE_SIZE, PhysAddr, VirtAddr,
 };
@@ -60,6 +64,35 @@ pub type ExceptionHandler = fn(
     page_fault_address: u64,
 ) -> bool;
 
+/// The lowest address of the main stack the guest may use, the pages
+/// below it being guard pages. 0 when the stack is not limited.
+static STACK_LIMIT_GVA: AtomicU64 = AtomicU64::new(0);
+
+/// Limits the main stack, which starts at `top`, to `max_size` bytes,
+/// as requested by the host in the PEB. 0 leaves the stack unlimited.
+pub(crate) fn set_main_stack(top: u64, max_size: u64) {
+    if max_size > 0 {
+        let limit = top.saturating_sub(max_size).max(MAIN_STACK_LIMIT_GVA);
+        STACK_LIMIT_GVA.store(limit, Ordering::Relaxed);
+    }
+}
+
+/// Reports a fault on the guard pages below the main stack to the host
+fn abort_stack_overflow(exn_info: *mut ExceptionInfo, gva: u64) -> ! {
+    let saved_rip = unsafe { (&raw const (*exn_info).rip).read_volatile() };
+    write_abort(&[ErrorCode::StackOverflow as u8, Exception::PageFault as u8]);
+    let _ = write!(
+        HyperlightAbortWriter,
+        "Stack overflow: access to guard page {:#x} by instruction {:#x}",
+        gva, saved_rip
+    );
+    write_abort(&[0xFF]);
+    // At this point, write_abort with the 0xFF terminator is expected
+    // to terminate guest execution, so control should never reach
+    // beyond this call.
+    unreachable!();
+}
+
 fn handle_stack_pagefault(gva: u64) {
     // TODO: perhaps we should have a sanity check that the
     // stack grows only one page at a time, which should be
@@ -125,6 +158,120 @@ fn handle_cow_pagefault(_phys: PhysAddr, virt: VirtAddr, perms: CowMapping) {
     }
 }
 
+/// The trap flag, which makes the vCPU raise a debug exception after
+/// the next instruction
+#[cfg(feature = "mem_trace")]
+const RFLAGS_TF: u64 = 1 << 8;
+
+/// The scratch bookkeeping slot at `offset`, which the host never traces
+#[cfg(feature = "mem_trace")]
+fn mem_trace_slot(offset: u64) -> *mut u64 {
+    (hyperlight_common::layout::MAX_GVA as u64 - offset + 1) as *mut u64
+}
+
+/// Whether the host asked for the stores to `gva` to be reported
+#[cfg(feature = "mem_trace")]
+fn is_traced(gva: u64) -> bool {
+    use hyperlight_common::layout::{
+        SCRATCH_TOP_MEM_TRACE_BASE_OFFSET, SCRATCH_TOP_MEM_TRACE_LEN_OFFSET,
+    };
+    // Safety: the scratch bookkeeping area is always mapped, and the host
+    // only writes to it while the vCPU is not running
+    let base = unsafe { mem_trace_slot(SCRATCH_TOP_MEM_TRACE_BASE_OFFSET).read_volatile() };
+    let len = unsafe { mem_trace_slot(SCRATCH_TOP_MEM_TRACE_LEN_OFFSET).read_volatile() };
+    gva.checked_sub(base).is_some_and(|offset| offset < len)
+}
+
+/// Lets a store to a traced page through: the host is told about the
+/// store, the page is made writable, copying it first if it is still
+/// backed by the snapshot, and the vCPU single-steps the store, after
+/// which [`finish_traced_store`] write protects the page again
+#[cfg(feature = "mem_trace")]
+fn handle_traced_store(exn_info: *mut ExceptionInfo, gva: u64, mapping: vmem::Mapping) -> bool {
+    use hyperlight_common::layout::SCRATCH_TOP_MEM_TRACE_PENDING_OFFSET;
+    use hyperlight_common::outb::{MemoryTracePhase, OutBAction};
+
+    let (readable, executable) = match mapping.kind {
+        MappingKind::Basic(bm) => (bm.readable, bm.executable),
+        MappingKind::Cow(cm) => (cm.readable, cm.executable),
+    };
+    let page = gva & !(PAGE_SIZE as u64 - 1);
+    let mut phys = mapping.phys_base + (page - mapping.virt_base);
+    unsafe {
+        let rip = (&raw const (*exn_info).rip).read_volatile();
+        hyperlight_common::intrinsics::out32_with_args(
+            OutBAction::TraceMemoryAccess as u16,
+            MemoryTracePhase::Store as u32,
+            [gva, rip, 0],
+        );
+        if crate::paging::phys_to_virt(phys).is_none() {
+            let new_page = hyperlight_guest::prim_alloc::alloc_phys_pages(1);
+            let Some(copy) = crate::paging::phys_to_virt(new_page) else {
+                return false;
+            };
+            core::ptr::copy(page as *const u8, copy, PAGE_SIZE);
+            phys = new_page;
+        }
+        crate::paging::map_region(
+            phys,
+            page as *mut u8,
+            PAGE_SIZE as u64,
+            MappingKind::Basic(BasicMapping {
+                readable,
+                writable: true,
+                executable,
+            }),
+        );
+        core::arch::asm!("invlpg [{}]", in(reg) page, options(readonly, nostack, preserves_flags));
+
+        mem_trace_slot(SCRATCH_TOP_MEM_TRACE_PENDING_OFFSET).write_volatile(page);
+        let rflags = (&raw const (*exn_info).rflags).read_volatile();
+        (&raw mut (*exn_info).rflags).write_volatile(rflags | RFLAGS_TF);
+    }
+    true
+}
+
+/// Tells the host the store let through by [`handle_traced_store`] is
+/// done, and write protects its page again
+#[cfg(feature = "mem_trace")]
+fn finish_traced_store(exn_info: *mut ExceptionInfo) -> bool {
+    use hyperlight_common::layout::SCRATCH_TOP_MEM_TRACE_PENDING_OFFSET;
+    use hyperlight_common::outb::{MemoryTracePhase, OutBAction};
+
+    let pending = mem_trace_slot(SCRATCH_TOP_MEM_TRACE_PENDING_OFFSET);
+    let page = unsafe { pending.read_volatile() };
+    if page == 0 {
+        return false;
+    }
+    let Some(mapping) = crate::paging::virt_to_phys(page).next() else {
+        return false;
+    };
+    let MappingKind::Basic(bm) = mapping.kind else {
+        return false;
+    };
+    unsafe {
+        hyperlight_common::intrinsics::out32_with_args(
+            OutBAction::TraceMemoryAccess as u16,
+            MemoryTracePhase::Done as u32,
+            [0, 0, 0],
+        );
+        crate::paging::map_region(
+            mapping.phys_base + (page - mapping.virt_base),
+            page as *mut u8,
+            PAGE_SIZE as u64,
+            MappingKind::Basic(BasicMapping {
+                writable: false,
+                ..bm
+            }),
+        );
+        core::arch::asm!("invlpg [{}]", in(reg) page, options(readonly, nostack, preserves_flags));
+        pending.write_volatile(0);
+        let rflags = (&raw const (*exn_info).rflags).read_volatile();
+        (&raw mut (*exn_info).rflags).write_volatile(rflags & !RFLAGS_TF);
+    }
+    true
+}
+
 fn try_handle_internal_pagefault(
     exn_info: *mut ExceptionInfo,
     _ctx: *mut Context,
@@ -136,6 +283,9 @@ fn try_handle_internal_pagefault(
         // If the fault was caused by a not-present page, check if we
         // should populate it with a stack page
         if (MAIN_STACK_LIMIT_GVA..MAIN_STACK_TOP_GVA).contains(&gva) {
+            if gva < STACK_LIMIT_GVA.load(Ordering::Relaxed) {
+                abort_stack_overflow(exn_info, gva);
+            }
             handle_stack_pagefault(gva);
             return true;
         }
@@ -152,6 +302,13 @@ fn try_handle_internal_pagefault(
     let access_was_user = (error_code & (1 << 2)) != 0;
     let access_was_insn = (error_code & (1 << 4)) != 0;
     if access_was_write && !access_was_user && !access_was_insn {
+        #[cfg(feature = "mem_trace")]
+        if is_traced(gva)
+            && let Some(mapping) = crate::paging::virt_to_phys(gva).next()
+        {
+            return handle_traced_store(exn_info, gva, mapping);
+        }
+
         // The fault was probably caused by a lack of write
         // permission. Check if that's because the page needs to be
         // CoW'd
@@ -181,6 +338,13 @@ pub(crate) extern "C" fn hl_exception_handler(
     let ctx = stack_pointer as *mut Context;
     let exn_info = (stack_pointer + size_of::<Context>() as u64) as *mut ExceptionInfo;
 
+    // Interrupts injected by the host are not exceptions, and return to
+    // the interrupted code once their handler has run
+    if exception_number >= FIRST_HOST_INTERRUPT_VECTOR as u64 {
+        crate::interrupt::dispatch(exception_number as u8);
+        return;
+    }
+
     let exception = Exception::try_from(exception_number as u8).expect("Invalid exception number");
 
     // Check if it is a page fault that needs to be handled for normal Hyperlight operation
@@ -188,6 +352,12 @@ pub(crate) extern "C" fn hl_exception_handler(
         return;
     }
 
+    // Check if it is the end of a store to traced memory
+    #[cfg(feature = "mem_trace")]
+    if exception_number == 1 && finish_traced_store(exn_info) {
+        return;
+    }
+
     // Check for registered user handlers (only for architecture-defined vectors 0-30)
     if exception_number < 31 {
         let handler =
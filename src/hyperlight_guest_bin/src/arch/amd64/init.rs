@@ -17,6 +17,8 @@ limitations under the License.
 use core::arch::asm;
 use core::mem;
 
+use hyperlight_common::mem::HyperlightPEB;
+
 use super::exception::entry::init_idt;
 use super::machine::{GDT, GdtEntry, GdtPointer, ProcCtrl, TSS};
 
@@ -105,10 +107,20 @@ unsafe fn init_tss(pc: *mut ProcCtrl) {
 }
 
 /// To initialise the main stack, we just pre-emptively map the first
-/// page of it.
-unsafe fn init_stack() -> u64 {
-    use hyperlight_guest::layout::MAIN_STACK_TOP_GVA;
-    let stack_top_page_base = (MAIN_STACK_TOP_GVA - 1) & !0xfff;
+/// page of it. The host may ask for the stack to start some pages
+/// below its usual top, to place it at a random address, and to limit
+/// its size.
+unsafe fn init_stack(peb: *const HyperlightPEB) -> u64 {
+    use hyperlight_guest::layout::{MAIN_STACK_LIMIT_GVA, MAIN_STACK_TOP_GVA};
+    let (stack_offset, max_stack_size) = unsafe { ((*peb).stack_offset, (*peb).max_stack_size) };
+    let stack_offset = if stack_offset < MAIN_STACK_TOP_GVA - MAIN_STACK_LIMIT_GVA {
+        stack_offset & !0xfff
+    } else {
+        0
+    };
+    let stack_top = MAIN_STACK_TOP_GVA - stack_offset;
+    super::exception::handle::set_main_stack(stack_top, max_stack_size);
+    let stack_top_page_base = (stack_top - 1) & !0xfff;
     unsafe {
         use hyperlight_common::vmem::{BasicMapping, MappingKind, PAGE_SIZE};
         crate::paging::map_region(
@@ -123,7 +135,7 @@ unsafe fn init_stack() -> u64 {
         );
         crate::paging::barrier::first_valid_same_ctx();
     }
-    MAIN_STACK_TOP_GVA
+    stack_top
 }
 
 /// Machine-specific initialisation; calls [`crate::generic_init`]
@@ -138,7 +150,7 @@ pub extern "C" fn entrypoint(peb_address: u64, seed: u64, ops: u64, max_log_leve
         init_gdt(pc);
         init_tss(pc);
         init_idt(pc);
-        let stack_top = init_stack();
+        let stack_top = init_stack(peb_address as *const HyperlightPEB);
 
         // Architecture early init is complete! We pivot now to
         // executing on the main stack, and jump into generic
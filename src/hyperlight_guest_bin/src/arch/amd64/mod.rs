@@ -14,9 +14,11 @@ See the License for the specific language governing permissions and
 limitations under the License.
  */
 
+pub(crate) mod backtrace;
 pub(crate) mod context;
 pub(crate) mod dispatch;
 pub mod exception;
 mod init;
 mod layout;
 pub(crate) mod machine;
+pub(crate) mod tls;
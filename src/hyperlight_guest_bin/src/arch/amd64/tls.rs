@@ -0,0 +1,40 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+ */
+
+use core::arch::asm;
+
+/// The MSR holding the base address of the `fs` segment
+const IA32_FS_BASE: u32 = 0xC000_0100;
+
+/// Sets the thread pointer, which the x86_64 ABI keeps in the base of
+/// the `fs` segment, to `tp`. Thread-local variables are then accessed
+/// at negative offsets from `tp`, and `%fs:0` must read `tp` back.
+///
+/// # Safety
+/// `tp` must point to a thread control block that follows the
+/// thread-local block of the guest, and stays valid for the lifetime
+/// of the guest.
+pub(crate) unsafe fn set_thread_pointer(tp: u64) {
+    unsafe {
+        asm!(
+            "wrmsr",
+            in("ecx") IA32_FS_BASE,
+            in("eax") tp as u32,
+            in("edx") (tp >> 32) as u32,
+            options(nostack, preserves_flags)
+        );
+    }
+}
@@ -0,0 +1,82 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The buffers the host lends to a guest function call, mapped into the
+//! guest rather than copied through the input buffer. The host gives the
+//! guest the address and length of the buffer, usually as arguments of
+//! the call.
+
+use hyperlight_common::mem::{BORROWED_BUFFER_BASE, BORROWED_BUFFER_WINDOW_SIZE, PAGE_SIZE};
+use hyperlight_common::vmem::{BasicMapping, MappingKind};
+
+use crate::paging;
+
+/// Maps the buffer of `len` bytes the host lent at `address`, writable
+/// if `writable` is set
+///
+/// # Safety
+/// See [`borrowed_buffer`]
+unsafe fn map(address: u64, len: u64, writable: bool) {
+    assert!(
+        address >= BORROWED_BUFFER_BASE
+            && address
+                .checked_add(len)
+                .is_some_and(|end| end <= BORROWED_BUFFER_BASE + BORROWED_BUFFER_WINDOW_SIZE),
+        "{:#x} is not the address of a buffer lent by the host",
+        address
+    );
+    unsafe {
+        paging::map_region(
+            address,
+            address as *mut u8,
+            len.next_multiple_of(PAGE_SIZE),
+            MappingKind::Basic(BasicMapping {
+                readable: true,
+                writable,
+                executable: false,
+            }),
+        );
+        paging::barrier::first_valid_same_ctx();
+    }
+}
+
+/// Maps the buffer of `len` bytes the host lent at `address` and returns
+/// it.
+///
+/// # Safety
+/// `address` and `len` must be those the host gave the guest function
+/// call, and the slice must not be used once the call returns, when the
+/// host takes the buffer back.
+pub unsafe fn borrowed_buffer(address: u64, len: u64) -> &'static [u8] {
+    unsafe {
+        map(address, len, false);
+        core::slice::from_raw_parts(address as *const u8, len as usize)
+    }
+}
+
+/// Maps the buffer of `len` bytes the host lent read-write at `address`
+/// and returns it. What the guest writes to it is seen by the host.
+///
+/// # Safety
+/// See [`borrowed_buffer`]. The buffer must have been lent read-write,
+/// writing to a read-only buffer aborts the call with a memory access
+/// violation.
+pub unsafe fn borrowed_buffer_mut(address: u64, len: u64) -> &'static mut [u8] {
+    unsafe {
+        map(address, len, true);
+        core::slice::from_raw_parts_mut(address as *mut u8, len as usize)
+    }
+}
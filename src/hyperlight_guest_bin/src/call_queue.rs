@@ -0,0 +1,41 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Runs the guest function calls queued by the host, see
+//! [`hyperlight_common::call_queue`]
+
+use hyperlight_common::call_queue::CALL_QUEUE_FUNCTION;
+use hyperlight_common::flatbuffer_wrappers::function_call::FunctionCall;
+use hyperlight_guest::error::Result;
+
+use crate::GUEST_HANDLE;
+use crate::guest_function::call::{call_guest_function, push_function_call_result};
+use crate::guest_function::register::register_fn;
+
+/// Registers the function running the queued calls, before the guest
+/// registers its own functions
+pub(crate) fn register_call_queue() {
+    register_fn(CALL_QUEUE_FUNCTION, run_call_queue);
+}
+
+fn run_call_queue(count: u32) -> Result<()> {
+    let handle = unsafe { GUEST_HANDLE };
+    for _ in 0..count {
+        let function_call = handle.try_pop_shared_input_data_into::<FunctionCall>()?;
+        push_function_call_result(call_guest_function(function_call))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,134 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The self-test, benchmark and function listing functions every guest
+//! provides, see [`hyperlight_common::diagnostics`]
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::hint::black_box;
+
+use hyperlight_common::diagnostics::{
+    BENCH_FUNCTION, GuestBenchTimings, HOST_ECHO_FUNCTION, LIST_FUNCTIONS_FUNCTION,
+    SELF_TEST_FUNCTION,
+};
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
+use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
+use hyperlight_guest::error::{HyperlightGuestError, Result};
+use hyperlight_guest_tracing::invariant_tsc::read_tsc;
+
+use crate::REGISTERED_GUEST_FUNCTIONS;
+use crate::guest_function::register::register_fn;
+use crate::host_comm::call_host;
+
+/// The sizes of the buffers allocated by the self-test and benchmark
+const ALLOC_SIZES: [usize; 4] = [16, 256, 4096, 64 * 1024];
+
+/// Registers the diagnostics functions. This is done before the guest
+/// registers its own functions, so a guest can replace them.
+pub(crate) fn register_diagnostics() {
+    register_fn(SELF_TEST_FUNCTION, self_test);
+    register_fn(BENCH_FUNCTION, bench);
+    register_fn(LIST_FUNCTIONS_FUNCTION, list_functions);
+}
+
+fn self_test() -> Result<String> {
+    for size in ALLOC_SIZES {
+        check_alloc(size)?;
+    }
+
+    let payload: Vec<u8> = (0..=255).collect();
+    let echoed = call_host::<Vec<u8>>(HOST_ECHO_FUNCTION, (payload.clone(),))?;
+    if echoed != payload {
+        return Err(failure(format!(
+            "host call round trip returned {} bytes instead of the {} sent",
+            echoed.len(),
+            payload.len()
+        )));
+    }
+
+    Ok(format!(
+        "allocation of {:?} bytes: ok\nhost call round trip: ok\n",
+        ALLOC_SIZES
+    ))
+}
+
+fn bench(iterations: u32) -> Result<Vec<u8>> {
+    let start = read_tsc();
+    for _ in 0..iterations {
+        for size in ALLOC_SIZES {
+            black_box(vec![0xa5u8; size]);
+        }
+    }
+    let alloc_cycles = read_tsc().wrapping_sub(start);
+
+    let start = read_tsc();
+    for _ in 0..iterations {
+        call_host::<Vec<u8>>(HOST_ECHO_FUNCTION, (Vec::new(),))?;
+    }
+    let host_call_cycles = read_tsc().wrapping_sub(start);
+
+    Ok(GuestBenchTimings {
+        iterations,
+        alloc_cycles,
+        host_call_cycles,
+    }
+    .to_bytes())
+}
+
+fn list_functions() -> Result<Vec<u8>> {
+    // Use &raw const to get an immutable reference to the static register
+    // this is to avoid the clippy warning "shared reference to mutable static"
+    let functions = unsafe { &*(&raw const REGISTERED_GUEST_FUNCTIONS) }
+        .definitions()
+        .map(|function| HostFunctionDefinition {
+            function_name: function.function_name.clone(),
+            parameter_types: Some(function.parameter_types.clone()),
+            return_type: function.return_type,
+        })
+        .collect();
+    let details = HostFunctionDetails {
+        host_functions: Some(functions),
+    };
+    Vec::try_from(&details).map_err(|e| {
+        HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!("{} failed: {}", LIST_FUNCTIONS_FUNCTION, e),
+        )
+    })
+}
+
+/// Allocates a buffer of `size` bytes and checks that it holds what was
+/// written to it
+fn check_alloc(size: usize) -> Result<()> {
+    let buffer: Vec<u8> = (0..size).map(|i| i as u8).collect();
+    if buffer.iter().enumerate().any(|(i, b)| *b != i as u8) {
+        return Err(failure(format!(
+            "a buffer of {} bytes does not hold what was written to it",
+            size
+        )));
+    }
+    Ok(())
+}
+
+fn failure(message: String) -> HyperlightGuestError {
+    HyperlightGuestError::new(
+        ErrorCode::GuestError,
+        format!("{} failed: {}", SELF_TEST_FUNCTION, message),
+    )
+}
@@ -0,0 +1,41 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Configuration embedded in the guest binary and read by the host when
+//! it loads it, see [`hyperlight_common::guest_config`]
+//!
+//! ```ignore
+//! use hyperlight_guest_bin::guest_config::{GuestBinaryConfig, GuestCapabilities};
+//!
+//! hyperlight_guest_bin::guest_config!(
+//!     GuestBinaryConfig::new()
+//!         .with_heap_size(4 * 1024 * 1024)
+//!         .requires(GuestCapabilities::TRACE_GUEST)
+//! );
+//! ```
+
+pub use hyperlight_common::guest_config::{GuestBinaryConfig, GuestCapabilities};
+
+/// Embeds `config`, a constant [`GuestBinaryConfig`], in the guest
+/// binary. It can be used once per binary.
+#[macro_export]
+macro_rules! guest_config {
+    ($config:expr) => {
+        #[used]
+        #[unsafe(link_section = ".hyperlight_config")]
+        static __HYPERLIGHT_GUEST_CONFIG: $crate::guest_config::GuestBinaryConfig = $config;
+    };
+}
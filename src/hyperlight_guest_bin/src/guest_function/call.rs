@@ -72,10 +72,26 @@ pub(crate) fn call_guest_function(function_call: FunctionCall) -> Result<Vec<u8>
     }
 }
 
+/// Pushes the result of a guest function call onto the output buffer,
+/// where the host reads it
+pub(crate) fn push_function_call_result(res: Result<Vec<u8>>) -> Result<()> {
+    let handle = unsafe { GUEST_HANDLE };
+    match res {
+        Ok(bytes) => handle.push_shared_output_data(bytes.as_slice()),
+        Err(err) => {
+            let guest_error = Err(GuestError::new(err.kind, err.message));
+            let fcr = FunctionCallResult::new(guest_error);
+            let mut builder = FlatBufferBuilder::new();
+            let data = fcr.encode(&mut builder);
+            handle.push_shared_output_data(data)
+        }
+    }
+}
+
 pub(crate) fn internal_dispatch_function() {
     // Read the current TSC to report it to the host with the spans/events
     // This helps calculating the timestamps relative to the guest call
-    #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
+    #[cfg(feature = "trace_guest")]
     let _entered = {
         let guest_start_tsc = hyperlight_guest_tracing::invariant_tsc::read_tsc();
         // Reset the trace state for the new guest function call with the new start TSC
@@ -91,28 +107,17 @@ pub(crate) fn internal_dispatch_function() {
         .try_pop_shared_input_data_into::<FunctionCall>()
         .expect("Function call deserialization failed");
 
+    #[cfg(target_arch = "x86_64")]
+    crate::interrupt::enable_if_handled();
     let res = call_guest_function(function_call);
+    #[cfg(target_arch = "x86_64")]
+    crate::interrupt::disable();
 
-    match res {
-        Ok(bytes) => {
-            handle
-                .push_shared_output_data(bytes.as_slice())
-                .expect("Failed to serialize function call result");
-        }
-        Err(err) => {
-            let guest_error = Err(GuestError::new(err.kind, err.message));
-            let fcr = FunctionCallResult::new(guest_error);
-            let mut builder = FlatBufferBuilder::new();
-            let data = fcr.encode(&mut builder);
-            handle
-                .push_shared_output_data(data)
-                .expect("Failed to serialize function call result");
-        }
-    }
+    push_function_call_result(res).expect("Failed to serialize function call result");
 
     // All this tracing logic shall be done right before the call to `hlt` which is done after this
     // function returns
-    #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
+    #[cfg(feature = "trace_guest")]
     {
         // This span captures the internal dispatch function only, without tracing internals.
         // Close the span before flushing to ensure that the `flush` call is not included in the span
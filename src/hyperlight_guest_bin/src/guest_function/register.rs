@@ -62,6 +62,11 @@ impl<F: Copy> GuestFunctionRegister<F> {
     pub fn get(&self, function_name: &str) -> Option<&GuestFunctionDefinition<F>> {
         self.guest_functions.get(function_name)
     }
+
+    /// The registered `GuestFunctionDefinition`s, sorted by name.
+    pub fn definitions(&self) -> impl Iterator<Item = &GuestFunctionDefinition<F>> {
+        self.guest_functions.values()
+    }
 }
 
 impl GuestFunctionRegister<GuestFunc> {
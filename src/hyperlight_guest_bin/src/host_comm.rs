@@ -71,6 +71,13 @@ pub fn get_host_return_value<T: TryFrom<ReturnValue>>() -> Result<T> {
     handle.get_host_return_value::<T>()
 }
 
+/// Declares a point where the state of the guest is consistent, see
+/// [`GuestHandle::checkpoint`](hyperlight_guest::guest_handle::handle::GuestHandle::checkpoint)
+pub fn checkpoint(name: &str) -> Result<()> {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.checkpoint(name)
+}
+
 pub fn read_n_bytes_from_user_memory(num: u64) -> Result<Vec<u8>> {
     let handle = unsafe { GUEST_HANDLE };
     handle.read_n_bytes_from_user_memory(num)
@@ -0,0 +1,113 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Runs the parts of the guest binary that C and C++ toolchains expect
+//! the program loader and the C runtime to take care of: setting up the
+//! thread-local storage, and calling the functions of the `.init_array`
+//! and `.fini_array` sections, such as static constructors and
+//! destructors. The host finds them when loading the binary and passes
+//! them in the PEB, see [`GuestImageInfo`].
+
+use alloc::alloc::{alloc_zeroed, handle_alloc_error};
+use core::alloc::Layout;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use hyperlight_common::mem::{GuestImageInfo, GuestMemoryRegion};
+
+use crate::GUEST_HANDLE;
+use crate::arch::tls::set_thread_pointer;
+
+/// The thread control block following the thread-local block, which
+/// only holds the pointer to itself that the x86_64 ABI requires at
+/// the thread pointer
+type Tcb = u64;
+
+/// Whether the functions of the `.fini_array` section have run
+static FINALIZED: AtomicBool = AtomicBool::new(false);
+
+fn image_info() -> GuestImageInfo {
+    #[allow(static_mut_refs)]
+    let Some(peb_ptr) = (unsafe { GUEST_HANDLE.peb() }) else {
+        return GuestImageInfo::default();
+    };
+    unsafe { (*peb_ptr).image }
+}
+
+/// Returns the functions of an `.init_array` or `.fini_array` section
+fn functions(region: GuestMemoryRegion) -> &'static [extern "C" fn()] {
+    let len = region.size as usize / size_of::<extern "C" fn()>();
+    if region.ptr == 0 || len == 0 {
+        return &[];
+    }
+    // The sections are part of the loaded binary, and the host already
+    // applied their relocations
+    unsafe { core::slice::from_raw_parts(region.ptr as *const extern "C" fn(), len) }
+}
+
+/// Allocates the thread-local block of the guest, initialised from the
+/// TLS image of the binary, and points the thread pointer at it. This
+/// must run once the heap is set up and before any thread-local variable
+/// is accessed.
+pub(crate) fn init_tls() {
+    let tls = image_info().tls;
+    if tls.mem_size == 0 {
+        return;
+    }
+
+    // The block is laid out as variant II of the ELF TLS ABI: the
+    // thread-local data, padded to its alignment, ends right where the
+    // TCB the thread pointer points to starts. The bytes past the
+    // initialised data (`.tbss`) are left zeroed.
+    let align = (tls.align as usize).max(align_of::<Tcb>());
+    let tls_size = (tls.mem_size as usize).next_multiple_of(align);
+    let layout = Layout::from_size_align(tls_size + size_of::<Tcb>(), align)
+        .expect("Invalid thread-local storage layout");
+    unsafe {
+        let block = alloc_zeroed(layout);
+        if block.is_null() {
+            handle_alloc_error(layout);
+        }
+        core::ptr::copy_nonoverlapping(tls.ptr as *const u8, block, tls.file_size as usize);
+
+        let tp = block.add(tls_size) as *mut Tcb;
+        tp.write(tp as Tcb);
+        set_thread_pointer(tp as u64);
+    }
+}
+
+/// Runs the functions of the `.init_array` section of the guest, in
+/// order. This is done before `hyperlight_main`.
+pub(crate) fn run_init_array() {
+    for init in functions(image_info().init_array) {
+        init();
+    }
+}
+
+/// Runs the functions of the `.fini_array` section of the guest, such as
+/// the destructors of C++ static objects, in reverse order. They only
+/// run the first time this is called.
+///
+/// The host does not notify the guest before it is torn down, so guests
+/// that rely on these functions call this themselves, for instance from
+/// their implementation of `exit`.
+pub fn run_fini_array() {
+    if FINALIZED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    for fini in functions(image_info().fini_array).iter().rev() {
+        fini();
+    }
+}
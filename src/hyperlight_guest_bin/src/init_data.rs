@@ -0,0 +1,71 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_guest::error::{HyperlightGuestError, Result};
+
+use crate::GUEST_HANDLE;
+
+/// Returns the initialization data provided by the host when it created
+/// the sandbox, which is empty if it provided none.
+pub fn init_data() -> &'static [u8] {
+    #[allow(static_mut_refs)]
+    let Some(peb_ptr) = (unsafe { GUEST_HANDLE.peb() }) else {
+        return &[];
+    };
+    let (ptr, size) = unsafe { ((*peb_ptr).init_data.ptr, (*peb_ptr).init_data.size) };
+    if ptr.is_null() || size == 0 {
+        return &[];
+    }
+    // The initialization data stays mapped for the lifetime of the sandbox
+    unsafe { core::slice::from_raw_parts(ptr as *const u8, size as usize) }
+}
+
+/// A configuration decoded from the initialization data the host provided,
+/// which is passed to the functions marked with `#[guest_init]`.
+///
+/// Implement this trait to decode the data in the format the host encoded
+/// it with.
+pub trait FromInitData: Sized {
+    /// Decodes the configuration from the initialization data
+    fn from_init_data(data: &'static [u8]) -> Result<Self>;
+}
+
+impl FromInitData for &'static [u8] {
+    fn from_init_data(data: &'static [u8]) -> Result<Self> {
+        Ok(data)
+    }
+}
+
+impl FromInitData for Vec<u8> {
+    fn from_init_data(data: &'static [u8]) -> Result<Self> {
+        Ok(data.to_vec())
+    }
+}
+
+impl FromInitData for String {
+    fn from_init_data(data: &'static [u8]) -> Result<Self> {
+        String::from_utf8(data.to_vec()).map_err(|e| {
+            HyperlightGuestError::new(
+                ErrorCode::GuestError,
+                alloc::format!("Initialization data is not valid UTF-8: {}", e),
+            )
+        })
+    }
+}
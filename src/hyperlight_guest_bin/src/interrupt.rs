@@ -0,0 +1,97 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Handlers for the interrupts the host injects into the guest, for
+//! example to signal a timer tick or to ask a long-running guest
+//! function to stop early, without the guest having to poll the host.
+
+use alloc::format;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::interrupt::{
+    FIRST_HOST_INTERRUPT_VECTOR, HOST_INTERRUPT_VECTOR_COUNT, is_host_interrupt_vector,
+};
+use hyperlight_guest::error::{HyperlightGuestError, Result};
+
+/// Handler for an interrupt injected by the host, called with its vector.
+///
+/// Handlers run on the exception stack with interrupts disabled, so they
+/// should do little more than record that the interrupt happened.
+pub type InterruptHandler = fn(vector: u8);
+
+/// Installed handlers, indexed by vector from [`FIRST_HOST_INTERRUPT_VECTOR`]
+static HANDLERS: [AtomicU64; HOST_INTERRUPT_VECTOR_COUNT as usize] =
+    [const { AtomicU64::new(0) }; HOST_INTERRUPT_VECTOR_COUNT as usize];
+
+/// Installs `handler` for the interrupt `vector`, replacing any handler
+/// installed before.
+///
+/// Guest functions run with interrupts enabled once a handler is
+/// installed; interrupts the host injects for a vector without a handler
+/// are ignored.
+pub fn register_handler(vector: u8, handler: InterruptHandler) -> Result<()> {
+    if !is_host_interrupt_vector(vector) {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!(
+                "Interrupt vector {} is outside of the host interrupt vectors {}..{}",
+                vector,
+                FIRST_HOST_INTERRUPT_VECTOR,
+                FIRST_HOST_INTERRUPT_VECTOR + HOST_INTERRUPT_VECTOR_COUNT
+            ),
+        ));
+    }
+    HANDLERS[(vector - FIRST_HOST_INTERRUPT_VECTOR) as usize]
+        .store(handler as usize as u64, Ordering::Release);
+    Ok(())
+}
+
+/// Removes the handler of the interrupt `vector`, if any
+pub fn unregister_handler(vector: u8) {
+    if is_host_interrupt_vector(vector) {
+        HANDLERS[(vector - FIRST_HOST_INTERRUPT_VECTOR) as usize].store(0, Ordering::Release);
+    }
+}
+
+/// Calls the handler of the interrupt `vector`, if one is installed
+pub(crate) fn dispatch(vector: u8) {
+    if !is_host_interrupt_vector(vector) {
+        return;
+    }
+    let handler = HANDLERS[(vector - FIRST_HOST_INTERRUPT_VECTOR) as usize].load(Ordering::Acquire);
+    if handler != 0 {
+        let handler = unsafe { core::mem::transmute::<u64, InterruptHandler>(handler) };
+        handler(vector);
+    }
+}
+
+/// Enables interrupts if any handler is installed. The host clears
+/// RFLAGS.IF at the start of every guest function call.
+pub(crate) fn enable_if_handled() {
+    if HANDLERS
+        .iter()
+        .any(|handler| handler.load(Ordering::Acquire) != 0)
+    {
+        unsafe { core::arch::asm!("sti", options(nomem, nostack)) };
+    }
+}
+
+/// Disables interrupts, so none arrive while the result of a guest
+/// function call is sent to the host
+pub(crate) fn disable() {
+    unsafe { core::arch::asm!("cli", options(nomem, nostack)) };
+}
@@ -18,26 +18,37 @@ limitations under the License.
 // === Dependencies ===
 extern crate alloc;
 
+use core::alloc::{GlobalAlloc, Layout};
 use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 use arch::dispatch::dispatch_function;
 use buddy_system_allocator::LockedHeap;
 use guest_function::register::GuestFunctionRegister;
 use guest_logger::init_logger;
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+#[cfg(feature = "mem_profile")]
+use hyperlight_common::intrinsics;
+#[cfg(feature = "mem_profile")]
+use hyperlight_common::layout::{MAX_GVA, SCRATCH_TOP_MEM_PROFILE_OFFSET};
 use hyperlight_common::log_level::GuestLogFilter;
-use hyperlight_common::mem::HyperlightPEB;
+use hyperlight_common::mem::{GuestAllocFaults, HyperlightPEB};
 #[cfg(feature = "mem_profile")]
 use hyperlight_common::outb::OutBAction;
+use hyperlight_common::outb::{ABORT_PANIC_BACKTRACE, ABORT_PANIC_LOCATION, ABORT_TERMINATOR};
 use hyperlight_guest::exit::write_abort;
 use hyperlight_guest::guest_handle::handle::GuestHandle;
 
 // === Modules ===
 #[cfg_attr(target_arch = "x86_64", path = "arch/amd64/mod.rs")]
 mod arch;
+#[cfg(target_arch = "x86_64")]
+pub mod borrowed_buffer;
+mod call_queue;
 // temporarily expose the architecture-specific exception interface;
 // this should be replaced with something a bit more abstract in the
 // near future.
+mod diagnostics;
 #[cfg(target_arch = "x86_64")]
 pub mod exception;
 pub mod guest_function {
@@ -46,42 +57,72 @@ pub mod guest_function {
     pub mod register;
 }
 
+pub mod guest_config;
 pub mod guest_logger;
 pub mod host_comm;
+pub mod image;
+pub mod init_data;
+#[cfg(target_arch = "x86_64")]
+pub mod interrupt;
+pub mod locale;
 pub mod memory;
+pub mod messaging;
 pub mod paging;
+pub mod secrets;
+pub mod stream;
 
 // Globals
 #[cfg(feature = "mem_profile")]
 struct ProfiledLockedHeap<const ORDER: usize>(LockedHeap<ORDER>);
+
+/// Returns true if the host asked for allocations of `size` bytes to be
+/// reported to the memory profiler. The host can change its mind
+/// between guest calls, so this is checked for every allocation.
+#[cfg(feature = "mem_profile")]
+fn mem_profile_reports(size: usize) -> bool {
+    let min_size = (MAX_GVA as u64 - SCRATCH_TOP_MEM_PROFILE_OFFSET + 1) as *const u64;
+    // Safety: the scratch bookkeeping area is always mapped, and the host
+    // only writes to it while the vCPU is not running
+    size as u64 >= unsafe { min_size.read_volatile() }
+}
+
 #[cfg(feature = "mem_profile")]
 unsafe impl<const ORDER: usize> alloc::alloc::GlobalAlloc for ProfiledLockedHeap<ORDER> {
     unsafe fn alloc(&self, layout: core::alloc::Layout) -> *mut u8 {
         let addr = unsafe { self.0.alloc(layout) };
-        unsafe {
-            core::arch::asm!("out dx, al",
-                in("dx") OutBAction::TraceMemoryAlloc as u16,
-                in("rax") layout.size() as u64,
-                in("rcx") addr as u64);
+        if mem_profile_reports(layout.size()) {
+            unsafe {
+                intrinsics::out64_with_arg(
+                    OutBAction::TraceMemoryAlloc as u16,
+                    layout.size() as u64,
+                    addr as u64,
+                );
+            }
         }
         addr
     }
     unsafe fn dealloc(&self, ptr: *mut u8, layout: core::alloc::Layout) {
-        unsafe {
-            core::arch::asm!("out dx, al",
-                in("dx") OutBAction::TraceMemoryFree as u16,
-                in("rax") layout.size() as u64,
-                in("rcx") ptr as u64);
-            self.0.dealloc(ptr, layout)
+        if mem_profile_reports(layout.size()) {
+            unsafe {
+                intrinsics::out64_with_arg(
+                    OutBAction::TraceMemoryFree as u16,
+                    layout.size() as u64,
+                    ptr as u64,
+                );
+            }
         }
+        unsafe { self.0.dealloc(ptr, layout) }
     }
     unsafe fn alloc_zeroed(&self, layout: core::alloc::Layout) -> *mut u8 {
         let addr = unsafe { self.0.alloc_zeroed(layout) };
-        unsafe {
-            core::arch::asm!("out dx, al",
-                in("dx") OutBAction::TraceMemoryAlloc as u16,
-                in("rax") layout.size() as u64,
-                in("rcx") addr as u64);
+        if mem_profile_reports(layout.size()) {
+            unsafe {
+                intrinsics::out64_with_arg(
+                    OutBAction::TraceMemoryAlloc as u16,
+                    layout.size() as u64,
+                    addr as u64,
+                );
+            }
         }
         addr
     }
@@ -92,28 +133,106 @@ unsafe impl<const ORDER: usize> alloc::alloc::GlobalAlloc for ProfiledLockedHeap
         new_size: usize,
     ) -> *mut u8 {
         let new_ptr = unsafe { self.0.realloc(ptr, layout, new_size) };
-        unsafe {
-            core::arch::asm!("out dx, al",
-                in("dx") OutBAction::TraceMemoryFree as u16,
-                in("rax") layout.size() as u64,
-                in("rcx") ptr);
-            core::arch::asm!("out dx, al",
-                in("dx") OutBAction::TraceMemoryAlloc as u16,
-                in("rax") new_size as u64,
-                in("rcx") new_ptr);
+        if mem_profile_reports(layout.size()) {
+            unsafe {
+                intrinsics::out64_with_arg(
+                    OutBAction::TraceMemoryFree as u16,
+                    layout.size() as u64,
+                    ptr as u64,
+                );
+            }
+        }
+        if mem_profile_reports(new_size) {
+            unsafe {
+                intrinsics::out64_with_arg(
+                    OutBAction::TraceMemoryAlloc as u16,
+                    new_size as u64,
+                    new_ptr as u64,
+                );
+            }
         }
         new_ptr
     }
 }
 
+/// Allocator that makes the allocations the host asked for fail,
+/// see [`GuestAllocFaults`]
+struct FaultInjectingHeap<A> {
+    heap: A,
+    fail_every: AtomicU64,
+    byte_budget: AtomicU64,
+    allocs: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl<A> FaultInjectingHeap<A> {
+    const fn new(heap: A) -> Self {
+        Self {
+            heap,
+            fail_every: AtomicU64::new(0),
+            byte_budget: AtomicU64::new(0),
+            allocs: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Starts injecting `faults`, counting the allocations from now on
+    fn arm(&self, faults: GuestAllocFaults) {
+        self.allocs.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.fail_every.store(faults.fail_every, Ordering::Relaxed);
+        self.byte_budget
+            .store(faults.byte_budget, Ordering::Relaxed);
+    }
+
+    /// Counts an allocation of `size` bytes and returns whether it must fail
+    fn inject_failure(&self, size: usize) -> bool {
+        let fail_every = self.fail_every.load(Ordering::Relaxed);
+        let byte_budget = self.byte_budget.load(Ordering::Relaxed);
+        if fail_every == 0 && byte_budget == 0 {
+            return false;
+        }
+
+        let allocs = self.allocs.fetch_add(1, Ordering::Relaxed) + 1;
+        let bytes = self.bytes.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+        (fail_every != 0 && allocs % fail_every == 0) || (byte_budget != 0 && bytes > byte_budget)
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for FaultInjectingHeap<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.inject_failure(layout.size()) {
+            return core::ptr::null_mut();
+        }
+        unsafe { self.heap.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.heap.dealloc(ptr, layout) }
+    }
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if self.inject_failure(layout.size()) {
+            return core::ptr::null_mut();
+        }
+        unsafe { self.heap.alloc_zeroed(layout) }
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // On failure the original allocation is left untouched
+        if self.inject_failure(new_size) {
+            return core::ptr::null_mut();
+        }
+        unsafe { self.heap.realloc(ptr, layout, new_size) }
+    }
+}
+
 // === Globals ===
 #[cfg(not(feature = "mem_profile"))]
 #[global_allocator]
-pub(crate) static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::<32>::empty();
+pub(crate) static HEAP_ALLOCATOR: FaultInjectingHeap<LockedHeap<32>> =
+    FaultInjectingHeap::new(LockedHeap::<32>::empty());
 #[cfg(feature = "mem_profile")]
 #[global_allocator]
-pub(crate) static HEAP_ALLOCATOR: ProfiledLockedHeap<32> =
-    ProfiledLockedHeap(LockedHeap::<32>::empty());
+pub(crate) static HEAP_ALLOCATOR: FaultInjectingHeap<ProfiledLockedHeap<32>> =
+    FaultInjectingHeap::new(ProfiledLockedHeap(LockedHeap::<32>::empty()));
 
 pub static mut GUEST_HANDLE: GuestHandle = GuestHandle::new();
 pub(crate) static mut REGISTERED_GUEST_FUNCTIONS: GuestFunctionRegister<GuestFunc> =
@@ -157,14 +276,33 @@ fn _panic_handler(info: &core::panic::PanicInfo) -> ! {
     // begin abort sequence by writing the error code
     write_abort(&[ErrorCode::UnknownError as u8]);
 
-    let write_res = write!(w, "{}", info);
+    let write_res = write!(w, "{}", info.message());
     if write_res.is_err() {
         write_abort("panic: message format failed".as_bytes());
     }
 
+    // the location and the backtrace follow the message, so that the
+    // host can tell them apart from it
+    if let Some(location) = info.location() {
+        write_abort(&[ABORT_PANIC_LOCATION]);
+        let _ = write!(
+            w,
+            "{}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+    }
+    let mut frames = [0; arch::backtrace::MAX_PANIC_FRAMES];
+    let len = arch::backtrace::walk_frames(&mut frames);
+    write_abort(&[ABORT_PANIC_BACKTRACE]);
+    for frame in &frames[..len] {
+        let _ = write!(w, "{:x} ", frame);
+    }
+
     // write abort terminator to finish the abort
     // and signal to the host that the message can now be read
-    write_abort(&[0xFF]);
+    write_abort(&[ABORT_TERMINATOR]);
     unreachable!();
 }
 
@@ -184,7 +322,7 @@ pub(crate) extern "C" fn generic_init(
     ops: u64,
     max_log_level: u64,
 ) -> u64 {
-    unsafe {
+    let alloc_faults = unsafe {
         GUEST_HANDLE = GuestHandle::init(peb_address as *mut HyperlightPEB);
         #[allow(static_mut_refs)]
         let peb_ptr = GUEST_HANDLE.peb().unwrap();
@@ -192,16 +330,19 @@ pub(crate) extern "C" fn generic_init(
         let heap_start = (*peb_ptr).guest_heap.ptr as usize;
         let heap_size = (*peb_ptr).guest_heap.size as usize;
         #[cfg(not(feature = "mem_profile"))]
-        let heap_allocator = &HEAP_ALLOCATOR;
+        let heap_allocator = &HEAP_ALLOCATOR.heap;
         #[cfg(feature = "mem_profile")]
-        let heap_allocator = &HEAP_ALLOCATOR.0;
+        let heap_allocator = &HEAP_ALLOCATOR.heap.0;
         heap_allocator
             .try_lock()
             .expect("Failed to access HEAP_ALLOCATOR")
             .init(heap_start, heap_size);
-        peb_ptr
+        (*peb_ptr).alloc_faults
     };
 
+    // Thread-local variables are usable from here on
+    image::init_tls();
+
     // Save the guest start TSC for tracing
     #[cfg(feature = "trace_guest")]
     let guest_start_tsc = hyperlight_guest_tracing::invariant_tsc::read_tsc();
@@ -231,21 +372,36 @@ pub(crate) extern "C" fn generic_init(
     // Open a span to partly capture the initialization of the guest.
     // This is done here because the tracing subscriber is initialized and the guest is in a
     // well-known state
-    #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
+    #[cfg(feature = "trace_guest")]
     let _entered = tracing::span!(tracing::Level::INFO, "generic_init").entered();
 
+    diagnostics::register_diagnostics();
+    call_queue::register_call_queue();
+
+    // Static constructors run once the guest can log and call the host,
+    // before any user initialisation
+    image::run_init_array();
+
     #[cfg(feature = "macros")]
     for registration in __private::GUEST_FUNCTION_INIT {
         registration();
     }
 
+    #[cfg(feature = "macros")]
+    for init in __private::GUEST_INIT {
+        init(init_data::init_data());
+    }
+
     unsafe {
         hyperlight_main();
     }
 
+    // Allocation failures are only injected once the guest is initialised
+    HEAP_ALLOCATOR.arm(alloc_faults);
+
     // All this tracing logic shall be done right before the call to `hlt` which is done after this
     // function returns
-    #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
+    #[cfg(feature = "trace_guest")]
     {
         // NOTE: This is necessary to avoid closing the span twice. Flush closes all the open
         // spans, when preparing to close a guest function call context.
@@ -271,6 +427,42 @@ pub mod __private {
     #[linkme::distributed_slice]
     pub static GUEST_FUNCTION_INIT: [fn()];
 
+    /// The functions marked with `#[guest_init]`, run with the
+    /// initialization data before `hyperlight_main`
+    #[linkme::distributed_slice]
+    pub static GUEST_INIT: [fn(&'static [u8])];
+
+    pub trait InitResult {
+        fn into_result(self) -> Result<(), HyperlightGuestError>;
+    }
+
+    impl InitResult for () {
+        fn into_result(self) -> Result<(), HyperlightGuestError> {
+            Ok(())
+        }
+    }
+
+    impl InitResult for Result<(), HyperlightGuestError> {
+        fn into_result(self) -> Result<(), HyperlightGuestError> {
+            self
+        }
+    }
+
+    /// Runs `init` with the configuration decoded from `data`, panicking
+    /// if either fails so that the sandbox initialization fails
+    pub fn run_guest_init<T: crate::init_data::FromInitData, R: InitResult>(
+        init: fn(T) -> R,
+        data: &'static [u8],
+    ) {
+        let config = match T::from_init_data(data) {
+            Ok(config) => config,
+            Err(e) => panic!("Failed to decode the initialization data: {}", e.message),
+        };
+        if let Err(e) = init(config).into_result() {
+            panic!("Guest initialization failed: {}", e.message);
+        }
+    }
+
     pub trait FromResult {
         type Output;
         fn from_result(res: Result<Self::Output, HyperlightGuestError>) -> Self;
@@ -305,6 +497,6 @@ pub mod __private {
 }
 
 #[cfg(feature = "macros")]
-pub use hyperlight_guest_macro::{guest_function, host_function};
+pub use hyperlight_guest_macro::{guest_function, guest_init, host_function};
 
 pub use crate::guest_function::definition::GuestFunc;
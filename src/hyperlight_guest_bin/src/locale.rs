@@ -0,0 +1,42 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Timezone and locale data provided by the host, see
+//! [`hyperlight_common::locale`]
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use hyperlight_common::locale::{HOST_LOCALE_FUNCTION, HOST_TIMEZONE_FUNCTION};
+use hyperlight_guest::error::Result;
+
+use crate::host_comm::call_host;
+
+/// Returns the TZif data of the timezone `name`, such as `Europe/Paris`,
+/// or `None` if the host does not know it. This fails if the host does
+/// not provide timezone data to the sandbox.
+pub fn timezone_data(name: &str) -> Result<Option<Vec<u8>>> {
+    let data = call_host::<Vec<u8>>(HOST_TIMEZONE_FUNCTION, (name.to_string(),))?;
+    Ok((!data.is_empty()).then_some(data))
+}
+
+/// Returns the data of the locale `tag`, such as `fr-FR`, or `None` if
+/// the host does not know it. This fails if the host does not provide
+/// locale data to the sandbox.
+pub fn locale_data(tag: &str) -> Result<Option<Vec<u8>>> {
+    let data = call_host::<Vec<u8>>(HOST_LOCALE_FUNCTION, (tag.to_string(),))?;
+    Ok((!data.is_empty()).then_some(data))
+}
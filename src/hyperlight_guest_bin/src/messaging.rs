@@ -0,0 +1,48 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Messages exchanged with the other sandboxes of the process, see
+//! [`hyperlight_common::messaging`]
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use hyperlight_common::messaging::{
+    HOST_POLL_FUNCTION, HOST_PUBLISH_FUNCTION, HOST_SUBSCRIBE_FUNCTION,
+};
+use hyperlight_guest::error::Result;
+
+use crate::host_comm::call_host;
+
+/// Publishes `message` to `topic`, for the other sandboxes subscribed
+/// to it. This fails if the host does not connect the sandbox to a
+/// message bus, or if its policy does not allow publishing to `topic`.
+pub fn publish(topic: &str, message: &[u8]) -> Result<()> {
+    call_host::<()>(HOST_PUBLISH_FUNCTION, (topic.to_string(), message.to_vec()))
+}
+
+/// Subscribes to `topic`, so that the messages published to it from
+/// now on can be received with [`poll`]
+pub fn subscribe(topic: &str) -> Result<()> {
+    call_host::<()>(HOST_SUBSCRIBE_FUNCTION, (topic.to_string(),))
+}
+
+/// Returns the next message published to `topic`, or `None` if there
+/// is none. The sandbox must have subscribed to `topic`.
+pub fn poll(topic: &str) -> Result<Option<Vec<u8>>> {
+    let message = call_host::<Vec<u8>>(HOST_POLL_FUNCTION, (topic.to_string(),))?;
+    Ok((!message.is_empty()).then_some(message))
+}
@@ -14,9 +14,12 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use alloc::format;
 use core::arch::asm;
 
-use hyperlight_common::vmem;
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::vmem::{self, BasicMapping, CowMapping, MappingKind};
+use hyperlight_guest::error::{HyperlightGuestError, Result};
 use hyperlight_guest::prim_alloc::alloc_phys_pages;
 
 // TODO: This is not at all thread-safe atm
@@ -145,6 +148,93 @@ pub fn phys_to_virt(gpa: vmem::PhysAddr) -> Option<*mut u8> {
     GuestMappingOperations::new().try_phys_to_virt(gpa)
 }
 
+/// The permissions [`protect`] gives to guest pages, none of which is
+/// both writable and executable
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protection {
+    ReadOnly,
+    ReadWrite,
+    ReadExecute,
+}
+
+/// Changes the permissions of the `len` bytes at `addr`, both page
+/// aligned, every page of which must be mapped. A JIT compiler writes
+/// its code to read-write pages, then makes them read-execute before
+/// running it. The pages of the snapshot made read-write are mapped
+/// copy-on-write, as they are initially.
+///
+/// # Safety
+/// As for [`map_region`], no other page table operations may run
+/// concurrently, and no live reference may rely on the old permissions
+/// of the pages.
+pub unsafe fn protect(addr: *mut u8, len: u64, protection: Protection) -> Result<()> {
+    let page_size = vmem::PAGE_SIZE as u64;
+    let base = addr as u64;
+    let end = base
+        .checked_add(len)
+        .filter(|_| len != 0 && base.is_multiple_of(page_size) && len.is_multiple_of(page_size));
+    let Some(end) = end else {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!("{:#x} (len {:#x}) is not a page aligned range", base, len),
+        ));
+    };
+
+    // Check that the whole range is mapped before changing anything
+    let ops = GuestMappingOperations::new();
+    let page_phys = |virt: u64| {
+        let mut mappings = unsafe { vmem::virt_to_phys::<_>(ops, virt, 1) };
+        mappings
+            .next()
+            .map(|mapping| mapping.phys_base + (virt - mapping.virt_base))
+    };
+    if let Some(virt) = (base..end)
+        .step_by(page_size as usize)
+        .find(|&virt| page_phys(virt).is_none())
+    {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::GuestError,
+            format!("{:#x} is not mapped", virt),
+        ));
+    }
+
+    for virt in (base..end).step_by(page_size as usize) {
+        let Some(phys) = page_phys(virt) else {
+            continue;
+        };
+        let kind = match protection {
+            // Pages still backed by the snapshot are copied on the
+            // first write
+            Protection::ReadWrite if phys < ops.scratch_base_gpa => MappingKind::Cow(CowMapping {
+                readable: true,
+                executable: false,
+            }),
+            Protection::ReadWrite => MappingKind::Basic(BasicMapping {
+                readable: true,
+                writable: true,
+                executable: false,
+            }),
+            Protection::ReadExecute => MappingKind::Basic(BasicMapping {
+                readable: true,
+                writable: false,
+                executable: true,
+            }),
+            Protection::ReadOnly => MappingKind::Basic(BasicMapping {
+                readable: true,
+                writable: false,
+                executable: false,
+            }),
+        };
+        unsafe {
+            map_region(phys, virt as *mut u8, page_size, kind);
+            // The page was already mapped, possibly with more
+            // permissions, so its TLB entry has to go
+            asm!("invlpg [{}]", in(reg) virt, options(readonly, nostack, preserves_flags));
+        }
+    }
+    Ok(())
+}
+
 /// Barriers that other code may need to use when updating page tables
 pub mod barrier {
     /// Call this function when a virtual address has just been made
@@ -0,0 +1,32 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Secrets given by the host, see [`hyperlight_common::secrets`]
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use hyperlight_common::secrets::HOST_SECRET_FUNCTION;
+use hyperlight_guest::error::Result;
+
+use crate::host_comm::call_host;
+
+/// Returns the value of the secret `name`. This fails if the host does
+/// not give this secret to the sandbox, or if its access policy does not
+/// allow reading it now, for instance because it could only be read once.
+pub fn get_secret(name: &str) -> Result<Vec<u8>> {
+    call_host::<Vec<u8>>(HOST_SECRET_FUNCTION, (name.to_string(),))
+}
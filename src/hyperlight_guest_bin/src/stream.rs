@@ -0,0 +1,98 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Payloads larger than the input and output buffers, transferred in
+//! chunks through the streams the host opened, see
+//! [`hyperlight_common::stream`]
+
+use alloc::vec::Vec;
+
+use hyperlight_common::stream::{
+    HOST_STREAM_READ_FUNCTION, HOST_STREAM_WRITE_FUNCTION, STREAM_CHUNK_SIZE,
+};
+use hyperlight_guest::error::Result;
+
+use crate::host_comm::call_host;
+
+/// Reads a stream the host opened for reading, identified by the `u32`
+/// the host gave the guest
+#[derive(Debug)]
+pub struct HostStreamReader {
+    id: u32,
+    done: bool,
+}
+
+impl HostStreamReader {
+    /// Reads the stream `id`
+    pub fn new(id: u32) -> Self {
+        Self { id, done: false }
+    }
+
+    /// Reads the next chunk of the stream into `buf`, returning how many
+    /// bytes were read, which is 0 once the whole stream was read
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let Some(chunk) = self.next_chunk(buf.len())? else {
+            return Ok(0);
+        };
+        buf[..chunk.len()].copy_from_slice(&chunk);
+        Ok(chunk.len())
+    }
+
+    /// Returns the next chunk of the stream, of at most
+    /// [`STREAM_CHUNK_SIZE`] bytes, or `None` once the whole stream was
+    /// read
+    pub fn next_chunk(&mut self, max_len: usize) -> Result<Option<Vec<u8>>> {
+        if self.done || max_len == 0 {
+            return Ok(None);
+        }
+        let max_len = max_len.min(STREAM_CHUNK_SIZE) as u32;
+        let chunk = call_host::<Vec<u8>>(HOST_STREAM_READ_FUNCTION, (self.id, max_len))?;
+        self.done = chunk.is_empty();
+        Ok((!chunk.is_empty()).then_some(chunk))
+    }
+
+    /// Reads the rest of the stream
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        while let Some(chunk) = self.next_chunk(STREAM_CHUNK_SIZE)? {
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+}
+
+/// Writes to a stream the host opened for writing, identified by the
+/// `u32` the host gave the guest
+#[derive(Debug)]
+pub struct HostStreamWriter {
+    id: u32,
+}
+
+impl HostStreamWriter {
+    /// Writes to the stream `id`
+    pub fn new(id: u32) -> Self {
+        Self { id }
+    }
+
+    /// Writes all of `data` to the stream, in chunks of at most
+    /// [`STREAM_CHUNK_SIZE`] bytes
+    pub fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        for chunk in data.chunks(STREAM_CHUNK_SIZE) {
+            call_host::<()>(HOST_STREAM_WRITE_FUNCTION, (self.id, chunk.to_vec()))?;
+        }
+        Ok(())
+    }
+}
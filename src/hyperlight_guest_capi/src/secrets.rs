@@ -0,0 +1,34 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use alloc::boxed::Box;
+use core::ffi::{CStr, c_char};
+use core::ptr;
+
+use hyperlight_guest_bin::secrets::get_secret;
+
+use crate::types::FfiVec;
+
+/// Returns the value of the secret `name`, or null if the host does not
+/// give this secret to the sandbox or does not allow reading it now.
+#[unsafe(no_mangle)]
+pub extern "C" fn hl_secret_get(name: *const c_char) -> *mut FfiVec {
+    let name = unsafe { CStr::from_ptr(name).to_string_lossy() };
+    match get_secret(&name) {
+        Ok(value) => Box::into_raw(Box::new(unsafe { FfiVec::from_vec(value) })),
+        Err(_) => ptr::null_mut(),
+    }
+}
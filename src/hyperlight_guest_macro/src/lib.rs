@@ -156,6 +156,107 @@ pub fn guest_function(attr: TokenStream, item: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// Attribute macro to mark a function as a guest initialization function.
+/// This will run the function once while the sandbox is initialized, before
+/// `hyperlight_main`, with the initialization data the host provided when
+/// building the sandbox.
+///
+/// The function must take a single argument whose type implements
+/// `FromInitData`, which decodes the initialization data, and return either
+/// `()` or a `Result<(), HyperlightGuestError>`.
+///
+/// # Panic
+/// If decoding the initialization data fails or the function returns an
+/// error, the guest will panic and the sandbox initialization will fail.
+///
+/// # Example
+/// ```ignore
+/// use hyperlight_guest_bin::guest_init;
+/// #[guest_init]
+/// fn init(config: String) {
+///     // parse and store the configuration
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn guest_init(attr: TokenStream, item: TokenStream) -> TokenStream {
+    // Obtain the crate name for hyperlight-guest-bin
+    let crate_name =
+        crate_name("hyperlight-guest-bin").expect("hyperlight-guest-bin must be a dependency");
+    let crate_name = match crate_name {
+        FoundCrate::Itself => quote! {crate},
+        FoundCrate::Name(name) => {
+            let ident = syn::Ident::new(&name, proc_macro2::Span::call_site());
+            quote! {::#ident}
+        }
+    };
+
+    if !attr.is_empty() {
+        return Error::new(
+            proc_macro2::Span::call_site(),
+            "guest_init does not take any arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // Parse the function definition that we will be working with, and
+    // early return if parsing as `ItemFn` fails.
+    let fn_declaration = parse_macro_input!(item as ItemFn);
+
+    // Obtain the name of the function being decorated.
+    let ident = fn_declaration.sig.ident.clone();
+
+    // Small sanity checks to improve error messages, as for guest_function.
+
+    // Check that there are no receiver arguments (i.e., `self`, `&self`, `Box<Self>`, etc).
+    if let Some(syn::FnArg::Receiver(arg)) = fn_declaration.sig.inputs.first() {
+        return Error::new(
+            arg.span(),
+            "Receiver (self) argument is not allowed in guest init functions",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // Check that the function takes the configuration as its only argument.
+    if fn_declaration.sig.inputs.len() != 1 {
+        return Error::new(
+            fn_declaration.sig.inputs.span(),
+            "Guest init functions must take exactly one argument",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // Check that the function is not async.
+    if fn_declaration.sig.asyncness.is_some() {
+        return Error::new(
+            fn_declaration.sig.asyncness.span(),
+            "Async functions are not allowed in guest init functions",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // The generated code will replace the decorated code, so we need to
+    // include the original function declaration in the output.
+    let output = quote! {
+        #fn_declaration
+
+        const _: () = {
+            // Add the function in the GUEST_INIT distributed slice so that it
+            // runs at program initialization
+            #[#crate_name::__private::linkme::distributed_slice(#crate_name::__private::GUEST_INIT)]
+            #[linkme(crate = #crate_name::__private::linkme)]
+            static INIT: fn(&'static [u8]) = |data| {
+                #crate_name::__private::run_guest_init(#ident, data);
+            };
+        };
+    };
+
+    output.into()
+}
+
 /// Attribute macro to mark a function as a host function.
 /// This will generate a function that calls the host function with the same name.
 ///
@@ -15,33 +15,52 @@ limitations under the License.
 */
 
 /// Module for checking invariant TSC support and reading the timestamp counter
-use core::arch::x86_64::{__cpuid, _rdtsc};
+use hyperlight_common::intrinsics::{cpuid, read_timestamp};
 
 /// Check if the processor supports invariant TSC
 ///
 /// Returns true if CPUID.80000007H:EDX[8] is set, indicating invariant TSC support
 pub fn has_invariant_tsc() -> bool {
     // Check if extended CPUID functions are available
-    let max_extended = unsafe { __cpuid(0x80000000) };
+    let max_extended = cpuid(0x80000000);
     if max_extended.eax < 0x80000007 {
         return false;
     }
 
     // Query CPUID.80000007H for invariant TSC support
-    let cpuid_result = unsafe { __cpuid(0x80000007) };
+    let cpuid_result = cpuid(0x80000007);
 
     // Check bit 8 of EDX register for invariant TSC support
     (cpuid_result.edx & (1 << 8)) != 0
 }
 
+/// Returns the frequency of the timestamp counter in Hz, or 0 if the
+/// processor does not report it
+///
+/// The frequency is computed from CPUID.15H, which reports the ratio of the
+/// TSC to the core crystal clock and the frequency of the crystal. The host
+/// sets this leaf when it runs the guest TSC at a configured frequency.
+pub fn tsc_frequency() -> u64 {
+    let max_basic = cpuid(0);
+    if max_basic.eax < 0x15 {
+        return 0;
+    }
+
+    // EAX is the denominator and EBX the numerator of the TSC to crystal
+    // clock ratio, ECX the crystal clock frequency in Hz
+    let leaf = cpuid(0x15);
+    if leaf.eax == 0 {
+        return 0;
+    }
+    leaf.ecx as u64 * leaf.ebx as u64 / leaf.eax as u64
+}
+
 /// Read the timestamp counter
 ///
 /// This function provides a high-performance timestamp by reading the TSC.
 /// Should only be used when invariant TSC is supported for reliable timing.
 ///
-/// # Safety
-/// This function uses unsafe assembly instructions but is safe to call.
-/// However, the resulting timestamp is only meaningful if invariant TSC is supported.
+/// The resulting timestamp is only meaningful if invariant TSC is supported.
 pub fn read_tsc() -> u64 {
-    unsafe { _rdtsc() }
+    read_timestamp()
 }
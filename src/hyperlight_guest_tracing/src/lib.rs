@@ -38,6 +38,7 @@ pub use state::TraceBatchInfo;
 #[cfg(feature = "trace")]
 pub use trace::{
     end_trace, flush, init_guest_tracing, is_trace_enabled, new_call, reset, serialized_data,
+    try_end_trace,
 };
 
 /// This module is gated because some of these types are also used on the host, but we want
@@ -106,6 +107,29 @@ mod trace {
         }
     }
 
+    /// Best-effort variant of [`end_trace`] meant for abort paths.
+    ///
+    /// Ends all active spans so that the events leading up to an abort are
+    /// sent to the host along with the abort outb. Unlike [`end_trace`], this
+    /// never panics: if the state is already locked (e.g. the abort was caused
+    /// by an exception raised while the tracing code was running), nothing is
+    /// done and `false` is returned so the caller can skip sending trace data.
+    ///
+    /// The work done here is bounded by the number of open spans; if the
+    /// encoder buffer fills up while closing them, it is flushed to the host
+    /// through the regular `TraceBatch` outb.
+    pub fn try_end_trace() -> bool {
+        if let Some(w) = GUEST_STATE.get()
+            && let Some(state_mutex) = w.upgrade()
+        {
+            let Some(mut state) = state_mutex.try_lock() else {
+                return false;
+            };
+            state.end_trace();
+        }
+        true
+    }
+
     /// Flushes the current trace data to prepare it for reading by the host.
     /// NOTE: Panics if unable to lock the guest state.
     pub fn flush() {
@@ -22,6 +22,8 @@ use core::sync::atomic::{AtomicU64, Ordering};
 use hyperlight_common::flatbuffer_wrappers::guest_trace_data::{
     EventsBatchEncoder, EventsEncoder, GuestEvent, MAX_TRACE_DATA_SIZE,
 };
+use hyperlight_common::intrinsics::out32_with_args;
+use hyperlight_common::layout::{MAX_GVA, SCRATCH_TOP_TRACE_FLUSH_OFFSET};
 use hyperlight_common::outb::OutBAction;
 use tracing_core::Event;
 use tracing_core::span::{Attributes, Id, Record};
@@ -41,6 +43,9 @@ pub(crate) struct GuestState {
     next_id: AtomicU64,
     /// Stack of active spans
     stack: Vec<u64>,
+    /// Frequency of the TSC reported to the host with each call, read once
+    /// as CPUID exits to the host
+    tsc_freq: u64,
 }
 
 /// Start with a stack capacity for active spans
@@ -48,30 +53,51 @@ const ACTIVE_SPANS_CAPACITY: usize = 64;
 
 /// Triggers a VM exit to flush the current events to the host.
 fn send_to_host(data: &[u8]) {
+    // Safety: the host handles trace batch exits and only reads the data
+    // while the vCPU is stopped on the exit
     unsafe {
-        core::arch::asm!("out dx, al",
-            // Port value for tracing
-            in("dx") OutBAction::TraceBatch as u16,
-            in("al") 0u8,
-            // Additional magic number to identify the action
-            in("r8") OutBAction::TraceBatch as u64,
-            in("r9") data.as_ptr() as u64,
-            in("r10") data.len() as u64,
+        out32_with_args(
+            OutBAction::TraceBatch as u16,
+            0,
+            [
+                // Additional magic number to identify the action
+                OutBAction::TraceBatch as u64,
+                data.as_ptr() as u64,
+                data.len() as u64,
+            ],
         );
     }
 }
 
+/// Returns true, and clears the request, if the host asked for the
+/// current events to be flushed
+fn take_flush_request() -> bool {
+    let flag = (MAX_GVA as u64 - SCRATCH_TOP_TRACE_FLUSH_OFFSET + 1) as *mut u64;
+    // Safety: the scratch bookkeeping area is always mapped, and the host
+    // only writes to it while the vCPU is not running
+    unsafe {
+        if flag.read_volatile() == 0 {
+            return false;
+        }
+        flag.write_volatile(0);
+    }
+    true
+}
+
 impl GuestState {
     pub(crate) fn new(guest_start_tsc: u64) -> Self {
+        let tsc_freq = invariant_tsc::tsc_frequency();
         let mut encoder = EventsBatchEncoder::new(MAX_TRACE_DATA_SIZE, send_to_host);
         encoder.encode(&GuestEvent::GuestStart {
             tsc: guest_start_tsc,
+            tsc_freq,
         });
 
         Self {
             encoder,
             next_id: AtomicU64::new(1),
             stack: Vec::with_capacity(ACTIVE_SPANS_CAPACITY),
+            tsc_freq,
         }
     }
 
@@ -95,13 +121,24 @@ impl GuestState {
         self.encoder.flush();
     }
 
+    /// Send the current events to the host if it asked for them, without
+    /// ending the active spans, so that it gets up-to-date traces of long
+    /// running calls
+    fn flush_if_requested(&mut self) {
+        if take_flush_request() {
+            self.encoder.flush();
+        }
+    }
+
     /// Prepare the trace state for a new guest function call
     /// This resets the internal serializer and adds a GuestStart event
-    /// with the provided start timestamp counter (TSC)
+    /// with the provided start timestamp counter (TSC) and the TSC frequency
     pub(crate) fn new_call(&mut self, start_tsc: u64) {
         self.encoder.reset();
-        self.encoder
-            .encode(&GuestEvent::GuestStart { tsc: start_tsc });
+        self.encoder.encode(&GuestEvent::GuestStart {
+            tsc: start_tsc,
+            tsc_freq: self.tsc_freq,
+        });
     }
 
     /// Reset the trace state, clearing all existing spans and events
@@ -163,6 +200,7 @@ impl GuestState {
 
         // Serialize the event
         self.encoder.encode(&event);
+        self.flush_if_requested();
 
         id
     }
@@ -187,6 +225,7 @@ impl GuestState {
 
         // Serialize the event
         self.encoder.encode(&event);
+        self.flush_if_requested();
     }
 
     /// Record new values for an existing span
@@ -225,6 +264,7 @@ impl GuestState {
 
         // Serialize the event
         self.encoder.encode(&event);
+        self.flush_if_requested();
 
         true
     }
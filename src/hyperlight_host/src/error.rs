@@ -21,7 +21,7 @@ use std::error::Error;
 use std::num::TryFromIntError;
 use std::string::FromUtf8Error;
 use std::sync::{MutexGuard, PoisonError};
-use std::time::SystemTimeError;
+use std::time::{Duration, SystemTimeError};
 
 #[cfg(target_os = "windows")]
 use crossbeam_channel::{RecvError, SendError};
@@ -35,6 +35,8 @@ use crate::hypervisor::hyperlight_vm::HyperlightVmError;
 use crate::hypervisor::wrappers::HandleWrapper;
 use crate::mem::memory_region::MemoryRegionFlags;
 use crate::mem::ptr::RawPtr;
+use crate::sandbox::quota::{QuotaResource, SandboxQuotaResource};
+use crate::sandbox::status::SandboxStatus;
 
 /// The error type for Hyperlight operations
 #[derive(Error, Debug)]
@@ -46,6 +48,12 @@ pub enum HyperlightError {
     #[error("Offset: {0} out of bounds, Max is: {1}")]
     BoundsCheckFailed(u64, usize),
 
+    /// The guest function call was cancelled with its
+    /// [`CancellationToken`](crate::sandbox::CancellationToken), and the
+    /// sandbox was restored to its state before the call
+    #[error("The guest function call was cancelled")]
+    Cancelled,
+
     /// Checked Add Overflow
     #[error("Couldn't add offset to base address. Offset: {0}, Base Address: {1}")]
     CheckedAddOverflow(u64, u64),
@@ -64,6 +72,11 @@ pub enum HyperlightError {
     #[error("Error converting CString {0:?}")]
     CStringConversionError(#[from] std::ffi::NulError),
 
+    /// The guest function call did not complete within its deadline, and
+    /// the sandbox was restored to its state before the call
+    #[error("The guest function call did not complete within {0:?}")]
+    DeadlineExceeded(Duration),
+
     /// A generic error with a message
     #[error("{0}")]
     Error(String),
@@ -72,6 +85,11 @@ pub enum HyperlightError {
     #[error("Non-executable address {0:#x} tried to be executed")]
     ExecutionAccessViolation(u64),
 
+    /// The guest function call used up the CPU time budget set with
+    /// `SandboxConfiguration::set_guest_cpu_time_budget`
+    #[error("The guest function call exceeded its CPU time budget of {0:?}")]
+    ExecutionBudgetExceeded(Duration),
+
     /// Guest execution was cancelled by the host
     #[error("Execution was cancelled by the host.")]
     ExecutionCanceledByHost(),
@@ -84,10 +102,20 @@ pub enum HyperlightError {
     #[error("Field Name {0} not found in decoded GuestLogData")]
     FieldIsMissingInGuestLogData(String),
 
+    /// The guest used up its fuel, and the handler set with
+    /// `MultiUseSandbox::on_fuel_exhausted` did not refuel it
+    #[error("The guest used up its fuel")]
+    FuelExhausted,
+
     /// Guest aborted during outb
     #[error("Guest aborted: {0} {1}")]
     GuestAborted(u8, String),
 
+    /// The guest touched the guard pages below its main stack, see
+    /// `SandboxConfiguration::set_max_stack_size`
+    #[error("Guest stack overflow: {0}")]
+    GuestStackOverflow(String),
+
     /// Guest call resulted in error in guest
     #[error("Guest error occurred {0:?}: {1}")]
     GuestError(ErrorCode, String),
@@ -100,6 +128,15 @@ pub enum HyperlightError {
     #[error("Guest call is already in progress")]
     GuestFunctionCallAlreadyInProgress(),
 
+    /// A guest function was bound but the guest does not provide it.
+    #[error("GuestFunction {0} was not found")]
+    GuestFunctionNotFound(String),
+
+    /// A guest function was bound with parameter or return types other
+    /// than the ones the guest declares for it.
+    #[error("GuestFunction {0} was bound as {1}, but the guest declares it as {2}")]
+    GuestFunctionSignatureMismatch(String, String, String),
+
     /// The given type is not supported by the guest interface.
     #[error("Unsupported type: {0}")]
     GuestInterfaceUnsupportedType(String),
@@ -108,10 +145,20 @@ pub enum HyperlightError {
     #[error("The guest offset {0} is invalid.")]
     GuestOffsetIsInvalid(usize),
 
+    /// A Host function was called by the guest but its policy did not
+    /// allow the call.
+    #[error("HostFunction {0} was denied by its policy")]
+    HostFunctionDenied(String),
+
     /// A Host function was called by the guest but it was not registered.
     #[error("HostFunction {0} was not found")]
     HostFunctionNotFound(String),
 
+    /// A Host function was called by the guest with parameter or return
+    /// types other than the ones it was registered with.
+    #[error("HostFunction {0} was called as {1}, but it was registered as {2}")]
+    HostFunctionSignatureMismatch(String, String, String),
+
     /// Hyperlight VM error.
     ///
     /// **Note:** This error variant is considered internal and its structure is not stable.
@@ -124,6 +171,11 @@ pub enum HyperlightError {
     #[error("Reading Writing or Seeking data failed {0:?}")]
     IOError(#[from] std::io::Error),
 
+    /// The guest binary requires something this host does not provide,
+    /// see `hyperlight_common::guest_config`
+    #[error("The guest binary cannot run on this host: {0}")]
+    IncompatibleGuestBinary(String),
+
     /// Failed to convert to Integer
     #[error("Failed To Convert Size to usize")]
     IntConversionFailure(#[from] TryFromIntError),
@@ -132,6 +184,14 @@ pub enum HyperlightError {
     #[error("The flatbuffer is invalid")]
     InvalidFlatBuffer(#[from] InvalidFlatbuffer),
 
+    /// The interrupt vector is not one the host can inject into the guest
+    #[error("Interrupt vector {0} cannot be injected into the guest")]
+    InvalidInterruptVector(u8),
+
+    /// The operation cannot be carried out in the current state of the sandbox
+    #[error("Cannot {0} while the sandbox is {1}")]
+    InvalidSandboxState(&'static str, SandboxStatus),
+
     /// Conversion of str to Json failed
     #[error("Conversion of str data to json failed")]
     JsonConversionFailure(#[from] serde_json::Error),
@@ -177,6 +237,11 @@ pub enum HyperlightError {
     #[error("mprotect failed with os error {0:?}")]
     MprotectFailed(Option<i32>),
 
+    /// The guest read (`false`) or wrote (`true`) the given MSR, which is
+    /// denied by the [`MsrPolicy`](crate::sandbox::MsrPolicy) of the sandbox
+    #[error("Guest {} of MSR {0:#x} denied by the MSR policy", if *.1 { "write" } else { "read" })]
+    MsrAccessDenied(u32, bool),
+
     /// No Hypervisor was found for Sandbox.
     #[error("No Hypervisor was found for Sandbox")]
     NoHypervisorFound(),
@@ -214,6 +279,11 @@ pub enum HyperlightError {
     #[error("The sandbox was poisoned")]
     PoisonedSandbox,
 
+    /// Reserving a resource would exceed the process quota, see
+    /// [`crate::sandbox::quota::ProcessQuota`]
+    #[error("The process quota of {1} for {0} would be exceeded")]
+    ProcessQuotaExceeded(QuotaResource, usize),
+
     /// Raw pointer is less than base address
     #[error("Raw pointer ({0:?}) was less than the base address ({1})")]
     RawPointerLessThanBaseAddress(RawPtr, u64),
@@ -230,6 +300,11 @@ pub enum HyperlightError {
     #[error("Failed To Convert Return Value {0:?} to {1:?}")]
     ReturnValueConversionFailure(ReturnValue, &'static str),
 
+    /// The sandbox used up its quota of a resource, see
+    /// [`crate::sandbox::quota::SandboxQuota`]
+    #[error("The sandbox used up its quota of {0}")]
+    SandboxQuotaExceeded(SandboxQuotaResource),
+
     /// Attempted to process a snapshot but the snapshot size does not match the current memory size
     #[error("Snapshot Size Mismatch: Memory Size {0:?} Snapshot Size {1:?}")]
     SnapshotSizeMismatch(usize, usize),
@@ -238,6 +313,10 @@ pub enum HyperlightError {
     #[error("Snapshot was taken from a different sandbox")]
     SnapshotSandboxMismatch,
 
+    /// The memory layout of a snapshot or migrated sandbox state is not the layout of this sandbox
+    #[error("Snapshot layout is incompatible: {0}")]
+    SnapshotLayoutMismatch(String),
+
     /// SystemTimeError
     #[error("SystemTimeError {0:?}")]
     SystemTimeError(#[from] SystemTimeError),
@@ -277,6 +356,18 @@ pub enum HyperlightError {
     #[cfg(target_os = "windows")]
     #[error("Windows API Error Result {0:?}")]
     WindowsAPIError(#[from] windows_result::Error),
+
+    /// The workspace of a sandbox was used after the sandbox was dropped
+    #[error("The workspace was removed when its sandbox was dropped")]
+    WorkspaceClosed,
+
+    /// A path given to a workspace is absolute or leaves the workspace
+    #[error("Path {0:?} is not inside the workspace")]
+    WorkspacePathOutside(String),
+
+    /// Writing to a workspace would exceed its quota
+    #[error("Writing {0} bytes would exceed the workspace quota of {1} bytes")]
+    WorkspaceQuotaExceeded(u64, u64),
 }
 
 impl From<Infallible> for HyperlightError {
@@ -321,10 +412,14 @@ impl HyperlightError {
             // These errors poison the sandbox because they can leave it in an inconsistent state due
             // to the guest not running to completion.
             HyperlightError::GuestAborted(_, _)
+            | HyperlightError::GuestStackOverflow(_)
             | HyperlightError::ExecutionCanceledByHost()
+            | HyperlightError::ExecutionBudgetExceeded(_)
+            | HyperlightError::FuelExhausted
             | HyperlightError::PoisonedSandbox
             | HyperlightError::ExecutionAccessViolation(_)
             | HyperlightError::MemoryAccessViolation(_, _, _)
+            | HyperlightError::MsrAccessDenied(_, _)
             | HyperlightError::SnapshotSizeMismatch(_, _)
             | HyperlightError::MemoryRegionSizeMismatch(_, _, _)
             // HyperlightVmError::Restore is already handled manually in restore(), but we mark it
@@ -336,6 +431,8 @@ impl HyperlightError {
             // failing partway through
             HyperlightError::HyperlightVmError(HyperlightVmError::UpdateRegion(_))
             | HyperlightError::HyperlightVmError(HyperlightVmError::AccessPageTable(_)) => true,
+            #[cfg(gdb)]
+            HyperlightError::HyperlightVmError(HyperlightVmError::HwBreakpoints(_)) => true,
 
             // HyperlightVmError::DispatchGuestCall may poison the sandbox
             HyperlightError::HyperlightVmError(HyperlightVmError::DispatchGuestCall(e)) => {
@@ -345,24 +442,33 @@ impl HyperlightError {
             // All other errors do not poison the sandbox.
             HyperlightError::AnyhowError(_)
             | HyperlightError::BoundsCheckFailed(_, _)
+            | HyperlightError::Cancelled
             | HyperlightError::CheckedAddOverflow(_, _)
             | HyperlightError::CStringConversionError(_)
+            | HyperlightError::DeadlineExceeded(_)
             | HyperlightError::Error(_)
             | HyperlightError::FailedToGetValueFromParameter()
             | HyperlightError::FieldIsMissingInGuestLogData(_)
             | HyperlightError::GuestError(_, _)
             | HyperlightError::GuestExecutionHungOnHostFunctionCall()
             | HyperlightError::GuestFunctionCallAlreadyInProgress()
+            | HyperlightError::GuestFunctionNotFound(_)
+            | HyperlightError::GuestFunctionSignatureMismatch(_, _, _)
             | HyperlightError::GuestInterfaceUnsupportedType(_)
             | HyperlightError::GuestOffsetIsInvalid(_)
+            | HyperlightError::HostFunctionDenied(_)
             | HyperlightError::HostFunctionNotFound(_)
+            | HyperlightError::HostFunctionSignatureMismatch(_, _, _)
             | HyperlightError::HyperlightVmError(HyperlightVmError::Create(_))
             | HyperlightError::HyperlightVmError(HyperlightVmError::Initialize(_))
             | HyperlightError::HyperlightVmError(HyperlightVmError::MapRegion(_))
             | HyperlightError::HyperlightVmError(HyperlightVmError::UnmapRegion(_))
             | HyperlightError::IOError(_)
+            | HyperlightError::IncompatibleGuestBinary(_)
             | HyperlightError::IntConversionFailure(_)
             | HyperlightError::InvalidFlatBuffer(_)
+            | HyperlightError::InvalidInterruptVector(_)
+            | HyperlightError::InvalidSandboxState(_, _)
             | HyperlightError::JsonConversionFailure(_)
             | HyperlightError::LockAttemptFailed(_)
             | HyperlightError::MemoryAllocationFailed(_)
@@ -376,10 +482,13 @@ impl HyperlightError {
             | HyperlightError::NoMemorySnapshot
             | HyperlightError::ParameterValueConversionFailure(_, _)
             | HyperlightError::PEFileProcessingFailure(_)
+            | HyperlightError::ProcessQuotaExceeded(_, _)
             | HyperlightError::RawPointerLessThanBaseAddress(_, _)
             | HyperlightError::RefCellBorrowFailed(_)
             | HyperlightError::RefCellMutBorrowFailed(_)
             | HyperlightError::ReturnValueConversionFailure(_, _)
+            | HyperlightError::SandboxQuotaExceeded(_)
+            | HyperlightError::SnapshotLayoutMismatch(_)
             | HyperlightError::SnapshotSandboxMismatch
             | HyperlightError::SystemTimeError(_)
             | HyperlightError::TryFromSliceError(_)
@@ -387,7 +496,10 @@ impl HyperlightError {
             | HyperlightError::UnexpectedParameterValueType(_, _)
             | HyperlightError::UnexpectedReturnValueType(_, _)
             | HyperlightError::UTF8StringConversionFailure(_)
-            | HyperlightError::VectorCapacityIncorrect(_, _, _) => false,
+            | HyperlightError::VectorCapacityIncorrect(_, _, _)
+            | HyperlightError::WorkspaceClosed
+            | HyperlightError::WorkspacePathOutside(_)
+            | HyperlightError::WorkspaceQuotaExceeded(_, _) => false,
 
             #[cfg(target_os = "windows")]
             HyperlightError::CrossBeamReceiveError(_) => false,
@@ -443,6 +555,24 @@ mod tests {
         );
     }
 
+    /// Test that ExecutionBudgetExceeded promotes to HyperlightError::ExecutionBudgetExceeded
+    #[test]
+    fn test_promote_execution_budget_exceeded() {
+        let budget = Duration::from_millis(10);
+        let err = DispatchGuestCallError::Run(RunVmError::ExecutionBudgetExceeded(budget));
+        let (promoted, should_poison) = err.promote();
+
+        assert!(
+            should_poison,
+            "ExecutionBudgetExceeded should poison the sandbox"
+        );
+        assert!(
+            matches!(promoted, HyperlightError::ExecutionBudgetExceeded(b) if b == budget),
+            "Expected HyperlightError::ExecutionBudgetExceeded, got {:?}",
+            promoted
+        );
+    }
+
     /// Test that GuestAborted promotes to HyperlightError::GuestAborted with correct values
     #[test]
     fn test_promote_guest_aborted() {
@@ -450,6 +580,8 @@ mod tests {
             HandleOutbError::GuestAborted {
                 code: 42,
                 message: "test abort".to_string(),
+                exception: None,
+                panic: None,
             },
         )));
         let (promoted, should_poison) = err.promote();
@@ -491,6 +623,30 @@ mod tests {
         }
     }
 
+    /// Test that MsrAccessDenied promotes to HyperlightError::MsrAccessDenied
+    #[test]
+    fn test_promote_msr_access_denied() {
+        let err = DispatchGuestCallError::Run(RunVmError::MsrAccessDenied {
+            msr: 0xc000_0103,
+            write: true,
+        });
+        let (promoted, should_poison) = err.promote();
+
+        assert!(should_poison, "MsrAccessDenied should poison the sandbox");
+        assert!(
+            matches!(
+                promoted,
+                HyperlightError::MsrAccessDenied(0xc000_0103, true)
+            ),
+            "Expected HyperlightError::MsrAccessDenied, got {:?}",
+            promoted
+        );
+        assert_eq!(
+            promoted.to_string(),
+            "Guest write of MSR 0xc0000103 denied by the MSR policy"
+        );
+    }
+
     /// Test that non-promoted Run errors are wrapped in HyperlightVmError
     #[test]
     fn test_promote_other_run_errors_wrapped() {
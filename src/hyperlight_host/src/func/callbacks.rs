@@ -0,0 +1,105 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::cell::RefCell;
+
+use crossbeam_channel::Sender;
+use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
+use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
+
+use super::{ParameterTuple, SupportedReturnType};
+use crate::{Result, new_error};
+
+/// How deeply the guest and host functions calling each other back can
+/// nest, counting the guest function called by the host as the first level
+pub const MAX_CALLBACK_DEPTH: usize = 8;
+
+thread_local! {
+    /// Where the host function running on this thread sends the guest
+    /// function calls it makes
+    static CURRENT_CALLBACKS: RefCell<Option<Sender<CallbackRequest>>> =
+        const { RefCell::new(None) };
+}
+
+/// A guest function call made by a host function with [`call_guest`],
+/// which the thread running the vCPU of the sandbox makes
+pub(crate) struct CallbackRequest {
+    pub(crate) call: FunctionCall,
+    pub(crate) reply: Sender<Result<ReturnValue>>,
+}
+
+/// Lets the host function running on this thread call back into the
+/// guest through `requests`, for as long as the guard lives
+pub(crate) struct CallbackScope {
+    previous: Option<Sender<CallbackRequest>>,
+}
+
+impl CallbackScope {
+    pub(crate) fn enter(requests: Sender<CallbackRequest>) -> Self {
+        let previous = CURRENT_CALLBACKS.with(|current| current.replace(Some(requests)));
+        Self { previous }
+    }
+}
+
+impl Drop for CallbackScope {
+    fn drop(&mut self) {
+        CURRENT_CALLBACKS.with(|current| *current.borrow_mut() = self.previous.take());
+    }
+}
+
+/// Calls the guest function `func_name` with `args` from a host function
+/// registered with
+/// [`UninitializedSandbox::register_with_callbacks`](crate::UninitializedSandbox::register_with_callbacks),
+/// while the guest function that called the host function waits for it
+/// to return. Event-driven guest runtimes use this to deliver the events
+/// raised by the host functions they call.
+///
+/// The guest runs the callback on its stack, below the frames of the
+/// call it is waiting in, so it must not use the state that call holds
+/// exclusively. The callback can itself call host functions, including
+/// ones calling back into the guest, up to [`MAX_CALLBACK_DEPTH`] levels.
+///
+/// Fails when not called from a host function registered with
+/// `register_with_callbacks`. If the callback leaves the guest unable to
+/// resume, for instance because it aborted, the guest function call made
+/// by the host fails too.
+pub fn call_guest<Output: SupportedReturnType>(
+    func_name: &str,
+    args: impl ParameterTuple,
+) -> Result<Output> {
+    let requests = CURRENT_CALLBACKS
+        .with(|current| current.borrow().clone())
+        .ok_or_else(|| {
+            new_error!(
+                "{} can only be called back from a host function registered with register_with_callbacks",
+                func_name
+            )
+        })?;
+    let (reply, result) = crossbeam_channel::bounded(1);
+    let call = FunctionCall::new(
+        func_name.to_string(),
+        Some(args.into_value()),
+        FunctionCallType::Guest,
+        Output::TYPE,
+    );
+    requests
+        .send(CallbackRequest { call, reply })
+        .map_err(|_| new_error!("The sandbox stopped running callbacks"))?;
+    let value = result
+        .recv()
+        .map_err(|_| new_error!("The sandbox stopped running callbacks"))??;
+    Ok(Output::from_value(value)?)
+}
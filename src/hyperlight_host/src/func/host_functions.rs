@@ -50,6 +50,8 @@ impl Registerable for UninitializedSandbox {
             function: hf.into().into(),
             parameter_types: Args::TYPE,
             return_type: Output::TYPE,
+            check_signature: false,
+            allows_callbacks: false,
         };
 
         (*hfs).register_host_function(name.to_string(), entry)
@@ -91,8 +93,105 @@ where
     func: Arc<dyn Function<Output, Args, HyperlightError> + Send + Sync + 'static>,
 }
 
+/// A set of host functions, built up with
+/// [`register_host_fn`](Self::register_host_fn) and registered at once on
+/// sandboxes with [`UninitializedSandbox::register_host_functions`].
+///
+/// The parameter and return types of each function, and how they are
+/// converted from and to flatbuffers, are derived from the signature of
+/// its closure. The guest calls to the function are checked against that
+/// signature, and fail with
+/// [`HyperlightError::HostFunctionSignatureMismatch`] if they do not match.
+///
+/// ```no_run
+/// # use hyperlight_host::{Result, UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::func::HostFunctions;
+/// # fn example() -> Result<()> {
+/// let host_functions = HostFunctions::new()
+///     .register_host_fn("Add", |a: i32, b: i32| -> Result<i32> { Ok(a + b) })
+///     .register_host_fn("Greet", |name: String| -> Result<Vec<u8>> {
+///         Ok(format!("Hello, {name}").into_bytes())
+///     });
+///
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.register_host_functions(&host_functions)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct HostFunctions {
+    entries: Vec<(String, FunctionEntry)>,
+}
+
+impl HostFunctions {
+    /// Creates an empty set of host functions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the host function `name`, replacing any function added
+    /// before with the same name
+    pub fn register_host_fn<Args: ParameterTuple, Output: SupportedReturnType>(
+        mut self,
+        name: impl Into<String>,
+        hf: impl Into<HostFunction<Output, Args>>,
+    ) -> Self {
+        let name = name.into();
+        self.entries.retain(|(existing, _)| *existing != name);
+        self.entries.push((
+            name,
+            FunctionEntry {
+                function: hf.into().into(),
+                parameter_types: Args::TYPE,
+                return_type: Output::TYPE,
+                check_signature: true,
+                allows_callbacks: false,
+            },
+        ));
+        self
+    }
+
+    /// The names of the host functions in the set
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = &(String, FunctionEntry)> {
+        self.entries.iter()
+    }
+}
+
+impl std::fmt::Debug for HostFunctions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// Whether the guest may call a host function, set with
+/// [`UninitializedSandbox::set_host_function_policy`] or
+/// [`MultiUseSandbox::set_host_function_policy`](crate::MultiUseSandbox::set_host_function_policy).
+///
+/// A call the policy does not allow fails without calling the function,
+/// and the guest gets the error code
+/// [`ErrorCode::HostFunctionDenied`](hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::HostFunctionDenied).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HostFunctionPolicy {
+    /// The guest may call the function
+    #[default]
+    Allowed,
+    /// The guest may not call the function
+    Denied,
+    /// The guest may call the function this many times in each guest
+    /// function call
+    AllowedTimes(u32),
+}
+
+#[derive(Clone)]
 pub(crate) struct TypeErasedHostFunction {
-    func: Box<dyn Fn(Vec<ParameterValue>) -> Result<ReturnValue> + Send + Sync + 'static>,
+    func: Arc<dyn Fn(Vec<ParameterValue>) -> Result<ReturnValue> + Send + Sync + 'static>,
 }
 
 impl<Args, Output> HostFunction<Output, Args>
@@ -141,7 +240,7 @@ where
 {
     fn from(func: HostFunction<Output, Args>) -> TypeErasedHostFunction {
         TypeErasedHostFunction {
-            func: Box::new(move |args: Vec<ParameterValue>| {
+            func: Arc::new(move |args: Vec<ParameterValue>| {
                 let args = Args::from_value(args)?;
                 Ok(func.call(args)?.into_value())
             }),
@@ -190,6 +289,8 @@ pub(crate) fn register_host_function<Args: ParameterTuple, Output: SupportedRetu
         function: func,
         parameter_types: Args::TYPE,
         return_type: Output::TYPE,
+        check_signature: false,
+        allows_callbacks: false,
     };
 
     sandbox
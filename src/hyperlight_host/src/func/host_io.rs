@@ -0,0 +1,120 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::hypervisor::InterruptHandleImpl;
+use crate::{HyperlightError, Result};
+
+/// How long blocking helpers wait at most before checking for cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How many bytes file helpers read or write at most before checking for
+/// cancellation
+const IO_CHUNK_SIZE: usize = 64 * 1024;
+
+thread_local! {
+    /// The interrupt handle of the sandbox whose host function call is
+    /// being handled on this thread
+    static CURRENT_CALL: RefCell<Option<Arc<dyn InterruptHandleImpl>>> =
+        const { RefCell::new(None) };
+}
+
+/// Makes the cancellation of the sandbox owning `interrupt_handle`
+/// visible to the helpers of this module, for as long as the guard lives
+pub(crate) struct HostCallScope {
+    previous: Option<Arc<dyn InterruptHandleImpl>>,
+}
+
+impl HostCallScope {
+    pub(crate) fn enter(interrupt_handle: Arc<dyn InterruptHandleImpl>) -> Self {
+        let previous = CURRENT_CALL.with(|call| call.replace(Some(interrupt_handle)));
+        Self { previous }
+    }
+}
+
+impl Drop for HostCallScope {
+    fn drop(&mut self) {
+        CURRENT_CALL.with(|call| *call.borrow_mut() = self.previous.take());
+    }
+}
+
+/// Returns true if the guest function call that made the current host
+/// function call was cancelled with
+/// [`InterruptHandle::kill`](crate::hypervisor::InterruptHandle::kill).
+///
+/// Host functions doing long-running work should check this regularly and
+/// give up once it returns true, since the guest will not be resumed.
+/// Returns false when not called from a host function.
+pub fn is_cancelled() -> bool {
+    CURRENT_CALL.with(|call| call.borrow().as_ref().is_some_and(|h| h.is_cancelled()))
+}
+
+/// Returns [`HyperlightError::ExecutionCanceledByHost`] if the current
+/// guest function call was cancelled, see [`is_cancelled`]
+pub fn check_cancelled() -> Result<()> {
+    if is_cancelled() {
+        return Err(HyperlightError::ExecutionCanceledByHost());
+    }
+    Ok(())
+}
+
+/// Sleeps for `duration`, returning early with an error if the current
+/// guest function call is cancelled
+pub fn sleep(duration: Duration) -> Result<()> {
+    let deadline = Instant::now() + duration;
+    loop {
+        check_cancelled()?;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        std::thread::sleep(remaining.min(POLL_INTERVAL));
+    }
+}
+
+/// Reads the whole file at `path`, returning early with an error if the
+/// current guest function call is cancelled
+pub fn read_file(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut data = Vec::new();
+    let mut chunk = vec![0; IO_CHUNK_SIZE];
+    loop {
+        check_cancelled()?;
+        match file.read(&mut chunk)? {
+            0 => return Ok(data),
+            n => data.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+/// Writes `data` to the file at `path`, replacing its content, and
+/// returning early with an error if the current guest function call is
+/// cancelled, in which case the file may be partially written
+pub fn write_file(path: impl AsRef<Path>, data: &[u8]) -> Result<()> {
+    let mut file = File::create(path)?;
+    for chunk in data.chunks(IO_CHUNK_SIZE) {
+        check_cancelled()?;
+        file.write_all(chunk)?;
+    }
+    file.flush()?;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+
+use crate::Result;
+
+/// A layer wrapping every host function call made by the guest, added
+/// with [`UninitializedSandbox::add_host_call_middleware`] or
+/// [`MultiUseSandbox::add_host_call_middleware`].
+///
+/// A layer gets the name and arguments of the call, and decides what to
+/// do with it: pass it on to the rest of the chain with [`Next::run`],
+/// possibly with another name or other arguments, return a result of its
+/// own without calling the host function, or fail the call. It can also
+/// observe the result and how long the call took. This is how auditing,
+/// rate limiting or caching are added to host functions.
+///
+/// The layers added first wrap the ones added after them. Closures taking
+/// the same arguments as [`call`](Self::call) are layers.
+///
+/// ```no_run
+/// # use std::time::Instant;
+/// # use hyperlight_host::{UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::func::{Next, ParameterValue};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.add_host_call_middleware(
+///     |name: &str, args: Vec<ParameterValue>, next: Next<'_>| {
+///         let start = Instant::now();
+///         let result = next.run(name, args);
+///         println!("{name} took {:?}", start.elapsed());
+///         result
+///     },
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`UninitializedSandbox::add_host_call_middleware`]: crate::UninitializedSandbox::add_host_call_middleware
+/// [`MultiUseSandbox::add_host_call_middleware`]: crate::MultiUseSandbox::add_host_call_middleware
+pub trait HostCallMiddleware: Send + Sync {
+    /// Handles the call of the host function `name` with `args`, usually
+    /// by passing it on to `next`
+    fn call(&self, name: &str, args: Vec<ParameterValue>, next: Next<'_>) -> Result<ReturnValue>;
+}
+
+impl<F> HostCallMiddleware for F
+where
+    F: Fn(&str, Vec<ParameterValue>, Next<'_>) -> Result<ReturnValue> + Send + Sync,
+{
+    fn call(&self, name: &str, args: Vec<ParameterValue>, next: Next<'_>) -> Result<ReturnValue> {
+        self(name, args, next)
+    }
+}
+
+/// The layers of the chain after the one handling a call, ending with the
+/// host function
+pub struct Next<'a> {
+    layers: &'a [Arc<dyn HostCallMiddleware>],
+    dispatch: &'a dyn Fn(&str, Vec<ParameterValue>) -> Result<ReturnValue>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        layers: &'a [Arc<dyn HostCallMiddleware>],
+        dispatch: &'a dyn Fn(&str, Vec<ParameterValue>) -> Result<ReturnValue>,
+    ) -> Self {
+        Self { layers, dispatch }
+    }
+
+    /// Passes the call of the host function `name` with `args` on to the
+    /// next layer, or to the host function if this is the last layer
+    pub fn run(self, name: &str, args: Vec<ParameterValue>) -> Result<ReturnValue> {
+        match self.layers.split_first() {
+            Some((layer, layers)) => layer.call(
+                name,
+                args,
+                Next {
+                    layers,
+                    dispatch: self.dispatch,
+                },
+            ),
+            None => (self.dispatch)(name, args),
+        }
+    }
+}
+
+impl std::fmt::Debug for Next<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Next")
+            .field("layers", &self.layers.len())
+            .finish_non_exhaustive()
+    }
+}
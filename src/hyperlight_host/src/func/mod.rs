@@ -14,6 +14,8 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+/// Calls back into the guest from the host functions it called
+pub mod callbacks;
 /// Definitions and functionality to enable guest-to-host function calling,
 /// also called "host functions"
 ///
@@ -26,9 +28,17 @@ limitations under the License.
 /// - Dynamically dispatching a call from the guest to the appropriate
 ///   host function
 pub(crate) mod host_functions;
+/// Helpers for doing I/O from host functions that stop waiting when the
+/// guest function call is cancelled
+pub mod host_io;
+/// Layers wrapping the host function calls made by the guest
+pub mod middleware;
+/// Per-sandbox scratch directories with a size quota, for host
+/// functions that store files on behalf of the guest
+pub mod workspace;
 
 /// Re-export for `HostFunction` trait
-pub use host_functions::{HostFunction, Registerable};
+pub use host_functions::{HostFunction, HostFunctionPolicy, HostFunctions, Registerable};
 /// Re-export for `ParameterValue` enum
 pub use hyperlight_common::flatbuffer_wrappers::function_types::ParameterValue;
 /// Re-export for `ReturnType` enum
@@ -38,3 +48,5 @@ pub use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
 pub use hyperlight_common::func::{
     ParameterTuple, ResultType, SupportedParameterType, SupportedReturnType,
 };
+/// Re-export for the `HostCallMiddleware` trait and its `Next` layers
+pub use middleware::{HostCallMiddleware, Next};
@@ -0,0 +1,235 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::host_io;
+use crate::{HyperlightError, Result};
+
+/// A directory private to a sandbox, where host functions can store files
+/// on behalf of the guest, up to a quota of bytes.
+///
+/// A workspace is created with
+/// [`UninitializedSandbox::create_workspace`](crate::UninitializedSandbox::create_workspace),
+/// and the handle it returns can be cloned into the host functions of the
+/// sandbox. The directory and its content are removed when the sandbox is
+/// dropped, after which every operation fails with
+/// [`HyperlightError::WorkspaceClosed`].
+///
+/// Paths are relative to the workspace and cannot leave it. Only the files
+/// written through the workspace count towards the quota.
+#[derive(Clone, Debug)]
+pub struct Workspace {
+    inner: Arc<WorkspaceInner>,
+}
+
+#[derive(Debug)]
+struct WorkspaceInner {
+    root: PathBuf,
+    quota: u64,
+    state: Mutex<WorkspaceState>,
+}
+
+#[derive(Debug)]
+struct WorkspaceState {
+    /// The bytes used by the files in the workspace
+    used: u64,
+    /// Whether the directory was removed
+    closed: bool,
+}
+
+impl Workspace {
+    /// Creates an empty workspace in the system's temporary directory
+    pub(crate) fn new(quota: u64) -> Result<Self> {
+        let root = std::env::temp_dir().join(format!("hl_workspace_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&root)?;
+        Ok(Self {
+            inner: Arc::new(WorkspaceInner {
+                root,
+                quota,
+                state: Mutex::new(WorkspaceState {
+                    used: 0,
+                    closed: false,
+                }),
+            }),
+        })
+    }
+
+    /// Returns the directory of the workspace
+    pub fn path(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Returns the most bytes the files of the workspace can use
+    pub fn quota(&self) -> u64 {
+        self.inner.quota
+    }
+
+    /// Returns the bytes used by the files of the workspace
+    pub fn used(&self) -> u64 {
+        self.inner.state().used
+    }
+
+    /// Reads the whole file at `path`, see [`host_io::read_file`]
+    pub fn read_file(&self, path: impl AsRef<Path>) -> Result<Vec<u8>> {
+        let path = self.resolve(path.as_ref())?;
+        let state = self.inner.state();
+        if state.closed {
+            return Err(HyperlightError::WorkspaceClosed);
+        }
+        host_io::read_file(path)
+    }
+
+    /// Writes `data` to the file at `path`, replacing its content and
+    /// creating the missing parent directories, see
+    /// [`host_io::write_file`].
+    ///
+    /// Fails with [`HyperlightError::WorkspaceQuotaExceeded`], leaving the
+    /// file untouched, if the workspace would then use more than its quota.
+    pub fn write_file(&self, path: impl AsRef<Path>, data: &[u8]) -> Result<()> {
+        let path = self.resolve(path.as_ref())?;
+        let mut state = self.inner.state();
+        if state.closed {
+            return Err(HyperlightError::WorkspaceClosed);
+        }
+
+        let previous = file_size(&path);
+        let used = state.used.saturating_sub(previous);
+        if used + data.len() as u64 > self.inner.quota {
+            return Err(HyperlightError::WorkspaceQuotaExceeded(
+                data.len() as u64,
+                self.inner.quota,
+            ));
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let res = host_io::write_file(&path, data);
+        // The file may have been partially written if the call was cancelled
+        state.used = used + file_size(&path);
+        res
+    }
+
+    /// Removes the file at `path`
+    pub fn remove_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = self.resolve(path.as_ref())?;
+        let mut state = self.inner.state();
+        if state.closed {
+            return Err(HyperlightError::WorkspaceClosed);
+        }
+
+        let size = file_size(&path);
+        std::fs::remove_file(&path)?;
+        state.used = state.used.saturating_sub(size);
+        Ok(())
+    }
+
+    /// Removes the directory of the workspace
+    pub(crate) fn close(&self) {
+        self.inner.close();
+    }
+
+    /// Resolves `path` within the workspace
+    fn resolve(&self, path: &Path) -> Result<PathBuf> {
+        let inside = path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !inside || path.as_os_str().is_empty() {
+            return Err(HyperlightError::WorkspacePathOutside(
+                path.to_string_lossy().into_owned(),
+            ));
+        }
+        Ok(self.inner.root.join(path))
+    }
+}
+
+impl WorkspaceInner {
+    fn state(&self) -> MutexGuard<'_, WorkspaceState> {
+        // The accounting is updated once the file operations are done, so
+        // the state is consistent even if one of them panicked
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn close(&self) {
+        let mut state = self.state();
+        if state.closed {
+            return;
+        }
+        state.closed = true;
+        state.used = 0;
+        if let Err(e) = std::fs::remove_dir_all(&self.root) {
+            log::error!(
+                "Failed to remove workspace {}: {:?}",
+                self.root.display(),
+                e
+            );
+        }
+    }
+}
+
+impl Drop for WorkspaceInner {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Returns the size of the file at `path`, or 0 if there is none
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Workspace;
+    use crate::HyperlightError;
+
+    #[test]
+    fn quota_and_cleanup() {
+        let workspace = Workspace::new(10).unwrap();
+        let root = workspace.path().to_path_buf();
+
+        workspace.write_file("dir/a", b"hello").unwrap();
+        assert_eq!(workspace.read_file("dir/a").unwrap(), b"hello");
+        assert_eq!(workspace.used(), 5);
+
+        // Overwriting a file only counts the difference
+        workspace.write_file("dir/a", b"hello!").unwrap();
+        workspace.write_file("b", b"four").unwrap();
+        assert_eq!(workspace.used(), 10);
+
+        let err = workspace.write_file("c", b"x").unwrap_err();
+        assert!(matches!(
+            err,
+            HyperlightError::WorkspaceQuotaExceeded(1, 10)
+        ));
+        assert!(!root.join("c").exists());
+
+        workspace.remove_file("b").unwrap();
+        assert_eq!(workspace.used(), 6);
+
+        for path in ["../escape", "/etc/passwd", ""] {
+            let err = workspace.write_file(path, b"x").unwrap_err();
+            assert!(matches!(err, HyperlightError::WorkspacePathOutside(_)));
+        }
+
+        workspace.close();
+        assert!(!root.exists());
+        let err = workspace.read_file("dir/a").unwrap_err();
+        assert!(matches!(err, HyperlightError::WorkspaceClosed));
+    }
+}
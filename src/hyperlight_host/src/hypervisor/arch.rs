@@ -0,0 +1,53 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The architecture specific parts of inspecting a guest.
+//!
+//! The debugger, the memory profiler, the guest tracing and the crash
+//! reports only need to know a few things about the registers of a
+//! stopped vCPU: where it is executing, where its stack and frames are,
+//! and which registers the guest passes the arguments of its host
+//! requests in. They get these through [`GuestRegisters`] instead of the
+//! registers of a given architecture, so that only this module has to
+//! change to support another one.
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) use x86_64::*;
+
+/// The registers of a stopped vCPU, independently of the architecture
+pub(crate) trait GuestRegisters {
+    /// The address of the next instruction the vCPU executes
+    #[cfg_attr(not(any(gdb, crashdump, feature = "mem_profile")), allow(dead_code))]
+    fn pc(&self) -> u64;
+    /// The stack pointer
+    #[cfg_attr(not(feature = "mem_profile"), allow(dead_code))]
+    fn sp(&self) -> u64;
+    /// The frame pointer, the start of the chain of frame records the
+    /// stack is unwound from
+    #[cfg_attr(not(any(crashdump, feature = "mem_profile")), allow(dead_code))]
+    fn fp(&self) -> u64;
+    /// The size and the address of the memory block a memory profiling
+    /// request of the guest is about
+    #[cfg(feature = "mem_profile")]
+    fn mem_profile_args(&self) -> (u64, u64);
+    /// The magic number, the address and the length of the batch of
+    /// trace events a tracing request of the guest carries
+    #[cfg(feature = "trace_guest")]
+    fn trace_batch_args(&self) -> (u64, u64, u64);
+}
@@ -0,0 +1,48 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+#[cfg(crashdump)]
+mod core_regs;
+
+#[cfg(crashdump)]
+pub(crate) use core_regs::*;
+
+use super::GuestRegisters;
+use crate::hypervisor::regs::CommonRegisters;
+
+impl GuestRegisters for CommonRegisters {
+    fn pc(&self) -> u64 {
+        self.rip
+    }
+
+    fn sp(&self) -> u64 {
+        self.rsp
+    }
+
+    fn fp(&self) -> u64 {
+        self.rbp
+    }
+
+    #[cfg(feature = "mem_profile")]
+    fn mem_profile_args(&self) -> (u64, u64) {
+        (self.rax, self.rcx)
+    }
+
+    #[cfg(feature = "trace_guest")]
+    fn trace_batch_args(&self) -> (u64, u64, u64) {
+        (self.r8, self.r9, self.r10)
+    }
+}
@@ -0,0 +1,150 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::hypervisor::arch::GuestRegisters;
+use crate::hypervisor::regs::{CommonRegisters, CommonSpecialRegisters};
+
+/// The number of registers in the `prstatus` note of an ELF core dump
+const CORE_REGISTER_COUNT: usize = 27;
+
+/// The names of the registers of an ELF core dump, in the order of the
+/// `prstatus` note
+const CORE_REGISTER_NAMES: [&str; CORE_REGISTER_COUNT] = [
+    "r15", "r14", "r13", "r12", "rbp", "rbx", "r11", "r10", "r9", "r8", "rax", "rcx", "rdx", "rsi",
+    "rdi", "orig_rax", "rip", "cs", "eflags", "rsp", "ss", "fs_base", "gs_base", "ds", "es", "fs",
+    "gs",
+];
+const RBP: usize = 4;
+const RIP: usize = 16;
+const RSP: usize = 19;
+
+/// The general purpose and segment registers of a vCPU, as written to
+/// the `prstatus` note of an ELF core dump
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct CoreRegisters([u64; CORE_REGISTER_COUNT]);
+
+impl CoreRegisters {
+    pub(crate) fn new(regs: &CommonRegisters, sregs: &CommonSpecialRegisters) -> Self {
+        Self([
+            regs.r15,
+            regs.r14,
+            regs.r13,
+            regs.r12,
+            regs.rbp,
+            regs.rbx,
+            regs.r11,
+            regs.r10,
+            regs.r9,
+            regs.r8,
+            regs.rax,
+            regs.rcx,
+            regs.rdx,
+            regs.rsi,
+            regs.rdi,
+            // orig_rax is the system call number, which guests do not have
+            0,
+            regs.rip,
+            sregs.cs.selector as u64,
+            regs.rflags,
+            regs.rsp,
+            sregs.ss.selector as u64,
+            sregs.fs.base,
+            sregs.gs.base,
+            sregs.ds.selector as u64,
+            sregs.es.selector as u64,
+            sregs.fs.selector as u64,
+            sregs.gs.selector as u64,
+        ])
+    }
+
+    /// The values of the registers, in the order of [`CORE_REGISTER_NAMES`]
+    pub(crate) fn values(&self) -> &[u64] {
+        &self.0
+    }
+
+    /// The names of the registers along with their values
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        CORE_REGISTER_NAMES.into_iter().zip(self.0.iter().copied())
+    }
+}
+
+impl GuestRegisters for CoreRegisters {
+    fn pc(&self) -> u64 {
+        self.0[RIP]
+    }
+
+    fn sp(&self) -> u64 {
+        self.0[RSP]
+    }
+
+    fn fp(&self) -> u64 {
+        self.0[RBP]
+    }
+
+    #[cfg(feature = "mem_profile")]
+    fn mem_profile_args(&self) -> (u64, u64) {
+        // rax and rcx
+        (self.0[10], self.0[11])
+    }
+
+    #[cfg(feature = "trace_guest")]
+    fn trace_batch_args(&self) -> (u64, u64, u64) {
+        // r8, r9 and r10
+        (self.0[9], self.0[8], self.0[7])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CoreRegisters, GuestRegisters};
+    use crate::hypervisor::regs::CommonRegisters;
+    use crate::hypervisor::regs::CommonSpecialRegisters;
+
+    #[test]
+    fn core_registers() {
+        let mut sregs = CommonSpecialRegisters::default();
+        sregs.cs.selector = 0x8;
+        sregs.fs.base = 0x7000;
+        let regs = CommonRegisters {
+            rax: 1,
+            rcx: 2,
+            r8: 3,
+            r9: 4,
+            r10: 5,
+            rbp: 0x1f00,
+            rsp: 0x1ef0,
+            rip: 0x4000,
+            ..Default::default()
+        };
+
+        let core = CoreRegisters::new(&regs, &sregs);
+        let find = |name| core.iter().find(|(n, _)| *n == name).unwrap().1;
+        assert_eq!(find("rip"), 0x4000);
+        assert_eq!(find("cs"), 0x8);
+        assert_eq!(find("fs_base"), 0x7000);
+        assert_eq!(find("orig_rax"), 0);
+
+        // The core dump registers and the vCPU registers agree
+        assert_eq!(
+            (core.pc(), core.sp(), core.fp()),
+            (regs.pc(), regs.sp(), regs.fp())
+        );
+        #[cfg(feature = "mem_profile")]
+        assert_eq!(core.mem_profile_args(), regs.mem_profile_args());
+        #[cfg(feature = "trace_guest")]
+        assert_eq!(core.trace_batch_args(), regs.trace_batch_args());
+    }
+}
@@ -0,0 +1,163 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The CPU time budget of guest function calls, see
+//! [`SandboxConfiguration::set_guest_cpu_time_budget`].
+//!
+//! The budget is enforced by a POSIX timer on the CPU time clock of the
+//! thread running the vCPU. When it expires, the timer kicks the vCPU out
+//! of the guest with the signal [`InterruptHandle::kill`] uses, so no
+//! thread has to watch over the call.
+//!
+//! [`SandboxConfiguration::set_guest_cpu_time_budget`]: crate::sandbox::SandboxConfiguration::set_guest_cpu_time_budget
+//! [`InterruptHandle::kill`]: crate::hypervisor::InterruptHandle::kill
+
+use std::io;
+use std::time::Duration;
+
+/// Errors that can occur when starting the CPU time budget of a call
+#[derive(Debug, thiserror::Error)]
+pub enum CpuBudgetError {
+    #[error("Failed to create the CPU time budget timer: {0}")]
+    CreateTimer(io::Error),
+    #[error("Failed to read the CPU time of the vCPU thread: {0}")]
+    ReadClock(io::Error),
+    #[error("Failed to arm the CPU time budget timer: {0}")]
+    SetTimer(io::Error),
+}
+
+/// The CPU time each guest function call can use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CpuBudget {
+    pub(crate) budget: Duration,
+    /// The signal interrupting the vCPU
+    pub(crate) signal: libc::c_int,
+}
+
+impl CpuBudget {
+    /// Starts counting the CPU time the current thread uses against the
+    /// budget, until the returned timer is dropped
+    pub(crate) fn start(&self) -> Result<CpuBudgetTimer, CpuBudgetError> {
+        let now = thread_cpu_time().map_err(CpuBudgetError::ReadClock)?;
+
+        let mut event: libc::sigevent = unsafe { std::mem::zeroed() };
+        event.sigev_notify = libc::SIGEV_THREAD_ID;
+        event.sigev_signo = self.signal;
+        event.sigev_notify_thread_id = unsafe { libc::gettid() };
+        let mut timer: libc::timer_t = std::ptr::null_mut();
+        if unsafe { libc::timer_create(libc::CLOCK_THREAD_CPUTIME_ID, &mut event, &mut timer) } != 0
+        {
+            return Err(CpuBudgetError::CreateTimer(io::Error::last_os_error()));
+        }
+        // From here on the timer is deleted when dropped
+        let timer = CpuBudgetTimer {
+            timer,
+            budget: self.budget,
+            deadline: now + self.budget,
+        };
+
+        let expiry = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            // Relative to the current CPU time of the thread
+            it_value: libc::timespec {
+                tv_sec: self.budget.as_secs() as libc::time_t,
+                tv_nsec: self.budget.subsec_nanos() as libc::c_long,
+            },
+        };
+        if unsafe { libc::timer_settime(timer.timer, 0, &expiry, std::ptr::null_mut()) } != 0 {
+            return Err(CpuBudgetError::SetTimer(io::Error::last_os_error()));
+        }
+        Ok(timer)
+    }
+}
+
+/// A timer interrupting the vCPU once the thread that started it has used
+/// up the CPU time budget of the current call
+#[must_use]
+pub(crate) struct CpuBudgetTimer {
+    timer: libc::timer_t,
+    budget: Duration,
+    /// The CPU time of the thread at which the budget is used up
+    deadline: Duration,
+}
+
+impl CpuBudgetTimer {
+    pub(crate) fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Returns whether the current thread has used up the budget. This
+    /// must be called on the thread that started the timer.
+    pub(crate) fn exhausted(&self) -> bool {
+        // Reading the clock of the current thread only fails if it is not
+        // supported, in which case the timer could not have been started
+        thread_cpu_time().is_ok_and(|now| now >= self.deadline)
+    }
+}
+
+impl Drop for CpuBudgetTimer {
+    fn drop(&mut self) {
+        if unsafe { libc::timer_delete(self.timer) } != 0 {
+            tracing::warn!(
+                "Failed to delete the CPU time budget timer: {}",
+                io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// The CPU time the current thread has used
+fn thread_cpu_time() -> io::Result<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CpuBudget;
+
+    #[test]
+    fn cpu_budget_timer() {
+        // A signal nothing handles would kill the test, so the timer
+        // signals the thread with one that is ignored by default
+        let budget = CpuBudget {
+            budget: Duration::from_millis(20),
+            signal: libc::SIGURG,
+        };
+        let timer = budget.start().unwrap();
+        assert_eq!(timer.budget(), Duration::from_millis(20));
+        assert!(!timer.exhausted());
+
+        // Sleeping does not use CPU time
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(!timer.exhausted());
+
+        while !timer.exhausted() {
+            std::hint::spin_loop();
+        }
+    }
+}
@@ -0,0 +1,427 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Crash report bundles.
+//!
+//! A crash report is a directory named `hl_crash_<timestamp>` holding:
+//! - `report.json`: what crashed, why, and the configuration of the sandbox
+//! - `registers.json`: the general purpose, segment and vector registers of
+//!   the vCPU
+//! - `stack.json`: the guest call stack, symbolized from the guest binary
+//! - `events.json`: the last [`DebugEvent`]s raised by the sandbox
+//! - `core.elf`: an ELF core dump of the guest
+//!
+//! Addresses are written as hexadecimal strings, since JSON numbers cannot
+//! represent every 64-bit value. Any change to the layout of these files
+//! that could break their consumers must bump [`SCHEMA_VERSION`].
+
+use std::io::Write;
+use std::path::Path;
+
+use elfcore::ReadProcessMemory;
+use goblin::elf::Elf;
+use goblin::elf::sym::STT_FUNC;
+use serde_json::{Map, Value, json};
+
+use super::crashdump::{
+    CrashDumpContext, GuestMemReader, checked_core_dump, dump_output_dir, dump_timestamp,
+};
+use crate::hypervisor::arch::GuestRegisters;
+use crate::hypervisor::hyperlight_vm::HyperlightVm;
+use crate::hypervisor::regs::XsaveVectorRegs;
+use crate::mem::mgr::SandboxMemoryManager;
+use crate::mem::shared_mem::HostSharedMemory;
+use crate::sandbox::SandboxConfiguration;
+use crate::sandbox::debug_events::DebugEvent;
+use crate::{Result, new_error};
+
+/// The version of the layout of the files in a crash report
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+
+/// The most frames walked when unwinding the guest stack, which bounds
+/// the walk when the frame pointers are corrupted
+const MAX_STACK_FRAMES: usize = 64;
+
+/// Writes a crash report bundle describing the state of the guest.
+///
+/// The bundle is placed in the directory the sandbox was configured to
+/// place core dumps in, or else in the one given by the
+/// `HYPERLIGHT_CORE_DUMP_DIR` environment variable, or in the system's
+/// temporary directory if neither is set.
+///
+/// # Arguments
+/// * `hv`: Reference to the hypervisor implementation
+/// * `mem_mgr`: Mutable reference to the sandbox memory manager
+/// * `reason`: Description of the error that made the guest crash
+pub(crate) fn generate_crash_report(
+    hv: &HyperlightVm,
+    mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    reason: &str,
+) -> Result<()> {
+    let ctx = hv
+        .capture_crash_context(mem_mgr)
+        .map_err(|e| new_error!("Failed to get crashdump context: {:?}", e))?;
+
+    let dump_dir = hv
+        .core_dump_dir()
+        .or_else(|| std::env::var("HYPERLIGHT_CORE_DUMP_DIR").ok());
+    let bundle_dir = dump_output_dir(dump_dir).join(format!("hl_crash_{}", dump_timestamp()));
+
+    write_crash_report(
+        &bundle_dir,
+        ctx,
+        reason,
+        hv.sandbox_config(),
+        &hv.debug_events().recent(),
+    )?;
+
+    println!(
+        "Crash report created successfully: {}",
+        bundle_dir.display()
+    );
+    log::error!("Crash report: {}", bundle_dir.display());
+
+    Ok(())
+}
+
+/// Writes the files of a crash report to `bundle_dir`, which is created
+fn write_crash_report(
+    bundle_dir: &Path,
+    ctx: CrashDumpContext,
+    reason: &str,
+    config: &SandboxConfiguration,
+    events: &[DebugEvent],
+) -> Result<()> {
+    std::fs::create_dir_all(bundle_dir)
+        .map_err(|e| new_error!("Failed to create crash report directory: {:?}", e))?;
+
+    let write_json = |name: &str, value: &Value| -> Result<()> {
+        let content = serde_json::to_vec_pretty(value)
+            .map_err(|e| new_error!("Failed to serialize {}: {:?}", name, e))?;
+        std::fs::write(bundle_dir.join(name), content)
+            .map_err(|e| new_error!("Failed to write {}: {:?}", name, e))
+    };
+
+    write_json("registers.json", &registers_json(&ctx))?;
+    write_json("stack.json", &Value::Array(stack_json(&ctx)))?;
+    write_json(
+        "events.json",
+        &Value::Array(events.iter().map(event_json).collect()),
+    )?;
+
+    let mut report = report_json(&ctx, reason, config);
+
+    // The report is still useful without the core dump, so a failure to
+    // write it is only recorded
+    let core_path = bundle_dir.join("core.elf");
+    let create_core_file = || {
+        Ok(Box::new(
+            std::fs::File::create(&core_path)
+                .map_err(|e| new_error!("Failed to create core dump file: {:?}", e))?,
+        ) as Box<dyn Write>)
+    };
+    report["files"]["core_dump"] = match checked_core_dump(Some(ctx), create_core_file) {
+        Ok(_) => json!("core.elf"),
+        Err(e) => {
+            log::error!("Failed to create core dump for crash report: {:?}", e);
+            Value::Null
+        }
+    };
+
+    write_json("report.json", &report)
+}
+
+fn hex(value: u64) -> String {
+    format!("{:#x}", value)
+}
+
+fn report_json(ctx: &CrashDumpContext, reason: &str, config: &SandboxConfiguration) -> Value {
+    let regions: Vec<Value> = ctx
+        .regions
+        .iter()
+        .map(|r| {
+            json!({
+                "guest_start": hex(r.guest_region.start as u64),
+                "guest_end": hex(r.guest_region.end as u64),
+                "flags": format!("{:?}", r.flags),
+                "type": format!("{:?}", r.region_type),
+            })
+        })
+        .collect();
+
+    let faults = config.get_guest_alloc_faults();
+    #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+    let mut sandbox_config = json!({
+        "input_data_size": config.get_input_data_size(),
+        "output_data_size": config.get_output_data_size(),
+        "heap_size": config.get_heap_size(),
+        "scratch_size": config.get_scratch_size(),
+        "guest_large_pages": config.get_guest_large_pages(),
+        "host_huge_pages": config.get_host_huge_pages(),
+        "memory_backing": format!("{:?}", config.get_memory_backing()),
+        "zeroize_memory": config.get_zeroize_memory(),
+        "max_stack_size": config.get_max_stack_size(),
+        "heap_guard_pages": config.get_heap_guard_pages(),
+        "guest_aslr": config.get_guest_aslr(),
+        "guest_write_xor_execute": config.get_guest_write_xor_execute(),
+        "guest_core_dump": config.get_guest_core_dump(),
+        "guest_alloc_fail_every": faults.fail_every,
+        "guest_alloc_byte_budget": faults.byte_budget,
+        "host_call_fail_every": config.get_host_call_fail_every(),
+    });
+    #[cfg(target_os = "linux")]
+    {
+        sandbox_config["interrupt_retry_delay_us"] =
+            json!(config.get_interrupt_retry_delay().as_micros() as u64);
+        sandbox_config["interrupt_vcpu_sigrtmin_offset"] =
+            json!(config.get_interrupt_vcpu_sigrtmin_offset());
+    }
+
+    json!({
+        "schema_version": SCHEMA_VERSION,
+        "timestamp": chrono::Local::now().to_rfc3339(),
+        "hyperlight_version": env!("CARGO_PKG_VERSION"),
+        "reason": reason,
+        "binary": ctx.binary,
+        "entry_point": hex(ctx.entry),
+        "regions": regions,
+        "sandbox_config": sandbox_config,
+        "files": {
+            "registers": "registers.json",
+            "stack": "stack.json",
+            "events": "events.json",
+            "core_dump": Value::Null,
+        },
+    })
+}
+
+fn registers_json(ctx: &CrashDumpContext) -> Value {
+    let mut registers: Map<String, Value> = ctx
+        .regs
+        .iter()
+        .map(|(name, value)| (name.to_string(), json!(hex(value))))
+        .collect();
+
+    // The vector registers are only known when the XSAVE area was captured
+    if let Some(vector) = XsaveVectorRegs::from_xsave(&ctx.xsave) {
+        registers.insert("mxcsr".to_string(), json!(hex(vector.mxcsr.into())));
+        for i in 0..vector.xmm.len() {
+            registers.insert(format!("ymm{}", i), json!(hex_bytes(&vector.ymm(i))));
+        }
+    }
+    Value::Object(registers)
+}
+
+/// Formats little-endian bytes as a hexadecimal number
+fn hex_bytes(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().rev().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", digits)
+}
+
+fn event_json(event: &DebugEvent) -> Value {
+    match event {
+        DebugEvent::GuestPrint(text) => json!({ "kind": "guest_print", "text": text }),
+        DebugEvent::Exception { code, message } => {
+            json!({ "kind": "exception", "code": code, "message": message })
+        }
+        #[cfg(gdb)]
+        DebugEvent::BreakpointHit { rip } => json!({ "kind": "breakpoint_hit", "rip": hex(*rip) }),
+        #[cfg(gdb)]
+        DebugEvent::WatchpointHit { rip, addr } => {
+            json!({ "kind": "watchpoint_hit", "rip": hex(*rip), "addr": hex(*addr) })
+        }
+        #[cfg(gdb)]
+        DebugEvent::StepComplete { rip } => json!({ "kind": "step_complete", "rip": hex(*rip) }),
+    }
+}
+
+/// Walks the guest stack by following the frame pointers, and
+/// symbolizes every return address with the symbols of the guest binary
+fn stack_json(ctx: &CrashDumpContext) -> Vec<Value> {
+    let symbols = ctx
+        .binary
+        .as_deref()
+        .and_then(|path| Symbolizer::load(path, ctx.entry));
+
+    let mut reader = GuestMemReader::new(ctx);
+    let mut read_u64 = |addr: u64| {
+        let mut buf = [0u8; 8];
+        match reader.read_process_memory(addr as usize, &mut buf) {
+            Ok(8) => Some(u64::from_le_bytes(buf)),
+            _ => None,
+        }
+    };
+
+    let mut frames = Vec::new();
+    let mut pc = ctx.regs.pc();
+    let mut fp = ctx.regs.fp();
+    while pc != 0 && frames.len() < MAX_STACK_FRAMES {
+        frames.push(json!({
+            "address": hex(pc),
+            "symbol": symbols.as_ref().and_then(|s| s.symbolize(pc)),
+        }));
+
+        // The caller's frame pointer is at [fp] and the return address
+        // right above it
+        let (Some(next_fp), Some(ret)) = (read_u64(fp), read_u64(fp.wrapping_add(8))) else {
+            break;
+        };
+        // Frames grow down, so a frame pointer that does not move up
+        // means the chain is broken
+        if next_fp <= fp {
+            if ret != 0 {
+                frames.push(json!({
+                    "address": hex(ret),
+                    "symbol": symbols.as_ref().and_then(|s| s.symbolize(ret)),
+                }));
+            }
+            break;
+        }
+        pc = ret;
+        fp = next_fp;
+    }
+    frames
+}
+
+/// Resolves guest addresses to the function symbols of the guest binary
+struct Symbolizer {
+    /// The start, end and name of every function, relative to the
+    /// address the binary was linked at
+    functions: Vec<(u64, u64, String)>,
+    /// How far the binary was loaded from the address it was linked at
+    load_offset: u64,
+}
+
+impl Symbolizer {
+    /// Loads the symbols of the binary at `path`, which was loaded so that
+    /// its entry point is at `entry`
+    fn load(path: &str, entry: u64) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+        let elf = Elf::parse(&data).ok()?;
+        let functions = elf
+            .syms
+            .iter()
+            .filter(|sym| sym.st_type() == STT_FUNC && sym.st_size > 0)
+            .filter_map(|sym| {
+                let name = elf.strtab.get_at(sym.st_name)?;
+                Some((sym.st_value, sym.st_value + sym.st_size, name.to_string()))
+            })
+            .collect();
+        Some(Self {
+            functions,
+            load_offset: entry.wrapping_sub(elf.entry),
+        })
+    }
+
+    /// Returns `symbol+0xoffset` for the function containing `addr`
+    fn symbolize(&self, addr: u64) -> Option<String> {
+        let addr = addr.wrapping_sub(self.load_offset);
+        self.functions
+            .iter()
+            .find(|(start, end, _)| (*start..*end).contains(&addr))
+            .map(|(start, _, name)| format!("{}+{:#x}", name, addr - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hypervisor::arch::CoreRegisters;
+    use crate::hypervisor::regs::{CommonRegisters, CommonSpecialRegisters};
+    use crate::mem::memory_region::{CrashDumpRegion, MemoryRegionFlags, MemoryRegionType};
+
+    /// Check that every file of the bundle is written, and that the stack
+    /// is walked through the frame pointers stored in guest memory
+    #[test]
+    fn test_crash_report_bundle() {
+        // Two frames: rbp points at 0x1f00, which links to 0x1f80, whose
+        // saved frame pointer ends the chain
+        let mut mem = vec![0u64; 0x1000 / 8];
+        mem[0xf00 / 8] = 0x1f80;
+        mem[0xf08 / 8] = 0x4010;
+        mem[0xf80 / 8] = 0;
+        mem[0xf88 / 8] = 0x4020;
+        let ptr = mem.as_ptr() as usize;
+        let regions = vec![CrashDumpRegion {
+            guest_region: 0x1000..0x2000,
+            host_region: ptr..ptr + 0x1000,
+            flags: MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
+            region_type: MemoryRegionType::Scratch,
+        }];
+        let regs = CoreRegisters::new(
+            &CommonRegisters {
+                rbp: 0x1f00,
+                rip: 0x4000,
+                ..Default::default()
+            },
+            &CommonSpecialRegisters::default(),
+        );
+        // An XSAVE area where the lowest and the highest byte of YMM0 are set
+        let mut xsave = vec![0u8; 4096];
+        xsave[24..28].copy_from_slice(&0x1f80u32.to_le_bytes());
+        xsave[160] = 0x01;
+        xsave[512] = 0b111;
+        xsave[576 + 15] = 0xff;
+        let ctx = CrashDumpContext::new(regions, regs, xsave, 0x4000, None, None);
+
+        let bundle_dir = std::env::temp_dir().join(format!("hl_crash_test_{}", std::process::id()));
+        let events = [DebugEvent::GuestPrint("hello".to_string())];
+        write_crash_report(
+            &bundle_dir,
+            ctx,
+            "guest aborted",
+            &SandboxConfiguration::default(),
+            &events,
+        )
+        .unwrap();
+
+        let read_json = |name: &str| -> Value {
+            serde_json::from_slice(&std::fs::read(bundle_dir.join(name)).unwrap()).unwrap()
+        };
+
+        let report = read_json("report.json");
+        assert_eq!(report["schema_version"], SCHEMA_VERSION);
+        assert_eq!(report["reason"], "guest aborted");
+        assert_eq!(report["entry_point"], "0x4000");
+        assert_eq!(report["files"]["core_dump"], "core.elf");
+        assert!(bundle_dir.join("core.elf").exists());
+
+        let registers = read_json("registers.json");
+        assert_eq!(registers["rip"], "0x4000");
+        assert_eq!(registers["rbp"], "0x1f00");
+        assert_eq!(registers["mxcsr"], "0x1f80");
+        assert_eq!(
+            registers["ymm0"],
+            format!("0xff{}01", "00".repeat(30)).as_str()
+        );
+        assert_eq!(registers["ymm1"], format!("0x{}", "00".repeat(32)).as_str());
+
+        let addresses: Vec<Value> = read_json("stack.json")
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|frame| frame["address"].clone())
+            .collect();
+        assert_eq!(addresses, vec!["0x4000", "0x4010", "0x4020"]);
+
+        assert_eq!(
+            read_json("events.json"),
+            json!([{ "kind": "guest_print", "text": "hello" }])
+        );
+
+        std::fs::remove_dir_all(&bundle_dir).unwrap();
+    }
+}
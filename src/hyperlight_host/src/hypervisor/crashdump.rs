@@ -23,6 +23,7 @@ use elfcore::{
     ReadProcessMemory, ThreadView, VaProtection, VaRegion,
 };
 
+use crate::hypervisor::arch::CoreRegisters;
 use crate::hypervisor::hyperlight_vm::HyperlightVm;
 use crate::mem::memory_region::{CrashDumpRegion, MemoryRegionFlags};
 use crate::mem::mgr::SandboxMemoryManager;
@@ -46,18 +47,18 @@ const CORE_DUMP_PAGE_SIZE: usize = 0x1000;
 /// This structure contains the information needed to create a core dump
 #[derive(Debug)]
 pub(crate) struct CrashDumpContext {
-    regions: Vec<CrashDumpRegion>,
-    regs: [u64; 27],
-    xsave: Vec<u8>,
-    entry: u64,
-    binary: Option<String>,
+    pub(super) regions: Vec<CrashDumpRegion>,
+    pub(super) regs: CoreRegisters,
+    pub(super) xsave: Vec<u8>,
+    pub(super) entry: u64,
+    pub(super) binary: Option<String>,
     filename: Option<String>,
 }
 
 impl CrashDumpContext {
     pub(crate) fn new(
         regions: Vec<CrashDumpRegion>,
-        regs: [u64; 27],
+        regs: CoreRegisters,
         xsave: Vec<u8>,
         entry: u64,
         binary: Option<String>,
@@ -148,7 +149,7 @@ impl GuestView {
             cmd_line: cmd,
 
             arch_state: Box::new(ArchState {
-                gpr_state: ctx.regs.to_vec(),
+                gpr_state: ctx.regs.values().to_vec(),
                 components,
             }),
         };
@@ -203,12 +204,12 @@ impl ProcessInfoSource for GuestView {
 /// Structure that reads the guest memory
 /// This structure serves as a custom memory reader for `elfcore`'s
 /// [`CoreDumpBuilder`]
-struct GuestMemReader {
+pub(super) struct GuestMemReader {
     regions: Vec<CrashDumpRegion>,
 }
 
 impl GuestMemReader {
-    fn new(ctx: &CrashDumpContext) -> Self {
+    pub(super) fn new(ctx: &CrashDumpContext) -> Self {
         Self {
             regions: ctx.regions.clone(),
         }
@@ -258,8 +259,9 @@ impl ReadProcessMemory for GuestMemReader {
 /// which can be used for debugging when crashes occur.
 ///
 /// If `override_dir` is `Some`, the core dump is placed there. Otherwise, the
-/// location is determined by the `HYPERLIGHT_CORE_DUMP_DIR` environment variable.
-/// If neither is set, it defaults to the system's temporary directory.
+/// location is the directory the sandbox was configured with, if any, or the
+/// one given by the `HYPERLIGHT_CORE_DUMP_DIR` environment variable.
+/// If none is set, it defaults to the system's temporary directory.
 ///
 /// # Arguments
 /// * `hv`: Reference to the hypervisor implementation
@@ -278,8 +280,11 @@ pub(crate) fn generate_crashdump(
         .crashdump_context(mem_mgr)
         .map_err(|e| new_error!("Failed to get crashdump context: {:?}", e))?;
 
-    // Prefer the explicit override, then the env var, then the system temp dir
-    let core_dump_dir = override_dir.or_else(|| std::env::var("HYPERLIGHT_CORE_DUMP_DIR").ok());
+    // Prefer the explicit override, then the sandbox configuration, then the
+    // env var, then the system temp dir
+    let core_dump_dir = override_dir
+        .or_else(|| hv.core_dump_dir())
+        .or_else(|| std::env::var("HYPERLIGHT_CORE_DUMP_DIR").ok());
 
     // Compute file path on the filesystem
     let file_path = core_dump_file_path(core_dump_dir);
@@ -318,13 +323,26 @@ pub(crate) fn generate_crashdump(
 /// Returns:
 /// * `String`: The file path for the core dump file.
 fn core_dump_file_path(dump_dir: Option<String>) -> String {
-    // Generate timestamp string for the filename using chrono
-    let timestamp = chrono::Local::now()
+    // Create the filename with timestamp
+    let filename = format!("hl_core_{}.elf", dump_timestamp());
+    let file_path = dump_output_dir(dump_dir).join(filename);
+
+    file_path.to_string_lossy().to_string()
+}
+
+/// Generates the timestamp used to name the files created on a crash
+pub(super) fn dump_timestamp() -> String {
+    chrono::Local::now()
         .format("%Y%m%d_T%H%M%S%.3f")
-        .to_string();
+        .to_string()
+}
 
-    // Determine the output directory based on environment variable
-    let output_dir = if let Some(dump_dir) = dump_dir {
+/// Determines the directory the files created on a crash are placed in.
+///
+/// Falls back to the system's temp directory if `dump_dir` is `None` or
+/// does not exist.
+pub(super) fn dump_output_dir(dump_dir: Option<String>) -> std::path::PathBuf {
+    if let Some(dump_dir) = dump_dir {
         // Check if the directory exists
         // If it doesn't exist, fall back to the system temp directory
         // This is to ensure that the core dump can be created even if the directory is not set
@@ -340,13 +358,7 @@ fn core_dump_file_path(dump_dir: Option<String>) -> String {
     } else {
         // Fall back to the system temp directory
         std::env::temp_dir()
-    };
-
-    // Create the filename with timestamp
-    let filename = format!("hl_core_{}.elf", timestamp);
-    let file_path = output_dir.join(filename);
-
-    file_path.to_string_lossy().to_string()
+    }
 }
 
 /// Create core dump from Hypervisor context if the sandbox is configured to allow core dumps.
@@ -358,7 +370,7 @@ fn core_dump_file_path(dump_dir: Option<String>) -> String {
 ///
 /// Returns:
 /// * `Result<usize>`: The number of bytes written to the core dump file.
-fn checked_core_dump(
+pub(super) fn checked_core_dump(
     ctx: Option<CrashDumpContext>,
     get_writer: impl FnOnce() -> Result<Box<dyn Write>>,
 ) -> Result<usize> {
@@ -451,7 +463,7 @@ mod test {
         // Create a dummy context
         let ctx = CrashDumpContext::new(
             vec![],
-            [0; 27],
+            CoreRegisters::default(),
             vec![],
             0,
             Some("dummy_binary".to_string()),
@@ -483,7 +495,7 @@ mod test {
         // Create a dummy context
         let ctx = CrashDumpContext::new(
             regions,
-            [0; 27],
+            CoreRegisters::default(),
             vec![],
             0x1000,
             Some("dummy_binary".to_string()),
@@ -16,8 +16,10 @@ limitations under the License.
 
 //! This file contains architecture specific code for the x86_64
 
+use gdbstub::target::ext::breakpoints::WatchKind;
+
 use super::{DebugError, DebuggableVm, VcpuStopReason};
-use crate::hypervisor::regs::CommonRegisters;
+use crate::hypervisor::arch::GuestRegisters;
 use crate::hypervisor::virtual_machine::RegisterError;
 
 /// Errors that can occur when determining the vCPU stop reason
@@ -27,6 +29,8 @@ pub enum VcpuStopReasonError {
     GetRegs(#[from] RegisterError),
     #[error("Failed to remove hardware breakpoint: {0}")]
     RemoveHwBreakpoint(#[from] DebugError),
+    #[error("Failed to get hardware breakpoint registers: {0}")]
+    HwBreakpointRegs(DebugError),
 }
 
 // Described in Table 6-1. Exceptions and Interrupts at Page 6-13 Vol. 1
@@ -58,6 +62,176 @@ pub(crate) const DR6_HW_BP_FLAGS_POS: usize = 0;
 /// Bit mask of HW breakpoints status in DR6 debug register
 pub(crate) const DR6_HW_BP_FLAGS_MASK: u64 = 0x0F << DR6_HW_BP_FLAGS_POS;
 
+/// Check page 19-5 Vol. 3B of Intel 64 and IA-32
+/// Architectures Software Developer's Manual
+/// Bit position of the R/W0 field in DR7, each breakpoint uses 4 bits
+/// (R/Wn and LENn) starting from this position
+pub(crate) const DR7_COND_POS: usize = 16;
+/// Break on instruction execution
+const DR7_RW_EXECUTE: u64 = 0b00;
+/// Break on data writes
+const DR7_RW_WRITE: u64 = 0b01;
+/// Break on data reads or writes
+const DR7_RW_READ_WRITE: u64 = 0b11;
+
+/// Hardware breakpoint registers as programmed by the debugger
+/// DR0-DR3 hold the addresses and DR7 enables them and sets their conditions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct HwBreakpointRegs {
+    /// Addresses held by DR0-DR3
+    pub(crate) addrs: [u64; MAX_NO_OF_HW_BP],
+    /// Control register DR7
+    pub(crate) dr7: u64,
+}
+
+/// Condition of a hardware breakpoint: the R/Wn bits followed by the LENn bits
+type HwBreakpointCond = u64;
+
+impl HwBreakpointRegs {
+    fn is_enabled(&self, i: usize) -> bool {
+        self.dr7 & (1 << (i * 2)) != 0
+    }
+
+    fn cond(&self, i: usize) -> HwBreakpointCond {
+        (self.dr7 >> (DR7_COND_POS + i * 4)) & 0xF
+    }
+
+    fn position(&self, addr: u64, cond: HwBreakpointCond) -> Option<usize> {
+        (0..MAX_NO_OF_HW_BP)
+            .position(|i| self.is_enabled(i) && self.addrs[i] == addr && self.cond(i) == cond)
+    }
+
+    /// Programs a breakpoint for each of the (address, condition) pairs,
+    /// either all of them or none if there are not enough free slots.
+    /// Pairs that are already programmed are left untouched.
+    fn add(&mut self, bps: &[(u64, HwBreakpointCond)]) -> Result<(), DebugError> {
+        let mut regs = *self;
+        for &(addr, cond) in bps {
+            if regs.position(addr, cond).is_some() {
+                continue;
+            }
+
+            // Find the first available LOCAL (L0–L3) slot
+            let i = (0..MAX_NO_OF_HW_BP)
+                .position(|i| !regs.is_enabled(i))
+                .ok_or(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP))?;
+
+            regs.addrs[i] = addr;
+            let cond_pos = DR7_COND_POS + i * 4;
+            regs.dr7 &= !(0xF << cond_pos);
+            // Enable LOCAL bit and set the condition
+            regs.dr7 |= (1 << (i * 2)) | (cond << cond_pos);
+        }
+        *self = regs;
+
+        Ok(())
+    }
+
+    /// Removes the breakpoints for each of the (address, condition) pairs,
+    /// either all of them or none if any of them is not programmed
+    fn remove(&mut self, bps: &[(u64, HwBreakpointCond)]) -> Result<(), DebugError> {
+        let mut regs = *self;
+        for &(addr, cond) in bps {
+            let i = regs
+                .position(addr, cond)
+                .ok_or(DebugError::HwBreakpointNotFound(addr))?;
+
+            // Clear the address and disable LOCAL bit
+            regs.addrs[i] = 0;
+            regs.dr7 &= !((1 << (i * 2)) | (0xF << (DR7_COND_POS + i * 4)));
+        }
+        *self = regs;
+
+        Ok(())
+    }
+
+    /// Programs an instruction breakpoint at `addr`. Idempotent.
+    pub(crate) fn add_breakpoint(&mut self, addr: u64) -> Result<(), DebugError> {
+        self.add(&[(addr, DR7_RW_EXECUTE)])
+    }
+
+    /// Removes the instruction breakpoint at `addr`
+    pub(crate) fn remove_breakpoint(&mut self, addr: u64) -> Result<(), DebugError> {
+        self.remove(&[(addr, DR7_RW_EXECUTE)])
+    }
+
+    /// Programs a data watchpoint covering `len` bytes from `addr`.
+    /// Regions that cannot be covered by a single debug register are split
+    /// across several of them. Idempotent.
+    pub(crate) fn add_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchKind,
+    ) -> Result<(), DebugError> {
+        self.add(&watchpoint_conds(addr, len, kind)?)
+    }
+
+    /// Removes the data watchpoint covering `len` bytes from `addr`
+    pub(crate) fn remove_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchKind,
+    ) -> Result<(), DebugError> {
+        self.remove(&watchpoint_conds(addr, len, kind)?)
+    }
+
+    /// Returns the address and kind of the data watchpoint that triggered
+    /// according to the B0-B3 flags of `dr6`, if any
+    pub(crate) fn hit_watchpoint(&self, dr6: u64) -> Option<(u64, WatchKind)> {
+        (0..MAX_NO_OF_HW_BP)
+            .filter(|&i| dr6 & (1 << (DR6_HW_BP_FLAGS_POS + i)) != 0 && self.is_enabled(i))
+            .find_map(|i| match self.cond(i) & 0b11 {
+                DR7_RW_WRITE => Some((self.addrs[i], WatchKind::Write)),
+                DR7_RW_READ_WRITE => Some((self.addrs[i], WatchKind::ReadWrite)),
+                _ => None,
+            })
+    }
+}
+
+/// Splits the `len` bytes watched from `addr` into naturally aligned chunks
+/// of 1, 2, 4 or 8 bytes, as required by the debug registers, and returns
+/// the address and condition of each chunk
+fn watchpoint_conds(
+    addr: u64,
+    len: u64,
+    kind: WatchKind,
+) -> Result<Vec<(u64, HwBreakpointCond)>, DebugError> {
+    let rw = match kind {
+        WatchKind::Write => DR7_RW_WRITE,
+        WatchKind::ReadWrite => DR7_RW_READ_WRITE,
+        // x86 debug registers cannot break on data reads only
+        WatchKind::Read => return Err(DebugError::UnsupportedHwWatchpoint { addr, len }),
+    };
+    if len == 0 || addr.checked_add(len).is_none() {
+        return Err(DebugError::UnsupportedHwWatchpoint { addr, len });
+    }
+
+    let mut conds = Vec::new();
+    let (mut start, end) = (addr, addr + len);
+    while start < end {
+        let size = [8, 4, 2, 1]
+            .into_iter()
+            .find(|&size| start % size == 0 && start + size <= end)
+            .unwrap_or(1);
+        if conds.len() == MAX_NO_OF_HW_BP {
+            return Err(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP));
+        }
+        // LENn encoding: 00 - 1 byte, 01 - 2 bytes, 10 - 8 bytes, 11 - 4 bytes
+        let len_bits = match size {
+            1 => 0b00,
+            2 => 0b01,
+            8 => 0b10,
+            _ => 0b11,
+        };
+        conds.push((start, rw | (len_bits << 2)));
+        start += size;
+    }
+
+    Ok(conds)
+}
+
 /// Determine the reason the vCPU stopped
 /// This is done by checking the DR6 register and the exception id
 pub(crate) fn vcpu_stop_reason(
@@ -66,7 +240,7 @@ pub(crate) fn vcpu_stop_reason(
     entrypoint: u64,
     exception: u32,
 ) -> std::result::Result<VcpuStopReason, VcpuStopReasonError> {
-    let CommonRegisters { rip, .. } = vm.regs()?;
+    let rip = vm.regs()?.pc();
     if DB_EX_ID == exception {
         // If the BS flag in DR6 register is set, it means a single step
         // instruction triggered the exit
@@ -85,6 +259,12 @@ pub(crate) fn vcpu_stop_reason(
                 vm.remove_hw_breakpoint(entrypoint)?;
                 return Ok(VcpuStopReason::EntryPointBp);
             }
+            let hw_regs = vm
+                .hw_breakpoint_regs()
+                .map_err(VcpuStopReasonError::HwBreakpointRegs)?;
+            if let Some((addr, kind)) = hw_regs.hit_watchpoint(dr6) {
+                return Ok(VcpuStopReason::Watchpoint { addr, kind });
+            }
             return Ok(VcpuStopReason::HwBp);
         }
     }
@@ -109,3 +289,65 @@ pub(crate) fn vcpu_stop_reason(
 
     Ok(VcpuStopReason::Unknown)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breakpoints_and_watchpoints_share_slots() {
+        let mut regs = HwBreakpointRegs::default();
+
+        regs.add_breakpoint(0x1000).unwrap();
+        regs.add_breakpoint(0x1000).unwrap();
+        regs.add_watchpoint(0x2000, 4, WatchKind::Write).unwrap();
+        assert_eq!(regs.addrs, [0x1000, 0x2000, 0, 0]);
+        // L0 and L1 enabled, slot 1 breaks on 4 byte writes
+        assert_eq!(regs.dr7, 0b101 | (0b1101 << (DR7_COND_POS + 4)));
+
+        // An unaligned 8 byte region needs 3 slots, but only 2 are left
+        assert!(matches!(
+            regs.add_watchpoint(0x3002, 8, WatchKind::ReadWrite),
+            Err(DebugError::TooManyHwBreakpoints(_))
+        ));
+        assert_eq!(regs.addrs, [0x1000, 0x2000, 0, 0]);
+
+        // A breakpoint does not match a watchpoint at the same address
+        assert!(regs.remove_breakpoint(0x2000).is_err());
+        regs.remove_breakpoint(0x1000).unwrap();
+        regs.add_watchpoint(0x3002, 6, WatchKind::ReadWrite)
+            .unwrap();
+        assert_eq!(regs.addrs, [0x3002, 0x2000, 0x3004, 0]);
+
+        assert_eq!(
+            regs.hit_watchpoint(1 << 2),
+            Some((0x3004, WatchKind::ReadWrite))
+        );
+        assert_eq!(regs.hit_watchpoint(1 << 3), None);
+
+        regs.remove_watchpoint(0x3002, 6, WatchKind::ReadWrite)
+            .unwrap();
+        regs.remove_watchpoint(0x2000, 4, WatchKind::Write).unwrap();
+        assert_eq!(regs, HwBreakpointRegs::default());
+    }
+
+    #[test]
+    fn unsupported_watchpoints() {
+        let mut regs = HwBreakpointRegs::default();
+        for (addr, len, kind) in [
+            (0x1000, 4, WatchKind::Read),
+            (0x1000, 0, WatchKind::Write),
+            (u64::MAX, 2, WatchKind::Write),
+        ] {
+            assert!(matches!(
+                regs.add_watchpoint(addr, len, kind),
+                Err(DebugError::UnsupportedHwWatchpoint { .. })
+            ));
+        }
+        assert!(matches!(
+            regs.add_watchpoint(0x1001, 32, WatchKind::Write),
+            Err(DebugError::TooManyHwBreakpoints(_))
+        ));
+        assert_eq!(regs, HwBreakpointRegs::default());
+    }
+}
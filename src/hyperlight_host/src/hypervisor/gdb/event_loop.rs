@@ -19,6 +19,7 @@ use gdbstub::conn::ConnectionExt;
 use gdbstub::stub::{
     BaseStopReason, DisconnectReason, GdbStub, SingleThreadStopReason, run_blocking,
 };
+use gdbstub::target::ext::base::reverse_exec::ReplayLogPosition;
 
 use super::x86_64_target::HyperlightSandboxTarget;
 use super::{DebugResponse, GdbTargetError, VcpuStopReason};
@@ -62,6 +63,16 @@ impl run_blocking::BlockingEventLoop for GdbBlockingEventLoop {
                         VcpuStopReason::EntryPointBp => BaseStopReason::HwBreak(()),
                         VcpuStopReason::SwBp => BaseStopReason::SwBreak(()),
                         VcpuStopReason::HwBp => BaseStopReason::HwBreak(()),
+                        VcpuStopReason::Watchpoint { addr, kind } => BaseStopReason::Watch {
+                            tid: (),
+                            kind,
+                            addr,
+                        },
+                        // Reverse execution went back as far as the recorded history allows
+                        VcpuStopReason::HistoryExhausted => BaseStopReason::ReplayLog {
+                            tid: None,
+                            pos: ReplayLogPosition::Begin,
+                        },
                         // This is a consequence of the GDB client sending an interrupt signal
                         // to the target thread
                         VcpuStopReason::Interrupt => BaseStopReason::SignalWithThread {
@@ -148,6 +159,10 @@ pub(crate) fn event_loop_thread(
         },
         Err(e) => {
             log::error!("fatal error encountered: {e:?}");
+            // Let the guest continue without the debugger, as if it disconnected
+            if let Err(e) = target.disable_debug() {
+                log::error!("Cannot disable debugging: {:?}", e);
+            }
         }
     }
 }
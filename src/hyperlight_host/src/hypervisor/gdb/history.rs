@@ -0,0 +1,399 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Bounded history of the sandbox state at each debugger stop, used to
+//! implement reverse execution (`reverse-stepi` / `reverse-continue`).
+//!
+//! The history is only kept when reverse execution is enabled with
+//! [`DebugInfo::reverse_execution_stops`](crate::sandbox::config::DebugInfo::reverse_execution_stops).
+//! Like a [`SnapshotChain`](crate::sandbox::snapshot_chain::SnapshotChain),
+//! it holds a single copy of the memory, that of the current stop, and
+//! each stop only stores the pages that changed since the previous one.
+//!
+//! The guest never writes to the snapshot region, so only the pages the
+//! host wrote since the previous stop are compared there, as tracked by
+//! the shared memory for snapshot restores. The guest writes to the
+//! scratch region are not tracked, so each of its pages is compared in
+//! turn with the copy of the previous stop.
+
+use std::collections::{HashMap, VecDeque};
+
+use hyperlight_common::mem::PAGE_SIZE_USIZE;
+
+use super::VcpuStopReason;
+use crate::Result;
+use crate::hypervisor::regs::{CommonFpu, CommonRegisters, CommonSpecialRegisters};
+use crate::mem::shared_mem::{HostSharedMemory, SharedMemory};
+
+/// State of the vCPU captured when it stopped in the debugger
+pub(crate) struct DebugCheckpoint {
+    /// General purpose registers
+    pub(crate) regs: CommonRegisters,
+    /// FPU/SSE registers
+    pub(crate) fpu: CommonFpu,
+    /// Special registers
+    pub(crate) sregs: CommonSpecialRegisters,
+    /// Software breakpoints set at the time of the checkpoint
+    /// (addr -> original instruction)
+    pub(crate) sw_breakpoints: HashMap<u64, u8>,
+    /// Reason the vCPU stopped when the checkpoint was taken
+    pub(crate) stop_reason: VcpuStopReason,
+}
+
+impl DebugCheckpoint {
+    /// Whether the vCPU stopped because it hit a breakpoint or a watchpoint
+    fn at_breakpoint(&self) -> bool {
+        matches!(
+            self.stop_reason,
+            VcpuStopReason::SwBp
+                | VcpuStopReason::HwBp
+                | VcpuStopReason::EntryPointBp
+                | VcpuStopReason::Watchpoint { .. }
+        )
+    }
+}
+
+/// The pages of a memory that changed from one stop to the next, with
+/// their contents at the earlier stop
+#[derive(Default)]
+struct PageUndo {
+    /// The index of each page, and its contents at the earlier stop
+    pages: Vec<(usize, Box<[u8]>)>,
+}
+
+/// A memory of the sandbox as it was at the current stop
+#[derive(Default)]
+struct RecordedMemory {
+    current: Vec<u8>,
+    /// The pages of `current` gone back to an earlier stop, which are
+    /// yet to be written back to the memory of the sandbox
+    restored: Vec<usize>,
+    /// The buffer a page of the memory of the sandbox is read into,
+    /// kept across stops
+    page: Vec<u8>,
+    /// The buffer the pages written by the host are taken into, kept
+    /// across stops
+    dirty: Vec<usize>,
+}
+
+impl RecordedMemory {
+    /// Records the contents of `memory` at a new stop, returning what
+    /// goes back to the previous one. Only the pages written by the host
+    /// since the previous stop are compared, unless `guest_writable`.
+    fn record(&mut self, memory: &HostSharedMemory, guest_writable: bool) -> Result<PageUndo> {
+        let mut dirty = std::mem::take(&mut self.dirty);
+        dirty.clear();
+        memory.take_debug_dirty_pages(&mut dirty);
+        // The pages gone back to are compared again in case they were not
+        // written back
+        dirty.append(&mut self.restored);
+
+        let mut undo = PageUndo::default();
+        let res = if self.current.len() != memory.mem_size() {
+            // The first stop, or the memory was replaced by one of
+            // another size, so there is nothing to compare with
+            self.current.resize(memory.mem_size(), 0);
+            memory.copy_to_slice(&mut self.current, 0)
+        } else if guest_writable {
+            (0..self.current.len().div_ceil(PAGE_SIZE_USIZE))
+                .try_for_each(|page| self.record_page(memory, page, &mut undo))
+        } else {
+            dirty
+                .iter()
+                .try_for_each(|&page| self.record_page(memory, page, &mut undo))
+        };
+        self.dirty = dirty;
+        res.map(|_| undo)
+    }
+
+    /// Copies the page `page` of `memory` to `current` if it changed,
+    /// adding its contents at the previous stop to `undo`
+    fn record_page(
+        &mut self,
+        memory: &HostSharedMemory,
+        page: usize,
+        undo: &mut PageUndo,
+    ) -> Result<()> {
+        let start = page * PAGE_SIZE_USIZE;
+        let end = (start + PAGE_SIZE_USIZE).min(self.current.len());
+        self.page.resize(PAGE_SIZE_USIZE, 0);
+        let new = &mut self.page[..end - start];
+        memory.copy_to_slice(new, start)?;
+        let old = &mut self.current[start..end];
+        if old != new {
+            undo.pages.push((page, Box::from(&*old)));
+            old.copy_from_slice(new);
+        }
+        Ok(())
+    }
+
+    /// Goes back to the contents at the previous stop
+    fn undo(&mut self, undo: PageUndo) {
+        for (page, contents) in undo.pages {
+            let start = page * PAGE_SIZE_USIZE;
+            self.current[start..start + contents.len()].copy_from_slice(&contents);
+            self.restored.push(page);
+        }
+    }
+
+    /// Writes to `memory` the pages gone back to an earlier stop, along
+    /// with the pages the host wrote since the current stop was recorded,
+    /// such as the breakpoints set by the debugger
+    fn write_back(&mut self, memory: &HostSharedMemory) -> Result<()> {
+        let mut pages = std::mem::take(&mut self.restored);
+        memory.take_debug_dirty_pages(&mut pages);
+        if self.current.len() != memory.mem_size() {
+            // Nothing was recorded from this memory
+            pages.clear();
+            self.restored = pages;
+            return Ok(());
+        }
+        pages.sort_unstable();
+        pages.dedup();
+        let res = pages.iter().try_for_each(|&page| {
+            let start = page * PAGE_SIZE_USIZE;
+            let end = (start + PAGE_SIZE_USIZE).min(self.current.len());
+            memory.copy_from_slice(&self.current[start..end], start)
+        });
+        pages.clear();
+        self.restored = pages;
+        res
+    }
+}
+
+/// A checkpoint along with what goes back from its memory to that of the
+/// checkpoint before it
+struct HistoryEntry {
+    checkpoint: DebugCheckpoint,
+    snapshot_undo: PageUndo,
+    scratch_undo: PageUndo,
+}
+
+/// Ring of checkpoints taken at each debugger stop, oldest first.
+/// The last checkpoint always corresponds to the current stop.
+pub(crate) struct DebugHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    /// Writes the memory of the current stop back to `snapshot_mem` and
+    /// `scratch_mem`, after going back to it. Only the pages that differ
+    /// from the stop gone back from are written.
+    pub(crate) fn write_back(
+        &mut self,
+        snapshot_mem: &HostSharedMemory,
+        scratch_mem: &HostSharedMemory,
+    ) -> Result<()> {
+        self.snapshot_mem.write_back(snapshot_mem)?;
+        self.scratch_mem.write_back(scratch_mem)
+    }
+
+    /// Removes all checkpoints. The copy of the memory is kept, to only
+    /// compare the pages that changed at the next stop.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// The checkpoint of the current stop
+    pub(crate) fn current(&self) -> Option<&DebugCheckpoint> {
+        self.entries.back().map(|entry| &entry.checkpoint)
+    }
+
+    fn drop_current(&mut self) {
+        if let Some(entry) = self.entries.pop_back() {
+            self.snapshot_mem.undo(entry.snapshot_undo);
+            self.scratch_mem.undo(entry.scratch_undo);
+        }
+    }
+
+    /// Number of checkpoints in the history
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Number of pages stored to go back to the earlier stops
+    #[cfg(test)]
+    fn stored_pages(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| entry.snapshot_undo.pages.len() + entry.scratch_undo.pages.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::shared_mem::ExclusiveSharedMemory;
+
+    /// The number of stops kept by the tests that do not test eviction
+    const STOPS: usize = 16;
+
+    fn checkpoint(rip: u64, stop_reason: VcpuStopReason) -> DebugCheckpoint {
+        DebugCheckpoint {
+            regs: CommonRegisters {
+                rip,
+                ..Default::default()
+            },
+            fpu: CommonFpu::default(),
+            sregs: CommonSpecialRegisters::default(),
+            sw_breakpoints: HashMap::new(),
+            stop_reason,
+        }
+    }
+
+    fn memory(pages: usize) -> HostSharedMemory {
+        ExclusiveSharedMemory::new(pages * PAGE_SIZE_USIZE)
+            .unwrap()
+            .build()
+            .0
+    }
+
+    fn push_to(
+        history: &mut DebugHistory,
+        rip: u64,
+        snapshot: &HostSharedMemory,
+        scratch: &HostSharedMemory,
+    ) {
+        history
+            .push(checkpoint(rip, VcpuStopReason::DoneStep), snapshot, scratch)
+            .unwrap();
+    }
+
+    fn push(history: &mut DebugHistory, rip: u64, stop_reason: VcpuStopReason) {
+        let (snapshot, scratch) = (memory(1), memory(1));
+        history
+            .push(checkpoint(rip, stop_reason), &snapshot, &scratch)
+            .unwrap();
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut history = DebugHistory::new(3);
+        for rip in 0..5 {
+            push(&mut history, rip, VcpuStopReason::DoneStep);
+        }
+        assert_eq!(history.len(), 3);
+
+        // Current stop is 4, so stepping back returns 3
+        assert_eq!(history.step_back().unwrap().regs.rip, 3);
+        assert_eq!(history.step_back().unwrap().regs.rip, 2);
+
+        // The oldest checkpoints were evicted
+        assert!(history.step_back().is_none());
+    }
+
+    #[test]
+    fn step_back_needs_previous_stop() {
+        let mut history = DebugHistory::new(STOPS);
+        assert!(history.step_back().is_none());
+
+        push(&mut history, 0, VcpuStopReason::EntryPointBp);
+        assert!(history.step_back().is_none());
+
+        history.clear();
+        assert_eq!(history.len(), 0);
+    }
+
+    #[test]
+    fn rewind_stops_at_previous_breakpoint() {
+        let mut history = DebugHistory::new(STOPS);
+        push(&mut history, 0, VcpuStopReason::EntryPointBp);
+        push(&mut history, 1, VcpuStopReason::DoneStep);
+        push(&mut history, 2, VcpuStopReason::SwBp);
+        push(&mut history, 3, VcpuStopReason::DoneStep);
+        push(&mut history, 4, VcpuStopReason::DoneStep);
+
+        assert_eq!(history.rewind_to_breakpoint().unwrap().regs.rip, 2);
+        assert_eq!(history.rewind_to_breakpoint().unwrap().regs.rip, 0);
+        assert!(history.rewind_to_breakpoint().is_none());
+    }
+
+    #[test]
+    fn rewind_falls_back_to_oldest_stop() {
+        let mut history = DebugHistory::new(2);
+        push(&mut history, 0, VcpuStopReason::EntryPointBp);
+        push(&mut history, 1, VcpuStopReason::DoneStep);
+        push(&mut history, 2, VcpuStopReason::DoneStep);
+
+        // The entry point stop was evicted, so the oldest stop is returned
+        let cp = history.rewind_to_breakpoint().unwrap();
+        assert_eq!(cp.regs.rip, 1);
+        assert!(!cp.at_breakpoint());
+    }
+
+    #[test]
+    fn stops_only_store_changed_pages() {
+        let mut history = DebugHistory::new(STOPS);
+        let snapshot = memory(4);
+        let scratch = memory(2);
+        history
+            .push(
+                checkpoint(0, VcpuStopReason::EntryPointBp),
+                &snapshot,
+                &scratch,
+            )
+            .unwrap();
+        assert_eq!(history.stored_pages(), 0);
+
+        snapshot.write::<u8>(PAGE_SIZE_USIZE, 1).unwrap();
+        history
+            .push(checkpoint(1, VcpuStopReason::DoneStep), &snapshot, &scratch)
+            .unwrap();
+        assert_eq!(history.stored_pages(), 1);
+
+        snapshot.write::<u8>(3 * PAGE_SIZE_USIZE, 2).unwrap();
+        scratch.write::<u8>(PAGE_SIZE_USIZE + 5, 3).unwrap();
+        history
+            .push(checkpoint(2, VcpuStopReason::DoneStep), &snapshot, &scratch)
+            .unwrap();
+        assert_eq!(history.stored_pages(), 3);
+
+        history.step_back().unwrap();
+        history.write_back(&snapshot, &scratch).unwrap();
+        assert_eq!(snapshot.read::<u8>(3 * PAGE_SIZE_USIZE).unwrap(), 0);
+        assert_eq!(snapshot.read::<u8>(PAGE_SIZE_USIZE).unwrap(), 1);
+        assert_eq!(scratch.read::<u8>(PAGE_SIZE_USIZE + 5).unwrap(), 0);
+
+        history.step_back().unwrap();
+        history.write_back(&snapshot, &scratch).unwrap();
+        assert_eq!(snapshot.read::<u8>(PAGE_SIZE_USIZE).unwrap(), 0);
+    }
+
+    #[test]
+    fn write_back_undoes_host_writes_since_the_stop() {
+        let mut history = DebugHistory::new(STOPS);
+        let snapshot = memory(2);
+        let scratch = memory(1);
+        push_to(&mut history, 0, &snapshot, &scratch);
+        push_to(&mut history, 1, &snapshot, &scratch);
+
+        // Written after the current stop was recorded, as the debugger
+        // does when setting a breakpoint
+        snapshot.write::<u8>(PAGE_SIZE_USIZE, 0xcc).unwrap();
+        history.step_back().unwrap();
+        history.write_back(&snapshot, &scratch).unwrap();
+        assert_eq!(snapshot.read::<u8>(PAGE_SIZE_USIZE).unwrap(), 0);
+    }
+
+    #[test]
+    fn disabled_history_records_nothing() {
+        let mut history = DebugHistory::new(0);
+        assert!(!history.is_enabled());
+        push(&mut history, 0, VcpuStopReason::EntryPointBp);
+        assert_eq!(history.len(), 0);
+        assert_eq!(history.stored_pages(), 0);
+    }
+}
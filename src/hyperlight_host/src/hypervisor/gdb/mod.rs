@@ -16,30 +16,35 @@ limitations under the License.
 
 pub(crate) mod arch;
 mod event_loop;
+pub(crate) mod history;
+pub(crate) mod monitor;
 mod x86_64_target;
 
 use std::io::{self, ErrorKind};
-use std::net::TcpListener;
+use std::net::{IpAddr, SocketAddr, TcpListener};
 use std::sync::{Arc, Mutex};
 use std::{slice, thread};
 
+use arch::HwBreakpointRegs;
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use event_loop::event_loop_thread;
 use gdbstub::conn::ConnectionExt;
 use gdbstub::stub::GdbStub;
 use gdbstub::target::TargetError;
+use gdbstub::target::ext::breakpoints::WatchKind;
 use thiserror::Error;
 use x86_64_target::HyperlightSandboxTarget;
 
 use super::InterruptHandle;
-use super::regs::CommonRegisters;
+use super::regs::{CommonRegisters, CommonSpecialRegisters};
 use crate::HyperlightError;
 use crate::hypervisor::regs::CommonFpu;
 use crate::hypervisor::virtual_machine::{HypervisorError, RegisterError, VirtualMachine};
 use crate::mem::layout::SandboxMemoryLayout;
-use crate::mem::memory_region::MemoryRegion;
+use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::{HostSharedMemory, SharedMemory};
+use crate::sandbox::config::DebugInfo;
 
 #[derive(Debug, Error)]
 pub enum GdbTargetError {
@@ -95,6 +100,25 @@ pub enum DebugMemoryAccessError {
     LockFailed(&'static str, u32, String),
     #[error("Failed to translate guest address {0:#x}")]
     TranslateGuestAddress(u64),
+    #[error("Access of {len} bytes at guest address {gpa:#x} is out of the mapped region")]
+    OutOfBounds { gpa: u64, len: usize },
+    #[error("Guest address {0:#x} is mapped read-only")]
+    ReadOnly(u64),
+}
+
+impl DebugMemoryAccessError {
+    /// Whether the error is caused by the address the debugger asked for,
+    /// in which case it is reported back to the debugger instead of
+    /// failing the guest
+    pub(crate) fn is_bad_address(&self) -> bool {
+        matches!(
+            self,
+            Self::CopyFailed(_)
+                | Self::TranslateGuestAddress(_)
+                | Self::OutOfBounds { .. }
+                | Self::ReadOnly(_)
+        )
+    }
 }
 
 impl DebugMemoryAccess {
@@ -107,6 +131,88 @@ impl DebugMemoryAccess {
     // relatively large volumes of data, so it's not clear if it's
     // terribly easy to combine them
 
+    /// Looks for the mapped memory region containing `gpa` and returns a
+    /// pointer to the host memory backing it.
+    ///
+    /// Returns `None` if `gpa` is not in a mapped region, and an error if
+    /// the `len` bytes accessed don't all fit in the region, or if the
+    /// region is not writable and `write` is set.
+    fn mapped_region_ptr(
+        &self,
+        gpa: u64,
+        len: usize,
+        write: bool,
+    ) -> std::result::Result<Option<*mut u8>, DebugMemoryAccessError> {
+        let gpa_usize = gpa as usize;
+        let Some(reg) = self
+            .guest_mmap_regions
+            .iter()
+            .find(|reg| reg.guest_region.contains(&gpa_usize))
+        else {
+            return Ok(None);
+        };
+        log::debug!("Found mapped region containing {:X}: {:#?}", gpa, reg);
+
+        // Calculate the offset within the region and make sure the whole
+        // access stays within it
+        let region_offset = gpa_usize - reg.guest_region.start;
+        if region_offset
+            .checked_add(len)
+            .is_none_or(|end| end > reg.guest_region.len())
+        {
+            log::warn!(
+                "Access of {:#X} bytes at {:#X} is out of the mapped region {:#X?}",
+                len,
+                gpa,
+                reg.guest_region
+            );
+            return Err(DebugMemoryAccessError::OutOfBounds { gpa, len });
+        }
+        if write && !reg.flags.contains(MemoryRegionFlags::WRITE) {
+            return Err(DebugMemoryAccessError::ReadOnly(gpa));
+        }
+
+        let host_start_ptr = <_ as Into<usize>>::into(reg.host_region.start);
+        Ok(Some((host_start_ptr + region_offset) as *mut u8))
+    }
+
+    /// Returns the sandbox memory (snapshot or scratch) containing `gpa`
+    /// and the offset of `gpa` in it
+    fn sandbox_memory_offset<'a>(
+        mgr: &'a mut SandboxMemoryManager<HostSharedMemory>,
+        gpa: u64,
+    ) -> std::result::Result<(&'a mut HostSharedMemory, usize), DebugMemoryAccessError> {
+        let scratch_base = hyperlight_common::layout::scratch_base_gpa(mgr.scratch_mem.mem_size());
+        let (mem, offset, name): (&mut HostSharedMemory, _, _) = if gpa >= scratch_base {
+            (
+                &mut mgr.scratch_mem,
+                (gpa - scratch_base) as usize,
+                "scratch",
+            )
+        } else {
+            let mem_offset = (gpa as usize)
+                .checked_sub(SandboxMemoryLayout::BASE_ADDRESS)
+                .ok_or_else(|| {
+                    log::warn!(
+                        "gpa={:#X} causes subtract with underflow: \"gpa - BASE_ADDRESS={:#X}-{:#X}\"",
+                        gpa,
+                        gpa,
+                        SandboxMemoryLayout::BASE_ADDRESS
+                    );
+                    DebugMemoryAccessError::TranslateGuestAddress(gpa)
+                })?;
+            (&mut mgr.shared_mem, mem_offset, "snapshot")
+        };
+        log::debug!(
+            "No mapped region found containing {:X}. Trying {} memory at offset {:X} ...",
+            gpa,
+            name,
+            offset
+        );
+
+        Ok((mem, offset))
+    }
+
     /// Reads memory from the guest's address space with a maximum length of a PAGE_SIZE
     ///
     /// # Arguments
@@ -120,74 +226,23 @@ impl DebugMemoryAccess {
         data: &mut [u8],
         gpa: u64,
     ) -> std::result::Result<(), DebugMemoryAccessError> {
-        let read_len = data.len();
-
-        let mem_offset = (gpa as usize)
-            .checked_sub(SandboxMemoryLayout::BASE_ADDRESS)
-            .ok_or_else(|| {
-                log::warn!(
-                    "gpa={:#X} causes subtract with underflow: \"gpa - BASE_ADDRESS={:#X}-{:#X}\"",
-                    gpa,
-                    gpa,
-                    SandboxMemoryLayout::BASE_ADDRESS
-                );
-                DebugMemoryAccessError::TranslateGuestAddress(gpa)
-            })?;
-
         // First check the mapped memory regions to see if the address is within any of them
-        let mut region_found = false;
-        for reg in self.guest_mmap_regions.iter() {
-            if reg.guest_region.contains(&mem_offset) {
-                log::debug!("Found mapped region containing {:X}: {:#?}", gpa, reg);
+        if let Some(ptr) = self.mapped_region_ptr(gpa, data.len(), false)? {
+            // SAFETY: the whole range was checked to be within the mapped region
+            let bytes: &[u8] = unsafe { slice::from_raw_parts(ptr, data.len()) };
+            data.copy_from_slice(bytes);
 
-                // Region found - calculate the offset within the region
-                let region_offset = mem_offset.checked_sub(reg.guest_region.start).ok_or_else(|| {
-                    log::warn!(
-                        "Cannot calculate offset in memory region: mem_offset={:#X}, base={:#X}",
-                        mem_offset,
-                        reg.guest_region.start,
-                    );
-                    DebugMemoryAccessError::TranslateGuestAddress(mem_offset as u64)
-                })?;
-
-                let host_start_ptr = <_ as Into<usize>>::into(reg.host_region.start);
-                let bytes: &[u8] = unsafe {
-                    slice::from_raw_parts(host_start_ptr as *const u8, reg.guest_region.len())
-                };
-                data[..read_len].copy_from_slice(&bytes[region_offset..region_offset + read_len]);
-
-                region_found = true;
-                break;
-            }
-        }
-
-        if !region_found {
-            let mut mgr = self
-                .dbg_mem_access_fn
-                .try_lock()
-                .map_err(|e| DebugMemoryAccessError::LockFailed(file!(), line!(), e.to_string()))?;
-            let scratch_base =
-                hyperlight_common::layout::scratch_base_gpa(mgr.scratch_mem.mem_size());
-            let (mem, offset, name): (&mut HostSharedMemory, _, _) = if gpa >= scratch_base {
-                (
-                    &mut mgr.scratch_mem,
-                    (gpa - scratch_base) as usize,
-                    "scratch",
-                )
-            } else {
-                (&mut mgr.shared_mem, mem_offset, "snapshot")
-            };
-            log::debug!(
-                "No mapped region found containing {:X}. Trying {} memory at offset {:X} ...",
-                gpa,
-                name,
-                offset
-            );
-            mem.copy_to_slice(&mut data[..read_len], offset)
-                .map_err(|e| DebugMemoryAccessError::CopyFailed(Box::new(e)))?;
+            return Ok(());
         }
 
-        Ok(())
+        let mut mgr = self
+            .dbg_mem_access_fn
+            .try_lock()
+            .map_err(|e| DebugMemoryAccessError::LockFailed(file!(), line!(), e.to_string()))?;
+        // The shared memory checks the bounds of the copy
+        let (mem, offset) = Self::sandbox_memory_offset(&mut mgr, gpa)?;
+        mem.copy_to_slice(data, offset)
+            .map_err(|e| DebugMemoryAccessError::CopyFailed(Box::new(e)))
     }
 
     /// Writes memory from the guest's address space with a maximum length of a PAGE_SIZE
@@ -203,79 +258,29 @@ impl DebugMemoryAccess {
         data: &[u8],
         gpa: u64,
     ) -> std::result::Result<(), DebugMemoryAccessError> {
-        let write_len = data.len();
-
-        let mem_offset = (gpa as usize)
-            .checked_sub(SandboxMemoryLayout::BASE_ADDRESS)
-            .ok_or_else(|| {
-                log::warn!(
-                    "gpa={:#X} causes subtract with underflow: \"gpa - BASE_ADDRESS={:#X}-{:#X}\"",
-                    gpa,
-                    gpa,
-                    SandboxMemoryLayout::BASE_ADDRESS
-                );
-                DebugMemoryAccessError::TranslateGuestAddress(gpa)
-            })?;
-
         // First check the mapped memory regions to see if the address is within any of them
-        let mut region_found = false;
-        for reg in self.guest_mmap_regions.iter() {
-            if reg.guest_region.contains(&mem_offset) {
-                log::debug!("Found mapped region containing {:X}: {:#?}", gpa, reg);
-
-                // Region found - calculate the offset within the region
-                let region_offset = mem_offset.checked_sub(reg.guest_region.start).ok_or_else(|| {
-                    log::warn!(
-                        "Cannot calculate offset in memory region: mem_offset={:#X}, base={:#X}",
-                        mem_offset,
-                        reg.guest_region.start,
-                    );
-                    DebugMemoryAccessError::TranslateGuestAddress(mem_offset as u64)
-                })?;
-
-                let host_start_ptr = <_ as Into<usize>>::into(reg.host_region.start);
-                let bytes: &mut [u8] = unsafe {
-                    slice::from_raw_parts_mut(host_start_ptr as *mut u8, reg.guest_region.len())
-                };
-                bytes[region_offset..region_offset + write_len].copy_from_slice(&data[..write_len]);
-
-                region_found = true;
-                break;
-            }
-        }
+        if let Some(ptr) = self.mapped_region_ptr(gpa, data.len(), true)? {
+            // SAFETY: the whole range was checked to be within the mapped
+            // region, which is writable
+            let bytes: &mut [u8] = unsafe { slice::from_raw_parts_mut(ptr, data.len()) };
+            bytes.copy_from_slice(data);
 
-        if !region_found {
-            let mut mgr = self
-                .dbg_mem_access_fn
-                .try_lock()
-                .map_err(|e| DebugMemoryAccessError::LockFailed(file!(), line!(), e.to_string()))?;
-            let scratch_base =
-                hyperlight_common::layout::scratch_base_gpa(mgr.scratch_mem.mem_size());
-            let (mem, offset, name): (&mut HostSharedMemory, _, _) = if gpa >= scratch_base {
-                (
-                    &mut mgr.scratch_mem,
-                    (gpa - scratch_base) as usize,
-                    "scratch",
-                )
-            } else {
-                (&mut mgr.shared_mem, mem_offset, "snapshot")
-            };
-            log::debug!(
-                "No mapped region found containing {:X}. Trying {} memory at offset {:X} ...",
-                gpa,
-                name,
-                offset
-            );
-            mem.copy_from_slice(&data[..write_len], offset)
-                .map_err(|e| DebugMemoryAccessError::CopyFailed(Box::new(e)))?;
+            return Ok(());
         }
 
-        Ok(())
+        let mut mgr = self
+            .dbg_mem_access_fn
+            .try_lock()
+            .map_err(|e| DebugMemoryAccessError::LockFailed(file!(), line!(), e.to_string()))?;
+        // The shared memory checks the bounds of the copy
+        let (mem, offset) = Self::sandbox_memory_offset(&mut mgr, gpa)?;
+        mem.copy_from_slice(data, offset)
+            .map_err(|e| DebugMemoryAccessError::CopyFailed(Box::new(e)))
     }
 }
 
 /// Defines the possible reasons for which a vCPU can be stopped when debugging
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum VcpuStopReason {
     Crash,
     DoneStep,
@@ -283,44 +288,70 @@ pub enum VcpuStopReason {
     /// at the entry point. This is used to avoid the guest from executing
     /// the entry point code before the debugger is connected
     EntryPointBp,
+    /// Reverse execution reached the oldest recorded stop
+    HistoryExhausted,
     HwBp,
     SwBp,
     Interrupt,
+    /// Hardware watchpoint triggered by an access to the watched address
+    Watchpoint {
+        addr: u64,
+        kind: WatchKind,
+    },
     Unknown,
 }
 
+/// The vCPU state exposed to the debugger through the register packets
+pub(crate) type VcpuRegisters = (CommonRegisters, CommonFpu, CommonSpecialRegisters);
+
 /// Enumerates the possible actions that a debugger can ask from a Hypervisor
 #[derive(Debug)]
 pub(crate) enum DebugMsg {
     AddHwBreakpoint(u64),
+    AddHwWatchpoint(u64, u64, WatchKind),
     AddSwBreakpoint(u64),
     Continue,
     DisableDebug,
     GetCodeSectionOffset,
+    GetEntryPoint,
+    GetExecFile,
+    MonitorCommand(String),
     ReadAddr(u64, usize),
+    RangeStep(u64, u64),
     ReadRegisters,
     RemoveHwBreakpoint(u64),
+    RemoveHwWatchpoint(u64, u64, WatchKind),
     RemoveSwBreakpoint(u64),
+    ReverseContinue,
+    ReverseStep,
     Step,
     WriteAddr(u64, Vec<u8>),
-    WriteRegisters(Box<(CommonRegisters, CommonFpu)>),
+    WriteRegisters(Box<VcpuRegisters>),
 }
 
 /// Enumerates the possible responses that a hypervisor can provide to a debugger
 #[derive(Debug)]
 pub(crate) enum DebugResponse {
     AddHwBreakpoint(bool),
+    AddHwWatchpoint(bool),
     AddSwBreakpoint(bool),
     Continue,
     DisableDebug,
     ErrorOccurred,
     GetCodeSectionOffset(u64),
+    GetEntryPoint(u64),
+    GetExecFile(Option<String>),
+    MonitorCommand(String),
     NotAllowed,
     InterruptHandle(Arc<dyn InterruptHandle>),
+    RangeStep,
     ReadAddr(Vec<u8>),
-    ReadRegisters(Box<(CommonRegisters, CommonFpu)>),
+    ReadRegisters(Box<VcpuRegisters>),
     RemoveHwBreakpoint(bool),
+    RemoveHwWatchpoint(bool),
     RemoveSwBreakpoint(bool),
+    ReverseContinue(VcpuStopReason),
+    ReverseStep(VcpuStopReason),
     Step,
     VcpuStopped(VcpuStopReason),
     WriteAddr,
@@ -343,6 +374,8 @@ pub enum DebugError {
     TooManyHwBreakpoints(usize),
     #[error("Translation of guest virtual address failed: {0}")]
     TranslateGva(u64),
+    #[error("Unsupported hardware watchpoint of {len} bytes at address {addr:#x}")]
+    UnsupportedHwWatchpoint { addr: u64, len: u64 },
 }
 
 /// Trait for VMs that support debugging capabilities.
@@ -357,12 +390,54 @@ pub(crate) trait DebuggableVm: VirtualMachine {
     /// Enable/disable single stepping
     fn set_single_step(&mut self, enable: bool) -> std::result::Result<(), DebugError>;
 
+    /// Get the debug registers used for hardware breakpoints and watchpoints
+    fn hw_breakpoint_regs(&self) -> std::result::Result<HwBreakpointRegs, DebugError>;
+
+    /// Program the debug registers used for hardware breakpoints and watchpoints
+    fn set_hw_breakpoint_regs(
+        &mut self,
+        regs: &HwBreakpointRegs,
+    ) -> std::result::Result<(), DebugError>;
+
     /// Add a hardware breakpoint at the given address.
     /// Must be idempotent.
-    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError>;
+    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
+        let mut regs = self.hw_breakpoint_regs()?;
+        regs.add_breakpoint(addr)?;
+        self.set_hw_breakpoint_regs(&regs)
+    }
 
     /// Remove a hardware breakpoint at the given address
-    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError>;
+    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
+        let mut regs = self.hw_breakpoint_regs()?;
+        regs.remove_breakpoint(addr)?;
+        self.set_hw_breakpoint_regs(&regs)
+    }
+
+    /// Add a hardware watchpoint covering `len` bytes from the given address.
+    /// Must be idempotent.
+    fn add_hw_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchKind,
+    ) -> std::result::Result<(), DebugError> {
+        let mut regs = self.hw_breakpoint_regs()?;
+        regs.add_watchpoint(addr, len, kind)?;
+        self.set_hw_breakpoint_regs(&regs)
+    }
+
+    /// Remove the hardware watchpoint covering `len` bytes from the given address
+    fn remove_hw_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchKind,
+    ) -> std::result::Result<(), DebugError> {
+        let mut regs = self.hw_breakpoint_regs()?;
+        regs.remove_watchpoint(addr, len, kind)?;
+        self.set_hw_breakpoint_regs(&regs)
+    }
 }
 
 /// Debug communication channel that is used for sending a request type and
@@ -408,15 +483,40 @@ impl<T, U> DebugCommChannel<T, U> {
     }
 }
 
+/// Where the gdb thread listens for a gdb client
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GdbConfig {
+    /// Address to bind to
+    pub(crate) addr: IpAddr,
+    /// Port to bind to, `0` lets the OS pick a free port
+    pub(crate) port: u16,
+    /// Whether the stops are recorded for reverse execution
+    pub(crate) reverse_execution: bool,
+}
+
+impl From<DebugInfo> for GdbConfig {
+    fn from(debug_info: DebugInfo) -> Self {
+        Self {
+            addr: debug_info.addr,
+            port: debug_info.port,
+            reverse_execution: debug_info.reverse_execution_stops > 0,
+        }
+    }
+}
+
 /// Creates a thread that handles gdb protocol
+///
+/// Returns the communication channel with the thread, along with the
+/// port it actually listens on, which differs from the configured one
+/// when that is `0`.
 pub(crate) fn create_gdb_thread(
-    port: u16,
-) -> Result<DebugCommChannel<DebugResponse, DebugMsg>, GdbTargetError> {
+    config: GdbConfig,
+) -> Result<(DebugCommChannel<DebugResponse, DebugMsg>, u16), GdbTargetError> {
     let (gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
-    let socket = format!("localhost:{}", port);
 
+    let listener = TcpListener::bind(SocketAddr::new(config.addr, config.port))?;
+    let socket = listener.local_addr()?;
     log::info!("Listening on {:?}", socket);
-    let listener = TcpListener::bind(socket)?;
 
     log::info!("Starting GDB thread");
     let _handle = thread::Builder::new()
@@ -429,6 +529,7 @@ pub(crate) fn create_gdb_thread(
             let debugger = GdbStub::new(conn);
 
             let mut target = HyperlightSandboxTarget::new(hyp_conn);
+            target.set_reverse_execution(config.reverse_execution);
 
             // Waits for vCPU to stop at entrypoint breakpoint
             let msg = target.recv()?;
@@ -450,7 +551,7 @@ pub(crate) fn create_gdb_thread(
             Ok(())
         });
 
-    Ok(gdb_conn)
+    Ok((gdb_conn, socket.port()))
 }
 
 #[cfg(test)]
@@ -471,16 +572,36 @@ mod tests {
         let res = gdb_conn.try_recv();
         assert!(res.is_err());
 
-        let res = hyp_conn.send(DebugResponse::ReadRegisters(Box::new((
-            Default::default(),
-            Default::default(),
-        ))));
+        let res = hyp_conn.send(DebugResponse::ReadRegisters(Box::default()));
         assert!(res.is_ok());
 
         let res = gdb_conn.recv();
         assert!(res.is_ok());
     }
 
+    #[test]
+    fn test_gdb_thread_any_port() {
+        let config = GdbConfig {
+            addr: std::net::Ipv4Addr::LOCALHOST.into(),
+            port: 0,
+            reverse_execution: false,
+        };
+
+        // Two sandboxes can be debugged at once when the OS picks the ports
+        let (_conn1, port1) = create_gdb_thread(config).unwrap();
+        let (_conn2, port2) = create_gdb_thread(config).unwrap();
+        assert_ne!(port1, 0);
+        assert_ne!(port2, 0);
+        assert_ne!(port1, port2);
+
+        // The port that was picked is actually in use
+        let res = create_gdb_thread(GdbConfig {
+            port: port1,
+            ..config
+        });
+        assert!(matches!(res, Err(GdbTargetError::CannotBind)));
+    }
+
     #[cfg(target_os = "linux")]
     mod mem_access_tests {
         use std::os::fd::AsRawFd;
@@ -537,7 +658,9 @@ mod tests {
                 guest_mmap_regions: vec![MemoryRegion {
                     host_region: mapped_mem as usize..mapped_mem.wrapping_add(size) as usize,
                     guest_region: BASE_VIRT..BASE_VIRT + size,
-                    flags: MemoryRegionFlags::READ | MemoryRegionFlags::EXECUTE,
+                    flags: MemoryRegionFlags::READ
+                        | MemoryRegionFlags::WRITE
+                        | MemoryRegionFlags::EXECUTE,
                     region_type: MemoryRegionType::Heap,
                 }],
             };
@@ -663,5 +786,47 @@ mod tests {
 
             Ok(())
         }
+
+        #[test]
+        fn test_mem_access_out_of_bounds() -> crate::Result<()> {
+            let mut mem_access = get_mem_access()?;
+            let size = mem_access.guest_mmap_regions[0].guest_region.len();
+
+            // An access that starts in the mapped region but ends past it
+            let mut read_data = [0u8; 16];
+            let err = mem_access
+                .read(&mut read_data, (BASE_VIRT + size - 8) as u64)
+                .unwrap_err();
+            assert!(matches!(err, DebugMemoryAccessError::OutOfBounds { .. }));
+            assert!(err.is_bad_address());
+
+            let write_data = [0xAAu8; 16];
+            let err = mem_access
+                .write(&write_data, (BASE_VIRT + size - 8) as u64)
+                .unwrap_err();
+            assert!(matches!(err, DebugMemoryAccessError::OutOfBounds { .. }));
+
+            // Memory outside of any mapped region or sandbox memory
+            let err = mem_access
+                .read(&mut read_data, (BASE_VIRT + size + 0x1000) as u64)
+                .unwrap_err();
+            assert!(err.is_bad_address());
+
+            // A read-only region cannot be written
+            mem_access.guest_mmap_regions[0].flags =
+                MemoryRegionFlags::READ | MemoryRegionFlags::EXECUTE;
+            let err = mem_access
+                .write(&write_data, (BASE_VIRT + 8) as u64)
+                .unwrap_err();
+            assert!(matches!(err, DebugMemoryAccessError::ReadOnly(_)));
+            {
+                let slice = unsafe { get_mmap_slice(&mut mem_access) };
+                assert_ne!(slice[8..24], write_data);
+            }
+
+            drop_mem_access(mem_access);
+
+            Ok(())
+        }
     }
 }
@@ -0,0 +1,161 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Write};
+use std::sync::Arc;
+
+use crate::{Result, new_error};
+
+/// Handles a monitor command registered by the host, given the arguments
+/// that follow the name of the command, and returns its output
+pub(crate) type MonitorCommandHandler = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// The monitor commands answered by the VM itself, with their help text
+pub(crate) const BUILTIN_COMMANDS: &[(&str, &str)] = &[
+    ("help", "List the available commands"),
+    (
+        "layout",
+        "Print the memory layout of the sandbox and the regions mapped into it",
+    ),
+    (
+        "snapshot",
+        "Print where the snapshot and scratch memory are mapped in the guest",
+    ),
+    (
+        "trace-flush",
+        "Ask the guest to send its buffered trace data once it resumes",
+    ),
+];
+
+/// The monitor commands registered by the host for a sandbox, which gdb
+/// sends through `monitor <command> [args]` (the `qRcmd` packet)
+#[derive(Clone, Default)]
+pub(crate) struct MonitorCommands {
+    commands: BTreeMap<String, (String, MonitorCommandHandler)>,
+}
+
+impl Debug for MonitorCommands {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.commands.keys()).finish()
+    }
+}
+
+impl MonitorCommands {
+    /// Registers a command, whose name must not be taken already
+    pub(crate) fn register(
+        &mut self,
+        name: String,
+        help: String,
+        handler: MonitorCommandHandler,
+    ) -> Result<()> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(new_error!("Invalid monitor command name {:?}", name));
+        }
+        if BUILTIN_COMMANDS.iter().any(|(builtin, _)| *builtin == name)
+            || self.commands.contains_key(&name)
+        {
+            return Err(new_error!("Monitor command {} is already registered", name));
+        }
+        self.commands.insert(name, (help, handler));
+        Ok(())
+    }
+
+    /// Runs the registered command `name`, if there is one
+    pub(crate) fn run(&self, name: &str, args: &str) -> Option<String> {
+        self.commands.get(name).map(|(_, handler)| handler(args))
+    }
+
+    /// Lists the built-in and registered commands with their help text
+    pub(crate) fn help(&self) -> String {
+        let commands = BUILTIN_COMMANDS.iter().copied().chain(
+            self.commands
+                .iter()
+                .map(|(name, (help, _))| (name.as_str(), help.as_str())),
+        );
+
+        let mut out = String::from("Hyperlight monitor commands:\n");
+        for (name, help) in commands {
+            let _ = writeln!(out, "  {:<12} {}", name, help);
+        }
+        out
+    }
+}
+
+/// Splits a monitor command into its name and its arguments
+pub(crate) fn split_command(cmd: &str) -> (&str, &str) {
+    let cmd = cmd.trim();
+    match cmd.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim_start()),
+        None => (cmd, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{MonitorCommands, split_command};
+
+    #[test]
+    fn registry() {
+        let mut commands = MonitorCommands::default();
+        commands
+            .register(
+                "echo".to_string(),
+                "Print the arguments".to_string(),
+                Arc::new(|args: &str| args.to_string()),
+            )
+            .unwrap();
+
+        assert!(
+            commands
+                .register(
+                    "layout".to_string(),
+                    String::new(),
+                    Arc::new(|_: &str| String::new())
+                )
+                .is_err()
+        );
+        assert!(
+            commands
+                .register(
+                    "echo".to_string(),
+                    String::new(),
+                    Arc::new(|_: &str| String::new())
+                )
+                .is_err()
+        );
+        assert!(
+            commands
+                .register(
+                    "two words".to_string(),
+                    String::new(),
+                    Arc::new(|_: &str| String::new())
+                )
+                .is_err()
+        );
+
+        let (name, args) = split_command("  echo  hello world ");
+        assert_eq!((name, args), ("echo", "hello world"));
+        assert_eq!(commands.run(name, args).as_deref(), Some("hello world"));
+        assert_eq!(commands.run("unknown", ""), None);
+
+        let help = commands.help();
+        assert!(help.contains("trace-flush"));
+        assert!(help.contains("echo         Print the arguments"));
+    }
+}
@@ -18,22 +18,41 @@ use std::sync::Arc;
 
 use crossbeam_channel::TryRecvError;
 use gdbstub::arch::Arch;
-use gdbstub::common::Signal;
+use gdbstub::common::{Pid, Signal};
+use gdbstub::target::ext::auxv::{Auxv, AuxvOps};
 use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::base::reverse_exec::{
+    ReverseCont, ReverseContOps, ReverseStep, ReverseStepOps,
+};
+use gdbstub::target::ext::base::single_register_access::{
+    SingleRegisterAccess, SingleRegisterAccessOps,
+};
 use gdbstub::target::ext::base::singlethread::{
-    SingleThreadBase, SingleThreadResume, SingleThreadResumeOps, SingleThreadSingleStep,
-    SingleThreadSingleStepOps,
+    SingleThreadBase, SingleThreadRangeStepping, SingleThreadRangeSteppingOps, SingleThreadResume,
+    SingleThreadResumeOps, SingleThreadSingleStep, SingleThreadSingleStepOps,
 };
 use gdbstub::target::ext::breakpoints::{
-    Breakpoints, BreakpointsOps, HwBreakpoint, HwBreakpointOps, SwBreakpoint, SwBreakpointOps,
+    Breakpoints, BreakpointsOps, HwBreakpoint, HwBreakpointOps, HwWatchpoint, HwWatchpointOps,
+    SwBreakpoint, SwBreakpointOps, WatchKind,
 };
+use gdbstub::target::ext::exec_file::{ExecFile, ExecFileOps};
+use gdbstub::target::ext::monitor_cmd::{ConsoleOutput, MonitorCmd, MonitorCmdOps};
 use gdbstub::target::ext::section_offsets::{Offsets, SectionOffsets};
 use gdbstub::target::{Target, TargetError, TargetResult};
 use gdbstub_arch::x86::X86_64_SSE as GdbTargetArch;
+use gdbstub_arch::x86::reg::X86_64CoreRegs;
+use gdbstub_arch::x86::reg::id::{X86_64CoreRegId, X86SegmentRegId, X87FpuInternalRegId};
 
-use super::{DebugCommChannel, DebugMsg, DebugResponse, GdbTargetError};
+use super::{DebugCommChannel, DebugMsg, DebugResponse, GdbTargetError, VcpuRegisters};
 use crate::hypervisor::InterruptHandle;
-use crate::hypervisor::regs::{CommonFpu, CommonRegisters};
+use crate::hypervisor::regs::CommonRegisters;
+
+/// Identifies the entry point of the program in the auxiliary vector,
+/// which gdb compares with the entry point of the binary to compute the
+/// load offset of a PIE binary
+const AT_ENTRY: u64 = 9;
+/// Marks the end of the auxiliary vector
+const AT_NULL: u64 = 0;
 
 /// Gdbstub target used by the gdbstub crate to provide GDB protocol implementation
 pub(crate) struct HyperlightSandboxTarget {
@@ -41,6 +60,8 @@ pub(crate) struct HyperlightSandboxTarget {
     hyp_conn: DebugCommChannel<DebugMsg, DebugResponse>,
     /// Interrupt handle for the vCPU thread
     interrupt_handle: Option<Arc<dyn InterruptHandle>>,
+    /// Whether the vCPU records its stops for reverse execution
+    reverse_execution: bool,
 }
 
 impl HyperlightSandboxTarget {
@@ -48,6 +69,7 @@ impl HyperlightSandboxTarget {
         HyperlightSandboxTarget {
             hyp_conn,
             interrupt_handle: None,
+            reverse_execution: false,
         }
     }
 
@@ -69,6 +91,12 @@ impl HyperlightSandboxTarget {
         self.interrupt_handle = Some(handle);
     }
 
+    /// Set whether the vCPU records its stops for reverse execution, which
+    /// is only offered to gdb if it does
+    pub(crate) fn set_reverse_execution(&mut self, enabled: bool) {
+        self.reverse_execution = enabled;
+    }
+
     /// Waits for a response over the communication channel
     pub(crate) fn recv(&self) -> Result<DebugResponse, GdbTargetError> {
         self.hyp_conn.recv()
@@ -118,10 +146,53 @@ impl HyperlightSandboxTarget {
         }
     }
 
-    /// Interrupts the vCPU execution
+    /// Reads the registers of the vCPU
+    fn read_vcpu_registers(&mut self) -> TargetResult<Box<VcpuRegisters>, Self> {
+        match self.send_command(DebugMsg::ReadRegisters)? {
+            DebugResponse::ReadRegisters(vcpu_regs) => Ok(vcpu_regs),
+            DebugResponse::ErrorOccurred => {
+                log::error!("Error occurred");
+                Err(TargetError::NonFatal)
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
+            }
+        }
+    }
+
+    /// Writes the registers of the vCPU, which take effect when it resumes
+    fn write_vcpu_registers(&mut self, vcpu_regs: Box<VcpuRegisters>) -> TargetResult<(), Self> {
+        match self.send_command(DebugMsg::WriteRegisters(vcpu_regs))? {
+            DebugResponse::WriteRegisters => Ok(()),
+            DebugResponse::NotAllowed => {
+                log::error!("Action not allowed at this time, crash might have occurred");
+                // This is a consequence of the target crashing or being in an invalid state
+                // we cannot continue execution, but we can still read registers and memory
+                Ok(())
+            }
+            DebugResponse::ErrorOccurred => {
+                log::error!("Error occurred");
+                Err(TargetError::NonFatal)
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
+            }
+        }
+    }
+
+    /// Interrupts the vCPU execution.
+    /// If the vCPU is not running, for example during a host function call,
+    /// it stops as soon as it runs again.
+    /// Returns false if the interrupt could not be requested.
     pub(crate) fn interrupt_vcpu(&mut self) -> bool {
         if let Some(handle) = &self.interrupt_handle {
-            handle.kill_from_debugger()
+            if !handle.kill_from_debugger() {
+                log::info!("vCPU is not running, it will stop the next time it runs");
+            }
+
+            true
         } else {
             log::warn!("No interrupt handle set, cannot interrupt vCPU");
 
@@ -148,6 +219,18 @@ impl Target for HyperlightSandboxTarget {
     ) -> Option<gdbstub::target::ext::section_offsets::SectionOffsetsOps<'_, Self>> {
         Some(self)
     }
+
+    fn support_exec_file(&mut self) -> Option<ExecFileOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_auxv(&mut self) -> Option<AuxvOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_monitor_cmd(&mut self) -> Option<MonitorCmdOps<'_, Self>> {
+        Some(self)
+    }
 }
 
 impl SingleThreadBase for HyperlightSandboxTarget {
@@ -208,43 +291,10 @@ impl SingleThreadBase for HyperlightSandboxTarget {
     ) -> TargetResult<(), Self> {
         log::debug!("Read regs");
 
-        match self.send_command(DebugMsg::ReadRegisters)? {
-            DebugResponse::ReadRegisters(boxed_regs) => {
-                let (read_regs, read_fpu) = boxed_regs.as_ref();
-                regs.regs[0] = read_regs.rax;
-                regs.regs[1] = read_regs.rbp;
-                regs.regs[2] = read_regs.rcx;
-                regs.regs[3] = read_regs.rdx;
-                regs.regs[4] = read_regs.rsi;
-                regs.regs[5] = read_regs.rdi;
-                regs.regs[6] = read_regs.rbp;
-                regs.regs[7] = read_regs.rsp;
-                regs.regs[8] = read_regs.r8;
-                regs.regs[9] = read_regs.r9;
-                regs.regs[10] = read_regs.r10;
-                regs.regs[11] = read_regs.r11;
-                regs.regs[12] = read_regs.r12;
-                regs.regs[13] = read_regs.r13;
-                regs.regs[14] = read_regs.r14;
-                regs.regs[15] = read_regs.r15;
-                regs.rip = read_regs.rip;
-                regs.eflags = read_regs.rflags as u32;
-
-                regs.xmm = read_fpu.xmm.map(u128::from_le_bytes);
-                regs.mxcsr = read_fpu.mxcsr;
-
-                Ok(())
-            }
-            DebugResponse::ErrorOccurred => {
-                log::error!("Error occurred");
-                Err(TargetError::NonFatal)
-            }
+        let vcpu_regs = self.read_vcpu_registers()?;
+        vcpu_regs_to_gdb(&vcpu_regs, regs);
 
-            msg => {
-                log::error!("Unexpected message received: {:?}", msg);
-                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
-            }
-        }
+        Ok(())
     }
 
     fn write_registers(
@@ -253,58 +303,16 @@ impl SingleThreadBase for HyperlightSandboxTarget {
     ) -> TargetResult<(), Self> {
         log::debug!("Write regs");
 
-        let common_regs = CommonRegisters {
-            rax: regs.regs[0],
-            rbx: regs.regs[1],
-            rcx: regs.regs[2],
-            rdx: regs.regs[3],
-            rsi: regs.regs[4],
-            rdi: regs.regs[5],
-            rbp: regs.regs[6],
-            rsp: regs.regs[7],
-            r8: regs.regs[8],
-            r9: regs.regs[9],
-            r10: regs.regs[10],
-            r11: regs.regs[11],
-            r12: regs.regs[12],
-            r13: regs.regs[13],
-            r14: regs.regs[14],
-            r15: regs.regs[15],
-            rip: regs.rip,
-            rflags: u64::from(regs.eflags),
-        };
-
-        let mut xmm = [[0u8; 16]; 16];
-        for (i, &reg) in regs.xmm.iter().enumerate() {
-            xmm[i] = reg.to_le_bytes();
-        }
+        // Start from the current state so the vCPU state gdb doesn't know
+        // about is kept as is
+        let mut vcpu_regs = self.read_vcpu_registers()?;
+        gdb_to_vcpu_regs(regs, &mut vcpu_regs);
 
-        let common_fpu = CommonFpu {
-            xmm,
-            mxcsr: regs.mxcsr,
-            ..Default::default()
-        };
+        self.write_vcpu_registers(vcpu_regs)
+    }
 
-        match self.send_command(DebugMsg::WriteRegisters(Box::new((
-            common_regs,
-            common_fpu,
-        ))))? {
-            DebugResponse::WriteRegisters => Ok(()),
-            DebugResponse::NotAllowed => {
-                log::error!("Action not allowed at this time, crash might have occurred");
-                // This is a consequence of the target crashing or being in an invalid state
-                // we cannot continue execution, but we can still read registers and memory
-                Ok(())
-            }
-            DebugResponse::ErrorOccurred => {
-                log::error!("Error occurred");
-                Err(TargetError::NonFatal)
-            }
-            msg => {
-                log::error!("Unexpected message received: {:?}", msg);
-                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
-            }
-        }
+    fn support_single_register_access(&mut self) -> Option<SingleRegisterAccessOps<'_, (), Self>> {
+        Some(self)
     }
 
     fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
@@ -312,6 +320,48 @@ impl SingleThreadBase for HyperlightSandboxTarget {
     }
 }
 
+impl SingleRegisterAccess<()> for HyperlightSandboxTarget {
+    fn read_register(
+        &mut self,
+        _tid: (),
+        reg_id: X86_64CoreRegId,
+        buf: &mut [u8],
+    ) -> TargetResult<usize, Self> {
+        log::debug!("Read reg {:?}", reg_id);
+
+        let mut regs = X86_64CoreRegs::default();
+        vcpu_regs_to_gdb(&self.read_vcpu_registers()?, &mut regs);
+
+        let bytes = gdb_reg_bytes(&mut regs, reg_id).ok_or(TargetError::NonFatal)?;
+        let len = bytes.len().min(buf.len());
+        buf[..len].copy_from_slice(&bytes[..len]);
+
+        Ok(len)
+    }
+
+    fn write_register(
+        &mut self,
+        _tid: (),
+        reg_id: X86_64CoreRegId,
+        val: &[u8],
+    ) -> TargetResult<(), Self> {
+        log::debug!("Write reg {:?}", reg_id);
+
+        let mut vcpu_regs = self.read_vcpu_registers()?;
+        let mut regs = X86_64CoreRegs::default();
+        vcpu_regs_to_gdb(&vcpu_regs, &mut regs);
+
+        let bytes = gdb_reg_bytes(&mut regs, reg_id).ok_or(TargetError::NonFatal)?;
+        if val.len() != bytes.len() {
+            return Err(TargetError::NonFatal);
+        }
+        bytes.copy_from_slice(val);
+
+        gdb_to_vcpu_regs(&regs, &mut vcpu_regs);
+        self.write_vcpu_registers(vcpu_regs)
+    }
+}
+
 impl SectionOffsets for HyperlightSandboxTarget {
     fn get_section_offsets(&mut self) -> Result<Offsets<<Self::Arch as Arch>::Usize>, Self::Error> {
         log::debug!("Get section offsets");
@@ -333,6 +383,86 @@ impl SectionOffsets for HyperlightSandboxTarget {
     }
 }
 
+impl ExecFile for HyperlightSandboxTarget {
+    fn get_exec_file(
+        &self,
+        _pid: Option<Pid>,
+        offset: u64,
+        length: usize,
+        buf: &mut [u8],
+    ) -> TargetResult<usize, Self> {
+        log::debug!("Get exec file");
+
+        match self.send_command(DebugMsg::GetExecFile)? {
+            DebugResponse::GetExecFile(Some(path)) => {
+                Ok(copy_range_to_buf(path.as_bytes(), offset, length, buf))
+            }
+            // The guest binary was loaded from a buffer, so there is no
+            // file gdb could load the symbols from
+            DebugResponse::GetExecFile(None) => Err(TargetError::NonFatal),
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
+            }
+        }
+    }
+}
+
+impl Auxv for HyperlightSandboxTarget {
+    fn get_auxv(&self, offset: u64, length: usize, buf: &mut [u8]) -> TargetResult<usize, Self> {
+        log::debug!("Get auxv");
+
+        match self.send_command(DebugMsg::GetEntryPoint)? {
+            DebugResponse::GetEntryPoint(entry) => {
+                let auxv: Vec<u8> = [AT_ENTRY, entry, AT_NULL, 0]
+                    .iter()
+                    .flat_map(|v| v.to_le_bytes())
+                    .collect();
+
+                Ok(copy_range_to_buf(&auxv, offset, length, buf))
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
+            }
+        }
+    }
+}
+
+impl MonitorCmd for HyperlightSandboxTarget {
+    fn handle_monitor_cmd(
+        &mut self,
+        cmd: &[u8],
+        mut out: ConsoleOutput<'_>,
+    ) -> Result<(), Self::Error> {
+        let cmd = String::from_utf8_lossy(cmd).into_owned();
+        log::debug!("Monitor command {:?}", cmd);
+
+        match self.send_command(DebugMsg::MonitorCommand(cmd))? {
+            DebugResponse::MonitorCommand(output) => {
+                out.write_raw(output.as_bytes());
+                Ok(())
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(GdbTargetError::UnexpectedMessage)
+            }
+        }
+    }
+}
+
+/// Copies the `length` bytes of `data` starting at `offset` to `buf`, as
+/// requested by the `qXfer` packets, returning how many bytes were copied
+fn copy_range_to_buf(data: &[u8], offset: u64, length: usize, buf: &mut [u8]) -> usize {
+    let start = usize::try_from(offset)
+        .unwrap_or(usize::MAX)
+        .min(data.len());
+    let end = start.saturating_add(length.min(buf.len())).min(data.len());
+    let len = end - start;
+    buf[..len].copy_from_slice(&data[start..end]);
+    len
+}
+
 impl Breakpoints for HyperlightSandboxTarget {
     fn support_hw_breakpoint(&mut self) -> Option<HwBreakpointOps<'_, Self>> {
         Some(self)
@@ -340,6 +470,9 @@ impl Breakpoints for HyperlightSandboxTarget {
     fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
         Some(self)
     }
+    fn support_hw_watchpoint(&mut self) -> Option<HwWatchpointOps<'_, Self>> {
+        Some(self)
+    }
 }
 
 impl HwBreakpoint for HyperlightSandboxTarget {
@@ -396,6 +529,72 @@ impl HwBreakpoint for HyperlightSandboxTarget {
     }
 }
 
+impl HwWatchpoint for HyperlightSandboxTarget {
+    fn add_hw_watchpoint(
+        &mut self,
+        addr: <Self::Arch as Arch>::Usize,
+        len: <Self::Arch as Arch>::Usize,
+        kind: WatchKind,
+    ) -> TargetResult<bool, Self> {
+        log::debug!(
+            "Add hw watchpoint at address {:X} len: {:X} kind: {:?}",
+            addr,
+            len,
+            kind
+        );
+
+        match self.send_command(DebugMsg::AddHwWatchpoint(addr, len, kind))? {
+            DebugResponse::AddHwWatchpoint(rsp) => Ok(rsp),
+            DebugResponse::NotAllowed => {
+                log::error!("Action not allowed at this time, crash might have occurred");
+                // This is a consequence of the target crashing or being in an invalid state
+                // we cannot continue execution, but we can still read registers and memory
+                Err(TargetError::NonFatal)
+            }
+            DebugResponse::ErrorOccurred => {
+                log::error!("Error occurred");
+                Err(TargetError::NonFatal)
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
+            }
+        }
+    }
+
+    fn remove_hw_watchpoint(
+        &mut self,
+        addr: <Self::Arch as Arch>::Usize,
+        len: <Self::Arch as Arch>::Usize,
+        kind: WatchKind,
+    ) -> TargetResult<bool, Self> {
+        log::debug!(
+            "Remove hw watchpoint at address {:X} len: {:X} kind: {:?}",
+            addr,
+            len,
+            kind
+        );
+
+        match self.send_command(DebugMsg::RemoveHwWatchpoint(addr, len, kind))? {
+            DebugResponse::RemoveHwWatchpoint(rsp) => Ok(rsp),
+            DebugResponse::NotAllowed => {
+                log::error!("Action not allowed at this time, crash might have occurred");
+                // This is a consequence of the target crashing or being in an invalid state
+                // we cannot continue execution, but we can still read registers and memory
+                Err(TargetError::NonFatal)
+            }
+            DebugResponse::ErrorOccurred => {
+                log::error!("Error occurred");
+                Err(TargetError::NonFatal)
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
+            }
+        }
+    }
+}
+
 impl SwBreakpoint for HyperlightSandboxTarget {
     fn add_sw_breakpoint(
         &mut self,
@@ -460,6 +659,23 @@ impl SingleThreadResume for HyperlightSandboxTarget {
     fn support_single_step(&mut self) -> Option<SingleThreadSingleStepOps<'_, Self>> {
         Some(self)
     }
+    fn support_range_step(&mut self) -> Option<SingleThreadRangeSteppingOps<'_, Self>> {
+        Some(self)
+    }
+    fn support_reverse_step(&mut self) -> Option<ReverseStepOps<'_, (), Self>> {
+        if self.reverse_execution {
+            Some(self)
+        } else {
+            None
+        }
+    }
+    fn support_reverse_cont(&mut self) -> Option<ReverseContOps<'_, (), Self>> {
+        if self.reverse_execution {
+            Some(self)
+        } else {
+            None
+        }
+    }
 }
 
 impl SingleThreadSingleStep for HyperlightSandboxTarget {
@@ -487,11 +703,238 @@ impl SingleThreadSingleStep for HyperlightSandboxTarget {
     }
 }
 
+impl SingleThreadRangeStepping for HyperlightSandboxTarget {
+    /// Steps the vCPU execution until it leaves the `start..end` range
+    fn resume_range_step(
+        &mut self,
+        start: <Self::Arch as Arch>::Usize,
+        end: <Self::Arch as Arch>::Usize,
+    ) -> Result<(), Self::Error> {
+        log::debug!("Range step {:X}..{:X}", start, end);
+        match self.send_command(DebugMsg::RangeStep(start, end))? {
+            DebugResponse::RangeStep => Ok(()),
+            DebugResponse::ErrorOccurred => {
+                log::error!("Error occurred");
+                Err(GdbTargetError::UnexpectedError)
+            }
+            DebugResponse::NotAllowed => {
+                log::error!("Action not allowed at this time, crash might have occurred");
+                // This is a consequence of the target crashing or being in an invalid state
+                // we cannot continue execution, but we can still read registers and memory
+                Ok(())
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(GdbTargetError::UnexpectedMessage)
+            }
+        }
+    }
+}
+
+impl ReverseStep<()> for HyperlightSandboxTarget {
+    /// Restores the vCPU to the state it had at the previous stop
+    fn reverse_step(&mut self, _tid: ()) -> Result<(), Self::Error> {
+        log::debug!("Reverse step");
+        match self.send_command(DebugMsg::ReverseStep)? {
+            DebugResponse::ReverseStep(_) => Ok(()),
+            DebugResponse::NotAllowed => {
+                log::error!("Action not allowed at this time, crash might have occurred");
+                // This is a consequence of the target crashing or being in an invalid state
+                // we cannot continue execution, but we can still read registers and memory
+                Ok(())
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(GdbTargetError::UnexpectedMessage)
+            }
+        }
+    }
+}
+
+impl ReverseCont<()> for HyperlightSandboxTarget {
+    /// Restores the vCPU to the state it had at the previous breakpoint hit
+    fn reverse_cont(&mut self) -> Result<(), Self::Error> {
+        log::debug!("Reverse continue");
+        match self.send_command(DebugMsg::ReverseContinue)? {
+            DebugResponse::ReverseContinue(_) => Ok(()),
+            DebugResponse::NotAllowed => {
+                log::error!("Action not allowed at this time, crash might have occurred");
+                // This is a consequence of the target crashing or being in an invalid state
+                // we cannot continue execution, but we can still read registers and memory
+                Ok(())
+            }
+            msg => {
+                log::error!("Unexpected message received: {:?}", msg);
+                Err(GdbTargetError::UnexpectedMessage)
+            }
+        }
+    }
+}
+
+/// Returns the little-endian bytes of the register `reg_id` in `regs`
+fn gdb_reg_bytes(regs: &mut X86_64CoreRegs, reg_id: X86_64CoreRegId) -> Option<&mut [u8]> {
+    fn bytes_of<T: Copy>(val: &mut T) -> &mut [u8] {
+        // SAFETY: only used with integers and byte arrays, which have no
+        // padding and for which any bit pattern is valid
+        unsafe { std::slice::from_raw_parts_mut(val as *mut T as *mut u8, size_of::<T>()) }
+    }
+
+    let fpu = &mut regs.fpu;
+    let segments = &mut regs.segments;
+    let bytes = match reg_id {
+        X86_64CoreRegId::Gpr(i) => bytes_of(regs.regs.get_mut(i as usize)?),
+        X86_64CoreRegId::Rip => bytes_of(&mut regs.rip),
+        X86_64CoreRegId::Eflags => bytes_of(&mut regs.eflags),
+        X86_64CoreRegId::Segment(seg) => bytes_of(match seg {
+            X86SegmentRegId::CS => &mut segments.cs,
+            X86SegmentRegId::SS => &mut segments.ss,
+            X86SegmentRegId::DS => &mut segments.ds,
+            X86SegmentRegId::ES => &mut segments.es,
+            X86SegmentRegId::FS => &mut segments.fs,
+            X86SegmentRegId::GS => &mut segments.gs,
+        }),
+        X86_64CoreRegId::St(i) => bytes_of(regs.st.get_mut(i as usize)?),
+        X86_64CoreRegId::Fpu(reg) => bytes_of(match reg {
+            X87FpuInternalRegId::Fctrl => &mut fpu.fctrl,
+            X87FpuInternalRegId::Fstat => &mut fpu.fstat,
+            X87FpuInternalRegId::Ftag => &mut fpu.ftag,
+            X87FpuInternalRegId::Fiseg => &mut fpu.fiseg,
+            X87FpuInternalRegId::Fioff => &mut fpu.fioff,
+            X87FpuInternalRegId::Foseg => &mut fpu.foseg,
+            X87FpuInternalRegId::Fooff => &mut fpu.fooff,
+            X87FpuInternalRegId::Fop => &mut fpu.fop,
+        }),
+        X86_64CoreRegId::Xmm(i) => bytes_of(regs.xmm.get_mut(i as usize)?),
+        X86_64CoreRegId::Mxcsr => bytes_of(&mut regs.mxcsr),
+        _ => return None,
+    };
+
+    // The registers are stored in the host's byte order, which is the
+    // little-endian order gdb expects on x86_64
+    Some(bytes)
+}
+
+/// Fills the gdb registers with the state of the vCPU
+fn vcpu_regs_to_gdb((regs, fpu, sregs): &VcpuRegisters, gdb_regs: &mut X86_64CoreRegs) {
+    gdb_regs.regs = [
+        regs.rax, regs.rbx, regs.rcx, regs.rdx, regs.rsi, regs.rdi, regs.rbp, regs.rsp, regs.r8,
+        regs.r9, regs.r10, regs.r11, regs.r12, regs.r13, regs.r14, regs.r15,
+    ];
+    gdb_regs.rip = regs.rip;
+    gdb_regs.eflags = regs.rflags as u32;
+
+    gdb_regs.segments.cs = sregs.cs.selector.into();
+    gdb_regs.segments.ss = sregs.ss.selector.into();
+    gdb_regs.segments.ds = sregs.ds.selector.into();
+    gdb_regs.segments.es = sregs.es.selector.into();
+    gdb_regs.segments.fs = sregs.fs.selector.into();
+    gdb_regs.segments.gs = sregs.gs.selector.into();
+
+    for (st, fpr) in gdb_regs.st.iter_mut().zip(fpu.fpr.iter()) {
+        st.copy_from_slice(&fpr[..st.len()]);
+    }
+    gdb_regs.fpu.fctrl = fpu.fcw.into();
+    gdb_regs.fpu.fstat = fpu.fsw.into();
+    gdb_regs.fpu.ftag = full_tag_word(fpu.ftwx).into();
+    // In 64-bit mode the segment fields hold the upper half of the
+    // instruction and operand pointers
+    gdb_regs.fpu.fiseg = (fpu.last_ip >> 32) as u32;
+    gdb_regs.fpu.fioff = fpu.last_ip as u32;
+    gdb_regs.fpu.foseg = (fpu.last_dp >> 32) as u32;
+    gdb_regs.fpu.fooff = fpu.last_dp as u32;
+    gdb_regs.fpu.fop = fpu.last_opcode.into();
+
+    gdb_regs.xmm = fpu.xmm.map(u128::from_le_bytes);
+    gdb_regs.mxcsr = fpu.mxcsr;
+}
+
+/// Applies the gdb registers to the state of the vCPU
+fn gdb_to_vcpu_regs(gdb_regs: &X86_64CoreRegs, (regs, fpu, sregs): &mut VcpuRegisters) {
+    let [
+        rax,
+        rbx,
+        rcx,
+        rdx,
+        rsi,
+        rdi,
+        rbp,
+        rsp,
+        r8,
+        r9,
+        r10,
+        r11,
+        r12,
+        r13,
+        r14,
+        r15,
+    ] = gdb_regs.regs;
+    *regs = CommonRegisters {
+        rax,
+        rbx,
+        rcx,
+        rdx,
+        rsi,
+        rdi,
+        rbp,
+        rsp,
+        r8,
+        r9,
+        r10,
+        r11,
+        r12,
+        r13,
+        r14,
+        r15,
+        rip: gdb_regs.rip,
+        rflags: u64::from(gdb_regs.eflags),
+    };
+
+    sregs.cs.selector = gdb_regs.segments.cs as u16;
+    sregs.ss.selector = gdb_regs.segments.ss as u16;
+    sregs.ds.selector = gdb_regs.segments.ds as u16;
+    sregs.es.selector = gdb_regs.segments.es as u16;
+    sregs.fs.selector = gdb_regs.segments.fs as u16;
+    sregs.gs.selector = gdb_regs.segments.gs as u16;
+
+    for (fpr, st) in fpu.fpr.iter_mut().zip(gdb_regs.st.iter()) {
+        fpr[..st.len()].copy_from_slice(st);
+    }
+    fpu.fcw = gdb_regs.fpu.fctrl as u16;
+    fpu.fsw = gdb_regs.fpu.fstat as u16;
+    fpu.ftwx = abridged_tag_word(gdb_regs.fpu.ftag as u16);
+    fpu.last_ip = (u64::from(gdb_regs.fpu.fiseg) << 32) | u64::from(gdb_regs.fpu.fioff);
+    fpu.last_dp = (u64::from(gdb_regs.fpu.foseg) << 32) | u64::from(gdb_regs.fpu.fooff);
+    fpu.last_opcode = gdb_regs.fpu.fop as u16;
+
+    for (xmm, &reg) in fpu.xmm.iter_mut().zip(gdb_regs.xmm.iter()) {
+        *xmm = reg.to_le_bytes();
+    }
+    fpu.mxcsr = gdb_regs.mxcsr;
+}
+
+/// Converts the abridged x87 tag word saved by FXSAVE, with one bit per
+/// register set when it is not empty, to the full tag word gdb expects.
+///
+/// The full tag word distinguishes valid, zero and special values, but
+/// telling them apart requires decoding the registers, so non-empty
+/// registers are all reported as valid.
+fn full_tag_word(ftwx: u8) -> u16 {
+    (0..8)
+        .filter(|i| ftwx & (1 << i) == 0)
+        .fold(0, |tag, i| tag | (0b11 << (2 * i)))
+}
+
+/// Converts a full x87 tag word to the abridged one, see [`full_tag_word`]
+fn abridged_tag_word(ftag: u16) -> u8 {
+    (0..8)
+        .filter(|i| (ftag >> (2 * i)) & 0b11 != 0b11)
+        .fold(0, |tag, i| tag | (1 << i))
+}
+
 #[cfg(test)]
 mod tests {
-    use gdbstub_arch::x86::reg::X86_64CoreRegs;
-
     use super::*;
+    use crate::hypervisor::regs::{CommonFpu, CommonSegmentRegister, CommonSpecialRegisters};
 
     #[test]
     fn test_gdb_target() {
@@ -511,7 +954,9 @@ mod tests {
             "Failed to read registers"
         );
 
-        // Check response to write registers
+        // Check response to write registers, which first reads the current ones
+        let res = gdb_conn.send(DebugResponse::ReadRegisters(Box::default()));
+        assert!(res.is_ok());
         let msg = DebugResponse::WriteRegisters;
         let res = gdb_conn.send(msg);
         assert!(res.is_ok());
@@ -520,6 +965,33 @@ mod tests {
             "Failed to write registers"
         );
 
+        // Check that registers are reported in the order expected by gdb
+        let msg = DebugResponse::ReadRegisters(Box::new((
+            CommonRegisters {
+                rax: 1,
+                rbx: 2,
+                rcx: 3,
+                rbp: 7,
+                rsp: 8,
+                rip: 0x1000,
+                ..Default::default()
+            },
+            CommonFpu::default(),
+            Default::default(),
+        )));
+        let res = gdb_conn.send(msg);
+        assert!(res.is_ok());
+        assert!(
+            target.read_registers(&mut regs).is_ok(),
+            "Failed to read registers"
+        );
+        assert_eq!(regs.regs[0], 1);
+        assert_eq!(regs.regs[1], 2);
+        assert_eq!(regs.regs[2], 3);
+        assert_eq!(regs.regs[6], 7);
+        assert_eq!(regs.regs[7], 8);
+        assert_eq!(regs.rip, 0x1000);
+
         // Check response when the channel is dropped
         drop(gdb_conn);
         assert!(
@@ -528,4 +1000,198 @@ mod tests {
             expected to fail"
         );
     }
+    #[test]
+    fn test_gdb_target_fpu_and_segment_registers() {
+        let (gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
+        let mut target = HyperlightSandboxTarget::new(hyp_conn);
+
+        let mut fpu = CommonFpu {
+            fcw: 0x37f,
+            fsw: 0x3800,
+            // ST0 and ST1 are in use
+            ftwx: 0b11,
+            last_opcode: 0x5d9,
+            last_ip: 0x1234_5678_9abc,
+            last_dp: 0xdead_beef,
+            mxcsr: 0x1f80,
+            ..Default::default()
+        };
+        fpu.fpr[1][..10].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        fpu.xmm[15] = 0xaabb_u128.to_le_bytes();
+        let sregs = CommonSpecialRegisters {
+            cs: CommonSegmentRegister {
+                selector: 0x8,
+                ..Default::default()
+            },
+            ss: CommonSegmentRegister {
+                selector: 0x10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let vcpu_regs: VcpuRegisters = (CommonRegisters::default(), fpu, sregs);
+
+        gdb_conn
+            .send(DebugResponse::ReadRegisters(Box::new(vcpu_regs)))
+            .unwrap();
+        let mut regs = X86_64CoreRegs::default();
+        assert!(target.read_registers(&mut regs).is_ok());
+        assert_eq!(regs.segments.cs, 0x8);
+        assert_eq!(regs.segments.ss, 0x10);
+        assert_eq!(regs.st[1], [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(regs.fpu.fctrl, 0x37f);
+        assert_eq!(regs.fpu.fstat, 0x3800);
+        assert_eq!(regs.fpu.ftag, 0xfff0);
+        assert_eq!(regs.fpu.fiseg, 0x1234);
+        assert_eq!(regs.fpu.fioff, 0x5678_9abc);
+        assert_eq!(regs.fpu.fooff, 0xdead_beef);
+        assert_eq!(regs.fpu.fop, 0x5d9);
+        assert_eq!(regs.xmm[15], 0xaabb);
+
+        // Converting back doesn't lose any state
+        let mut written = (CommonRegisters::default(), CommonFpu::default(), sregs);
+        gdb_to_vcpu_regs(&regs, &mut written);
+        assert_eq!(written, vcpu_regs);
+
+        // Single registers are read from and written to the vCPU state
+        gdb_conn
+            .send(DebugResponse::ReadRegisters(Box::new(vcpu_regs)))
+            .unwrap();
+        let mut buf = [0u8; 16];
+        let len = target
+            .read_register((), X86_64CoreRegId::Xmm(15), &mut buf)
+            .unwrap();
+        assert_eq!(len, 16);
+        assert_eq!(u128::from_le_bytes(buf), 0xaabb);
+
+        gdb_conn
+            .send(DebugResponse::ReadRegisters(Box::new(vcpu_regs)))
+            .unwrap();
+        gdb_conn.send(DebugResponse::WriteRegisters).unwrap();
+        assert!(
+            target
+                .write_register((), X86_64CoreRegId::Gpr(0), &42u64.to_le_bytes())
+                .is_ok()
+        );
+        // Skip the read requests sent by the target
+        let written = std::iter::from_fn(|| gdb_conn.try_recv().ok())
+            .find_map(|msg| match msg {
+                DebugMsg::WriteRegisters(written) => Some(written),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(written.0.rax, 42);
+        assert_eq!(written.1, fpu);
+        assert_eq!(written.2, sregs);
+
+        // A value of the wrong size is rejected
+        gdb_conn
+            .send(DebugResponse::ReadRegisters(Box::new(vcpu_regs)))
+            .unwrap();
+        assert!(
+            target
+                .write_register((), X86_64CoreRegId::Rip, &[0; 4])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_gdb_target_range_step() {
+        let (gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
+        let mut target = HyperlightSandboxTarget::new(hyp_conn);
+
+        gdb_conn.send(DebugResponse::RangeStep).unwrap();
+        assert!(target.resume_range_step(0x1000, 0x1010).is_ok());
+        assert!(matches!(
+            gdb_conn.try_recv(),
+            Ok(DebugMsg::RangeStep(0x1000, 0x1010))
+        ));
+
+        gdb_conn.send(DebugResponse::Step).unwrap();
+        assert!(target.resume_range_step(0x1000, 0x1010).is_err());
+    }
+
+    #[test]
+    fn test_gdb_target_exec_file_and_auxv() {
+        let (gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
+        let target = HyperlightSandboxTarget::new(hyp_conn);
+        let mut buf = [0u8; 64];
+
+        // The path is transferred in chunks
+        let path = "/path/to/guest".to_string();
+        gdb_conn
+            .send(DebugResponse::GetExecFile(Some(path.clone())))
+            .unwrap();
+        let n = target.get_exec_file(None, 0, 4, &mut buf).unwrap();
+        assert_eq!(&buf[..n], b"/pat");
+        gdb_conn
+            .send(DebugResponse::GetExecFile(Some(path)))
+            .unwrap();
+        let n = target.get_exec_file(None, 12, 64, &mut buf).unwrap();
+        assert_eq!(&buf[..n], b"st");
+
+        // There is no file to report for guests loaded from a buffer
+        gdb_conn.send(DebugResponse::GetExecFile(None)).unwrap();
+        assert!(matches!(
+            target.get_exec_file(None, 0, 64, &mut buf),
+            Err(TargetError::NonFatal)
+        ));
+
+        gdb_conn.send(DebugResponse::GetEntryPoint(0x1234)).unwrap();
+        let n = target.get_auxv(0, 64, &mut buf).unwrap();
+        assert_eq!(n, 32);
+        assert_eq!(buf[..8], AT_ENTRY.to_le_bytes());
+        assert_eq!(buf[8..16], 0x1234u64.to_le_bytes());
+        assert_eq!(buf[16..32], [0; 16]);
+    }
+
+    #[derive(Debug, Default)]
+    struct MockInterruptHandle {
+        running: bool,
+        debug_interrupted: std::sync::atomic::AtomicBool,
+    }
+
+    impl InterruptHandle for MockInterruptHandle {
+        fn kill(&self) -> bool {
+            self.running
+        }
+
+        fn kill_from_debugger(&self) -> bool {
+            self.debug_interrupted
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+            self.running
+        }
+
+        #[cfg(feature = "trace_guest")]
+        fn flush_guest_traces(&self) -> bool {
+            self.running
+        }
+
+        fn inject_interrupt(&self, _vector: u8) -> bool {
+            self.running
+        }
+
+        fn dropped(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_gdb_target_interrupt() {
+        let (_gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
+        let mut target = HyperlightSandboxTarget::new(hyp_conn);
+
+        // Without an interrupt handle the vCPU cannot be interrupted
+        assert!(!target.interrupt_vcpu());
+
+        // An interrupt while the vCPU is not running is deferred, not an error
+        let handle = Arc::new(MockInterruptHandle::default());
+        target.set_interrupt_handle(handle.clone());
+        assert!(target.interrupt_vcpu());
+        assert!(
+            handle
+                .debug_interrupted
+                .load(std::sync::atomic::Ordering::Relaxed)
+        );
+    }
 }
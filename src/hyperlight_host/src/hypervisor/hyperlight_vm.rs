@@ -16,23 +16,34 @@ limitations under the License.
 
 #[cfg(gdb)]
 use std::collections::HashMap;
+#[cfg(gdb)]
+use std::ops::Range;
 #[cfg(crashdump)]
 use std::path::Path;
 use std::str::FromStr;
 #[cfg(any(kvm, mshv3))]
 use std::sync::atomic::AtomicBool;
-use std::sync::atomic::AtomicU8;
 #[cfg(any(kvm, mshv3))]
 use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU8, AtomicU16};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use flatbuffers::FlatBufferBuilder;
+use hyperlight_common::flatbuffer_wrappers::function_call::FunctionCall;
+use hyperlight_common::flatbuffer_wrappers::function_types::FunctionCallResult;
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::interrupt::FIRST_HOST_INTERRUPT_VECTOR;
 use hyperlight_common::log_level::GuestLogFilter;
+use hyperlight_common::outb::{Exception, OutBAction};
 use tracing::{Span, instrument};
 use tracing_core::LevelFilter;
 
 #[cfg(gdb)]
 use super::gdb::arch::VcpuStopReasonError;
 #[cfg(gdb)]
+use super::gdb::history::DebugHistory;
+#[cfg(gdb)]
 use super::gdb::{
     DebugCommChannel, DebugMsg, DebugResponse, DebuggableVm, GdbTargetError, VcpuStopReason, arch,
 };
@@ -40,15 +51,24 @@ use super::regs::{CommonFpu, CommonRegisters};
 #[cfg(target_os = "windows")]
 use super::{PartitionState, WindowsInterruptHandle};
 use crate::HyperlightError;
+use crate::func::callbacks::{CallbackRequest, CallbackScope, MAX_CALLBACK_DEPTH};
+use crate::func::host_io::HostCallScope;
 #[cfg(any(kvm, mshv3))]
 use crate::hypervisor::LinuxInterruptHandle;
 #[cfg(crashdump)]
-use crate::hypervisor::crashdump;
+use crate::hypervisor::arch::CoreRegisters;
+#[cfg(gdb)]
+use crate::hypervisor::arch::GuestRegisters;
+#[cfg(any(kvm, mshv3))]
+use crate::hypervisor::cpu_budget::{CpuBudget, CpuBudgetError, CpuBudgetTimer};
 #[cfg(gdb)]
-use crate::hypervisor::gdb::{DebugError, DebugMemoryAccessError};
+use crate::hypervisor::gdb::DebugError;
 #[cfg(gdb)]
 use crate::hypervisor::hyperlight_vm::debug::ProcessDebugRequestError;
+#[cfg(kvm)]
+use crate::hypervisor::perf_counters::{FuelMeter, GuestPerfCounters, PerfCountersError};
 use crate::hypervisor::regs::{CommonDebugRegs, CommonSpecialRegisters};
+use crate::hypervisor::vcpu_thread::{VcpuThreadError, VcpuThreadSettings};
 #[cfg(not(gdb))]
 use crate::hypervisor::virtual_machine::VirtualMachine;
 #[cfg(kvm)]
@@ -58,23 +78,44 @@ use crate::hypervisor::virtual_machine::mshv::MshvVm;
 #[cfg(target_os = "windows")]
 use crate::hypervisor::virtual_machine::whp::WhpVm;
 use crate::hypervisor::virtual_machine::{
-    HypervisorType, MapMemoryError, RegisterError, RunVcpuError, UnmapMemoryError, VmError, VmExit,
-    get_available_hypervisor,
+    HypervisorKind, HypervisorType, MapMemoryError, RegisterError, RunVcpuError, UnmapMemoryError,
+    VmError, VmExit, resolve_hypervisor,
 };
 use crate::hypervisor::{InterruptHandle, InterruptHandleImpl};
+#[cfg(crashdump)]
+use crate::hypervisor::{crash_report, crashdump};
 use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags, MemoryRegionType};
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::ptr::RawPtr;
 use crate::mem::shared_mem::{GuestSharedMemory, HostSharedMemory, SharedMemory};
-use crate::metrics::{METRIC_ERRONEOUS_VCPU_KICKS, METRIC_GUEST_CANCELLATION};
-use crate::sandbox::SandboxConfiguration;
+use crate::metrics::{
+    METRIC_ERRONEOUS_VCPU_KICKS, METRIC_GUEST_CANCELLATION, MetricsRecorder, SandboxMetric,
+    VmExitReason,
+};
+use crate::new_error;
+use crate::sandbox::checkpoint::{GuestCheckpoint, GuestCheckpoints};
+use crate::sandbox::debug_events::DebugEventSink;
+use crate::sandbox::fuel::FuelTank;
+use crate::sandbox::guest_crash::{
+    GuestCrashRegisters, GuestCrashReport, GuestPanic, read_crash_stack, walk_crash_frames,
+};
+use crate::sandbox::guest_log::GuestLogSink;
 use crate::sandbox::host_funcs::FunctionRegistry;
-use crate::sandbox::outb::{HandleOutbError, handle_outb};
+use crate::sandbox::metrics::{CreationPhase, CreationReport, SandboxUsage};
+use crate::sandbox::mmio::MmioRegions;
+use crate::sandbox::outb::{
+    HandleOutbError, call_host_function, handle_outb, read_host_function_call,
+    write_host_function_result,
+};
+use crate::sandbox::quota::{SandboxQuota, SandboxQuotaResource};
 use crate::sandbox::snapshot::NextAction;
 #[cfg(feature = "mem_profile")]
 use crate::sandbox::trace::MemTraceInfo;
-#[cfg(crashdump)]
+#[cfg(feature = "mem_trace")]
+use crate::sandbox::trace::MemoryAccessLog;
+#[cfg(any(crashdump, gdb))]
 use crate::sandbox::uninitialized::SandboxRuntimeConfig;
+use crate::sandbox::{SandboxConfiguration, SandboxMetrics, VmExitStats};
 
 /// Get the logging level filter to pass to the guest entrypoint
 ///
@@ -170,26 +211,75 @@ pub(crate) struct HyperlightVm {
     mmap_regions: Vec<(u32, MemoryRegion)>, // Later mapped regions (slot number, region)
 
     pending_tlb_flush: bool,
+    // How many guest function calls made by host functions with
+    // `call_guest` are running, nested in the guest function call
+    callback_depth: usize,
+    // The frequency the guest TSC is scaled to, if it is
+    #[cfg(feature = "trace_guest")]
+    guest_tsc_khz: Option<u32>,
+    // Injected interrupts the guest could not take yet, indexed by vector
+    // from `FIRST_HOST_INTERRUPT_VECTOR`
+    pending_interrupts: u16,
 
     #[cfg(gdb)]
     gdb_conn: Option<DebugCommChannel<DebugResponse, DebugMsg>>,
     #[cfg(gdb)]
     sw_breakpoints: HashMap<u64, u8>, // addr -> original instruction
+    #[cfg(gdb)]
+    dbg_history: DebugHistory, // state at previous debugger stops, for reverse execution
+    #[cfg(gdb)]
+    dbg_step_range: Option<Range<u64>>, // addresses single-stepped through without stopping
+    debug_events: DebugEventSink,
+    guest_logs: GuestLogSink,
+    guest_checkpoints: GuestCheckpoints,
+    vcpu_thread: VcpuThreadSettings,
+    #[cfg(any(kvm, mshv3))]
+    cpu_budget: Option<CpuBudget>,
+    #[cfg(kvm)]
+    perf_counters: bool,
+    /// The fuel left to the guest
+    fuel: FuelTank,
+    /// Meters the fuel of the guest during a guest function call
+    #[cfg(kvm)]
+    fuel_meter: Option<FuelMeter>,
+    /// The signal the fuel meter interrupts the vCPU with
+    #[cfg(kvm)]
+    fuel_signal: libc::c_int,
+    last_call_metrics: Option<SandboxMetrics>,
+    exit_stats: VmExitStats,
+    /// The time the vCPU ran the guest since the VM was created
+    cpu_time: Duration,
+    /// The host function calls made by the guest since the VM was created
+    host_calls: u64,
+    /// The limits on the resources the sandbox uses
+    usage_quota: SandboxQuota,
+    /// Where the measurements of the sandbox are reported
+    metrics: MetricsRecorder,
     #[cfg(feature = "mem_profile")]
     trace_info: MemTraceInfo,
-    #[cfg(crashdump)]
+    #[cfg(feature = "mem_trace")]
+    memory_accesses: MemoryAccessLog,
+    #[cfg(any(crashdump, gdb))]
     rt_cfg: SandboxRuntimeConfig,
 }
 
 /// DispatchGuestCall error
 #[derive(Debug, thiserror::Error)]
 pub enum DispatchGuestCallError {
+    #[cfg(any(kvm, mshv3))]
+    #[error("Failed to start the CPU time budget: {0}")]
+    CpuBudget(CpuBudgetError),
+    #[cfg(kvm)]
+    #[error("Failed to count the guest events: {0}")]
+    PerfCounters(PerfCountersError),
     #[error("Failed to run vm: {0}")]
     Run(#[from] RunVmError),
     #[error("Failed to setup registers: {0}")]
     SetupRegs(RegisterError),
     #[error("VM was uninitialized")]
     Uninitialized,
+    #[error("Failed to set up the vCPU thread: {0}")]
+    VcpuThread(VcpuThreadError),
 }
 
 impl DispatchGuestCallError {
@@ -199,7 +289,37 @@ impl DispatchGuestCallError {
             // These errors poison the sandbox because they can leave it in an inconsistent state
             // by returning before the guest can unwind properly
             DispatchGuestCallError::Run(_) => true,
-            DispatchGuestCallError::SetupRegs(_) | DispatchGuestCallError::Uninitialized => false,
+            #[cfg(any(kvm, mshv3))]
+            DispatchGuestCallError::CpuBudget(_) => false,
+            #[cfg(kvm)]
+            DispatchGuestCallError::PerfCounters(_) => false,
+            DispatchGuestCallError::SetupRegs(_)
+            | DispatchGuestCallError::Uninitialized
+            | DispatchGuestCallError::VcpuThread(_) => false,
+        }
+    }
+
+    /// What the guest was doing when it aborted, if this error is the
+    /// guest aborting
+    pub(crate) fn crash_report(&self) -> Option<GuestCrashReport> {
+        match self {
+            DispatchGuestCallError::Run(RunVmError::GuestCrashed(report)) => {
+                Some(report.as_ref().clone())
+            }
+            DispatchGuestCallError::Run(RunVmError::HandleIo(HandleIoError::Outb(
+                HandleOutbError::GuestAborted {
+                    code,
+                    message,
+                    exception,
+                    panic,
+                },
+            ))) => Some(GuestCrashReport::new(
+                *code,
+                message.clone(),
+                *exception,
+                panic.clone(),
+            )),
+            _ => None,
         }
     }
 
@@ -215,8 +335,36 @@ impl DispatchGuestCallError {
                 HyperlightError::ExecutionCanceledByHost()
             }
 
+            DispatchGuestCallError::Run(RunVmError::ExecutionBudgetExceeded(budget)) => {
+                HyperlightError::ExecutionBudgetExceeded(budget)
+            }
+
+            DispatchGuestCallError::Run(RunVmError::QuotaExceeded(resource)) => {
+                HyperlightError::SandboxQuotaExceeded(resource)
+            }
+
+            DispatchGuestCallError::Run(RunVmError::FuelExhausted) => {
+                HyperlightError::FuelExhausted
+            }
+
+            DispatchGuestCallError::Run(RunVmError::GuestCrashed(report))
+                if report.code == ErrorCode::StackOverflow as u8 =>
+            {
+                HyperlightError::GuestStackOverflow(report.message)
+            }
+
+            DispatchGuestCallError::Run(RunVmError::GuestCrashed(report)) => {
+                HyperlightError::GuestAborted(report.code, report.message)
+            }
+
+            DispatchGuestCallError::Run(RunVmError::HandleIo(HandleIoError::Outb(
+                HandleOutbError::GuestAborted { code, message, .. },
+            ))) if code == ErrorCode::StackOverflow as u8 => {
+                HyperlightError::GuestStackOverflow(message)
+            }
+
             DispatchGuestCallError::Run(RunVmError::HandleIo(HandleIoError::Outb(
-                HandleOutbError::GuestAborted { code, message },
+                HandleOutbError::GuestAborted { code, message, .. },
             ))) => HyperlightError::GuestAborted(code, message),
 
             DispatchGuestCallError::Run(RunVmError::MemoryAccessViolation {
@@ -225,6 +373,10 @@ impl DispatchGuestCallError {
                 region_flags,
             }) => HyperlightError::MemoryAccessViolation(addr, access_type, region_flags),
 
+            DispatchGuestCallError::Run(RunVmError::MsrAccessDenied { msr, write }) => {
+                HyperlightError::MsrAccessDenied(msr, write)
+            }
+
             // Leave others as is
             other => HyperlightVmError::DispatchGuestCall(other).into(),
         };
@@ -243,6 +395,8 @@ pub enum InitializeError {
     SetupRegs(#[from] RegisterError),
     #[error("Guest initialised stack pointer to architecturally invalid value: {0}")]
     InvalidStackPointer(u64),
+    #[error("Failed to set up the vCPU thread: {0}")]
+    VcpuThread(#[from] VcpuThreadError),
 }
 
 /// Errors that can occur during VM execution in the run loop
@@ -254,6 +408,19 @@ pub enum RunVmError {
     #[cfg(gdb)]
     #[error("Debug handler error: {0}")]
     DebugHandler(#[from] HandleDebugError),
+    #[error("Failed to call back into the guest: {0}")]
+    Callback(String),
+    #[error("Execution exceeded its CPU time budget of {0:?}")]
+    ExecutionBudgetExceeded(Duration),
+    #[error("The sandbox used up its quota of {0}")]
+    QuotaExceeded(SandboxQuotaResource),
+    #[error("The guest used up its fuel")]
+    FuelExhausted,
+    #[cfg(kvm)]
+    #[error("Failed to meter the fuel of the guest: {0}")]
+    Fuel(PerfCountersError),
+    #[error("Guest aborted: error code {}, message: {}", .0.code, .0.message)]
+    GuestCrashed(Box<GuestCrashReport>),
     #[error("Execution was cancelled by the host")]
     ExecutionCancelledByHost,
     #[error("Failed to access page: {0}")]
@@ -261,6 +428,8 @@ pub enum RunVmError {
     #[cfg(feature = "trace_guest")]
     #[error("Failed to get registers: {0}")]
     GetRegs(RegisterError),
+    #[error("Failed to inject interrupt: {0}")]
+    InjectInterrupt(RegisterError),
     #[error("IO handling error: {0}")]
     HandleIo(#[from] HandleIoError),
     #[error(
@@ -275,6 +444,8 @@ pub enum RunVmError {
     MmioReadUnmapped(u64),
     #[error("MMIO WRITE access to unmapped address {0:#x}")]
     MmioWriteUnmapped(u64),
+    #[error("MSR {msr:#x} {} denied by the MSR policy", if *write { "write" } else { "read" })]
+    MsrAccessDenied { msr: u32, write: bool },
     #[error("vCPU run failed: {0}")]
     RunVcpu(#[from] RunVcpuError),
     #[error("Unexpected VM exit: {0}")]
@@ -287,9 +458,18 @@ pub enum RunVmError {
 /// Errors that can occur during IO (outb) handling
 #[derive(Debug, thiserror::Error)]
 pub enum HandleIoError {
-    #[cfg(feature = "mem_profile")]
+    #[error("Failed to take a snapshot at a guest checkpoint: {0}")]
+    Checkpoint(String),
+    #[cfg(any(feature = "mem_profile", feature = "mem_trace"))]
     #[error("Failed to get registers: {0}")]
     GetRegs(RegisterError),
+    #[cfg(feature = "mem_trace")]
+    #[error("Failed to trace a guest memory access: {0}")]
+    MemoryTrace(String),
+    #[error("A guest function called back by a host function failed: {0}")]
+    Callback(Box<RunVmError>),
+    #[error("Failed to run the host function on a thread of its own: {0}")]
+    HostCallThread(String),
     #[error("No data was given in IO interrupt")]
     NoData,
     #[error("{0}")]
@@ -349,6 +529,10 @@ pub enum CreateHyperlightVmError {
     AddHwBreakpoint(DebugError),
     #[error("No hypervisor was found")]
     NoHypervisorFound,
+    #[error(
+        "The {0} hypervisor was requested, but it is not supported by this build or not usable on this host"
+    )]
+    HypervisorUnavailable(HypervisorKind),
     #[cfg(gdb)]
     #[error("Failed to send debug message: {0}")]
     SendDbgMsg(#[from] SendDbgMsgError),
@@ -411,6 +595,9 @@ pub enum HyperlightVmError {
     UpdateRegion(#[from] UpdateRegionError),
     #[error("Access page table error: {0}")]
     AccessPageTable(#[from] AccessPageTableError),
+    #[cfg(gdb)]
+    #[error("Hardware breakpoints error: {0}")]
+    HwBreakpoints(DebugError),
 }
 
 impl HyperlightVm {
@@ -423,36 +610,71 @@ impl HyperlightVm {
         _pml4_addr: u64,
         entrypoint: NextAction,
         rsp_gva: u64,
-        #[cfg_attr(target_os = "windows", allow(unused_variables))] config: &SandboxConfiguration,
+        config: &SandboxConfiguration,
         #[cfg(gdb)] gdb_conn: Option<DebugCommChannel<DebugResponse, DebugMsg>>,
-        #[cfg(crashdump)] rt_cfg: SandboxRuntimeConfig,
+        #[cfg(any(crashdump, gdb))] rt_cfg: SandboxRuntimeConfig,
         #[cfg(feature = "mem_profile")] trace_info: MemTraceInfo,
+        creation_report: &mut CreationReport,
     ) -> std::result::Result<Self, CreateHyperlightVmError> {
         #[cfg(gdb)]
         type VmType = Box<dyn DebuggableVm>;
         #[cfg(not(gdb))]
         type VmType = Box<dyn VirtualMachine>;
 
-        let vm: VmType = match get_available_hypervisor() {
-            #[cfg(kvm)]
-            Some(HypervisorType::Kvm) => Box::new(KvmVm::new().map_err(VmError::CreateVm)?),
-            #[cfg(mshv3)]
-            Some(HypervisorType::Mshv) => Box::new(MshvVm::new().map_err(VmError::CreateVm)?),
-            #[cfg(target_os = "windows")]
-            Some(HypervisorType::Whp) => Box::new(WhpVm::new().map_err(VmError::CreateVm)?),
-            None => return Err(CreateHyperlightVmError::NoHypervisorFound),
-        };
-
-        #[cfg(feature = "init-paging")]
-        vm.set_sregs(&CommonSpecialRegisters::standard_64bit_defaults(_pml4_addr))
-            .map_err(VmError::Register)?;
-        #[cfg(not(feature = "init-paging"))]
-        vm.set_sregs(&CommonSpecialRegisters::standard_real_mode_defaults())
-            .map_err(VmError::Register)?;
+        let cpuid_overrides = config.get_cpuid_overrides();
+        let msr_policy = config.get_msr_policy();
+        let hypervisor = config.get_hypervisor();
+        let hypervisor_type = resolve_hypervisor(hypervisor);
+        let mut vm = creation_report.time(
+            CreationPhase::VmCreation,
+            || -> std::result::Result<VmType, CreateHyperlightVmError> {
+                let vm: VmType = match hypervisor_type {
+                    #[cfg(kvm)]
+                    Some(HypervisorType::Kvm) => Box::new(
+                        KvmVm::new(&cpuid_overrides, msr_policy).map_err(VmError::CreateVm)?,
+                    ),
+                    #[cfg(mshv3)]
+                    Some(HypervisorType::Mshv) => Box::new(
+                        MshvVm::new(&cpuid_overrides, msr_policy).map_err(VmError::CreateVm)?,
+                    ),
+                    #[cfg(target_os = "windows")]
+                    Some(HypervisorType::Whp) => Box::new(
+                        WhpVm::new(&cpuid_overrides, msr_policy).map_err(VmError::CreateVm)?,
+                    ),
+                    None if hypervisor == HypervisorKind::Auto => {
+                        return Err(CreateHyperlightVmError::NoHypervisorFound);
+                    }
+                    None => return Err(CreateHyperlightVmError::HypervisorUnavailable(hypervisor)),
+                };
+                Ok(vm)
+            },
+        )?;
+        if let Some(hypervisor_type) = hypervisor_type {
+            creation_report.set_hypervisor(hypervisor_type.into());
+        }
+        creation_report.time(
+            CreationPhase::VcpuSetup,
+            || -> std::result::Result<(), CreateHyperlightVmError> {
+                vm.set_tsc(
+                    config.get_guest_tsc_offset(),
+                    config.get_guest_tsc_frequency(),
+                )
+                .map_err(VmError::CreateVm)?;
+
+                #[cfg(feature = "init-paging")]
+                vm.set_sregs(&CommonSpecialRegisters::standard_64bit_defaults(_pml4_addr))
+                    .map_err(VmError::Register)?;
+                #[cfg(not(feature = "init-paging"))]
+                vm.set_sregs(&CommonSpecialRegisters::standard_real_mode_defaults())
+                    .map_err(VmError::Register)?;
+                Ok(())
+            },
+        )?;
 
         #[cfg(any(kvm, mshv3))]
         let interrupt_handle: Arc<dyn InterruptHandleImpl> = Arc::new(LinuxInterruptHandle {
             state: AtomicU8::new(0),
+            pending_interrupts: AtomicU16::new(0),
             #[cfg(all(
                 target_arch = "x86_64",
                 target_vendor = "unknown",
@@ -475,6 +697,7 @@ impl HyperlightVm {
         #[cfg(target_os = "windows")]
         let interrupt_handle: Arc<dyn InterruptHandleImpl> = Arc::new(WindowsInterruptHandle {
             state: AtomicU8::new(0),
+            pending_interrupts: AtomicU16::new(0),
             partition_state: std::sync::RwLock::new(PartitionState {
                 handle: vm.partition_handle(),
                 dropped: false,
@@ -502,19 +725,64 @@ impl HyperlightVm {
             mmap_regions: Vec::new(),
 
             pending_tlb_flush: false,
+            callback_depth: 0,
+            #[cfg(feature = "trace_guest")]
+            guest_tsc_khz: config.get_guest_tsc_frequency(),
+            pending_interrupts: 0,
 
             #[cfg(gdb)]
             gdb_conn,
             #[cfg(gdb)]
             sw_breakpoints: HashMap::new(),
+            #[cfg(gdb)]
+            dbg_history: DebugHistory::new(
+                rt_cfg
+                    .debug_info
+                    .map_or(0, |info| info.reverse_execution_stops),
+            ),
+            #[cfg(gdb)]
+            dbg_step_range: None,
+            debug_events: DebugEventSink::default(),
+            guest_logs: GuestLogSink::new(config.get_guest_log_backpressure()),
+            guest_checkpoints: GuestCheckpoints::new(config.get_guest_checkpoint_policy()),
+            vcpu_thread: VcpuThreadSettings {
+                affinity: config.get_vcpu_affinity(),
+                priority: config.get_vcpu_priority(),
+            },
+            #[cfg(any(kvm, mshv3))]
+            cpu_budget: config.get_guest_cpu_time_budget().map(|budget| CpuBudget {
+                budget,
+                signal: libc::SIGRTMIN()
+                    + config.get_interrupt_vcpu_sigrtmin_offset() as libc::c_int,
+            }),
+            // The counters only tell apart the guest from the host on KVM
+            #[cfg(kvm)]
+            perf_counters: config.get_guest_perf_counters()
+                && hypervisor_type == Some(HypervisorType::Kvm),
+            fuel: FuelTank::default(),
+            #[cfg(kvm)]
+            fuel_meter: None,
+            #[cfg(kvm)]
+            fuel_signal: libc::SIGRTMIN()
+                + config.get_interrupt_vcpu_sigrtmin_offset() as libc::c_int,
+            last_call_metrics: None,
+            exit_stats: VmExitStats::default(),
+            cpu_time: Duration::ZERO,
+            host_calls: 0,
+            usage_quota: SandboxQuota::new(),
+            metrics: MetricsRecorder::default(),
             #[cfg(feature = "mem_profile")]
             trace_info,
-            #[cfg(crashdump)]
+            #[cfg(feature = "mem_trace")]
+            memory_accesses: MemoryAccessLog::default(),
+            #[cfg(any(crashdump, gdb))]
             rt_cfg,
         };
 
-        ret.update_snapshot_mapping(snapshot_mem)?;
-        ret.update_scratch_mapping(scratch_mem)?;
+        creation_report.time(CreationPhase::VmCreation, || {
+            ret.update_snapshot_mapping(snapshot_mem)?;
+            ret.update_scratch_mapping(scratch_mem)
+        })?;
 
         // Send the interrupt handle to the GDB thread if debugging is enabled
         // This is used to allow the GDB thread to stop the vCPU
@@ -574,9 +842,12 @@ impl HyperlightVm {
         };
         self.vm.set_regs(&regs)?;
 
+        let _vcpu_thread = self.vcpu_thread.apply()?;
         self.run(
             mem_mgr,
             host_funcs,
+            #[cfg(any(kvm, mshv3))]
+            None,
             #[cfg(gdb)]
             dbg_mem_access_fn,
         )
@@ -689,6 +960,54 @@ impl HyperlightVm {
         Ok(())
     }
 
+    /// Records a store of the guest to its traced memory, reading the
+    /// bytes it writes to before and after it is single-stepped
+    #[cfg(feature = "mem_trace")]
+    fn trace_memory_access(
+        &mut self,
+        mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+        phase: u32,
+    ) -> std::result::Result<(), HandleIoError> {
+        use hyperlight_common::outb::MemoryTracePhase;
+
+        let phase = MemoryTracePhase::try_from(phase)
+            .map_err(|e| HandleIoError::MemoryTrace(e.to_string()))?;
+        let root_pt = self
+            .get_root_pt()
+            .map_err(|e| HandleIoError::MemoryTrace(e.to_string()))?;
+        let mut read = |gva: u64| {
+            mem_mgr
+                .read_guest_memory_by_gva(gva, MemoryAccessLog::compared_len(gva), root_pt)
+                .map_err(|e| HandleIoError::MemoryTrace(e.to_string()))
+        };
+        match phase {
+            MemoryTracePhase::Store => {
+                let regs = self.vm.regs().map_err(HandleIoError::GetRegs)?;
+                let before = read(regs.r8)?;
+                self.memory_accesses.begin(regs.r8, regs.r9, before);
+            }
+            MemoryTracePhase::Done => {
+                if let Some(gva) = self.memory_accesses.pending_gva() {
+                    let after = read(gva)?;
+                    self.memory_accesses.finish(&after);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the stores of the guest to its traced memory recorded so far
+    #[cfg(feature = "mem_trace")]
+    pub(crate) fn take_memory_accesses(&mut self) -> Vec<crate::sandbox::trace::MemoryAccess> {
+        self.memory_accesses.take()
+    }
+
+    /// Makes the guest flush its TLB when it is next called into, after
+    /// the host changed its page tables
+    pub(crate) fn request_tlb_flush(&mut self) {
+        self.pending_tlb_flush = true;
+    }
+
     /// Get the current base page table physical address.
     ///
     /// With `init-paging`, reads CR3 from the vCPU special registers.
@@ -713,6 +1032,31 @@ impl HyperlightVm {
         Ok(self.vm.sregs()?)
     }
 
+    /// Get the hardware breakpoints that need to be stored in a snapshot,
+    /// which are the ones programmed by the debugger if one is attached
+    #[cfg(gdb)]
+    pub(crate) fn get_snapshot_hw_breakpoints(
+        &self,
+    ) -> std::result::Result<Option<arch::HwBreakpointRegs>, DebugError> {
+        if self.gdb_conn.is_none() {
+            return Ok(None);
+        }
+        self.vm.hw_breakpoint_regs().map(Some)
+    }
+
+    /// Program the hardware breakpoints stored in a snapshot, since
+    /// resetting the vCPU clears the ones the debugger programmed
+    #[cfg(gdb)]
+    pub(crate) fn restore_hw_breakpoints(
+        &mut self,
+        regs: &arch::HwBreakpointRegs,
+    ) -> std::result::Result<(), DebugError> {
+        if self.gdb_conn.is_none() {
+            return Ok(());
+        }
+        self.vm.set_hw_breakpoint_regs(regs)
+    }
+
     /// Get the current stack top virtual address
     pub(crate) fn get_stack_top(&mut self) -> u64 {
         self.rsp_gva
@@ -780,19 +1124,249 @@ impl HyperlightVm {
             .set_fpu(&CommonFpu::default())
             .map_err(DispatchGuestCallError::SetupRegs)?;
 
-        self.run(
-            mem_mgr,
-            host_funcs,
-            #[cfg(gdb)]
-            dbg_mem_access_fn,
-        )
-        .map_err(DispatchGuestCallError::Run)
+        let _vcpu_thread = self
+            .vcpu_thread
+            .apply()
+            .map_err(DispatchGuestCallError::VcpuThread)?;
+        #[cfg(kvm)]
+        let perf_counters = self
+            .perf_counters
+            .then(GuestPerfCounters::open)
+            .transpose()
+            .map_err(DispatchGuestCallError::PerfCounters)?;
+        #[cfg(kvm)]
+        self.start_fuel_meter()
+            .map_err(DispatchGuestCallError::PerfCounters)?;
+        // Started last, so that only the call itself counts against the budget
+        #[cfg(any(kvm, mshv3))]
+        let budget_timer = self
+            .cpu_budget
+            .map(|budget| budget.start())
+            .transpose()
+            .map_err(DispatchGuestCallError::CpuBudget)?;
+        let result = self
+            .run(
+                mem_mgr,
+                host_funcs,
+                #[cfg(any(kvm, mshv3))]
+                budget_timer.as_ref(),
+                #[cfg(gdb)]
+                dbg_mem_access_fn,
+            )
+            .map_err(DispatchGuestCallError::Run);
+
+        // The counters are also read when the call failed, since the
+        // guest ran up to the failure
+        #[cfg(kvm)]
+        let result = {
+            let charged = self
+                .charge_fuel()
+                .map_err(DispatchGuestCallError::PerfCounters);
+            result.and(charged)
+        };
+        #[cfg(kvm)]
+        if let Some(counters) = perf_counters {
+            self.last_call_metrics = counters
+                .read()
+                .inspect_err(|e| tracing::warn!("Cannot read the guest counters: {}", e))
+                .ok();
+        }
+        result
+    }
+
+    pub(crate) fn fuel(&self) -> &FuelTank {
+        &self.fuel
+    }
+
+    pub(crate) fn fuel_mut(&mut self) -> &mut FuelTank {
+        &mut self.fuel
+    }
+
+    /// Starts metering the fuel left to the guest, if it is metered and
+    /// has any left
+    #[cfg(kvm)]
+    fn start_fuel_meter(&mut self) -> Result<(), PerfCountersError> {
+        self.fuel_meter = match self.fuel.remaining() {
+            Some(fuel) if fuel > 0 => Some(FuelMeter::start(fuel, self.fuel_signal)?),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    /// Stops metering the fuel of the guest, taking the instructions it
+    /// retired out of its fuel
+    #[cfg(kvm)]
+    fn charge_fuel(&mut self) -> Result<(), PerfCountersError> {
+        if let Some(mut meter) = self.fuel_meter.take() {
+            self.fuel.consume(meter.consumed()?);
+        }
+        Ok(())
+    }
+
+    /// Returns whether the guest can carry on, asking the fuel handler
+    /// for more fuel once the guest used up all of it
+    #[cfg(kvm)]
+    fn check_fuel(&mut self) -> Result<bool, PerfCountersError> {
+        let Some(remaining) = self.fuel.remaining() else {
+            return Ok(true);
+        };
+        let exhausted = match &mut self.fuel_meter {
+            Some(meter) => meter.consumed()? >= meter.fuel(),
+            // Only guest function calls are metered
+            None => remaining == 0,
+        };
+        if !exhausted {
+            return Ok(true);
+        }
+        self.charge_fuel()?;
+        if !self.fuel.refuel() {
+            return Ok(false);
+        }
+        self.start_fuel_meter()?;
+        Ok(true)
+    }
+
+    /// The performance counters of the guest during the last guest
+    /// function call, if they are counted
+    pub(crate) fn last_call_metrics(&self) -> Option<SandboxMetrics> {
+        self.last_call_metrics
+    }
+
+    /// The exits of the vCPU since the VM was created, by reason
+    pub(crate) fn exit_stats(&self) -> VmExitStats {
+        self.exit_stats
+    }
+
+    /// The resources used since the VM was created, with the bytes
+    /// transferred through the buffers of `mem_mgr`
+    pub(crate) fn usage(&self, mem_mgr: &SandboxMemoryManager<HostSharedMemory>) -> SandboxUsage {
+        SandboxUsage {
+            cpu_time: self.cpu_time,
+            vm_exits: self.exit_stats.total(),
+            host_calls: self.host_calls,
+            bytes_transferred: mem_mgr.transferred_bytes,
+        }
+    }
+
+    pub(crate) fn usage_quota(&self) -> SandboxQuota {
+        self.usage_quota
+    }
+
+    pub(crate) fn set_usage_quota(&mut self, quota: SandboxQuota) {
+        self.usage_quota = quota;
+    }
+
+    /// Returns the first resource of which the sandbox used up its quota,
+    /// if any
+    pub(crate) fn exceeded_quota(
+        &self,
+        mem_mgr: &SandboxMemoryManager<HostSharedMemory>,
+    ) -> Option<SandboxQuotaResource> {
+        self.usage_quota.exceeded(&self.usage(mem_mgr))
+    }
+
+    /// Where the measurements of the sandbox are reported
+    pub(crate) fn metrics(&self) -> &MetricsRecorder {
+        &self.metrics
+    }
+
+    pub(crate) fn set_metrics(&mut self, metrics: MetricsRecorder) {
+        self.metrics = metrics;
+    }
+
+    /// Starts taking snapshots at the checkpoints declared by the guest,
+    /// which belong to the sandbox `sandbox_id`
+    pub(crate) fn enable_guest_checkpoints(&mut self, sandbox_id: u64) {
+        self.guest_checkpoints.enable(sandbox_id);
+    }
+
+    /// The last snapshot taken at a checkpoint declared by the guest
+    pub(crate) fn last_guest_checkpoint(&self) -> Option<&GuestCheckpoint> {
+        self.guest_checkpoints.last()
+    }
+
+    /// Takes a snapshot at the checkpoint the guest declared, if the
+    /// checkpoint policy of the sandbox says so. The snapshot restores to
+    /// the start of the stack, like the snapshots taken between calls,
+    /// since the guest functions called after restoring it start afresh.
+    fn take_guest_checkpoint(
+        &mut self,
+        mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    ) -> std::result::Result<(), HandleIoError> {
+        let name = mem_mgr
+            .read_checkpoint_name()
+            .map_err(|e| HandleIoError::Checkpoint(e.to_string()))?;
+        let now = Instant::now();
+        let Some(sandbox_id) = self.guest_checkpoints.due(now) else {
+            return Ok(());
+        };
+
+        let mapped_regions = self.get_mapped_regions().cloned().collect();
+        let root_pt_gpa = self
+            .get_root_pt()
+            .map_err(|e| HandleIoError::Checkpoint(e.to_string()))?;
+        let sregs = self
+            .get_snapshot_sregs()
+            .map_err(|e| HandleIoError::Checkpoint(e.to_string()))?;
+        let snapshot = mem_mgr
+            .snapshot(
+                sandbox_id,
+                mapped_regions,
+                root_pt_gpa,
+                self.rsp_gva,
+                sregs,
+                self.entrypoint,
+            )
+            .map_err(|e| HandleIoError::Checkpoint(e.to_string()))?;
+        self.guest_checkpoints.record(name, Arc::new(snapshot), now);
+        Ok(())
     }
 
     pub(crate) fn interrupt_handle(&self) -> Arc<dyn InterruptHandle> {
         self.interrupt_handle.clone()
     }
 
+    /// The sink of the debug events raised while running the guest
+    pub(crate) fn debug_events(&self) -> &DebugEventSink {
+        &self.debug_events
+    }
+
+    /// Sets the sink of the debug events raised while running the guest,
+    /// so that they reach the subscriptions made before the VM was created
+    pub(crate) fn set_debug_events(&mut self, debug_events: DebugEventSink) {
+        self.debug_events = debug_events;
+    }
+
+    /// Emulates the guest accesses to the MMIO `regions` with their
+    /// handlers, instead of failing the guest call
+    pub(crate) fn set_mmio_regions(
+        &mut self,
+        regions: MmioRegions,
+    ) -> std::result::Result<(), CreateHyperlightVmError> {
+        self.vm
+            .set_mmio_regions(regions)
+            .map_err(|e| VmError::CreateVm(e).into())
+    }
+
+    /// Where core dumps and crash reports are placed, if the sandbox was
+    /// configured with one
+    #[cfg(crashdump)]
+    pub(crate) fn core_dump_dir(&self) -> Option<String> {
+        self.rt_cfg.core_dump_dir.clone()
+    }
+
+    /// The path of the guest binary, if it was loaded from a file
+    #[cfg(any(crashdump, gdb))]
+    pub(crate) fn binary_path(&self) -> Option<String> {
+        self.rt_cfg.binary_path.clone()
+    }
+
+    /// The configuration of the sandbox this VM was created for
+    #[cfg(crashdump)]
+    pub(crate) fn sandbox_config(&self) -> &SandboxConfiguration {
+        &self.rt_cfg.sandbox_config
+    }
+
     pub(crate) fn clear_cancel(&self) {
         self.interrupt_handle.clear_cancel();
     }
@@ -801,13 +1375,47 @@ impl HyperlightVm {
         &mut self,
         mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
         host_funcs: &Arc<Mutex<FunctionRegistry>>,
+        #[cfg(any(kvm, mshv3))] budget_timer: Option<&CpuBudgetTimer>,
         #[cfg(gdb)] dbg_mem_access_fn: Arc<Mutex<SandboxMemoryManager<HostSharedMemory>>>,
     ) -> std::result::Result<(), RunVmError> {
         // Keeps the trace context and open spans
         #[cfg(feature = "trace_guest")]
         let mut tc = crate::sandbox::trace::TraceContext::new();
+        #[cfg(feature = "trace_guest")]
+        if let Some(khz) = self.guest_tsc_khz {
+            tc.set_tsc_freq(khz as u64 * 1000);
+        }
+
+        // Reverse execution cannot go back past the start of this call, since
+        // the host side of previous calls has already completed
+        #[cfg(gdb)]
+        self.dbg_history.clear();
 
         let result = loop {
+            // The budget can also be used up outside of the guest, for
+            // instance by a host function, in which case the timer signal
+            // did not interrupt the vcpu
+            #[cfg(any(kvm, mshv3))]
+            if let Some(timer) = budget_timer
+                && timer.exhausted()
+            {
+                metrics::counter!(METRIC_GUEST_CANCELLATION).increment(1);
+                break Err(RunVmError::ExecutionBudgetExceeded(timer.budget()));
+            }
+
+            if let Some(resource) = self.exceeded_quota(mem_mgr) {
+                break Err(RunVmError::QuotaExceeded(resource));
+            }
+
+            // The fuel meter kicks the vcpu once the guest used up its
+            // fuel, or the guest may have used it up in a previous call
+            #[cfg(kvm)]
+            match self.check_fuel() {
+                Ok(true) => {}
+                Ok(false) => break Err(RunVmError::FuelExhausted),
+                Err(e) => break Err(RunVmError::Fuel(e)),
+            }
+
             // ===== KILL() TIMING POINT 2: Before set_tid() =====
             // If kill() is called and ran to completion BEFORE this line executes:
             //    - CANCEL_BIT will be set and we will return an early VmExit::Cancelled()
@@ -818,6 +1426,32 @@ impl HyperlightVm {
             // NOTE: `set_running()`` must be called before checking `is_cancelled()`
             // otherwise we risk missing a call to `kill()` because the vcpu would not be marked as running yet so signals won't be sent
 
+            // Pass on any trace flush request to the guest before resuming it. A request
+            // made after this point kicks the vcpu so it is passed on in the next iteration.
+            // This is not critical to correct execution of the guest, so errors are only logged.
+            #[cfg(feature = "trace_guest")]
+            if self.interrupt_handle.take_trace_flush_request()
+                && let Err(e) = mem_mgr.request_guest_trace_flush()
+            {
+                tracing::error!("Cannot request a guest trace flush: {}", e);
+            }
+
+            // Deliver the injected interrupts, one per iteration, as soon as the guest can
+            // take them. An interrupt injected after this point kicks the vcpu so it is
+            // delivered in the next iteration.
+            self.pending_interrupts |= self.interrupt_handle.take_injected_interrupts();
+            if self.pending_interrupts != 0 {
+                let index = self.pending_interrupts.trailing_zeros();
+                let more_pending = self.pending_interrupts.count_ones() > 1;
+                if self
+                    .vm
+                    .inject_interrupt(FIRST_HOST_INTERRUPT_VECTOR + index as u8, more_pending)
+                    .map_err(RunVmError::InjectInterrupt)?
+                {
+                    self.pending_interrupts &= !(1 << index);
+                }
+            }
+
             let exit_reason = if self.interrupt_handle.is_cancelled()
                 || self.interrupt_handle.is_debug_interrupted()
             {
@@ -826,10 +1460,12 @@ impl HyperlightVm {
                 // ==== KILL() TIMING POINT 3: Before calling run() ====
                 // If kill() is called and ran to completion BEFORE this line executes:
                 //    - Will still do a VM entry, but signals will be sent until VM exits
+                let entered = Instant::now();
                 let result = self.vm.run_vcpu(
                     #[cfg(feature = "trace_guest")]
                     &mut tc,
                 );
+                self.cpu_time += entered.elapsed();
 
                 // End current host trace by closing the current span that captures traces
                 // happening when a guest exits and re-enters.
@@ -868,6 +1504,12 @@ impl HyperlightVm {
             let cancel_requested = self.interrupt_handle.is_cancelled();
             let debug_interrupted = self.interrupt_handle.is_debug_interrupted();
 
+            if let Ok(exit) = &exit_reason {
+                self.exit_stats.record(exit);
+                self.metrics
+                    .record(SandboxMetric::VmExit(VmExitReason::of(exit)));
+            }
+
             // ===== KILL() TIMING POINT 6: Before checking exit_reason =====
             // If kill() is called and ran to completion BEFORE this line executes:
             //    - CANCEL_BIT will be set. Cancellation is deferred to the next iteration.
@@ -890,9 +1532,29 @@ impl HyperlightVm {
                 Ok(VmExit::Halt()) => {
                     break Ok(());
                 }
-                Ok(VmExit::IoOut(port, data)) => {
-                    self.handle_io(mem_mgr, host_funcs, port, data)?;
-                }
+                Ok(VmExit::IoOut(port, data)) => match self.handle_io(
+                    mem_mgr,
+                    host_funcs,
+                    port,
+                    data,
+                    #[cfg(gdb)]
+                    dbg_mem_access_fn.clone(),
+                ) {
+                    // The guest cannot resume the call the callback was
+                    // nested in, which already ended with the error
+                    Err(HandleIoError::Callback(e)) => return Err(*e),
+                    Err(HandleIoError::Outb(HandleOutbError::GuestAborted {
+                        code,
+                        message,
+                        exception,
+                        panic,
+                    })) => {
+                        let report =
+                            self.guest_crash_report(mem_mgr, code, message, exception, panic);
+                        return Err(RunVmError::GuestCrashed(Box::new(report)));
+                    }
+                    result => result?,
+                },
                 Ok(VmExit::MmioRead(addr)) => {
                     let all_regions = self.get_mapped_regions();
                     match get_memory_access_violation(
@@ -931,12 +1593,32 @@ impl HyperlightVm {
                         }
                     }
                 }
+                Ok(VmExit::MsrAccess { msr, write }) => {
+                    break Err(RunVmError::MsrAccessDenied { msr, write });
+                }
                 Ok(VmExit::Cancelled()) => {
                     // If cancellation was not requested for this specific guest function call,
                     // the vcpu was interrupted by a stale cancellation. This can occur when:
                     // - Linux: A signal from a previous call arrives late
                     // - Windows: WHvCancelRunVirtualProcessor called right after vcpu exits but RUNNING_BIT is still true
                     if !cancel_requested && !debug_interrupted {
+                        // The vcpu was kicked by the CPU time budget timer,
+                        // which is reported at the start of the next iteration
+                        #[cfg(any(kvm, mshv3))]
+                        if budget_timer.is_some_and(CpuBudgetTimer::exhausted) {
+                            continue;
+                        }
+                        // The vcpu was kicked by the fuel meter, which is
+                        // checked at the start of the next iteration
+                        #[cfg(kvm)]
+                        if self.fuel_meter.is_some() {
+                            continue;
+                        }
+                        // The vcpu was kicked to deliver injected interrupts,
+                        // which happens at the start of the next iteration
+                        if self.interrupt_handle.has_injected_interrupts() {
+                            continue;
+                        }
                         // Track that an erroneous vCPU kick occurred
                         metrics::counter!(METRIC_ERRONEOUS_VCPU_KICKS).increment(1);
                         // treat this the same as a VmExit::Retry, the cancel was not meant for this call
@@ -952,6 +1634,11 @@ impl HyperlightVm {
                         {
                             break Err(e.into());
                         }
+                        // An interrupt from the debugger only pauses the guest, so resume
+                        // it once the debugger continues, unless the call was also cancelled
+                        if !cancel_requested {
+                            continue;
+                        }
                     }
 
                     metrics::counter!(METRIC_GUEST_CANCELLATION).increment(1);
@@ -960,7 +1647,7 @@ impl HyperlightVm {
                 Ok(VmExit::Unknown(reason)) => {
                     break Err(RunVmError::UnexpectedVmExit(reason));
                 }
-                Ok(VmExit::Retry()) => continue,
+                Ok(VmExit::Retry()) | Ok(VmExit::MmioEmulated()) => continue,
                 Err(e) => {
                     break Err(RunVmError::RunVcpu(e));
                 }
@@ -973,12 +1660,23 @@ impl HyperlightVm {
                 // no need to crashdump this
                 Err(RunVmError::ExecutionCancelledByHost)
             }
+            Err(RunVmError::ExecutionBudgetExceeded(budget)) => {
+                // nor this, the guest did not crash
+                Err(RunVmError::ExecutionBudgetExceeded(budget))
+            }
+            Err(RunVmError::QuotaExceeded(resource)) => Err(RunVmError::QuotaExceeded(resource)),
+            Err(RunVmError::FuelExhausted) => Err(RunVmError::FuelExhausted),
             Err(e) => {
                 #[cfg(crashdump)]
                 if self.rt_cfg.guest_core_dump {
                     crashdump::generate_crashdump(self, mem_mgr, None)
                         .map_err(|e| RunVmError::CrashdumpGeneration(Box::new(e)))?;
                 }
+                #[cfg(crashdump)]
+                if self.rt_cfg.guest_crash_report {
+                    crash_report::generate_crash_report(self, mem_mgr, &e.to_string())
+                        .map_err(|e| RunVmError::CrashdumpGeneration(Box::new(e)))?;
+                }
 
                 // If GDB is enabled, we handle the debug memory access
                 // Disregard return value as we want to return the error
@@ -991,6 +1689,191 @@ impl HyperlightVm {
         }
     }
 
+    /// Describes what the guest was doing when it aborted with `code`,
+    /// `message`, `exception` and `panic`, from the registers of the vCPU
+    /// and the guest stack
+    fn guest_crash_report(
+        &mut self,
+        mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+        code: u8,
+        message: String,
+        exception: Option<Exception>,
+        panic: Option<GuestPanic>,
+    ) -> GuestCrashReport {
+        let mut report = GuestCrashReport::new(code, message, exception, panic);
+        let regs = match self.vm.regs() {
+            Ok(regs) => regs,
+            Err(e) => {
+                tracing::warn!("Cannot read the registers of the aborted guest: {}", e);
+                return report;
+            }
+        };
+        report.registers = Some(GuestCrashRegisters::new(&regs));
+
+        let Ok(root_pt) = self.get_root_pt() else {
+            return report;
+        };
+        let stack_top = self.get_stack_top();
+        let mut read = |gva, len| mem_mgr.read_guest_memory_by_gva(gva, len, root_pt).ok();
+        report.stack = read_crash_stack(regs.rsp, stack_top, &mut read);
+        report.frames = walk_crash_frames(regs.rip, regs.rbp, &mut read);
+        report
+    }
+
+    /// Calls the host function the guest called with `call` on a thread of
+    /// its own, while this thread runs the guest functions it calls back
+    /// with [`call_guest`](crate::func::callbacks::call_guest)
+    fn call_host_function_with_callbacks(
+        &mut self,
+        mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+        host_funcs: &Arc<Mutex<FunctionRegistry>>,
+        call: FunctionCall,
+        #[cfg(gdb)] dbg_mem_access_fn: Arc<Mutex<SandboxMemoryManager<HostSharedMemory>>>,
+    ) -> std::result::Result<FunctionCallResult, HandleIoError> {
+        // The host function holds the lock of the registry while it runs,
+        // so the guest functions it calls back use a registry of their own
+        let callback_funcs = Arc::new(Mutex::new(
+            host_funcs
+                .try_lock()
+                .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?
+                .for_callbacks(),
+        ));
+        let (requests, pending) = crossbeam_channel::unbounded::<CallbackRequest>();
+        let debug_events = self.debug_events.clone();
+        let interrupt_handle = self.interrupt_handle.clone();
+        // The error of the first callback the guest could not complete,
+        // after which it cannot run anything else
+        let mut fatal = None;
+
+        let result = std::thread::scope(|scope| {
+            let host_call = std::thread::Builder::new()
+                .name("hl-host-call".to_string())
+                .spawn_scoped(scope, move || {
+                    let _scope = HostCallScope::enter(interrupt_handle);
+                    let _callbacks = CallbackScope::enter(requests);
+                    call_host_function(host_funcs, &debug_events, call)
+                })
+                .map_err(|e| HandleIoError::HostCallThread(e.to_string()))?;
+
+            // Ends once the host function returned and dropped its end
+            for request in pending.iter() {
+                let name = request.call.function_name.clone();
+                let reply = if let Some(e) = &fatal {
+                    Err(new_error!(
+                        "Cannot call {} back after a callback failed: {}",
+                        name,
+                        e
+                    ))
+                } else if self.callback_depth + 1 >= MAX_CALLBACK_DEPTH {
+                    Err(new_error!(
+                        "Cannot call {} back, callbacks are nested {} levels deep",
+                        name,
+                        MAX_CALLBACK_DEPTH
+                    ))
+                } else {
+                    match self.dispatch_callback(
+                        mem_mgr,
+                        &callback_funcs,
+                        request.call,
+                        #[cfg(gdb)]
+                        dbg_mem_access_fn.clone(),
+                    ) {
+                        Ok(result) => result
+                            .into_inner()
+                            .map_err(|e| HyperlightError::GuestError(e.code, e.message)),
+                        Err(e) => {
+                            let error = new_error!("Callback {} failed: {}", name, e);
+                            fatal = Some(e);
+                            Err(error)
+                        }
+                    }
+                };
+                // The host function may have given up on the callback
+                let _ = request.reply.send(reply);
+            }
+
+            match host_call.join() {
+                Ok(result) => Ok(result?),
+                // Panics in host functions propagate as they do when the
+                // function runs on this thread
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        });
+
+        match fatal {
+            Some(e) => Err(HandleIoError::Callback(Box::new(e))),
+            None => result,
+        }
+    }
+
+    /// Runs the guest function call `call`, made by a host function, on
+    /// the stack of the guest below the frames of the guest function
+    /// waiting for the host function, which is resumed afterwards
+    fn dispatch_callback(
+        &mut self,
+        mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+        host_funcs: &Arc<Mutex<FunctionRegistry>>,
+        call: FunctionCall,
+        #[cfg(gdb)] dbg_mem_access_fn: Arc<Mutex<SandboxMemoryManager<HostSharedMemory>>>,
+    ) -> std::result::Result<FunctionCallResult, RunVmError> {
+        let NextAction::Call(dispatch_func_addr) = self.entrypoint else {
+            return Err(RunVmError::Callback(
+                "the guest is not initialised".to_string(),
+            ));
+        };
+        let saved_regs = self
+            .vm
+            .regs()
+            .map_err(|e| RunVmError::Callback(e.to_string()))?;
+        let saved_fpu = self
+            .vm
+            .fpu()
+            .map_err(|e| RunVmError::Callback(e.to_string()))?;
+
+        let mut builder = FlatBufferBuilder::new();
+        mem_mgr
+            .write_guest_function_call(call.encode(&mut builder))
+            .map_err(|e| RunVmError::Callback(e.to_string()))?;
+        // Skip the red zone of the interrupted frame, and align the stack
+        // as the dispatch function expects. The TLB is left as it is,
+        // since the guest is running.
+        let regs = CommonRegisters {
+            rip: dispatch_func_addr,
+            rsp: (saved_regs.rsp - 128) & !0xf,
+            rflags: 1 << 1,
+            ..Default::default()
+        };
+        self.vm
+            .set_regs(&regs)
+            .map_err(|e| RunVmError::Callback(e.to_string()))?;
+        self.vm
+            .set_fpu(&CommonFpu::default())
+            .map_err(|e| RunVmError::Callback(e.to_string()))?;
+
+        self.callback_depth += 1;
+        let run = self.run(
+            mem_mgr,
+            host_funcs,
+            #[cfg(any(kvm, mshv3))]
+            None,
+            #[cfg(gdb)]
+            dbg_mem_access_fn,
+        );
+        self.callback_depth -= 1;
+        run?;
+
+        let result = mem_mgr
+            .get_guest_function_call_result()
+            .map_err(|e| RunVmError::Callback(e.to_string()))?;
+        self.vm
+            .set_regs(&saved_regs)
+            .map_err(|e| RunVmError::Callback(e.to_string()))?;
+        self.vm
+            .set_fpu(&saved_fpu)
+            .map_err(|e| RunVmError::Callback(e.to_string()))?;
+        Ok(result)
+    }
+
     /// Handle an IO exit
     fn handle_io(
         &mut self,
@@ -998,6 +1881,7 @@ impl HyperlightVm {
         host_funcs: &Arc<Mutex<FunctionRegistry>>,
         port: u16,
         data: Vec<u8>,
+        #[cfg(gdb)] dbg_mem_access_fn: Arc<Mutex<SandboxMemoryManager<HostSharedMemory>>>,
     ) -> std::result::Result<(), HandleIoError> {
         if data.is_empty() {
             return Err(HandleIoError::NoData);
@@ -1011,15 +1895,67 @@ impl HyperlightVm {
             data.get(3).copied().unwrap_or(0),
         ]);
 
+        if port == OutBAction::Checkpoint as u16 {
+            return self.take_guest_checkpoint(mem_mgr);
+        }
+
+        #[cfg(feature = "mem_trace")]
+        if port == OutBAction::TraceMemoryAccess as u16 {
+            return self.trace_memory_access(mem_mgr, val);
+        }
+
+        // Let the host function called by the guest, if any, see whether
+        // the call gets cancelled
+        let _scope = HostCallScope::enter(self.interrupt_handle.clone());
+
+        if port == OutBAction::CallFunction as u16 {
+            let call = read_host_function_call(mem_mgr)?;
+            self.host_calls += 1;
+            let start = self.metrics.is_enabled().then(Instant::now);
+            let function = start.map(|_| call.function_name.clone());
+            let allows_callbacks = host_funcs
+                .try_lock()
+                .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?
+                .allows_callbacks(&call.function_name);
+            let result = if allows_callbacks {
+                self.call_host_function_with_callbacks(
+                    mem_mgr,
+                    host_funcs,
+                    call,
+                    #[cfg(gdb)]
+                    dbg_mem_access_fn,
+                )?
+            } else {
+                call_host_function(host_funcs, &self.debug_events, call)?
+            };
+            if let (Some(start), Some(function)) = (start, &function) {
+                self.metrics.record(SandboxMetric::HostCall {
+                    function,
+                    duration: start.elapsed(),
+                    failed: result.is_err(),
+                });
+            }
+            write_host_function_result(mem_mgr, &result)?;
+            return Ok(());
+        }
+
         #[cfg(feature = "mem_profile")]
         {
             let regs = self.vm.regs().map_err(HandleIoError::GetRegs)?;
-            handle_outb(mem_mgr, host_funcs, port, val, &regs, &mut self.trace_info)?;
+            handle_outb(
+                mem_mgr,
+                &self.debug_events,
+                &mut self.guest_logs,
+                port,
+                val,
+                &regs,
+                &mut self.trace_info,
+            )?;
         }
 
         #[cfg(not(feature = "mem_profile"))]
         {
-            handle_outb(mem_mgr, host_funcs, port, val)?;
+            handle_outb(mem_mgr, &self.debug_events, &mut self.guest_logs, port, val)?;
         }
 
         Ok(())
@@ -1078,6 +2014,22 @@ impl HyperlightVm {
             return Err(HandleDebugError::DebugNotEnabled);
         }
 
+        // When range stepping, keep stepping without stopping as long as
+        // the vCPU stays in the range
+        if let Some(range) = self.dbg_step_range.take()
+            && matches!(stop_reason, VcpuStopReason::DoneStep)
+        {
+            let rip = self
+                .vm
+                .regs()
+                .map_err(|e| ProcessDebugRequestError::Vm(VmError::Register(e)))?
+                .pc();
+            if range.contains(&rip) {
+                self.dbg_step_range = Some(range);
+                return Ok(());
+            }
+        }
+
         let mem_access = DebugMemoryAccess {
             // TODO: dbg_mem_access_fn could be out of sync with the
             // actual snapshot/scratch regions, if a snapshot restore
@@ -1112,14 +2064,20 @@ impl HyperlightVm {
                             DebugResponse::DisableDebug
                         }
                         // Do not allow continue or step requests
-                        DebugMsg::Continue | DebugMsg::Step => {
+                        DebugMsg::Continue
+                        | DebugMsg::Step
+                        | DebugMsg::RangeStep(..)
+                        | DebugMsg::ReverseContinue
+                        | DebugMsg::ReverseStep => {
                             deny_continue = true;
                             DebugResponse::NotAllowed
                         }
                         // Do not allow adding/removing breakpoints and writing to memory or registers
                         DebugMsg::AddHwBreakpoint(_)
+                        | DebugMsg::AddHwWatchpoint(..)
                         | DebugMsg::AddSwBreakpoint(_)
                         | DebugMsg::RemoveHwBreakpoint(_)
+                        | DebugMsg::RemoveHwWatchpoint(..)
                         | DebugMsg::RemoveSwBreakpoint(_)
                         | DebugMsg::WriteAddr(_, _)
                         | DebugMsg::WriteRegisters(_) => DebugResponse::NotAllowed,
@@ -1130,12 +2088,7 @@ impl HyperlightVm {
                             match result {
                                 Ok(response) => response,
                                 // Treat non-fatal errors separately so the guest doesn't fail
-                                Err(ProcessDebugRequestError::ReadMemory(
-                                    DebugMemoryAccessError::TranslateGuestAddress(_),
-                                ))
-                                | Err(ProcessDebugRequestError::Debug(DebugError::TranslateGva(
-                                    _,
-                                ))) => DebugResponse::ErrorOccurred,
+                                Err(e) if e.is_bad_address() => DebugResponse::ErrorOccurred,
                                 Err(e) => {
                                     log::error!("Error processing debug request: {:?}", e);
                                     return Err(HandleDebugError::ProcessRequest(e));
@@ -1164,6 +2117,12 @@ impl HyperlightVm {
             // If the vCPU stopped because of any other reason except a crash, we can handle it
             // normally
             _ => {
+                // Let the debug event subscribers know why the vCPU stopped
+                self.emit_dbg_stop_event(stop_reason)?;
+
+                // Remember the state at this stop so the debugger can go back to it
+                self.record_dbg_checkpoint(&mem_access, stop_reason)?;
+
                 // Send the stop reason to the gdb thread
                 self.send_dbg_msg(DebugResponse::VcpuStopped(stop_reason))?;
 
@@ -1177,12 +2136,7 @@ impl HyperlightVm {
                     let response = match result {
                         Ok(response) => response,
                         // Treat non-fatal errors separately so the guest doesn't fail
-                        Err(ProcessDebugRequestError::ReadMemory(
-                            DebugMemoryAccessError::TranslateGuestAddress(_),
-                        ))
-                        | Err(ProcessDebugRequestError::Debug(DebugError::TranslateGva(_))) => {
-                            DebugResponse::ErrorOccurred
-                        }
+                        Err(e) if e.is_bad_address() => DebugResponse::ErrorOccurred,
                         Err(e) => {
                             return Err(HandleDebugError::ProcessRequest(e));
                         }
@@ -1190,11 +2144,25 @@ impl HyperlightVm {
 
                     let cont = matches!(
                         response,
-                        DebugResponse::Continue | DebugResponse::Step | DebugResponse::DisableDebug
+                        DebugResponse::Continue
+                            | DebugResponse::Step
+                            | DebugResponse::RangeStep
+                            | DebugResponse::DisableDebug
                     );
+                    let reverse_stop = match &response {
+                        DebugResponse::ReverseContinue(reason)
+                        | DebugResponse::ReverseStep(reason) => Some(*reason),
+                        _ => None,
+                    };
 
                     self.send_dbg_msg(response)?;
 
+                    // Reverse execution restores a previous state without running the vCPU,
+                    // so the new stop is reported right away
+                    if let Some(reason) = reverse_stop {
+                        self.send_dbg_msg(DebugResponse::VcpuStopped(reason))?;
+                    }
+
                     // Check if we should continue execution
                     // We continue if the response is one of the following: Step, Continue, or DisableDebug
                     if cont {
@@ -1213,78 +2181,57 @@ impl HyperlightVm {
         mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
     ) -> std::result::Result<Option<super::crashdump::CrashDumpContext>, CrashDumpError> {
         if self.rt_cfg.guest_core_dump {
-            let mut regs = [0; 27];
-
-            let vcpu_regs = self.vm.regs()?;
-            let sregs = self.vm.sregs()?;
-            let xsave = self.vm.xsave()?;
-
-            // Set up the registers for the crash dump
-            regs[0] = vcpu_regs.r15; // r15
-            regs[1] = vcpu_regs.r14; // r14
-            regs[2] = vcpu_regs.r13; // r13
-            regs[3] = vcpu_regs.r12; // r12
-            regs[4] = vcpu_regs.rbp; // rbp
-            regs[5] = vcpu_regs.rbx; // rbx
-            regs[6] = vcpu_regs.r11; // r11
-            regs[7] = vcpu_regs.r10; // r10
-            regs[8] = vcpu_regs.r9; // r9
-            regs[9] = vcpu_regs.r8; // r8
-            regs[10] = vcpu_regs.rax; // rax
-            regs[11] = vcpu_regs.rcx; // rcx
-            regs[12] = vcpu_regs.rdx; // rdx
-            regs[13] = vcpu_regs.rsi; // rsi
-            regs[14] = vcpu_regs.rdi; // rdi
-            regs[15] = 0; // orig rax
-            regs[16] = vcpu_regs.rip; // rip
-            regs[17] = sregs.cs.selector as u64; // cs
-            regs[18] = vcpu_regs.rflags; // eflags
-            regs[19] = vcpu_regs.rsp; // rsp
-            regs[20] = sregs.ss.selector as u64; // ss
-            regs[21] = sregs.fs.base; // fs_base
-            regs[22] = sregs.gs.base; // gs_base
-            regs[23] = sregs.ds.selector as u64; // ds
-            regs[24] = sregs.es.selector as u64; // es
-            regs[25] = sregs.fs.selector as u64; // fs
-            regs[26] = sregs.gs.selector as u64; // gs
-
-            // Get the filename from the binary path
-            let filename = self.rt_cfg.binary_path.clone().and_then(|path| {
-                Path::new(&path)
-                    .file_name()
-                    .and_then(|name| name.to_os_string().into_string().ok())
-            });
-
-            // Use the stored entry point address from the runtime config.
-            // This is the original entry point (load_addr + ELF entry offset)
-            // which GDB needs for AT_ENTRY to compute the PIE load offset.
-            // We cannot use self.entrypoint here because it transitions from
-            // Initialise(addr) to Call(dispatch_addr) after guest init.
-            let initialise = self.rt_cfg.entry_point.unwrap_or_else(|| {
-                tracing::warn!(
-                    "entry_point was never set in SandboxRuntimeConfig; AT_ENTRY will be 0"
-                );
-                0
-            });
-            let mmap_regions: Vec<MemoryRegion> = self.get_mapped_regions().cloned().collect();
-            let root_pt = self.get_root_pt()?;
-
-            let regions = mem_mgr
-                .get_guest_memory_regions(root_pt, &mmap_regions)
-                .map_err(|e| CrashDumpError::AccessPageTable(Box::new(e)))?;
-
-            Ok(Some(crashdump::CrashDumpContext::new(
-                regions,
-                regs,
-                xsave.to_vec(),
-                initialise,
-                self.rt_cfg.binary_path.clone(),
-                filename,
-            )))
+            self.capture_crash_context(mem_mgr).map(Some)
         } else {
             Ok(None)
         }
     }
+
+    /// Captures the state of the guest, regardless of whether the sandbox
+    /// is configured to allow core dumps
+    #[cfg(crashdump)]
+    pub(crate) fn capture_crash_context(
+        &self,
+        mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    ) -> std::result::Result<super::crashdump::CrashDumpContext, CrashDumpError> {
+        let vcpu_regs = self.vm.regs()?;
+        let sregs = self.vm.sregs()?;
+        let xsave = self.vm.xsave()?;
+
+        let regs = CoreRegisters::new(&vcpu_regs, &sregs);
+
+        // Get the filename from the binary path
+        let filename = self.rt_cfg.binary_path.clone().and_then(|path| {
+            Path::new(&path)
+                .file_name()
+                .and_then(|name| name.to_os_string().into_string().ok())
+        });
+
+        // Use the stored entry point address from the runtime config.
+        // This is the original entry point (load_addr + ELF entry offset)
+        // which GDB needs for AT_ENTRY to compute the PIE load offset.
+        // We cannot use self.entrypoint here because it transitions from
+        // Initialise(addr) to Call(dispatch_addr) after guest init.
+        let initialise = self.rt_cfg.entry_point.unwrap_or_else(|| {
+            tracing::warn!("entry_point was never set in SandboxRuntimeConfig; AT_ENTRY will be 0");
+            0
+        });
+        let mmap_regions: Vec<MemoryRegion> = self.get_mapped_regions().cloned().collect();
+        let root_pt = self.get_root_pt()?;
+
+        let regions = mem_mgr
+            .get_guest_memory_regions(root_pt, &mmap_regions)
+            .map_err(|e| CrashDumpError::AccessPageTable(Box::new(e)))?;
+
+        Ok(crashdump::CrashDumpContext::new(
+            regions,
+            regs,
+            xsave.to_vec(),
+            initialise,
+            self.rt_cfg.binary_path.clone(),
+            filename,
+        ))
+    }
 }
 
 impl Drop for HyperlightVm {
@@ -1317,14 +2264,23 @@ fn get_memory_access_violation<'a>(
 
 #[cfg(gdb)]
 mod debug {
+    use std::fmt::Write;
+
     use hyperlight_common::mem::PAGE_SIZE;
 
     use super::HyperlightVm;
+    use crate::hypervisor::arch::GuestRegisters;
     use crate::hypervisor::gdb::arch::{SW_BP, SW_BP_SIZE};
+    use crate::hypervisor::gdb::history::DebugCheckpoint;
+    use crate::hypervisor::gdb::monitor::split_command;
     use crate::hypervisor::gdb::{
         DebugError, DebugMemoryAccess, DebugMemoryAccessError, DebugMsg, DebugResponse,
+        VcpuStopReason,
     };
     use crate::hypervisor::virtual_machine::VmError;
+    use crate::mem::layout::SandboxMemoryLayout;
+    use crate::mem::shared_mem::SharedMemory;
+    use crate::sandbox::debug_events::DebugEvent;
 
     /// Errors that can occur during GDB debug request processing
     #[derive(Debug, thiserror::Error)]
@@ -1345,6 +2301,19 @@ mod debug {
         WriteMemory(DebugMemoryAccessError),
     }
 
+    impl ProcessDebugRequestError {
+        /// Whether the request failed because the debugger asked for an
+        /// address that cannot be accessed, which is reported back to the
+        /// debugger rather than failing the guest
+        pub(crate) fn is_bad_address(&self) -> bool {
+            match self {
+                Self::ReadMemory(e) | Self::WriteMemory(e) => e.is_bad_address(),
+                Self::Debug(DebugError::TranslateGva(_)) => true,
+                _ => false,
+            }
+        }
+    }
+
     impl HyperlightVm {
         pub(crate) fn process_dbg_request(
             &mut self,
@@ -1363,6 +2332,18 @@ mod debug {
                             })
                             .is_ok(),
                     )),
+                    DebugMsg::AddHwWatchpoint(addr, len, kind) => {
+                        Ok(DebugResponse::AddHwWatchpoint(
+                            self.vm
+                                .add_hw_watchpoint(addr, len, kind)
+                                .map_err(|e| {
+                                    log::error!("Failed to add hw watchpoint: {:?}", e);
+
+                                    e
+                                })
+                                .is_ok(),
+                        ))
+                    }
                     DebugMsg::AddSwBreakpoint(addr) => Ok(DebugResponse::AddSwBreakpoint(
                         self.add_sw_breakpoint(addr, mem_access)
                             .map_err(|e| {
@@ -1382,6 +2363,12 @@ mod debug {
                         Ok(DebugResponse::Continue)
                     }
                     DebugMsg::DisableDebug => {
+                        // The guest keeps running without a debugger, so it must not
+                        // hit any of the INT3 instructions that were left behind
+                        self.remove_all_sw_breakpoints(mem_access).map_err(|e| {
+                            log::error!("Failed to remove software breakpoints: {:?}", e);
+                            e
+                        })?;
                         self.vm.set_debug(false).map_err(|e| {
                             log::error!("Failed to disable debugging: {:?}", e);
                             e
@@ -1399,6 +2386,23 @@ mod debug {
 
                         Ok(DebugResponse::GetCodeSectionOffset(offset as u64))
                     }
+                    DebugMsg::GetEntryPoint => Ok(DebugResponse::GetEntryPoint(
+                        self.rt_cfg.entry_point.unwrap_or_default(),
+                    )),
+                    DebugMsg::GetExecFile => {
+                        // gdb resolves relative paths from its own working
+                        // directory, which may differ from the host's
+                        let path = self.rt_cfg.binary_path.as_ref().map(|path| {
+                            std::fs::canonicalize(path)
+                                .map(|path| path.to_string_lossy().into_owned())
+                                .unwrap_or_else(|_| path.clone())
+                        });
+
+                        Ok(DebugResponse::GetExecFile(path))
+                    }
+                    DebugMsg::MonitorCommand(cmd) => Ok(DebugResponse::MonitorCommand(
+                        self.run_monitor_command(&cmd, mem_access),
+                    )),
                     DebugMsg::ReadAddr(addr, len) => {
                         let mut data = vec![0u8; len];
 
@@ -1410,10 +2414,21 @@ mod debug {
 
                         Ok(DebugResponse::ReadAddr(data))
                     }
+                    DebugMsg::RangeStep(start, end) => {
+                        self.vm.set_single_step(true).map_err(|e| {
+                            log::error!("Failed to enable step instruction: {:?}", e);
+
+                            e
+                        })?;
+                        self.dbg_step_range = Some(start..end);
+
+                        Ok(DebugResponse::RangeStep)
+                    }
                     DebugMsg::ReadRegisters => {
                         let regs = self.vm.regs().map_err(VmError::Register)?;
                         let fpu = self.vm.fpu().map_err(VmError::Register)?;
-                        Ok(DebugResponse::ReadRegisters(Box::new((regs, fpu))))
+                        let sregs = self.vm.sregs().map_err(VmError::Register)?;
+                        Ok(DebugResponse::ReadRegisters(Box::new((regs, fpu, sregs))))
                     }
                     DebugMsg::RemoveHwBreakpoint(addr) => Ok(DebugResponse::RemoveHwBreakpoint(
                         self.vm
@@ -1425,6 +2440,18 @@ mod debug {
                             })
                             .is_ok(),
                     )),
+                    DebugMsg::RemoveHwWatchpoint(addr, len, kind) => {
+                        Ok(DebugResponse::RemoveHwWatchpoint(
+                            self.vm
+                                .remove_hw_watchpoint(addr, len, kind)
+                                .map_err(|e| {
+                                    log::error!("Failed to remove hw watchpoint: {:?}", e);
+
+                                    e
+                                })
+                                .is_ok(),
+                        ))
+                    }
                     DebugMsg::RemoveSwBreakpoint(addr) => Ok(DebugResponse::RemoveSwBreakpoint(
                         self.remove_sw_breakpoint(addr, mem_access)
                             .map_err(|e| {
@@ -1434,6 +2461,30 @@ mod debug {
                             })
                             .is_ok(),
                     )),
+                    DebugMsg::ReverseContinue => {
+                        let went_back = self.dbg_history.rewind_to_breakpoint().is_some();
+                        // Going back to the oldest stop without finding a breakpoint means
+                        // there is nothing more to go back to
+                        let reason = match self.go_back_to(went_back, mem_access)? {
+                            Some(
+                                reason @ (VcpuStopReason::SwBp
+                                | VcpuStopReason::HwBp
+                                | VcpuStopReason::EntryPointBp),
+                            ) => reason,
+                            _ => VcpuStopReason::HistoryExhausted,
+                        };
+
+                        Ok(DebugResponse::ReverseContinue(reason))
+                    }
+                    DebugMsg::ReverseStep => {
+                        let went_back = self.dbg_history.step_back().is_some();
+                        let reason = match self.go_back_to(went_back, mem_access)? {
+                            Some(_) => VcpuStopReason::DoneStep,
+                            None => VcpuStopReason::HistoryExhausted,
+                        };
+
+                        Ok(DebugResponse::ReverseStep(reason))
+                    }
                     DebugMsg::Step => {
                         self.vm.set_single_step(true).map_err(|e| {
                             log::error!("Failed to enable step instruction: {:?}", e);
@@ -1453,9 +2504,14 @@ mod debug {
                         Ok(DebugResponse::WriteAddr)
                     }
                     DebugMsg::WriteRegisters(boxed_regs) => {
-                        let (regs, fpu) = boxed_regs.as_ref();
+                        let (regs, fpu, sregs) = boxed_regs.as_ref();
                         self.vm.set_regs(regs).map_err(VmError::Register)?;
                         self.vm.set_fpu(fpu).map_err(VmError::Register)?;
+                        // Only the segment selectors can be changed from gdb, so
+                        // avoid writing back the special registers when unchanged
+                        if *sregs != self.vm.sregs().map_err(VmError::Register)? {
+                            self.vm.set_sregs(sregs).map_err(VmError::Register)?;
+                        }
 
                         Ok(DebugResponse::WriteRegisters)
                     }
@@ -1465,6 +2521,90 @@ mod debug {
             }
         }
 
+        /// Runs a monitor command sent by gdb and returns its output.
+        /// Failures are reported in the output, as gdb only shows the
+        /// output of the command.
+        fn run_monitor_command(&self, cmd: &str, mem_access: &DebugMemoryAccess) -> String {
+            let (name, args) = split_command(cmd);
+            let mut output = match name {
+                "" | "help" => self.rt_cfg.monitor_commands.help(),
+                "layout" => match mem_access.dbg_mem_access_fn.try_lock() {
+                    Ok(mgr) => {
+                        let mut out = format!("{:#?}\n", mgr.layout);
+                        if !mem_access.guest_mmap_regions.is_empty() {
+                            out.push_str("Mapped regions:\n");
+                        }
+                        for region in &mem_access.guest_mmap_regions {
+                            let _ = writeln!(
+                                out,
+                                "  {:#x}..{:#x} {:?} {:?}",
+                                region.guest_region.start,
+                                region.guest_region.end,
+                                region.region_type,
+                                region.flags
+                            );
+                        }
+                        out
+                    }
+                    Err(_) => "The memory manager is in use, try again".to_string(),
+                },
+                "snapshot" => {
+                    let mut out = String::new();
+                    if let Some(snapshot) = &self.snapshot_memory {
+                        let base = SandboxMemoryLayout::BASE_ADDRESS;
+                        let size = snapshot.mem_size();
+                        let _ = writeln!(
+                            out,
+                            "snapshot: {:#x}..{:#x} ({:#x} bytes)",
+                            base,
+                            base + size,
+                            size
+                        );
+                    }
+                    if let Some(scratch) = &self.scratch_memory {
+                        let size = scratch.mem_size();
+                        let base = hyperlight_common::layout::scratch_base_gpa(size);
+                        let _ = writeln!(
+                            out,
+                            "scratch:  {:#x}..{:#x} ({:#x} bytes)",
+                            base,
+                            base + size as u64,
+                            size
+                        );
+                    }
+                    let _ = writeln!(out, "mapped regions: {}", self.mmap_regions.len());
+                    out
+                }
+                #[cfg(feature = "trace_guest")]
+                "trace-flush" => match mem_access.dbg_mem_access_fn.try_lock() {
+                    Ok(mut mgr) => match mgr.request_guest_trace_flush() {
+                        Ok(()) => {
+                            "The guest will send its trace data once it resumes".to_string()
+                        }
+                        Err(e) => format!("Cannot request a guest trace flush: {}", e),
+                    },
+                    Err(_) => "The memory manager is in use, try again".to_string(),
+                },
+                #[cfg(not(feature = "trace_guest"))]
+                "trace-flush" => {
+                    "Guest tracing is not available, the host was built without the trace_guest feature"
+                        .to_string()
+                }
+                name => self
+                    .rt_cfg
+                    .monitor_commands
+                    .run(name, args)
+                    .unwrap_or_else(|| {
+                        format!("Unknown command {}, see `monitor help`", name)
+                    }),
+            };
+
+            if !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output
+        }
+
         pub(crate) fn recv_dbg_msg(
             &mut self,
         ) -> std::result::Result<DebugMsg, super::RecvDbgMsgError> {
@@ -1494,6 +2634,127 @@ mod debug {
             Ok(gdb_conn.send(cmd)?)
         }
 
+        /// Reports breakpoint and single step stops to the debug event
+        /// subscribers
+        pub(crate) fn emit_dbg_stop_event(
+            &self,
+            stop_reason: VcpuStopReason,
+        ) -> std::result::Result<(), ProcessDebugRequestError> {
+            let rip = || {
+                self.vm
+                    .regs()
+                    .map(|regs| regs.pc())
+                    .map_err(VmError::Register)
+            };
+            let event = match stop_reason {
+                VcpuStopReason::SwBp | VcpuStopReason::HwBp | VcpuStopReason::EntryPointBp => {
+                    DebugEvent::BreakpointHit { rip: rip()? }
+                }
+                VcpuStopReason::Watchpoint { addr, .. } => {
+                    DebugEvent::WatchpointHit { rip: rip()?, addr }
+                }
+                VcpuStopReason::DoneStep => DebugEvent::StepComplete { rip: rip()? },
+                _ => return Ok(()),
+            };
+            self.debug_events.emit(event);
+
+            Ok(())
+        }
+
+        /// Captures the vCPU and memory state of the current stop so that
+        /// reverse execution requests can go back to it later
+        pub(crate) fn record_dbg_checkpoint(
+            &mut self,
+            mem_access: &DebugMemoryAccess,
+            stop_reason: VcpuStopReason,
+        ) -> std::result::Result<(), ProcessDebugRequestError> {
+            if !self.dbg_history.is_enabled() {
+                return Ok(());
+            }
+            let regs = self.vm.regs().map_err(VmError::Register)?;
+            let fpu = self.vm.fpu().map_err(VmError::Register)?;
+            let sregs = self.vm.sregs().map_err(VmError::Register)?;
+
+            let mgr = mem_access
+                .dbg_mem_access_fn
+                .try_lock()
+                .map_err(|_| ProcessDebugRequestError::TryLockError(file!(), line!()))?;
+            self.dbg_history
+                .push(
+                    DebugCheckpoint {
+                        regs,
+                        fpu,
+                        sregs,
+                        sw_breakpoints: self.sw_breakpoints.clone(),
+                        stop_reason,
+                    },
+                    &mgr.shared_mem,
+                    &mgr.scratch_mem,
+                )
+                .map_err(|e| DebugMemoryAccessError::CopyFailed(Box::new(e)))?;
+
+            Ok(())
+        }
+
+        /// Restores the state of the current stop of the debug history, which
+        /// the history has just gone back to if `went_back`, and returns the
+        /// reason the vCPU had stopped there.
+        /// If there is no earlier stop to go back to, the state is left as is
+        /// and `None` is returned.
+        fn go_back_to(
+            &mut self,
+            went_back: bool,
+            mem_access: &DebugMemoryAccess,
+        ) -> std::result::Result<Option<VcpuStopReason>, ProcessDebugRequestError> {
+            if !went_back {
+                return Ok(None);
+            }
+
+            {
+                let mgr = mem_access
+                    .dbg_mem_access_fn
+                    .try_lock()
+                    .map_err(|_| ProcessDebugRequestError::TryLockError(file!(), line!()))?;
+                self.dbg_history
+                    .write_back(&mgr.shared_mem, &mgr.scratch_mem)
+                    .map_err(|e| {
+                        ProcessDebugRequestError::WriteMemory(DebugMemoryAccessError::CopyFailed(
+                            Box::new(e),
+                        ))
+                    })?;
+            }
+
+            let Some(checkpoint) = self.dbg_history.current() else {
+                return Ok(None);
+            };
+
+            self.vm
+                .set_sregs(&checkpoint.sregs)
+                .map_err(VmError::Register)?;
+            self.vm
+                .set_regs(&checkpoint.regs)
+                .map_err(VmError::Register)?;
+            self.vm
+                .set_fpu(&checkpoint.fpu)
+                .map_err(VmError::Register)?;
+            let reason = checkpoint.stop_reason;
+            let checkpoint_breakpoints = checkpoint.sw_breakpoints.clone();
+
+            // The restored memory contains the software breakpoints that were set when
+            // the checkpoint was taken, so bring it in sync with the current ones
+            for (addr, orig) in checkpoint_breakpoints.iter() {
+                if !self.sw_breakpoints.contains_key(addr) {
+                    self.write_addrs(*addr, &[*orig], mem_access)?;
+                }
+            }
+            let current: Vec<u64> = self.sw_breakpoints.keys().copied().collect();
+            for addr in current {
+                self.write_addrs(addr, &SW_BP, mem_access)?;
+            }
+
+            Ok(Some(reason))
+        }
+
         fn read_addrs(
             &mut self,
             mut gva: u64,
@@ -1587,6 +2848,19 @@ mod debug {
                 Err(ProcessDebugRequestError::SwBreakpointNotFound(gva))
             }
         }
+
+        /// Restores the original instruction at every software breakpoint
+        fn remove_all_sw_breakpoints(
+            &mut self,
+            mem_access: &DebugMemoryAccess,
+        ) -> std::result::Result<(), ProcessDebugRequestError> {
+            let addrs: Vec<u64> = self.sw_breakpoints.keys().copied().collect();
+            for addr in addrs {
+                self.remove_sw_breakpoint(addr, mem_access)?;
+            }
+
+            Ok(())
+        }
     }
 }
 
@@ -1603,7 +2877,7 @@ mod tests {
     #[cfg(kvm)]
     use crate::hypervisor::regs::FP_CONTROL_WORD_DEFAULT;
     use crate::hypervisor::regs::{CommonSegmentRegister, CommonTableRegister, MXCSR_DEFAULT};
-    use crate::hypervisor::virtual_machine::VirtualMachine;
+    use crate::hypervisor::virtual_machine::{VirtualMachine, get_available_hypervisor};
     use crate::mem::layout::SandboxMemoryLayout;
     use crate::mem::memory_region::{GuestMemoryRegion, MemoryRegionFlags};
     use crate::mem::mgr::{GuestPageTableBuffer, SandboxMemoryManager};
@@ -2230,6 +3504,7 @@ mod tests {
             #[cfg(any(crashdump, gdb))]
             rt_cfg,
             crate::mem::exe::LoadInfo::dummy(),
+            &mut CreationReport::default(),
         )
         .unwrap();
 
@@ -2808,6 +4083,8 @@ mod tests {
                     .run(
                         &mut self.ctx.hshm,
                         &self.ctx.host_funcs,
+                        #[cfg(any(kvm, mshv3))]
+                        None,
                         #[cfg(gdb)]
                         self.ctx.dbg_mem_access_hdl.clone(),
                     )
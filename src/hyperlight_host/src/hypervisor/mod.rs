@@ -21,8 +21,23 @@ pub(crate) mod gdb;
 /// Abstracts over different hypervisor register representations
 pub(crate) mod regs;
 
+/// Abstracts over the architectures of the guest registers
+#[cfg(any(gdb, crashdump, feature = "mem_profile", feature = "trace_guest"))]
+pub(crate) mod arch;
+
 pub(crate) mod virtual_machine;
 
+/// The CPU affinity and the scheduling priority of the vCPU threads
+pub(crate) mod vcpu_thread;
+
+/// The CPU time budget of guest function calls
+#[cfg(any(kvm, mshv3))]
+pub(crate) mod cpu_budget;
+
+/// The hardware performance counters of the guest
+#[cfg(kvm)]
+pub(crate) mod perf_counters;
+
 #[cfg(target_os = "windows")]
 /// Hyperlight Surrogate Process
 pub(crate) mod surrogate_process;
@@ -33,6 +48,8 @@ pub(crate) mod surrogate_process_manager;
 #[cfg(target_os = "windows")]
 pub(crate) mod wrappers;
 
+#[cfg(crashdump)]
+pub(crate) mod crash_report;
 #[cfg(crashdump)]
 pub(crate) mod crashdump;
 
@@ -40,12 +57,14 @@ pub(crate) mod hyperlight_vm;
 
 use std::fmt::Debug;
 #[cfg(any(kvm, mshv3))]
-use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU16, AtomicU64, Ordering};
 #[cfg(target_os = "windows")]
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicU8, AtomicU16, Ordering};
 #[cfg(any(kvm, mshv3))]
 use std::time::Duration;
 
+use hyperlight_common::interrupt::{FIRST_HOST_INTERRUPT_VECTOR, is_host_interrupt_vector};
+
 /// A trait for platform-specific interrupt handle implementation details
 pub(crate) trait InterruptHandleImpl: InterruptHandle {
     /// Set the thread ID for the vcpu thread
@@ -73,6 +92,17 @@ pub(crate) trait InterruptHandleImpl: InterruptHandle {
     // Clear the debug interrupt request flag
     #[cfg(gdb)]
     fn clear_debug_interrupt(&self);
+
+    /// Check and clear the guest trace flush request flag
+    #[cfg(feature = "trace_guest")]
+    fn take_trace_flush_request(&self) -> bool;
+
+    /// Take the interrupts injected since the last call, as a bitmask
+    /// indexed by vector from `FIRST_HOST_INTERRUPT_VECTOR`
+    fn take_injected_interrupts(&self) -> u16;
+
+    /// Check if interrupts were injected and not taken yet
+    fn has_injected_interrupts(&self) -> bool;
 }
 
 /// A trait for handling interrupts to a sandbox's vcpu
@@ -98,6 +128,28 @@ pub trait InterruptHandle: Send + Sync + Debug {
     #[cfg(gdb)]
     fn kill_from_debugger(&self) -> bool;
 
+    /// Asks the guest to send the trace data it has buffered so far to the host,
+    /// without waiting for the current guest function call to finish.
+    ///
+    /// The guest sends its trace data the next time it records a span or an event
+    /// after the request. If the vcpu is running, it is interrupted and resumed so
+    /// the request reaches the guest right away, and `true` is returned. Otherwise
+    /// the request is deferred to the next time the vcpu runs, and `false` is returned.
+    #[cfg(feature = "trace_guest")]
+    fn flush_guest_traces(&self) -> bool;
+
+    /// Injects the interrupt `vector` into the guest, whose handler for it runs
+    /// as soon as the guest has interrupts enabled, which guests do while running
+    /// a guest function once they have installed an interrupt handler.
+    ///
+    /// `vector` must be one of the `HOST_INTERRUPT_VECTOR_COUNT` vectors starting
+    /// at `FIRST_HOST_INTERRUPT_VECTOR`, other vectors are ignored and `false` is
+    /// returned. If the vcpu is running, it is interrupted and resumed so the
+    /// interrupt reaches the guest right away, and `true` is returned. Otherwise
+    /// the interrupt stays pending until the next time the vcpu runs, and `false`
+    /// is returned.
+    fn inject_interrupt(&self, vector: u8) -> bool;
+
     /// Returns true if the corresponding sandbox has been dropped
     fn dropped(&self) -> bool;
 }
@@ -108,6 +160,8 @@ pub(super) struct LinuxInterruptHandle {
     /// Atomic value packing vcpu execution state.
     ///
     /// Bit layout:
+    /// - Bit 4: INJECT_BIT - set when interrupts were injected and not taken yet
+    /// - Bit 3: TRACE_FLUSH_BIT - set when a guest trace flush is requested
     /// - Bit 2: DEBUG_INTERRUPT_BIT - set when debugger interrupt is requested
     /// - Bit 1: RUNNING_BIT - set when vcpu is actively running
     /// - Bit 0: CANCEL_BIT - set when cancellation has been requested
@@ -116,6 +170,9 @@ pub(super) struct LinuxInterruptHandle {
     /// (e.g., during host function calls), but is cleared at the start of each new `VirtualCPU::run()` call.
     state: AtomicU8,
 
+    /// Interrupts injected and not taken yet, indexed by vector from `FIRST_HOST_INTERRUPT_VECTOR`
+    pending_interrupts: AtomicU16,
+
     /// Thread ID where the vcpu is running.
     ///
     /// Note: Multiple VMs may have the same `tid` (same thread runs multiple sandboxes sequentially),
@@ -138,6 +195,9 @@ impl LinuxInterruptHandle {
     const CANCEL_BIT: u8 = 1 << 0;
     #[cfg(gdb)]
     const DEBUG_INTERRUPT_BIT: u8 = 1 << 2;
+    #[cfg(feature = "trace_guest")]
+    const TRACE_FLUSH_BIT: u8 = 1 << 3;
+    const INJECT_BIT: u8 = 1 << 4;
 
     /// Get the running, cancel and debug flags atomically.
     ///
@@ -152,6 +212,12 @@ impl LinuxInterruptHandle {
         let debug = state & Self::DEBUG_INTERRUPT_BIT != 0;
         #[cfg(not(gdb))]
         let debug = false;
+        // A trace flush request is handled like a debug interrupt: the vcpu
+        // is kicked until it exits and the vcpu thread takes the request
+        #[cfg(feature = "trace_guest")]
+        let debug = debug || state & Self::TRACE_FLUSH_BIT != 0;
+        // So are injected interrupts
+        let debug = debug || state & Self::INJECT_BIT != 0;
         (running, cancel, debug)
     }
 
@@ -236,6 +302,25 @@ impl InterruptHandleImpl for LinuxInterruptHandle {
             .fetch_and(!Self::DEBUG_INTERRUPT_BIT, Ordering::Release);
     }
 
+    #[cfg(feature = "trace_guest")]
+    fn take_trace_flush_request(&self) -> bool {
+        self.state
+            .fetch_and(!Self::TRACE_FLUSH_BIT, Ordering::AcqRel)
+            & Self::TRACE_FLUSH_BIT
+            != 0
+    }
+
+    fn take_injected_interrupts(&self) -> u16 {
+        // The bit is cleared first, so an interrupt injected concurrently
+        // is either taken here or leaves the bit set for the next call
+        self.state.fetch_and(!Self::INJECT_BIT, Ordering::AcqRel);
+        self.pending_interrupts.swap(0, Ordering::AcqRel)
+    }
+
+    fn has_injected_interrupts(&self) -> bool {
+        self.state.load(Ordering::Acquire) & Self::INJECT_BIT != 0
+    }
+
     fn set_dropped(&self) {
         // Release ordering to ensure all VM cleanup operations are visible
         // to any thread that checks dropped() via Acquire
@@ -260,6 +345,25 @@ impl InterruptHandle for LinuxInterruptHandle {
             .fetch_or(Self::DEBUG_INTERRUPT_BIT, Ordering::Release);
         self.send_signal()
     }
+
+    #[cfg(feature = "trace_guest")]
+    fn flush_guest_traces(&self) -> bool {
+        self.state
+            .fetch_or(Self::TRACE_FLUSH_BIT, Ordering::Release);
+        self.send_signal()
+    }
+
+    fn inject_interrupt(&self, vector: u8) -> bool {
+        if !is_host_interrupt_vector(vector) {
+            return false;
+        }
+        self.pending_interrupts.fetch_or(
+            1 << (vector - FIRST_HOST_INTERRUPT_VECTOR),
+            Ordering::Release,
+        );
+        self.state.fetch_or(Self::INJECT_BIT, Ordering::Release);
+        self.send_signal()
+    }
     fn dropped(&self) -> bool {
         // Acquire ordering to synchronize with the Release in set_dropped()
         // This ensures we see all VM cleanup operations that happened before drop
@@ -273,6 +377,8 @@ pub(super) struct WindowsInterruptHandle {
     /// Atomic value packing vcpu execution state.
     ///
     /// Bit layout:
+    /// - Bit 4: INJECT_BIT - set when interrupts were injected and not taken yet
+    /// - Bit 3: TRACE_FLUSH_BIT - set when a guest trace flush is requested
     /// - Bit 2: DEBUG_INTERRUPT_BIT - set when debugger interrupt is requested
     /// - Bit 1: RUNNING_BIT - set when vcpu is actively running
     /// - Bit 0: CANCEL_BIT - set when cancellation has been requested
@@ -284,6 +390,9 @@ pub(super) struct WindowsInterruptHandle {
     /// (e.g., during host function calls), but is cleared at the start of each new `VirtualCPU::run()` call.
     state: AtomicU8,
 
+    /// Interrupts injected and not taken yet, indexed by vector from `FIRST_HOST_INTERRUPT_VECTOR`
+    pending_interrupts: AtomicU16,
+
     /// RwLock protecting the partition handle and dropped state.
     ///
     /// This lock prevents a race condition between `kill()` calling `WHvCancelRunVirtualProcessor`
@@ -320,6 +429,38 @@ impl WindowsInterruptHandle {
     const CANCEL_BIT: u8 = 1 << 0;
     #[cfg(gdb)]
     const DEBUG_INTERRUPT_BIT: u8 = 1 << 2;
+    #[cfg(feature = "trace_guest")]
+    const TRACE_FLUSH_BIT: u8 = 1 << 3;
+    const INJECT_BIT: u8 = 1 << 4;
+
+    /// Cancels the run of the vcpu if it is running, returning whether it was cancelled
+    fn cancel_run(&self) -> bool {
+        use windows::Win32::System::Hypervisor::WHvCancelRunVirtualProcessor;
+
+        // Acquire ordering to synchronize with the Release in set_running()
+        // This ensures we see the running state set by the vcpu thread
+        let state = self.state.load(Ordering::Acquire);
+        if state & Self::RUNNING_BIT == 0 {
+            return false;
+        }
+
+        // Take read lock to prevent race with WHvDeletePartition in set_dropped().
+        // Multiple kill() calls can proceed concurrently (read locks don't block each other),
+        // but set_dropped() will wait for all kill() calls to complete before proceeding.
+        let guard = match self.partition_state.read() {
+            Ok(guard) => guard,
+            Err(e) => {
+                log::error!("Failed to acquire partition_state read lock: {}", e);
+                return false;
+            }
+        };
+
+        if guard.dropped {
+            return false;
+        }
+
+        unsafe { WHvCancelRunVirtualProcessor(guard.handle, 0, 0).is_ok() }
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -364,6 +505,25 @@ impl InterruptHandleImpl for WindowsInterruptHandle {
             .fetch_and(!Self::DEBUG_INTERRUPT_BIT, Ordering::Release);
     }
 
+    #[cfg(feature = "trace_guest")]
+    fn take_trace_flush_request(&self) -> bool {
+        self.state
+            .fetch_and(!Self::TRACE_FLUSH_BIT, Ordering::AcqRel)
+            & Self::TRACE_FLUSH_BIT
+            != 0
+    }
+
+    fn take_injected_interrupts(&self) -> u16 {
+        // The bit is cleared first, so an interrupt injected concurrently
+        // is either taken here or leaves the bit set for the next call
+        self.state.fetch_and(!Self::INJECT_BIT, Ordering::AcqRel);
+        self.pending_interrupts.swap(0, Ordering::AcqRel)
+    }
+
+    fn has_injected_interrupts(&self) -> bool {
+        self.state.load(Ordering::Acquire) & Self::INJECT_BIT != 0
+    }
+
     fn set_dropped(&self) {
         // Take write lock to:
         // 1. Wait for any in-flight kill() calls (holding read locks) to complete
@@ -385,63 +545,35 @@ impl InterruptHandleImpl for WindowsInterruptHandle {
 #[cfg(target_os = "windows")]
 impl InterruptHandle for WindowsInterruptHandle {
     fn kill(&self) -> bool {
-        use windows::Win32::System::Hypervisor::WHvCancelRunVirtualProcessor;
-
         // Release ordering ensures that any writes before kill() are visible to the vcpu thread
         // when it checks is_cancelled() with Acquire ordering
         self.state.fetch_or(Self::CANCEL_BIT, Ordering::Release);
-
-        // Acquire ordering to synchronize with the Release in set_running()
-        // This ensures we see the running state set by the vcpu thread
-        let state = self.state.load(Ordering::Acquire);
-        if state & Self::RUNNING_BIT == 0 {
-            return false;
-        }
-
-        // Take read lock to prevent race with WHvDeletePartition in set_dropped().
-        // Multiple kill() calls can proceed concurrently (read locks don't block each other),
-        // but set_dropped() will wait for all kill() calls to complete before proceeding.
-        let guard = match self.partition_state.read() {
-            Ok(guard) => guard,
-            Err(e) => {
-                log::error!("Failed to acquire partition_state read lock: {}", e);
-                return false;
-            }
-        };
-
-        if guard.dropped {
-            return false;
-        }
-
-        unsafe { WHvCancelRunVirtualProcessor(guard.handle, 0, 0).is_ok() }
+        self.cancel_run()
     }
     #[cfg(gdb)]
     fn kill_from_debugger(&self) -> bool {
-        use windows::Win32::System::Hypervisor::WHvCancelRunVirtualProcessor;
-
         self.state
             .fetch_or(Self::DEBUG_INTERRUPT_BIT, Ordering::Release);
+        self.cancel_run()
+    }
 
-        // Acquire ordering to synchronize with the Release in set_running()
-        let state = self.state.load(Ordering::Acquire);
-        if state & Self::RUNNING_BIT == 0 {
-            return false;
-        }
-
-        // Take read lock to prevent race with WHvDeletePartition in set_dropped()
-        let guard = match self.partition_state.read() {
-            Ok(guard) => guard,
-            Err(e) => {
-                log::error!("Failed to acquire partition_state read lock: {}", e);
-                return false;
-            }
-        };
+    #[cfg(feature = "trace_guest")]
+    fn flush_guest_traces(&self) -> bool {
+        self.state
+            .fetch_or(Self::TRACE_FLUSH_BIT, Ordering::Release);
+        self.cancel_run()
+    }
 
-        if guard.dropped {
+    fn inject_interrupt(&self, vector: u8) -> bool {
+        if !is_host_interrupt_vector(vector) {
             return false;
         }
-
-        unsafe { WHvCancelRunVirtualProcessor(guard.handle, 0, 0).is_ok() }
+        self.pending_interrupts.fetch_or(
+            1 << (vector - FIRST_HOST_INTERRUPT_VECTOR),
+            Ordering::Release,
+        );
+        self.state.fetch_or(Self::INJECT_BIT, Ordering::Release);
+        self.cancel_run()
     }
 
     fn dropped(&self) -> bool {
@@ -462,6 +594,7 @@ pub(crate) mod tests {
 
     use hyperlight_testing::dummy_guest_as_string;
 
+    use crate::sandbox::metrics::CreationReport;
     use crate::sandbox::uninitialized::GuestBinary;
     #[cfg(any(crashdump, gdb))]
     use crate::sandbox::uninitialized::SandboxRuntimeConfig;
@@ -496,6 +629,7 @@ pub(crate) mod tests {
             #[cfg(any(crashdump, gdb))]
             rt_cfg,
             sandbox.load_info,
+            &mut CreationReport::default(),
         )?;
 
         // Set up required parameters for initialise
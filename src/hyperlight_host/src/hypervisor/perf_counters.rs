@@ -0,0 +1,198 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Counting the instructions and cycles of the guest with the hardware
+//! performance counters of the host, see
+//! [`SandboxConfiguration::set_guest_perf_counters`].
+//!
+//! The counters are opened with `perf_event_open` on the thread running
+//! the vCPU, excluding the events of the host, so that KVM only lets them
+//! count while the vCPU runs the guest. This programs the fixed counters
+//! of the CPU where it has them, without exposing a PMU to the guest.
+//!
+//! The fuel of the guest, see [`MultiUseSandbox::set_fuel`], is metered
+//! the same way, with an instructions counter that overflows once the
+//! guest has used up its fuel. The kernel then signals the thread running
+//! the vCPU, which kicks the vCPU out of the guest like
+//! [`InterruptHandle::kill`] does.
+//!
+//! [`SandboxConfiguration::set_guest_perf_counters`]: crate::sandbox::SandboxConfiguration::set_guest_perf_counters
+//! [`MultiUseSandbox::set_fuel`]: crate::MultiUseSandbox::set_fuel
+//! [`InterruptHandle::kill`]: crate::hypervisor::InterruptHandle::kill
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd};
+
+use linux_raw_sys::general::{F_OWNER_TID, F_SETOWN_EX, F_SETSIG, f_owner_ex};
+
+use crate::sandbox::SandboxMetrics;
+
+/// Errors that can occur when counting the events of the guest
+#[derive(Debug, thiserror::Error)]
+pub enum PerfCountersError {
+    #[error("Failed to open the {0} counter: {1}")]
+    Open(&'static str, io::Error),
+    #[error("Failed to read the {0} counter: {1}")]
+    Read(&'static str, io::Error),
+}
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+/// The `exclude_host` bit of the flags of [`PerfEventAttr`]
+const PERF_ATTR_EXCLUDE_HOST: u64 = 1 << 19;
+
+/// The first version of `struct perf_event_attr`, which has all the
+/// fields counting needs, see `perf_event_open(2)`
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+const _: () = assert!(size_of::<PerfEventAttr>() == 64);
+
+/// The counters of the instructions and cycles of the guest running on
+/// the current thread, which count from when they are opened
+pub(crate) struct GuestPerfCounters {
+    instructions: File,
+    cycles: File,
+}
+
+impl GuestPerfCounters {
+    pub(crate) fn open() -> Result<Self, PerfCountersError> {
+        Ok(Self {
+            instructions: open_counter(PERF_COUNT_HW_INSTRUCTIONS)
+                .map_err(|e| PerfCountersError::Open("instructions", e))?,
+            cycles: open_counter(PERF_COUNT_HW_CPU_CYCLES)
+                .map_err(|e| PerfCountersError::Open("cycles", e))?,
+        })
+    }
+
+    /// Reads what the guest ran since the counters were opened
+    pub(crate) fn read(mut self) -> Result<SandboxMetrics, PerfCountersError> {
+        Ok(SandboxMetrics {
+            instructions_retired: read_counter(&mut self.instructions)
+                .map_err(|e| PerfCountersError::Read("instructions", e))?,
+            cycles: read_counter(&mut self.cycles)
+                .map_err(|e| PerfCountersError::Read("cycles", e))?,
+        })
+    }
+}
+
+/// A counter of the instructions retired by the guest running on the
+/// current thread, which interrupts the vCPU with a signal once the guest
+/// has used up the fuel the meter was started with
+pub(crate) struct FuelMeter {
+    counter: File,
+    fuel: u64,
+}
+
+impl FuelMeter {
+    /// Starts metering `fuel` instructions, interrupting the vCPU with
+    /// `signal` once they are retired. `fuel` must not be zero.
+    pub(crate) fn start(fuel: u64, signal: libc::c_int) -> Result<Self, PerfCountersError> {
+        let counter =
+            open_fuel_counter(fuel, signal).map_err(|e| PerfCountersError::Open("fuel", e))?;
+        Ok(Self { counter, fuel })
+    }
+
+    /// The fuel the meter was started with
+    pub(crate) fn fuel(&self) -> u64 {
+        self.fuel
+    }
+
+    /// The instructions the guest retired since the meter was started,
+    /// which can be a few more than its fuel, since the vCPU only stops
+    /// shortly after the counter overflows
+    pub(crate) fn consumed(&mut self) -> Result<u64, PerfCountersError> {
+        read_counter(&mut self.counter).map_err(|e| PerfCountersError::Read("fuel", e))
+    }
+}
+
+fn open_counter(config: u64) -> io::Result<File> {
+    open_event(&PerfEventAttr {
+        type_: PERF_TYPE_HARDWARE,
+        size: size_of::<PerfEventAttr>() as u32,
+        config,
+        // The guest kernel mode is not excluded, since the guest runs in
+        // ring 0
+        flags: PERF_ATTR_EXCLUDE_HOST,
+        ..Default::default()
+    })
+}
+
+/// Opens an instructions counter that signals the current thread with
+/// `signal` once it counted `fuel` instructions
+fn open_fuel_counter(fuel: u64, signal: libc::c_int) -> io::Result<File> {
+    let counter = open_event(&PerfEventAttr {
+        type_: PERF_TYPE_HARDWARE,
+        size: size_of::<PerfEventAttr>() as u32,
+        config: PERF_COUNT_HW_INSTRUCTIONS,
+        sample_period: fuel,
+        flags: PERF_ATTR_EXCLUDE_HOST,
+        // Notify on the first overflow
+        wakeup_events: 1,
+        ..Default::default()
+    })?;
+    let fd = counter.as_raw_fd();
+    // `libc` does not define the commands that direct the signal of a file
+    // descriptor to a thread, so they are taken from the kernel headers
+    let owner = f_owner_ex {
+        type_: F_OWNER_TID as libc::c_int,
+        pid: unsafe { libc::gettid() },
+    };
+    if unsafe { libc::fcntl(fd, F_SETOWN_EX as libc::c_int, &owner as *const f_owner_ex) } != 0
+        || unsafe { libc::fcntl(fd, F_SETSIG as libc::c_int, signal) } != 0
+        || unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_ASYNC) } != 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(counter)
+}
+
+fn open_event(attr: &PerfEventAttr) -> io::Result<File> {
+    // The current thread, on any CPU, in no group
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            attr as *const PerfEventAttr,
+            0 as libc::pid_t,
+            -1 as libc::c_int,
+            -1 as libc::c_int,
+            libc::O_CLOEXEC as libc::c_ulong,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(fd as libc::c_int) })
+}
+
+fn read_counter(counter: &mut File) -> io::Result<u64> {
+    let mut value = [0u8; 8];
+    counter.read_exact(&mut value)?;
+    Ok(u64::from_ne_bytes(value))
+}
@@ -18,6 +18,8 @@ mod debug_regs;
 mod fpu;
 mod special_regs;
 mod standard_regs;
+#[cfg(crashdump)]
+mod xsave;
 
 #[cfg(target_os = "windows")]
 use std::collections::HashSet;
@@ -26,6 +28,8 @@ pub(crate) use debug_regs::*;
 pub(crate) use fpu::*;
 pub(crate) use special_regs::*;
 pub(crate) use standard_regs::*;
+#[cfg(crashdump)]
+pub(crate) use xsave::*;
 
 #[cfg(target_os = "windows")]
 #[derive(Debug, PartialEq)]
@@ -0,0 +1,133 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+/// Offset of MXCSR in the legacy region of an XSAVE area
+const XSAVE_MXCSR_OFFSET: usize = 24;
+/// Offset of XMM0 in the legacy region of an XSAVE area
+const XSAVE_XMM_OFFSET: usize = 160;
+/// Offset of the XSAVE header, whose first field is XSTATE_BV
+const XSAVE_HEADER_OFFSET: usize = 512;
+/// Offset of the upper halves of YMM0-15. AVX is the first extended state
+/// component, so it starts right after the header in both the standard and
+/// the compacted formats.
+const XSAVE_YMM_HI_OFFSET: usize = 576;
+/// The bit of the AVX state component in XSTATE_BV
+const XSTATE_AVX: u64 = 1 << 2;
+
+/// The SSE and AVX registers of a vCPU, read from its XSAVE area (see
+/// [`VirtualMachine::xsave`](crate::hypervisor::virtual_machine::VirtualMachine::xsave)),
+/// which unlike [`CommonFpu`] also holds the upper halves of the YMM
+/// registers.
+///
+/// The AVX-512 state is not read, since where it is in the standard format
+/// depends on the CPU.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct XsaveVectorRegs {
+    pub mxcsr: u32,
+    pub xmm: [[u8; 16]; 16],
+    /// The upper 128 bits of YMM0-15, which are all zero when the AVX state
+    /// is in its initial configuration
+    pub ymm_hi: [[u8; 16]; 16],
+}
+
+impl XsaveVectorRegs {
+    /// Reads the vector registers from an XSAVE area in the standard or
+    /// compacted format, or returns `None` if it is too short to hold
+    /// the state it claims to
+    pub(crate) fn from_xsave(xsave: &[u8]) -> Option<Self> {
+        let header = xsave.get(XSAVE_HEADER_OFFSET..XSAVE_HEADER_OFFSET + 8)?;
+        let xstate_bv = u64::from_le_bytes(header.try_into().ok()?);
+        let mxcsr = xsave.get(XSAVE_MXCSR_OFFSET..XSAVE_MXCSR_OFFSET + 4)?;
+
+        let mut regs = Self {
+            mxcsr: u32::from_le_bytes(mxcsr.try_into().ok()?),
+            xmm: [[0u8; 16]; 16],
+            ymm_hi: [[0u8; 16]; 16],
+        };
+        let legacy = xsave.get(XSAVE_XMM_OFFSET..XSAVE_XMM_OFFSET + 16 * 16)?;
+        for (xmm, bytes) in regs.xmm.iter_mut().zip(legacy.chunks_exact(16)) {
+            xmm.copy_from_slice(bytes);
+        }
+        if xstate_bv & XSTATE_AVX != 0 {
+            let avx = xsave.get(XSAVE_YMM_HI_OFFSET..XSAVE_YMM_HI_OFFSET + 16 * 16)?;
+            for (ymm_hi, bytes) in regs.ymm_hi.iter_mut().zip(avx.chunks_exact(16)) {
+                ymm_hi.copy_from_slice(bytes);
+            }
+        }
+        Some(regs)
+    }
+
+    /// Returns the 256 bits of YMM`index`, in little-endian order
+    pub(crate) fn ymm(&self, index: usize) -> [u8; 32] {
+        let mut ymm = [0u8; 32];
+        ymm[..16].copy_from_slice(&self.xmm[index]);
+        ymm[16..].copy_from_slice(&self.ymm_hi[index]);
+        ymm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hypervisor::regs::MXCSR_DEFAULT;
+
+    /// Builds a standard format XSAVE area, where XMMn and the upper half
+    /// of YMMn are filled with `n` and `0x80 | n`
+    fn xsave_area(xstate_bv: u64) -> Vec<u8> {
+        let mut xsave = vec![0u8; 4096];
+        xsave[XSAVE_MXCSR_OFFSET..XSAVE_MXCSR_OFFSET + 4]
+            .copy_from_slice(&MXCSR_DEFAULT.to_le_bytes());
+        for i in 0..16 {
+            let xmm = XSAVE_XMM_OFFSET + 16 * i;
+            xsave[xmm..xmm + 16].fill(i as u8);
+            let ymm_hi = XSAVE_YMM_HI_OFFSET + 16 * i;
+            xsave[ymm_hi..ymm_hi + 16].fill(0x80 | i as u8);
+        }
+        xsave[XSAVE_HEADER_OFFSET..XSAVE_HEADER_OFFSET + 8]
+            .copy_from_slice(&xstate_bv.to_le_bytes());
+        xsave
+    }
+
+    #[test]
+    fn avx_state() {
+        // x87, SSE and AVX
+        let regs = XsaveVectorRegs::from_xsave(&xsave_area(0b111)).unwrap();
+        assert_eq!(regs.mxcsr, MXCSR_DEFAULT);
+        assert_eq!(regs.xmm[3], [3u8; 16]);
+        assert_eq!(regs.ymm_hi[3], [0x83u8; 16]);
+        assert_eq!(regs.ymm(15)[..16], [15u8; 16]);
+        assert_eq!(regs.ymm(15)[16..], [0x8fu8; 16]);
+
+        // Without AVX state, the upper halves are in their initial state
+        // whatever the area holds
+        let regs = XsaveVectorRegs::from_xsave(&xsave_area(0b011)).unwrap();
+        assert_eq!(regs.xmm[3], [3u8; 16]);
+        assert_eq!(regs.ymm_hi, [[0u8; 16]; 16]);
+    }
+
+    #[test]
+    fn short_area() {
+        let xsave = xsave_area(0b111);
+        assert!(XsaveVectorRegs::from_xsave(&[]).is_none());
+        assert!(XsaveVectorRegs::from_xsave(&xsave[..XSAVE_HEADER_OFFSET]).is_none());
+        assert!(XsaveVectorRegs::from_xsave(&xsave[..XSAVE_YMM_HI_OFFSET]).is_none());
+
+        // A legacy area with only x87 and SSE state is enough
+        let mut xsave = xsave_area(0b011);
+        xsave.truncate(XSAVE_YMM_HI_OFFSET);
+        assert!(XsaveVectorRegs::from_xsave(&xsave).is_some());
+    }
+}
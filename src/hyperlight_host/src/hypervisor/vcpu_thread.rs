@@ -0,0 +1,272 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The CPU affinity and the scheduling priority of the thread running a
+//! vCPU, see [`SandboxConfiguration::set_vcpu_affinity`] and
+//! [`SandboxConfiguration::set_vcpu_priority`].
+//!
+//! [`SandboxConfiguration::set_vcpu_affinity`]: crate::sandbox::SandboxConfiguration::set_vcpu_affinity
+//! [`SandboxConfiguration::set_vcpu_priority`]: crate::sandbox::SandboxConfiguration::set_vcpu_priority
+
+use std::io;
+
+use crate::sandbox::config::{CpuSet, VcpuPriority};
+
+/// Errors that can occur when applying the vCPU thread settings
+#[derive(Debug, thiserror::Error)]
+pub enum VcpuThreadError {
+    #[error("Failed to get the priority of the vCPU thread: {0}")]
+    GetPriority(io::Error),
+    #[error("Failed to set the CPU affinity of the vCPU thread: {0}")]
+    SetAffinity(io::Error),
+    #[error("Failed to set the priority of the vCPU thread: {0}")]
+    SetPriority(io::Error),
+}
+
+/// The settings the thread running a vCPU runs the guest with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct VcpuThreadSettings {
+    pub(crate) affinity: Option<CpuSet>,
+    pub(crate) priority: VcpuPriority,
+}
+
+impl VcpuThreadSettings {
+    /// Applies the settings to the current thread, until the returned
+    /// guard is dropped
+    pub(crate) fn apply(&self) -> Result<VcpuThreadGuard, VcpuThreadError> {
+        let mut guard = VcpuThreadGuard {
+            affinity: None,
+            priority: None,
+        };
+        if let Some(cpus) = &self.affinity {
+            let previous = os::set_affinity(cpus).map_err(VcpuThreadError::SetAffinity)?;
+            guard.affinity = Some(previous);
+        }
+        if let VcpuPriority::Nice(nice) = self.priority {
+            let previous = os::priority().map_err(VcpuThreadError::GetPriority)?;
+            if previous != os::nice_priority(nice) {
+                os::set_priority(os::nice_priority(nice)).map_err(VcpuThreadError::SetPriority)?;
+                guard.priority = Some(previous);
+            }
+        }
+        Ok(guard)
+    }
+}
+
+/// Restores the settings the current thread had before
+/// [`VcpuThreadSettings::apply`] when dropped
+#[must_use]
+pub(crate) struct VcpuThreadGuard {
+    affinity: Option<os::Affinity>,
+    priority: Option<os::Priority>,
+}
+
+impl Drop for VcpuThreadGuard {
+    fn drop(&mut self) {
+        if let Some(priority) = self.priority.take()
+            && let Err(e) = os::set_priority(priority)
+        {
+            tracing::warn!("Failed to restore the priority of the vCPU thread: {}", e);
+        }
+        if let Some(affinity) = self.affinity.take()
+            && let Err(e) = os::restore_affinity(&affinity)
+        {
+            tracing::warn!(
+                "Failed to restore the CPU affinity of the vCPU thread: {}",
+                e
+            );
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::io;
+
+    use crate::sandbox::config::CpuSet;
+
+    pub(super) type Affinity = libc::cpu_set_t;
+    pub(super) type Priority = i32;
+
+    /// Pins the calling thread to `cpus`, returning its previous affinity
+    pub(super) fn set_affinity(cpus: &CpuSet) -> io::Result<Affinity> {
+        // SAFETY: cpu_set_t is a plain bitmask the kernel writes at most
+        // the size of, and every CPU of the set is lower than CPU_SETSIZE
+        let (previous, set) = unsafe {
+            let mut previous: libc::cpu_set_t = std::mem::zeroed();
+            if libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut previous) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for cpu in cpus.iter() {
+                libc::CPU_SET(cpu, &mut set);
+            }
+            (previous, set)
+        };
+        restore_affinity(&set)?;
+        Ok(previous)
+    }
+
+    pub(super) fn restore_affinity(set: &Affinity) -> io::Result<()> {
+        // SAFETY: `set` is a valid cpu_set_t, and 0 is the calling thread
+        if unsafe { libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), set) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn nice_priority(nice: i8) -> Priority {
+        nice.clamp(-20, 19).into()
+    }
+
+    pub(super) fn priority() -> io::Result<Priority> {
+        // SAFETY: the thread id of the calling thread is valid, and errno
+        // is cleared since -1 is also a valid priority
+        unsafe {
+            *libc::__errno_location() = 0;
+            let nice = libc::getpriority(libc::PRIO_PROCESS as _, libc::gettid() as libc::id_t);
+            if nice == -1 && *libc::__errno_location() != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(nice)
+        }
+    }
+
+    pub(super) fn set_priority(nice: Priority) -> io::Result<()> {
+        // SAFETY: the thread id of the calling thread is valid
+        let ret = unsafe {
+            libc::setpriority(libc::PRIO_PROCESS as _, libc::gettid() as libc::id_t, nice)
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod os {
+    use std::io;
+
+    use windows::Win32::System::Threading::{
+        GetCurrentThread, GetThreadPriority, SetThreadAffinityMask, SetThreadPriority,
+        THREAD_PRIORITY, THREAD_PRIORITY_ABOVE_NORMAL, THREAD_PRIORITY_BELOW_NORMAL,
+        THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_LOWEST, THREAD_PRIORITY_NORMAL,
+    };
+
+    use crate::sandbox::config::CpuSet;
+
+    pub(super) type Affinity = usize;
+    pub(super) type Priority = THREAD_PRIORITY;
+
+    /// Pins the calling thread to `cpus`, returning its previous affinity
+    pub(super) fn set_affinity(cpus: &CpuSet) -> io::Result<Affinity> {
+        if let Some(cpu) = cpus.iter().find(|&cpu| cpu >= usize::BITS as usize) {
+            return Err(io::Error::other(format!(
+                "CPU {} is not in the processor group of the thread",
+                cpu
+            )));
+        }
+        let mask = cpus.iter().fold(0usize, |mask, cpu| mask | (1 << cpu));
+        // SAFETY: the pseudo handle of the current thread is always valid
+        let previous = unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) };
+        if previous == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(previous)
+    }
+
+    pub(super) fn restore_affinity(mask: &Affinity) -> io::Result<()> {
+        // SAFETY: the pseudo handle of the current thread is always valid
+        if unsafe { SetThreadAffinityMask(GetCurrentThread(), *mask) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn nice_priority(nice: i8) -> Priority {
+        match nice {
+            ..=-15 => THREAD_PRIORITY_HIGHEST,
+            -14..=-5 => THREAD_PRIORITY_ABOVE_NORMAL,
+            -4..=4 => THREAD_PRIORITY_NORMAL,
+            5..=14 => THREAD_PRIORITY_BELOW_NORMAL,
+            15.. => THREAD_PRIORITY_LOWEST,
+        }
+    }
+
+    pub(super) fn priority() -> io::Result<Priority> {
+        // SAFETY: the pseudo handle of the current thread is always valid
+        let priority = unsafe { GetThreadPriority(GetCurrentThread()) };
+        // THREAD_PRIORITY_ERROR_RETURN
+        if priority == i32::MAX {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(THREAD_PRIORITY(priority))
+    }
+
+    pub(super) fn set_priority(priority: Priority) -> io::Result<()> {
+        // SAFETY: the pseudo handle of the current thread is always valid
+        unsafe { SetThreadPriority(GetCurrentThread(), priority) }.map_err(io::Error::other)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{VcpuThreadSettings, os};
+    use crate::sandbox::config::{CpuSet, VcpuPriority};
+
+    fn pinned_cpus() -> Vec<usize> {
+        // SAFETY: cpu_set_t is a plain bitmask the kernel writes at most
+        // the size of, and every CPU is lower than CPU_SETSIZE
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            assert_eq!(
+                libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set),
+                0
+            );
+            (0..CpuSet::MAX_CPUS)
+                .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn settings_are_restored() {
+        // Run on a thread of its own, so that a failure cannot leave the
+        // thread of other tests pinned
+        std::thread::spawn(|| {
+            let before = pinned_cpus();
+            let nice = os::priority().unwrap();
+            let settings = VcpuThreadSettings {
+                affinity: Some(CpuSet::from_cpus([before[0]]).unwrap()),
+                // Keeping the same nice value needs no privilege
+                priority: VcpuPriority::Nice(nice as i8),
+            };
+
+            let guard = settings.apply().unwrap();
+            assert_eq!(pinned_cpus(), [before[0]]);
+            assert_eq!(os::priority().unwrap(), nice);
+            drop(guard);
+            assert_eq!(pinned_cpus(), before);
+
+            // Nothing is changed without settings
+            let _guard = VcpuThreadSettings::default().apply().unwrap();
+            assert_eq!(pinned_cpus(), before);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -19,7 +19,10 @@ use std::sync::LazyLock;
 #[cfg(gdb)]
 use kvm_bindings::kvm_guest_debug;
 use kvm_bindings::{
-    kvm_debugregs, kvm_fpu, kvm_regs, kvm_sregs, kvm_userspace_memory_region, kvm_xsave,
+    KVM_CAP_X86_USER_SPACE_MSR, KVM_CPUID_FLAG_SIGNIFCANT_INDEX, KVM_MSR_EXIT_REASON_INVAL,
+    KVM_MSR_EXIT_REASON_UNKNOWN, KVM_VCPU_TSC_CTRL, KVM_VCPU_TSC_OFFSET, kvm_cpuid_entry2,
+    kvm_debugregs, kvm_device_attr, kvm_enable_cap, kvm_fpu, kvm_regs, kvm_sregs,
+    kvm_userspace_memory_region, kvm_xsave,
 };
 use kvm_ioctls::Cap::UserMemory;
 use kvm_ioctls::{Kvm, VcpuExit, VcpuFd, VmFd};
@@ -27,6 +30,8 @@ use tracing::{Span, instrument};
 #[cfg(feature = "trace_guest")]
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+#[cfg(gdb)]
+use crate::hypervisor::gdb::arch::HwBreakpointRegs;
 #[cfg(gdb)]
 use crate::hypervisor::gdb::{DebugError, DebuggableVm};
 use crate::hypervisor::regs::{
@@ -40,8 +45,10 @@ use crate::hypervisor::virtual_machine::{
     VmExit,
 };
 use crate::mem::memory_region::MemoryRegion;
+use crate::sandbox::mmio::MmioRegions;
 #[cfg(feature = "trace_guest")]
 use crate::sandbox::trace::TraceContext as SandboxTraceContext;
+use crate::sandbox::{CpuidOverride, MsrPolicy};
 
 /// On KVM x86-64 only, we have to set this in order to set the guest
 /// physical address width.
@@ -88,6 +95,8 @@ pub(crate) fn is_hypervisor_present() -> bool {
 pub(crate) struct KvmVm {
     vm_fd: VmFd,
     vcpu_fd: VcpuFd,
+    msr_policy: MsrPolicy,
+    mmio_regions: MmioRegions,
 
     // KVM, as opposed to mshv/whp, has no get_guest_debug() ioctl, so we must track the state ourselves
     #[cfg(gdb)]
@@ -98,14 +107,34 @@ static KVM: LazyLock<std::result::Result<Kvm, CreateVmError>> =
     LazyLock::new(|| Kvm::new().map_err(|e| CreateVmError::HypervisorNotAvailable(e.into())));
 
 impl KvmVm {
-    /// Create a new instance of a `KvmVm`
+    /// Create a new instance of a `KvmVm`, whose CPUID is changed by
+    /// `cpuid_overrides`, and whose accesses to the MSRs KVM does not
+    /// virtualize are handled according to `msr_policy`
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
-    pub(crate) fn new() -> std::result::Result<Self, CreateVmError> {
+    pub(crate) fn new(
+        cpuid_overrides: &[CpuidOverride],
+        msr_policy: MsrPolicy,
+    ) -> std::result::Result<Self, CreateVmError> {
         let hv = KVM.as_ref().map_err(|e| e.clone())?;
 
         let vm_fd = hv
             .create_vm_with_type(0)
             .map_err(|e| CreateVmError::CreateVmFd(e.into()))?;
+
+        // Make the accesses to the MSRs KVM does not know about, and the
+        // invalid accesses to the ones it knows about, exit to user space
+        // instead of injecting a #GP into the guest
+        if msr_policy != MsrPolicy::Allow {
+            let mut cap = kvm_enable_cap {
+                cap: KVM_CAP_X86_USER_SPACE_MSR,
+                ..Default::default()
+            };
+            cap.args[0] = (KVM_MSR_EXIT_REASON_UNKNOWN | KVM_MSR_EXIT_REASON_INVAL) as u64;
+            vm_fd
+                .enable_cap(&cap)
+                .map_err(|e| CreateVmError::SetMsrPolicy(e.into()))?;
+        }
+
         let vcpu_fd = vm_fd
             .create_vcpu(0)
             .map_err(|e| CreateVmError::CreateVcpuFd(e.into()))?;
@@ -124,6 +153,43 @@ impl KvmVm {
                 entry.eax &= !0xff;
                 entry.eax |= hyperlight_common::layout::MAX_GPA.ilog2() + 1;
             }
+            for cpuid_override in cpuid_overrides
+                .iter()
+                .filter(|o| o.matches(entry.function, entry.index))
+            {
+                [entry.eax, entry.ebx, entry.ecx, entry.edx] =
+                    cpuid_override.apply([entry.eax, entry.ebx, entry.ecx, entry.edx]);
+            }
+        }
+        // KVM reports the leaves it does not know about as zero, so the
+        // overrides of these leaves apply to zero
+        for cpuid_override in cpuid_overrides {
+            let index = cpuid_override.index().unwrap_or(0);
+            let known = kvm_cpuid
+                .as_slice()
+                .iter()
+                .any(|entry| cpuid_override.matches(entry.function, entry.index));
+            if known {
+                continue;
+            }
+            let [eax, ebx, ecx, edx] = cpuid_override.apply([0; 4]);
+            let entry = kvm_cpuid_entry2 {
+                function: cpuid_override.function(),
+                index,
+                flags: if cpuid_override.index().is_some() {
+                    KVM_CPUID_FLAG_SIGNIFCANT_INDEX
+                } else {
+                    0
+                },
+                eax,
+                ebx,
+                ecx,
+                edx,
+                ..Default::default()
+            };
+            kvm_cpuid
+                .push(entry)
+                .map_err(|e| CreateVmError::SetCpuid(format!("{:?}", e)))?;
         }
         vcpu_fd
             .set_cpuid2(&kvm_cpuid)
@@ -132,6 +198,8 @@ impl KvmVm {
         Ok(Self {
             vm_fd,
             vcpu_fd,
+            msr_policy,
+            mmio_regions: MmioRegions::default(),
             #[cfg(gdb)]
             debug_regs: kvm_guest_debug::default(),
         })
@@ -174,8 +242,53 @@ impl VirtualMachine for KvmVm {
         match self.vcpu_fd.run() {
             Ok(VcpuExit::Hlt) => Ok(VmExit::Halt()),
             Ok(VcpuExit::IoOut(port, data)) => Ok(VmExit::IoOut(port, data.to_vec())),
-            Ok(VcpuExit::MmioRead(addr, _)) => Ok(VmExit::MmioRead(addr)),
-            Ok(VcpuExit::MmioWrite(addr, _)) => Ok(VmExit::MmioWrite(addr)),
+            // KVM completes an emulated read with `data` when the vCPU runs again
+            Ok(VcpuExit::MmioRead(addr, data)) => {
+                data.fill(0);
+                if self.mmio_regions.handle(addr, false, data) {
+                    Ok(VmExit::MmioEmulated())
+                } else {
+                    Ok(VmExit::MmioRead(addr))
+                }
+            }
+            Ok(VcpuExit::MmioWrite(addr, data)) => {
+                if self.mmio_regions.handle(addr, true, &mut data.to_vec()) {
+                    Ok(VmExit::MmioEmulated())
+                } else {
+                    Ok(VmExit::MmioWrite(addr))
+                }
+            }
+            // KVM completes the access when the vCPU runs again, injecting
+            // a #GP if `error` is set
+            Ok(VcpuExit::X86Rdmsr(msr_exit)) => match self.msr_policy {
+                MsrPolicy::EmulateAsZero => {
+                    *msr_exit.data = 0;
+                    *msr_exit.error = 0;
+                    Ok(VmExit::Retry())
+                }
+                MsrPolicy::Allow | MsrPolicy::Deny => {
+                    *msr_exit.error = 1;
+                    Ok(VmExit::MsrAccess {
+                        msr: msr_exit.index,
+                        write: false,
+                    })
+                }
+            },
+            Ok(VcpuExit::X86Wrmsr(msr_exit)) => match self.msr_policy {
+                MsrPolicy::EmulateAsZero => {
+                    *msr_exit.error = 0;
+                    Ok(VmExit::Retry())
+                }
+                MsrPolicy::Allow | MsrPolicy::Deny => {
+                    *msr_exit.error = 1;
+                    Ok(VmExit::MsrAccess {
+                        msr: msr_exit.index,
+                        write: true,
+                    })
+                }
+            },
+            // The guest can take the next injected interrupt
+            Ok(VcpuExit::IrqWindowOpen) => Ok(VmExit::Retry()),
             #[cfg(gdb)]
             Ok(VcpuExit::Debug(debug_exit)) => Ok(VmExit::Debug {
                 dr6: debug_exit.dr6,
@@ -194,6 +307,64 @@ impl VirtualMachine for KvmVm {
         }
     }
 
+    fn set_mmio_regions(&mut self, regions: MmioRegions) -> std::result::Result<(), CreateVmError> {
+        self.mmio_regions = regions;
+        Ok(())
+    }
+
+    fn set_tsc(
+        &mut self,
+        offset: Option<i64>,
+        frequency_khz: Option<u32>,
+    ) -> std::result::Result<(), CreateVmError> {
+        // Scaling first, as the offset applies to the scaled TSC
+        if let Some(khz) = frequency_khz {
+            self.vcpu_fd
+                .set_tsc_khz(khz)
+                .map_err(|e| CreateVmError::SetTsc(e.into()))?;
+        }
+        if let Some(offset) = offset {
+            let attr = kvm_device_attr {
+                group: KVM_VCPU_TSC_CTRL,
+                attr: KVM_VCPU_TSC_OFFSET as u64,
+                addr: &offset as *const i64 as u64,
+                flags: 0,
+            };
+            self.vcpu_fd
+                .set_device_attr(&attr)
+                .map_err(|e| CreateVmError::SetTsc(e.into()))?;
+        }
+        Ok(())
+    }
+
+    fn inject_interrupt(
+        &mut self,
+        vector: u8,
+        more_pending: bool,
+    ) -> std::result::Result<bool, RegisterError> {
+        // RFLAGS.IF
+        let interrupts_enabled = self.regs()?.rflags & (1 << 9) != 0;
+        let mut events = self
+            .vcpu_fd
+            .get_vcpu_events()
+            .map_err(|e| RegisterError::InjectInterrupt(e.into()))?;
+        let injectable = interrupts_enabled
+            && events.interrupt.shadow == 0
+            && events.interrupt.injected == 0
+            && events.exception.injected == 0;
+        if injectable {
+            events.interrupt.injected = 1;
+            events.interrupt.nr = vector;
+            events.interrupt.soft = 0;
+            self.vcpu_fd
+                .set_vcpu_events(&events)
+                .map_err(|e| RegisterError::InjectInterrupt(e.into()))?;
+        }
+        // KVM exits with `VcpuExit::IrqWindowOpen` once the guest can take an interrupt
+        self.vcpu_fd.get_kvm_run().request_interrupt_window = u8::from(!injectable || more_pending);
+        Ok(injectable)
+    }
+
     fn regs(&self) -> std::result::Result<CommonRegisters, RegisterError> {
         let kvm_regs = self
             .vcpu_fd
@@ -384,43 +555,20 @@ impl DebuggableVm for KvmVm {
         Ok(())
     }
 
-    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        use crate::hypervisor::gdb::arch::MAX_NO_OF_HW_BP;
-
-        // Check if breakpoint already exists
-        if self.debug_regs.arch.debugreg[..4].contains(&addr) {
-            return Ok(());
-        }
-
-        // Find the first available LOCAL (L0–L3) slot
-        let i = (0..MAX_NO_OF_HW_BP)
-            .position(|i| self.debug_regs.arch.debugreg[7] & (1 << (i * 2)) == 0)
-            .ok_or(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP))?;
-
-        // Assign to corresponding debug register
-        self.debug_regs.arch.debugreg[i] = addr;
-
-        // Enable LOCAL bit
-        self.debug_regs.arch.debugreg[7] |= 1 << (i * 2);
-
-        self.vcpu_fd
-            .set_guest_debug(&self.debug_regs)
-            .map_err(|e| RegisterError::SetDebugRegs(e.into()))?;
-        Ok(())
+    fn hw_breakpoint_regs(&self) -> std::result::Result<HwBreakpointRegs, DebugError> {
+        let debugreg = &self.debug_regs.arch.debugreg;
+        Ok(HwBreakpointRegs {
+            addrs: [debugreg[0], debugreg[1], debugreg[2], debugreg[3]],
+            dr7: debugreg[7],
+        })
     }
 
-    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        // Find the index of the breakpoint
-        let index = self.debug_regs.arch.debugreg[..4]
-            .iter()
-            .position(|&a| a == addr)
-            .ok_or(DebugError::HwBreakpointNotFound(addr))?;
-
-        // Clear the address
-        self.debug_regs.arch.debugreg[index] = 0;
-
-        // Disable LOCAL bit
-        self.debug_regs.arch.debugreg[7] &= !(1 << (index * 2));
+    fn set_hw_breakpoint_regs(
+        &mut self,
+        regs: &HwBreakpointRegs,
+    ) -> std::result::Result<(), DebugError> {
+        self.debug_regs.arch.debugreg[..4].copy_from_slice(&regs.addrs);
+        self.debug_regs.arch.debugreg[7] = regs.dr7;
 
         self.vcpu_fd
             .set_guest_debug(&self.debug_regs)
@@ -14,7 +14,7 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::sync::OnceLock;
 
 use tracing::{Span, instrument};
@@ -25,6 +25,9 @@ use crate::hypervisor::regs::{
     CommonDebugRegs, CommonFpu, CommonRegisters, CommonSpecialRegisters,
 };
 use crate::mem::memory_region::MemoryRegion;
+#[cfg(any(mshv3, target_os = "windows"))]
+use crate::sandbox::CpuidOverride;
+use crate::sandbox::mmio::MmioRegions;
 #[cfg(feature = "trace_guest")]
 use crate::sandbox::trace::TraceContext as SandboxTraceContext;
 
@@ -87,6 +90,88 @@ pub fn is_hypervisor_present() -> bool {
     get_available_hypervisor().is_some()
 }
 
+/// The hypervisor a sandbox runs on, see
+/// [`SandboxConfiguration::set_hypervisor`](crate::sandbox::SandboxConfiguration::set_hypervisor).
+///
+/// Every kind exists on every platform, so that configurations can be
+/// shared, but a sandbox can only run on the hypervisors its build
+/// supports, see [`available_backends`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub enum HypervisorKind {
+    /// The hypervisor found on the host, among the ones this build
+    /// supports. This is the default.
+    #[default]
+    Auto,
+    /// The Linux Kernel-based Virtual Machine, with the `kvm` feature
+    Kvm,
+    /// The Microsoft Hypervisor, through `/dev/mshv`, with the `mshv3`
+    /// feature
+    Mshv,
+    /// The Windows Hypervisor Platform
+    Whp,
+}
+
+impl Display for HypervisorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HypervisorKind::Auto => write!(f, "automatically selected"),
+            HypervisorKind::Kvm => write!(f, "KVM"),
+            HypervisorKind::Mshv => write!(f, "MSHV"),
+            HypervisorKind::Whp => write!(f, "WHP"),
+        }
+    }
+}
+
+impl From<HypervisorType> for HypervisorKind {
+    fn from(hypervisor: HypervisorType) -> Self {
+        match hypervisor {
+            #[cfg(kvm)]
+            HypervisorType::Kvm => HypervisorKind::Kvm,
+            #[cfg(mshv3)]
+            HypervisorType::Mshv => HypervisorKind::Mshv,
+            #[cfg(target_os = "windows")]
+            HypervisorType::Whp => HypervisorKind::Whp,
+        }
+    }
+}
+
+/// Returns the hypervisors this build supports that are usable on the
+/// host, in the order [`HypervisorKind::Auto`] picks them
+#[instrument(skip_all, parent = Span::current())]
+pub fn available_backends() -> Vec<HypervisorKind> {
+    #[allow(unused_mut)]
+    let mut backends = Vec::new();
+    #[cfg(mshv3)]
+    if mshv::is_hypervisor_present() {
+        backends.push(HypervisorKind::Mshv);
+    }
+    #[cfg(kvm)]
+    if kvm::is_hypervisor_present() {
+        backends.push(HypervisorKind::Kvm);
+    }
+    #[cfg(target_os = "windows")]
+    if whp::is_hypervisor_present() {
+        backends.push(HypervisorKind::Whp);
+    }
+    backends
+}
+
+/// Returns the hypervisor to create a VM with for `kind`, or `None` if
+/// this build does not support it or it is not usable on the host
+pub(crate) fn resolve_hypervisor(kind: HypervisorKind) -> Option<HypervisorType> {
+    match kind {
+        HypervisorKind::Auto => *get_available_hypervisor(),
+        #[cfg(kvm)]
+        HypervisorKind::Kvm if kvm::is_hypervisor_present() => Some(HypervisorType::Kvm),
+        #[cfg(mshv3)]
+        HypervisorKind::Mshv if mshv::is_hypervisor_present() => Some(HypervisorType::Mshv),
+        #[cfg(target_os = "windows")]
+        HypervisorKind::Whp if whp::is_hypervisor_present() => Some(HypervisorType::Whp),
+        _ => None,
+    }
+}
+
 /// The hypervisor types available for the current platform
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub(crate) enum HypervisorType {
@@ -111,6 +196,22 @@ pub(crate) const XSAVE_MIN_SIZE: usize = 576;
 #[cfg(all(any(kvm, mshv3), test, feature = "init-paging"))]
 pub(crate) const XSAVE_BUFFER_SIZE: usize = 4096;
 
+/// Returns the EAX, EBX, ECX and EDX values the host CPU reports for the
+/// leaf `cpuid_override` changes, with the change applied. This is used by
+/// the hypervisors that pass CPUID through from the hardware, where an
+/// override has to replace the whole leaf.
+#[cfg(any(mshv3, target_os = "windows"))]
+pub(crate) fn overridden_host_cpuid(cpuid_override: &CpuidOverride) -> [u32; 4] {
+    // SAFETY: CPUID is available on every x86_64 CPU
+    let host = unsafe {
+        std::arch::x86_64::__cpuid_count(
+            cpuid_override.function(),
+            cpuid_override.index().unwrap_or(0),
+        )
+    };
+    cpuid_override.apply([host.eax, host.ebx, host.ecx, host.edx])
+}
+
 // Compiler error if no hypervisor type is available
 #[cfg(not(any(kvm, mshv3, target_os = "windows")))]
 compile_error!(
@@ -130,18 +231,18 @@ pub(crate) enum VmExit {
     MmioRead(u64),
     /// The vCPU tried to write to the given (unmapped) addr
     MmioWrite(u64),
+    /// The vCPU accessed a registered MMIO region and the access was
+    /// emulated, the vCPU can be run again
+    MmioEmulated(),
     /// The vCPU execution has been cancelled
     Cancelled(),
     /// The vCPU has exited for a reason that is not handled by Hyperlight
     Unknown(String),
-    /// The operation should be retried, for example this can happen on Linux where a call to run the CPU can return EAGAIN
-    #[cfg_attr(
-        target_os = "windows",
-        expect(
-            dead_code,
-            reason = "Retry() is never constructed on Windows, but it is still matched on (which dead_code lint ignores)"
-        )
-    )]
+    /// The vCPU read or wrote (`write`) an MSR that the hypervisor does
+    /// not virtualize, and the MSR policy denies the access
+    MsrAccess { msr: u32, write: bool },
+    /// The operation should be retried, for example this can happen on Linux where a call to run the CPU can return EAGAIN,
+    /// or after an MSR access was emulated
     Retry(),
 }
 
@@ -174,8 +275,20 @@ pub enum CreateVmError {
     HypervisorNotAvailable(HypervisorError),
     #[error("Initialize VM failed: {0}")]
     InitializeVm(HypervisorError),
+    #[cfg(any(mshv3, target_os = "windows"))]
+    #[error("MMIO regions are not supported by this hypervisor")]
+    MmioNotSupported,
+    #[error("Set CPUID failed: {0}")]
+    SetCpuid(String),
+    #[cfg(any(mshv3, target_os = "windows"))]
+    #[error("TSC scaling is not supported by this hypervisor")]
+    TscScalingNotSupported,
+    #[error("Set MSR policy failed: {0}")]
+    SetMsrPolicy(HypervisorError),
     #[error("Set Partition Property failed: {0}")]
     SetPartitionProperty(HypervisorError),
+    #[error("Set TSC failed: {0}")]
+    SetTsc(HypervisorError),
     #[cfg(target_os = "windows")]
     #[error("Surrogate process creation failed: {0}")]
     SurrogateProcess(String),
@@ -186,6 +299,8 @@ pub enum CreateVmError {
 pub enum RunVcpuError {
     #[error("Failed to decode message type: {0}")]
     DecodeIOMessage(u32),
+    #[error("Emulate MSR access failed: {0}")]
+    EmulateMsr(HypervisorError),
     #[cfg(gdb)]
     #[error("Failed to get DR6 debug register: {0}")]
     GetDr6(HypervisorError),
@@ -220,6 +335,8 @@ pub enum RegisterError {
     GetXsave(HypervisorError),
     #[error("Failed to set xsave: {0}")]
     SetXsave(HypervisorError),
+    #[error("Failed to inject interrupt: {0}")]
+    InjectInterrupt(HypervisorError),
     #[error("Xsave size mismatch: expected {expected} bytes, got {actual}")]
     XsaveSizeMismatch {
         /// Expected size in bytes
@@ -285,6 +402,15 @@ pub enum HypervisorError {
 
 /// Trait for single-vCPU VMs. Provides a common interface for basic VM operations.
 /// Abstracts over differences between KVM, MSHV and WHP implementations.
+///
+/// This is the only layer that is specific to a hypervisor: `HyperlightVm`
+/// drives a `Box<dyn VirtualMachine>`, so features built on running the
+/// vCPU, accessing its registers or mapping memory are written once
+/// against this trait, and only the primitives they need are added here
+/// and implemented by each backend. Registers are exchanged in the
+/// hypervisor-independent types of [`crate::hypervisor::regs`], and debug
+/// control for gdb is provided by the `DebuggableVm` extension of this
+/// trait.
 pub(crate) trait VirtualMachine: Debug + Send {
     /// Map memory region into this VM
     ///
@@ -313,6 +439,27 @@ pub(crate) trait VirtualMachine: Debug + Send {
         #[cfg(feature = "trace_guest")] tc: &mut SandboxTraceContext,
     ) -> std::result::Result<VmExit, RunVcpuError>;
 
+    /// Emulate the guest accesses to `regions` with their handlers
+    fn set_mmio_regions(&mut self, regions: MmioRegions) -> std::result::Result<(), CreateVmError>;
+
+    /// Offset the TSC the guest reads from the host TSC by `offset` cycles,
+    /// and run it at `frequency_khz`, each if set
+    fn set_tsc(
+        &mut self,
+        offset: Option<i64>,
+        frequency_khz: Option<u32>,
+    ) -> std::result::Result<(), CreateVmError>;
+
+    /// Inject the external interrupt `vector`, delivered when the vCPU next runs.
+    /// Returns `false` without injecting it if the guest cannot take an interrupt
+    /// now. In that case, and if `more_pending` is set, the vCPU exits with
+    /// `VmExit::Retry` as soon as the guest can take the next interrupt.
+    fn inject_interrupt(
+        &mut self,
+        vector: u8,
+        more_pending: bool,
+    ) -> std::result::Result<bool, RegisterError>;
+
     /// Get regs
     #[allow(dead_code)]
     fn regs(&self) -> std::result::Result<CommonRegisters, RegisterError>;
@@ -370,4 +517,23 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn available_backends_match_auto_selection() {
+        use super::{HypervisorKind, available_backends, resolve_hypervisor};
+
+        let backends = available_backends();
+        assert_eq!(backends.is_empty(), !super::is_hypervisor_present());
+        for kind in [
+            HypervisorKind::Kvm,
+            HypervisorKind::Mshv,
+            HypervisorKind::Whp,
+        ] {
+            assert_eq!(resolve_hypervisor(kind).is_some(), backends.contains(&kind));
+        }
+        assert_eq!(
+            resolve_hypervisor(HypervisorKind::Auto),
+            *super::get_available_hypervisor()
+        );
+    }
 }
@@ -21,18 +21,29 @@ use std::sync::LazyLock;
 #[cfg(gdb)]
 use mshv_bindings::{DebugRegisters, hv_message_type_HVMSG_X64_EXCEPTION_INTERCEPT};
 use mshv_bindings::{
-    FloatingPointUnit, SpecialRegisters, StandardRegisters, XSave, hv_message_type,
+    FloatingPointUnit, HV_INTERCEPT_ACCESS_MASK_READ, HV_INTERCEPT_ACCESS_MASK_WRITE,
+    SpecialRegisters, StandardRegisters, XSave, hv_cpuid_entry, hv_intercept_parameters,
+    hv_intercept_type_HV_INTERCEPT_TYPE_X64_MSR, hv_message_type,
     hv_message_type_HVMSG_GPA_INTERCEPT, hv_message_type_HVMSG_UNMAPPED_GPA,
-    hv_message_type_HVMSG_X64_HALT, hv_message_type_HVMSG_X64_IO_PORT_INTERCEPT,
+    hv_message_type_HVMSG_X64_HALT, hv_message_type_HVMSG_X64_INTERRUPTION_DELIVERABLE,
+    hv_message_type_HVMSG_X64_IO_PORT_INTERCEPT, hv_message_type_HVMSG_X64_MSR_INTERCEPT,
     hv_partition_property_code_HV_PARTITION_PROPERTY_SYNTHETIC_PROC_FEATURES,
     hv_partition_synthetic_processor_features, hv_register_assoc,
-    hv_register_name_HV_X64_REGISTER_RIP, hv_register_value, mshv_user_mem_region,
+    hv_register_name_HV_REGISTER_INTERRUPT_STATE,
+    hv_register_name_HV_REGISTER_PENDING_INTERRUPTION,
+    hv_register_name_HV_X64_REGISTER_DELIVERABILITY_NOTIFICATIONS,
+    hv_register_name_HV_X64_REGISTER_RAX, hv_register_name_HV_X64_REGISTER_RDX,
+    hv_register_name_HV_X64_REGISTER_RFLAGS, hv_register_name_HV_X64_REGISTER_RIP,
+    hv_register_name_HV_X64_REGISTER_TSC, hv_register_value, mshv_install_intercept,
+    mshv_user_mem_region,
 };
 use mshv_ioctls::{Mshv, VcpuFd, VmFd};
 use tracing::{Span, instrument};
 #[cfg(feature = "trace_guest")]
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+#[cfg(gdb)]
+use crate::hypervisor::gdb::arch::HwBreakpointRegs;
 #[cfg(gdb)]
 use crate::hypervisor::gdb::{DebugError, DebuggableVm};
 use crate::hypervisor::regs::{
@@ -43,11 +54,13 @@ use crate::hypervisor::regs::{
 use crate::hypervisor::virtual_machine::XSAVE_BUFFER_SIZE;
 use crate::hypervisor::virtual_machine::{
     CreateVmError, MapMemoryError, RegisterError, RunVcpuError, UnmapMemoryError, VirtualMachine,
-    VmExit, XSAVE_MIN_SIZE,
+    VmExit, XSAVE_MIN_SIZE, overridden_host_cpuid,
 };
 use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
+use crate::sandbox::mmio::MmioRegions;
 #[cfg(feature = "trace_guest")]
 use crate::sandbox::trace::TraceContext as SandboxTraceContext;
+use crate::sandbox::{CpuidOverride, MsrPolicy};
 
 /// Determine whether the HyperV for Linux hypervisor API is present
 /// and functional.
@@ -67,15 +80,21 @@ pub(crate) fn is_hypervisor_present() -> bool {
 pub(crate) struct MshvVm {
     vm_fd: VmFd,
     vcpu_fd: VcpuFd,
+    msr_policy: MsrPolicy,
 }
 
 static MSHV: LazyLock<std::result::Result<Mshv, CreateVmError>> =
     LazyLock::new(|| Mshv::new().map_err(|e| CreateVmError::HypervisorNotAvailable(e.into())));
 
 impl MshvVm {
-    /// Create a new instance of a MshvVm
+    /// Create a new instance of a MshvVm, whose CPUID is changed by
+    /// `cpuid_overrides`, and whose accesses to the MSRs mshv does not
+    /// virtualize are handled according to `msr_policy`
     #[instrument(skip_all, parent = Span::current(), level = "Trace")]
-    pub(crate) fn new() -> std::result::Result<Self, CreateVmError> {
+    pub(crate) fn new(
+        cpuid_overrides: &[CpuidOverride],
+        msr_policy: MsrPolicy,
+    ) -> std::result::Result<Self, CreateVmError> {
         let mshv = MSHV.as_ref().map_err(|e| e.clone())?;
 
         let pr = Default::default();
@@ -103,7 +122,46 @@ impl MshvVm {
                 .map_err(|e| CreateVmError::CreateVcpuFd(e.into()))?
         };
 
-        Ok(Self { vm_fd, vcpu_fd })
+        // mshv passes CPUID through from the hardware, so the overridden
+        // leaves are replaced by the host values with the overrides applied
+        for cpuid_override in cpuid_overrides {
+            let [eax, ebx, ecx, edx] = overridden_host_cpuid(cpuid_override);
+            let entry = hv_cpuid_entry {
+                function: cpuid_override.function(),
+                index: cpuid_override.index().unwrap_or(0),
+                eax,
+                ebx,
+                ecx,
+                edx,
+                ..Default::default()
+            };
+            vcpu_fd
+                .register_intercept_result_cpuid_entry(
+                    &entry,
+                    Some(1),
+                    Some(cpuid_override.index().is_some() as u8),
+                )
+                .map_err(|e| CreateVmError::SetCpuid(e.to_string()))?;
+        }
+
+        // Deliver the accesses to the MSRs mshv does not virtualize to us
+        // instead of injecting a #GP into the guest
+        if msr_policy != MsrPolicy::Allow {
+            vm_fd
+                .install_intercept(mshv_install_intercept {
+                    access_type_mask: HV_INTERCEPT_ACCESS_MASK_READ
+                        | HV_INTERCEPT_ACCESS_MASK_WRITE,
+                    intercept_type: hv_intercept_type_HV_INTERCEPT_TYPE_X64_MSR,
+                    intercept_parameter: hv_intercept_parameters::default(),
+                })
+                .map_err(|e| CreateVmError::SetMsrPolicy(e.into()))?;
+        }
+
+        Ok(Self {
+            vm_fd,
+            vcpu_fd,
+            msr_policy,
+        })
     }
 }
 
@@ -137,6 +195,9 @@ impl VirtualMachine for MshvVm {
             hv_message_type_HVMSG_X64_IO_PORT_INTERCEPT;
         const UNMAPPED_GPA_MESSAGE: hv_message_type = hv_message_type_HVMSG_UNMAPPED_GPA;
         const INVALID_GPA_ACCESS_MESSAGE: hv_message_type = hv_message_type_HVMSG_GPA_INTERCEPT;
+        const MSR_INTERCEPT_MESSAGE: hv_message_type = hv_message_type_HVMSG_X64_MSR_INTERCEPT;
+        const INTERRUPTION_DELIVERABLE_MESSAGE: hv_message_type =
+            hv_message_type_HVMSG_X64_INTERRUPTION_DELIVERABLE;
         #[cfg(gdb)]
         const EXCEPTION_INTERCEPT: hv_message_type = hv_message_type_HVMSG_X64_EXCEPTION_INTERCEPT;
 
@@ -149,6 +210,8 @@ impl VirtualMachine for MshvVm {
         let result = match exit_reason {
             Ok(m) => match m.header.message_type {
                 HALT_MESSAGE => VmExit::Halt(),
+                // The guest can take the next injected interrupt
+                INTERRUPTION_DELIVERABLE_MESSAGE => VmExit::Retry(),
                 IO_PORT_INTERCEPT_MESSAGE => {
                     let io_message = m
                         .to_ioport_info()
@@ -196,6 +259,44 @@ impl VirtualMachine for MshvVm {
                         _ => VmExit::Unknown("Unknown MMIO access".to_string()),
                     }
                 }
+                MSR_INTERCEPT_MESSAGE => {
+                    let msr_message = m
+                        .to_msr_info()
+                        .map_err(|_| RunVcpuError::DecodeIOMessage(m.header.message_type))?;
+                    // The access type is 0 for reads and 1 for writes
+                    let write = msr_message.header.intercept_access_type == 1;
+                    match self.msr_policy {
+                        MsrPolicy::EmulateAsZero => {
+                            let rip = msr_message.header.rip
+                                + msr_message.header.instruction_length() as u64;
+                            let mut regs = vec![hv_register_assoc {
+                                name: hv_register_name_HV_X64_REGISTER_RIP,
+                                value: hv_register_value { reg64: rip },
+                                ..Default::default()
+                            }];
+                            if !write {
+                                for name in [
+                                    hv_register_name_HV_X64_REGISTER_RAX,
+                                    hv_register_name_HV_X64_REGISTER_RDX,
+                                ] {
+                                    regs.push(hv_register_assoc {
+                                        name,
+                                        value: hv_register_value { reg64: 0 },
+                                        ..Default::default()
+                                    });
+                                }
+                            }
+                            self.vcpu_fd
+                                .set_reg(&regs)
+                                .map_err(|e| RunVcpuError::EmulateMsr(e.into()))?;
+                            VmExit::Retry()
+                        }
+                        MsrPolicy::Allow | MsrPolicy::Deny => VmExit::MsrAccess {
+                            msr: msr_message.msr_number,
+                            write,
+                        },
+                    }
+                }
                 #[cfg(gdb)]
                 EXCEPTION_INTERCEPT => {
                     let ex_info = m
@@ -222,6 +323,89 @@ impl VirtualMachine for MshvVm {
         Ok(result)
     }
 
+    // Emulating an access needs the faulting instruction to be decoded,
+    // which only KVM does
+    fn set_mmio_regions(&mut self, regions: MmioRegions) -> std::result::Result<(), CreateVmError> {
+        if !regions.is_empty() {
+            return Err(CreateVmError::MmioNotSupported);
+        }
+        Ok(())
+    }
+
+    // The TSC can only be set to a value, which is computed from the host
+    // TSC when the sandbox is created
+    fn set_tsc(
+        &mut self,
+        offset: Option<i64>,
+        frequency_khz: Option<u32>,
+    ) -> std::result::Result<(), CreateVmError> {
+        if frequency_khz.is_some() {
+            return Err(CreateVmError::TscScalingNotSupported);
+        }
+        if let Some(offset) = offset {
+            let tsc = unsafe { core::arch::x86_64::_rdtsc() }.wrapping_add_signed(offset);
+            self.vcpu_fd
+                .set_reg(&[hv_register_assoc {
+                    name: hv_register_name_HV_X64_REGISTER_TSC,
+                    value: hv_register_value { reg64: tsc },
+                    ..Default::default()
+                }])
+                .map_err(|e| CreateVmError::SetTsc(e.into()))?;
+        }
+        Ok(())
+    }
+
+    fn inject_interrupt(
+        &mut self,
+        vector: u8,
+        more_pending: bool,
+    ) -> std::result::Result<bool, RegisterError> {
+        let mut state = [
+            hv_register_name_HV_X64_REGISTER_RFLAGS,
+            hv_register_name_HV_REGISTER_INTERRUPT_STATE,
+            hv_register_name_HV_REGISTER_PENDING_INTERRUPTION,
+        ]
+        .map(|name| hv_register_assoc {
+            name,
+            ..Default::default()
+        });
+        self.vcpu_fd
+            .get_reg(&mut state)
+            .map_err(|e| RegisterError::InjectInterrupt(e.into()))?;
+        let [rflags, interrupt_state, pending] = state.map(|reg| unsafe { reg.value.reg64 });
+        // RFLAGS.IF, the interrupt shadow, and whether an event is already pending
+        let injectable = rflags & (1 << 9) != 0 && interrupt_state & 1 == 0 && pending & 1 == 0;
+
+        // Bit 1 asks for an HVMSG_X64_INTERRUPTION_DELIVERABLE exit once the
+        // guest can take an interrupt
+        let mut regs = vec![hv_register_assoc {
+            name: hv_register_name_HV_X64_REGISTER_DELIVERABILITY_NOTIFICATIONS,
+            value: hv_register_value {
+                reg64: if !injectable || more_pending {
+                    1 << 1
+                } else {
+                    0
+                },
+            },
+            ..Default::default()
+        }];
+        if injectable {
+            // Bit 0 marks the interruption pending, bits 1-3 are its type (0 for
+            // an external interrupt), and bits 16-31 are its vector
+            regs.push(hv_register_assoc {
+                name: hv_register_name_HV_REGISTER_PENDING_INTERRUPTION,
+                value: hv_register_value {
+                    reg64: 1 | (vector as u64) << 16,
+                },
+                ..Default::default()
+            });
+        }
+        self.vcpu_fd
+            .set_reg(&regs)
+            .map_err(|e| RegisterError::InjectInterrupt(e.into()))?;
+        Ok(injectable)
+    }
+
     fn regs(&self) -> std::result::Result<CommonRegisters, RegisterError> {
         let mshv_regs = self
             .vcpu_fd
@@ -416,50 +600,28 @@ impl DebuggableVm for MshvVm {
         Ok(())
     }
 
-    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        use crate::hypervisor::gdb::arch::MAX_NO_OF_HW_BP;
-
-        let mut regs = self.debug_regs()?;
-
-        // Check if breakpoint already exists
-        if [regs.dr0, regs.dr1, regs.dr2, regs.dr3].contains(&addr) {
-            return Ok(());
-        }
-
-        // Find the first available LOCAL (L0–L3) slot
-        let i = (0..MAX_NO_OF_HW_BP)
-            .position(|i| regs.dr7 & (1 << (i * 2)) == 0)
-            .ok_or(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP))?;
-
-        // Assign to corresponding debug register
-        *[&mut regs.dr0, &mut regs.dr1, &mut regs.dr2, &mut regs.dr3][i] = addr;
-
-        // Enable LOCAL bit
-        regs.dr7 |= 1 << (i * 2);
-
-        self.set_debug_regs(&regs)?;
-        Ok(())
+    fn hw_breakpoint_regs(&self) -> std::result::Result<HwBreakpointRegs, DebugError> {
+        let regs = self.debug_regs()?;
+        Ok(HwBreakpointRegs {
+            addrs: [regs.dr0, regs.dr1, regs.dr2, regs.dr3],
+            dr7: regs.dr7,
+        })
     }
 
-    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
+    fn set_hw_breakpoint_regs(
+        &mut self,
+        regs: &HwBreakpointRegs,
+    ) -> std::result::Result<(), DebugError> {
         let mut debug_regs = self.debug_regs()?;
-
-        let regs = [
-            &mut debug_regs.dr0,
-            &mut debug_regs.dr1,
-            &mut debug_regs.dr2,
-            &mut debug_regs.dr3,
-        ];
-
-        if let Some(i) = regs.iter().position(|&&mut reg| reg == addr) {
-            // Clear the address
-            *regs[i] = 0;
-            // Disable LOCAL bit
-            debug_regs.dr7 &= !(1 << (i * 2));
-            self.set_debug_regs(&debug_regs)?;
-            Ok(())
-        } else {
-            Err(DebugError::HwBreakpointNotFound(addr))
-        }
+        [
+            debug_regs.dr0,
+            debug_regs.dr1,
+            debug_regs.dr2,
+            debug_regs.dr3,
+        ] = regs.addrs;
+        debug_regs.dr7 = regs.dr7;
+
+        self.set_debug_regs(&debug_regs)?;
+        Ok(())
     }
 }
@@ -26,6 +26,8 @@ use windows::Win32::System::LibraryLoader::*;
 use windows::core::s;
 use windows_result::HRESULT;
 
+#[cfg(gdb)]
+use crate::hypervisor::gdb::arch::HwBreakpointRegs;
 #[cfg(gdb)]
 use crate::hypervisor::gdb::{DebugError, DebuggableVm};
 use crate::hypervisor::regs::{
@@ -38,11 +40,13 @@ use crate::hypervisor::surrogate_process::SurrogateProcess;
 use crate::hypervisor::surrogate_process_manager::get_surrogate_process_manager;
 use crate::hypervisor::virtual_machine::{
     CreateVmError, HypervisorError, MapMemoryError, RegisterError, RunVcpuError, UnmapMemoryError,
-    VirtualMachine, VmExit, XSAVE_MIN_SIZE,
+    VirtualMachine, VmExit, XSAVE_MIN_SIZE, overridden_host_cpuid,
 };
 use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
+use crate::sandbox::mmio::MmioRegions;
 #[cfg(feature = "trace_guest")]
 use crate::sandbox::trace::TraceContext as SandboxTraceContext;
+use crate::sandbox::{CpuidOverride, MsrPolicy};
 
 #[allow(dead_code)] // Will be used for runtime hypervisor detection
 pub(crate) fn is_hypervisor_present() -> bool {
@@ -71,8 +75,13 @@ pub(crate) struct WhpVm {
     partition: WHV_PARTITION_HANDLE,
     // Surrogate process for memory mapping
     surrogate_process: SurrogateProcess,
+    msr_policy: MsrPolicy,
 }
 
+/// The bit of `WHV_EXTENDED_VM_EXITS` that makes the accesses to the
+/// MSRs the hypervisor does not virtualize exit
+const X64_MSR_EXIT: u64 = 1 << 1;
+
 // Safety: `WhpVm` is !Send because it holds `SurrogateProcess` which contains a raw pointer
 // `allocated_address` (*mut c_void). This pointer represents a memory mapped view address
 // in the surrogate process. It is never dereferenced, only used for address arithmetic and
@@ -81,8 +90,34 @@ pub(crate) struct WhpVm {
 unsafe impl Send for WhpVm {}
 
 impl WhpVm {
-    pub(crate) fn new() -> Result<Self, CreateVmError> {
+    pub(crate) fn new(
+        cpuid_overrides: &[CpuidOverride],
+        msr_policy: MsrPolicy,
+    ) -> Result<Self, CreateVmError> {
         const NUM_CPU: u32 = 1;
+        // WHP passes CPUID through from the hardware, and can only replace
+        // whole leaves, with the host values with the overrides applied
+        let cpuid_results = cpuid_overrides
+            .iter()
+            .map(|cpuid_override| {
+                if cpuid_override.index().is_some_and(|index| index != 0) {
+                    return Err(CreateVmError::SetCpuid(format!(
+                        "WHP cannot override subleaf {:#x} of CPUID leaf {:#x}",
+                        cpuid_override.index().unwrap_or(0),
+                        cpuid_override.function()
+                    )));
+                }
+                let [eax, ebx, ecx, edx] = overridden_host_cpuid(cpuid_override);
+                Ok(WHV_X64_CPUID_RESULT {
+                    Function: cpuid_override.function(),
+                    Reserved: [0; 3],
+                    Eax: eax,
+                    Ebx: ebx,
+                    Ecx: ecx,
+                    Edx: edx,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
         let partition = unsafe {
             let partition =
                 WHvCreatePartition().map_err(|e| CreateVmError::CreateVmFd(e.into()))?;
@@ -93,6 +128,29 @@ impl WhpVm {
                 std::mem::size_of_val(&NUM_CPU) as _,
             )
             .map_err(|e| CreateVmError::SetPartitionProperty(e.into()))?;
+            if !cpuid_results.is_empty() {
+                WHvSetPartitionProperty(
+                    partition,
+                    WHvPartitionPropertyCodeCpuidResultList,
+                    cpuid_results.as_ptr() as *const _,
+                    std::mem::size_of_val(cpuid_results.as_slice()) as _,
+                )
+                .map_err(|e| CreateVmError::SetCpuid(e.to_string()))?;
+            }
+            if msr_policy != MsrPolicy::Allow {
+                let property = WHV_PARTITION_PROPERTY {
+                    ExtendedVmExits: WHV_EXTENDED_VM_EXITS {
+                        AsUINT64: X64_MSR_EXIT,
+                    },
+                };
+                WHvSetPartitionProperty(
+                    partition,
+                    WHvPartitionPropertyCodeExtendedVmExits,
+                    &property as *const _ as *const c_void,
+                    std::mem::size_of::<WHV_PARTITION_PROPERTY>() as u32,
+                )
+                .map_err(|e| CreateVmError::SetMsrPolicy(e.into()))?;
+            }
             WHvSetupPartition(partition).map_err(|e| CreateVmError::InitializeVm(e.into()))?;
             WHvCreateVirtualProcessor(partition, 0, 0)
                 .map_err(|e| CreateVmError::CreateVcpuFd(e.into()))?;
@@ -109,6 +167,7 @@ impl WhpVm {
         Ok(WhpVm {
             partition,
             surrogate_process,
+            msr_policy,
         })
     }
 
@@ -253,6 +312,38 @@ impl VirtualMachine for WhpVm {
                 )
             },
             WHvRunVpExitReasonX64Halt => VmExit::Halt(),
+            WHvRunVpExitReasonX64MsrAccess => {
+                let msr_access = unsafe { exit_context.Anonymous.MsrAccess };
+                // Bit 0 of the access info is set for writes
+                let write = unsafe { msr_access.AccessInfo.AsUINT32 } & 1 != 0;
+                match self.msr_policy {
+                    MsrPolicy::EmulateAsZero => {
+                        let instruction_length = exit_context.VpContext._bitfield & 0xF;
+                        let rip = exit_context.VpContext.Rip + instruction_length as u64;
+                        let mut registers = vec![(
+                            WHvX64RegisterRip,
+                            Align16(WHV_REGISTER_VALUE { Reg64: rip }),
+                        )];
+                        if !write {
+                            registers.push((
+                                WHvX64RegisterRax,
+                                Align16(WHV_REGISTER_VALUE { Reg64: 0 }),
+                            ));
+                            registers.push((
+                                WHvX64RegisterRdx,
+                                Align16(WHV_REGISTER_VALUE { Reg64: 0 }),
+                            ));
+                        }
+                        self.set_registers(&registers)
+                            .map_err(|e| RunVcpuError::EmulateMsr(e.into()))?;
+                        VmExit::Retry()
+                    }
+                    MsrPolicy::Allow | MsrPolicy::Deny => VmExit::MsrAccess {
+                        msr: msr_access.MsrNumber,
+                        write,
+                    },
+                }
+            }
             WHvRunVpExitReasonMemoryAccess => {
                 let gpa = unsafe { exit_context.Anonymous.MemoryAccess.Gpa };
                 let access_info = unsafe {
@@ -271,6 +362,8 @@ impl VirtualMachine for WhpVm {
             }
             // Execution was cancelled by the host.
             WHvRunVpExitReasonCanceled => VmExit::Cancelled(),
+            // The guest can take the next injected interrupt
+            WHvRunVpExitReasonX64InterruptWindow => VmExit::Retry(),
             #[cfg(gdb)]
             WHvRunVpExitReasonException => {
                 let exception = unsafe { exit_context.Anonymous.VpException };
@@ -305,6 +398,88 @@ impl VirtualMachine for WhpVm {
         Ok(result)
     }
 
+    // Emulating an access needs the faulting instruction to be decoded,
+    // which only KVM does
+    fn set_mmio_regions(&mut self, regions: MmioRegions) -> std::result::Result<(), CreateVmError> {
+        if !regions.is_empty() {
+            return Err(CreateVmError::MmioNotSupported);
+        }
+        Ok(())
+    }
+
+    // The TSC can only be set to a value, which is computed from the host
+    // TSC when the sandbox is created
+    fn set_tsc(
+        &mut self,
+        offset: Option<i64>,
+        frequency_khz: Option<u32>,
+    ) -> std::result::Result<(), CreateVmError> {
+        if frequency_khz.is_some() {
+            return Err(CreateVmError::TscScalingNotSupported);
+        }
+        if let Some(offset) = offset {
+            let tsc = unsafe { core::arch::x86_64::_rdtsc() }.wrapping_add_signed(offset);
+            self.set_registers(&[(
+                WHvX64RegisterTsc,
+                Align16(WHV_REGISTER_VALUE { Reg64: tsc }),
+            )])
+            .map_err(|e| CreateVmError::SetTsc(e.into()))?;
+        }
+        Ok(())
+    }
+
+    fn inject_interrupt(
+        &mut self,
+        vector: u8,
+        more_pending: bool,
+    ) -> std::result::Result<bool, RegisterError> {
+        let names = [
+            WHvX64RegisterRflags,
+            WHvRegisterInterruptState,
+            WHvRegisterPendingInterruption,
+        ];
+        let mut state: [Align16<WHV_REGISTER_VALUE>; 3] = unsafe { std::mem::zeroed() };
+        unsafe {
+            WHvGetVirtualProcessorRegisters(
+                self.partition,
+                0,
+                names.as_ptr(),
+                names.len() as u32,
+                state.as_mut_ptr() as *mut WHV_REGISTER_VALUE,
+            )
+            .map_err(|e| RegisterError::InjectInterrupt(e.into()))?;
+        }
+        let [rflags, interrupt_state, pending] = state.map(|value| unsafe { value.0.Reg64 });
+        // RFLAGS.IF, the interrupt shadow, and whether an event is already pending
+        let injectable = rflags & (1 << 9) != 0 && interrupt_state & 1 == 0 && pending & 1 == 0;
+
+        // Bit 1 asks for a WHvRunVpExitReasonX64InterruptWindow exit once the
+        // guest can take an interrupt
+        let mut registers = vec![(
+            WHvX64RegisterDeliverabilityNotifications,
+            Align16(WHV_REGISTER_VALUE {
+                Reg64: if !injectable || more_pending {
+                    1 << 1
+                } else {
+                    0
+                },
+            }),
+        )];
+        if injectable {
+            // Bit 0 marks the interruption pending, bits 1-3 are its type (0 for
+            // an external interrupt), and bits 16-31 are its vector
+            registers.push((
+                WHvRegisterPendingInterruption,
+                Align16(WHV_REGISTER_VALUE {
+                    Reg64: 1 | (vector as u64) << 16,
+                }),
+            ));
+        }
+        self.set_registers(&registers)
+            .map_err(|e| RegisterError::InjectInterrupt(e.into()))?;
+        Ok(injectable)
+    }
+
     fn regs(&self) -> std::result::Result<CommonRegisters, RegisterError> {
         let mut whv_regs_values: [Align16<WHV_REGISTER_VALUE>; WHP_REGS_NAMES_LEN] =
             unsafe { std::mem::zeroed() };
@@ -658,7 +833,11 @@ impl DebuggableVm for WhpVm {
     }
 
     fn set_debug(&mut self, enable: bool) -> std::result::Result<(), DebugError> {
-        let extended_vm_exits = if enable { 1 << 2 } else { 0 };
+        let mut extended_vm_exits = if enable { 1 << 2 } else { 0 };
+        // Keep the MSR exits of the MSR policy
+        if self.msr_policy != MsrPolicy::Allow {
+            extended_vm_exits |= X64_MSR_EXIT;
+        }
         let exception_exit_bitmap = if enable {
             (1 << WHvX64ExceptionTypeDebugTrapOrFault.0)
                 | (1 << WHvX64ExceptionTypeBreakpointTrap.0)
@@ -711,54 +890,29 @@ impl DebuggableVm for WhpVm {
         Ok(())
     }
 
-    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        use crate::hypervisor::gdb::arch::MAX_NO_OF_HW_BP;
-
-        // Get current debug registers
-        let mut regs = self.debug_regs()?;
-
-        // Check if breakpoint already exists
-        if [regs.dr0, regs.dr1, regs.dr2, regs.dr3].contains(&addr) {
-            return Ok(());
-        }
-
-        // Find the first available LOCAL (L0–L3) slot
-        let i = (0..MAX_NO_OF_HW_BP)
-            .position(|i| regs.dr7 & (1 << (i * 2)) == 0)
-            .ok_or(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP))?;
-
-        // Assign to corresponding debug register
-        *[&mut regs.dr0, &mut regs.dr1, &mut regs.dr2, &mut regs.dr3][i] = addr;
-
-        // Enable LOCAL bit
-        regs.dr7 |= 1 << (i * 2);
-
-        self.set_debug_regs(&regs)?;
-        Ok(())
+    fn hw_breakpoint_regs(&self) -> std::result::Result<HwBreakpointRegs, DebugError> {
+        let regs = self.debug_regs()?;
+        Ok(HwBreakpointRegs {
+            addrs: [regs.dr0, regs.dr1, regs.dr2, regs.dr3],
+            dr7: regs.dr7,
+        })
     }
 
-    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        // Get current debug registers
+    fn set_hw_breakpoint_regs(
+        &mut self,
+        regs: &HwBreakpointRegs,
+    ) -> std::result::Result<(), DebugError> {
         let mut debug_regs = self.debug_regs()?;
-
-        let regs = [
-            &mut debug_regs.dr0,
-            &mut debug_regs.dr1,
-            &mut debug_regs.dr2,
-            &mut debug_regs.dr3,
-        ];
-
-        if let Some(i) = regs.iter().position(|&&mut reg| reg == addr) {
-            // Clear the address
-            *regs[i] = 0;
-            // Disable LOCAL bit
-            debug_regs.dr7 &= !(1 << (i * 2));
-
-            self.set_debug_regs(&debug_regs)?;
-            Ok(())
-        } else {
-            Err(DebugError::HwBreakpointNotFound(addr))
-        }
+        [
+            debug_regs.dr0,
+            debug_regs.dr1,
+            debug_regs.dr2,
+            debug_regs.dr3,
+        ] = regs.addrs;
+        debug_regs.dr7 = regs.dr7;
+
+        self.set_debug_regs(&debug_regs)?;
+        Ok(())
     }
 }
 
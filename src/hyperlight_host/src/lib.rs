@@ -79,12 +79,19 @@ pub mod sandbox;
 /// Signal handling for Linux
 #[cfg(target_os = "linux")]
 pub(crate) mod signal_handlers;
+/// Helpers for writing tests against Hyperlight that run wherever a
+/// hypervisor is available and are skipped elsewhere
+pub mod test_support;
 /// Utilities for testing including interacting with `simpleguest` testing guest binary
 #[cfg(test)]
 pub(crate) mod testing;
 
 /// The re-export for the `HyperlightError` type
 pub use error::HyperlightError;
+/// The re-export for the `HypervisorKind` type
+pub use hypervisor::virtual_machine::HypervisorKind;
+/// The re-export for the `available_backends` function
+pub use hypervisor::virtual_machine::available_backends;
 /// The re-export for the `is_hypervisor_present` type
 pub use hypervisor::virtual_machine::is_hypervisor_present;
 /// A sandbox that can call be used to make multiple calls to guest functions,
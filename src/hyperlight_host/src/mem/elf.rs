@@ -14,20 +14,26 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::ops::Range;
 #[cfg(feature = "mem_profile")]
 use std::sync::Arc;
 
+use goblin::elf::program_header::{PF_W, PF_X};
 #[cfg(target_arch = "aarch64")]
 use goblin::elf::reloc::{R_AARCH64_NONE, R_AARCH64_RELATIVE};
 #[cfg(target_arch = "x86_64")]
 use goblin::elf::reloc::{R_X86_64_NONE, R_X86_64_RELATIVE};
+use goblin::elf::section_header::{SHT_FINI_ARRAY, SHT_INIT_ARRAY};
 use goblin::elf::{Elf, ProgramHeaders, Reloc};
 #[cfg(not(feature = "init-paging"))]
-use goblin::elf32::program_header::PT_LOAD;
+use goblin::elf32::program_header::{PT_LOAD, PT_TLS};
 #[cfg(feature = "init-paging")]
-use goblin::elf64::program_header::PT_LOAD;
+use goblin::elf64::program_header::{PT_LOAD, PT_TLS};
+use hyperlight_common::guest_config::{GUEST_CONFIG_SECTION, GuestBinaryConfig};
+use hyperlight_common::mem::{GuestImageInfo, GuestMemoryRegion, GuestTlsImage};
 
 use super::exe::LoadInfo;
+use super::memory_region::MemoryRegionFlags;
 use crate::{Result, log_then_return, new_error};
 
 #[cfg(feature = "mem_profile")]
@@ -45,6 +51,12 @@ pub(crate) struct ElfInfo {
     shdrs: Vec<ResolvedSectionHeader>,
     entry: u64,
     relocs: Vec<Reloc>,
+    /// The `.init_array` section, as its address and size
+    init_array: Option<(u64, u64)>,
+    /// The `.fini_array` section, as its address and size
+    fini_array: Option<(u64, u64)>,
+    /// The configuration embedded in the binary, if any
+    guest_config: Option<GuestBinaryConfig>,
 }
 
 #[cfg(feature = "mem_profile")]
@@ -120,6 +132,31 @@ impl ElfInfo {
         {
             log_then_return!("ELF must have at least one PT_LOAD header");
         }
+        let array_section = |sh_type| {
+            elf.section_headers
+                .iter()
+                .find(|sh| sh.sh_type == sh_type)
+                .map(|sh| (sh.sh_addr, sh.sh_size))
+        };
+        let guest_config = match elf
+            .section_headers
+            .iter()
+            .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(GUEST_CONFIG_SECTION))
+        {
+            Some(sh) => {
+                let data = bytes
+                    .get(sh.sh_offset as usize..)
+                    .and_then(|data| data.get(..sh.sh_size as usize))
+                    .ok_or_else(|| {
+                        new_error!("{} section is out of bounds", GUEST_CONFIG_SECTION)
+                    })?;
+                match GuestBinaryConfig::from_bytes(data) {
+                    Some(config) => Some(config),
+                    None => log_then_return!("{} section is malformed", GUEST_CONFIG_SECTION),
+                }
+            }
+            None => None,
+        };
         Ok(ElfInfo {
             payload: bytes.to_vec(),
             phdrs: elf.program_headers,
@@ -138,8 +175,14 @@ impl ElfInfo {
                 .collect(),
             entry: elf.entry,
             relocs,
+            init_array: array_section(SHT_INIT_ARRAY),
+            fini_array: array_section(SHT_FINI_ARRAY),
+            guest_config,
         })
     }
+    pub(crate) fn guest_config(&self) -> Option<&GuestBinaryConfig> {
+        self.guest_config.as_ref()
+    }
     pub(crate) fn entrypoint_va(&self) -> u64 {
         self.entry
     }
@@ -162,7 +205,73 @@ impl ElfInfo {
             .unwrap();
         (max_phdr.p_vaddr + max_phdr.p_memsz - self.get_base_va()) as usize
     }
+    /// The loadable segments of the binary, as ranges of offsets from
+    /// its load address, with the permissions of their program headers
+    pub(crate) fn segments(&self) -> Vec<(Range<usize>, MemoryRegionFlags)> {
+        let base_va = self.get_base_va();
+        self.phdrs
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_LOAD)
+            .map(|phdr| {
+                let start = (phdr.p_vaddr - base_va) as usize;
+                let mut flags = MemoryRegionFlags::READ;
+                if phdr.p_flags & PF_W != 0 {
+                    flags |= MemoryRegionFlags::WRITE;
+                }
+                if phdr.p_flags & PF_X != 0 {
+                    flags |= MemoryRegionFlags::EXECUTE;
+                }
+                (start..start + phdr.p_memsz as usize, flags)
+            })
+            .collect()
+    }
+    /// Finds the thread-local storage image and the init and fini arrays
+    /// of the binary, at their addresses once it is loaded at `load_addr`
+    fn image_info(&self, load_addr: u64) -> Result<GuestImageInfo> {
+        let base_va = self.get_base_va();
+        let va_size = self.get_va_size() as u64;
+        let to_guest = |vaddr: u64, size: u64| -> Result<u64> {
+            vaddr
+                .checked_sub(base_va)
+                .filter(|offset| offset.checked_add(size).is_some_and(|end| end <= va_size))
+                .map(|offset| load_addr + offset)
+                .ok_or_else(|| {
+                    new_error!(
+                        "ELF range {:#x}..{:#x} is not in a PT_LOAD segment",
+                        vaddr,
+                        vaddr.saturating_add(size)
+                    )
+                })
+        };
+
+        let mut image = GuestImageInfo::default();
+        if let Some(tls) = self.phdrs.iter().find(|phdr| phdr.p_type == PT_TLS) {
+            let align = tls.p_align.max(1);
+            if !align.is_power_of_two() || tls.p_filesz > tls.p_memsz {
+                log_then_return!("invalid PT_TLS header");
+            }
+            image.tls = GuestTlsImage {
+                ptr: to_guest(tls.p_vaddr, tls.p_filesz)?,
+                file_size: tls.p_filesz,
+                mem_size: tls.p_memsz,
+                align,
+            };
+        }
+        for (section, region) in [
+            (self.init_array, &mut image.init_array),
+            (self.fini_array, &mut image.fini_array),
+        ] {
+            if let Some((vaddr, size)) = section.filter(|&(_, size)| size > 0) {
+                *region = GuestMemoryRegion {
+                    size,
+                    ptr: to_guest(vaddr, size)?,
+                };
+            }
+        }
+        Ok(image)
+    }
     pub(crate) fn load_at(self, load_addr: usize, target: &mut [u8]) -> Result<LoadInfo> {
+        let image = self.image_info(load_addr as u64)?;
         let base_va = self.get_base_va();
         for phdr in self.phdrs.iter().filter(|phdr| phdr.p_type == PT_LOAD) {
             let start_va = (phdr.p_vaddr - base_va) as usize;
@@ -207,6 +316,7 @@ impl ElfInfo {
                 let va_size = self.get_va_size() as u64;
                 let base_svma = self.get_base_va();
                 Ok(LoadInfo {
+                    image,
                     info: Arc::new(UnwindInfo {
                         payload: self.payload,
                         load_addr: load_addr as u64,
@@ -216,7 +326,7 @@ impl ElfInfo {
                     })
                 })
             } else {
-                Ok(LoadInfo {})
+                Ok(LoadInfo { image })
             }
         }
     }
@@ -16,11 +16,16 @@ limitations under the License.
 
 use std::fs::File;
 use std::io::Read;
+use std::ops::Range;
 #[cfg(feature = "mem_profile")]
 use std::sync::Arc;
 use std::vec::Vec;
 
+use hyperlight_common::guest_config::GuestBinaryConfig;
+use hyperlight_common::mem::GuestImageInfo;
+
 use super::elf::ElfInfo;
+use super::memory_region::MemoryRegionFlags;
 use super::ptr_offset::Offset;
 use crate::Result;
 
@@ -60,6 +65,9 @@ impl<A> framehop::ModuleSectionInfo<A> for &DummyUnwindInfo {
 
 #[derive(Clone)]
 pub(crate) struct LoadInfo {
+    /// What the guest startup code needs to know about the binary,
+    /// written to the PEB
+    pub(crate) image: GuestImageInfo,
     #[cfg(feature = "mem_profile")]
     pub(crate) info: Arc<dyn UnwindInfo>,
 }
@@ -67,6 +75,7 @@ pub(crate) struct LoadInfo {
 impl LoadInfo {
     pub(crate) fn dummy() -> Self {
         LoadInfo {
+            image: GuestImageInfo::default(),
             #[cfg(feature = "mem_profile")]
             info: Arc::new(DummyUnwindInfo {}),
         }
@@ -88,11 +97,25 @@ impl ExeInfo {
             ExeInfo::Elf(elf) => Offset::from(elf.entrypoint_va()),
         }
     }
+    /// The configuration embedded in the guest binary, see
+    /// [`hyperlight_common::guest_config`]
+    pub fn guest_config(&self) -> Option<&GuestBinaryConfig> {
+        match self {
+            ExeInfo::Elf(elf) => elf.guest_config(),
+        }
+    }
     pub fn loaded_size(&self) -> usize {
         match self {
             ExeInfo::Elf(elf) => elf.get_va_size(),
         }
     }
+    /// The loadable segments of the binary, as ranges of offsets from
+    /// its load address, with the permissions the binary asks for
+    pub(crate) fn segments(&self) -> Vec<(Range<usize>, MemoryRegionFlags)> {
+        match self {
+            ExeInfo::Elf(elf) => elf.segments(),
+        }
+    }
     // todo: this doesn't morally need to be &mut self, since we're
     // copying into target, but the PE loader chooses to apply
     // relocations in its owned representation of the PE contents,
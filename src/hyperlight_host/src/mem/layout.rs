@@ -60,10 +60,17 @@ limitations under the License.
 //! |                Input Data                 |
 //! +-------------------------------------------+ (scratch size)
 
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::mem::{offset_of, size_of};
 
-use hyperlight_common::mem::{GuestMemoryRegion, HyperlightPEB, PAGE_SIZE_USIZE};
+use hyperlight_common::mem::{
+    GuestAllocFaults, GuestImageInfo, GuestMemoryRegion, GuestTlsImage, HyperlightPEB,
+    PAGE_SIZE_USIZE,
+};
+use hyperlight_common::vmem::LARGE_PAGE_SIZE;
+use rand::RngExt;
+use serde_json::{Map, Value, json};
 use tracing::{Span, instrument};
 
 use super::memory_region::MemoryRegionType::{Code, Heap, InitData, Peb};
@@ -73,9 +80,10 @@ use super::memory_region::{
 };
 use super::shared_mem::{ExclusiveSharedMemory, SharedMemory};
 use crate::error::HyperlightError::{
-    GuestOffsetIsInvalid, MemoryRequestTooBig, MemoryRequestTooSmall,
+    GuestOffsetIsInvalid, MemoryRequestTooBig, MemoryRequestTooSmall, SnapshotLayoutMismatch,
 };
 use crate::sandbox::SandboxConfiguration;
+use crate::sandbox::snapshot_file::{Decoder, Encoder};
 use crate::{Result, new_error};
 
 #[derive(Copy, Clone)]
@@ -92,6 +100,8 @@ pub(crate) struct SandboxMemoryLayout {
     peb_output_data_offset: usize,
     peb_init_data_offset: usize,
     peb_heap_data_offset: usize,
+    peb_alloc_faults_offset: usize,
+    peb_image_offset: usize,
 
     guest_heap_buffer_offset: usize,
     init_data_offset: usize,
@@ -99,6 +109,11 @@ pub(crate) struct SandboxMemoryLayout {
 
     // other
     pub(crate) peb_address: usize,
+    // How far above its guest physical address the snapshot region is
+    // mapped in the guest, 0 unless the layout is randomized
+    gva_slide: usize,
+    // How far below its usual top the main stack of the guest starts
+    stack_offset: u64,
     code_size: usize,
     // The offset in the sandbox memory where the code starts
     guest_code_offset: usize,
@@ -123,6 +138,8 @@ impl Debug for SandboxMemoryLayout {
                 &format_args!("{:#x}", self.init_data_size),
             )
             .field("PEB Address", &format_args!("{:#x}", self.peb_address))
+            .field("GVA Slide", &format_args!("{:#x}", self.gva_slide))
+            .field("Stack Offset", &format_args!("{:#x}", self.stack_offset))
             .field("PEB Offset", &format_args!("{:#x}", self.peb_offset))
             .field("Code Size", &format_args!("{:#x}", self.code_size))
             .field(
@@ -141,6 +158,14 @@ impl Debug for SandboxMemoryLayout {
                 "Guest Heap Offset",
                 &format_args!("{:#x}", self.peb_heap_data_offset),
             )
+            .field(
+                "Alloc Faults Offset",
+                &format_args!("{:#x}", self.peb_alloc_faults_offset),
+            )
+            .field(
+                "Image Info Offset",
+                &format_args!("{:#x}", self.peb_image_offset),
+            )
             .field(
                 "Guest Heap Buffer Offset",
                 &format_args!("{:#x}", self.guest_heap_buffer_offset),
@@ -179,6 +204,16 @@ impl SandboxMemoryLayout {
     #[cfg(not(feature = "init-paging"))]
     pub(crate) const BASE_ADDRESS: usize = 0x0;
 
+    /// When the layout is randomized, the snapshot region is mapped into
+    /// the guest this far above its guest physical address, plus a
+    /// random multiple of 2MiB below [`Self::RANDOMIZED_GVA_SLOTS`]
+    const RANDOMIZED_GVA_SLIDE_MIN: usize = 0x0000_4000_0000_0000;
+    const RANDOMIZED_GVA_SLOTS: usize = 1 << 24;
+    /// When the layout is randomized, the main stack of the guest starts
+    /// a random number of pages below [`Self::RANDOMIZED_STACK_PAGES`]
+    /// under its usual top
+    const RANDOMIZED_STACK_PAGES: u64 = 1 << 24;
+
     // the offset into a sandbox's input/output buffer where the stack starts
     pub(crate) const STACK_POINTER_SIZE_BYTES: u64 = 8;
 
@@ -192,6 +227,16 @@ impl SandboxMemoryLayout {
         init_data_permissions: Option<MemoryRegionFlags>,
     ) -> Result<Self> {
         let heap_size = usize::try_from(cfg.get_heap_size())?;
+        let heap_guard_size = usize::try_from(cfg.get_heap_guard_pages())?
+            .checked_mul(PAGE_SIZE_USIZE)
+            .ok_or_else(|| new_error!("Too many heap guard pages"))?;
+        if heap_guard_size > 0 && heap_size <= 2 * heap_guard_size {
+            return Err(new_error!(
+                "The heap size {:#x} leaves no room for {} guard pages on each side",
+                heap_size,
+                cfg.get_heap_guard_pages()
+            ));
+        }
         let scratch_size = cfg.get_scratch_size();
         if scratch_size > Self::MAX_MEMORY_SIZE {
             return Err(MemoryRequestTooBig(scratch_size, Self::MAX_MEMORY_SIZE));
@@ -211,13 +256,15 @@ impl SandboxMemoryLayout {
         let peb_output_data_offset = peb_offset + offset_of!(HyperlightPEB, output_stack);
         let peb_init_data_offset = peb_offset + offset_of!(HyperlightPEB, init_data);
         let peb_heap_data_offset = peb_offset + offset_of!(HyperlightPEB, guest_heap);
+        let peb_alloc_faults_offset = peb_offset + offset_of!(HyperlightPEB, alloc_faults);
+        let peb_image_offset = peb_offset + offset_of!(HyperlightPEB, image);
 
         // The following offsets are the actual values that relate to memory layout,
         // which are written to PEB struct
         let peb_address = Self::BASE_ADDRESS + peb_offset;
         // make sure heap buffer starts at 4K boundary
-        let guest_heap_buffer_offset = (peb_heap_data_offset + size_of::<GuestMemoryRegion>())
-            .next_multiple_of(PAGE_SIZE_USIZE);
+        let guest_heap_buffer_offset =
+            (peb_offset + size_of::<HyperlightPEB>()).next_multiple_of(PAGE_SIZE_USIZE);
 
         // make sure init data starts at 4K boundary
         let init_data_offset =
@@ -230,10 +277,14 @@ impl SandboxMemoryLayout {
             peb_output_data_offset,
             peb_init_data_offset,
             peb_heap_data_offset,
+            peb_alloc_faults_offset,
+            peb_image_offset,
             sandbox_memory_config: cfg,
             code_size,
             guest_heap_buffer_offset,
             peb_address,
+            gva_slide: 0,
+            stack_offset: 0,
             guest_code_offset,
             init_data_offset,
             init_data_size,
@@ -375,7 +426,35 @@ impl SandboxMemoryLayout {
     /// Get the guest address of the code section in the sandbox
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_code_address(&self) -> usize {
-        Self::BASE_ADDRESS + self.guest_code_offset
+        Self::BASE_ADDRESS + self.gva_slide + self.guest_code_offset
+    }
+
+    /// How far above its guest physical address the snapshot region is
+    /// mapped in the guest
+    #[cfg_attr(not(feature = "init-paging"), allow(unused))]
+    pub(crate) fn gva_slide(&self) -> usize {
+        self.gva_slide
+    }
+
+    /// Maps the snapshot region at a random guest virtual address and
+    /// moves the main stack of the guest down by a random number of
+    /// pages, see [`SandboxConfiguration::set_guest_aslr`]. This must
+    /// happen before the guest binary is loaded and the page tables are
+    /// built.
+    pub(crate) fn randomize(&mut self) {
+        let mut rng = rand::rng();
+        let slot = rng.random_range(0..Self::RANDOMIZED_GVA_SLOTS);
+        let stack_pages = rng.random_range(0..Self::RANDOMIZED_STACK_PAGES);
+        self.set_randomization(
+            Self::RANDOMIZED_GVA_SLIDE_MIN + slot * LARGE_PAGE_SIZE,
+            stack_pages * PAGE_SIZE_USIZE as u64,
+        );
+    }
+
+    fn set_randomization(&mut self, gva_slide: usize, stack_offset: u64) {
+        self.gva_slide = gva_slide;
+        self.stack_offset = stack_offset;
+        self.peb_address = Self::BASE_ADDRESS + gva_slide + self.peb_offset;
     }
 
     /// Get the total size of guest memory in `self`'s memory
@@ -414,12 +493,113 @@ impl SandboxMemoryLayout {
         Ok(())
     }
 
+    /// The size of the guard pages on each side of the guest heap
+    fn heap_guard_size(&self) -> usize {
+        self.sandbox_memory_config.get_heap_guard_pages() as usize * PAGE_SIZE_USIZE
+    }
+
+    /// Whether no page of the guest may be both writable and executable,
+    /// see [`SandboxConfiguration::set_guest_write_xor_execute`]
+    pub(crate) fn write_xor_execute(&self) -> bool {
+        self.sandbox_memory_config.get_guest_write_xor_execute()
+    }
+
+    /// Whether the host memory of the sandbox is zeroed before it is
+    /// released, see [`SandboxConfiguration::set_zeroize_memory`]
+    pub(crate) fn zeroize_memory(&self) -> bool {
+        self.sandbox_memory_config.get_zeroize_memory()
+    }
+
+    /// What backs the host memory of the sandbox, see
+    /// [`SandboxConfiguration::set_memory_backing`]
+    pub(crate) fn memory_backing(&self) -> crate::sandbox::MemoryBacking {
+        self.sandbox_memory_config.get_memory_backing()
+    }
+
+    /// Whether the host memory of the sandbox is backed by huge pages
+    pub(crate) fn host_huge_pages(&self) -> bool {
+        self.sandbox_memory_config.get_host_huge_pages()
+    }
+
     /// Get the size of the memory region used for page tables
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_pt_size(&self) -> usize {
         self.pt_size.unwrap_or(0)
     }
 
+    /// Describe this layout in the stable form that is checked when
+    /// restoring snapshots
+    pub(crate) fn info(&self) -> SandboxLayoutInfo {
+        SandboxLayoutInfo {
+            version: SandboxLayoutInfo::VERSION,
+            base_address: Self::BASE_ADDRESS as u64,
+            memory_size: self
+                .get_unaligned_memory_size()
+                .next_multiple_of(PAGE_SIZE_USIZE) as u64,
+            scratch_size: self.scratch_size as u64,
+            input_data_size: self.sandbox_memory_config.get_input_data_size() as u64,
+            output_data_size: self.sandbox_memory_config.get_output_data_size() as u64,
+            code_offset: self.guest_code_offset as u64,
+            code_size: self.code_size as u64,
+            peb_offset: self.peb_offset as u64,
+            peb_size: size_of::<HyperlightPEB>() as u64,
+            peb_fields: SandboxLayoutInfo::build_peb_fields(),
+            heap_offset: self.guest_heap_buffer_offset as u64,
+            heap_size: self.heap_size as u64,
+            init_data_offset: self.init_data_offset as u64,
+            init_data_size: self.init_data_size as u64,
+            page_table_size: self.get_pt_size() as u64,
+        }
+    }
+
+    /// Writes the sizes this layout is computed from to a snapshot file
+    pub(crate) fn encode(&self, enc: &mut Encoder) {
+        enc.u64(self.heap_size as u64);
+        enc.u64(self.scratch_size as u64);
+        enc.u64(self.sandbox_memory_config.get_input_data_size() as u64);
+        enc.u64(self.sandbox_memory_config.get_output_data_size() as u64);
+        enc.u64(self.sandbox_memory_config.get_max_stack_size());
+        enc.u64(self.sandbox_memory_config.get_heap_guard_pages());
+        enc.u8(self.sandbox_memory_config.get_guest_write_xor_execute() as u8);
+        enc.u64(self.code_size as u64);
+        enc.u64(self.init_data_size as u64);
+        enc.option_u64(self.init_data_permissions.map(|flags| flags.bits() as u64));
+        enc.option_u64(self.pt_size.map(|size| size as u64));
+        enc.u64(self.gva_slide as u64);
+        enc.u64(self.stack_offset);
+    }
+
+    /// Computes the layout written by [`encode`](Self::encode) again
+    pub(crate) fn decode(dec: &mut Decoder) -> Result<Self> {
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_heap_size(dec.u64()?);
+        cfg.set_scratch_size(dec.usize()?);
+        cfg.set_input_data_size(dec.usize()?);
+        cfg.set_output_data_size(dec.usize()?);
+        cfg.set_max_stack_size(dec.u64()?);
+        cfg.set_heap_guard_pages(dec.u64()?);
+        cfg.set_guest_write_xor_execute(dec.u8()? != 0);
+        let code_size = dec.usize()?;
+        let init_data_size = dec.usize()?;
+        let init_data_permissions = dec
+            .option_u64()?
+            .map(|bits| {
+                u32::try_from(bits)
+                    .ok()
+                    .and_then(MemoryRegionFlags::from_bits)
+                    .ok_or_else(|| new_error!("Invalid init data permissions {:#x}", bits))
+            })
+            .transpose()?;
+        let mut layout = Self::new(cfg, code_size, init_data_size, init_data_permissions)?;
+        if let Some(pt_size) = dec.option_u64()? {
+            layout.set_pt_size(usize::try_from(pt_size)?)?;
+        }
+        let gva_slide = dec.usize()?;
+        let stack_offset = dec.u64()?;
+        layout.set_randomization(gva_slide, stack_offset);
+        Ok(layout)
+    }
+
     /// Returns the memory regions associated with this memory layout,
     /// suitable for passing to a hypervisor for mapping into memory
     #[cfg_attr(not(feature = "init-paging"), allow(unused))]
@@ -463,19 +643,28 @@ impl SandboxMemoryLayout {
             ));
         }
 
-        // heap
+        // heap, between its guard pages, which are not mapped in the
+        // guest
+        let heap_guard_size = self.heap_guard_size();
+        if heap_guard_size > 0 {
+            builder.push_page_aligned(heap_guard_size, MemoryRegionFlags::NONE, Heap);
+        }
         #[cfg(feature = "executable_heap")]
-        let init_data_offset = builder.push_page_aligned(
-            self.heap_size,
+        let mut init_data_offset = builder.push_page_aligned(
+            self.heap_size - 2 * heap_guard_size,
             MemoryRegionFlags::READ | MemoryRegionFlags::WRITE | MemoryRegionFlags::EXECUTE,
             Heap,
         );
         #[cfg(not(feature = "executable_heap"))]
-        let init_data_offset = builder.push_page_aligned(
-            self.heap_size,
+        let mut init_data_offset = builder.push_page_aligned(
+            self.heap_size - 2 * heap_guard_size,
             MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
             Heap,
         );
+        if heap_guard_size > 0 {
+            init_data_offset =
+                builder.push_page_aligned(heap_guard_size, MemoryRegionFlags::NONE, Heap);
+        }
 
         let expected_init_data_offset = TryInto::<usize>::try_into(self.init_data_offset)?;
 
@@ -519,6 +708,48 @@ impl SandboxMemoryLayout {
         Ok(())
     }
 
+    /// Write what the guest startup code needs to know about the guest
+    /// binary to the PEB in `out`
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_image_info(&self, out: &mut [u8], image: &GuestImageInfo) -> Result<()> {
+        let tls = offset_of!(GuestImageInfo, tls);
+        let init_array = offset_of!(GuestImageInfo, init_array);
+        let fini_array = offset_of!(GuestImageInfo, fini_array);
+        let fields = [
+            (tls + offset_of!(GuestTlsImage, ptr), image.tls.ptr),
+            (
+                tls + offset_of!(GuestTlsImage, file_size),
+                image.tls.file_size,
+            ),
+            (
+                tls + offset_of!(GuestTlsImage, mem_size),
+                image.tls.mem_size,
+            ),
+            (tls + offset_of!(GuestTlsImage, align), image.tls.align),
+            (
+                init_array + offset_of!(GuestMemoryRegion, size),
+                image.init_array.size,
+            ),
+            (
+                init_array + offset_of!(GuestMemoryRegion, ptr),
+                image.init_array.ptr,
+            ),
+            (
+                fini_array + offset_of!(GuestMemoryRegion, size),
+                image.fini_array.size,
+            ),
+            (
+                fini_array + offset_of!(GuestMemoryRegion, ptr),
+                image.fini_array.ptr,
+            ),
+        ];
+        for (offset, value) in fields {
+            let offset = self.peb_image_offset + offset;
+            out[offset..offset + size_of::<u64>()].copy_from_slice(&value.to_le_bytes());
+        }
+        Ok(())
+    }
+
     /// Write the finished memory layout to `shared_mem` and return
     /// `Ok` if successful.
     ///
@@ -534,7 +765,7 @@ impl SandboxMemoryLayout {
     ) -> Result<()> {
         macro_rules! get_address {
             ($something:ident) => {
-                u64::try_from(guest_offset + self.$something)?
+                u64::try_from(guest_offset + self.gva_slide + self.$something)?
             };
         }
 
@@ -583,11 +814,36 @@ impl SandboxMemoryLayout {
         let addr = get_address!(init_data_offset);
         shared_mem.write_u64(self.get_init_data_pointer_offset(), addr)?;
 
-        // Set up heap buffer pointer
-        let addr = get_address!(guest_heap_buffer_offset);
-        shared_mem.write_u64(self.get_heap_size_offset(), self.heap_size.try_into()?)?;
+        // Set up heap buffer pointer, leaving out the guard pages
+        let heap_guard_size = self.heap_guard_size();
+        let addr = get_address!(guest_heap_buffer_offset) + heap_guard_size as u64;
+        shared_mem.write_u64(
+            self.get_heap_size_offset(),
+            (self.heap_size - 2 * heap_guard_size).try_into()?,
+        )?;
         shared_mem.write_u64(self.get_heap_pointer_offset(), addr)?;
 
+        // Set up the limit and the position of the main stack
+        shared_mem.write_u64(
+            self.peb_offset + offset_of!(HyperlightPEB, max_stack_size),
+            self.sandbox_memory_config.get_max_stack_size(),
+        )?;
+        shared_mem.write_u64(
+            self.peb_offset + offset_of!(HyperlightPEB, stack_offset),
+            self.stack_offset,
+        )?;
+
+        // Set up the allocation failures the guest allocator injects
+        let alloc_faults = self.sandbox_memory_config.get_guest_alloc_faults();
+        shared_mem.write_u64(
+            self.peb_alloc_faults_offset + offset_of!(GuestAllocFaults, fail_every),
+            alloc_faults.fail_every,
+        )?;
+        shared_mem.write_u64(
+            self.peb_alloc_faults_offset + offset_of!(GuestAllocFaults, byte_budget),
+            alloc_faults.byte_budget,
+        )?;
+
         // End of setting up the PEB
 
         // The input and output data regions do not have their layout
@@ -599,6 +855,232 @@ impl SandboxMemoryLayout {
     }
 }
 
+/// A stable description of the memory layout of a sandbox.
+///
+/// Snapshots only restore correctly into sandboxes with the same
+/// layout, and the layout depends both on the sandbox configuration and
+/// on the build of hyperlight, through the shape of the PEB. The
+/// description can be saved alongside a snapshot or migrated sandbox
+/// state as JSON, and checked with
+/// [`check_compatible`](Self::check_compatible) before the state is
+/// restored by another host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SandboxLayoutInfo {
+    /// The version of this description, see [`Self::VERSION`]
+    pub version: u32,
+    /// The guest physical address the snapshot region starts at
+    pub base_address: u64,
+    /// The size of the snapshot region
+    pub memory_size: u64,
+    /// The size of the scratch region
+    pub scratch_size: u64,
+    /// The size of the input data buffer
+    pub input_data_size: u64,
+    /// The size of the output data buffer
+    pub output_data_size: u64,
+    /// The offset of the guest code in the snapshot region
+    pub code_offset: u64,
+    /// The size of the guest code
+    pub code_size: u64,
+    /// The offset of the PEB in the snapshot region
+    pub peb_offset: u64,
+    /// The size of the PEB
+    pub peb_size: u64,
+    /// The offsets of the fields of the PEB, from the start of the PEB
+    pub peb_fields: BTreeMap<String, u64>,
+    /// The offset of the guest heap in the snapshot region
+    pub heap_offset: u64,
+    /// The size of the guest heap
+    pub heap_size: u64,
+    /// The offset of the init data in the snapshot region
+    pub init_data_offset: u64,
+    /// The size of the init data
+    pub init_data_size: u64,
+    /// The size of the page tables. Each snapshot builds its own page
+    /// tables, so this is not checked for compatibility.
+    pub page_table_size: u64,
+}
+
+impl SandboxLayoutInfo {
+    /// The version of the layout description produced by this build.
+    /// It changes when the way the sandbox memory is laid out changes,
+    /// and descriptions of another version are never compatible.
+    pub const VERSION: u32 = 3;
+
+    fn build_peb_fields() -> BTreeMap<String, u64> {
+        [
+            ("input_stack", offset_of!(HyperlightPEB, input_stack)),
+            ("output_stack", offset_of!(HyperlightPEB, output_stack)),
+            ("init_data", offset_of!(HyperlightPEB, init_data)),
+            ("guest_heap", offset_of!(HyperlightPEB, guest_heap)),
+            ("alloc_faults", offset_of!(HyperlightPEB, alloc_faults)),
+            ("image", offset_of!(HyperlightPEB, image)),
+            ("max_stack_size", offset_of!(HyperlightPEB, max_stack_size)),
+            ("stack_offset", offset_of!(HyperlightPEB, stack_offset)),
+        ]
+        .into_iter()
+        .map(|(name, offset)| (name.to_string(), offset as u64))
+        .collect()
+    }
+
+    /// Checks that state laid out as described by `self` can be restored
+    /// by this build of hyperlight, whatever the sandbox configuration.
+    /// This compares the version and the shape of the PEB.
+    pub fn check_build_compatible(&self) -> Result<()> {
+        self.check_peb(
+            Self::VERSION,
+            size_of::<HyperlightPEB>() as u64,
+            &Self::build_peb_fields(),
+        )
+    }
+
+    /// Checks that state laid out as described by `self`, for example a
+    /// snapshot, can be restored into a sandbox laid out as described by
+    /// `current`. Returns a
+    /// [`SnapshotLayoutMismatch`](crate::HyperlightError::SnapshotLayoutMismatch)
+    /// error naming the first difference found.
+    pub fn check_compatible(&self, current: &SandboxLayoutInfo) -> Result<()> {
+        self.check_peb(current.version, current.peb_size, &current.peb_fields)?;
+        let fields = [
+            ("base_address", self.base_address, current.base_address),
+            ("memory_size", self.memory_size, current.memory_size),
+            ("scratch_size", self.scratch_size, current.scratch_size),
+            (
+                "input_data_size",
+                self.input_data_size,
+                current.input_data_size,
+            ),
+            (
+                "output_data_size",
+                self.output_data_size,
+                current.output_data_size,
+            ),
+            ("code_offset", self.code_offset, current.code_offset),
+            ("code_size", self.code_size, current.code_size),
+            ("peb_offset", self.peb_offset, current.peb_offset),
+            ("heap_offset", self.heap_offset, current.heap_offset),
+            ("heap_size", self.heap_size, current.heap_size),
+            (
+                "init_data_offset",
+                self.init_data_offset,
+                current.init_data_offset,
+            ),
+            (
+                "init_data_size",
+                self.init_data_size,
+                current.init_data_size,
+            ),
+        ];
+        match fields.into_iter().find(|(_, ours, theirs)| ours != theirs) {
+            Some((name, ours, theirs)) => Err(SnapshotLayoutMismatch(format!(
+                "{name} is {ours:#x}, expected {theirs:#x}"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn check_peb(
+        &self,
+        version: u32,
+        peb_size: u64,
+        peb_fields: &BTreeMap<String, u64>,
+    ) -> Result<()> {
+        if self.version != version {
+            return Err(SnapshotLayoutMismatch(format!(
+                "layout version is {}, expected {}",
+                self.version, version
+            )));
+        }
+        if self.peb_size != peb_size {
+            return Err(SnapshotLayoutMismatch(format!(
+                "PEB size is {:#x}, expected {:#x}",
+                self.peb_size, peb_size
+            )));
+        }
+        if self.peb_fields != *peb_fields {
+            let name = peb_fields
+                .iter()
+                .find(|(name, offset)| self.peb_fields.get(*name) != Some(offset))
+                .or_else(|| {
+                    self.peb_fields
+                        .iter()
+                        .find(|(name, _)| !peb_fields.contains_key(*name))
+                })
+                .map(|(name, _)| name.as_str())
+                .unwrap_or_default();
+            return Err(SnapshotLayoutMismatch(format!(
+                "PEB field {name} does not match"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Serializes the description to JSON
+    pub fn to_json(&self) -> String {
+        let value = json!({
+            "version": self.version,
+            "base_address": self.base_address,
+            "memory_size": self.memory_size,
+            "scratch_size": self.scratch_size,
+            "input_data_size": self.input_data_size,
+            "output_data_size": self.output_data_size,
+            "code_offset": self.code_offset,
+            "code_size": self.code_size,
+            "peb_offset": self.peb_offset,
+            "peb_size": self.peb_size,
+            "peb_fields": self.peb_fields,
+            "heap_offset": self.heap_offset,
+            "heap_size": self.heap_size,
+            "init_data_offset": self.init_data_offset,
+            "init_data_size": self.init_data_size,
+            "page_table_size": self.page_table_size,
+        });
+        // Serializing a `Value` cannot fail
+        serde_json::to_string_pretty(&value).unwrap_or_default()
+    }
+
+    /// Deserializes a description produced by [`to_json`](Self::to_json)
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json)?;
+        let field = |name: &str| {
+            value
+                .get(name)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| new_error!("Sandbox layout has no {}", name))
+        };
+        let peb_fields = value
+            .get("peb_fields")
+            .and_then(Value::as_object)
+            .map(Map::iter)
+            .ok_or_else(|| new_error!("Sandbox layout has no peb_fields"))?
+            .map(|(name, offset)| {
+                offset
+                    .as_u64()
+                    .map(|offset| (name.clone(), offset))
+                    .ok_or_else(|| new_error!("Sandbox layout has an invalid PEB field {}", name))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: u32::try_from(field("version")?)?,
+            base_address: field("base_address")?,
+            memory_size: field("memory_size")?,
+            scratch_size: field("scratch_size")?,
+            input_data_size: field("input_data_size")?,
+            output_data_size: field("output_data_size")?,
+            code_offset: field("code_offset")?,
+            code_size: field("code_size")?,
+            peb_offset: field("peb_offset")?,
+            peb_size: field("peb_size")?,
+            peb_fields,
+            heap_offset: field("heap_offset")?,
+            heap_size: field("heap_size")?,
+            init_data_offset: field("init_data_offset")?,
+            init_data_size: field("init_data_size")?,
+            page_table_size: field("page_table_size")?,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use hyperlight_common::mem::PAGE_SIZE_USIZE;
@@ -628,6 +1110,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_heap_guard_pages() {
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_heap_guard_pages(2);
+        let layout = SandboxMemoryLayout::new(cfg, 4096, 0, None).unwrap();
+        let regions = layout
+            .get_memory_regions_::<crate::mem::memory_region::GuestMemoryRegion>(())
+            .unwrap();
+        let heap: Vec<_> = regions
+            .iter()
+            .filter(|rgn| rgn.region_type == Heap)
+            .map(|rgn| (rgn.guest_region.len(), rgn.flags))
+            .collect();
+        let guard = (2 * PAGE_SIZE_USIZE, MemoryRegionFlags::NONE);
+        assert_eq!(heap.len(), 3);
+        assert_eq!(heap[0], guard);
+        assert_eq!(heap[1].0, layout.heap_size - 4 * PAGE_SIZE_USIZE);
+        assert_eq!(heap[2], guard);
+
+        cfg.set_heap_size(4 * PAGE_SIZE_USIZE as u64);
+        assert!(SandboxMemoryLayout::new(cfg, 4096, 0, None).is_err());
+    }
+
     #[test]
     fn test_max_memory_sandbox() {
         let mut cfg = SandboxConfiguration::default();
@@ -636,4 +1141,80 @@ mod tests {
         let layout = SandboxMemoryLayout::new(cfg, 4096, 4096, None);
         assert!(matches!(layout.unwrap_err(), MemoryRequestTooBig(..)));
     }
+
+    #[test]
+    fn test_write_image_info() {
+        let layout =
+            SandboxMemoryLayout::new(SandboxConfiguration::default(), 4096, 0, None).unwrap();
+        let image = GuestImageInfo {
+            tls: GuestTlsImage {
+                ptr: 0x1000,
+                file_size: 0x10,
+                mem_size: 0x30,
+                align: 0x8,
+            },
+            init_array: GuestMemoryRegion {
+                size: 0x18,
+                ptr: 0x2000,
+            },
+            fini_array: GuestMemoryRegion {
+                size: 0x8,
+                ptr: 0x2018,
+            },
+        };
+        let mut memory = vec![0; layout.get_memory_size().unwrap()];
+        layout.write_image_info(&mut memory, &image).unwrap();
+
+        let peb = unsafe {
+            std::ptr::read_unaligned(memory[layout.peb_offset..].as_ptr() as *const HyperlightPEB)
+        };
+        assert_eq!(peb.image, image);
+    }
+
+    // The golden file pins the layout of a default sandbox. If this
+    // fails, snapshots taken by earlier builds can no longer be
+    // restored: bump `SandboxLayoutInfo::VERSION` and update the file.
+    #[test]
+    #[cfg(feature = "init-paging")]
+    fn test_layout_info_golden() {
+        let layout =
+            SandboxMemoryLayout::new(SandboxConfiguration::default(), 4096, 0, None).unwrap();
+        let golden =
+            SandboxLayoutInfo::from_json(include_str!("../../tests/golden/sandbox_layout.json"))
+                .unwrap();
+        assert_eq!(layout.info(), golden);
+        golden.check_build_compatible().unwrap();
+    }
+
+    #[test]
+    fn test_layout_info_compatibility() {
+        let layout =
+            SandboxMemoryLayout::new(SandboxConfiguration::default(), 4096, 0, None).unwrap();
+        let info = layout.info();
+        let parsed = SandboxLayoutInfo::from_json(&info.to_json()).unwrap();
+        assert_eq!(parsed, info);
+        parsed.check_compatible(&info).unwrap();
+
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_heap_size(2 * SandboxConfiguration::DEFAULT_HEAP_SIZE);
+        let other = SandboxMemoryLayout::new(cfg, 4096, 0, None).unwrap().info();
+        assert!(matches!(
+            info.check_compatible(&other),
+            Err(SnapshotLayoutMismatch(_))
+        ));
+
+        let mut old = info.clone();
+        old.version -= 1;
+        assert!(matches!(
+            old.check_build_compatible(),
+            Err(SnapshotLayoutMismatch(_))
+        ));
+
+        let mut old = info;
+        old.peb_fields.remove("image");
+        assert!(matches!(
+            old.check_compatible(&parsed),
+            Err(SnapshotLayoutMismatch(_))
+        ));
+    }
 }
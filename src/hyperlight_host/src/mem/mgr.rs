@@ -19,19 +19,22 @@ use hyperlight_common::flatbuffer_wrappers::function_call::{
 };
 use hyperlight_common::flatbuffer_wrappers::function_types::FunctionCallResult;
 use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
-use hyperlight_common::vmem::{self, PAGE_TABLE_SIZE, PageTableEntry, PhysAddr};
-#[cfg(all(feature = "crashdump", feature = "init-paging"))]
-use hyperlight_common::vmem::{BasicMapping, MappingKind};
+use hyperlight_common::layout::scratch_base_gpa;
+use hyperlight_common::vmem::{
+    self, BasicMapping, MappingKind, PAGE_TABLE_SIZE, PageTableEntry, PhysAddr,
+};
 use tracing::{Span, instrument};
 
 use super::layout::SandboxMemoryLayout;
 use super::shared_mem::{ExclusiveSharedMemory, GuestSharedMemory, HostSharedMemory, SharedMemory};
 use crate::hypervisor::regs::CommonSpecialRegisters;
-use crate::mem::memory_region::MemoryRegion;
 #[cfg(crashdump)]
-use crate::mem::memory_region::{
-    CrashDumpRegion, HostGuestMemoryRegion, MemoryRegionFlags, MemoryRegionType,
-};
+use crate::mem::memory_region::{CrashDumpRegion, HostGuestMemoryRegion, MemoryRegionType};
+use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
+#[cfg(feature = "mem_profile")]
+use crate::sandbox::MemProfileCapture;
+use crate::sandbox::MemoryBacking;
+use crate::sandbox::metrics::MemoryStats;
 use crate::sandbox::snapshot::{NextAction, Snapshot};
 use crate::{Result, new_error};
 
@@ -142,6 +145,19 @@ pub(crate) struct SandboxMemoryManager<S> {
     pub(crate) mapped_rgns: u64,
     /// Buffer for accumulating guest abort messages
     pub(crate) abort_buffer: Vec<u8>,
+    /// The hash of the snapshot that `shared_mem` was last restored
+    /// from, in which case only the pages written since then need to
+    /// be copied to restore it again
+    pub(crate) restored_snapshot: Option<[u8; 32]>,
+    /// The bytes the host and the guest passed each other through the
+    /// input and output data buffers, counted by [`SandboxUsage`]
+    ///
+    /// [`SandboxUsage`]: crate::sandbox::SandboxUsage
+    pub(crate) transferred_bytes: u64,
+    /// Which guest allocations are reported to the memory profiler,
+    /// kept across snapshot restores
+    #[cfg(feature = "mem_profile")]
+    pub(crate) mem_profile_capture: MemProfileCapture,
 }
 
 pub(crate) struct GuestPageTableBuffer {
@@ -232,6 +248,106 @@ impl GuestPageTableBuffer {
     }
 }
 
+/// The live page tables of a running sandbox, which are in its scratch
+/// region, for the host to change the mappings of the guest. New tables
+/// are taken from the physical page allocator of the guest.
+struct ScratchPageTables<'a> {
+    snap: &'a HostSharedMemory,
+    scratch: &'a HostSharedMemory,
+    root: u64,
+    /// Set when an entry could not be written, since [`vmem::map`] has no
+    /// way to report it
+    failed: std::cell::Cell<bool>,
+}
+
+impl ScratchPageTables<'_> {
+    fn scratch_offset(&self, gpa: u64) -> Option<usize> {
+        let size = self.scratch.mem_size();
+        gpa.checked_sub(scratch_base_gpa(size))
+            .map(|offset| offset as usize)
+            .filter(|offset| offset + 8 <= size)
+    }
+}
+
+impl vmem::TableReadOps for ScratchPageTables<'_> {
+    type TableAddr = u64;
+
+    fn entry_addr(addr: u64, offset: u64) -> u64 {
+        addr + offset
+    }
+
+    unsafe fn read_entry(&self, addr: u64) -> PageTableEntry {
+        // Entries out of bounds read as not present
+        crate::sandbox::snapshot::access_gpa(self.snap, self.scratch, self.scratch.mem_size(), addr)
+            .and_then(|(mem, offset)| mem.read::<u64>(offset).ok())
+            .unwrap_or(0)
+    }
+
+    fn to_phys(addr: u64) -> PhysAddr {
+        addr
+    }
+
+    fn from_phys(addr: PhysAddr) -> u64 {
+        addr
+    }
+
+    fn root_table(&self) -> u64 {
+        self.root
+    }
+}
+
+impl vmem::TableOps for ScratchPageTables<'_> {
+    type TableMovability = vmem::MayNotMoveTable;
+
+    unsafe fn alloc_table(&self) -> u64 {
+        use hyperlight_common::layout::{MAX_GPA, SCRATCH_TOP_ALLOCATOR_OFFSET};
+
+        let allocator = self.scratch.mem_size() - SCRATCH_TOP_ALLOCATOR_OFFSET as usize;
+        let table = self.scratch.read::<u64>(allocator).unwrap_or(u64::MAX);
+        // The same limit as the guest allocator, which leaves the top
+        // pages of the scratch region alone
+        let limit = (MAX_GPA - vmem::PAGE_SIZE * 2) as u64;
+        let zeroed = table
+            .checked_add(PAGE_TABLE_SIZE as u64)
+            .filter(|&end| end < limit)
+            .and_then(|_| self.scratch_offset(table))
+            .is_some_and(|offset| {
+                self.scratch
+                    .copy_from_slice(&[0; PAGE_TABLE_SIZE], offset)
+                    .is_ok()
+                    && self
+                        .scratch
+                        .write::<u64>(allocator, table + PAGE_TABLE_SIZE as u64)
+                        .is_ok()
+            });
+        if !zeroed {
+            self.failed.set(true);
+        }
+        table
+    }
+
+    unsafe fn write_entry(&self, addr: u64, entry: PageTableEntry) -> Option<vmem::Void> {
+        // The live tables are all in the scratch region
+        let written = self
+            .scratch_offset(addr)
+            .is_some_and(|offset| self.scratch.write::<u64>(offset, entry).is_ok());
+        if !written {
+            self.failed.set(true);
+        }
+        None
+    }
+
+    unsafe fn update_root(&self, impossible: vmem::Void) {
+        match impossible {}
+    }
+}
+
+impl<'a> core::convert::AsRef<ScratchPageTables<'a>> for ScratchPageTables<'a> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
 impl<S> SandboxMemoryManager<S>
 where
     S: SharedMemory,
@@ -244,6 +360,10 @@ where
         scratch_mem: S,
         entrypoint: NextAction,
     ) -> Self {
+        if layout.zeroize_memory() {
+            shared_mem.zeroize_on_release();
+            scratch_mem.zeroize_on_release();
+        }
         Self {
             layout,
             shared_mem,
@@ -251,6 +371,10 @@ where
             entrypoint,
             mapped_rgns: 0,
             abort_buffer: Vec::new(),
+            restored_snapshot: None,
+            transferred_bytes: 0,
+            #[cfg(feature = "mem_profile")]
+            mem_profile_capture: MemProfileCapture::default(),
         }
     }
 
@@ -290,12 +414,43 @@ where
     }
 }
 
+/// Allocates `size` bytes of memory for a sandbox laid out as `layout`,
+/// to be mapped at the guest physical address `guest_base`
+fn new_sandbox_mem(
+    layout: &SandboxMemoryLayout,
+    size: usize,
+    guest_base: u64,
+) -> Result<ExclusiveSharedMemory> {
+    if layout.host_huge_pages() {
+        return ExclusiveSharedMemory::new_with_huge_pages(size, guest_base);
+    }
+    match layout.memory_backing() {
+        MemoryBacking::Anonymous => ExclusiveSharedMemory::new(size),
+        #[cfg(target_os = "linux")]
+        MemoryBacking::Memfd { sealed } => ExclusiveSharedMemory::new_memfd(size, sealed),
+        #[cfg(not(target_os = "linux"))]
+        MemoryBacking::Memfd { .. } => Err(new_error!(
+            "Backing sandbox memory with a memfd is only supported on Linux"
+        )),
+    }
+}
+
+/// Allocates the scratch region of a sandbox laid out as `layout`
+fn new_scratch_mem(layout: &SandboxMemoryLayout) -> Result<ExclusiveSharedMemory> {
+    let size = layout.get_scratch_size();
+    new_sandbox_mem(layout, size, scratch_base_gpa(size))
+}
+
 impl SandboxMemoryManager<ExclusiveSharedMemory> {
     pub(crate) fn from_snapshot(s: &Snapshot) -> Result<Self> {
         let layout = *s.layout();
-        let mut shared_mem = ExclusiveSharedMemory::new(s.mem_size())?;
-        shared_mem.copy_from_slice(s.memory(), 0)?;
-        let scratch_mem = ExclusiveSharedMemory::new(s.layout().get_scratch_size())?;
+        let mut shared_mem = new_sandbox_mem(
+            &layout,
+            s.mem_size(),
+            SandboxMemoryLayout::BASE_ADDRESS as u64,
+        )?;
+        shared_mem.init_from_snapshot(s)?;
+        let scratch_mem = new_scratch_mem(&layout)?;
         let entrypoint = s.entrypoint();
         Ok(Self::new(layout, shared_mem, scratch_mem, entrypoint))
     }
@@ -336,6 +491,10 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
             entrypoint: self.entrypoint,
             mapped_rgns: self.mapped_rgns,
             abort_buffer: self.abort_buffer,
+            restored_snapshot: self.restored_snapshot,
+            transferred_bytes: self.transferred_bytes,
+            #[cfg(feature = "mem_profile")]
+            mem_profile_capture: self.mem_profile_capture,
         };
         let guest_mgr = SandboxMemoryManager {
             shared_mem: gshm,
@@ -344,6 +503,10 @@ impl SandboxMemoryManager<ExclusiveSharedMemory> {
             entrypoint: self.entrypoint,
             mapped_rgns: self.mapped_rgns,
             abort_buffer: Vec::new(), // Guest doesn't need abort buffer
+            restored_snapshot: None,
+            transferred_bytes: 0,
+            #[cfg(feature = "mem_profile")]
+            mem_profile_capture: self.mem_profile_capture,
         };
         host_mgr.update_scratch_bookkeeping()?;
         Ok((host_mgr, guest_mgr))
@@ -354,10 +517,7 @@ impl SandboxMemoryManager<HostSharedMemory> {
     /// Reads a host function call from memory
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_host_function_call(&mut self) -> Result<FunctionCall> {
-        self.scratch_mem.try_pop_buffer_into::<FunctionCall>(
-            self.layout.get_output_data_buffer_scratch_host_offset(),
-            self.layout.sandbox_memory_config.get_output_data_size(),
-        )
+        self.pop_output_data::<FunctionCall>()
     }
 
     /// Writes a host function call result to memory
@@ -373,7 +533,9 @@ impl SandboxMemoryManager<HostSharedMemory> {
             self.layout.get_input_data_buffer_scratch_host_offset(),
             self.layout.sandbox_memory_config.get_input_data_size(),
             data,
-        )
+        )?;
+        self.transferred_bytes += data.len() as u64;
+        Ok(())
     }
 
     /// Writes a guest function call to memory
@@ -391,6 +553,7 @@ impl SandboxMemoryManager<HostSharedMemory> {
             self.layout.sandbox_memory_config.get_input_data_size(),
             buffer,
         )?;
+        self.transferred_bytes += buffer.len() as u64;
         Ok(())
     }
 
@@ -398,10 +561,42 @@ impl SandboxMemoryManager<HostSharedMemory> {
     /// A function call result can be either an error or a successful return value.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_function_call_result(&mut self) -> Result<FunctionCallResult> {
-        self.scratch_mem.try_pop_buffer_into::<FunctionCallResult>(
+        self.pop_output_data::<FunctionCallResult>()
+    }
+
+    /// Pops the function call or result on top of the output data buffer,
+    /// counting its bytes as transferred
+    fn pop_output_data<T>(&mut self) -> Result<T>
+    where
+        T: for<'b> TryFrom<&'b [u8]>,
+    {
+        let offset = self.layout.get_output_data_buffer_scratch_host_offset();
+        let before = self.scratch_mem.read::<u64>(offset)?;
+        let data = self.scratch_mem.try_pop_buffer_into::<T>(
+            offset,
+            self.layout.sandbox_memory_config.get_output_data_size(),
+        )?;
+        // The stack pointer of the buffer moved back over the element
+        let after = self.scratch_mem.read::<u64>(offset)?;
+        self.transferred_bytes += before.saturating_sub(after);
+        Ok(data)
+    }
+
+    /// Read the name of the checkpoint declared by the guest, which is
+    /// size-prefixed like the other elements of the output buffer
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn read_checkpoint_name(&mut self) -> Result<String> {
+        let data = self.scratch_mem.try_pop_buffer_into::<Vec<u8>>(
             self.layout.get_output_data_buffer_scratch_host_offset(),
             self.layout.sandbox_memory_config.get_output_data_size(),
-        )
+        )?;
+        let len = data
+            .first_chunk::<4>()
+            .map(|len| u32::from_le_bytes(*len) as usize)
+            .filter(|len| 4 + len <= data.len())
+            .ok_or_else(|| new_error!("Invalid checkpoint name length"))?;
+        String::from_utf8(data[4..4 + len].to_vec())
+            .map_err(|e| new_error!("Invalid checkpoint name: {}", e))
     }
 
     /// Read guest log data from the `SharedMemory` contained within `self`
@@ -442,18 +637,34 @@ impl SandboxMemoryManager<HostSharedMemory> {
         let gsnapshot = if self.shared_mem.mem_size() == snapshot.mem_size() {
             None
         } else {
-            let new_snapshot_mem = ExclusiveSharedMemory::new(snapshot.mem_size())?;
+            let new_snapshot_mem = new_sandbox_mem(
+                snapshot.layout(),
+                snapshot.mem_size(),
+                SandboxMemoryLayout::BASE_ADDRESS as u64,
+            )?;
+            if self.layout.zeroize_memory() {
+                new_snapshot_mem.zeroize_on_release();
+            }
             let (hsnapshot, gsnapshot) = new_snapshot_mem.build();
             self.shared_mem = hsnapshot;
             Some(gsnapshot)
         };
-        self.shared_mem.restore_from_snapshot(snapshot)?;
+        if self.restored_snapshot == Some(snapshot.hash()) {
+            self.shared_mem.restore_dirty_pages(snapshot)?;
+        } else {
+            self.shared_mem.restore_from_snapshot(snapshot)?;
+            self.shared_mem.clear_dirty_pages();
+            self.restored_snapshot = Some(snapshot.hash());
+        }
         let new_scratch_size = snapshot.layout().get_scratch_size();
         let gscratch = if new_scratch_size == self.scratch_mem.mem_size() {
             self.scratch_mem.zero()?;
             None
         } else {
-            let new_scratch_mem = ExclusiveSharedMemory::new(new_scratch_size)?;
+            let new_scratch_mem = new_scratch_mem(snapshot.layout())?;
+            if self.layout.zeroize_memory() {
+                new_scratch_mem.zeroize_on_release();
+            }
             let (hscratch, gscratch) = new_scratch_mem.build();
             // Even though this destroys the reference to the host
             // side of the old scratch mapping, the VM should still
@@ -469,6 +680,14 @@ impl SandboxMemoryManager<HostSharedMemory> {
         Ok((gsnapshot, gscratch))
     }
 
+    /// How much of the snapshot and scratch memory is committed
+    pub(crate) fn memory_stats(&self) -> Result<MemoryStats> {
+        Ok(MemoryStats {
+            configured: self.shared_mem.mem_size() + self.scratch_mem.mem_size(),
+            committed: self.shared_mem.resident_size()? + self.scratch_mem.resident_size()?,
+        })
+    }
+
     #[inline]
     fn update_scratch_bookkeeping_item(&mut self, offset: u64, value: u64) -> Result<()> {
         let scratch_size = self.scratch_mem.mem_size();
@@ -476,6 +695,52 @@ impl SandboxMemoryManager<HostSharedMemory> {
         self.scratch_mem.write::<u64>(base_offset, value)
     }
 
+    /// Asks the guest to send its buffered trace data to the host the next
+    /// time it records a span or an event
+    #[cfg(feature = "trace_guest")]
+    pub(crate) fn request_guest_trace_flush(&mut self) -> Result<()> {
+        self.update_scratch_bookkeeping_item(
+            hyperlight_common::layout::SCRATCH_TOP_TRACE_FLUSH_OFFSET,
+            1,
+        )
+    }
+
+    /// Sets which guest allocations the guest reports to the memory
+    /// profiler from its next allocation on
+    #[cfg(feature = "mem_profile")]
+    pub(crate) fn set_mem_profile_capture(&mut self, capture: MemProfileCapture) -> Result<()> {
+        self.mem_profile_capture = capture;
+        self.update_scratch_bookkeeping_item(
+            hyperlight_common::layout::SCRATCH_TOP_MEM_PROFILE_OFFSET,
+            capture.min_size(),
+        )
+    }
+
+    /// Asks the guest to report its stores to the `len` bytes at `gva`,
+    /// a `len` of 0 turning the reports off
+    #[cfg(feature = "mem_trace")]
+    pub(crate) fn set_memory_trace_range(&mut self, gva: u64, len: u64) -> Result<()> {
+        use hyperlight_common::layout::{
+            SCRATCH_TOP_MEM_TRACE_BASE_OFFSET, SCRATCH_TOP_MEM_TRACE_LEN_OFFSET,
+        };
+        self.update_scratch_bookkeeping_item(SCRATCH_TOP_MEM_TRACE_BASE_OFFSET, gva)?;
+        self.update_scratch_bookkeeping_item(SCRATCH_TOP_MEM_TRACE_LEN_OFFSET, len)
+    }
+
+    /// The range set with [`Self::set_memory_trace_range`], as `(gva, len)`
+    #[cfg(feature = "mem_trace")]
+    pub(crate) fn memory_trace_range(&self) -> Result<(u64, u64)> {
+        use hyperlight_common::layout::{
+            SCRATCH_TOP_MEM_TRACE_BASE_OFFSET, SCRATCH_TOP_MEM_TRACE_LEN_OFFSET,
+        };
+        let scratch_size = self.scratch_mem.mem_size();
+        let read = |offset: u64| self.scratch_mem.read::<u64>(scratch_size - offset as usize);
+        Ok((
+            read(SCRATCH_TOP_MEM_TRACE_BASE_OFFSET)?,
+            read(SCRATCH_TOP_MEM_TRACE_LEN_OFFSET)?,
+        ))
+    }
+
     fn update_scratch_bookkeeping(&mut self) -> Result<()> {
         use hyperlight_common::layout::*;
         let scratch_size = self.scratch_mem.mem_size();
@@ -484,6 +749,11 @@ impl SandboxMemoryManager<HostSharedMemory> {
             SCRATCH_TOP_ALLOCATOR_OFFSET,
             self.layout.get_first_free_scratch_gpa(),
         )?;
+        #[cfg(feature = "mem_profile")]
+        self.update_scratch_bookkeeping_item(
+            SCRATCH_TOP_MEM_PROFILE_OFFSET,
+            self.mem_profile_capture.min_size(),
+        )?;
 
         // Initialise the guest input and output data buffers in
         // scratch memory. TODO: remove the need for this.
@@ -647,104 +917,214 @@ impl SandboxMemoryManager<HostSharedMemory> {
     /// * `gva` - The Guest Virtual Address to read from
     /// * `len` - The number of bytes to read
     /// * `root_pt` - The root page table physical address (CR3)
-    #[cfg(feature = "trace_guest")]
     pub(crate) fn read_guest_memory_by_gva(
         &mut self,
         gva: u64,
         len: usize,
         root_pt: u64,
     ) -> Result<Vec<u8>> {
-        use hyperlight_common::vmem::PAGE_SIZE;
+        let mut result = vec![0; len];
+        let mut done = 0;
+        for (gpa, chunk) in self.translate_gva_range(gva, len, root_pt, false)? {
+            let (mem, offset) = self.access_gpa(gpa, gva)?;
+            mem.copy_to_slice(&mut result[done..done + chunk], offset)?;
+            done += chunk;
+        }
+        Ok(result)
+    }
 
-        use crate::sandbox::snapshot::{SharedMemoryPageTableBuffer, access_gpa};
+    /// Write `data` to guest memory at a Guest Virtual Address (GVA), the
+    /// same way [`Self::read_guest_memory_by_gva`] reads it. Fails if a
+    /// page of the range is not writable by the guest, which includes the
+    /// copy-on-write pages the guest has not written yet.
+    pub(crate) fn write_guest_memory_by_gva(
+        &mut self,
+        gva: u64,
+        data: &[u8],
+        root_pt: u64,
+    ) -> Result<()> {
+        let mut done = 0;
+        for (gpa, chunk) in self.translate_gva_range(gva, data.len(), root_pt, true)? {
+            let (mem, offset) = self.access_gpa(gpa, gva)?;
+            mem.copy_from_slice(&data[done..done + chunk], offset)?;
+            done += chunk;
+        }
+        Ok(())
+    }
+
+    /// Translate the `len` bytes at `gva` to the GPA ranges backing them,
+    /// by walking the page tables rooted at `root_pt`. With `write`, every
+    /// page of the range must be writable by the guest.
+    fn translate_gva_range(
+        &mut self,
+        gva: u64,
+        len: usize,
+        root_pt: u64,
+        write: bool,
+    ) -> Result<Vec<(u64, usize)>> {
+        use crate::sandbox::snapshot::SharedMemoryPageTableBuffer;
 
+        let end = gva
+            .checked_add(len as u64)
+            .ok_or_else(|| new_error!("GVA range {:#x} (len {}) overflows", gva, len))?;
         let scratch_size = self.scratch_mem.mem_size();
 
         self.shared_mem.with_exclusivity(|snap| {
             self.scratch_mem.with_exclusivity(|scratch| {
                 let pt_buf = SharedMemoryPageTableBuffer::new(snap, scratch, scratch_size, root_pt);
 
-                // Walk page tables to get all mappings that cover the GVA range
-                let mappings: Vec<_> = unsafe {
-                    hyperlight_common::vmem::virt_to_phys(&pt_buf, gva, len as u64)
-                }
-                .collect();
-
-                if mappings.is_empty() {
-                    return Err(new_error!(
-                        "No page table mappings found for GVA {:#x} (len {})",
-                        gva,
-                        len,
-                    ));
-                }
-
-                // Resulting vector of bytes to return
-                let mut result = Vec::with_capacity(len);
+                let mut ranges = Vec::new();
                 let mut current_gva = gva;
-
-                for mapping in &mappings {
-                    // The page table walker should only return valid mappings
-                    // that cover our current read position.
+                // Walk page tables to get all mappings that cover the GVA range
+                for mapping in unsafe { vmem::virt_to_phys(&pt_buf, gva, len as u64) } {
+                    if current_gva == end {
+                        break;
+                    }
+                    // The page table walker skips the pages that are not
+                    // mapped
                     if mapping.virt_base > current_gva {
+                        break;
+                    }
+                    if write
+                        && !matches!(
+                            mapping.kind,
+                            MappingKind::Basic(BasicMapping { writable: true, .. })
+                        )
+                    {
                         return Err(new_error!(
-                            "Page table walker returned mapping with virt_base {:#x} > current read position {:#x}",
-                            mapping.virt_base,
-                            current_gva,
+                            "GVA {:#x} is not writable by the guest",
+                            current_gva
                         ));
                     }
-
-                    // Calculate the offset within this page where to start copying
-                    let page_offset = (current_gva - mapping.virt_base) as usize;
-
-                    let bytes_remaining = len - result.len();
-                    let available_in_page = PAGE_SIZE - page_offset;
-                    let bytes_to_copy = bytes_remaining.min(available_in_page);
-
-                    // Translate the GPA to host memory
-                    let gpa = mapping.phys_base + page_offset as u64;
-                    let (mem, offset) = access_gpa(snap, scratch, scratch_size, gpa)
-                        .ok_or_else(|| {
-                            new_error!(
-                                "Failed to resolve GPA {:#x} to host memory (GVA {:#x})",
-                                gpa,
-                                gva
-                            )
-                        })?;
-
-                    let slice = mem
-                        .as_slice()
-                        .get(offset..offset + bytes_to_copy)
-                        .ok_or_else(|| {
-                            new_error!(
-                                "GPA {:#x} resolved to out-of-bounds host offset {} (need {} bytes)",
-                                gpa,
-                                offset,
-                                bytes_to_copy
-                            )
-                        })?;
-
-                    result.extend_from_slice(slice);
-                    current_gva += bytes_to_copy as u64;
+                    let page_offset = current_gva - mapping.virt_base;
+                    let chunk = (end - current_gva).min(mapping.len - page_offset);
+                    ranges.push((mapping.phys_base + page_offset, chunk as usize));
+                    current_gva += chunk;
                 }
 
-                if result.len() != len {
-                    tracing::error!(
-                        "Page table walker returned mappings that don't cover the full requested length: got {}, expected {}",
-                        result.len(),
-                        len,
-                    );
+                if current_gva != end {
                     return Err(new_error!(
-                        "Could not read full GVA range: got {} of {} bytes {:?}",
-                        result.len(),
-                        len,
-                        mappings
+                        "GVA {:#x} is not mapped (reading {:#x}, len {})",
+                        current_gva,
+                        gva,
+                        len
                     ));
                 }
-
-                Ok(result)
+                Ok(ranges)
             })
         })??
     }
+
+    /// Changes the permissions of the pages of the `len` bytes at `gva`,
+    /// all of which must be mapped, in the page tables rooted at
+    /// `root_pt`. Writable pages of the snapshot region are mapped
+    /// copy-on-write. The caller flushes the TLB of the guest.
+    pub(crate) fn set_guest_page_permissions(
+        &mut self,
+        gva: u64,
+        len: u64,
+        flags: MemoryRegionFlags,
+        root_pt: u64,
+    ) -> Result<()> {
+        use hyperlight_common::vmem::{CowMapping, Mapping, PAGE_SIZE};
+
+        let end = gva
+            .checked_add(len)
+            .ok_or_else(|| new_error!("GVA range {:#x} (len {}) overflows", gva, len))?;
+        let tables = ScratchPageTables {
+            snap: &self.shared_mem,
+            scratch: &self.scratch_mem,
+            root: root_pt,
+            failed: std::cell::Cell::new(false),
+        };
+
+        // Check that the whole range is mapped before changing anything
+        let mut pages = Vec::new();
+        let mut current_gva = gva;
+        for mapping in unsafe { vmem::virt_to_phys(&tables, gva, len) } {
+            if current_gva == end {
+                break;
+            }
+            if mapping.virt_base > current_gva {
+                break;
+            }
+            let mapping_end = (mapping.virt_base + mapping.len).min(end);
+            while current_gva < mapping_end {
+                pages.push((
+                    current_gva,
+                    mapping.phys_base + current_gva - mapping.virt_base,
+                ));
+                current_gva += PAGE_SIZE as u64;
+            }
+        }
+        if current_gva != end {
+            return Err(new_error!(
+                "GVA {:#x} is not mapped (changing the permissions of {:#x}, len {})",
+                current_gva,
+                gva,
+                len
+            ));
+        }
+
+        let readable = flags.contains(MemoryRegionFlags::READ);
+        let executable = flags.contains(MemoryRegionFlags::EXECUTE);
+        let scratch_base = scratch_base_gpa(self.scratch_mem.mem_size());
+        for (virt_base, phys_base) in pages {
+            let kind = if !flags.contains(MemoryRegionFlags::WRITE) {
+                MappingKind::Basic(BasicMapping {
+                    readable,
+                    writable: false,
+                    executable,
+                })
+            } else if phys_base < scratch_base {
+                // The guest copies the page to the scratch region on its
+                // first write, leaving the snapshot untouched
+                MappingKind::Cow(CowMapping {
+                    readable,
+                    executable,
+                })
+            } else {
+                MappingKind::Basic(BasicMapping {
+                    readable,
+                    writable: true,
+                    executable,
+                })
+            };
+            let mapping = Mapping {
+                phys_base,
+                virt_base,
+                len: PAGE_SIZE as u64,
+                kind,
+            };
+            unsafe { vmem::map(&tables, mapping) };
+        }
+        if tables.failed.get() {
+            return Err(new_error!(
+                "Failed to update the page tables of the guest for {:#x} (len {})",
+                gva,
+                len
+            ));
+        }
+        Ok(())
+    }
+
+    /// The memory backing `gpa`, part of the range starting at `gva`, and
+    /// the offset of `gpa` in it
+    fn access_gpa(&self, gpa: u64, gva: u64) -> Result<(&HostSharedMemory, usize)> {
+        crate::sandbox::snapshot::access_gpa(
+            &self.shared_mem,
+            &self.scratch_mem,
+            self.scratch_mem.mem_size(),
+            gpa,
+        )
+        .ok_or_else(|| {
+            new_error!(
+                "Failed to resolve GPA {:#x} to host memory (GVA {:#x})",
+                gpa,
+                gva
+            )
+        })
+    }
 }
 
 #[cfg(test)]
@@ -829,9 +1209,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn write_xor_execute_page_tables() {
+        use hyperlight_common::vmem::{BasicMapping, CowMapping};
+
+        let mut config = SandboxConfiguration::default();
+        config.set_guest_write_xor_execute(true);
+        let path = simple_guest_as_string().unwrap();
+        let snapshot = Snapshot::from_env(GuestBinary::FilePath(path), config).unwrap();
+
+        let mut executable_pages = 0;
+        let max_gva = hyperlight_common::layout::MAX_GVA as u64;
+        for mapping in unsafe { hyperlight_common::vmem::virt_to_phys(&snapshot, 0, max_gva) } {
+            match mapping.kind {
+                MappingKind::Basic(BasicMapping {
+                    writable: true,
+                    executable: true,
+                    ..
+                })
+                | MappingKind::Cow(CowMapping {
+                    executable: true, ..
+                }) => panic!("{:#x} is writable and executable", mapping.virt_base),
+                MappingKind::Basic(BasicMapping {
+                    executable: true, ..
+                }) => executable_pages += 1,
+                _ => {}
+            }
+        }
+        // The text of the binary is still executable
+        assert!(executable_pages > 0);
+    }
+
     #[test]
     fn test_page_tables_for_various_configurations() {
-        let test_cases: [(&str, SandboxConfiguration); 4] = [
+        let test_cases: [(&str, SandboxConfiguration); 5] = [
             ("default", { SandboxConfiguration::default() }),
             ("small (8MB heap)", {
                 let mut cfg = SandboxConfiguration::default();
@@ -849,6 +1260,13 @@ mod tests {
                 cfg.set_scratch_size(0x100000);
                 cfg
             }),
+            ("large pages (256MB heap)", {
+                let mut cfg = SandboxConfiguration::default();
+                cfg.set_heap_size(LARGE_HEAP_SIZE);
+                cfg.set_scratch_size(0x100000);
+                cfg.set_guest_large_pages(true);
+                cfg
+            }),
         ];
 
         for (name, config) in test_cases {
@@ -20,9 +20,12 @@ use std::io::Error;
 use std::mem::{align_of, size_of};
 #[cfg(target_os = "linux")]
 use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 
 use hyperlight_common::mem::PAGE_SIZE_USIZE;
+#[cfg(target_os = "linux")]
+use hyperlight_common::vmem::LARGE_PAGE_SIZE;
 use tracing::{Span, instrument};
 #[cfg(target_os = "windows")]
 use windows::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
@@ -97,21 +100,57 @@ macro_rules! generate_writer {
 pub struct HostMapping {
     ptr: *mut u8,
     size: usize,
+    /// Whether the memory is backed by transparent huge pages, which
+    /// restoring a snapshot must not replace with the 4KiB pages of
+    /// the snapshot memfd
+    huge_pages: bool,
+    /// Whether the memory is overwritten with zeroes before its pages
+    /// are given back to the OS, when it is dropped or replaced by the
+    /// pages of a snapshot
+    zeroize: AtomicBool,
+    /// The memfd mapped between the guard pages, if the memory is backed
+    /// by one rather than by anonymous memory
+    #[cfg(target_os = "linux")]
+    memfd: Option<std::fs::File>,
     #[cfg(target_os = "windows")]
     handle: HANDLE,
 }
 
+impl HostMapping {
+    /// Overwrites the usable memory, between the guard pages, with zeroes
+    fn zeroize(&self) {
+        // Safety: the memory between the guard pages stays mapped
+        // read-write for as long as the mapping exists
+        unsafe {
+            std::ptr::write_bytes(
+                self.ptr.add(PAGE_SIZE_USIZE),
+                0,
+                self.size - 2 * PAGE_SIZE_USIZE,
+            );
+        }
+        // The zeroes must be written even though nothing reads them
+        // before the memory is unmapped
+        std::sync::atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
+
 impl Drop for HostMapping {
     #[cfg(target_os = "linux")]
     fn drop(&mut self) {
         use libc::munmap;
 
+        if *self.zeroize.get_mut() {
+            self.zeroize();
+        }
         unsafe {
             munmap(self.ptr as *mut c_void, self.size);
         }
     }
     #[cfg(target_os = "windows")]
     fn drop(&mut self) {
+        if *self.zeroize.get_mut() {
+            self.zeroize();
+        }
         let mem_mapped_address = MEMORY_MAPPED_VIEW_ADDRESS {
             Value: self.ptr as *mut c_void,
         };
@@ -323,9 +362,114 @@ unsafe impl Send for GuestSharedMemory {}
 pub struct HostSharedMemory {
     region: Arc<HostMapping>,
     lock: Arc<RwLock<()>>,
+    dirty: Arc<DirtyPages>,
 }
 unsafe impl Send for HostSharedMemory {}
 
+/// The pages of a [`HostSharedMemory`] that the host wrote since they
+/// were last taken, one bit per page.
+///
+/// The guest never writes to the snapshot region, which is mapped
+/// read-only (its writes are copied to the scratch region instead), so
+/// these are also the only pages of the snapshot region that can differ
+/// from the snapshot it was last restored from.
+///
+/// The reverse execution history of the debugger takes the same pages
+/// separately, so that it does not take them from snapshot restores.
+#[derive(Debug)]
+struct DirtyPages {
+    bits: Vec<AtomicU64>,
+    #[cfg(gdb)]
+    debug_bits: Vec<AtomicU64>,
+    pages: usize,
+}
+
+impl DirtyPages {
+    fn new(mem_size: usize) -> Self {
+        let pages = mem_size.div_ceil(PAGE_SIZE_USIZE);
+        Self {
+            bits: (0..pages.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            #[cfg(gdb)]
+            debug_bits: (0..pages.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            pages,
+        }
+    }
+
+    /// Marks the pages overlapping `[offset, offset + len)` as dirty
+    fn mark(&self, offset: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        for page in offset / PAGE_SIZE_USIZE..=(offset + len - 1) / PAGE_SIZE_USIZE {
+            self.bits[page / 64].fetch_or(1 << (page % 64), Ordering::Relaxed);
+            #[cfg(gdb)]
+            self.debug_bits[page / 64].fetch_or(1 << (page % 64), Ordering::Relaxed);
+        }
+    }
+
+    fn mark_all(&self) {
+        for word in &self.bits {
+            word.store(u64::MAX, Ordering::Relaxed);
+        }
+        #[cfg(gdb)]
+        for word in &self.debug_bits {
+            word.store(u64::MAX, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the indices of the dirty pages, marking them clean
+    fn take(&self) -> Vec<usize> {
+        let mut pages = Vec::new();
+        self.take_bits(&self.bits, &mut pages);
+        pages
+    }
+
+    /// Appends to `pages` the indices of the pages set in `words`,
+    /// clearing them
+    fn take_bits(&self, words: &[AtomicU64], pages: &mut Vec<usize>) {
+        for (i, word) in words.iter().enumerate() {
+            let mut bits = word.swap(0, Ordering::Relaxed);
+            while bits != 0 {
+                let page = i * 64 + bits.trailing_zeros() as usize;
+                if page < self.pages {
+                    pages.push(page);
+                }
+                bits &= bits - 1;
+            }
+        }
+    }
+}
+
+/// Maps `file`, a sealed memfd of the same size, copy-on-write over the
+/// usable part of `region`, so that its pages are only copied once they
+/// are written
+#[cfg(all(target_os = "linux", not(miri)))]
+fn map_private(region: &HostMapping, file: &std::fs::File) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    use libc::{MAP_FAILED, MAP_FIXED, MAP_PRIVATE, PROT_READ, PROT_WRITE, mmap};
+
+    // Safety: the range is the usable part of the mapping owned by
+    // `region`, between its guard pages, and the memfd is sealed, so it
+    // stays as large as the range
+    let addr = unsafe {
+        mmap(
+            region.ptr.add(PAGE_SIZE_USIZE) as *mut c_void,
+            region.size - 2 * PAGE_SIZE_USIZE,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_FIXED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if addr == MAP_FAILED {
+        log_then_return!(HyperlightError::MmapFailed(
+            Error::last_os_error().raw_os_error()
+        ));
+    }
+    Ok(())
+}
+
 impl ExclusiveSharedMemory {
     /// Create a new region of shared memory with the given minimum
     /// size in bytes. The region will be surrounded by guard pages.
@@ -334,9 +478,99 @@ impl ExclusiveSharedMemory {
     #[cfg(target_os = "linux")]
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub fn new(min_size_bytes: usize) -> Result<Self> {
+        Self::new_placed(min_size_bytes, None)
+    }
+
+    /// Create a new region of shared memory with the given minimum
+    /// size in bytes, backed by transparent huge pages where the kernel
+    /// has them available. The region will be surrounded by guard pages.
+    ///
+    /// `guest_base` is the guest physical address the region will be
+    /// mapped at. The host address of the region is chosen at the same
+    /// offset in a 2MiB page, which hypervisors require to map the
+    /// region into the guest with large pages.
+    #[cfg(target_os = "linux")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn new_with_huge_pages(min_size_bytes: usize, guest_base: u64) -> Result<Self> {
+        Self::new_placed(min_size_bytes, Some(guest_base))
+    }
+
+    /// Create a new region of shared memory with the given minimum
+    /// size in bytes, backed by a memfd whose descriptor can be passed
+    /// to other tools, see [`SharedMemory::memfd`]. If `sealed`, the
+    /// size of the memfd is sealed. The region will be surrounded by
+    /// guard pages.
+    #[cfg(target_os = "linux")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn new_memfd(min_size_bytes: usize, sealed: bool) -> Result<Self> {
+        #[cfg(miri)]
+        {
+            let _ = (min_size_bytes, sealed);
+            Err(new_error!(
+                "memfd backed shared memory is not supported under miri"
+            ))
+        }
+        #[cfg(not(miri))]
+        {
+            use std::os::fd::{AsRawFd, FromRawFd};
+
+            use libc::{MAP_FAILED, MAP_FIXED, MAP_SHARED, PROT_READ, PROT_WRITE, mmap};
+
+            let mut shared_mem = Self::new(min_size_bytes)?;
+            let fd = unsafe {
+                libc::memfd_create(
+                    c"hyperlight-sandbox".as_ptr(),
+                    libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+                )
+            };
+            if fd < 0 {
+                log_then_return!("memfd_create failed: {}", Error::last_os_error());
+            }
+            // Safety: the descriptor was just created and is owned by nothing else
+            let file = unsafe { std::fs::File::from_raw_fd(fd) };
+            file.set_len(shared_mem.mem_size() as u64)?;
+            if sealed {
+                let seals = libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW;
+                if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } < 0 {
+                    log_then_return!("Failed to seal the memfd: {}", Error::last_os_error());
+                }
+            }
+            // Safety: the range is the usable part of the mapping, between
+            // its guard pages, and the memfd is as large as the range
+            let addr = unsafe {
+                mmap(
+                    shared_mem.base_ptr() as *mut c_void,
+                    shared_mem.mem_size(),
+                    PROT_READ | PROT_WRITE,
+                    MAP_SHARED | MAP_FIXED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if addr == MAP_FAILED {
+                log_then_return!(HyperlightError::MmapFailed(
+                    Error::last_os_error().raw_os_error()
+                ));
+            }
+            Arc::get_mut(&mut shared_mem.region)
+                .ok_or_else(|| new_error!("The shared memory was just created"))?
+                .memfd = Some(file);
+            Ok(shared_mem)
+        }
+    }
+
+    /// Huge pages are not supported on Windows, where this is the same
+    /// as [`new`](Self::new)
+    #[cfg(target_os = "windows")]
+    pub(crate) fn new_with_huge_pages(min_size_bytes: usize, _guest_base: u64) -> Result<Self> {
+        Self::new(min_size_bytes)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn new_placed(min_size_bytes: usize, huge_page_guest_base: Option<u64>) -> Result<Self> {
         use libc::{
-            MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, PROT_READ, PROT_WRITE, c_int, mmap, off_t,
-            size_t,
+            MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, PROT_READ, PROT_WRITE, c_int, mmap, munmap,
+            off_t, size_t,
         };
         #[cfg(not(miri))]
         use libc::{MAP_NORESERVE, PROT_NONE, mprotect};
@@ -371,10 +605,17 @@ impl ExclusiveSharedMemory {
         #[cfg(miri)]
         let flags = MAP_ANONYMOUS | MAP_PRIVATE;
 
+        // To place the region for huge pages, a huge page more is
+        // reserved and the excess is unmapped below
+        let slack = if huge_page_guest_base.is_some() {
+            LARGE_PAGE_SIZE
+        } else {
+            0
+        };
         let addr = unsafe {
             mmap(
                 null_mut(),
-                total_size as size_t,
+                (total_size + slack) as size_t,
                 PROT_READ | PROT_WRITE,
                 flags,
                 -1 as c_int,
@@ -386,6 +627,43 @@ impl ExclusiveSharedMemory {
                 Error::last_os_error().raw_os_error()
             ));
         }
+        let addr = match huge_page_guest_base {
+            Some(guest_base) => {
+                // The offset at which the usable memory, after the leading
+                // guard page, is at the same offset in a huge page as
+                // `guest_base`
+                let shift = (guest_base as usize).wrapping_sub(addr as usize + PAGE_SIZE_USIZE)
+                    % LARGE_PAGE_SIZE;
+                unsafe {
+                    if shift > 0 {
+                        munmap(addr, shift);
+                    }
+                    if shift < slack {
+                        munmap(
+                            (addr as *mut u8).add(shift + total_size) as *mut c_void,
+                            slack - shift,
+                        );
+                    }
+                    let addr = (addr as *mut u8).add(shift) as *mut c_void;
+                    // Without transparent huge pages the memory is still
+                    // usable, only with 4KiB pages
+                    #[cfg(not(miri))]
+                    if libc::madvise(
+                        (addr as *mut u8).add(PAGE_SIZE_USIZE) as *mut c_void,
+                        min_size_bytes,
+                        libc::MADV_HUGEPAGE,
+                    ) != 0
+                    {
+                        tracing::warn!(
+                            "Transparent huge pages are not available: {}",
+                            Error::last_os_error()
+                        );
+                    }
+                    addr
+                }
+            }
+            None => addr,
+        };
 
         // protect the guard pages
         #[cfg(not(miri))]
@@ -423,6 +701,9 @@ impl ExclusiveSharedMemory {
             region: Arc::new(HostMapping {
                 ptr: addr as *mut u8,
                 size: total_size,
+                huge_pages: huge_page_guest_base.is_some(),
+                zeroize: AtomicBool::new(false),
+                memfd: None,
             }),
         })
     }
@@ -540,6 +821,8 @@ impl ExclusiveSharedMemory {
             region: Arc::new(HostMapping {
                 ptr: addr.Value as *mut u8,
                 size: total_size,
+                huge_pages: false,
+                zeroize: AtomicBool::new(false),
                 handle,
             }),
         })
@@ -618,6 +901,44 @@ impl ExclusiveSharedMemory {
         Ok(())
     }
 
+    /// Fills this freshly allocated memory, of the size of `snapshot`,
+    /// with the memory of `snapshot`. On Linux, the memfd of the
+    /// snapshot is mapped copy-on-write rather than copied, so sandboxes
+    /// created from the same snapshot share the pages none of them has
+    /// written, unless the memory is backed by huge pages or by its own
+    /// memfd.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn init_from_snapshot(&mut self, snapshot: &Snapshot) -> Result<()> {
+        if snapshot.mem_size() != self.mem_size() {
+            return Err(SnapshotSizeMismatch(self.mem_size(), snapshot.mem_size()));
+        }
+        #[cfg(all(target_os = "linux", not(miri)))]
+        if !self.region.huge_pages
+            && self.region.memfd.is_none()
+            && let Some(file) = snapshot.file()
+        {
+            return map_private(&self.region, file);
+        }
+        self.copy_nonzero_pages_from_slice(snapshot.memory());
+        Ok(())
+    }
+
+    /// Copies the pages of `src` that are not all zeroes to the start of
+    /// this freshly allocated memory, which is already zeroed, so that
+    /// the other pages are not touched and are only backed by host
+    /// memory once the guest touches them
+    fn copy_nonzero_pages_from_slice(&mut self, src: &[u8]) {
+        let dst = self.as_mut_slice();
+        for (dst, src) in dst
+            .chunks_mut(PAGE_SIZE_USIZE)
+            .zip(src.chunks(PAGE_SIZE_USIZE))
+        {
+            if src.iter().any(|&b| b != 0) {
+                dst[..src.len()].copy_from_slice(src);
+            }
+        }
+    }
+
     generate_reader!(read_u8, u8);
     generate_reader!(read_i8, i8);
     generate_reader!(read_u16, u16);
@@ -650,6 +971,7 @@ impl ExclusiveSharedMemory {
         let lock = Arc::new(RwLock::new(()));
         (
             HostSharedMemory {
+                dirty: Arc::new(DirtyPages::new(self.mem_size())),
                 region: self.region.clone(),
                 lock: lock.clone(),
             },
@@ -780,6 +1102,52 @@ pub trait SharedMemory {
         self.with_exclusivity(|e| e.copy_from_slice(snapshot.memory(), 0))?
     }
 
+    /// The size of the pages of this memory that are resident on the
+    /// host, excluding the guard pages. Pages are only backed by host
+    /// memory once they are touched on Linux; on Windows the memory is
+    /// committed when it is created, and this is its whole size.
+    fn resident_size(&self) -> Result<usize> {
+        #[cfg(all(target_os = "linux", not(miri)))]
+        {
+            let mut pages = vec![0u8; self.mem_size().div_ceil(PAGE_SIZE_USIZE)];
+            // Safety: the range is the usable part of the mapping, which
+            // stays mapped while `self` exists, and `pages` holds a byte
+            // per page of it
+            let ret = unsafe {
+                libc::mincore(
+                    self.base_ptr() as *mut c_void,
+                    self.mem_size(),
+                    pages.as_mut_ptr(),
+                )
+            };
+            if ret != 0 {
+                log_then_return!(
+                    "mincore failed on shared memory: {}",
+                    Error::last_os_error()
+                );
+            }
+            Ok(pages.iter().filter(|&&page| page & 1 != 0).count() * PAGE_SIZE_USIZE)
+        }
+        #[cfg(not(all(target_os = "linux", not(miri))))]
+        Ok(self.mem_size())
+    }
+
+    /// The memfd backing this memory, if it was created with
+    /// [`ExclusiveSharedMemory::new_memfd`]
+    #[cfg(target_os = "linux")]
+    fn memfd(&self) -> Option<std::os::fd::BorrowedFd<'_>> {
+        use std::os::fd::AsFd;
+
+        self.region().memfd.as_ref().map(|file| file.as_fd())
+    }
+
+    /// Makes the memory be overwritten with zeroes, rather than only
+    /// discarded, whenever its pages are given back to the OS: when it
+    /// is dropped, zeroed, or replaced by the pages of a snapshot
+    fn zeroize_on_release(&self) {
+        self.region().zeroize.store(true, Ordering::Relaxed);
+    }
+
     /// Zero a shared memory region
     fn zero(&mut self) -> Result<()> {
         self.with_exclusivity(|e| {
@@ -789,12 +1157,28 @@ pub trait SharedMemory {
             // TODO: Find a similar lazy zeroing approach that works on MSHV.
             //       (See Note [Keeping mappings in sync between userspace and the guest])
             #[cfg(all(target_os = "linux", feature = "kvm", not(any(feature = "mshv3"))))]
-            unsafe {
-                let ret = libc::madvise(
-                    e.region.ptr as *mut libc::c_void,
-                    e.region.size,
-                    libc::MADV_DONTNEED,
-                );
+            if !e.region.zeroize.load(Ordering::Relaxed) {
+                let ret = match &e.region.memfd {
+                    // Discarding the pages of a shared mapping leaves
+                    // them in the memfd, where they are freed instead
+                    Some(memfd) => unsafe {
+                        use std::os::fd::AsRawFd;
+
+                        libc::fallocate(
+                            memfd.as_raw_fd(),
+                            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                            0,
+                            e.mem_size() as libc::off_t,
+                        )
+                    },
+                    None => unsafe {
+                        libc::madvise(
+                            e.region.ptr as *mut libc::c_void,
+                            e.region.size,
+                            libc::MADV_DONTNEED,
+                        )
+                    },
+                };
                 if ret == 0 {
                     do_copy = false;
                 }
@@ -953,6 +1337,7 @@ impl HostSharedMemory {
             .lock
             .try_read()
             .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        self.dirty.mark(offset, slice.len());
 
         const CHUNK: usize = size_of::<u128>();
         let len = slice.len();
@@ -1004,6 +1389,7 @@ impl HostSharedMemory {
             .lock
             .try_read()
             .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        self.dirty.mark(offset, len);
 
         const CHUNK: usize = size_of::<u128>();
         let value_u128 = u128::from_ne_bytes([value; CHUNK]);
@@ -1043,6 +1429,46 @@ impl HostSharedMemory {
         Ok(())
     }
 
+    /// Restore from `snapshot` only the pages written by the host since
+    /// the dirty pages were last taken, which is enough when this memory
+    /// held `snapshot` at that time. Returns the number of pages copied.
+    pub(crate) fn restore_dirty_pages(&mut self, snapshot: &Snapshot) -> Result<usize> {
+        if snapshot.memory().len() != self.mem_size() {
+            return Err(SnapshotSizeMismatch(self.mem_size(), snapshot.mem_size()));
+        }
+        let guard = self
+            .lock
+            .try_write()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        let mut excl = ExclusiveSharedMemory {
+            region: self.region.clone(),
+        };
+        let pages = self.dirty.take();
+        for page in &pages {
+            let start = page * PAGE_SIZE_USIZE;
+            let end = (start + PAGE_SIZE_USIZE).min(self.mem_size());
+            excl.copy_from_slice(&snapshot.memory()[start..end], start)?;
+        }
+        drop(excl);
+        drop(guard);
+        Ok(pages.len())
+    }
+
+    /// Marks every page clean, after the whole memory was restored from
+    /// a snapshot
+    pub(crate) fn clear_dirty_pages(&self) {
+        self.dirty.take();
+    }
+
+    /// Appends to `pages` the indices of the pages written by the host
+    /// since this was last called, for the reverse execution history of
+    /// the debugger. These are taken apart from the dirty pages of
+    /// [`restore_dirty_pages`](Self::restore_dirty_pages).
+    #[cfg(gdb)]
+    pub(crate) fn take_debug_dirty_pages(&self, pages: &mut Vec<usize>) {
+        self.dirty.take_bits(&self.dirty.debug_bits, pages);
+    }
+
     /// Pushes the given data onto shared memory to the buffer at the given offset.
     /// NOTE! buffer_start_offset must point to the beginning of the buffer
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
@@ -1191,6 +1617,8 @@ impl SharedMemory for HostSharedMemory {
         &mut self,
         f: F,
     ) -> Result<T> {
+        // Any page may be written through the exclusive access
+        self.dirty.mark_all();
         let guard = self
             .lock
             .try_write()
@@ -1203,6 +1631,40 @@ impl SharedMemory for HostSharedMemory {
         drop(guard);
         Ok(ret)
     }
+
+    /// Maps the memfd of the snapshot copy-on-write over this memory,
+    /// rather than copying the whole snapshot, so that only the pages
+    /// that are touched afterwards cost anything. KVM follows the
+    /// change of backing pages; mshv does not (see Note [Keeping
+    /// mappings in sync between userspace and the guest]), so there the
+    /// snapshot is still copied.
+    #[cfg(all(
+        target_os = "linux",
+        feature = "kvm",
+        not(feature = "mshv3"),
+        not(miri)
+    ))]
+    fn restore_from_snapshot(&mut self, snapshot: &Snapshot) -> Result<()> {
+        if snapshot.memory().len() != self.mem_size() {
+            return Err(SnapshotSizeMismatch(self.mem_size(), snapshot.mem_size()));
+        }
+        let file = match snapshot.file() {
+            Some(file) if !self.region.huge_pages && self.region.memfd.is_none() => file,
+            _ => return self.with_exclusivity(|e| e.copy_from_slice(snapshot.memory(), 0))?,
+        };
+        self.dirty.mark_all();
+        let guard = self
+            .lock
+            .try_write()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        // Nothing accesses the memory while the lock is held
+        if self.region.zeroize.load(Ordering::Relaxed) {
+            self.region.zeroize();
+        }
+        let res = map_private(&self.region, file);
+        drop(guard);
+        res
+    }
 }
 
 #[cfg(test)]
@@ -1218,6 +1680,77 @@ mod tests {
     #[cfg(not(miri))]
     use crate::mem::shared_mem_tests::read_write_test_suite;
 
+    #[test]
+    #[cfg(all(target_os = "linux", not(miri)))]
+    fn resident_size() {
+        let eshm = ExclusiveSharedMemory::new(4 * PAGE_SIZE_USIZE).unwrap();
+        let (mut hshm, _) = eshm.build();
+        // Nothing is committed until it is touched
+        assert_eq!(hshm.resident_size().unwrap(), 0);
+        hshm.fill(1, PAGE_SIZE_USIZE, 16).unwrap();
+        assert_eq!(hshm.resident_size().unwrap(), PAGE_SIZE_USIZE);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", not(miri)))]
+    fn memfd() {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::fs::FileExt;
+
+        let eshm = ExclusiveSharedMemory::new_memfd(4 * PAGE_SIZE_USIZE, true).unwrap();
+        let (mut hshm, _) = eshm.build();
+        let memfd = std::fs::File::from(hshm.memfd().unwrap().try_clone_to_owned().unwrap());
+        assert_eq!(memfd.metadata().unwrap().len(), 4 * PAGE_SIZE_USIZE as u64);
+
+        // The memfd and the mapping are the same memory
+        hshm.copy_from_slice(b"hello", PAGE_SIZE_USIZE).unwrap();
+        let mut buf = [0; 5];
+        memfd
+            .read_exact_at(&mut buf, PAGE_SIZE_USIZE as u64)
+            .unwrap();
+        assert_eq!(&buf, b"hello");
+        memfd.write_all_at(b"world", 0).unwrap();
+        assert_eq!(hshm.read::<u8>(0).unwrap(), b'w');
+
+        // Its size is sealed
+        let seals = unsafe { libc::fcntl(memfd.as_raw_fd(), libc::F_GET_SEALS) };
+        assert_eq!(
+            seals,
+            libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW
+        );
+        assert!(memfd.set_len(PAGE_SIZE_USIZE as u64).is_err());
+
+        hshm.zero().unwrap();
+        memfd
+            .read_exact_at(&mut buf, PAGE_SIZE_USIZE as u64)
+            .unwrap();
+        assert_eq!(buf, [0; 5]);
+        assert!(
+            ExclusiveSharedMemory::new(PAGE_SIZE_USIZE)
+                .unwrap()
+                .memfd()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn zeroize_on_release() {
+        use std::sync::atomic::Ordering;
+
+        let eshm = ExclusiveSharedMemory::new(PAGE_SIZE_USIZE).unwrap();
+        let (mut hshm, gshm) = eshm.build();
+        hshm.zeroize_on_release();
+        // Both sides of the memory share the setting
+        assert!(gshm.region().zeroize.load(Ordering::Relaxed));
+
+        hshm.fill(0xaa, 0, PAGE_SIZE_USIZE).unwrap();
+        hshm.zero().unwrap();
+        let vec = hshm
+            .with_exclusivity(|e| e.copy_all_to_vec().unwrap())
+            .unwrap();
+        assert!(vec.iter().all(|&x| x == 0));
+    }
+
     #[test]
     fn fill() {
         let mem_size: usize = 4096;
@@ -1368,6 +1901,24 @@ mod tests {
         assert!(gm.is_err());
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn huge_pages_follow_the_guest_alignment() {
+        use hyperlight_common::vmem::LARGE_PAGE_SIZE;
+
+        for guest_base in [0x1000, 0x20_0000, 0x3f_f000, 0xffff_ffff_ffc0_0000] {
+            let mut eshm =
+                ExclusiveSharedMemory::new_with_huge_pages(0x40_0000, guest_base).unwrap();
+            assert_eq!(
+                eshm.base_addr() as u64 % LARGE_PAGE_SIZE as u64,
+                guest_base % LARGE_PAGE_SIZE as u64
+            );
+            assert!(eshm.region.huge_pages);
+            eshm.as_mut_slice().fill(0xaa);
+            assert!(eshm.as_slice().iter().all(|b| *b == 0xaa));
+        }
+    }
+
     #[test]
     fn clone() {
         let eshm = ExclusiveSharedMemory::new(PAGE_SIZE_USIZE).unwrap();
@@ -14,6 +14,11 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+mod sink;
+
+pub(crate) use sink::MetricsRecorder;
+pub use sink::{MetricsRsSink, MetricsSink, SandboxMetric, VmExitReason};
+
 // Counter metric that counter number of times a guest error occurred
 pub(crate) static METRIC_GUEST_ERROR: &str = "guest_errors_total";
 pub(crate) static METRIC_GUEST_ERROR_LABEL_CODE: &str = "code";
@@ -27,6 +32,17 @@ pub(crate) static METRIC_GUEST_CANCELLATION: &str = "guest_cancellations_total";
 // 2. Windows: WHvCancelRunVirtualProcessor is called right after vCPU exits but RUNNING_BIT is still true
 pub(crate) static METRIC_ERRONEOUS_VCPU_KICKS: &str = "erroneous_vcpu_kicks_total";
 
+// Counter metric that counts the guest log records dropped because the host log sinks could not keep up
+pub(crate) static METRIC_GUEST_LOG_DROPPED: &str = "guest_log_records_dropped_total";
+pub(crate) static METRIC_GUEST_LOG_DROPPED_LABEL_REASON: &str = "reason";
+
+// Counter metric that counts the sandboxes checked out of sandbox pools
+pub(crate) static METRIC_SANDBOX_POOL_CHECKOUTS: &str = "sandbox_pool_checkouts_total";
+
+// Counter metric that counts the pooled sandboxes dropped instead of being given back to their pool
+pub(crate) static METRIC_SANDBOX_POOL_DISCARDED: &str = "sandbox_pool_discarded_total";
+pub(crate) static METRIC_SANDBOX_POOL_DISCARDED_LABEL_REASON: &str = "reason";
+
 // Histogram metric that measures the duration of guest function calls
 #[cfg(feature = "function_call_metrics")]
 pub(crate) static METRIC_GUEST_FUNC_DURATION: &str = "guest_call_duration_seconds";
@@ -0,0 +1,296 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::hypervisor::virtual_machine::VmExit;
+
+/// Why the vCPU of a sandbox exited to the host, see
+/// [`SandboxMetric::VmExit`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum VmExitReason {
+    /// The guest halted, which ends every guest call
+    Halt,
+    /// The guest wrote to an I/O port, to call the host, log or report
+    /// traces
+    IoOut,
+    /// The guest accessed an MMIO region that the host emulated
+    Mmio,
+    /// The guest accessed guest physical memory that is not mapped
+    EptViolation,
+    /// The guest accessed an MSR that the hypervisor does not virtualize
+    Msr,
+    /// Any other reason, such as the vCPU being interrupted
+    Other,
+}
+
+impl VmExitReason {
+    /// The name of the reason, as used in the labels of metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VmExitReason::Halt => "halt",
+            VmExitReason::IoOut => "io_out",
+            VmExitReason::Mmio => "mmio",
+            VmExitReason::EptViolation => "ept_violation",
+            VmExitReason::Msr => "msr",
+            VmExitReason::Other => "other",
+        }
+    }
+
+    pub(crate) fn of(exit: &VmExit) -> Self {
+        match exit {
+            VmExit::MmioEmulated() => VmExitReason::Mmio,
+            VmExit::MmioRead(_) | VmExit::MmioWrite(_) => VmExitReason::EptViolation,
+            VmExit::Halt() => VmExitReason::Halt,
+            VmExit::IoOut(_, _) => VmExitReason::IoOut,
+            VmExit::MsrAccess { .. } => VmExitReason::Msr,
+            #[cfg(gdb)]
+            VmExit::Debug { .. } => VmExitReason::Other,
+            VmExit::Cancelled() | VmExit::Unknown(_) | VmExit::Retry() => VmExitReason::Other,
+        }
+    }
+}
+
+/// A measurement of a sandbox, reported to the [`MetricsSink`] set with
+/// [`MultiUseSandbox::set_metrics_sink`] as it happens.
+///
+/// [`MultiUseSandbox::set_metrics_sink`]: crate::MultiUseSandbox::set_metrics_sink
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum SandboxMetric<'a> {
+    /// The host called a guest function
+    GuestCall {
+        /// The name of the function
+        function: &'a str,
+        /// How long the call took
+        duration: Duration,
+        /// Whether the call failed
+        failed: bool,
+    },
+    /// The guest called a host function
+    HostCall {
+        /// The name of the function
+        function: &'a str,
+        /// How long the call took
+        duration: Duration,
+        /// Whether the call failed
+        failed: bool,
+    },
+    /// The vCPU of the sandbox exited to the host
+    VmExit(VmExitReason),
+    /// The memory of the sandbox was restored from a snapshot
+    MemoryRestored {
+        /// The size of the memory restored, in bytes
+        bytes: u64,
+    },
+}
+
+/// Where the measurements of a sandbox go, to aggregate them into
+/// counters and histograms and export them.
+///
+/// Hyperlight does not depend on any exporter: [`MetricsRsSink`] forwards
+/// the measurements to the recorder of the `metrics` crate, which has
+/// Prometheus and OpenTelemetry exporters, and other backends can be
+/// plugged in by implementing this trait. Closures taking the same
+/// arguments as [`record`](Self::record) are sinks.
+///
+/// The sink is called on the thread running the sandbox, while the guest
+/// waits, so it should only do little work, such as updating atomic
+/// counters.
+pub trait MetricsSink: Send + Sync {
+    /// Records `metric` of the sandbox `sandbox_id`
+    fn record(&self, sandbox_id: u64, metric: SandboxMetric<'_>);
+}
+
+impl<F> MetricsSink for F
+where
+    F: Fn(u64, SandboxMetric<'_>) + Send + Sync,
+{
+    fn record(&self, sandbox_id: u64, metric: SandboxMetric<'_>) {
+        self(sandbox_id, metric)
+    }
+}
+
+/// A [`MetricsSink`] forwarding the measurements of sandboxes to the
+/// recorder installed for the `metrics` crate, labelled with the sandbox
+/// they belong to:
+///
+/// - `sandbox_guest_calls_total` and `sandbox_guest_call_duration_seconds`,
+///   labelled with the function and whether the call failed
+/// - `sandbox_host_calls_total` and `sandbox_host_call_duration_seconds`,
+///   labelled with the function and whether the call failed
+/// - `sandbox_vm_exits_total`, labelled with the reason of the exit
+/// - `sandbox_memory_restored_bytes_total`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetricsRsSink;
+
+impl MetricsSink for MetricsRsSink {
+    fn record(&self, sandbox_id: u64, metric: SandboxMetric<'_>) {
+        let sandbox_id = sandbox_id.to_string();
+        match metric {
+            SandboxMetric::GuestCall {
+                function,
+                duration,
+                failed,
+            } => {
+                let labels = [
+                    ("sandbox_id", sandbox_id),
+                    ("function_name", function.to_string()),
+                    ("failed", failed.to_string()),
+                ];
+                metrics::counter!("sandbox_guest_calls_total", &labels).increment(1);
+                metrics::histogram!("sandbox_guest_call_duration_seconds", &labels)
+                    .record(duration);
+            }
+            SandboxMetric::HostCall {
+                function,
+                duration,
+                failed,
+            } => {
+                let labels = [
+                    ("sandbox_id", sandbox_id),
+                    ("function_name", function.to_string()),
+                    ("failed", failed.to_string()),
+                ];
+                metrics::counter!("sandbox_host_calls_total", &labels).increment(1);
+                metrics::histogram!("sandbox_host_call_duration_seconds", &labels).record(duration);
+            }
+            SandboxMetric::VmExit(reason) => {
+                metrics::counter!(
+                    "sandbox_vm_exits_total",
+                    "sandbox_id" => sandbox_id,
+                    "reason" => reason.as_str()
+                )
+                .increment(1);
+            }
+            SandboxMetric::MemoryRestored { bytes } => {
+                metrics::counter!("sandbox_memory_restored_bytes_total", "sandbox_id" => sandbox_id)
+                    .increment(bytes);
+            }
+        }
+    }
+}
+
+/// Reports the measurements of a sandbox to its sink, if it has one
+#[derive(Clone, Default)]
+pub(crate) struct MetricsRecorder {
+    sandbox_id: u64,
+    sink: Option<Arc<dyn MetricsSink>>,
+}
+
+impl MetricsRecorder {
+    pub(crate) fn new(sandbox_id: u64, sink: Option<Arc<dyn MetricsSink>>) -> Self {
+        Self { sandbox_id, sink }
+    }
+
+    /// Whether the measurements are reported, so that they are only
+    /// taken when they are
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub(crate) fn sink(&self) -> Option<&Arc<dyn MetricsSink>> {
+        self.sink.as_ref()
+    }
+
+    pub(crate) fn record(&self, metric: SandboxMetric<'_>) {
+        if let Some(sink) = &self.sink {
+            sink.record(self.sandbox_id, metric);
+        }
+    }
+}
+
+impl std::fmt::Debug for MetricsRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetricsRecorder")
+            .field("sandbox_id", &self.sandbox_id)
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::{MetricsSink, SandboxMetric};
+    use crate::{GuestBinary, MultiUseSandbox, UninitializedSandbox};
+
+    #[test]
+    fn sandbox_metrics_are_reported() {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let sink: Arc<dyn MetricsSink> = Arc::new({
+            let recorded = recorded.clone();
+            move |sandbox_id: u64, metric: SandboxMetric<'_>| {
+                let metric = match metric {
+                    SandboxMetric::GuestCall {
+                        function, failed, ..
+                    } => format!("guest call {function} failed={failed}"),
+                    SandboxMetric::HostCall {
+                        function, failed, ..
+                    } => format!("host call {function} failed={failed}"),
+                    SandboxMetric::VmExit(reason) => format!("exit {}", reason.as_str()),
+                    SandboxMetric::MemoryRestored { bytes } => format!("restored {}", bytes > 0),
+                };
+                recorded.lock().unwrap().push((sandbox_id, metric));
+            }
+        });
+
+        let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_as_string().unwrap()),
+            None,
+        )
+        .unwrap()
+        .evolve()
+        .unwrap();
+        let snapshot = sandbox.snapshot().unwrap();
+        sandbox.set_metrics_sink(Some(sink));
+
+        sandbox
+            .call::<i32>("PrintOutput", "Hello".to_string())
+            .unwrap();
+        sandbox
+            .call::<i32>("FunctionThatDoesNotExist", ())
+            .unwrap_err();
+        sandbox.restore(snapshot).unwrap();
+
+        let count = {
+            let recorded = recorded.lock().unwrap();
+            assert!(recorded.iter().all(|(id, _)| *id == sandbox.id()));
+            let metrics: Vec<_> = recorded.iter().map(|(_, metric)| metric.as_str()).collect();
+            for expected in [
+                "guest call PrintOutput failed=false",
+                "host call HostPrint failed=false",
+                "guest call FunctionThatDoesNotExist failed=true",
+                "exit halt",
+                "exit io_out",
+                "restored true",
+            ] {
+                assert!(metrics.contains(&expected), "{expected} in {metrics:?}");
+            }
+            recorded.len()
+        };
+
+        // Nothing is reported once the sink is removed
+        sandbox.set_metrics_sink(None);
+        sandbox.call::<String>("Echo", "quiet".to_string()).unwrap();
+        assert_eq!(recorded.lock().unwrap().len(), count);
+    }
+}
@@ -0,0 +1,289 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+use crossbeam_channel::Sender;
+use hyperlight_common::func::{ParameterTuple, SupportedReturnType};
+
+use super::initialized_multi_use::MultiUseSandbox;
+use crate::hypervisor::InterruptHandle;
+use crate::{Result, new_error};
+
+/// Work for the thread of an [`AsyncSandbox`]
+type Job = Box<dyn FnOnce(&mut MultiUseSandbox) + Send>;
+
+/// A [`MultiUseSandbox`] whose guest functions are called from async
+/// code, such as the handlers of an HTTP server running on tokio.
+///
+/// The sandbox is moved to a thread of its own, which runs the vCPU,
+/// so that a guest call does not block a worker of the async runtime.
+/// Calls return a [`SandboxFuture`], which works with any runtime, and
+/// run one at a time in the order they were made.
+///
+/// Dropping a [`SandboxFuture`] does not stop its call, use
+/// [`interrupt_handle`](Self::interrupt_handle) for that. Dropping the
+/// `AsyncSandbox` lets the thread run the calls already made, then drops
+/// the sandbox.
+///
+/// ```no_run
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::sandbox::async_sandbox::AsyncSandbox;
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+/// let sandbox = AsyncSandbox::new(sandbox)?;
+///
+/// let message: String = sandbox.call("Echo", "Hello".to_string()).await?;
+/// assert_eq!(message, "Hello");
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncSandbox {
+    jobs: Option<Sender<Job>>,
+    worker: Option<JoinHandle<MultiUseSandbox>>,
+    interrupt_handle: Arc<dyn InterruptHandle>,
+}
+
+impl AsyncSandbox {
+    /// Moves `sandbox` to a new thread, which the guest calls run on.
+    /// The thread uses the tracing dispatcher that is the default on the
+    /// calling thread.
+    pub fn new(sandbox: MultiUseSandbox) -> Result<Self> {
+        let interrupt_handle = sandbox.interrupt_handle();
+        let (jobs, rx) = crossbeam_channel::unbounded::<Job>();
+        let dispatch = tracing::dispatcher::get_default(Clone::clone);
+        let worker = std::thread::Builder::new()
+            .name("hl-sandbox".to_string())
+            .spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    let mut sandbox = sandbox;
+                    for job in rx {
+                        job(&mut sandbox);
+                    }
+                    sandbox
+                })
+            })
+            .map_err(|e| new_error!("Failed to spawn the sandbox thread: {}", e))?;
+        Ok(Self {
+            jobs: Some(jobs),
+            worker: Some(worker),
+            interrupt_handle,
+        })
+    }
+
+    /// Calls the guest function `func_name` with `args`, as
+    /// [`MultiUseSandbox::call`] does, once the calls made before it
+    /// complete
+    pub fn call<Output, Args>(&self, func_name: &str, args: Args) -> SandboxFuture<Output>
+    where
+        Output: SupportedReturnType + Send + 'static,
+        Args: ParameterTuple + Send + 'static,
+    {
+        let func_name = func_name.to_string();
+        self.run(move |sandbox| sandbox.call(&func_name, args))
+    }
+
+    /// Runs `f` with the sandbox on its thread, once the calls made
+    /// before complete, for instance to take or restore a snapshot
+    /// between guest calls
+    pub fn with_sandbox<T, F>(&self, f: F) -> SandboxFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut MultiUseSandbox) -> T + Send + 'static,
+    {
+        self.run(move |sandbox| Ok(f(sandbox)))
+    }
+
+    /// The handle to interrupt the guest call running on the thread of
+    /// the sandbox, see [`MultiUseSandbox::interrupt_handle`]
+    pub fn interrupt_handle(&self) -> Arc<dyn InterruptHandle> {
+        self.interrupt_handle.clone()
+    }
+
+    /// Waits for the calls already made to complete, blocking the calling
+    /// thread, and gives the sandbox back
+    pub fn into_sandbox(mut self) -> Result<MultiUseSandbox> {
+        drop(self.jobs.take());
+        self.worker
+            .take()
+            .ok_or_else(|| new_error!("The sandbox thread was already joined"))?
+            .join()
+            .map_err(|_| new_error!("The sandbox thread panicked"))
+    }
+
+    fn run<T, F>(&self, f: F) -> SandboxFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut MultiUseSandbox) -> Result<T> + Send + 'static,
+    {
+        let (completer, future) = SandboxFuture::new();
+        let job: Job = Box::new(move |sandbox| completer.complete(f(sandbox)));
+        // If the thread is gone, the job is dropped along with its
+        // completer, which fails the future
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(job);
+        }
+        future
+    }
+}
+
+impl Drop for AsyncSandbox {
+    fn drop(&mut self) {
+        // The thread runs the jobs left in the queue, then exits and
+        // drops the sandbox, without this thread waiting for it
+        drop(self.jobs.take());
+    }
+}
+
+impl std::fmt::Debug for AsyncSandbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncSandbox").finish_non_exhaustive()
+    }
+}
+
+/// The state shared by a [`SandboxFuture`] and the job completing it
+struct Slot<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// The result of a call made through an [`AsyncSandbox`], available once
+/// the thread of the sandbox ran the call
+#[must_use = "the result of the call is lost if the future is dropped"]
+pub struct SandboxFuture<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> SandboxFuture<T> {
+    fn new() -> (Completer<T>, Self) {
+        let slot = Arc::new(Mutex::new(Slot {
+            result: None,
+            waker: None,
+        }));
+        (
+            Completer {
+                slot: Some(slot.clone()),
+            },
+            Self { slot },
+        )
+    }
+}
+
+impl<T> Future for SandboxFuture<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self
+            .slot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for SandboxFuture<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SandboxFuture").finish_non_exhaustive()
+    }
+}
+
+/// Completes a [`SandboxFuture`], with an error if it is dropped before
+struct Completer<T> {
+    slot: Option<Arc<Mutex<Slot<T>>>>,
+}
+
+impl<T> Completer<T> {
+    fn complete(mut self, result: Result<T>) {
+        self.set(result);
+    }
+
+    fn set(&mut self, result: Result<T>) {
+        let Some(slot) = self.slot.take() else {
+            return;
+        };
+        let waker = {
+            let mut slot = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            slot.result = Some(result);
+            slot.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        self.set(Err(new_error!(
+            "The sandbox thread stopped before the call completed"
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::AsyncSandbox;
+    use crate::sandbox::uninitialized::GuestBinary;
+    use crate::{MultiUseSandbox, UninitializedSandbox};
+
+    fn new_sandbox() -> MultiUseSandbox {
+        let path = simple_guest_as_string().unwrap();
+        UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn calls_run_in_order() {
+        let sandbox = AsyncSandbox::new(new_sandbox()).unwrap();
+
+        let echo = sandbox.call::<String, _>("Echo", "hello".to_string());
+        let first = sandbox.call::<i32, _>("AddToStatic", 5);
+        let second = sandbox.call::<i32, _>("AddToStatic", 5);
+        // The calls were made before any of them was awaited
+        assert_eq!(second.await.unwrap(), 10);
+        assert_eq!(first.await.unwrap(), 5);
+        assert_eq!(echo.await.unwrap(), "hello");
+        assert!(sandbox.call::<i32, _>("NoSuchFunction", ()).await.is_err());
+
+        let snapshot = sandbox
+            .with_sandbox(|sandbox| sandbox.snapshot())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sandbox.call::<i32, _>("AddToStatic", 1).await.unwrap(), 11);
+
+        let mut sandbox = sandbox.into_sandbox().unwrap();
+        sandbox.restore(snapshot).unwrap();
+        assert_eq!(sandbox.call::<i32>("AddToStatic", 1).unwrap(), 11);
+    }
+}
@@ -0,0 +1,143 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use hyperlight_common::mem::{BORROWED_BUFFER_BASE, BORROWED_BUFFER_WINDOW_SIZE, PAGE_SIZE_USIZE};
+
+use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
+use crate::{Result, new_error};
+
+/// A host buffer lent to the guest for the duration of the guest function
+/// calls made in [`MultiUseSandbox::with_borrowed_buffer`], which maps it
+/// into the guest instead of copying it through the input buffer, for
+/// payloads of several megabytes.
+///
+/// The buffer must start on a page boundary and its length must be a
+/// non-zero multiple of the page size, so that no other host memory is
+/// exposed to the guest. Such a buffer can be allocated with
+/// [`std::alloc::alloc`] and a layout aligned to the page size.
+///
+/// [`MultiUseSandbox::with_borrowed_buffer`]: crate::MultiUseSandbox::with_borrowed_buffer
+#[derive(Debug)]
+pub struct BorrowedGuestBuffer<'a> {
+    host_region: Range<usize>,
+    writable: bool,
+    _buffer: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> BorrowedGuestBuffer<'a> {
+    /// Lends `buffer` to the guest, which can only read it
+    pub fn read_only(buffer: &'a [u8]) -> Result<Self> {
+        Self::new(buffer.as_ptr() as usize, buffer.len(), false)
+    }
+
+    /// Lends `buffer` to the guest, which can read and write it
+    pub fn read_write(buffer: &'a mut [u8]) -> Result<Self> {
+        Self::new(buffer.as_mut_ptr() as usize, buffer.len(), true)
+    }
+
+    fn new(base: usize, len: usize, writable: bool) -> Result<Self> {
+        if base % PAGE_SIZE_USIZE != 0 {
+            return Err(new_error!(
+                "The buffer at {:#x} lent to the guest is not page aligned",
+                base
+            ));
+        }
+        if len == 0 || len % PAGE_SIZE_USIZE != 0 {
+            return Err(new_error!(
+                "The length {:#x} of the buffer lent to the guest is not a multiple of the page size",
+                len
+            ));
+        }
+        Ok(Self {
+            host_region: base..base + len,
+            writable,
+            _buffer: PhantomData,
+        })
+    }
+
+    /// The length of the buffer in bytes
+    pub fn len(&self) -> usize {
+        self.host_region.len()
+    }
+
+    /// Whether the buffer is empty, which it never is
+    pub fn is_empty(&self) -> bool {
+        self.host_region.is_empty()
+    }
+
+    /// Whether the guest can write to the buffer
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    pub(crate) fn host_region(&self) -> Range<usize> {
+        self.host_region.clone()
+    }
+
+    pub(crate) fn flags(&self) -> MemoryRegionFlags {
+        if self.writable {
+            MemoryRegionFlags::READ | MemoryRegionFlags::WRITE
+        } else {
+            MemoryRegionFlags::READ
+        }
+    }
+}
+
+/// Whether `region` is a buffer lent to the guest, which is left out of
+/// the snapshots of the sandbox, since the host memory it maps is only
+/// valid while it is lent
+pub(crate) fn is_borrowed_buffer(region: &MemoryRegion) -> bool {
+    let window = BORROWED_BUFFER_BASE..BORROWED_BUFFER_BASE + BORROWED_BUFFER_WINDOW_SIZE;
+    window.contains(&(region.guest_region.start as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{Layout, alloc_zeroed, dealloc};
+
+    use hyperlight_common::mem::PAGE_SIZE_USIZE;
+
+    use super::BorrowedGuestBuffer;
+
+    #[test]
+    fn buffers_must_be_whole_pages() {
+        let layout = Layout::from_size_align(2 * PAGE_SIZE_USIZE, PAGE_SIZE_USIZE).unwrap();
+        // Safety: the layout is not zero sized, and the memory is only
+        // used while it is allocated
+        unsafe {
+            let ptr = alloc_zeroed(layout);
+            assert!(!ptr.is_null());
+            let buffer = std::slice::from_raw_parts_mut(ptr, layout.size());
+
+            let lent = BorrowedGuestBuffer::read_only(buffer).unwrap();
+            assert_eq!(lent.len(), 2 * PAGE_SIZE_USIZE);
+            assert!(!lent.is_writable());
+            assert!(
+                BorrowedGuestBuffer::read_write(buffer)
+                    .unwrap()
+                    .is_writable()
+            );
+            assert!(BorrowedGuestBuffer::read_only(&buffer[1..PAGE_SIZE_USIZE + 1]).is_err());
+            assert!(BorrowedGuestBuffer::read_only(&buffer[..100]).is_err());
+            assert!(BorrowedGuestBuffer::read_only(&buffer[..0]).is_err());
+
+            dealloc(ptr, layout);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::hypervisor::InterruptHandle;
+
+/// Cancels the guest function calls made with
+/// [`MultiUseSandbox::call_cancellable`], from any thread.
+///
+/// A cancelled call is interrupted if it is running, and the sandbox is
+/// restored to its state before the call, which then fails with
+/// [`HyperlightError::Cancelled`]. Unlike killing the call with the
+/// [`InterruptHandle`] of the sandbox, this leaves the sandbox usable.
+///
+/// Clones share the token. Once cancelled, a token stays cancelled, and
+/// the calls made with it fail without running.
+///
+/// [`MultiUseSandbox::call_cancellable`]: crate::MultiUseSandbox::call_cancellable
+/// [`HyperlightError::Cancelled`]: crate::HyperlightError::Cancelled
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+#[derive(Debug, Default)]
+struct TokenInner {
+    cancelled: AtomicBool,
+    /// The handle of the sandbox running a call made with the token
+    running: Mutex<Option<Arc<dyn InterruptHandle>>>,
+}
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the calls made with the token, interrupting the one that
+    /// is running, if any. This returns once the interrupt is requested,
+    /// without waiting for the call to return.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        let running = self
+            .inner
+            .running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(handle) = running.as_ref() {
+            handle.kill();
+        }
+    }
+
+    /// Whether the token was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Makes cancelling the token interrupt the call `handle` is about to
+    /// run, which it does right away if the token is already cancelled
+    pub(crate) fn attach(&self, handle: Arc<dyn InterruptHandle>) {
+        let mut running = self
+            .inner
+            .running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.is_cancelled() {
+            handle.kill();
+        }
+        *running = Some(handle);
+    }
+
+    /// Stops cancelling the token from interrupting the sandbox, once the
+    /// call made with it has returned
+    pub(crate) fn detach(&self) {
+        *self
+            .inner
+            .running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+}
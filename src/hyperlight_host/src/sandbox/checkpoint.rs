@@ -0,0 +1,132 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::config::GuestCheckpointPolicy;
+use super::snapshot::Snapshot;
+
+/// A snapshot taken at a checkpoint declared by the guest, a point during
+/// a call where the guest state is consistent. Restoring it with
+/// [`MultiUseSandbox::restore`](crate::MultiUseSandbox::restore) rolls the
+/// sandbox back to that point, after which guest functions can be called
+/// again.
+#[derive(Clone, Debug)]
+pub struct GuestCheckpoint {
+    name: String,
+    snapshot: Arc<Snapshot>,
+    taken_at: Instant,
+}
+
+impl GuestCheckpoint {
+    /// The name the guest gave to the checkpoint
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The snapshot of the sandbox at the checkpoint
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot.clone()
+    }
+
+    /// When the snapshot was taken
+    pub fn taken_at(&self) -> Instant {
+        self.taken_at
+    }
+}
+
+/// Decides, according to the [`GuestCheckpointPolicy`] of a sandbox,
+/// which checkpoints declared by the guest are snapshotted, and keeps the
+/// last of these snapshots
+#[derive(Debug)]
+pub(crate) struct GuestCheckpoints {
+    policy: GuestCheckpointPolicy,
+    /// The sandbox the snapshots belong to, only known once the guest is
+    /// initialised, checkpoints declared before are ignored
+    sandbox_id: Option<u64>,
+    last: Option<GuestCheckpoint>,
+}
+
+impl GuestCheckpoints {
+    pub(crate) fn new(policy: GuestCheckpointPolicy) -> Self {
+        Self {
+            policy,
+            sandbox_id: None,
+            last: None,
+        }
+    }
+
+    /// Starts taking snapshots for the sandbox `sandbox_id`
+    pub(crate) fn enable(&mut self, sandbox_id: u64) {
+        self.sandbox_id = Some(sandbox_id);
+    }
+
+    /// Returns the sandbox to take a snapshot of, if a checkpoint
+    /// declared now is to be snapshotted
+    pub(crate) fn due(&self, now: Instant) -> Option<u64> {
+        let sandbox_id = self.sandbox_id?;
+        let due = match self.policy {
+            GuestCheckpointPolicy::Ignore => false,
+            GuestCheckpointPolicy::Always => true,
+            GuestCheckpointPolicy::MinInterval(interval) => self
+                .last
+                .as_ref()
+                .is_none_or(|last| now.duration_since(last.taken_at) >= interval),
+        };
+        due.then_some(sandbox_id)
+    }
+
+    /// Keeps the snapshot taken at the checkpoint `name`
+    pub(crate) fn record(&mut self, name: String, snapshot: Arc<Snapshot>, taken_at: Instant) {
+        self.last = Some(GuestCheckpoint {
+            name,
+            snapshot,
+            taken_at,
+        });
+    }
+
+    pub(crate) fn last(&self) -> Option<&GuestCheckpoint> {
+        self.last.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::GuestCheckpoints;
+    use crate::sandbox::config::GuestCheckpointPolicy;
+
+    #[test]
+    fn policy() {
+        let now = Instant::now();
+
+        let mut checkpoints = GuestCheckpoints::new(GuestCheckpointPolicy::Always);
+        assert_eq!(checkpoints.due(now), None);
+        checkpoints.enable(7);
+        assert_eq!(checkpoints.due(now), Some(7));
+
+        let mut ignored = GuestCheckpoints::new(GuestCheckpointPolicy::Ignore);
+        ignored.enable(7);
+        assert_eq!(ignored.due(now), None);
+
+        let mut throttled =
+            GuestCheckpoints::new(GuestCheckpointPolicy::MinInterval(Duration::from_secs(1)));
+        throttled.enable(7);
+        assert_eq!(throttled.due(now), Some(7));
+    }
+}
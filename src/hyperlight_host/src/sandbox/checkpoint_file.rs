@@ -0,0 +1,187 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The on-disk format of the checkpoints saved with
+//! [`MultiUseSandbox::checkpoint`](crate::MultiUseSandbox::checkpoint).
+//!
+//! A checkpoint file starts with [`MAGIC`] and the [`VERSION`] of the
+//! format, followed by the host functions the sandbox had, as a
+//! `HostFunctionDetails` flatbuffer, and then by the snapshot of the
+//! sandbox in the format of a snapshot file, which holds its memory, its
+//! layout and the special registers of its vCPU.
+
+use std::path::Path;
+
+use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
+
+use super::SandboxConfiguration;
+use super::snapshot::Snapshot;
+use super::snapshot_file::{Decoder, Encoder};
+use crate::func::HostFunctions;
+use crate::{MultiUseSandbox, Result, UninitializedSandbox, new_error};
+
+/// The first bytes of a checkpoint file
+pub(crate) const MAGIC: [u8; 8] = *b"HLCHKPNT";
+/// The version of the format, changed on incompatible changes
+pub(crate) const VERSION: u32 = 1;
+
+/// Saves `snapshot` and the `host_functions` of the sandbox it was taken
+/// from to the file at `path`
+pub(crate) fn save(
+    path: impl AsRef<Path>,
+    snapshot: &Snapshot,
+    host_functions: HostFunctionDetails,
+) -> Result<()> {
+    let mut enc = Encoder::default();
+    enc.bytes.extend_from_slice(&MAGIC);
+    enc.u32(VERSION);
+    enc.bytes(&encode_host_functions(host_functions)?);
+    snapshot.encode(&mut enc)?;
+    std::fs::write(path, enc.bytes)?;
+    Ok(())
+}
+
+/// Loads the snapshot and the host functions saved with [`save`]
+pub(crate) fn load(path: impl AsRef<Path>) -> Result<(Snapshot, HostFunctionDetails)> {
+    let bytes = std::fs::read(path)?;
+    let mut dec = Decoder::new(&bytes);
+    let magic: [u8; 8] = dec.array()?;
+    if magic != MAGIC {
+        return Err(new_error!("Not a checkpoint file"));
+    }
+    let version = dec.u32()?;
+    if version != VERSION {
+        return Err(new_error!(
+            "Checkpoint file version {} is not supported, expected {}",
+            version,
+            VERSION
+        ));
+    }
+    let host_functions = decode_host_functions(dec.bytes()?)?;
+    let snapshot = Snapshot::decode(&mut dec)?;
+    dec.finish()?;
+    Ok((snapshot, host_functions))
+}
+
+/// Encodes the `host_functions` of a sandbox as a flatbuffer. The
+/// reserved host functions are left out, since hyperlight registers them
+/// again on the sandbox restored from the checkpoint.
+pub(crate) fn encode_host_functions(mut host_functions: HostFunctionDetails) -> Result<Vec<u8>> {
+    if let Some(functions) = &mut host_functions.host_functions {
+        functions.retain(|function| !function.function_name.starts_with("__hl_"));
+    }
+    (&host_functions)
+        .try_into()
+        .map_err(|e| new_error!("Cannot encode the host functions: {}", e))
+}
+
+/// Decodes host functions encoded with [`encode_host_functions`]
+pub(crate) fn decode_host_functions(bytes: &[u8]) -> Result<HostFunctionDetails> {
+    HostFunctionDetails::try_from(bytes)
+        .map_err(|e| new_error!("Invalid host functions in checkpoint: {}", e))
+}
+
+/// Creates a sandbox from `snapshot` with `host_functions`, which must
+/// have the host functions `saved` with the snapshot
+pub(crate) fn restore(
+    snapshot: Snapshot,
+    saved: &HostFunctionDetails,
+    host_functions: &HostFunctions,
+    cfg: Option<SandboxConfiguration>,
+) -> Result<MultiUseSandbox> {
+    let mut sandbox = UninitializedSandbox::from_loaded_snapshot(snapshot, cfg)?;
+    sandbox.register_host_functions(host_functions)?;
+    let registered = HostFunctionDetails::from(
+        &mut *sandbox
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?,
+    );
+    check_host_functions(saved, &registered)?;
+    sandbox.evolve()
+}
+
+/// Checks that every host function `saved` in a checkpoint is
+/// `registered` on the sandbox restored from it, with the same signature,
+/// since the guest may call any of them
+fn check_host_functions(
+    saved: &HostFunctionDetails,
+    registered: &HostFunctionDetails,
+) -> Result<()> {
+    let registered = registered.host_functions.as_deref().unwrap_or_default();
+    for function in saved.host_functions.as_deref().unwrap_or_default() {
+        match registered
+            .iter()
+            .find(|r| r.function_name == function.function_name)
+        {
+            Some(r)
+                if r.return_type == function.return_type
+                    && r.parameter_types.as_deref().unwrap_or_default()
+                        == function.parameter_types.as_deref().unwrap_or_default() => {}
+            Some(_) => {
+                return Err(new_error!(
+                    "Host function {} has another signature than when the checkpoint was taken",
+                    function.function_name
+                ));
+            }
+            None => {
+                return Err(new_error!(
+                    "Host function {} of the checkpoint is not registered",
+                    function.function_name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ReturnType};
+    use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
+    use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
+
+    use super::check_host_functions;
+
+    fn details(functions: &[(&str, ReturnType)]) -> HostFunctionDetails {
+        HostFunctionDetails {
+            host_functions: Some(
+                functions
+                    .iter()
+                    .map(|(name, return_type)| HostFunctionDefinition {
+                        function_name: name.to_string(),
+                        parameter_types: Some(vec![ParameterType::Int]),
+                        return_type: *return_type,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn host_functions_must_match() {
+        let saved = details(&[("Add", ReturnType::Int)]);
+
+        // More host functions than when the checkpoint was taken are fine
+        check_host_functions(
+            &saved,
+            &details(&[("Add", ReturnType::Int), ("Sub", ReturnType::Int)]),
+        )
+        .unwrap();
+        assert!(check_host_functions(&saved, &details(&[("Add", ReturnType::Long)])).is_err());
+        assert!(check_host_functions(&saved, &details(&[("Sub", ReturnType::Int)])).is_err());
+    }
+}
@@ -15,18 +15,444 @@ limitations under the License.
 */
 
 use std::cmp::max;
+#[cfg(gdb)]
+use std::net::{IpAddr, Ipv4Addr};
 use std::time::Duration;
 
+use hyperlight_common::guest_config::{
+    GUEST_CONFIG_ABI_VERSION, GuestBinaryConfig, GuestCapabilities,
+};
+use hyperlight_common::mem::{GuestAllocFaults, PAGE_SIZE_USIZE};
 #[cfg(target_os = "linux")]
 use libc::c_int;
 use tracing::{Span, instrument};
 
+use crate::hypervisor::virtual_machine::HypervisorKind;
+
 /// Used for passing debug configuration to a sandbox
 #[cfg(gdb)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct DebugInfo {
-    /// Guest debug port
+    /// Address the gdb server binds to
+    pub addr: IpAddr,
+    /// Guest debug port. If set to 0, a free port is picked by the OS
+    /// and logged when the gdb server starts listening
     pub port: u16,
+    /// How many of the last stops of each guest call are recorded for
+    /// reverse execution (`reverse-stepi` / `reverse-continue`). Each stop
+    /// stores the guest memory pages that changed since the previous one,
+    /// on top of a copy of the whole guest memory, so recording costs a
+    /// scan of the guest memory at every stop. Reverse execution is
+    /// disabled if set to 0, which is the default.
+    pub reverse_execution_stops: usize,
+}
+
+#[cfg(gdb)]
+impl DebugInfo {
+    /// Create a debug configuration listening on the given port of the
+    /// loopback interface
+    pub fn new(port: u16) -> Self {
+        Self {
+            addr: Ipv4Addr::LOCALHOST.into(),
+            port,
+            reverse_execution_stops: 0,
+        }
+    }
+}
+
+/// Failures injected deterministically into a sandbox, so that guest
+/// authors can exercise their error paths from integration tests.
+/// All the counters are disabled when set to 0.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct FaultInjection {
+    /// Make every Nth guest allocation fail. Allocations are counted
+    /// once the guest is initialised, and the count is part of the
+    /// guest memory, so restoring a snapshot restores it too.
+    pub guest_alloc_fail_every: u64,
+    /// Make the guest allocations fail once the bytes requested in total
+    /// since the guest was initialised exceed this budget
+    pub guest_alloc_byte_budget: u64,
+    /// Make every Nth host function call made by the guest fail with
+    /// a `HostFunctionError`, without calling the host function.
+    /// Calls are counted from the creation of the sandbox.
+    pub host_call_fail_every: u64,
+}
+
+/// What to do with a guest log record when the queue of records waiting
+/// to be delivered to the host log sinks is full, see
+/// [`GuestLogBackpressure`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub enum GuestLogOverflow {
+    /// Make the guest wait up to the given time for room in the queue,
+    /// then drop the record
+    Block(Duration),
+    /// Drop the record right away
+    Drop,
+    /// Once the queue is half full, only keep one in every N records,
+    /// dropping the others, and drop the records that do not fit
+    Sample(u32),
+}
+
+/// How guest log records are delivered to the host log sinks.
+///
+/// With a queue capacity of 0, the default, records are delivered while
+/// the guest waits, so a slow sink slows the guest down. Otherwise,
+/// records are queued and delivered from a thread of the sandbox, and
+/// `overflow` decides what happens when the sinks cannot keep up.
+/// Dropped records are counted by the `guest_log_records_dropped_total`
+/// metric.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct GuestLogBackpressure {
+    /// The number of records that can wait to be delivered
+    pub queue_capacity: usize,
+    /// What to do with a record when the queue is full
+    pub overflow: GuestLogOverflow,
+}
+
+impl Default for GuestLogBackpressure {
+    fn default() -> Self {
+        Self {
+            queue_capacity: 0,
+            overflow: GuestLogOverflow::Drop,
+        }
+    }
+}
+
+/// Which guest allocations are reported to the memory profiler, see
+/// [`MultiUseSandbox::set_mem_profile_capture`](crate::MultiUseSandbox::set_mem_profile_capture)
+#[cfg(feature = "mem_profile")]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum MemProfileCapture {
+    /// Report every allocation and deallocation
+    #[default]
+    All,
+    /// Only report the allocations and deallocations of at least this
+    /// many bytes
+    MinSize(u64),
+    /// Report nothing
+    Off,
+}
+
+#[cfg(feature = "mem_profile")]
+impl MemProfileCapture {
+    /// The size of the smallest allocation the guest reports
+    pub(crate) fn min_size(self) -> u64 {
+        match self {
+            MemProfileCapture::All => 0,
+            MemProfileCapture::MinSize(size) => size,
+            MemProfileCapture::Off => u64::MAX,
+        }
+    }
+}
+
+/// What backs the memory of a sandbox on the host, see
+/// [`SandboxConfiguration::set_memory_backing`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub enum MemoryBacking {
+    /// Private anonymous memory
+    #[default]
+    Anonymous,
+    /// A memfd, only on Linux, whose descriptor the host can pass to
+    /// other tools, such as symbolizers and dump writers, so that they
+    /// read the memory of the guest without it being copied
+    Memfd {
+        /// Whether the memfd is sealed with `F_SEAL_SHRINK`,
+        /// `F_SEAL_GROW` and `F_SEAL_SEAL`, so that no holder of its
+        /// descriptor can resize it. Shrinking an unsealed memfd makes
+        /// the host crash with `SIGBUS` when the memory is next touched.
+        sealed: bool,
+    },
+}
+
+/// A register of a CPUID leaf
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub enum CpuidRegister {
+    /// The EAX register
+    Eax = 0,
+    /// The EBX register
+    Ebx = 1,
+    /// The ECX register
+    Ecx = 2,
+    /// The EDX register
+    Edx = 3,
+}
+
+/// A change to the result of a CPUID leaf presented to the guest, which
+/// otherwise sees whatever the hypervisor reports by default.
+///
+/// Only the bits set or cleared through the builder methods are changed,
+/// the other bits keep the value the hypervisor reports, so that e.g. a
+/// feature can be hidden from the guest without knowing the rest of the
+/// leaf:
+///
+/// ```
+/// use hyperlight_host::sandbox::{CpuidOverride, CpuidRegister, SandboxConfiguration};
+///
+/// let mut cfg = SandboxConfiguration::default();
+/// // Hide AVX2, which is bit 5 of EBX in leaf 7, subleaf 0
+/// cfg.add_cpuid_override(
+///     CpuidOverride::new(7)
+///         .with_index(0)
+///         .clear_bits(CpuidRegister::Ebx, 1 << 5),
+/// )?;
+/// cfg.add_cpuid_override(CpuidOverride::without_avx512())?;
+/// cfg.add_cpuid_override(CpuidOverride::vendor(b"GenuineIntel"))?;
+/// # Ok::<(), hyperlight_host::HyperlightError>(())
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct CpuidOverride {
+    function: u32,
+    index: Option<u32>,
+    /// The bits changed in EAX, EBX, ECX and EDX
+    mask: [u32; 4],
+    /// The values of the changed bits
+    value: [u32; 4],
+}
+
+impl CpuidOverride {
+    /// The leaf of the hypervisor vendor, which the guest reads when
+    /// CPUID.1:ECX.HYPERVISOR is set
+    pub const HYPERVISOR_LEAF: u32 = 0x4000_0000;
+
+    /// Creates an override of the leaf `function` that changes nothing,
+    /// which applies to all its subleaves unless [`Self::with_index`]
+    /// is used
+    pub fn new(function: u32) -> Self {
+        Self {
+            function,
+            ..Default::default()
+        }
+    }
+
+    /// Only overrides the subleaf `index`, the value of ECX when the guest
+    /// runs CPUID
+    pub fn with_index(mut self, index: u32) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Replaces the whole value of `register`
+    pub fn set(self, register: CpuidRegister, value: u32) -> Self {
+        self.set_masked(register, u32::MAX, value)
+    }
+
+    /// Sets the given bits of `register`
+    pub fn set_bits(self, register: CpuidRegister, bits: u32) -> Self {
+        self.set_masked(register, bits, bits)
+    }
+
+    /// Clears the given bits of `register`
+    pub fn clear_bits(self, register: CpuidRegister, bits: u32) -> Self {
+        self.set_masked(register, bits, 0)
+    }
+
+    /// Overrides the CPU vendor string of leaf 0, e.g. `GenuineIntel` or
+    /// `AuthenticAMD`
+    pub fn vendor(vendor: &[u8; 12]) -> Self {
+        let [ebx, edx, ecx] = vendor_registers(vendor);
+        Self::new(0)
+            .set(CpuidRegister::Ebx, ebx)
+            .set(CpuidRegister::Edx, edx)
+            .set(CpuidRegister::Ecx, ecx)
+    }
+
+    /// Overrides the hypervisor vendor string of [`Self::HYPERVISOR_LEAF`],
+    /// e.g. `KVMKVMKVM\0\0\0` or `Microsoft Hv`
+    pub fn hypervisor_vendor(vendor: &[u8; 12]) -> Self {
+        let [ebx, ecx, edx] = vendor_registers(vendor);
+        Self::new(Self::HYPERVISOR_LEAF)
+            .set(CpuidRegister::Ebx, ebx)
+            .set(CpuidRegister::Ecx, ecx)
+            .set(CpuidRegister::Edx, edx)
+    }
+
+    /// Hides the AVX-512 instruction set extensions, reported in leaf 7,
+    /// subleaf 0, so that guests built to use them when available behave
+    /// the same on every host
+    pub fn without_avx512() -> Self {
+        // F, DQ, IFMA, PF, ER, CD, BW and VL
+        const EBX: u32 = (1 << 16)
+            | (1 << 17)
+            | (1 << 21)
+            | (1 << 26)
+            | (1 << 27)
+            | (1 << 28)
+            | (1 << 30)
+            | (1 << 31);
+        // VBMI, VBMI2, VNNI, BITALG and VPOPCNTDQ
+        const ECX: u32 = (1 << 1) | (1 << 6) | (1 << 11) | (1 << 12) | (1 << 14);
+        // 4VNNIW, 4FMAPS, VP2INTERSECT and FP16
+        const EDX: u32 = (1 << 2) | (1 << 3) | (1 << 8) | (1 << 23);
+        Self::new(7)
+            .with_index(0)
+            .clear_bits(CpuidRegister::Ebx, EBX)
+            .clear_bits(CpuidRegister::Ecx, ECX)
+            .clear_bits(CpuidRegister::Edx, EDX)
+    }
+
+    /// Reports a TSC frequency of `khz` in leaf 0x15, as a TSC to core
+    /// crystal clock ratio of `khz` over a 1kHz crystal
+    pub(crate) fn tsc_frequency(khz: u32) -> Self {
+        Self::new(0x15)
+            .set(CpuidRegister::Eax, 1)
+            .set(CpuidRegister::Ebx, khz)
+            .set(CpuidRegister::Ecx, 1000)
+    }
+
+    /// Returns the leaf this overrides
+    pub fn function(&self) -> u32 {
+        self.function
+    }
+
+    /// Returns the subleaf this overrides, or `None` for all of them
+    pub fn index(&self) -> Option<u32> {
+        self.index
+    }
+
+    fn set_masked(mut self, register: CpuidRegister, mask: u32, value: u32) -> Self {
+        let r = register as usize;
+        self.mask[r] |= mask;
+        self.value[r] = (self.value[r] & !mask) | (value & mask);
+        self
+    }
+
+    /// Returns whether this overrides the given subleaf
+    pub(crate) fn matches(&self, function: u32, index: u32) -> bool {
+        self.function == function && self.index.is_none_or(|i| i == index)
+    }
+
+    /// Applies the override to the EAX, EBX, ECX and EDX values of a leaf
+    pub(crate) fn apply(&self, regs: [u32; 4]) -> [u32; 4] {
+        std::array::from_fn(|r| (regs[r] & !self.mask[r]) | self.value[r])
+    }
+}
+
+/// Splits a vendor string into the three registers it is reported in
+fn vendor_registers(vendor: &[u8; 12]) -> [u32; 3] {
+    std::array::from_fn(|i| {
+        u32::from_le_bytes([
+            vendor[4 * i],
+            vendor[4 * i + 1],
+            vendor[4 * i + 2],
+            vendor[4 * i + 3],
+        ])
+    })
+}
+
+/// What happens when the guest reads or writes a model-specific register
+/// (MSR) that the hypervisor does not virtualize.
+///
+/// Without a policy, each hypervisor handles these accesses its own way,
+/// usually by injecting a general protection fault into the guest. With
+/// [`MsrPolicy::Deny`] or [`MsrPolicy::EmulateAsZero`], Hyperlight
+/// intercepts them on every hypervisor, through the user space MSR exits
+/// on KVM, the MSR intercept on MSHV and the MSR exits on WHP.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub enum MsrPolicy {
+    /// Leave the access to the hypervisor. This is the default.
+    #[default]
+    Allow,
+    /// Stop the guest, and fail the guest call with
+    /// [`HyperlightError::MsrAccessDenied`](crate::HyperlightError::MsrAccessDenied),
+    /// which poisons the sandbox
+    Deny,
+    /// Let the guest read zero from the register and ignore its writes
+    EmulateAsZero,
+}
+
+/// When the host takes a snapshot at the checkpoints the guest declares
+/// during a call, see [`MultiUseSandbox::last_checkpoint`](crate::MultiUseSandbox::last_checkpoint)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub enum GuestCheckpointPolicy {
+    /// Ignore the checkpoints. This is the default.
+    #[default]
+    Ignore,
+    /// Take a snapshot at every checkpoint
+    Always,
+    /// Take a snapshot at a checkpoint when the last one was taken at
+    /// least this long ago
+    MinInterval(Duration),
+}
+
+/// A set of host CPUs, see [`SandboxConfiguration::set_vcpu_affinity`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct CpuSet {
+    bits: [u64; CpuSet::MAX_CPUS / 64],
+}
+
+impl CpuSet {
+    /// The number of CPUs a set can hold, CPU numbers go from 0 to
+    /// `MAX_CPUS - 1`
+    pub const MAX_CPUS: usize = 256;
+
+    /// Creates an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the set of the CPUs `cpus`, returning an error if one is
+    /// not lower than [`Self::MAX_CPUS`]
+    pub fn from_cpus(cpus: impl IntoIterator<Item = usize>) -> crate::Result<Self> {
+        let mut set = Self::new();
+        for cpu in cpus {
+            set.add(cpu)?;
+        }
+        Ok(set)
+    }
+
+    /// Adds `cpu` to the set, returning an error if it is not lower than
+    /// [`Self::MAX_CPUS`]
+    pub fn add(&mut self, cpu: usize) -> crate::Result<()> {
+        if cpu >= Self::MAX_CPUS {
+            return Err(crate::new_error!(
+                "CPU {} is not lower than the {} CPUs a CpuSet can hold",
+                cpu,
+                Self::MAX_CPUS
+            ));
+        }
+        self.bits[cpu / 64] |= 1 << (cpu % 64);
+        Ok(())
+    }
+
+    /// Whether `cpu` is in the set
+    pub fn contains(&self, cpu: usize) -> bool {
+        cpu < Self::MAX_CPUS && self.bits[cpu / 64] & (1 << (cpu % 64)) != 0
+    }
+
+    /// Whether the set holds no CPU
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// The CPUs of the set, in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..Self::MAX_CPUS).filter(|&cpu| self.contains(cpu))
+    }
+}
+
+/// The scheduling priority of the thread running the vCPU of a sandbox,
+/// see [`SandboxConfiguration::set_vcpu_priority`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub enum VcpuPriority {
+    /// Keep the priority of the thread calling into the guest. This is
+    /// the default.
+    #[default]
+    Inherit,
+    /// Run with this nice value, from -20 for the highest priority to 19
+    /// for the lowest. On Windows, it is mapped to the closest thread
+    /// priority level, from `THREAD_PRIORITY_HIGHEST` to
+    /// `THREAD_PRIORITY_LOWEST`.
+    Nice(i8),
 }
 
 /// The complete set of configuration needed to create a Sandbox
@@ -41,6 +467,11 @@ pub struct SandboxConfiguration {
     /// The core dump files generation can be disabled by setting this field to false.
     #[cfg(crashdump)]
     guest_core_dump: bool,
+    /// Whether a crash report bundle is written when the guest crashes,
+    /// see [`SandboxConfiguration::set_guest_crash_report`]. Disabled by
+    /// default.
+    #[cfg(crashdump)]
+    guest_crash_report: bool,
     /// Guest gdb debug port
     #[cfg(gdb)]
     guest_debug_info: Option<DebugInfo>,
@@ -74,6 +505,58 @@ pub struct SandboxConfiguration {
     interrupt_vcpu_sigrtmin_offset: u8,
     /// How much writable memory to offer the guest
     scratch_size: usize,
+    /// Whether the initial guest page tables should map memory with
+    /// 2MiB pages wherever the alignment of the guest addresses allows
+    /// it, reducing TLB misses for guests that touch a lot of memory.
+    /// Ranges that cannot be mapped this way automatically fall back
+    /// to 4KiB pages.
+    guest_large_pages: bool,
+    /// Whether the memory of the sandbox is backed by transparent huge
+    /// pages on the host
+    host_huge_pages: bool,
+    /// What backs the memory of the sandbox on the host
+    memory_backing: MemoryBacking,
+    /// The size the main stack of the guest is limited to, 0 when it
+    /// can grow until the end of the stack region
+    max_stack_size: u64,
+    /// The number of unmapped pages on each side of the guest heap
+    heap_guard_pages: u64,
+    /// Whether the guest binary, heap and stack are placed at random
+    /// addresses
+    guest_aslr: bool,
+    /// Whether no page of the guest is ever both writable and executable
+    guest_write_xor_execute: bool,
+    /// Whether the memory of the sandbox is explicitly overwritten with
+    /// zeroes before it is given back to the OS
+    zeroize_memory: bool,
+    /// Failures injected into the guest allocator and the host function
+    /// calls made by the guest
+    fault_injection: FaultInjection,
+    /// How guest log records are delivered when the host log sinks are
+    /// slower than the guest
+    guest_log_backpressure: GuestLogBackpressure,
+    /// The changes to the CPUID leaves presented to the guest, see
+    /// [`SandboxConfiguration::add_cpuid_override`]
+    cpuid_overrides: [Option<CpuidOverride>; Self::MAX_CPUID_OVERRIDES],
+    /// What happens when the guest accesses an MSR that the hypervisor
+    /// does not virtualize
+    msr_policy: MsrPolicy,
+    /// When snapshots are taken at the checkpoints declared by the guest
+    guest_checkpoint_policy: GuestCheckpointPolicy,
+    /// The host CPUs the vCPU runs on, if it is pinned
+    vcpu_affinity: Option<CpuSet>,
+    /// The scheduling priority the vCPU runs with
+    vcpu_priority: VcpuPriority,
+    /// The CPU time a guest function call can use before it is stopped
+    guest_cpu_time_budget: Option<Duration>,
+    /// Whether the instructions and cycles of the guest are counted
+    guest_perf_counters: bool,
+    /// The offset of the guest TSC from the host TSC, if it is set
+    guest_tsc_offset: Option<i64>,
+    /// The frequency of the guest TSC in kHz, if it is scaled
+    guest_tsc_khz: Option<u32>,
+    /// The hypervisor the sandbox runs on
+    hypervisor: HypervisorKind,
 }
 
 impl SandboxConfiguration {
@@ -93,6 +576,8 @@ impl SandboxConfiguration {
     pub const DEFAULT_HEAP_SIZE: u64 = 131072;
     /// The default size of the scratch region
     pub const DEFAULT_SCRATCH_SIZE: usize = 0x48000;
+    /// The maximum number of CPUID leaves that can be overridden
+    pub const MAX_CPUID_OVERRIDES: usize = 16;
 
     #[allow(clippy::too_many_arguments)]
     /// Create a new configuration for a sandbox with the given sizes.
@@ -114,10 +599,32 @@ impl SandboxConfiguration {
             scratch_size,
             interrupt_retry_delay,
             interrupt_vcpu_sigrtmin_offset,
+            guest_large_pages: false,
+            host_huge_pages: false,
+            memory_backing: MemoryBacking::Anonymous,
+            max_stack_size: 0,
+            heap_guard_pages: 0,
+            guest_aslr: false,
+            guest_write_xor_execute: false,
+            zeroize_memory: false,
+            fault_injection: FaultInjection::default(),
+            guest_log_backpressure: GuestLogBackpressure::default(),
+            cpuid_overrides: [None; Self::MAX_CPUID_OVERRIDES],
+            msr_policy: MsrPolicy::default(),
+            guest_checkpoint_policy: GuestCheckpointPolicy::default(),
+            vcpu_affinity: None,
+            vcpu_priority: VcpuPriority::default(),
+            guest_cpu_time_budget: None,
+            guest_perf_counters: false,
+            guest_tsc_offset: None,
+            guest_tsc_khz: None,
+            hypervisor: HypervisorKind::Auto,
             #[cfg(gdb)]
             guest_debug_info,
             #[cfg(crashdump)]
             guest_core_dump,
+            #[cfg(crashdump)]
+            guest_crash_report: false,
         }
     }
 
@@ -187,6 +694,20 @@ impl SandboxConfiguration {
         self.guest_core_dump = enable;
     }
 
+    /// Toggles the crash report generation for a sandbox.
+    ///
+    /// When enabled, a crash of the guest creates a `hl_crash_<timestamp>`
+    /// directory next to where core dumps are placed, holding everything
+    /// needed to diagnose the crash: a `report.json` describing the crash
+    /// and the sandbox configuration, the registers, the symbolized guest
+    /// stack, a core dump and the last debug events of the sandbox.
+    /// This is only used when the `crashdump` feature is enabled
+    #[cfg(crashdump)]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_crash_report(&mut self, enable: bool) {
+        self.guest_crash_report = enable;
+    }
+
     /// Sets the configuration for the guest debug
     #[cfg(gdb)]
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
@@ -215,12 +736,413 @@ impl SandboxConfiguration {
         self.scratch_size = scratch_size;
     }
 
+    /// Sets whether the guest page tables should use 2MiB pages where
+    /// possible. Read-only guest memory (e.g. the guest code) whose
+    /// guest address is 2MiB aligned is then mapped with large pages;
+    /// everything else, including all writable memory, keeps using
+    /// 4KiB pages.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_large_pages(&mut self, enable: bool) {
+        self.guest_large_pages = enable;
+    }
+
+    #[cfg_attr(not(feature = "init-paging"), allow(dead_code))]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_large_pages(&self) -> bool {
+        self.guest_large_pages
+    }
+
+    /// Sets whether the memory of the sandbox is backed by 2MiB
+    /// transparent huge pages on the host, placed so that the hypervisor
+    /// can map it into the guest with large pages. This cuts the TLB
+    /// misses of guests that touch a lot of memory, most of all with
+    /// [`set_guest_large_pages`](Self::set_guest_large_pages), at the
+    /// cost of restoring snapshots by copying them. Only supported on
+    /// Linux, where it depends on transparent huge pages being enabled
+    /// in `madvise` or `always` mode.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_host_huge_pages(&mut self, enable: bool) {
+        self.host_huge_pages = enable;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_host_huge_pages(&self) -> bool {
+        self.host_huge_pages
+    }
+
+    /// Sets what backs the memory of the sandbox on the host. With
+    /// [`MemoryBacking::Memfd`], the descriptors of the memfds are
+    /// available from [`MultiUseSandbox::memory_fd`] and
+    /// [`MultiUseSandbox::scratch_memory_fd`], snapshots are restored by
+    /// copying them, and creating the sandbox fails on other platforms
+    /// than Linux. Huge pages, see
+    /// [`set_host_huge_pages`](Self::set_host_huge_pages), take
+    /// precedence over a memfd.
+    ///
+    /// [`MultiUseSandbox::memory_fd`]: crate::MultiUseSandbox::memory_fd
+    /// [`MultiUseSandbox::scratch_memory_fd`]: crate::MultiUseSandbox::scratch_memory_fd
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_memory_backing(&mut self, backing: MemoryBacking) {
+        self.memory_backing = backing;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_memory_backing(&self) -> MemoryBacking {
+        self.memory_backing
+    }
+
+    /// Sets the size the main stack of the guest is limited to, rounded
+    /// up to a page. The pages below the limit are guard pages: a guest
+    /// touching them is stopped and the call fails with
+    /// [`HyperlightError::GuestStackOverflow`](crate::HyperlightError::GuestStackOverflow)
+    /// instead of the stack growing further. 0, the default, lets the
+    /// stack grow until the end of the stack region.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_max_stack_size(&mut self, size: u64) {
+        self.max_stack_size = size.next_multiple_of(PAGE_SIZE_USIZE as u64);
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_max_stack_size(&self) -> u64 {
+        self.max_stack_size
+    }
+
+    /// Sets the number of pages left unmapped on each side of the guest
+    /// heap, so that an access running off either end of the heap
+    /// faults instead of reaching the neighbouring memory. The guard
+    /// pages are taken out of the heap size. 0, the default, leaves no
+    /// guard pages.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_heap_guard_pages(&mut self, pages: u64) {
+        self.heap_guard_pages = pages;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_heap_guard_pages(&self) -> u64 {
+        self.heap_guard_pages
+    }
+
+    /// Sets whether the guest binary, its heap and its main stack are
+    /// placed at random guest virtual addresses, chosen again for every
+    /// sandbox created from a guest binary. This makes memory
+    /// corruption bugs in the guest harder to exploit. The guest
+    /// physical layout is unchanged, and the sandboxes restored from a
+    /// snapshot or cloned from another sandbox keep its addresses.
+    /// Requires the guest binary to be position independent.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_aslr(&mut self, enable: bool) {
+        self.guest_aslr = enable;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_aslr(&self) -> bool {
+        self.guest_aslr
+    }
+
+    /// Sets whether no page of the guest may be both writable and
+    /// executable. The segments of the guest binary are then mapped with
+    /// the permissions of their ELF program headers rather than all
+    /// read-write-execute, creating a sandbox from a binary with a
+    /// writable and executable segment fails, and
+    /// [`MultiUseSandbox::set_region_permissions`] and the guest
+    /// `paging::protect` refuse to make pages both writable and
+    /// executable. Guests that generate code write it to read-write pages
+    /// which they then make read-execute.
+    ///
+    /// [`MultiUseSandbox::set_region_permissions`]: crate::MultiUseSandbox::set_region_permissions
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_write_xor_execute(&mut self, enable: bool) {
+        self.guest_write_xor_execute = enable;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_write_xor_execute(&self) -> bool {
+        self.guest_write_xor_execute
+    }
+
+    /// Sets whether the memory of the sandbox is explicitly overwritten
+    /// with zeroes when the sandbox is dropped, and when restoring a
+    /// snapshot discards the pages the guest wrote to, rather than only
+    /// being given back to the OS. This keeps the data a guest processed
+    /// for one request out of the freed host memory, at the cost of
+    /// slower drops and restores. The snapshots taken of the sandbox
+    /// are copies of its memory, which this does not cover.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_zeroize_memory(&mut self, enable: bool) {
+        self.zeroize_memory = enable;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_zeroize_memory(&self) -> bool {
+        self.zeroize_memory
+    }
+
+    /// Sets the failures to inject into the guest allocator and the
+    /// host function calls made by the guest. Allocation failures
+    /// require a guest built with `hyperlight_guest_bin`.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_fault_injection(&mut self, fault_injection: FaultInjection) {
+        self.fault_injection = fault_injection;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_alloc_faults(&self) -> GuestAllocFaults {
+        GuestAllocFaults {
+            fail_every: self.fault_injection.guest_alloc_fail_every,
+            byte_budget: self.fault_injection.guest_alloc_byte_budget,
+        }
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_host_call_fail_every(&self) -> u64 {
+        self.fault_injection.host_call_fail_every
+    }
+
+    /// Sets how guest log records are delivered to the host log sinks
+    /// when these cannot keep up with the guest, see
+    /// [`GuestLogBackpressure`]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_log_backpressure(&mut self, backpressure: GuestLogBackpressure) {
+        self.guest_log_backpressure = backpressure;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_log_backpressure(&self) -> GuestLogBackpressure {
+        self.guest_log_backpressure
+    }
+
+    /// Changes a CPUID leaf presented to the guest, see [`CpuidOverride`].
+    /// Overrides of the same leaf and subleaf are merged, the last one to
+    /// change a bit wins.
+    ///
+    /// Returns an error if more than [`Self::MAX_CPUID_OVERRIDES`] leaves
+    /// would be overridden. On WHP, only whole leaves can be overridden, so
+    /// creating the sandbox fails if the override is for a subleaf other
+    /// than 0.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn add_cpuid_override(&mut self, cpuid_override: CpuidOverride) -> crate::Result<()> {
+        let same_leaf = |o: &CpuidOverride| {
+            o.function == cpuid_override.function && o.index == cpuid_override.index
+        };
+        if let Some(existing) = self
+            .cpuid_overrides
+            .iter_mut()
+            .flatten()
+            .find(|o| same_leaf(o))
+        {
+            for r in [
+                CpuidRegister::Eax,
+                CpuidRegister::Ebx,
+                CpuidRegister::Ecx,
+                CpuidRegister::Edx,
+            ] {
+                *existing = existing.set_masked(
+                    r,
+                    cpuid_override.mask[r as usize],
+                    cpuid_override.value[r as usize],
+                );
+            }
+            return Ok(());
+        }
+        match self.cpuid_overrides.iter_mut().find(|o| o.is_none()) {
+            Some(slot) => {
+                *slot = Some(cpuid_override);
+                Ok(())
+            }
+            None => Err(crate::new_error!(
+                "Cannot override more than {} CPUID leaves",
+                Self::MAX_CPUID_OVERRIDES
+            )),
+        }
+    }
+
+    /// Removes all the CPUID overrides
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn clear_cpuid_overrides(&mut self) {
+        self.cpuid_overrides = [None; Self::MAX_CPUID_OVERRIDES];
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_cpuid_overrides(&self) -> Vec<CpuidOverride> {
+        let mut overrides: Vec<_> = self.cpuid_overrides.iter().flatten().copied().collect();
+        // Report the frequency the guest TSC is scaled to
+        if let Some(khz) = self.guest_tsc_khz {
+            overrides.push(CpuidOverride::tsc_frequency(khz));
+        }
+        overrides
+    }
+
+    /// Sets what happens when the guest reads or writes an MSR that the
+    /// hypervisor does not virtualize, see [`MsrPolicy`]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_msr_policy(&mut self, policy: MsrPolicy) {
+        self.msr_policy = policy;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_msr_policy(&self) -> MsrPolicy {
+        self.msr_policy
+    }
+
+    /// Sets when the host takes a snapshot at the checkpoints the guest
+    /// declares, see [`GuestCheckpointPolicy`]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_checkpoint_policy(&mut self, policy: GuestCheckpointPolicy) {
+        self.guest_checkpoint_policy = policy;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_checkpoint_policy(&self) -> GuestCheckpointPolicy {
+        self.guest_checkpoint_policy
+    }
+
+    /// Pins the thread running the vCPU to the host CPUs `cpus` while it
+    /// runs the guest, to keep the guest off the CPUs the host needs for
+    /// other work, or `None` to let it run anywhere. This is the default.
+    ///
+    /// The vCPU runs on the thread calling into the guest, whose affinity
+    /// is restored when the call returns. On Windows, only the CPUs of
+    /// the processor group of the thread, the first 64, can be used.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_vcpu_affinity(&mut self, cpus: Option<CpuSet>) {
+        self.vcpu_affinity = cpus.filter(|cpus| !cpus.is_empty());
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_vcpu_affinity(&self) -> Option<CpuSet> {
+        self.vcpu_affinity
+    }
+
+    /// Sets the scheduling priority of the thread running the vCPU while
+    /// it runs the guest, see [`VcpuPriority`].
+    ///
+    /// The vCPU runs on the thread calling into the guest, whose priority
+    /// is restored when the call returns. Raising a priority needs
+    /// privileges, such as `CAP_SYS_NICE` on Linux, so without them the
+    /// priority of a thread can be lowered for the guest but not restored
+    /// afterwards, in which case a warning is logged.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_vcpu_priority(&mut self, priority: VcpuPriority) {
+        self.vcpu_priority = priority;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_vcpu_priority(&self) -> VcpuPriority {
+        self.vcpu_priority
+    }
+
+    /// Sets the CPU time each guest function call can use, or `None`,
+    /// the default, to let calls run for as long as they need.
+    ///
+    /// The budget is enforced by a timer on the CPU time clock of the
+    /// thread calling into the guest, which interrupts the vCPU once the
+    /// budget is used up, so time the thread is not scheduled does not
+    /// count, but time spent in host functions called by the guest does.
+    /// The call then fails with [`HyperlightError::ExecutionBudgetExceeded`]
+    /// and the sandbox is poisoned, like when it is cancelled with
+    /// [`InterruptHandle::kill`](crate::hypervisor::InterruptHandle::kill),
+    /// whose signal the timer also uses to interrupt the vCPU, see
+    /// [`SandboxConfiguration::set_interrupt_vcpu_sigrtmin_offset`].
+    ///
+    /// [`HyperlightError::ExecutionBudgetExceeded`]: crate::HyperlightError::ExecutionBudgetExceeded
+    #[cfg(target_os = "linux")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_cpu_time_budget(&mut self, budget: Option<Duration>) {
+        self.guest_cpu_time_budget = budget.filter(|budget| !budget.is_zero());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_cpu_time_budget(&self) -> Option<Duration> {
+        self.guest_cpu_time_budget
+    }
+
+    /// Sets whether the instructions retired and the cycles of the guest
+    /// are counted during each guest function call, with the hardware
+    /// performance counters of the host. Disabled by default. The counts
+    /// of the last call are returned by
+    /// [`MultiUseSandbox::last_call_metrics`](crate::MultiUseSandbox::last_call_metrics).
+    ///
+    /// Counting is only supported on KVM, on other hypervisors there are
+    /// no counts. It needs access to the performance counters of the
+    /// kernel, usually a `kernel.perf_event_paranoid` setting of 1 or
+    /// less or the `CAP_PERFMON` capability, without which guest function
+    /// calls fail.
+    #[cfg(target_os = "linux")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_perf_counters(&mut self, enabled: bool) {
+        self.guest_perf_counters = enabled;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_perf_counters(&self) -> bool {
+        self.guest_perf_counters
+    }
+
+    /// Sets the offset, in cycles, of the TSC the guest reads from the TSC
+    /// of the host, or `None`, the default, to keep the TSC the hypervisor
+    /// gives the guest. Guests that are restored or moved across hosts can
+    /// be given the same TSC base this way.
+    ///
+    /// When the guest TSC is scaled with
+    /// [`SandboxConfiguration::set_guest_tsc_frequency`], the offset is
+    /// added to the scaled TSC. On mshv and WHP, the offset is applied
+    /// once when the sandbox is created.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_tsc_offset(&mut self, offset: Option<i64>) {
+        self.guest_tsc_offset = offset;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_tsc_offset(&self) -> Option<i64> {
+        self.guest_tsc_offset
+    }
+
+    /// Sets the frequency, in kHz, the guest TSC runs at, or `None`, the
+    /// default, to run it at the frequency of the host TSC, so that guest
+    /// timings do not depend on the host. The frequency is reported to the
+    /// guest in CPUID leaf 0x15, from which the guest tracing converts its
+    /// timestamps.
+    ///
+    /// Scaling is only supported on KVM, with hardware TSC scaling. On
+    /// other hypervisors creating the sandbox fails.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_guest_tsc_frequency(&mut self, khz: Option<u32>) {
+        self.guest_tsc_khz = khz.filter(|khz| *khz != 0);
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_tsc_frequency(&self) -> Option<u32> {
+        self.guest_tsc_khz
+    }
+
+    /// Sets the hypervisor the sandbox runs on. By default,
+    /// [`HypervisorKind::Auto`], it runs on the hypervisor found on the
+    /// host. Otherwise, evolving the sandbox fails if the requested
+    /// hypervisor is not one of the [`available_backends`](crate::available_backends).
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_hypervisor(&mut self, hypervisor: HypervisorKind) {
+        self.hypervisor = hypervisor;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_hypervisor(&self) -> HypervisorKind {
+        self.hypervisor
+    }
+
     #[cfg(crashdump)]
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_core_dump(&self) -> bool {
         self.guest_core_dump
     }
 
+    #[cfg(crashdump)]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_crash_report(&self) -> bool {
+        self.guest_crash_report
+    }
+
     #[cfg(gdb)]
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_debug_info(&self) -> Option<DebugInfo> {
@@ -232,6 +1154,43 @@ impl SandboxConfiguration {
         (self.heap_size_override > 0).then_some(self.heap_size_override)
     }
 
+    /// Raises the heap and scratch sizes to what the guest binary asks
+    /// for in its embedded configuration, and checks that this host has
+    /// the capabilities the guest requires
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn apply_guest_binary_config(
+        &mut self,
+        guest: &GuestBinaryConfig,
+    ) -> crate::Result<()> {
+        if guest.abi_version() != GUEST_CONFIG_ABI_VERSION {
+            return Err(crate::HyperlightError::IncompatibleGuestBinary(format!(
+                "embedded configuration version {} is not the supported version {}",
+                guest.abi_version(),
+                GUEST_CONFIG_ABI_VERSION
+            )));
+        }
+        let missing = guest
+            .required_capabilities()
+            .difference(host_capabilities());
+        if !missing.is_empty() {
+            return Err(crate::HyperlightError::IncompatibleGuestBinary(format!(
+                "the guest requires host capabilities {:#x} that are not enabled",
+                missing.bits()
+            )));
+        }
+        if guest.heap_size() > self.get_heap_size() {
+            self.heap_size_override = guest.heap_size();
+        }
+        if guest.stack_size() > 0 {
+            let min_scratch_size = hyperlight_common::layout::min_scratch_size(
+                self.input_data_size,
+                self.output_data_size,
+            ) + usize::try_from(guest.stack_size())?;
+            self.scratch_size = max(self.scratch_size, min_scratch_size);
+        }
+        Ok(())
+    }
+
     /// If self.heap_size_override is non-zero, return it. Otherwise,
     /// return exe_info.heap_reserve()
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
@@ -241,6 +1200,30 @@ impl SandboxConfiguration {
     }
 }
 
+/// The capabilities of this host that guest binaries can require, given
+/// by the features it was built with
+pub(crate) fn host_capabilities() -> GuestCapabilities {
+    #[allow(unused_mut)]
+    let mut capabilities = GuestCapabilities::NONE;
+    #[cfg(feature = "trace_guest")]
+    {
+        capabilities = capabilities.union(GuestCapabilities::TRACE_GUEST);
+    }
+    #[cfg(feature = "mem_profile")]
+    {
+        capabilities = capabilities.union(GuestCapabilities::MEM_PROFILE);
+    }
+    #[cfg(gdb)]
+    {
+        capabilities = capabilities.union(GuestCapabilities::GDB);
+    }
+    #[cfg(crashdump)]
+    {
+        capabilities = capabilities.union(GuestCapabilities::CRASHDUMP);
+    }
+    capabilities
+}
+
 impl Default for SandboxConfiguration {
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     fn default() -> Self {
@@ -261,7 +1244,10 @@ impl Default for SandboxConfiguration {
 
 #[cfg(test)]
 mod tests {
-    use super::SandboxConfiguration;
+    use hyperlight_common::guest_config::{GuestBinaryConfig, GuestCapabilities};
+
+    use super::{CpuSet, CpuidOverride, CpuidRegister, SandboxConfiguration, host_capabilities};
+    use crate::HyperlightError;
 
     #[test]
     fn overrides() {
@@ -320,6 +1306,109 @@ mod tests {
         assert_eq!(SandboxConfiguration::MIN_OUTPUT_SIZE, cfg.output_data_size);
     }
 
+    #[test]
+    fn guest_tsc_frequency() {
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_guest_tsc_frequency(Some(2_000_000));
+        let overrides = cfg.get_cpuid_overrides();
+        assert_eq!(overrides.len(), 1);
+        assert!(overrides[0].matches(0x15, 0));
+        // ECX * EBX / EAX is the frequency in Hz
+        assert_eq!(overrides[0].apply([0; 4]), [1, 2_000_000, 1000, 0]);
+
+        cfg.set_guest_tsc_frequency(Some(0));
+        assert_eq!(cfg.get_guest_tsc_frequency(), None);
+        assert!(cfg.get_cpuid_overrides().is_empty());
+    }
+
+    #[test]
+    fn cpuid_overrides() {
+        let o = CpuidOverride::new(1)
+            .set_bits(CpuidRegister::Ecx, 0b1100)
+            .clear_bits(CpuidRegister::Ecx, 0b0100);
+        assert!(o.matches(1, 0) && o.matches(1, 3) && !o.matches(2, 0));
+        assert_eq!(o.apply([1, 2, 0b0011, 4]), [1, 2, 0b1011, 4]);
+
+        let vendor = CpuidOverride::vendor(b"GenuineIntel");
+        assert_eq!(
+            vendor.apply([0xd, 0, 0, 0]),
+            [0xd, 0x756e_6547, 0x6c65_746e, 0x4965_6e69]
+        );
+
+        let avx512 = CpuidOverride::without_avx512();
+        assert!(avx512.matches(7, 0) && !avx512.matches(7, 1));
+        assert_eq!(avx512.apply([0; 4])[1] & (1 << 16), 0);
+        assert_eq!(avx512.apply([u32::MAX; 4])[1] & (1 << 5), 1 << 5);
+
+        // Overrides of the same subleaf are merged
+        let mut cfg = SandboxConfiguration::default();
+        cfg.add_cpuid_override(CpuidOverride::new(1).set(CpuidRegister::Eax, 1))
+            .unwrap();
+        cfg.add_cpuid_override(CpuidOverride::new(1).clear_bits(CpuidRegister::Ebx, 1))
+            .unwrap();
+        cfg.add_cpuid_override(CpuidOverride::new(1).with_index(0))
+            .unwrap();
+        let overrides = cfg.get_cpuid_overrides();
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides[0].apply([0, 3, 0, 0]), [1, 2, 0, 0]);
+
+        cfg.clear_cpuid_overrides();
+        for function in 0..SandboxConfiguration::MAX_CPUID_OVERRIDES as u32 {
+            cfg.add_cpuid_override(CpuidOverride::new(function))
+                .unwrap();
+        }
+        assert!(cfg.add_cpuid_override(CpuidOverride::new(0x100)).is_err());
+    }
+
+    #[test]
+    fn vcpu_affinity() {
+        let cpus = CpuSet::from_cpus([0, 63, 64, CpuSet::MAX_CPUS - 1]).unwrap();
+        assert!(cpus.contains(63) && cpus.contains(64) && !cpus.contains(1));
+        assert_eq!(
+            cpus.iter().collect::<Vec<_>>(),
+            [0, 63, 64, CpuSet::MAX_CPUS - 1]
+        );
+        assert!(CpuSet::from_cpus([CpuSet::MAX_CPUS]).is_err());
+
+        let mut cfg = SandboxConfiguration::default();
+        assert_eq!(cfg.get_vcpu_affinity(), None);
+        cfg.set_vcpu_affinity(Some(cpus));
+        assert_eq!(cfg.get_vcpu_affinity(), Some(cpus));
+        // Pinning to no CPU leaves the vCPU unpinned
+        cfg.set_vcpu_affinity(Some(CpuSet::new()));
+        assert_eq!(cfg.get_vcpu_affinity(), None);
+    }
+
+    #[test]
+    fn guest_binary_config() {
+        let mut cfg = SandboxConfiguration::default();
+        let guest = GuestBinaryConfig::new()
+            .with_heap_size(SandboxConfiguration::DEFAULT_HEAP_SIZE * 4)
+            .with_stack_size(0x10_0000);
+        cfg.apply_guest_binary_config(&guest).unwrap();
+        assert_eq!(
+            cfg.get_heap_size(),
+            SandboxConfiguration::DEFAULT_HEAP_SIZE * 4
+        );
+        assert!(cfg.get_scratch_size() > 0x10_0000);
+
+        // The sizes asked for by the guest are minimums
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_heap_size(SandboxConfiguration::DEFAULT_HEAP_SIZE * 8);
+        cfg.apply_guest_binary_config(&guest).unwrap();
+        assert_eq!(
+            cfg.get_heap_size(),
+            SandboxConfiguration::DEFAULT_HEAP_SIZE * 8
+        );
+
+        let missing = GuestCapabilities::from_bits(u64::MAX).difference(host_capabilities());
+        let guest = GuestBinaryConfig::new().requires(missing);
+        assert!(matches!(
+            cfg.apply_guest_binary_config(&guest),
+            Err(HyperlightError::IncompatibleGuestBinary(_))
+        ));
+    }
+
     mod proptests {
         use proptest::prelude::*;
 
@@ -354,7 +1443,7 @@ mod tests {
             #[cfg(gdb)]
             fn guest_debug_info(port in 9000..=u16::MAX) {
                 let mut cfg = SandboxConfiguration::default();
-                let debug_info = DebugInfo { port };
+                let debug_info = DebugInfo::new(port);
                 cfg.set_guest_debug_info(debug_info);
                 prop_assert_eq!(debug_info, *cfg.get_guest_debug_info().as_ref().unwrap());
             }
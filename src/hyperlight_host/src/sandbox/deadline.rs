@@ -0,0 +1,160 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::cancellation::CancellationToken;
+use crate::{Result, new_error};
+
+/// The timer cancelling the calls made with
+/// [`MultiUseSandbox::call_with_deadline`](crate::MultiUseSandbox::call_with_deadline)
+/// once their deadline passes. A single thread, started on the first
+/// call, serves the calls of every sandbox of the process.
+static TIMER: DeadlineTimer = DeadlineTimer {
+    state: Mutex::new(TimerState {
+        deadlines: BTreeMap::new(),
+        next_id: 0,
+        running: false,
+    }),
+    changed: Condvar::new(),
+};
+
+struct DeadlineTimer {
+    state: Mutex<TimerState>,
+    /// Notified when a deadline earlier than the ones the thread waits
+    /// for may have been added
+    changed: Condvar,
+}
+
+struct TimerState {
+    /// The tokens to cancel, by deadline, the id telling apart calls
+    /// with the same deadline
+    deadlines: BTreeMap<(Instant, u64), CancellationToken>,
+    next_id: u64,
+    /// Whether the thread of the timer was started
+    running: bool,
+}
+
+/// Keeps the deadline of a call until it is dropped, once the call
+/// returned
+pub(crate) struct Deadline {
+    key: Option<(Instant, u64)>,
+}
+
+impl Deadline {
+    /// Cancels `token` once `timeout` has passed, unless the returned
+    /// deadline was dropped before then
+    pub(crate) fn start(timeout: Duration, token: CancellationToken) -> Result<Self> {
+        // A deadline too far away to represent is never reached
+        let Some(at) = Instant::now().checked_add(timeout) else {
+            return Ok(Self { key: None });
+        };
+        let mut state = TIMER.lock();
+        if !state.running {
+            std::thread::Builder::new()
+                .name("hl-deadline".to_string())
+                .spawn(|| TIMER.run())
+                .map_err(|e| new_error!("Failed to spawn the deadline thread: {}", e))?;
+            state.running = true;
+        }
+        let key = (at, state.next_id);
+        state.next_id += 1;
+        state.deadlines.insert(key, token);
+        drop(state);
+        TIMER.changed.notify_one();
+        Ok(Self { key: Some(key) })
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            TIMER.lock().deadlines.remove(&key);
+        }
+    }
+}
+
+impl DeadlineTimer {
+    fn lock(&self) -> MutexGuard<'_, TimerState> {
+        // The state is only changed by code that cannot panic
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn run(&self) {
+        let mut state = self.lock();
+        loop {
+            let now = Instant::now();
+            let mut expired = Vec::new();
+            while let Some(entry) = state.deadlines.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                expired.push(entry.remove());
+            }
+            if !expired.is_empty() {
+                // Interrupting the calls is left out of the lock, so that
+                // calls can start and return meanwhile
+                drop(state);
+                expired.iter().for_each(CancellationToken::cancel);
+                state = self.lock();
+                continue;
+            }
+            state = match state.deadlines.keys().next() {
+                Some(&(at, _)) => {
+                    self.changed
+                        .wait_timeout(state, at - now)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0
+                }
+                None => self
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Deadline;
+    use crate::sandbox::cancellation::CancellationToken;
+
+    #[test]
+    fn deadlines_cancel_their_tokens() {
+        let late = CancellationToken::new();
+        let _late_deadline = Deadline::start(Duration::from_secs(3600), late.clone()).unwrap();
+        let dropped = CancellationToken::new();
+        drop(Deadline::start(Duration::from_millis(10), dropped.clone()).unwrap());
+        let soon = CancellationToken::new();
+        let _soon_deadline = Deadline::start(Duration::from_millis(10), soon.clone()).unwrap();
+
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(soon.is_cancelled());
+        assert!(!dropped.is_cancelled());
+        assert!(!late.is_cancelled());
+
+        let never = CancellationToken::new();
+        let _never_deadline = Deadline::start(Duration::MAX, never.clone()).unwrap();
+        assert!(!never.is_cancelled());
+    }
+}
@@ -0,0 +1,203 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+#[cfg(crashdump)]
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+
+/// An event raised while the guest runs, delivered to every
+/// [`DebugEvents`] subscription of the sandbox.
+///
+/// Events are reported independently of whether a debugger is attached,
+/// which allows embedders to build their own tooling on top of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DebugEvent {
+    /// The guest printed some text, either through the `HostPrint` host
+    /// function or through the debug print port (in which case the
+    /// text is reported one character at a time)
+    GuestPrint(String),
+    /// The guest aborted, which includes exceptions that the guest could
+    /// not handle
+    Exception {
+        /// The error code provided by the guest
+        code: u8,
+        /// The message provided by the guest
+        message: String,
+    },
+    /// The vCPU stopped at a software or hardware breakpoint
+    #[cfg(gdb)]
+    BreakpointHit {
+        /// The instruction pointer at the breakpoint
+        rip: u64,
+    },
+    /// The vCPU stopped after accessing memory watched by a hardware
+    /// watchpoint
+    #[cfg(gdb)]
+    WatchpointHit {
+        /// The instruction pointer after the access
+        rip: u64,
+        /// The watched address
+        addr: u64,
+    },
+    /// The vCPU completed a single step
+    #[cfg(gdb)]
+    StepComplete {
+        /// The instruction pointer after the step
+        rip: u64,
+    },
+}
+
+/// A subscription to the [`DebugEvent`]s of a sandbox.
+///
+/// Events are buffered until they are received, and the subscription
+/// ends when it is dropped. Once the sandbox is dropped, the remaining
+/// events can still be received.
+#[derive(Debug)]
+pub struct DebugEvents {
+    rx: Receiver<DebugEvent>,
+}
+
+impl DebugEvents {
+    /// Returns the next event if there is one, without blocking
+    pub fn try_recv(&self) -> Option<DebugEvent> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Waits for the next event for at most `timeout`
+    ///
+    /// Returns `None` if no event was raised in time, or if the sandbox
+    /// was dropped and all its events were received.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<DebugEvent> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    /// Returns an iterator over the events raised so far, without blocking
+    pub fn try_iter(&self) -> impl Iterator<Item = DebugEvent> + '_ {
+        self.rx.try_iter()
+    }
+}
+
+/// How many of the last events are kept to be included in crash reports
+#[cfg(crashdump)]
+const RECENT_EVENTS_CAPACITY: usize = 64;
+
+/// The sending side of the [`DebugEvents`] subscriptions of a sandbox,
+/// shared between the sandbox and its VM
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DebugEventSink {
+    subscribers: Arc<Mutex<Vec<Sender<DebugEvent>>>>,
+    /// The last events emitted, oldest first
+    #[cfg(crashdump)]
+    recent: Arc<Mutex<VecDeque<DebugEvent>>>,
+}
+
+impl DebugEventSink {
+    /// Creates a new subscription that receives all the events emitted
+    /// from now on
+    pub(crate) fn subscribe(&self) -> DebugEvents {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        // A poisoned lock still holds a valid list of subscribers
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tx);
+        DebugEvents { rx }
+    }
+
+    /// Delivers `event` to every subscription, dropping the ones that
+    /// ended
+    pub(crate) fn emit(&self, event: DebugEvent) {
+        #[cfg(crashdump)]
+        {
+            let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+            if recent.len() == RECENT_EVENTS_CAPACITY {
+                recent.pop_front();
+            }
+            recent.push_back(event.clone());
+        }
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Returns the last events emitted, oldest first, whether or not
+    /// they were received by a subscription
+    #[cfg(crashdump)]
+    pub(crate) fn recent(&self) -> Vec<DebugEvent> {
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_delivered_to_every_subscription() {
+        let sink = DebugEventSink::default();
+        sink.emit(DebugEvent::GuestPrint("unobserved".to_string()));
+
+        let first = sink.subscribe();
+        let second = sink.subscribe();
+        sink.emit(DebugEvent::GuestPrint("hello".to_string()));
+
+        for events in [&first, &second] {
+            assert_eq!(
+                events.try_recv(),
+                Some(DebugEvent::GuestPrint("hello".to_string()))
+            );
+            assert_eq!(events.try_recv(), None);
+        }
+
+        // Dropped subscriptions are forgotten
+        drop(first);
+        sink.emit(DebugEvent::Exception {
+            code: 1,
+            message: "boom".to_string(),
+        });
+        assert_eq!(sink.subscribers.lock().unwrap().len(), 1);
+        assert_eq!(
+            second.try_iter().collect::<Vec<_>>(),
+            vec![DebugEvent::Exception {
+                code: 1,
+                message: "boom".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    #[cfg(crashdump)]
+    fn recent_events_are_bounded() {
+        let sink = DebugEventSink::default();
+        for i in 0..RECENT_EVENTS_CAPACITY + 2 {
+            sink.emit(DebugEvent::GuestPrint(i.to_string()));
+        }
+
+        let recent = sink.recent();
+        assert_eq!(recent.len(), RECENT_EVENTS_CAPACITY);
+        assert_eq!(recent[0], DebugEvent::GuestPrint("2".to_string()));
+        assert_eq!(
+            recent.last(),
+            Some(&DebugEvent::GuestPrint(
+                (RECENT_EVENTS_CAPACITY + 1).to_string()
+            ))
+        );
+    }
+}
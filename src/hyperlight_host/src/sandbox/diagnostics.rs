@@ -0,0 +1,87 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::time::Duration;
+
+pub use hyperlight_common::diagnostics::GuestBenchTimings;
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ReturnType};
+use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
+use hyperlight_common::func::{ParameterTuple, SupportedReturnType};
+
+use crate::Result;
+
+/// The outcome of [`MultiUseSandbox::run_diagnostics`](crate::MultiUseSandbox::run_diagnostics)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticsReport {
+    /// What the self-test of the guest checked
+    pub self_test: String,
+    /// The mean time of a call to a guest function that does nothing,
+    /// measured by the host, which is the cost of a round trip into the
+    /// guest and of marshalling the call and its result
+    pub guest_call: Duration,
+    /// The timings measured by the guest
+    pub guest_timings: GuestBenchTimings,
+}
+
+impl DiagnosticsReport {
+    /// The mean TSC cycles the guest spent allocating, filling and freeing
+    /// a set of buffers from 16 bytes to 64KiB
+    pub fn guest_alloc_cycles(&self) -> u64 {
+        self.guest_timings.alloc_cycles / u64::from(self.guest_timings.iterations.max(1))
+    }
+
+    /// The mean TSC cycles the guest spent calling a host function
+    pub fn host_call_cycles(&self) -> u64 {
+        self.guest_timings.host_call_cycles / u64::from(self.guest_timings.iterations.max(1))
+    }
+}
+
+/// A function registered by the guest, as listed by
+/// [`MultiUseSandbox::guest_functions`](crate::MultiUseSandbox::guest_functions)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestFunctionInfo {
+    /// The name the function is called with
+    pub name: String,
+    /// The types of the parameters of the function
+    pub parameter_types: Vec<ParameterType>,
+    /// The type of the value the function returns
+    pub return_type: ReturnType,
+}
+
+impl GuestFunctionInfo {
+    /// Whether the function can be called with the argument types `Args`
+    /// and the return type `Output`, as
+    /// [`MultiUseSandbox::call`](crate::MultiUseSandbox::call) would
+    pub fn matches<Args: ParameterTuple, Output: SupportedReturnType>(&self) -> bool {
+        self.parameter_types.as_slice() == Args::TYPE && self.return_type == Output::TYPE
+    }
+}
+
+impl From<HostFunctionDefinition> for GuestFunctionInfo {
+    fn from(definition: HostFunctionDefinition) -> Self {
+        Self {
+            name: definition.function_name,
+            parameter_types: definition.parameter_types.unwrap_or_default(),
+            return_type: definition.return_type,
+        }
+    }
+}
+
+/// The host function the guest self-test calls back, which returns what
+/// it is given
+pub(crate) fn host_echo(data: Vec<u8>) -> Result<Vec<u8>> {
+    Ok(data)
+}
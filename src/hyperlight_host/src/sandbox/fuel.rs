@@ -0,0 +1,110 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+/// What to do with a guest that used up its fuel, as decided by the
+/// handler set with
+/// [`MultiUseSandbox::on_fuel_exhausted`](crate::MultiUseSandbox::on_fuel_exhausted)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FuelDecision {
+    /// Resumes the guest with this much more fuel, in instructions
+    Refuel(u64),
+    /// Stops the guest function call, which fails with
+    /// [`HyperlightError::FuelExhausted`](crate::HyperlightError::FuelExhausted)
+    Abort,
+}
+
+/// Decides what to do with a guest that used up its fuel
+type FuelHandler = Box<dyn FnMut() -> FuelDecision + Send>;
+
+/// The fuel left to a sandbox, and what to do once it is used up
+#[derive(Default)]
+pub(crate) struct FuelTank {
+    /// The instructions the guest can still retire, or `None` if it is
+    /// not metered
+    remaining: Option<u64>,
+    on_exhausted: Option<FuelHandler>,
+}
+
+impl FuelTank {
+    pub(crate) fn remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+
+    pub(crate) fn set(&mut self, fuel: Option<u64>) {
+        self.remaining = fuel;
+    }
+
+    pub(crate) fn set_handler(&mut self, handler: FuelHandler) {
+        self.on_exhausted = Some(handler);
+    }
+
+    /// Takes the `consumed` instructions out of the fuel left
+    #[cfg_attr(not(kvm), allow(dead_code))]
+    pub(crate) fn consume(&mut self, consumed: u64) {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.saturating_sub(consumed);
+        }
+    }
+
+    /// Asks the handler what to do with the guest that used up its fuel,
+    /// returning whether it was refuelled. Without a handler, or with no
+    /// fuel given, the guest is stopped.
+    #[cfg_attr(not(kvm), allow(dead_code))]
+    pub(crate) fn refuel(&mut self) -> bool {
+        let Some(handler) = &mut self.on_exhausted else {
+            return false;
+        };
+        match handler() {
+            FuelDecision::Refuel(fuel) if fuel > 0 => {
+                self.remaining = Some(fuel);
+                true
+            }
+            FuelDecision::Refuel(_) | FuelDecision::Abort => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FuelDecision, FuelTank};
+
+    #[test]
+    fn tank_is_refuelled_by_its_handler() {
+        let mut tank = FuelTank::default();
+        tank.consume(10);
+        assert_eq!(tank.remaining(), None);
+
+        tank.set(Some(100));
+        tank.consume(30);
+        assert_eq!(tank.remaining(), Some(70));
+        tank.consume(100);
+        assert_eq!(tank.remaining(), Some(0));
+        assert!(!tank.refuel());
+
+        let mut refuels = 0;
+        tank.set_handler(Box::new(move || {
+            refuels += 1;
+            if refuels == 1 {
+                FuelDecision::Refuel(50)
+            } else {
+                FuelDecision::Abort
+            }
+        }));
+        assert!(tank.refuel());
+        assert_eq!(tank.remaining(), Some(50));
+        assert!(!tank.refuel());
+    }
+}
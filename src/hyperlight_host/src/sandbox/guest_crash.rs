@@ -0,0 +1,397 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::{Display, Formatter};
+
+use hyperlight_common::outb::{ABORT_PANIC_BACKTRACE, ABORT_PANIC_LOCATION, Exception};
+
+use crate::hypervisor::regs::CommonRegisters;
+
+/// The most bytes of the guest stack kept in a [`GuestCrashReport`]
+pub const MAX_CRASH_STACK_BYTES: usize = 4096;
+
+/// The most frames walked when unwinding the guest stack, which bounds
+/// the walk when the frame pointers are corrupted
+const MAX_CRASH_FRAMES: usize = 64;
+
+/// What the guest was doing when it aborted, or when an exception it did
+/// not handle made it abort, returned by
+/// [`MultiUseSandbox::crash_report`](crate::MultiUseSandbox::crash_report)
+/// after a call fails with
+/// [`HyperlightError::GuestAborted`](crate::HyperlightError::GuestAborted).
+///
+/// The registers, stack and frames are captured when the guest aborts,
+/// so they describe the abort path of the guest, on top of the frames
+/// that led to it. They are left empty if they could not be read.
+#[derive(Clone, Debug, Default)]
+pub struct GuestCrashReport {
+    /// The error code the guest aborted with
+    pub code: u8,
+    /// The message the guest aborted with
+    pub message: String,
+    /// The exception that made the guest abort, if the guest reported one
+    pub exception: Option<Exception>,
+    /// Where and why the guest panicked, if it aborted because of a Rust
+    /// panic
+    pub panic: Option<GuestPanic>,
+    /// The general purpose registers of the vCPU
+    pub registers: Option<GuestCrashRegisters>,
+    /// The guest memory from the stack pointer up, at most
+    /// [`MAX_CRASH_STACK_BYTES`] bytes of it
+    pub stack: Vec<u8>,
+    /// The instruction pointer followed by the return addresses of the
+    /// frames on the stack, innermost first, walked through the frame
+    /// pointers of the guest
+    pub frames: Vec<u64>,
+}
+
+impl GuestCrashReport {
+    pub(crate) fn new(
+        code: u8,
+        message: String,
+        exception: Option<Exception>,
+        panic: Option<GuestPanic>,
+    ) -> Self {
+        Self {
+            code,
+            message,
+            exception,
+            panic,
+            ..Default::default()
+        }
+    }
+}
+
+impl Display for GuestCrashReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Guest aborted with code {}: {}", self.code, self.message)?;
+        if let Some(exception) = self.exception {
+            writeln!(f, "Exception: {:?}", exception)?;
+        }
+        if let Some(regs) = &self.registers {
+            writeln!(
+                f,
+                "rip={:#x} rsp={:#x} rbp={:#x} rflags={:#x}",
+                regs.rip, regs.rsp, regs.rbp, regs.rflags
+            )?;
+        }
+        for (i, frame) in self.frames.iter().enumerate() {
+            writeln!(f, "#{} {:#x}", i, frame)?;
+        }
+        if let Some(panic) = self.panic.as_ref().filter(|p| !p.backtrace.is_empty()) {
+            writeln!(f, "Panic backtrace:")?;
+            for (i, frame) in panic.backtrace.iter().enumerate() {
+                writeln!(f, "#{} {:#x}", i, frame)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A panic of a Rust guest, sent by the panic handler of
+/// `hyperlight_guest_bin` when it aborts, in a [`GuestCrashReport`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GuestPanic {
+    /// The message the guest panicked with
+    pub message: String,
+    /// Where in the source of the guest it panicked
+    pub location: Option<GuestPanicLocation>,
+    /// The return addresses of the frames of the panic, innermost first.
+    /// Only guests built with frame pointers send them.
+    pub backtrace: Vec<u64>,
+}
+
+/// The place in the source of the guest a [`GuestPanic`] comes from
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GuestPanicLocation {
+    /// The source file
+    pub file: String,
+    /// The line in the file, starting at 1
+    pub line: u32,
+    /// The column in the line, starting at 1
+    pub column: u32,
+}
+
+impl GuestPanic {
+    /// Parses the text of an abort message, which holds a panic if the
+    /// panic handler of the guest sent a location or a backtrace after
+    /// the message
+    pub(crate) fn parse(text: &[u8]) -> Option<Self> {
+        let end = text
+            .iter()
+            .position(|&b| b == ABORT_PANIC_LOCATION || b == ABORT_PANIC_BACKTRACE)?;
+        let (message, mut rest) = text.split_at(end);
+        let mut panic = Self {
+            message: String::from_utf8_lossy(message).into(),
+            ..Default::default()
+        };
+
+        if let Some(location) = rest.strip_prefix(&[ABORT_PANIC_LOCATION]) {
+            let end = location
+                .iter()
+                .position(|&b| b == ABORT_PANIC_BACKTRACE)
+                .unwrap_or(location.len());
+            panic.location = GuestPanicLocation::parse(&String::from_utf8_lossy(&location[..end]));
+            rest = &location[end..];
+        }
+        if let Some(backtrace) = rest.strip_prefix(&[ABORT_PANIC_BACKTRACE]) {
+            panic.backtrace = String::from_utf8_lossy(backtrace)
+                .split_whitespace()
+                .filter_map(|frame| u64::from_str_radix(frame, 16).ok())
+                .collect();
+        }
+        Some(panic)
+    }
+}
+
+impl Display for GuestPanic {
+    /// Formats the panic the way Rust does
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "panicked at {}:\n{}", location, self.message),
+            None => write!(f, "panicked:\n{}", self.message),
+        }
+    }
+}
+
+impl GuestPanicLocation {
+    /// Parses `file:line:column`, the file possibly containing colons
+    fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.rsplitn(3, ':');
+        let column = parts.next()?.parse().ok()?;
+        let line = parts.next()?.parse().ok()?;
+        let file = parts.next()?.to_string();
+        Some(Self { file, line, column })
+    }
+}
+
+impl Display for GuestPanicLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// The general purpose registers of the vCPU in a [`GuestCrashReport`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GuestCrashRegisters {
+    /// The `rax` register
+    pub rax: u64,
+    /// The `rbx` register
+    pub rbx: u64,
+    /// The `rcx` register
+    pub rcx: u64,
+    /// The `rdx` register
+    pub rdx: u64,
+    /// The `rsi` register
+    pub rsi: u64,
+    /// The `rdi` register
+    pub rdi: u64,
+    /// The `rsp` register
+    pub rsp: u64,
+    /// The `rbp` register
+    pub rbp: u64,
+    /// The `r8` register
+    pub r8: u64,
+    /// The `r9` register
+    pub r9: u64,
+    /// The `r10` register
+    pub r10: u64,
+    /// The `r11` register
+    pub r11: u64,
+    /// The `r12` register
+    pub r12: u64,
+    /// The `r13` register
+    pub r13: u64,
+    /// The `r14` register
+    pub r14: u64,
+    /// The `r15` register
+    pub r15: u64,
+    /// The `rip` register
+    pub rip: u64,
+    /// The `rflags` register
+    pub rflags: u64,
+}
+
+impl GuestCrashRegisters {
+    pub(crate) fn new(regs: &CommonRegisters) -> Self {
+        Self {
+            rax: regs.rax,
+            rbx: regs.rbx,
+            rcx: regs.rcx,
+            rdx: regs.rdx,
+            rsi: regs.rsi,
+            rdi: regs.rdi,
+            rsp: regs.rsp,
+            rbp: regs.rbp,
+            r8: regs.r8,
+            r9: regs.r9,
+            r10: regs.r10,
+            r11: regs.r11,
+            r12: regs.r12,
+            r13: regs.r13,
+            r14: regs.r14,
+            r15: regs.r15,
+            rip: regs.rip,
+            rflags: regs.rflags,
+        }
+    }
+}
+
+/// Reads the guest stack from `rsp` up to `stack_top`, with `read`
+/// reading guest memory by virtual address. A stack pointer outside the
+/// main stack, such as on an exception stack, is read up to the end of
+/// its page.
+pub(crate) fn read_crash_stack(
+    rsp: u64,
+    stack_top: u64,
+    mut read: impl FnMut(u64, usize) -> Option<Vec<u8>>,
+) -> Vec<u8> {
+    let page_end = (rsp | 0xfff) + 1;
+    let len = if rsp < stack_top {
+        stack_top - rsp
+    } else {
+        page_end - rsp
+    };
+    let len = (len as usize).min(MAX_CRASH_STACK_BYTES);
+    read(rsp, len)
+        .or_else(|| read(rsp, (page_end - rsp) as usize))
+        .unwrap_or_default()
+}
+
+/// Walks the guest stack from `rip` by following the frame pointers
+/// from `rbp`, with `read` reading guest memory by virtual address
+pub(crate) fn walk_crash_frames(
+    rip: u64,
+    rbp: u64,
+    mut read: impl FnMut(u64, usize) -> Option<Vec<u8>>,
+) -> Vec<u64> {
+    let mut read_u64 = |addr: u64| {
+        read(addr, 8)
+            .and_then(|bytes| bytes.first_chunk::<8>().copied())
+            .map(u64::from_le_bytes)
+    };
+
+    let mut frames = Vec::new();
+    let mut pc = rip;
+    let mut fp = rbp;
+    while pc != 0 && frames.len() < MAX_CRASH_FRAMES {
+        frames.push(pc);
+        // The caller's frame pointer is at [fp] and the return address
+        // right above it
+        let (Some(next_fp), Some(ret)) = (read_u64(fp), read_u64(fp.wrapping_add(8))) else {
+            break;
+        };
+        // Frames grow down, so a frame pointer that does not move up
+        // means the chain is broken
+        if next_fp <= fp {
+            if ret != 0 {
+                frames.push(ret);
+            }
+            break;
+        }
+        pc = ret;
+        fp = next_fp;
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::outb::{ABORT_PANIC_BACKTRACE, ABORT_PANIC_LOCATION};
+
+    use super::{
+        GuestPanic, GuestPanicLocation, MAX_CRASH_STACK_BYTES, read_crash_stack, walk_crash_frames,
+    };
+
+    /// Guest memory at 0x1000..0x3000 for the functions reading it
+    fn read(mem: &[u64]) -> impl FnMut(u64, usize) -> Option<Vec<u8>> + '_ {
+        move |addr, len| {
+            let bytes: Vec<u8> = mem.iter().flat_map(|v| v.to_le_bytes()).collect();
+            let start = (addr as usize).checked_sub(0x1000)?;
+            bytes.get(start..start + len).map(<[u8]>::to_vec)
+        }
+    }
+
+    #[test]
+    fn frames_are_walked() {
+        // Two frames: rbp points at 0x1f00, which links to 0x1f80, whose
+        // saved frame pointer ends the chain
+        let mut mem = vec![0u64; 0x2000 / 8];
+        mem[0xf00 / 8] = 0x1f80;
+        mem[0xf08 / 8] = 0x4010;
+        mem[0xf80 / 8] = 0;
+        mem[0xf88 / 8] = 0x4020;
+
+        assert_eq!(
+            walk_crash_frames(0x4000, 0x1f00, read(&mem)),
+            [0x4000, 0x4010, 0x4020]
+        );
+        // A frame pointer outside guest memory ends the walk
+        assert_eq!(walk_crash_frames(0x4000, 0x8000, read(&mem)), [0x4000]);
+    }
+
+    #[test]
+    fn stack_is_read_up_to_its_top() {
+        let mut mem = vec![0u64; 0x2000 / 8];
+        mem[0xf00 / 8] = 0xdead;
+
+        let stack = read_crash_stack(0x1f00, 0x1f10, read(&mem));
+        assert_eq!(stack.len(), 0x10);
+        assert_eq!(stack[..8], 0xdeadu64.to_le_bytes());
+
+        // The stack is capped, and read to the end of the page when the
+        // stack pointer is not on the main stack
+        assert_eq!(
+            read_crash_stack(0x1000, 0x3000, read(&mem)).len(),
+            MAX_CRASH_STACK_BYTES
+        );
+        assert_eq!(read_crash_stack(0x1f00, 0x1000, read(&mem)).len(), 0x100);
+        // A stack that cannot be read is left empty
+        assert!(read_crash_stack(0x8000, 0x9000, read(&mem)).is_empty());
+    }
+
+    #[test]
+    fn panics_are_parsed() {
+        let mut text = b"index out of bounds".to_vec();
+        text.push(ABORT_PANIC_LOCATION);
+        text.extend_from_slice(b"C:\\src\\main.rs:12:5");
+        text.push(ABORT_PANIC_BACKTRACE);
+        text.extend_from_slice(b"4010 4020 ");
+
+        let panic = GuestPanic::parse(&text).unwrap();
+        assert_eq!(panic.message, "index out of bounds");
+        assert_eq!(
+            panic.location,
+            Some(GuestPanicLocation {
+                file: "C:\\src\\main.rs".to_string(),
+                line: 12,
+                column: 5,
+            })
+        );
+        assert_eq!(panic.backtrace, [0x4010, 0x4020]);
+        assert_eq!(
+            panic.to_string(),
+            "panicked at C:\\src\\main.rs:12:5:\nindex out of bounds"
+        );
+
+        // A panic without a backtrace, and an abort message that is not
+        // a panic
+        let panic = GuestPanic::parse(b"oops\xfesrc/lib.rs:1:1").unwrap();
+        assert_eq!(panic.location.unwrap().file, "src/lib.rs");
+        assert!(panic.backtrace.is_empty());
+        assert!(GuestPanic::parse(b"Oh no").is_none());
+    }
+}
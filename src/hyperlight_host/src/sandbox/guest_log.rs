@@ -0,0 +1,315 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::thread::JoinHandle;
+
+use crossbeam_channel::{Receiver, SendTimeoutError, Sender, TrySendError};
+use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
+use log::{Level, Record};
+use tracing_log::format_trace;
+
+use super::config::{GuestLogBackpressure, GuestLogOverflow};
+use super::outb::HandleOutbError;
+use crate::metrics::{METRIC_GUEST_LOG_DROPPED, METRIC_GUEST_LOG_DROPPED_LABEL_REASON};
+
+/// Delivers the guest log records to the host log sinks, either right
+/// away or through a bounded queue, according to the
+/// [`GuestLogBackpressure`] of the sandbox
+#[derive(Debug, Default)]
+pub(crate) struct GuestLogSink {
+    queue: Option<GuestLogQueue>,
+}
+
+#[derive(Debug)]
+struct GuestLogQueue {
+    tx: Option<Sender<GuestLogData>>,
+    capacity: usize,
+    overflow: GuestLogOverflow,
+    /// The records seen while sampling, to keep one in every N of them
+    sampled: u64,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl GuestLogSink {
+    /// Creates a sink for the given backpressure configuration. When
+    /// records are queued, they are delivered from a thread that uses the
+    /// tracing dispatcher that is the default on the calling thread.
+    pub(crate) fn new(backpressure: GuestLogBackpressure) -> Self {
+        if backpressure.queue_capacity == 0 {
+            return Self::default();
+        }
+
+        let (tx, rx) = crossbeam_channel::bounded(backpressure.queue_capacity);
+        let dispatch = tracing::dispatcher::get_default(Clone::clone);
+        let worker = std::thread::Builder::new()
+            .name("hl-guest-log".to_string())
+            .spawn(move || tracing::dispatcher::with_default(&dispatch, || deliver_all(rx)));
+        match worker {
+            Ok(worker) => Self::with_queue(backpressure, tx, Some(worker)),
+            Err(e) => {
+                log::error!(
+                    "Cannot start the guest log delivery thread, delivering guest logs synchronously: {:?}",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    fn with_queue(
+        backpressure: GuestLogBackpressure,
+        tx: Sender<GuestLogData>,
+        worker: Option<JoinHandle<()>>,
+    ) -> Self {
+        Self {
+            queue: Some(GuestLogQueue {
+                tx: Some(tx),
+                capacity: backpressure.queue_capacity,
+                overflow: backpressure.overflow,
+                sampled: 0,
+                worker,
+            }),
+        }
+    }
+
+    /// Delivers a record logged by the guest
+    pub(crate) fn log(&mut self, log_data: GuestLogData) -> Result<(), HandleOutbError> {
+        match &mut self.queue {
+            None => emit(&log_data),
+            Some(queue) => {
+                queue.push(log_data);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl GuestLogQueue {
+    fn push(&mut self, log_data: GuestLogData) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+
+        let reason = match self.overflow {
+            GuestLogOverflow::Block(timeout) => match tx.send_timeout(log_data, timeout) {
+                Ok(()) => return,
+                Err(SendTimeoutError::Timeout(_)) => "queue_full",
+                Err(SendTimeoutError::Disconnected(_)) => "disconnected",
+            },
+            GuestLogOverflow::Drop => return Self::try_send(tx, log_data),
+            GuestLogOverflow::Sample(every) => {
+                if tx.len() < self.capacity.div_ceil(2) {
+                    self.sampled = 0;
+                    return Self::try_send(tx, log_data);
+                }
+                self.sampled += 1;
+                if self.sampled.is_multiple_of(u64::from(every.max(1))) {
+                    return Self::try_send(tx, log_data);
+                }
+                "sampled"
+            }
+        };
+        Self::count_dropped(reason);
+    }
+
+    fn try_send(tx: &Sender<GuestLogData>, log_data: GuestLogData) {
+        match tx.try_send(log_data) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => Self::count_dropped("queue_full"),
+            Err(TrySendError::Disconnected(_)) => Self::count_dropped("disconnected"),
+        }
+    }
+
+    fn count_dropped(reason: &'static str) {
+        metrics::counter!(
+            METRIC_GUEST_LOG_DROPPED,
+            METRIC_GUEST_LOG_DROPPED_LABEL_REASON => reason
+        )
+        .increment(1);
+    }
+}
+
+impl Drop for GuestLogQueue {
+    fn drop(&mut self) {
+        // Let the worker deliver the records left in the queue, which are
+        // bounded by its capacity, then wait for it to finish
+        self.tx = None;
+        if let Some(worker) = self.worker.take()
+            && worker.join().is_err()
+        {
+            log::error!("The guest log delivery thread panicked");
+        }
+    }
+}
+
+fn deliver_all(rx: Receiver<GuestLogData>) {
+    for log_data in rx {
+        if let Err(e) = emit(&log_data) {
+            log::error!("Cannot deliver a guest log record: {}", e);
+        }
+    }
+}
+
+/// Hands a guest log record to the host log sinks
+fn emit(log_data: &GuestLogData) -> Result<(), HandleOutbError> {
+    // This code will create either a logging record or a tracing record for the GuestLogData depending on if the host has set up a tracing subscriber.
+    // In theory as we have enabled the log feature in the Cargo.toml for tracing this should happen
+    // automatically (based on if there is tracing subscriber present) but only works if the event created using macros. (see https://github.com/tokio-rs/tracing/blob/master/tracing/src/macros.rs#L2421 )
+    // The reason that we don't want to use the tracing macros is that we want to be able to explicitly
+    // set the file and line number for the log record which is not possible with macros.
+    // This is because the file and line number come from the  guest not the call site.
+
+    let record_level: Level = (&log_data.level).into();
+
+    // Work out if we need to log or trace
+    // this API is marked as follows but it is the easiest way to work out if we should trace or log
+
+    // Private API for internal use by tracing's macros.
+    //
+    // This function is *not* considered part of `tracing`'s public API, and has no
+    // stability guarantees. If you use it, and it breaks or disappears entirely,
+    // don't say we didn't warn you.
+
+    let should_trace = tracing_core::dispatcher::has_been_set();
+    let source_file = Some(log_data.source_file.as_str());
+    let line = Some(log_data.line);
+    let source = Some(log_data.source.as_str());
+
+    // See https://github.com/rust-lang/rust/issues/42253 for the reason this has to be done this way
+
+    if should_trace {
+        // Create a tracing event for the GuestLogData
+        // Ideally we would create tracing metadata based on the Guest Log Data
+        // but tracing derives the metadata at compile time
+        // see https://github.com/tokio-rs/tracing/issues/2419
+        // so we leave it up to the subscriber to figure out that there are logging fields present with this data
+        format_trace(
+            &Record::builder()
+                .args(format_args!("{}", log_data.message))
+                .level(record_level)
+                .target("hyperlight_guest")
+                .file(source_file)
+                .line(line)
+                .module_path(source)
+                .build(),
+        )
+        .map_err(|e| HandleOutbError::TraceFormat(e.to_string()))?;
+    } else {
+        // Create a log record for the GuestLogData
+        log::logger().log(
+            &Record::builder()
+                .args(format_args!("{}", log_data.message))
+                .level(record_level)
+                .target("hyperlight_guest")
+                .file(Some(&log_data.source_file))
+                .line(Some(log_data.line))
+                .module_path(Some(&log_data.source))
+                .build(),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
+    use hyperlight_common::flatbuffer_wrappers::guest_log_level::LogLevel;
+    use metrics::with_local_recorder;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use super::GuestLogSink;
+    use crate::metrics::{METRIC_GUEST_LOG_DROPPED, METRIC_GUEST_LOG_DROPPED_LABEL_REASON};
+    use crate::sandbox::config::{GuestLogBackpressure, GuestLogOverflow};
+
+    /// Logs `count` records through a queue that is never drained, and
+    /// returns how many were queued and how many were dropped, by reason
+    fn overflow(
+        queue_capacity: usize,
+        overflow: GuestLogOverflow,
+        count: u32,
+    ) -> (usize, Vec<(String, u64)>) {
+        let backpressure = GuestLogBackpressure {
+            queue_capacity,
+            overflow,
+        };
+        let (tx, rx) = crossbeam_channel::bounded(queue_capacity);
+        let mut sink = GuestLogSink::with_queue(backpressure, tx, None);
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        with_local_recorder(&recorder, || {
+            for line in 0..count {
+                let log_data = GuestLogData::new(
+                    format!("record {}", line),
+                    "guest".to_string(),
+                    LogLevel::Information,
+                    "caller".to_string(),
+                    "guest.rs".to_string(),
+                    line,
+                );
+                sink.log(log_data).unwrap();
+            }
+        });
+
+        let mut dropped: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, ..)| key.key().name() == METRIC_GUEST_LOG_DROPPED)
+            .map(|(key, _, _, value)| {
+                let reason = key
+                    .key()
+                    .labels()
+                    .find(|l| l.key() == METRIC_GUEST_LOG_DROPPED_LABEL_REASON)
+                    .unwrap()
+                    .value()
+                    .to_string();
+                match value {
+                    DebugValue::Counter(count) => (reason, count),
+                    value => panic!("unexpected metric value {:?}", value),
+                }
+            })
+            .collect();
+        dropped.sort();
+        (rx.len(), dropped)
+    }
+
+    #[test]
+    fn drop_when_full() {
+        let (queued, dropped) = overflow(2, GuestLogOverflow::Drop, 5);
+        assert_eq!(queued, 2);
+        assert_eq!(dropped, vec![("queue_full".to_string(), 3)]);
+    }
+
+    #[test]
+    fn block_then_drop() {
+        let (queued, dropped) = overflow(1, GuestLogOverflow::Block(Duration::from_millis(10)), 3);
+        assert_eq!(queued, 1);
+        assert_eq!(dropped, vec![("queue_full".to_string(), 2)]);
+    }
+
+    #[test]
+    fn sample_when_half_full() {
+        // The first 2 records fill half the queue, after which only every
+        // other record is kept
+        let (queued, dropped) = overflow(4, GuestLogOverflow::Sample(2), 6);
+        assert_eq!(queued, 4);
+        assert_eq!(dropped, vec![("sampled".to_string(), 2)]);
+    }
+}
@@ -0,0 +1,695 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterValue, ReturnType, ReturnValue,
+};
+use serde_json::{Map, Value, json};
+
+use crate::{Result, new_error};
+
+/// A host function call made by the guest, and its outcome
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostCallRecord {
+    /// The name of the called host function
+    pub function_name: String,
+    /// The arguments passed by the guest
+    pub args: Vec<ParameterValue>,
+    /// The value returned to the guest, or the message of the error the
+    /// call failed with
+    pub result: std::result::Result<ReturnValue, String>,
+}
+
+/// The host function calls made by a guest, in the order they were made.
+///
+/// A fixture is obtained from a [`HostCallRecorder`], and can be saved as
+/// JSON to replay the calls in later runs with
+/// [`UninitializedSandbox::replay_host_calls`](crate::UninitializedSandbox::replay_host_calls).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HostCallFixture {
+    calls: Vec<HostCallRecord>,
+}
+
+impl HostCallFixture {
+    /// Creates a fixture from a list of calls, for example to mock host
+    /// functions that were never recorded
+    pub fn new(calls: Vec<HostCallRecord>) -> Self {
+        Self { calls }
+    }
+
+    /// The recorded calls, in the order they were made
+    pub fn calls(&self) -> &[HostCallRecord] {
+        &self.calls
+    }
+
+    /// Serializes the fixture to JSON
+    pub fn to_json(&self) -> String {
+        let calls = self.calls.iter().map(record_to_json).collect::<Vec<_>>();
+        // Serializing a `Value` cannot fail
+        serde_json::to_string_pretty(&json!({ "calls": calls })).unwrap_or_default()
+    }
+
+    /// Deserializes a fixture produced by [`to_json`](Self::to_json)
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json)?;
+        let calls = value
+            .get("calls")
+            .and_then(Value::as_array)
+            .ok_or_else(|| new_error!("Host call fixture has no calls"))?
+            .iter()
+            .map(record_from_json)
+            .collect::<Result<_>>()?;
+        Ok(Self { calls })
+    }
+
+    /// Saves the fixture as JSON to the file at `path`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    /// Loads a fixture saved with [`save`](Self::save)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// A guest function call, along with the host function calls it made and
+/// its outcome.
+///
+/// A record is made with [`MultiUseSandbox::record_call`](crate::MultiUseSandbox::record_call),
+/// and can be saved as JSON to reproduce the call offline, for instance
+/// to debug an incident, with
+/// [`MultiUseSandbox::replay_call`](crate::MultiUseSandbox::replay_call).
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuestCallRecord {
+    /// The name of the called guest function
+    pub function_name: String,
+    /// The arguments passed to the guest function
+    pub args: Vec<ParameterValue>,
+    /// The return type the guest function was called with
+    pub return_type: ReturnType,
+    /// The host function calls made by the guest during the call
+    pub host_calls: HostCallFixture,
+    /// The value returned by the guest function, or the message of the
+    /// error the call failed with
+    pub result: std::result::Result<ReturnValue, String>,
+}
+
+impl GuestCallRecord {
+    /// Serializes the record to JSON
+    pub fn to_json(&self) -> String {
+        let args = self.args.iter().map(param_to_json).collect::<Vec<_>>();
+        let host_calls = self
+            .host_calls
+            .calls
+            .iter()
+            .map(record_to_json)
+            .collect::<Vec<_>>();
+        // Serializing a `Value` cannot fail
+        serde_json::to_string_pretty(&json!({
+            "function": self.function_name,
+            "args": args,
+            "return_type": return_type_to_json(&self.return_type),
+            "host_calls": host_calls,
+            "result": result_to_json(&self.result),
+        }))
+        .unwrap_or_default()
+    }
+
+    /// Deserializes a record produced by [`to_json`](Self::to_json)
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json)?;
+        let function_name = value
+            .get("function")
+            .and_then(Value::as_str)
+            .ok_or_else(|| new_error!("Guest call record has no function name"))?
+            .to_string();
+        let args = value
+            .get("args")
+            .and_then(Value::as_array)
+            .ok_or_else(|| new_error!("Guest call record has no arguments"))?
+            .iter()
+            .map(param_from_json)
+            .collect::<Result<_>>()?;
+        let return_type = value
+            .get("return_type")
+            .and_then(Value::as_str)
+            .and_then(return_type_from_json)
+            .ok_or_else(|| new_error!("Guest call record has no valid return type"))?;
+        let host_calls = value
+            .get("host_calls")
+            .and_then(Value::as_array)
+            .ok_or_else(|| new_error!("Guest call record has no host calls"))?
+            .iter()
+            .map(record_from_json)
+            .collect::<Result<_>>()?;
+        let result = result_from_json(&value)
+            .ok_or_else(|| new_error!("Guest call record has no result"))??;
+        Ok(Self {
+            function_name,
+            args,
+            return_type,
+            host_calls: HostCallFixture::new(host_calls),
+            result,
+        })
+    }
+
+    /// Saves the record as JSON to the file at `path`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    /// Loads a record saved with [`save`](Self::save)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// How the replay of a [`GuestCallRecord`] departed from the recorded
+/// call, the first difference found being reported
+#[derive(Clone, Debug, PartialEq)]
+pub enum GuestCallDivergence {
+    /// The guest made host call `index` differently than recorded.
+    /// `expected` is `None` if the recorded call made fewer host calls.
+    HostCall {
+        /// The position of the host call among the calls of the guest
+        index: usize,
+        /// The name of the host function called by the guest
+        function_name: String,
+        /// The arguments passed by the guest
+        args: Vec<ParameterValue>,
+        /// The host call that was recorded at this position
+        expected: Option<HostCallRecord>,
+    },
+    /// The guest made only `made` of the `recorded` host calls
+    MissingHostCalls {
+        /// The number of host calls made by the guest
+        made: usize,
+        /// The number of recorded host calls
+        recorded: usize,
+    },
+    /// The guest function call had a different outcome
+    Result {
+        /// The recorded outcome
+        expected: std::result::Result<ReturnValue, String>,
+        /// The outcome of the replay
+        actual: std::result::Result<ReturnValue, String>,
+    },
+}
+
+/// The state of the replay of a [`HostCallFixture`]
+#[derive(Debug)]
+pub(crate) struct HostCallReplay {
+    remaining: VecDeque<HostCallRecord>,
+    made: usize,
+    divergence: Option<GuestCallDivergence>,
+}
+
+impl HostCallReplay {
+    /// The first host call that did not match the fixture, or the missing
+    /// calls if the guest made fewer calls than the fixture holds
+    pub(crate) fn divergence(self) -> Option<GuestCallDivergence> {
+        self.divergence.or_else(|| {
+            (!self.remaining.is_empty()).then_some(GuestCallDivergence::MissingHostCalls {
+                made: self.made,
+                recorded: self.made + self.remaining.len(),
+            })
+        })
+    }
+}
+
+/// A handle on the host function calls recorded for a sandbox, returned
+/// by [`UninitializedSandbox::record_host_calls`](crate::UninitializedSandbox::record_host_calls)
+#[derive(Clone, Debug)]
+pub struct HostCallRecorder {
+    calls: Arc<Mutex<Vec<HostCallRecord>>>,
+}
+
+impl HostCallRecorder {
+    /// Returns the calls recorded so far
+    pub fn fixture(&self) -> HostCallFixture {
+        // A poisoned lock still holds the calls recorded before the panic
+        let calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+        HostCallFixture::new(calls.clone())
+    }
+}
+
+/// How the host function calls made by the guest are handled
+#[derive(Debug, Default)]
+pub(crate) enum HostCallMode {
+    /// Call the registered host functions
+    #[default]
+    Live,
+    /// Call the registered host functions and record the calls
+    Record(Arc<Mutex<Vec<HostCallRecord>>>),
+    /// Answer the calls from a fixture without calling the registered
+    /// host functions
+    Replay(Mutex<HostCallReplay>),
+}
+
+impl HostCallMode {
+    /// Starts recording the calls, returning the handle on the recording
+    pub(crate) fn record() -> (Self, HostCallRecorder) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        (Self::Record(calls.clone()), HostCallRecorder { calls })
+    }
+
+    /// Starts replaying the calls of `fixture`
+    pub(crate) fn replay(fixture: HostCallFixture) -> Self {
+        Self::Replay(Mutex::new(HostCallReplay {
+            remaining: fixture.calls.into(),
+            made: 0,
+            divergence: None,
+        }))
+    }
+
+    /// The state of the replay, if the calls are replayed
+    pub(crate) fn into_replay(self) -> Option<HostCallReplay> {
+        match self {
+            Self::Replay(replay) => Some(replay.into_inner().unwrap_or_else(|e| e.into_inner())),
+            _ => None,
+        }
+    }
+
+    /// Records `calls` as if they had been made in this mode
+    pub(crate) fn extend_recording(&self, calls: impl IntoIterator<Item = HostCallRecord>) {
+        if let Self::Record(recorded) = self {
+            recorded
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .extend(calls);
+        }
+    }
+
+    /// Handles the call of host function `name` with `args`, where `call`
+    /// calls the registered host function
+    pub(crate) fn call(
+        &self,
+        name: &str,
+        args: Vec<ParameterValue>,
+        call: impl FnOnce(Vec<ParameterValue>) -> Result<ReturnValue>,
+    ) -> Result<ReturnValue> {
+        match self {
+            Self::Live => call(args),
+            Self::Record(calls) => {
+                let result = call(args.clone());
+                calls
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(HostCallRecord {
+                        function_name: name.to_string(),
+                        args,
+                        result: result.as_ref().map_err(|e| e.to_string()).cloned(),
+                    });
+                result
+            }
+            Self::Replay(replay) => {
+                let mut replay = replay.lock().unwrap_or_else(|e| e.into_inner());
+                let index = replay.made;
+                replay.made += 1;
+                let Some(expected) = replay.remaining.pop_front() else {
+                    replay
+                        .divergence
+                        .get_or_insert(GuestCallDivergence::HostCall {
+                            index,
+                            function_name: name.to_string(),
+                            args,
+                            expected: None,
+                        });
+                    return Err(new_error!(
+                        "Unexpected call to host function {}: fixture exhausted",
+                        name
+                    ));
+                };
+                if expected.function_name != name || expected.args != args {
+                    let err = new_error!(
+                        "Host function call {}({:?}) does not match the fixture, which expects {}({:?})",
+                        name,
+                        args,
+                        expected.function_name,
+                        expected.args
+                    );
+                    replay
+                        .divergence
+                        .get_or_insert(GuestCallDivergence::HostCall {
+                            index,
+                            function_name: name.to_string(),
+                            args,
+                            expected: Some(expected),
+                        });
+                    return Err(err);
+                }
+                expected.result.map_err(|e| new_error!("{}", e))
+            }
+        }
+    }
+}
+
+fn record_to_json(record: &HostCallRecord) -> Value {
+    let args = record.args.iter().map(param_to_json).collect::<Vec<_>>();
+    json!({
+        "function": record.function_name,
+        "args": args,
+        "result": result_to_json(&record.result),
+    })
+}
+
+fn result_to_json(result: &std::result::Result<ReturnValue, String>) -> Value {
+    match result {
+        Ok(value) => json!({ "Ok": return_to_json(value) }),
+        Err(message) => json!({ "Err": message }),
+    }
+}
+
+/// Reads the `result` field of `value`, `None` if it has none
+fn result_from_json(value: &Value) -> Option<Result<std::result::Result<ReturnValue, String>>> {
+    match value.get("result").and_then(tagged)? {
+        ("Ok", value) => Some(return_from_json(value).map(Ok)),
+        ("Err", Value::String(message)) => Some(Ok(Err(message.clone()))),
+        _ => None,
+    }
+}
+
+fn record_from_json(value: &Value) -> Result<HostCallRecord> {
+    let function_name = value
+        .get("function")
+        .and_then(Value::as_str)
+        .ok_or_else(|| new_error!("Host call record has no function name: {}", value))?
+        .to_string();
+    let args = value
+        .get("args")
+        .and_then(Value::as_array)
+        .ok_or_else(|| new_error!("Host call record has no arguments: {}", value))?
+        .iter()
+        .map(param_from_json)
+        .collect::<Result<_>>()?;
+    let result = result_from_json(value)
+        .ok_or_else(|| new_error!("Host call record has no result: {}", value))??;
+    Ok(HostCallRecord {
+        function_name,
+        args,
+        result,
+    })
+}
+
+/// Splits an object with a single field into the field name and value
+fn tagged(value: &Value) -> Option<(&str, &Value)> {
+    let object = value.as_object()?;
+    if object.len() != 1 {
+        return None;
+    }
+    object
+        .iter()
+        .next()
+        .map(|(tag, value)| (tag.as_str(), value))
+}
+
+fn single(tag: &str, value: Value) -> Value {
+    Value::Object(Map::from_iter([(tag.to_string(), value)]))
+}
+
+/// Floats that JSON can't represent (NaN and infinities) are stored as
+/// strings
+fn float_to_json(value: f64) -> Value {
+    serde_json::Number::from_f64(value)
+        .map(Value::Number)
+        .unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+fn float_from_json(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        _ => value.as_f64(),
+    }
+}
+
+fn bytes_to_json(bytes: &[u8]) -> Value {
+    Value::String(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn bytes_from_json(value: &Value) -> Option<Vec<u8>> {
+    let hex = value.as_str()?;
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn param_to_json(param: &ParameterValue) -> Value {
+    match param {
+        ParameterValue::Int(v) => single("Int", json!(v)),
+        ParameterValue::UInt(v) => single("UInt", json!(v)),
+        ParameterValue::Long(v) => single("Long", json!(v)),
+        ParameterValue::ULong(v) => single("ULong", json!(v)),
+        ParameterValue::Float(v) => single("Float", float_to_json(*v as f64)),
+        ParameterValue::Double(v) => single("Double", float_to_json(*v)),
+        ParameterValue::String(v) => single("String", json!(v)),
+        ParameterValue::Bool(v) => single("Bool", json!(v)),
+        ParameterValue::VecBytes(v) => single("VecBytes", bytes_to_json(v)),
+    }
+}
+
+fn param_from_json(value: &Value) -> Result<ParameterValue> {
+    let param = match tagged(value) {
+        Some(("Int", v)) => v
+            .as_i64()
+            .and_then(|v| v.try_into().ok())
+            .map(ParameterValue::Int),
+        Some(("UInt", v)) => v
+            .as_u64()
+            .and_then(|v| v.try_into().ok())
+            .map(ParameterValue::UInt),
+        Some(("Long", v)) => v.as_i64().map(ParameterValue::Long),
+        Some(("ULong", v)) => v.as_u64().map(ParameterValue::ULong),
+        Some(("Float", v)) => float_from_json(v).map(|v| ParameterValue::Float(v as f32)),
+        Some(("Double", v)) => float_from_json(v).map(ParameterValue::Double),
+        Some(("String", v)) => v.as_str().map(|v| ParameterValue::String(v.to_string())),
+        Some(("Bool", v)) => v.as_bool().map(ParameterValue::Bool),
+        Some(("VecBytes", v)) => bytes_from_json(v).map(ParameterValue::VecBytes),
+        _ => None,
+    };
+    param.ok_or_else(|| new_error!("Invalid host call argument: {}", value))
+}
+
+fn return_type_to_json(return_type: &ReturnType) -> &'static str {
+    match return_type {
+        ReturnType::Int => "Int",
+        ReturnType::UInt => "UInt",
+        ReturnType::Long => "Long",
+        ReturnType::ULong => "ULong",
+        ReturnType::Float => "Float",
+        ReturnType::Double => "Double",
+        ReturnType::String => "String",
+        ReturnType::Bool => "Bool",
+        ReturnType::Void => "Void",
+        ReturnType::VecBytes => "VecBytes",
+    }
+}
+
+fn return_type_from_json(name: &str) -> Option<ReturnType> {
+    Some(match name {
+        "Int" => ReturnType::Int,
+        "UInt" => ReturnType::UInt,
+        "Long" => ReturnType::Long,
+        "ULong" => ReturnType::ULong,
+        "Float" => ReturnType::Float,
+        "Double" => ReturnType::Double,
+        "String" => ReturnType::String,
+        "Bool" => ReturnType::Bool,
+        "Void" => ReturnType::Void,
+        "VecBytes" => ReturnType::VecBytes,
+        _ => return None,
+    })
+}
+
+fn return_to_json(value: &ReturnValue) -> Value {
+    match value {
+        ReturnValue::Int(v) => single("Int", json!(v)),
+        ReturnValue::UInt(v) => single("UInt", json!(v)),
+        ReturnValue::Long(v) => single("Long", json!(v)),
+        ReturnValue::ULong(v) => single("ULong", json!(v)),
+        ReturnValue::Float(v) => single("Float", float_to_json(*v as f64)),
+        ReturnValue::Double(v) => single("Double", float_to_json(*v)),
+        ReturnValue::String(v) => single("String", json!(v)),
+        ReturnValue::Bool(v) => single("Bool", json!(v)),
+        ReturnValue::Void(()) => single("Void", Value::Null),
+        ReturnValue::VecBytes(v) => single("VecBytes", bytes_to_json(v)),
+    }
+}
+
+fn return_from_json(value: &Value) -> Result<ReturnValue> {
+    let ret = match tagged(value) {
+        Some(("Int", v)) => v
+            .as_i64()
+            .and_then(|v| v.try_into().ok())
+            .map(ReturnValue::Int),
+        Some(("UInt", v)) => v
+            .as_u64()
+            .and_then(|v| v.try_into().ok())
+            .map(ReturnValue::UInt),
+        Some(("Long", v)) => v.as_i64().map(ReturnValue::Long),
+        Some(("ULong", v)) => v.as_u64().map(ReturnValue::ULong),
+        Some(("Float", v)) => float_from_json(v).map(|v| ReturnValue::Float(v as f32)),
+        Some(("Double", v)) => float_from_json(v).map(ReturnValue::Double),
+        Some(("String", v)) => v.as_str().map(|v| ReturnValue::String(v.to_string())),
+        Some(("Bool", v)) => v.as_bool().map(ReturnValue::Bool),
+        Some(("Void", Value::Null)) => Some(ReturnValue::Void(())),
+        Some(("VecBytes", v)) => bytes_from_json(v).map(ReturnValue::VecBytes),
+        _ => None,
+    };
+    ret.ok_or_else(|| new_error!("Invalid host call result: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> HostCallFixture {
+        HostCallFixture::new(vec![
+            HostCallRecord {
+                function_name: "HostPrint".to_string(),
+                args: vec![ParameterValue::String("hello".to_string())],
+                result: Ok(ReturnValue::Int(5)),
+            },
+            HostCallRecord {
+                function_name: "Everything".to_string(),
+                args: vec![
+                    ParameterValue::Int(-1),
+                    ParameterValue::UInt(u32::MAX),
+                    ParameterValue::Long(i64::MIN),
+                    ParameterValue::ULong(u64::MAX),
+                    ParameterValue::Float(1.5),
+                    ParameterValue::Double(f64::INFINITY),
+                    ParameterValue::Bool(true),
+                    ParameterValue::VecBytes(vec![0, 0xab, 0xff]),
+                ],
+                result: Ok(ReturnValue::VecBytes(vec![1, 2, 3])),
+            },
+            HostCallRecord {
+                function_name: "Failing".to_string(),
+                args: vec![],
+                result: Err("the host service is unavailable".to_string()),
+            },
+        ])
+    }
+
+    #[test]
+    fn fixture_json_round_trip() {
+        let fixture = fixture();
+        let json = fixture.to_json();
+        assert_eq!(HostCallFixture::from_json(&json).unwrap(), fixture);
+
+        assert!(HostCallFixture::from_json("{}").is_err());
+        assert!(
+            HostCallFixture::from_json(
+                r#"{"calls": [{"function": "f", "args": [{"Int": 1.5}], "result": {"Ok": {"Void": null}}}]}"#
+            )
+            .is_err()
+        );
+    }
+
+    fn not_called(_: Vec<ParameterValue>) -> Result<ReturnValue> {
+        panic!("replayed calls must not call the host function")
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let (mode, recorder) = HostCallMode::record();
+        for record in fixture().calls {
+            let result = mode
+                .call(&record.function_name, record.args.clone(), |_| {
+                    record.result.clone().map_err(|e| new_error!("{}", e))
+                })
+                .map_err(|e| e.to_string());
+            assert_eq!(result, record.result);
+        }
+        assert_eq!(recorder.fixture(), fixture());
+
+        let mode = HostCallMode::replay(recorder.fixture());
+        assert_eq!(
+            mode.call(
+                "HostPrint",
+                vec![ParameterValue::String("hello".to_string())],
+                not_called
+            )
+            .unwrap(),
+            ReturnValue::Int(5)
+        );
+        // A call that doesn't match the fixture fails
+        let err = mode.call("Failing", vec![], not_called).unwrap_err();
+        assert!(err.to_string().contains("does not match the fixture"));
+        // Recorded errors are returned as is
+        let err = mode.call("Failing", vec![], not_called).unwrap_err();
+        assert_eq!(err.to_string(), "the host service is unavailable");
+        let err = mode.call("Failing", vec![], not_called).unwrap_err();
+        assert!(err.to_string().contains("fixture exhausted"));
+    }
+
+    #[test]
+    fn replay_reports_divergence() {
+        let mode = HostCallMode::replay(fixture());
+        mode.call(
+            "HostPrint",
+            vec![ParameterValue::String("hello".to_string())],
+            not_called,
+        )
+        .unwrap();
+        assert_eq!(
+            mode.into_replay().unwrap().divergence(),
+            Some(GuestCallDivergence::MissingHostCalls {
+                made: 1,
+                recorded: 3
+            })
+        );
+
+        let mode = HostCallMode::replay(fixture());
+        mode.call("HostPrint", vec![], not_called).unwrap_err();
+        // Only the first divergence is reported
+        mode.call("Failing", vec![], not_called).unwrap_err();
+        assert_eq!(
+            mode.into_replay().unwrap().divergence(),
+            Some(GuestCallDivergence::HostCall {
+                index: 0,
+                function_name: "HostPrint".to_string(),
+                args: vec![],
+                expected: Some(fixture().calls[0].clone()),
+            })
+        );
+    }
+
+    #[test]
+    fn guest_call_json_round_trip() {
+        let record = GuestCallRecord {
+            function_name: "Process".to_string(),
+            args: vec![ParameterValue::String("input".to_string())],
+            return_type: ReturnType::VecBytes,
+            host_calls: fixture(),
+            result: Err("Guest error occurred".to_string()),
+        };
+        let json = record.to_json();
+        assert_eq!(GuestCallRecord::from_json(&json).unwrap(), record);
+        assert!(GuestCallRecord::from_json(&fixture().to_json()).is_err());
+    }
+}
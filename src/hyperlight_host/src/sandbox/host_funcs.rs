@@ -16,6 +16,8 @@ limitations under the License.
 
 use std::collections::HashMap;
 use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use hyperlight_common::flatbuffer_wrappers::function_types::{
     ParameterType, ParameterValue, ReturnType, ReturnValue,
@@ -25,14 +27,32 @@ use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionD
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use tracing::{Span, instrument};
 
-use crate::HyperlightError::HostFunctionNotFound;
-use crate::Result;
-use crate::func::host_functions::TypeErasedHostFunction;
+use super::host_call_recording::HostCallMode;
+use crate::HyperlightError::{
+    HostFunctionDenied, HostFunctionNotFound, HostFunctionSignatureMismatch,
+};
+use crate::func::host_functions::{HostFunctionPolicy, TypeErasedHostFunction};
+use crate::func::middleware::{HostCallMiddleware, Next};
+use crate::{Result, new_error};
 
 #[derive(Default)]
 /// A Wrapper around details of functions exposed by the Host
 pub struct FunctionRegistry {
     functions_map: HashMap<String, FunctionEntry>,
+    /// Fail every Nth call made through `call_host_function`, 0 disables it
+    fail_every: u64,
+    /// Number of calls made through `call_host_function`
+    calls: AtomicU64,
+    /// Whether calls made through `call_host_function` are recorded or
+    /// replayed
+    mode: HostCallMode,
+    /// The layers wrapping the calls made through `call_host_function`
+    middleware: Vec<Arc<dyn HostCallMiddleware>>,
+    /// The policies of the host functions that are not always allowed
+    policies: HashMap<String, HostFunctionPolicy>,
+    /// The calls made to each host function with a policy during the
+    /// current guest function call
+    policy_calls: Arc<Mutex<HashMap<String, u32>>>,
 }
 
 impl From<&mut FunctionRegistry> for HostFunctionDetails {
@@ -53,10 +73,17 @@ impl From<&mut FunctionRegistry> for HostFunctionDetails {
     }
 }
 
+#[derive(Clone)]
 pub struct FunctionEntry {
     pub function: TypeErasedHostFunction,
     pub parameter_types: &'static [ParameterType],
     pub return_type: ReturnType,
+    /// Whether the guest calls are checked against the signature, see
+    /// [`FunctionRegistry::check_signature`]
+    pub check_signature: bool,
+    /// Whether the function can call back into the guest, see
+    /// [`call_guest`](crate::func::callbacks::call_guest)
+    pub allows_callbacks: bool,
 }
 
 impl FunctionRegistry {
@@ -72,6 +99,111 @@ impl FunctionRegistry {
         Ok(())
     }
 
+    /// Remove the host function `name`, returning whether it was
+    /// registered.
+    #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    pub(crate) fn unregister_host_function(&mut self, name: &str) -> bool {
+        self.functions_map.remove(name).is_some()
+    }
+
+    /// The names of the registered host functions, sorted
+    pub(crate) fn function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.functions_map.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Make every Nth call made by the guest fail without calling the
+    /// host function. 0 disables the injected failures.
+    pub(crate) fn set_fail_every(&mut self, fail_every: u64) {
+        self.fail_every = fail_every;
+    }
+
+    /// A registry with the same host functions and injected failures,
+    /// for a sandbox cloned from the one this registry belongs to. The
+    /// calls of the clone are neither recorded nor replayed.
+    pub(crate) fn clone_functions(&self) -> FunctionRegistry {
+        FunctionRegistry {
+            functions_map: self.functions_map.clone(),
+            fail_every: self.fail_every,
+            middleware: self.middleware.clone(),
+            policies: self.policies.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// A registry with the same host functions, layers and policies, for
+    /// the host function calls made by the guest functions that a host
+    /// function calls back. The calls limited by policies are counted
+    /// along with those made by the guest function called by the host.
+    /// The calls are neither recorded nor replayed.
+    pub(crate) fn for_callbacks(&self) -> FunctionRegistry {
+        FunctionRegistry {
+            policy_calls: self.policy_calls.clone(),
+            ..self.clone_functions()
+        }
+    }
+
+    /// Whether the host function `name` can call back into the guest
+    pub(crate) fn allows_callbacks(&self, name: &str) -> bool {
+        self.functions_map
+            .get(name)
+            .is_some_and(|entry| entry.allows_callbacks)
+    }
+
+    /// Wrap the calls made by the guest from now on in `middleware`,
+    /// inside the layers added before
+    pub(crate) fn add_middleware(&mut self, middleware: Arc<dyn HostCallMiddleware>) {
+        self.middleware.push(middleware);
+    }
+
+    /// Set whether the guest may call the host function `name`
+    pub(crate) fn set_policy(&mut self, name: &str, policy: HostFunctionPolicy) {
+        match policy {
+            HostFunctionPolicy::Allowed => self.policies.remove(name),
+            _ => self.policies.insert(name.to_string(), policy),
+        };
+    }
+
+    /// Start counting the calls limited by policies from zero, at the
+    /// start of a guest function call
+    pub(crate) fn reset_policy_calls(&self) {
+        self.policy_calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// Check the call of the host function `name` against its policy,
+    /// counting it if it is allowed a number of times
+    fn authorize(&self, name: &str) -> Result<()> {
+        let allowed = match self.policies.get(name) {
+            None | Some(HostFunctionPolicy::Allowed) => true,
+            Some(HostFunctionPolicy::Denied) => false,
+            Some(HostFunctionPolicy::AllowedTimes(times)) => {
+                let mut calls = self.policy_calls.lock().unwrap_or_else(|e| e.into_inner());
+                let made = calls.entry(name.to_string()).or_default();
+                *made = made.saturating_add(1);
+                *made <= *times
+            }
+        };
+        if !allowed {
+            return Err(HostFunctionDenied(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Set how the calls made by the guest are handled from now on
+    pub(crate) fn set_mode(&mut self, mode: HostCallMode) {
+        self.mode = mode;
+    }
+
+    /// Set how the calls made by the guest are handled from now on,
+    /// returning how they were handled until now
+    pub(crate) fn replace_mode(&mut self, mode: HostCallMode) -> HostCallMode {
+        std::mem::replace(&mut self.mode, mode)
+    }
+
     /// Assuming a host function called `"HostPrint"` exists, and takes a
     /// single string parameter, call it with the given `msg` parameter.
     ///
@@ -89,15 +221,65 @@ impl FunctionRegistry {
     /// `args` and return its result.
     ///
     /// Return `Err` if no such function exists,
-    /// its parameter list doesn't match `args`, or there was another error
-    /// getting, configuring or calling the function.
+    /// its parameter list doesn't match `args`, there was another error
+    /// getting, configuring or calling the function, or a failure was
+    /// injected for this call.
+    ///
+    /// The call is checked against the policy of the function, then goes
+    /// through the middleware layers. When calls are
+    /// replayed, the result comes from the fixture and the function is
+    /// not called.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     pub(super) fn call_host_function(
         &self,
         name: &str,
         args: Vec<ParameterValue>,
     ) -> Result<ReturnValue> {
-        self.call_host_func_impl(name, args)
+        let call = self.calls.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.fail_every != 0 && call % self.fail_every == 0 {
+            return Err(new_error!(
+                "Injected failure of host function call {} to {}",
+                call,
+                name
+            ));
+        }
+        self.authorize(name)?;
+        let dispatch = |name: &str, args| {
+            self.mode
+                .call(name, args, |args| self.call_host_func_impl(name, args))
+        };
+        Next::new(&self.middleware, &dispatch).run(name, args)
+    }
+
+    /// Check that the guest calls the host function `name` with the
+    /// parameter and return types it was registered with, if it was
+    /// registered through [`HostFunctions`](crate::func::HostFunctions).
+    ///
+    /// Functions that are not registered are not checked, calling them
+    /// fails, unless the calls are replayed.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(super) fn check_signature(
+        &self,
+        name: &str,
+        args: &[ParameterValue],
+        return_type: ReturnType,
+    ) -> Result<()> {
+        let Some(entry) = self
+            .functions_map
+            .get(name)
+            .filter(|entry| entry.check_signature)
+        else {
+            return Ok(());
+        };
+        let parameter_types: Vec<ParameterType> = args.iter().map(ParameterType::from).collect();
+        if parameter_types.as_slice() != entry.parameter_types || return_type != entry.return_type {
+            return Err(HostFunctionSignatureMismatch(
+                name.to_string(),
+                format_signature(&parameter_types, return_type),
+                format_signature(entry.parameter_types, entry.return_type),
+            ));
+        }
+        Ok(())
     }
 
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
@@ -106,6 +288,8 @@ impl FunctionRegistry {
             function,
             parameter_types: _,
             return_type: _,
+            check_signature: _,
+            allows_callbacks: _,
         } = self
             .functions_map
             .get(name)
@@ -116,6 +300,18 @@ impl FunctionRegistry {
     }
 }
 
+/// Formats a signature as `fn(Int, String) -> Long`
+pub(crate) fn format_signature(
+    parameter_types: &[ParameterType],
+    return_type: ReturnType,
+) -> String {
+    let parameters: Vec<String> = parameter_types
+        .iter()
+        .map(|parameter_type| format!("{:?}", parameter_type))
+        .collect();
+    format!("fn({}) -> {:?}", parameters.join(", "), return_type)
+}
+
 /// The default writer function is to write to stdout with green text.
 #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
 pub(super) fn default_writer_func(s: String) -> Result<i32> {
@@ -14,39 +14,85 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::marker::PhantomData;
 #[cfg(unix)]
 use std::os::fd::AsRawFd;
 #[cfg(unix)]
 use std::os::linux::fs::MetadataExt;
 use std::path::Path;
-use std::sync::atomic::Ordering;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
 use flatbuffers::FlatBufferBuilder;
+use hyperlight_common::call_queue::CALL_QUEUE_FUNCTION;
+use hyperlight_common::diagnostics::{BENCH_FUNCTION, LIST_FUNCTIONS_FUNCTION, SELF_TEST_FUNCTION};
 use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
 use hyperlight_common::flatbuffer_wrappers::function_types::{
     ParameterValue, ReturnType, ReturnValue,
 };
+use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
 use hyperlight_common::flatbuffer_wrappers::util::estimate_flatbuffer_capacity;
+use hyperlight_common::interrupt::is_host_interrupt_vector;
+#[cfg(target_os = "linux")]
+use hyperlight_common::mem::{BORROWED_BUFFER_BASE, BORROWED_BUFFER_WINDOW_SIZE};
+use hyperlight_common::stream::{HOST_STREAM_READ_FUNCTION, HOST_STREAM_WRITE_FUNCTION};
 use tracing::{Span, instrument};
-
-use super::Callable;
-use super::host_funcs::FunctionRegistry;
+use tracing_core::LevelFilter;
+
+#[cfg(feature = "mem_profile")]
+use super::MemProfileCapture;
+#[cfg(target_os = "linux")]
+use super::borrowed_buffer::BorrowedGuestBuffer;
+use super::borrowed_buffer::is_borrowed_buffer;
+use super::cancellation::CancellationToken;
+use super::checkpoint::GuestCheckpoint;
+use super::checkpoint_file;
+use super::deadline::Deadline;
+use super::debug_events::DebugEvents;
+use super::diagnostics::{DiagnosticsReport, GuestBenchTimings, GuestFunctionInfo};
+use super::fuel::FuelDecision;
+use super::guest_crash::GuestCrashReport;
+use super::host_call_recording::{
+    GuestCallDivergence, GuestCallRecord, HostCallMode, HostCallReplay,
+};
+use super::host_funcs::{FunctionEntry, FunctionRegistry, format_signature};
+use super::memory_scan::{MemoryMatch, MemoryPattern, MemoryScanOptions, MemoryScanner};
+use super::metrics::{CreationReport, MemoryStats, SandboxMetrics, SandboxUsage, VmExitStats};
+use super::migration;
+use super::observer::SandboxObservers;
+use super::quota::{QuotaReservation, QuotaResource, SandboxQuota};
+#[cfg(target_os = "linux")]
+use super::shared_region::SharedRegion;
 use super::snapshot::Snapshot;
+use super::snapshot_chain::{SnapshotChain, SnapshotId};
+use super::status::{SandboxStatus, SandboxStatusHandle};
+use super::streams::{StreamId, Streams};
+#[cfg(feature = "mem_trace")]
+use super::trace::MemoryAccess;
+use super::{Callable, SandboxConfiguration};
 use crate::HyperlightError::{self, SnapshotSandboxMismatch};
-use crate::func::{ParameterTuple, SupportedReturnType};
+use crate::func::middleware::HostCallMiddleware;
+use crate::func::workspace::Workspace;
+use crate::func::{
+    HostFunction, HostFunctionPolicy, HostFunctions, ParameterTuple, SupportedReturnType,
+};
 use crate::hypervisor::InterruptHandle;
-use crate::hypervisor::hyperlight_vm::{HyperlightVm, HyperlightVmError};
-use crate::mem::memory_region::MemoryRegion;
-#[cfg(unix)]
-use crate::mem::memory_region::{MemoryRegionFlags, MemoryRegionType};
+use crate::hypervisor::hyperlight_vm::{DispatchGuestCallError, HyperlightVm, HyperlightVmError};
+use crate::mem::layout::{SandboxLayoutInfo, SandboxMemoryLayout};
+use crate::mem::memory_region::{
+    HostGuestMemoryRegion, MemoryRegion, MemoryRegionFlags, MemoryRegionType,
+};
 use crate::mem::mgr::SandboxMemoryManager;
-use crate::mem::shared_mem::HostSharedMemory;
+use crate::mem::shared_mem::{AllValid, HostSharedMemory, SharedMemory};
 use crate::metrics::{
-    METRIC_GUEST_ERROR, METRIC_GUEST_ERROR_LABEL_CODE, maybe_time_and_emit_guest_call,
+    METRIC_GUEST_ERROR, METRIC_GUEST_ERROR_LABEL_CODE, MetricsRecorder, MetricsSink, SandboxMetric,
+    maybe_time_and_emit_guest_call,
+};
+use crate::{
+    GuestBinary, HypervisorKind, Result, UninitializedSandbox, log_then_return, new_error,
 };
-use crate::{Result, log_then_return};
 
 /// A fully initialized sandbox that can execute guest functions multiple times.
 ///
@@ -89,16 +135,61 @@ use crate::{Result, log_then_return};
 pub struct MultiUseSandbox {
     /// Unique identifier for this sandbox instance
     id: u64,
-    /// Whether this sandbox is poisoned
-    poisoned: bool,
+    /// The lifecycle state of this sandbox, including whether it is poisoned
+    status: SandboxStatusHandle,
     pub(super) host_funcs: Arc<Mutex<FunctionRegistry>>,
     pub(crate) mem_mgr: SandboxMemoryManager<HostSharedMemory>,
     vm: HyperlightVm,
+    /// The files mapped with [`MultiUseSandbox::map_file_region`], which
+    /// are unmapped after the VM is dropped
+    #[cfg(target_os = "linux")]
+    mapped_files: Vec<MappedFile>,
+    /// The regions mapped with [`MultiUseSandbox::map_shared_region`],
+    /// kept alive until after the VM is dropped
+    #[cfg(target_os = "linux")]
+    shared_regions: Vec<SharedRegion>,
+    /// The regions of the buffers lent to the guest with
+    /// [`MultiUseSandbox::with_borrowed_buffer`], innermost last
+    #[cfg(target_os = "linux")]
+    lent_buffers: Vec<MemoryRegion>,
     #[cfg(gdb)]
     dbg_mem_access_fn: Arc<Mutex<SandboxMemoryManager<HostSharedMemory>>>,
     /// If the current state of the sandbox has been captured in a snapshot,
     /// that snapshot is stored here.
     snapshot: Option<Arc<Snapshot>>,
+    /// The workspace of the sandbox, removed when the sandbox is dropped
+    workspace: Option<Workspace>,
+    /// The resources of the sandbox reserved against the process quota,
+    /// released when the sandbox is dropped
+    _quota_reservations: Vec<QuotaReservation>,
+    /// How long each phase of the construction of the sandbox took
+    creation_report: CreationReport,
+    /// The configuration the sandbox was created with, which sandboxes
+    /// cloned from it are created with too
+    config: SandboxConfiguration,
+    /// The token the running call was made with by
+    /// [`MultiUseSandbox::call_cancellable`]
+    cancellation: Option<CancellationToken>,
+    /// The streams opened with [`MultiUseSandbox::open_input_stream`] and
+    /// [`MultiUseSandbox::open_output_stream`], set once the host functions
+    /// the guest uses them through are registered
+    streams: Option<Streams>,
+    /// The calls queued with [`MultiUseSandbox::queue_call`], first call
+    /// first
+    call_queue: Vec<FunctionCall>,
+    /// The maximum level of the guest logs, which the guest loaded by
+    /// [`MultiUseSandbox::reload_guest`] is initialised with too
+    max_guest_log_level: Option<LevelFilter>,
+    /// The snapshots taken with [`MultiUseSandbox::create_restore_point`],
+    /// by label
+    restore_points: HashMap<String, Arc<Snapshot>>,
+    /// What the guest was doing when it aborted, if the last guest
+    /// function call made it abort
+    crash_report: Option<GuestCrashReport>,
+    /// The observers added with
+    /// [`UninitializedSandbox::add_observer`], which sandboxes cloned or
+    /// reloaded from this one are observed by too
+    observers: SandboxObservers,
 }
 
 impl MultiUseSandbox {
@@ -109,21 +200,278 @@ impl MultiUseSandbox {
     /// (as a `From` implementation would be)
     #[instrument(skip_all, parent = Span::current(), level = "Trace")]
     pub(super) fn from_uninit(
+        id: u64,
         host_funcs: Arc<Mutex<FunctionRegistry>>,
         mgr: SandboxMemoryManager<HostSharedMemory>,
-        vm: HyperlightVm,
+        mut vm: HyperlightVm,
+        status: SandboxStatusHandle,
+        workspace: Option<Workspace>,
+        quota_reservations: Vec<QuotaReservation>,
+        creation_report: CreationReport,
+        config: SandboxConfiguration,
+        max_guest_log_level: Option<LevelFilter>,
+        observers: SandboxObservers,
         #[cfg(gdb)] dbg_mem_access_fn: Arc<Mutex<SandboxMemoryManager<HostSharedMemory>>>,
     ) -> MultiUseSandbox {
+        status.set(SandboxStatus::Initialized);
+        vm.enable_guest_checkpoints(id);
+        observers.initialized(id);
         Self {
-            id: super::snapshot::SANDBOX_CONFIGURATION_COUNTER.fetch_add(1, Ordering::Relaxed),
-            poisoned: false,
+            id,
+            status,
             host_funcs,
             mem_mgr: mgr,
             vm,
+            #[cfg(target_os = "linux")]
+            mapped_files: Vec::new(),
+            #[cfg(target_os = "linux")]
+            shared_regions: Vec::new(),
+            #[cfg(target_os = "linux")]
+            lent_buffers: Vec::new(),
             #[cfg(gdb)]
             dbg_mem_access_fn,
             snapshot: None,
+            workspace,
+            _quota_reservations: quota_reservations,
+            creation_report,
+            config,
+            cancellation: None,
+            streams: None,
+            call_queue: Vec::new(),
+            max_guest_log_level,
+            restore_points: HashMap::new(),
+            crash_report: None,
+            observers,
+        }
+    }
+
+    /// The id of the sandbox, unique within the process, which the
+    /// [`MetricsSink`] of the sandbox is given with its measurements
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Reports the measurements of the sandbox, such as its guest and
+    /// host function calls, the exits of its vCPU and its restores, to
+    /// `sink` as they happen, or stops reporting them if `sink` is
+    /// `None`. The sink can be shared by several sandboxes, and tells
+    /// them apart by the sandbox id it is given. Sandboxes cloned from
+    /// this one report to the same sink.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::metrics::MetricsRsSink;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// // Export through the recorder installed for the `metrics` crate
+    /// sandbox.set_metrics_sink(Some(Arc::new(MetricsRsSink)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_metrics_sink(&mut self, sink: Option<Arc<dyn MetricsSink>>) {
+        self.vm.set_metrics(MetricsRecorder::new(self.id, sink));
+    }
+
+    /// Returns how long each phase of the construction of the sandbox
+    /// took, from loading the guest binary to running its initialisation
+    /// function, and the hypervisor the sandbox runs on
+    pub fn creation_report(&self) -> &CreationReport {
+        &self.creation_report
+    }
+
+    /// Returns the snapshot taken at the last checkpoint the guest declared
+    /// with `hyperlight_guest_bin::host_comm::checkpoint`, if the
+    /// [`GuestCheckpointPolicy`](crate::sandbox::config::GuestCheckpointPolicy)
+    /// of the sandbox snapshotted any.
+    ///
+    /// When a call fails part way through, restoring this snapshot with
+    /// [`MultiUseSandbox::restore`] rolls the sandbox back to the last
+    /// consistent state the guest reached, rather than to the state
+    /// before the call.
+    pub fn last_checkpoint(&self) -> Option<GuestCheckpoint> {
+        self.vm.last_guest_checkpoint().cloned()
+    }
+
+    /// Returns the instructions retired and the cycles of the guest during
+    /// the last guest function call, including calls that failed. This is
+    /// `None` until a guest function is called, or if the guest is not
+    /// counted, see `SandboxConfiguration::set_guest_perf_counters`.
+    pub fn last_call_metrics(&self) -> Option<SandboxMetrics> {
+        self.vm.last_call_metrics()
+    }
+
+    /// Returns the number of exits of the vCPU to the host since the
+    /// sandbox was created, by reason. Restoring a snapshot does not
+    /// reset the counts, compare two readings to count the exits of a
+    /// single guest function call.
+    pub fn vm_exit_stats(&self) -> VmExitStats {
+        self.vm.exit_stats()
+    }
+
+    /// Returns how much of the memory of the sandbox is committed on the
+    /// host, against how much it was configured with. Restoring a
+    /// snapshot gives back the pages the guest wrote to since.
+    pub fn memory_stats(&self) -> Result<MemoryStats> {
+        self.mem_mgr.memory_stats()
+    }
+
+    /// Returns the resources the sandbox used since it was created, such
+    /// as the time its vCPU ran the guest and the host function calls the
+    /// guest made, which its [`SandboxQuota`] limits
+    pub fn usage(&self) -> SandboxUsage {
+        self.vm.usage(&self.mem_mgr)
+    }
+
+    /// Limits the resources the sandbox uses over its lifetime, from the
+    /// next guest function call on, for instance to share a host fairly
+    /// between tenants. The resources the sandbox already used count
+    /// against the quota.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use hyperlight_host::{HyperlightError, MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::quota::SandboxQuota;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let mut quota = SandboxQuota::new();
+    /// quota.set_max_cpu_time(Some(Duration::from_secs(10)));
+    /// quota.set_max_host_calls(Some(10_000));
+    /// sandbox.set_usage_quota(quota);
+    ///
+    /// match sandbox.call::<()>("HandleRequest", ()) {
+    ///     Err(HyperlightError::SandboxQuotaExceeded(resource)) => {
+    ///         println!("The tenant used up its {resource}");
+    ///     }
+    ///     result => result?,
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_usage_quota(&mut self, quota: SandboxQuota) {
+        self.vm.set_usage_quota(quota);
+    }
+
+    /// Returns the quota set with [`set_usage_quota`](Self::set_usage_quota)
+    pub fn usage_quota(&self) -> SandboxQuota {
+        self.vm.usage_quota()
+    }
+
+    /// Meters the guest function calls by the instructions the guest
+    /// retires, which unlike the time it runs for do not depend on the
+    /// load of the host, for instance to bill tenants. The guest gets
+    /// `fuel` instructions in total across its calls, or is not metered if
+    /// `None`, the default.
+    ///
+    /// Once the guest has used up its fuel, it is paused and the handler
+    /// set with [`on_fuel_exhausted`](Self::on_fuel_exhausted) decides
+    /// whether to refuel it or to abort the call, which then fails with
+    /// [`HyperlightError::FuelExhausted`] and poisons the sandbox. The
+    /// guest can retire a few more instructions than its fuel before it
+    /// is paused, which are taken out of the fuel it is given next.
+    ///
+    /// Fuel is counted with the hardware performance counters of the
+    /// host, only on KVM, and needs the same access to them as
+    /// `SandboxConfiguration::set_guest_perf_counters`, without which
+    /// guest function calls fail.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) -> Result<()> {
+        if fuel.is_some() && self.creation_report.hypervisor() != Some(HypervisorKind::Kvm) {
+            return Err(new_error!("Fuel metering is only supported on KVM"));
         }
+        self.vm.fuel_mut().set(fuel);
+        Ok(())
+    }
+
+    /// Returns the fuel left to the guest, in instructions, or `None` if
+    /// it is not metered, see [`set_fuel`](Self::set_fuel)
+    pub fn fuel(&self) -> Option<u64> {
+        self.vm.fuel().remaining()
+    }
+
+    /// Sets what to do with the guest once it used up its fuel, see
+    /// [`set_fuel`](Self::set_fuel). The handler is called while the
+    /// guest is paused, on the thread that called it. Without a handler,
+    /// the call is aborted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use std::sync::atomic::{AtomicU64, Ordering};
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::fuel::FuelDecision;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// // Bill the tenant for each million instructions, up to its credit
+    /// let credit = Arc::new(AtomicU64::new(10));
+    /// sandbox.set_fuel(Some(1_000_000))?;
+    /// sandbox.on_fuel_exhausted(move || {
+    ///     match credit.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| c.checked_sub(1)) {
+    ///         Ok(_) => FuelDecision::Refuel(1_000_000),
+    ///         Err(_) => FuelDecision::Abort,
+    ///     }
+    /// });
+    /// sandbox.call::<()>("HandleRequest", ())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_fuel_exhausted(&mut self, handler: impl FnMut() -> FuelDecision + Send + 'static) {
+        self.vm.fuel_mut().set_handler(Box::new(handler));
+    }
+
+    /// Returns the memfd backing the main memory of the guest, which
+    /// holds its code, heap and stack, with
+    /// [`MemoryBacking::Memfd`](crate::sandbox::MemoryBacking::Memfd).
+    /// Offset 0 of the memfd is at the guest physical address
+    /// [`SandboxLayoutInfo::base_address`]. Restoring a snapshot of
+    /// another size replaces the memfd.
+    #[cfg(target_os = "linux")]
+    pub fn memory_fd(&self) -> Option<std::os::fd::BorrowedFd<'_>> {
+        self.mem_mgr.shared_mem.memfd()
+    }
+
+    /// Returns the memfd backing the scratch memory of the guest, which
+    /// holds its page tables and the data exchanged with the host, with
+    /// [`MemoryBacking::Memfd`](crate::sandbox::MemoryBacking::Memfd).
+    /// Restoring a snapshot with another scratch size replaces the memfd.
+    #[cfg(target_os = "linux")]
+    pub fn scratch_memory_fd(&self) -> Option<std::os::fd::BorrowedFd<'_>> {
+        self.mem_mgr.scratch_mem.memfd()
+    }
+
+    /// Sets which guest allocations are reported to the memory profiler,
+    /// from the next allocation the guest makes on. This allows turning
+    /// heavy profiling on only while it is needed, in sandboxes that run
+    /// for a long time.
+    ///
+    /// The setting is kept when a snapshot is restored.
+    #[cfg(feature = "mem_profile")]
+    pub fn set_mem_profile_capture(&mut self, capture: MemProfileCapture) -> Result<()> {
+        self.mem_mgr.set_mem_profile_capture(capture)
+    }
+
+    /// Describes the memory layout of the sandbox. A snapshot can only be
+    /// restored into this sandbox if its
+    /// [`Snapshot::layout_info`] is compatible with this layout, see
+    /// [`SandboxLayoutInfo::check_compatible`].
+    pub fn layout_info(&self) -> SandboxLayoutInfo {
+        self.mem_mgr.layout.info()
     }
 
     /// Creates a snapshot of the sandbox's current memory state.
@@ -156,15 +504,16 @@ impl MultiUseSandbox {
     /// ```
     #[instrument(err(Debug), skip_all, parent = Span::current())]
     pub fn snapshot(&mut self) -> Result<Arc<Snapshot>> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
+        self.status.check("take a snapshot")?;
 
         if let Some(snapshot) = &self.snapshot {
             return Ok(snapshot.clone());
         }
         let mapped_regions_iter = self.vm.get_mapped_regions();
-        let mapped_regions_vec: Vec<MemoryRegion> = mapped_regions_iter.cloned().collect();
+        let mapped_regions_vec: Vec<MemoryRegion> = mapped_regions_iter
+            .filter(|region| !is_borrowed_buffer(region))
+            .cloned()
+            .collect();
         let root_pt_gpa = self
             .vm
             .get_root_pt()
@@ -183,11 +532,235 @@ impl MultiUseSandbox {
             sregs,
             entrypoint,
         )?;
+        #[cfg(gdb)]
+        let memory_snapshot = memory_snapshot.with_hw_breakpoints(
+            self.vm
+                .get_snapshot_hw_breakpoints()
+                .map_err(HyperlightVmError::HwBreakpoints)?,
+        );
         let snapshot = Arc::new(memory_snapshot);
         self.snapshot = Some(snapshot.clone());
         Ok(snapshot)
     }
 
+    /// Takes a snapshot of the sandbox, as [`snapshot`](Self::snapshot)
+    /// does, and saves it to the file at `path`. Sandboxes in the same
+    /// state can then be created from the file, in this or another
+    /// process, with [`UninitializedSandbox::from_snapshot_file`].
+    ///
+    /// Fails if host memory is mapped into the sandbox.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    /// sandbox.call_guest_function_by_name::<i32>("SetValue", 42)?;
+    /// sandbox.save_snapshot("warm.hlsnap")?;
+    ///
+    /// // Later, possibly on another host
+    /// let mut warm = UninitializedSandbox::from_snapshot_file("warm.hlsnap", None)?.evolve()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`UninitializedSandbox::from_snapshot_file`]: crate::UninitializedSandbox::from_snapshot_file
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn save_snapshot(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.snapshot()?.save(path)
+    }
+
+    /// Saves the sandbox to the file at `path`, so that the sandbox can
+    /// be carried on with [`from_checkpoint`](Self::from_checkpoint)
+    /// after the host process restarts, for instance to upgrade it,
+    /// instead of being created and warmed up again.
+    ///
+    /// The checkpoint holds a snapshot of the sandbox, as saved by
+    /// [`save_snapshot`](Self::save_snapshot), with the layout of its
+    /// memory and the special registers of its vCPU, along with the names
+    /// and signatures of its host functions. The host functions
+    /// themselves and the configuration of the sandbox are not saved.
+    ///
+    /// Fails if host memory is mapped into the sandbox.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, Result, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::func::HostFunctions;
+    /// # fn example() -> Result<()> {
+    /// let host_functions = HostFunctions::new()
+    ///     .register_host_fn("Add", |a: i32, b: i32| -> Result<i32> { Ok(a + b) });
+    /// let mut sandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?;
+    /// sandbox.register_host_functions(&host_functions)?;
+    /// let mut sandbox = sandbox.evolve()?;
+    /// sandbox.call::<()>("WarmUp", ())?;
+    /// sandbox.checkpoint("sandbox.hlckpt")?;
+    ///
+    /// // After the host process restarted
+    /// let mut sandbox = MultiUseSandbox::from_checkpoint("sandbox.hlckpt", &host_functions, None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn checkpoint(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.status.check("checkpoint the sandbox")?;
+        let snapshot = self.snapshot()?;
+        checkpoint_file::save(path, &snapshot, self.host_function_details())
+    }
+
+    /// Restores a sandbox saved with [`checkpoint`](Self::checkpoint),
+    /// possibly by another process, with the host functions of
+    /// `host_functions`.
+    ///
+    /// Every host function the sandbox had when the checkpoint was taken
+    /// must be in `host_functions` with the same signature, since the
+    /// guest may call any of them. The memory sizes of `cfg` are replaced
+    /// by those of the checkpoint.
+    #[instrument(err(Debug), skip(path, host_functions, cfg), parent = Span::current())]
+    pub fn from_checkpoint(
+        path: impl AsRef<Path>,
+        host_functions: &HostFunctions,
+        cfg: Option<SandboxConfiguration>,
+    ) -> Result<MultiUseSandbox> {
+        let (snapshot, saved) = checkpoint_file::load(path)?;
+        checkpoint_file::restore(snapshot, &saved, host_functions, cfg)
+    }
+
+    /// Carries on with a sandbox moved from another host or process with
+    /// a [`SandboxMigration`](super::migration::SandboxMigration),
+    /// received from `transport`, with the host functions of
+    /// `host_functions`. These must match the host functions of the
+    /// sandbox, as for [`from_checkpoint`](Self::from_checkpoint).
+    #[instrument(err(Debug), skip(transport, host_functions, cfg), parent = Span::current())]
+    pub fn receive_migration(
+        transport: impl Read,
+        host_functions: &HostFunctions,
+        cfg: Option<SandboxConfiguration>,
+    ) -> Result<MultiUseSandbox> {
+        migration::receive(transport, host_functions, cfg)
+    }
+
+    /// Creates an independent sandbox in the current state of this one,
+    /// for example to handle each request in a fresh child of a warmed-up
+    /// parent sandbox.
+    ///
+    /// The child is created from a snapshot of this sandbox, as taken by
+    /// [`snapshot`](Self::snapshot), with the same configuration and host
+    /// functions. Its memory is mapped copy-on-write from the snapshot
+    /// where the platform allows it, so creating it copies little. The
+    /// host functions are shared with this sandbox, along with any state
+    /// they capture.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut parent: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    /// parent.call::<()>("WarmUp", ())?;
+    ///
+    /// let mut child = parent.clone_sandbox()?;
+    /// let response: String = child.call("Handle", "request".to_string())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn clone_sandbox(&mut self) -> Result<MultiUseSandbox> {
+        self.status.check("clone the sandbox")?;
+        let snapshot = self.snapshot()?;
+        let mut host_funcs = self
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .clone_functions();
+        // The streams of this sandbox are not shared with the clone
+        host_funcs.unregister_host_function(HOST_STREAM_READ_FUNCTION);
+        host_funcs.unregister_host_function(HOST_STREAM_WRITE_FUNCTION);
+        let mut child = UninitializedSandbox::from_snapshot(
+            snapshot,
+            Some(self.config),
+            #[cfg(any(crashdump, gdb))]
+            self.vm.binary_path(),
+        )?;
+        child.host_funcs = Arc::new(Mutex::new(host_funcs));
+        child.observers = self.observers.clone();
+        let mut child = child.evolve()?;
+        child.set_metrics_sink(self.vm.metrics().sink().cloned());
+        Ok(child)
+    }
+
+    /// Replaces the guest of the sandbox with `guest_binary`, for example
+    /// to pick up a rebuilt guest during development or to roll out a new
+    /// version of it, without wiring up the host side again.
+    ///
+    /// The memory of the sandbox is discarded, and the new guest is loaded
+    /// and initialised with the configuration of the sandbox. The sandbox
+    /// keeps its host functions, along with their policies and middleware,
+    /// its streams, its workspace, its debug event subscriptions, its
+    /// metrics sink, its observers, its usage quota, its fuel and the
+    /// maximum level of its guest logs, while its usage starts over. It gets a new
+    /// [`id`](Self::id), so snapshots taken before the reload can no
+    /// longer be restored, its restore points are removed, and the calls
+    /// queued with [`queue_call`](Self::queue_call) are dropped.
+    ///
+    /// A poisoned sandbox can be reloaded too. Fails if host memory is
+    /// mapped into the sandbox. If the new guest fails to load or
+    /// initialise, the sandbox keeps the previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// // After the guest was rebuilt
+    /// sandbox.reload_guest(GuestBinary::FilePath("guest.bin".into()))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn reload_guest(&mut self, guest_binary: GuestBinary) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        if !self.mapped_files.is_empty()
+            || !self.shared_regions.is_empty()
+            || !self.lent_buffers.is_empty()
+        {
+            return Err(new_error!(
+                "The guest of a sandbox with host memory mapped into it cannot be reloaded"
+            ));
+        }
+        let mut sandbox = UninitializedSandbox::new(guest_binary, Some(self.config))?;
+        sandbox.host_funcs = self.host_funcs.clone();
+        sandbox.debug_events = self.vm.debug_events().clone();
+        sandbox.max_guest_log_level = self.max_guest_log_level;
+        sandbox.observers = self.observers.clone();
+        let mut reloaded = sandbox.evolve()?;
+        reloaded.set_metrics_sink(self.vm.metrics().sink().cloned());
+        reloaded.set_usage_quota(self.vm.usage_quota());
+        std::mem::swap(reloaded.vm.fuel_mut(), self.vm.fuel_mut());
+        // Taken rather than cloned, since dropping the previous sandbox
+        // closes its workspace
+        reloaded.workspace = self.workspace.take();
+        reloaded.streams = self.streams.take();
+        *self = reloaded;
+        Ok(())
+    }
+
     /// Restores the sandbox's memory to a previously captured snapshot state.
     ///
     /// The snapshot must have been created from this same sandbox instance.
@@ -292,6 +865,10 @@ impl MultiUseSandbox {
             return Err(SnapshotSandboxMismatch);
         }
 
+        // Restoring is allowed from any state, as this is how a sandbox
+        // recovers. Until the restore completes, the sandbox is unusable.
+        self.status.set(SandboxStatus::ResettingSnapshot);
+
         let (gsnapshot, gscratch) = self.mem_mgr.restore_snapshot(&snapshot)?;
         if let Some(gsnapshot) = gsnapshot {
             self.vm
@@ -312,15 +889,37 @@ impl MultiUseSandbox {
         self.vm
             .reset_vcpu(snapshot.root_pt_gpa(), sregs)
             .map_err(|e| {
-                self.poisoned = true;
+                self.status.set(SandboxStatus::Poisoned);
                 HyperlightVmError::Restore(e)
             })?;
+        #[cfg(gdb)]
+        if let Some(hw_breakpoints) = snapshot.hw_breakpoints() {
+            self.vm
+                .restore_hw_breakpoints(hw_breakpoints)
+                .map_err(|e| {
+                    self.status.set(SandboxStatus::Poisoned);
+                    HyperlightVmError::HwBreakpoints(e)
+                })?;
+        }
 
         self.vm.set_stack_top(snapshot.stack_top_gva());
         self.vm.set_entrypoint(snapshot.entrypoint());
 
-        let current_regions: HashSet<_> = self.vm.get_mapped_regions().cloned().collect();
-        let snapshot_regions: HashSet<_> = snapshot.regions().iter().cloned().collect();
+        // The buffers lent to the guest stay mapped until they are given
+        // back, and are never mapped again from a snapshot, such as one
+        // taken at a guest checkpoint while they were lent
+        let current_regions: HashSet<_> = self
+            .vm
+            .get_mapped_regions()
+            .filter(|region| !is_borrowed_buffer(region))
+            .cloned()
+            .collect();
+        let snapshot_regions: HashSet<_> = snapshot
+            .regions()
+            .iter()
+            .filter(|region| !is_borrowed_buffer(region))
+            .cloned()
+            .collect();
 
         let regions_to_unmap = current_regions.difference(&snapshot_regions);
         let regions_to_map = snapshot_regions.difference(&current_regions);
@@ -337,6 +936,10 @@ impl MultiUseSandbox {
             unsafe { self.vm.map_region(region) }.map_err(HyperlightVmError::MapRegion)?;
         }
 
+        self.vm.metrics().record(SandboxMetric::MemoryRestored {
+            bytes: snapshot.mem_size() as u64,
+        });
+
         // The restored snapshot is now our most current snapshot
         self.snapshot = Some(snapshot.clone());
 
@@ -349,11 +952,106 @@ impl MultiUseSandbox {
         //    - All leaked heap allocations (memory is restored to snapshot state)
         //    - All corrupted data structures (overwritten with consistent snapshot data)
         //    - All inconsistent global state (reset to snapshot values)
-        self.poisoned = false;
+        self.status.set(SandboxStatus::Initialized);
+        self.observers.restored(self.id);
+
+        Ok(())
+    }
 
+    /// Restores the sandbox to the snapshot `id` of `chain`, rebuilding
+    /// the snapshot from the pages stored in the chain, as
+    /// [`restore()`](Self::restore) would restore it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::snapshot_chain::SnapshotChain;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let mut chain = SnapshotChain::new(sandbox.snapshot()?);
+    /// sandbox.call_guest_function_by_name::<i32>("SetValue", 1)?;
+    /// let one = chain.push(&sandbox.snapshot()?)?;
+    /// sandbox.call_guest_function_by_name::<i32>("SetValue", 2)?;
+    /// chain.push(&sandbox.snapshot()?)?;
+    ///
+    /// sandbox.restore_to(&chain, one)?;
+    /// let value: i32 = sandbox.call_guest_function_by_name("GetValue", ())?;
+    /// assert_eq!(value, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn restore_to(&mut self, chain: &SnapshotChain, id: SnapshotId) -> Result<()> {
+        self.restore(chain.snapshot(id)?)
+    }
+
+    /// Takes a snapshot of the sandbox, as [`snapshot`](Self::snapshot)
+    /// does, and keeps it as the restore point `label`, which the sandbox
+    /// can later be rolled back to with [`rollback_to`](Self::rollback_to).
+    /// A restore point with the same label is replaced.
+    ///
+    /// The restore point holds the memory of the sandbox and the special
+    /// registers of its vCPU, so guest calls made after it can be undone
+    /// as a whole, such as when a transaction made of several calls fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// sandbox.create_restore_point("before-transfer")?;
+    /// let result = sandbox
+    ///     .call::<i32>("Withdraw", 10)
+    ///     .and_then(|_| sandbox.call::<i32>("Deposit", 10));
+    /// if result.is_err() {
+    ///     sandbox.rollback_to("before-transfer")?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn create_restore_point(&mut self, label: impl Into<String>) -> Result<()> {
+        let snapshot = self.snapshot()?;
+        self.restore_points.insert(label.into(), snapshot);
         Ok(())
     }
 
+    /// Rolls the sandbox back to the restore point `label`, as
+    /// [`restore()`](Self::restore) would restore its snapshot. The
+    /// restore point is kept, so the sandbox can be rolled back to it
+    /// again. This also recovers a poisoned sandbox.
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn rollback_to(&mut self, label: &str) -> Result<()> {
+        let snapshot = self
+            .restore_points
+            .get(label)
+            .cloned()
+            .ok_or_else(|| new_error!("The sandbox has no restore point {}", label))?;
+        self.restore(snapshot)
+    }
+
+    /// Removes the restore point `label`, freeing its snapshot once it is
+    /// not used anymore, returning whether there was one
+    pub fn remove_restore_point(&mut self, label: &str) -> bool {
+        self.restore_points.remove(label).is_some()
+    }
+
+    /// The labels of the restore points of the sandbox, in no particular
+    /// order
+    pub fn restore_points(&self) -> impl Iterator<Item = &str> {
+        self.restore_points.keys().map(String::as_str)
+    }
+
     /// Calls a guest function by name with the specified arguments.
     ///
     /// Changes made to the sandbox during execution are *not* persisted.
@@ -401,9 +1099,7 @@ impl MultiUseSandbox {
         func_name: &str,
         args: impl ParameterTuple,
     ) -> Result<Output> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
+        self.status.check("call a guest function")?;
         let snapshot = self.snapshot()?;
         let res = self.call(func_name, args);
         self.restore(snapshot)?;
@@ -492,9 +1188,7 @@ impl MultiUseSandbox {
         func_name: &str,
         args: impl ParameterTuple,
     ) -> Result<Output> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
+        self.status.check("call a guest function")?;
         // Reset snapshot since we are mutating the sandbox state
         self.snapshot = None;
         maybe_time_and_emit_guest_call(func_name, || {
@@ -510,27 +1204,305 @@ impl MultiUseSandbox {
         })
     }
 
-    /// Maps a region of host memory into the sandbox address space.
+    /// Calls a guest function by name with the specified arguments, as
+    /// [`call`](Self::call) does, unless `token` is cancelled before the
+    /// call returns.
     ///
-    /// The base address and length must meet platform alignment requirements
-    /// (typically page-aligned). The `region_type` field is ignored as guest
-    /// page table entries are not created.
+    /// A snapshot is taken before the call. Cancelling `token` from
+    /// another thread interrupts the call if it is running, and restores
+    /// that snapshot, whether the call was interrupted or had completed,
+    /// after which the call fails with
+    /// [`HyperlightError::Cancelled`](crate::HyperlightError::Cancelled)
+    /// and the sandbox is usable again. A call made with a token that is
+    /// already cancelled fails without running.
     ///
-    /// ## Poisoned Sandbox
+    /// # Examples
     ///
-    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
-    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    /// ```no_run
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// # use hyperlight_host::{HyperlightError, MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::CancellationToken;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
     ///
-    /// # Safety
+    /// let token = CancellationToken::new();
+    /// let canceller = token.clone();
+    /// thread::spawn(move || {
+    ///     thread::sleep(Duration::from_secs(1));
+    ///     canceller.cancel();
+    /// });
+    ///
+    /// let result = sandbox.call_cancellable::<()>("Spin", (), &token);
+    /// assert!(matches!(result, Err(HyperlightError::Cancelled)));
+    /// // The sandbox was rolled back and can still be called
+    /// let message: String = sandbox.call("Echo", "Hello".to_string())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self, args, token), parent = Span::current())]
+    pub fn call_cancellable<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+        token: &CancellationToken,
+    ) -> Result<Output> {
+        self.status.check("call a guest function")?;
+        if token.is_cancelled() {
+            return Err(HyperlightError::Cancelled);
+        }
+        let snapshot = self.snapshot()?;
+        self.cancellation = Some(token.clone());
+        let res = self.call(func_name, args);
+        self.cancellation = None;
+        token.detach();
+        if token.is_cancelled() {
+            self.restore(snapshot)?;
+            return Err(HyperlightError::Cancelled);
+        }
+        res
+    }
+
+    /// Calls a guest function by name with the specified arguments, as
+    /// [`call`](Self::call) does, unless it does not complete within
+    /// `deadline`.
+    ///
+    /// A call still running when the deadline passes is interrupted, the
+    /// sandbox is restored to its state before the call, as with
+    /// [`call_cancellable`](Self::call_cancellable), and the call fails
+    /// with [`HyperlightError::DeadlineExceeded`](crate::HyperlightError::DeadlineExceeded).
+    /// The sandbox is usable again after a timeout, unlike after killing
+    /// the call with its [`InterruptHandle`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use hyperlight_host::{HyperlightError, MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let result = sandbox.call_with_deadline::<()>("Spin", (), Duration::from_millis(100));
+    /// assert!(matches!(result, Err(HyperlightError::DeadlineExceeded(_))));
+    /// let message: String = sandbox.call("Echo", "Hello".to_string())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self, args), parent = Span::current())]
+    pub fn call_with_deadline<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+        deadline: Duration,
+    ) -> Result<Output> {
+        let token = CancellationToken::new();
+        let timer = Deadline::start(deadline, token.clone())?;
+        let res = self.call_cancellable(func_name, args, &token);
+        drop(timer);
+        match res {
+            Err(HyperlightError::Cancelled) => Err(HyperlightError::DeadlineExceeded(deadline)),
+            res => res,
+        }
+    }
+
+    /// Binds the guest function `func_name` with the argument types `Args`
+    /// and return type `Output`, for calling it repeatedly through the
+    /// returned [`BoundFunction`].
+    ///
+    /// Binding looks the function up in the functions the guest declares,
+    /// see [`guest_functions`](Self::guest_functions), and fails with
+    /// [`HyperlightError::GuestFunctionNotFound`] if the guest does not
+    /// provide it, or with
+    /// [`HyperlightError::GuestFunctionSignatureMismatch`] if `Args` and
+    /// `Output` are not the types the guest declares for it. The calls
+    /// through the bound function then do not need to be checked again.
+    ///
+    /// The bound function keeps the buffer the call is serialized into
+    /// across calls, so each call only serializes its arguments, without
+    /// allocating a buffer. This reduces the overhead of calling a
+    /// function in a tight loop. A bound function can be used with any
+    /// sandbox running the same guest.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let mut add = sandbox.bind_function::<(i32, i32), i32>("Add")?;
+    /// for i in 0..1000 {
+    ///     let sum = add.call(&mut sandbox, (i, 1))?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn bind_function<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+    ) -> Result<BoundFunction<Args, Output>> {
+        let function = self
+            .guest_functions()?
+            .into_iter()
+            .find(|function| function.name == func_name)
+            .ok_or_else(|| HyperlightError::GuestFunctionNotFound(func_name.to_string()))?;
+        if !function.matches::<Args, Output>() {
+            return Err(HyperlightError::GuestFunctionSignatureMismatch(
+                func_name.to_string(),
+                format_signature(Args::TYPE, Output::TYPE),
+                format_signature(&function.parameter_types, function.return_type),
+            ));
+        }
+        Ok(BoundFunction {
+            name: func_name.to_string(),
+            builder: FlatBufferBuilder::new(),
+            _types: PhantomData,
+        })
+    }
+
+    /// Queues a call of the guest function `func_name` with `args`,
+    /// returning `Output`, to run with the other queued calls by
+    /// [`run_queued_calls`](Self::run_queued_calls).
+    ///
+    /// The call is serialized when the queue is run, and is only checked
+    /// by the guest then.
+    pub fn queue_call<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+    ) {
+        self.call_queue.push(FunctionCall::new(
+            func_name.to_string(),
+            Some(args.into_value()),
+            FunctionCallType::Guest,
+            Output::TYPE,
+        ));
+    }
+
+    /// The number of calls queued with [`queue_call`](Self::queue_call)
+    /// since the queue was last run
+    pub fn queued_calls(&self) -> usize {
+        self.call_queue.len()
+    }
+
+    /// Runs the calls queued with [`queue_call`](Self::queue_call) in
+    /// order, and empties the queue.
+    ///
+    /// The calls run back to back in the guest, which only exits to the
+    /// host for the host functions they call, so a batch of small calls
+    /// costs a single VM entry and exit. The queued calls are passed to
+    /// the guest through the input buffer, and their results through the
+    /// output buffer, which must be large enough to hold all of them.
+    ///
+    /// Returns the result of each call, in the order they were queued. A
+    /// call failing does not stop the calls after it. The queue fails as
+    /// a whole, like [`call`](Self::call) does, if the guest is not run to
+    /// completion, which can poison the sandbox.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::func::ReturnValue;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// for i in 0..100 {
+    ///     sandbox.queue_call::<i32>("Double", i);
+    /// }
+    /// for result in sandbox.run_queued_calls()? {
+    ///     let ReturnValue::Int(doubled) = result? else { unreachable!() };
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn run_queued_calls(&mut self) -> Result<Vec<Result<ReturnValue>>> {
+        self.status.check("call a guest function")?;
+        let calls = std::mem::take(&mut self.call_queue);
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Reset snapshot since we are mutating the sandbox state
+        self.snapshot = None;
+
+        // The first call is pushed last, so that the guest pops it first
+        let mut builder = FlatBufferBuilder::new();
+        for call in calls.iter().rev() {
+            builder.reset();
+            if let Err(e) = self
+                .mem_mgr
+                .write_guest_function_call(call.encode(&mut builder))
+            {
+                self.mem_mgr.clear_io_buffers();
+                return Err(e);
+            }
+        }
+        maybe_time_and_emit_guest_call(CALL_QUEUE_FUNCTION, || {
+            self.call_guest_function_with_builder(
+                CALL_QUEUE_FUNCTION,
+                ReturnType::Void,
+                vec![ParameterValue::UInt(calls.len() as u32)],
+                &mut builder,
+            )
+        })?;
+
+        // The result of the last call is on top of the output buffer
+        let mut results = Vec::with_capacity(calls.len());
+        for _ in 0..calls.len() {
+            let result = self.mem_mgr.get_guest_function_call_result();
+            let result = match result {
+                Ok(result) => result.into_inner().map_err(|guest_error| {
+                    metrics::counter!(
+                        METRIC_GUEST_ERROR,
+                        METRIC_GUEST_ERROR_LABEL_CODE => (guest_error.code as u64).to_string()
+                    )
+                    .increment(1);
+                    HyperlightError::GuestError(guest_error.code, guest_error.message)
+                }),
+                Err(e) => {
+                    self.mem_mgr.clear_io_buffers();
+                    return Err(e);
+                }
+            };
+            results.push(result);
+        }
+        results.reverse();
+        Ok(results)
+    }
+
+    /// Maps a region of host memory into the sandbox address space.
+    ///
+    /// The base address and length must meet platform alignment requirements
+    /// (typically page-aligned). The `region_type` field is ignored as guest
+    /// page table entries are not created.
+    ///
+    /// ## Poisoned Sandbox
+    ///
+    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
+    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    ///
+    /// # Safety
     ///
     /// The caller must ensure the host memory region remains valid and unmodified
     /// for the lifetime of `self`.
     #[instrument(err(Debug), skip(self, rgn), parent = Span::current())]
     #[cfg(target_os = "linux")]
     unsafe fn map_region(&mut self, rgn: &MemoryRegion) -> Result<()> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
+        self.status.check("map a memory region")?;
         if rgn.flags.contains(MemoryRegionFlags::WRITE) {
             // TODO: Implement support for writable mappings, which
             // need to be registered with the memory manager so that
@@ -554,9 +1526,7 @@ impl MultiUseSandbox {
     /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
     #[instrument(err(Debug), skip(self, _fp, _guest_base), parent = Span::current())]
     pub fn map_file_cow(&mut self, _fp: &Path, _guest_base: u64) -> Result<u64> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
+        self.status.check("map a file")?;
         #[cfg(windows)]
         log_then_return!("mmap'ing a file into the guest is not yet supported on Windows");
         #[cfg(unix)]
@@ -591,6 +1561,188 @@ impl MultiUseSandbox {
         }
     }
 
+    /// Maps the file at `path` read-only into the guest at `guest_base`,
+    /// so that large assets, such as models, modules or scripts, are read
+    /// by the guest without being copied through function parameters.
+    ///
+    /// `flags` may contain [`MemoryRegionFlags::READ`] and
+    /// [`MemoryRegionFlags::EXECUTE`], but not [`MemoryRegionFlags::WRITE`].
+    /// The file should not be modified while it is mapped. The mapping is
+    /// kept until the sandbox is dropped, so that restoring a snapshot
+    /// taken while the file was mapped maps it again.
+    ///
+    /// Returns the length of the mapping in bytes, which is the size of
+    /// the file rounded up to a page.
+    ///
+    /// ## Poisoned Sandbox
+    ///
+    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
+    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    #[instrument(err(Debug), skip(self, path, guest_base), parent = Span::current())]
+    #[cfg(target_os = "linux")]
+    pub fn map_file_region(
+        &mut self,
+        path: &Path,
+        guest_base: u64,
+        flags: MemoryRegionFlags,
+    ) -> Result<u64> {
+        self.status.check("map a file")?;
+        if flags.contains(MemoryRegionFlags::WRITE) {
+            log_then_return!("Files can only be mapped read-only, not {}", flags);
+        }
+        if guest_base % page_size::get() as u64 != 0 {
+            log_then_return!(
+                "The guest address {:#x} of {} is not page aligned",
+                guest_base,
+                path.display()
+            );
+        }
+        let file = std::fs::File::open(path)?;
+        let mapping = MappedFile::new(&file, flags)
+            .map_err(|e| new_error!("Could not map {}: {}", path.display(), e))?;
+        let size = mapping.size;
+        // Safety: the mapping is owned by the sandbox, and only unmapped
+        // when the sandbox is dropped, after its VM
+        unsafe {
+            self.map_region(&MemoryRegion {
+                host_region: mapping.base as usize..mapping.base as usize + size,
+                guest_region: guest_base as usize..guest_base as usize + size,
+                flags,
+                region_type: MemoryRegionType::Heap,
+            })?;
+        }
+        self.mapped_files.push(mapping);
+        Ok(size as u64)
+    }
+
+    /// Maps `region` into the guest at `guest_base`, read-write if `flags`
+    /// contains [`MemoryRegionFlags::WRITE`] and read-only otherwise. The
+    /// same region can be mapped into several sandboxes, so that the
+    /// guests exchange data through it without calls to the host.
+    ///
+    /// Unlike the rest of the memory of the sandbox, what the guest writes
+    /// to the region is not rolled back when a snapshot is restored.
+    ///
+    /// ## Poisoned Sandbox
+    ///
+    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
+    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    #[instrument(err(Debug), skip(self, region, guest_base), parent = Span::current())]
+    #[cfg(target_os = "linux")]
+    pub fn map_shared_region(
+        &mut self,
+        region: &SharedRegion,
+        guest_base: u64,
+        flags: MemoryRegionFlags,
+    ) -> Result<()> {
+        self.status.check("map a shared region")?;
+        if guest_base % page_size::get() as u64 != 0 {
+            log_then_return!(
+                "The guest address {:#x} of the shared region {} is not page aligned",
+                guest_base,
+                region.name()
+            );
+        }
+        let base = region.base_addr();
+        let size = region.size();
+        // Reset snapshot since we are mutating the sandbox state
+        self.snapshot = None;
+        // Safety: a clone of the region is kept by the sandbox until after
+        // its VM is dropped
+        unsafe {
+            self.vm.map_region(&MemoryRegion {
+                host_region: base..base + size,
+                guest_region: guest_base as usize..guest_base as usize + size,
+                flags,
+                region_type: MemoryRegionType::Heap,
+            })
+        }
+        .map_err(HyperlightVmError::MapRegion)?;
+        self.mem_mgr.mapped_rgns += 1;
+        self.shared_regions.push(region.clone());
+        Ok(())
+    }
+
+    /// Lends `buffer` to the guest while `f` runs, mapping it into the
+    /// guest rather than copying it through the input and output buffers,
+    /// and returns the result of `f`.
+    ///
+    /// `f` is given a [`LendingSandbox`] to call the sandbox through and
+    /// the guest physical address of the buffer, which it passes to the
+    /// guest functions it calls, usually along with the length of the
+    /// buffer. The guest maps the buffer with
+    /// `hyperlight_guest_bin::borrowed_buffer`. What the guest writes to a
+    /// read-write buffer is in the buffer as soon as it writes it.
+    ///
+    /// The buffer is unmapped from the guest when `f` returns, including
+    /// when it panics. If the buffer cannot be unmapped, the process is
+    /// aborted, since the guest could otherwise still reach the buffer
+    /// after its borrow ended.
+    ///
+    /// The buffer is never part of a snapshot: restoring one while the
+    /// buffer is lent leaves it mapped, and restoring one once it was
+    /// given back does not map it again.
+    ///
+    /// ```no_run
+    /// # use std::alloc::{Layout, alloc_zeroed};
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::BorrowedGuestBuffer;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    /// let layout = Layout::from_size_align(16 << 20, 4096)?;
+    /// let payload = unsafe { std::slice::from_raw_parts_mut(alloc_zeroed(layout), layout.size()) };
+    /// let len = payload.len() as u64;
+    /// let checksum: u64 = sandbox.with_borrowed_buffer(
+    ///     BorrowedGuestBuffer::read_only(payload)?,
+    ///     |sandbox, address| sandbox.call("Checksum", (address, len)),
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Poisoned Sandbox
+    ///
+    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
+    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    #[instrument(err(Debug), skip(self, buffer, f), parent = Span::current())]
+    #[cfg(target_os = "linux")]
+    pub fn with_borrowed_buffer<T>(
+        &mut self,
+        buffer: BorrowedGuestBuffer<'_>,
+        f: impl FnOnce(&mut LendingSandbox<'_>, u64) -> Result<T>,
+    ) -> Result<T> {
+        self.status.check("lend a buffer")?;
+        let guest_base = self
+            .lent_buffers
+            .last()
+            .map_or(BORROWED_BUFFER_BASE as usize, |region| {
+                region.guest_region.end
+            });
+        let guest_end = guest_base + buffer.len();
+        if guest_end as u64 > BORROWED_BUFFER_BASE + BORROWED_BUFFER_WINDOW_SIZE {
+            log_then_return!(
+                "The buffers lent to the guest cannot exceed {:#x} bytes",
+                BORROWED_BUFFER_WINDOW_SIZE
+            );
+        }
+        let region = MemoryRegion {
+            host_region: buffer.host_region(),
+            guest_region: guest_base..guest_end,
+            flags: buffer.flags(),
+            region_type: MemoryRegionType::Heap,
+        };
+        // Safety: the buffer is borrowed until this function returns,
+        // and the guard below unmaps it before then, even if `f` panics
+        unsafe { self.vm.map_region(&region) }.map_err(HyperlightVmError::MapRegion)?;
+        self.lent_buffers.push(region);
+
+        let mut lending = LendingSandbox { sandbox: self };
+        f(&mut lending, guest_base as u64)
+    }
+
     /// Calls a guest function with type-erased parameters and return values.
     ///
     /// This function is used for fuzz testing parameter and return type handling.
@@ -607,9 +1759,223 @@ impl MultiUseSandbox {
         ret_type: ReturnType,
         args: Vec<ParameterValue>,
     ) -> Result<ReturnValue> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
+        self.call_erased(func_name, ret_type, args)
+    }
+
+    /// Registers a host function that the guest can call, as
+    /// [`UninitializedSandbox::register`] does before the sandbox is
+    /// initialized, replacing any host function with the same name.
+    ///
+    /// The guest looks host functions up by name each time it calls one,
+    /// so the function can be called from the next guest function call
+    /// on. This lets plugins add capabilities to a running sandbox.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// sandbox.register("GetTime", || -> hyperlight_host::Result<i64> { Ok(0) })?;
+    /// // ... guest calls that use GetTime ...
+    /// sandbox.unregister("GetTime");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn register<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: impl AsRef<str>,
+        host_func: impl Into<HostFunction<Output, Args>>,
+    ) -> Result<()> {
+        let entry = FunctionEntry {
+            function: host_func.into().into(),
+            parameter_types: Args::TYPE,
+            return_type: Output::TYPE,
+            check_signature: false,
+            allows_callbacks: false,
+        };
+        self.lock_host_funcs()
+            .register_host_function(name.as_ref().to_string(), entry)
+    }
+
+    /// Registers all the host functions of `host_functions`, see
+    /// [`register`](Self::register)
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn register_host_functions(&mut self, host_functions: &HostFunctions) -> Result<()> {
+        let mut registry = self.lock_host_funcs();
+        for (name, entry) in host_functions.entries() {
+            registry.register_host_function(name.clone(), entry.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Removes the host function `name`, returning whether it was
+    /// registered. The guest function calls made from then on fail to
+    /// call it.
+    #[instrument(skip(self), parent = Span::current())]
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.lock_host_funcs().unregister_host_function(name)
+    }
+
+    /// The names of the host functions the guest can call, sorted
+    pub fn host_function_names(&self) -> Vec<String> {
+        self.lock_host_funcs().function_names()
+    }
+
+    /// Opens a stream the guest reads from `reader` in chunks, with
+    /// `hyperlight_guest_bin::stream::HostStreamReader`, to pass it a
+    /// payload larger than the input buffer of the sandbox.
+    ///
+    /// The guest is given the [`StreamId::as_u32`] of the stream, usually
+    /// as an argument of the guest function call. The stream stays open
+    /// across calls, and across restores of the sandbox, until it is
+    /// closed with [`close_stream`](Self::close_stream).
+    ///
+    /// ```no_run
+    /// # use std::io::Cursor;
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    /// let input = sandbox.open_input_stream(Cursor::new(vec![0u8; 1 << 20]))?;
+    /// let output = sandbox.open_output_stream(Vec::new())?;
+    /// let copied: u64 = sandbox.call("StreamCopy", (input.as_u32(), output.as_u32()))?;
+    /// sandbox.close_stream(input)?;
+    /// sandbox.close_stream(output)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn open_input_stream(&mut self, reader: impl Read + Send + 'static) -> Result<StreamId> {
+        self.streams()?.open_input(reader)
+    }
+
+    /// Opens a stream the guest writes to `writer` in chunks, with
+    /// `hyperlight_guest_bin::stream::HostStreamWriter`, to return a
+    /// payload larger than the output buffer of the sandbox. See
+    /// [`open_input_stream`](Self::open_input_stream).
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn open_output_stream(&mut self, writer: impl Write + Send + 'static) -> Result<StreamId> {
+        self.streams()?.open_output(writer)
+    }
+
+    /// Closes the stream `id`, flushing it if the guest writes to it.
+    /// The guest fails to use the stream from then on.
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn close_stream(&mut self, id: StreamId) -> Result<()> {
+        self.streams()?.close(id)
+    }
+
+    /// The streams of the sandbox, registering the host functions the
+    /// guest uses them through the first time
+    fn streams(&mut self) -> Result<Streams> {
+        if let Some(streams) = &self.streams {
+            return Ok(streams.clone());
         }
+        let streams = Streams::default();
+        let reader = streams.clone();
+        self.register(HOST_STREAM_READ_FUNCTION, move |id: u32, max_len: u32| {
+            reader.read(id, max_len)
+        })?;
+        let writer = streams.clone();
+        self.register(HOST_STREAM_WRITE_FUNCTION, move |id: u32, data: Vec<u8>| {
+            writer.write(id, &data)
+        })?;
+        self.streams = Some(streams.clone());
+        Ok(streams)
+    }
+
+    /// Sets whether the guest may call the host function `name`, from
+    /// the next guest function call on, see [`HostFunctionPolicy`]
+    pub fn set_host_function_policy(&mut self, name: &str, policy: HostFunctionPolicy) {
+        self.lock_host_funcs().set_policy(name, policy);
+    }
+
+    /// Wraps the host function calls made by the guest from the next
+    /// guest function call on in `middleware`, inside the layers added
+    /// before. See [`HostCallMiddleware`].
+    pub fn add_host_call_middleware(&mut self, middleware: impl HostCallMiddleware + 'static) {
+        self.lock_host_funcs().add_middleware(Arc::new(middleware));
+    }
+
+    /// Calls the guest function `func_name`, recording the call along with
+    /// the host function calls it makes, so that it can be reproduced with
+    /// [`replay_call`](Self::replay_call), for instance to debug offline a
+    /// call that failed in production.
+    ///
+    /// Returns the outcome of the call along with its record. If the host
+    /// function calls of the sandbox are recorded with
+    /// [`UninitializedSandbox::record_host_calls`](crate::UninitializedSandbox::record_host_calls),
+    /// the calls made during this call are recorded there too.
+    #[instrument(skip(self, args), parent = Span::current())]
+    pub fn record_call(
+        &mut self,
+        func_name: &str,
+        ret_type: ReturnType,
+        args: Vec<ParameterValue>,
+    ) -> (Result<ReturnValue>, GuestCallRecord) {
+        let (mode, recorder) = HostCallMode::record();
+        let previous = self.lock_host_funcs().replace_mode(mode);
+        let result = self.call_erased(func_name, ret_type, args.clone());
+        let host_calls = recorder.fixture();
+        previous.extend_recording(host_calls.calls().iter().cloned());
+        self.lock_host_funcs().set_mode(previous);
+
+        let record = GuestCallRecord {
+            function_name: func_name.to_string(),
+            args,
+            return_type: ret_type,
+            host_calls,
+            result: result.as_ref().map_err(|e| e.to_string()).cloned(),
+        };
+        (result, record)
+    }
+
+    /// Replays a guest function call recorded with
+    /// [`record_call`](Self::record_call): the guest function is called with
+    /// the recorded arguments, and the host function calls it makes are
+    /// answered with the recorded results, without calling the registered
+    /// host functions.
+    ///
+    /// Returns the first difference between the replay and the recorded
+    /// call, if any. For the replay to be faithful, the sandbox must be in
+    /// the state the recorded sandbox was in before the call, for instance
+    /// freshly created from the same guest binary and configuration.
+    #[instrument(skip_all, parent = Span::current())]
+    pub fn replay_call(&mut self, record: &GuestCallRecord) -> Option<GuestCallDivergence> {
+        let mode = HostCallMode::replay(record.host_calls.clone());
+        let previous = self.lock_host_funcs().replace_mode(mode);
+        let result = self.call_erased(
+            &record.function_name,
+            record.return_type,
+            record.args.clone(),
+        );
+        let mode = self.lock_host_funcs().replace_mode(previous);
+
+        if let Some(divergence) = mode.into_replay().and_then(HostCallReplay::divergence) {
+            return Some(divergence);
+        }
+        let actual = result.map_err(|e| e.to_string());
+        (actual != record.result).then(|| GuestCallDivergence::Result {
+            expected: record.result.clone(),
+            actual,
+        })
+    }
+
+    fn call_erased(
+        &mut self,
+        func_name: &str,
+        ret_type: ReturnType,
+        args: Vec<ParameterValue>,
+    ) -> Result<ReturnValue> {
+        self.status.check("call a guest function")?;
         // Reset snapshot since we are mutating the sandbox state
         self.snapshot = None;
         maybe_time_and_emit_guest_call(func_name, || {
@@ -617,23 +1983,63 @@ impl MultiUseSandbox {
         })
     }
 
+    /// The names and signatures of the host functions of the sandbox
+    pub(crate) fn host_function_details(&self) -> HostFunctionDetails {
+        HostFunctionDetails::from(&mut *self.lock_host_funcs())
+    }
+
+    fn lock_host_funcs(&self) -> std::sync::MutexGuard<'_, FunctionRegistry> {
+        // The registry is only locked to call a host function or change
+        // it, which leaves it consistent even if a host function panicked
+        self.host_funcs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
     fn call_guest_function_by_name_no_reset(
         &mut self,
         function_name: &str,
         return_type: ReturnType,
         args: Vec<ParameterValue>,
     ) -> Result<ReturnValue> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
+        let estimated_capacity = estimate_flatbuffer_capacity(function_name, &args);
+        let mut builder = FlatBufferBuilder::with_capacity(estimated_capacity);
+        self.call_guest_function_with_builder(function_name, return_type, args, &mut builder)
+    }
+
+    /// Calls a guest function, serializing the call with `builder`, which
+    /// is reset first so that its buffer can be reused across calls
+    fn call_guest_function_with_builder(
+        &mut self,
+        function_name: &str,
+        return_type: ReturnType,
+        args: Vec<ParameterValue>,
+        builder: &mut FlatBufferBuilder<'static>,
+    ) -> Result<ReturnValue> {
+        self.status.check("call a guest function")?;
+        if let Some(resource) = self.vm.exceeded_quota(&self.mem_mgr) {
+            return Err(HyperlightError::SandboxQuotaExceeded(resource));
         }
+        let _call_reservation = QuotaReservation::reserve(QuotaResource::ConcurrentCalls, 1)?;
+        // If this call does not complete, for example because a host
+        // function panicked, the sandbox is left in this state
+        self.status
+            .set(SandboxStatus::Running(function_name.to_string()));
+        self.observers.call_started(self.id, function_name);
+        let mut poisoned = false;
+
         // ===== KILL() TIMING POINT 1 =====
         // Clear any stale cancellation from a previous guest function call or if kill() was called too early.
         // Any kill() that completed (even partially) BEFORE this line has NO effect on this call.
         self.vm.clear_cancel();
+        // Cancelling the token of the call from now on interrupts it
+        if let Some(token) = &self.cancellation {
+            token.attach(self.vm.interrupt_handle());
+        }
+        self.lock_host_funcs().reset_policy_calls();
+        let start = self.vm.metrics().is_enabled().then(Instant::now);
 
         let res = (|| {
-            let estimated_capacity = estimate_flatbuffer_capacity(function_name, &args);
-
             let fc = FunctionCall::new(
                 function_name.to_string(),
                 Some(args),
@@ -641,8 +2047,8 @@ impl MultiUseSandbox {
                 return_type,
             );
 
-            let mut builder = FlatBufferBuilder::with_capacity(estimated_capacity);
-            let buffer = fc.encode(&mut builder);
+            builder.reset();
+            let buffer = fc.encode(builder);
 
             self.mem_mgr.write_guest_function_call(buffer)?;
 
@@ -653,11 +2059,16 @@ impl MultiUseSandbox {
                 self.dbg_mem_access_fn.clone(),
             );
 
+            self.crash_report = dispatch_res
+                .as_ref()
+                .err()
+                .and_then(DispatchGuestCallError::crash_report);
+
             // Convert dispatch errors to HyperlightErrors to maintain backwards compatibility
             // but first determine if sandbox should be poisoned
             if let Err(e) = dispatch_res {
                 let (error, should_poison) = e.promote();
-                self.poisoned |= should_poison;
+                poisoned |= should_poison;
                 return Err(error);
             }
 
@@ -680,54 +2091,462 @@ impl MultiUseSandbox {
             }
         })();
 
-        // In the happy path we do not need to clear io-buffers from the host because:
-        // - the serialized guest function call is zeroed out by the guest during deserialization, see call to `try_pop_shared_input_data_into::<FunctionCall>()`
-        // - the serialized guest function result is zeroed out by us (the host) during deserialization, see `get_guest_function_call_result`
-        // - any serialized host function call are zeroed out by us (the host) during deserialization, see `get_host_function_call`
-        // - any serialized host function result is zeroed out by the guest during deserialization, see `get_host_return_value`
-        if let Err(e) = &res {
-            self.mem_mgr.clear_io_buffers();
+        // In the happy path we do not need to clear io-buffers from the host because:
+        // - the serialized guest function call is zeroed out by the guest during deserialization, see call to `try_pop_shared_input_data_into::<FunctionCall>()`
+        // - the serialized guest function result is zeroed out by us (the host) during deserialization, see `get_guest_function_call_result`
+        // - any serialized host function call are zeroed out by us (the host) during deserialization, see `get_host_function_call`
+        // - any serialized host function result is zeroed out by the guest during deserialization, see `get_host_return_value`
+        if let Some(start) = start {
+            self.vm.metrics().record(SandboxMetric::GuestCall {
+                function: function_name,
+                duration: start.elapsed(),
+                failed: res.is_err(),
+            });
+        }
+
+        if let Err(e) = &res {
+            self.mem_mgr.clear_io_buffers();
+
+            // Determine if we should poison the sandbox.
+            poisoned |= e.is_poison_error();
+        }
+
+        self.status.set(if poisoned {
+            SandboxStatus::Poisoned
+        } else {
+            SandboxStatus::Initialized
+        });
+        self.observers
+            .call_finished(self.id, function_name, res.as_ref());
+
+        // Note: clear_call_active() is automatically called when _guard is dropped here
+
+        res
+    }
+
+    /// Returns a handle for interrupting guest execution.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// // Get interrupt handle before starting long-running operation
+    /// let interrupt_handle = sandbox.interrupt_handle();
+    ///
+    /// // Spawn thread to interrupt after timeout
+    /// let handle_clone = interrupt_handle.clone();
+    /// thread::spawn(move || {
+    ///     thread::sleep(Duration::from_secs(5));
+    ///     handle_clone.kill();
+    /// });
+    ///
+    /// // This call may be interrupted by the spawned thread
+    /// let result = sandbox.call_guest_function_by_name::<i32>("LongRunningFunction", ());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn interrupt_handle(&self) -> Arc<dyn InterruptHandle> {
+        self.vm.interrupt_handle()
+    }
+
+    /// Injects the interrupt `vector` into the guest, so a long-running guest
+    /// function can be signalled, for example with timer ticks, without it
+    /// polling the host. The guest handles the interrupt with the handler it
+    /// installed with `hyperlight_guest_bin::interrupt::register_handler`;
+    /// interrupts for vectors without a handler are ignored.
+    ///
+    /// `vector` must be one of the
+    /// [`HOST_INTERRUPT_VECTOR_COUNT`](hyperlight_common::interrupt::HOST_INTERRUPT_VECTOR_COUNT)
+    /// vectors starting at
+    /// [`FIRST_HOST_INTERRUPT_VECTOR`](hyperlight_common::interrupt::FIRST_HOST_INTERRUPT_VECTOR). If no guest function is
+    /// running, the interrupt is delivered during the next call. To inject
+    /// interrupts while a call runs on this thread, use
+    /// [`InterruptHandle::inject_interrupt`] on the [`Self::interrupt_handle`].
+    pub fn inject_interrupt(&self, vector: u8) -> Result<()> {
+        if !is_host_interrupt_vector(vector) {
+            return Err(HyperlightError::InvalidInterruptVector(vector));
+        }
+        self.interrupt_handle().inject_interrupt(vector);
+        Ok(())
+    }
+
+    /// Subscribes to the [`DebugEvent`](crate::sandbox::debug_events::DebugEvent)s
+    /// raised while the guest runs, such as guest prints, guest aborts and,
+    /// when a gdb client is attached, breakpoint and single step stops.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::debug_events::DebugEvent;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let events = sandbox.subscribe_debug_events();
+    /// let _ = sandbox.call::<i32>("MightFail", ());
+    ///
+    /// for event in events.try_iter() {
+    ///     if let DebugEvent::Exception { code, message } = event {
+    ///         eprintln!("guest aborted with code {}: {}", code, message);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe_debug_events(&self) -> DebugEvents {
+        self.vm.debug_events().subscribe()
+    }
+
+    /// Searches the memory of the sandbox for `pattern`, in the regions
+    /// selected by `options`, and returns the matches ordered by guest
+    /// physical address.
+    ///
+    /// This reads the memory as the guest left it, so it also works on a
+    /// poisoned sandbox, e.g. to look for what corrupted it.
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// use hyperlight_host::mem::memory_region::MemoryRegionType;
+    /// use hyperlight_host::sandbox::memory_scan::{MemoryPattern, MemoryScanOptions};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    /// #     GuestBinary::FilePath("guest.bin".into()),
+    /// #     None
+    /// # )?.evolve()?;
+    /// let options = MemoryScanOptions::new()
+    ///     .region_type(MemoryRegionType::Scratch)
+    ///     .max_matches(10)
+    ///     .max_bytes_per_second(64 * 1024 * 1024);
+    /// for m in sandbox.scan_memory(&MemoryPattern::from(0xdead_beef_u32), &options)? {
+    ///     println!("found at {:#x}", m.gpa);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn scan_memory(
+        &mut self,
+        pattern: &MemoryPattern,
+        options: &MemoryScanOptions,
+    ) -> Result<Vec<MemoryMatch>> {
+        let mut scanner = MemoryScanner::new(pattern, options);
+
+        let snapshot_gpa = SandboxMemoryLayout::BASE_ADDRESS as u64;
+        self.mem_mgr.shared_mem.with_exclusivity(|snapshot| {
+            scanner.scan(
+                snapshot_gpa,
+                snapshot.as_slice(),
+                MemoryRegionType::Snapshot,
+            )
+        })?;
+        let scratch_gpa =
+            hyperlight_common::layout::scratch_base_gpa(self.mem_mgr.scratch_mem.mem_size());
+        self.mem_mgr.scratch_mem.with_exclusivity(|scratch| {
+            scanner.scan(scratch_gpa, scratch.as_slice(), MemoryRegionType::Scratch)
+        })?;
+
+        for region in self.vm.get_mapped_regions() {
+            let host_start = HostGuestMemoryRegion::to_addr(region.host_region.start);
+            // SAFETY: the host memory of a region stays mapped while the
+            // region is mapped into the sandbox, which it is while `self`
+            // is borrowed
+            let data = unsafe {
+                std::slice::from_raw_parts(host_start as *const u8, region.guest_region.len())
+            };
+            scanner.scan(region.guest_region.start as u64, data, region.region_type);
+        }
+
+        Ok(scanner.finish())
+    }
+
+    /// Reads `len` bytes of guest memory at the guest virtual address
+    /// `gva`, translated by the page tables of the guest.
+    ///
+    /// Fails if part of the range is not mapped by the guest, or is mapped
+    /// to memory other than the snapshot and scratch regions of the
+    /// sandbox. Like [`scan_memory`](Self::scan_memory), this also works
+    /// on a poisoned sandbox.
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn read_guest_bytes(&mut self, gva: u64, len: usize) -> Result<Vec<u8>> {
+        let root_pt = self
+            .vm
+            .get_root_pt()
+            .map_err(|e| HyperlightError::HyperlightVmError(e.into()))?;
+        self.mem_mgr.read_guest_memory_by_gva(gva, len, root_pt)
+    }
+
+    /// Reads a value of type `T` from guest memory at the guest virtual
+    /// address `gva`, the same way as [`read_guest_bytes`](Self::read_guest_bytes).
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    /// #     GuestBinary::FilePath("guest.bin".into()),
+    /// #     None
+    /// # )?.evolve()?;
+    /// let counter_gva: u64 = sandbox.call("GetCounterAddress", ())?;
+    /// let counter: u64 = sandbox.read_guest_memory(counter_gva)?;
+    /// sandbox.write_guest_memory(counter_gva, counter + 1)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_guest_memory<T: AllValid>(&mut self, gva: u64) -> Result<T> {
+        let bytes = self.read_guest_bytes(gva, std::mem::size_of::<T>())?;
+        // Safety: `T` is valid for any bit pattern, and `bytes` has its size
+        Ok(unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const T) })
+    }
+
+    /// Writes `data` to guest memory at the guest virtual address `gva`,
+    /// translated by the page tables of the guest.
+    ///
+    /// Fails if a page of the range is not writable by the guest. This
+    /// includes the pages of the snapshot the guest has not written to
+    /// yet, which are mapped copy-on-write.
+    ///
+    /// ## Poisoned Sandbox
+    ///
+    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
+    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    #[instrument(err(Debug), skip(self, data), parent = Span::current())]
+    pub fn write_guest_bytes(&mut self, gva: u64, data: &[u8]) -> Result<()> {
+        self.status.check("write guest memory")?;
+        let root_pt = self
+            .vm
+            .get_root_pt()
+            .map_err(|e| HyperlightError::HyperlightVmError(e.into()))?;
+        self.mem_mgr.write_guest_memory_by_gva(gva, data, root_pt)?;
+        // The state of the sandbox no longer matches its last snapshot
+        self.snapshot = None;
+        Ok(())
+    }
+
+    /// Writes `value` to guest memory at the guest virtual address `gva`,
+    /// the same way as [`write_guest_bytes`](Self::write_guest_bytes).
+    pub fn write_guest_memory<T: AllValid>(&mut self, gva: u64, value: T) -> Result<()> {
+        // Safety: as in `HostSharedMemory::write`, `T` is plain data of
+        // the same representation in the sandbox and the host
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                std::ptr::addr_of!(value) as *const u8,
+                std::mem::size_of::<T>(),
+            )
+        };
+        self.write_guest_bytes(gva, bytes)
+    }
+
+    /// Changes the permissions of the `len` bytes of guest memory at the
+    /// guest virtual address `gva`, both page aligned, for instance to
+    /// make the code a JIT compiler in the guest wrote executable.
+    ///
+    /// Every page of the range must be mapped by the guest, and `flags`
+    /// must include [`MemoryRegionFlags::READ`]. With
+    /// [`SandboxConfiguration::set_guest_write_xor_execute`], `flags`
+    /// cannot include both [`MemoryRegionFlags::WRITE`] and
+    /// [`MemoryRegionFlags::EXECUTE`]. Writable pages the guest has not
+    /// written to since the last snapshot are mapped copy-on-write, so
+    /// restoring the snapshot undoes the writes as well as the change of
+    /// permissions.
+    ///
+    /// ## Poisoned Sandbox
+    ///
+    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
+    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn set_region_permissions(
+        &mut self,
+        gva: u64,
+        len: u64,
+        flags: MemoryRegionFlags,
+    ) -> Result<()> {
+        self.status
+            .check("change the permissions of guest memory")?;
+        let page_size = hyperlight_common::vmem::PAGE_SIZE as u64;
+        if len == 0 || !gva.is_multiple_of(page_size) || !len.is_multiple_of(page_size) {
+            log_then_return!(
+                "The guest memory range {:#x} (len {:#x}) is not page aligned",
+                gva,
+                len
+            );
+        }
+        if !flags.contains(MemoryRegionFlags::READ)
+            || !(MemoryRegionFlags::READ | MemoryRegionFlags::WRITE | MemoryRegionFlags::EXECUTE)
+                .contains(flags)
+        {
+            log_then_return!("Guest memory cannot be mapped {}", flags);
+        }
+        if self.mem_mgr.layout.write_xor_execute()
+            && flags.contains(MemoryRegionFlags::WRITE | MemoryRegionFlags::EXECUTE)
+        {
+            log_then_return!(
+                "Guest memory cannot be both writable and executable in a sandbox enforcing W^X"
+            );
+        }
+        let root_pt = self
+            .vm
+            .get_root_pt()
+            .map_err(|e| HyperlightError::HyperlightVmError(e.into()))?;
+        self.mem_mgr
+            .set_guest_page_permissions(gva, len, flags, root_pt)?;
+        self.vm.request_tlb_flush();
+        // The state of the sandbox no longer matches its last snapshot
+        self.snapshot = None;
+        Ok(())
+    }
+
+    /// Starts reporting the stores of the guest to the `len` bytes of its
+    /// memory at the guest virtual address `gva`, both page aligned, to
+    /// find out which code corrupts a piece of data.
+    ///
+    /// The pages of the range are write protected, so that every store to
+    /// them faults, is reported, and is then single-stepped before the
+    /// page is write protected again: the guest runs much slower while it
+    /// writes to the range. Loads are not traced. The stores are emitted
+    /// as `tracing` events and kept until taken with
+    /// [`take_memory_accesses`](Self::take_memory_accesses).
+    ///
+    /// One range is traced at a time, tracing a range stops tracing the
+    /// previous one, and restoring a snapshot stops tracing. The pages of
+    /// the range are not executable while they are traced. This requires
+    /// a guest built with the `mem_trace` feature of
+    /// `hyperlight_guest_bin`, and cannot be used with a debugger
+    /// attached, which would see the single steps.
+    #[cfg(feature = "mem_trace")]
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn trace_memory_accesses(&mut self, gva: u64, len: u64) -> Result<()> {
+        // The guest handles the faults on the range with its scratch
+        // memory, which must stay writable
+        let scratch_gva =
+            hyperlight_common::layout::scratch_base_gva(self.mem_mgr.scratch_mem.mem_size());
+        if gva.saturating_add(len) > scratch_gva {
+            log_then_return!(
+                "The guest memory range {:#x} (len {:#x}) overlaps the scratch memory, which cannot be traced",
+                gva,
+                len
+            );
+        }
+        self.stop_tracing_memory_accesses()?;
+        self.set_region_permissions(gva, len, MemoryRegionFlags::READ)?;
+        self.mem_mgr.set_memory_trace_range(gva, len)
+    }
 
-            // Determine if we should poison the sandbox.
-            self.poisoned |= e.is_poison_error();
+    /// Stops the tracing started with
+    /// [`trace_memory_accesses`](Self::trace_memory_accesses), making the
+    /// traced range writable again
+    #[cfg(feature = "mem_trace")]
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn stop_tracing_memory_accesses(&mut self) -> Result<()> {
+        self.status.check("trace guest memory")?;
+        let (gva, len) = self.mem_mgr.memory_trace_range()?;
+        if len == 0 {
+            return Ok(());
         }
+        self.mem_mgr.set_memory_trace_range(0, 0)?;
+        self.set_region_permissions(gva, len, MemoryRegionFlags::READ | MemoryRegionFlags::WRITE)
+    }
 
-        // Note: clear_call_active() is automatically called when _guard is dropped here
+    /// Takes the stores of the guest to its traced memory recorded since
+    /// the last call, in the order they were made
+    #[cfg(feature = "mem_trace")]
+    pub fn take_memory_accesses(&mut self) -> Vec<MemoryAccess> {
+        self.vm.take_memory_accesses()
+    }
 
-        res
+    /// Checks that the sandbox works end to end, by running the self-test
+    /// function every guest built with `hyperlight_guest_bin` provides,
+    /// then timing `iterations` calls into the guest, and `iterations`
+    /// allocations and host function calls made by the guest.
+    ///
+    /// These are regular guest calls, so a snapshot can be taken before
+    /// and restored after to leave the state of the guest untouched.
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn run_diagnostics(&mut self, iterations: u32) -> Result<DiagnosticsReport> {
+        let self_test: String = self.call(SELF_TEST_FUNCTION, ())?;
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            self.call::<Vec<u8>>(BENCH_FUNCTION, 0u32)?;
+        }
+        let guest_call = start.elapsed() / iterations.max(1);
+
+        let timings: Vec<u8> = self.call(BENCH_FUNCTION, iterations)?;
+        let guest_timings = GuestBenchTimings::from_bytes(&timings).ok_or_else(|| {
+            new_error!(
+                "{} returned {} bytes, which are not timings",
+                BENCH_FUNCTION,
+                timings.len()
+            )
+        })?;
+
+        Ok(DiagnosticsReport {
+            self_test,
+            guest_call,
+            guest_timings,
+        })
     }
 
-    /// Returns a handle for interrupting guest execution.
+    /// Lists the functions the guest registered, sorted by name, for
+    /// instance to check that the guest binary implements the interface
+    /// the host expects before calling it.
+    ///
+    /// The functions every guest built with `hyperlight_guest_bin`
+    /// provides, whose names start with `__hl_`, are left out. Functions
+    /// a C guest handles in `c_guest_dispatch_function` without
+    /// registering them cannot be listed.
+    ///
+    /// This is a regular guest call, see
+    /// [`run_diagnostics`](Self::run_diagnostics).
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
-    /// # use std::thread;
-    /// # use std::time::Duration;
     /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
     ///     GuestBinary::FilePath("guest.bin".into()),
     ///     None
     /// )?.evolve()?;
     ///
-    /// // Get interrupt handle before starting long-running operation
-    /// let interrupt_handle = sandbox.interrupt_handle();
-    ///
-    /// // Spawn thread to interrupt after timeout
-    /// let handle_clone = interrupt_handle.clone();
-    /// thread::spawn(move || {
-    ///     thread::sleep(Duration::from_secs(5));
-    ///     handle_clone.kill();
-    /// });
-    ///
-    /// // This call may be interrupted by the spawned thread
-    /// let result = sandbox.call_guest_function_by_name::<i32>("LongRunningFunction", ());
+    /// let functions = sandbox.guest_functions()?;
+    /// let echo = functions.iter().find(|f| f.name == "Echo");
+    /// assert!(echo.is_some_and(|f| f.matches::<(String,), String>()));
     /// # Ok(())
     /// # }
     /// ```
-    pub fn interrupt_handle(&self) -> Arc<dyn InterruptHandle> {
-        self.vm.interrupt_handle()
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn guest_functions(&mut self) -> Result<Vec<GuestFunctionInfo>> {
+        let encoded: Vec<u8> = self.call(LIST_FUNCTIONS_FUNCTION, ())?;
+        let details = HostFunctionDetails::try_from(encoded.as_slice()).map_err(|e| {
+            new_error!(
+                "{} returned functions that could not be decoded: {}",
+                LIST_FUNCTIONS_FUNCTION,
+                e
+            )
+        })?;
+        Ok(details
+            .host_functions
+            .unwrap_or_default()
+            .into_iter()
+            .map(GuestFunctionInfo::from)
+            .filter(|function| !function.name.starts_with("__hl_"))
+            .collect())
+    }
+
+    /// Returns the workspace of the sandbox, if one was created with
+    /// [`UninitializedSandbox::create_workspace`](crate::UninitializedSandbox::create_workspace)
+    pub fn workspace(&self) -> Option<&Workspace> {
+        self.workspace.as_ref()
     }
 
     /// Generate a crash dump of the current state of the VM underlying this sandbox.
@@ -736,8 +2555,10 @@ impl MultiUseSandbox {
     /// captures the current state of the sandbox including registers, memory regions,
     /// and other execution context.
     ///
-    /// The location of the core dump file is determined by the `HYPERLIGHT_CORE_DUMP_DIR`
-    /// environment variable. If not set, it defaults to the system's temporary directory.
+    /// The core dump file is placed in the directory set with
+    /// [`UninitializedSandbox::set_core_dump_on_crash`](crate::UninitializedSandbox::set_core_dump_on_crash),
+    /// or else in the one given by the `HYPERLIGHT_CORE_DUMP_DIR` environment variable.
+    /// If neither is set, it defaults to the system's temporary directory.
     ///
     /// This is only available when the `crashdump` feature is enabled and then only if the sandbox
     /// is also configured to allow core dumps (which is the default behavior).
@@ -824,7 +2645,170 @@ impl MultiUseSandbox {
     /// # }
     /// ```
     pub fn poisoned(&self) -> bool {
-        self.poisoned
+        self.status.get() == SandboxStatus::Poisoned
+    }
+
+    /// Returns the current lifecycle state of the sandbox.
+    ///
+    /// Operations other than [`restore()`](Self::restore) are only allowed
+    /// while the sandbox is [`SandboxStatus::Initialized`], and otherwise
+    /// fail with [`crate::HyperlightError::PoisonedSandbox`] or
+    /// [`crate::HyperlightError::InvalidSandboxState`].
+    pub fn status(&self) -> SandboxStatus {
+        self.status.get()
+    }
+
+    /// Returns what the guest was doing when it aborted, if the last guest
+    /// function call failed with
+    /// [`HyperlightError::GuestAborted`](crate::HyperlightError::GuestAborted)
+    /// or [`HyperlightError::GuestStackOverflow`](crate::HyperlightError::GuestStackOverflow).
+    ///
+    /// The report is kept until the next guest function call, so it can
+    /// still be read after the sandbox is restored.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{HyperlightError, MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// if let Err(HyperlightError::GuestAborted(code, _)) = sandbox.call::<()>("Crash", ()) {
+    ///     let report = sandbox.crash_report().expect("report of the abort");
+    ///     println!("Guest aborted with {code}:\n{report}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn crash_report(&self) -> Option<&GuestCrashReport> {
+        self.crash_report.as_ref()
+    }
+
+    /// Returns a handle to query the status of this sandbox from other
+    /// threads, including while a guest function call is in progress.
+    pub fn status_handle(&self) -> SandboxStatusHandle {
+        self.status.clone()
+    }
+}
+
+/// The sandbox as seen by the closure given to
+/// [`MultiUseSandbox::with_borrowed_buffer`], while a buffer is lent to
+/// the guest. It only gives access to what can be done with the buffer
+/// mapped, and gives the buffer back when dropped.
+#[cfg(target_os = "linux")]
+pub struct LendingSandbox<'a> {
+    sandbox: &'a mut MultiUseSandbox,
+}
+
+#[cfg(target_os = "linux")]
+impl LendingSandbox<'_> {
+    /// Calls a guest function, see [`MultiUseSandbox::call`]
+    pub fn call<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+    ) -> Result<Output> {
+        self.sandbox.call(func_name, args)
+    }
+
+    /// Takes a snapshot of the sandbox, which does not include the lent
+    /// buffers, see [`MultiUseSandbox::snapshot`]
+    pub fn snapshot(&mut self) -> Result<Arc<Snapshot>> {
+        self.sandbox.snapshot()
+    }
+
+    /// Restores the sandbox, leaving the lent buffers mapped, see
+    /// [`MultiUseSandbox::restore`]
+    pub fn restore(&mut self, snapshot: Arc<Snapshot>) -> Result<()> {
+        self.sandbox.restore(snapshot)
+    }
+
+    /// Whether the sandbox is poisoned, see [`MultiUseSandbox::poisoned`]
+    pub fn poisoned(&self) -> bool {
+        self.sandbox.poisoned()
+    }
+
+    /// Lends another buffer to the guest while `f` runs, after the
+    /// buffers already lent, see [`MultiUseSandbox::with_borrowed_buffer`]
+    pub fn with_borrowed_buffer<T>(
+        &mut self,
+        buffer: BorrowedGuestBuffer<'_>,
+        f: impl FnOnce(&mut LendingSandbox<'_>, u64) -> Result<T>,
+    ) -> Result<T> {
+        self.sandbox.with_borrowed_buffer(buffer, f)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for LendingSandbox<'_> {
+    fn drop(&mut self) {
+        // Runs when the closure returns or panics, so the buffer is never
+        // left mapped once its borrow ends
+        let Some(region) = self.sandbox.lent_buffers.pop() else {
+            return;
+        };
+        if let Err(e) = self.sandbox.vm.unmap_region(&region) {
+            // Poisoning the sandbox is not enough, since restoring it
+            // would let the guest run with the freed buffer mapped
+            log::error!(
+                "Failed to unmap the buffer lent to the guest, aborting: {:?}",
+                e
+            );
+            std::process::abort();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::fmt::Debug for LendingSandbox<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LendingSandbox").finish_non_exhaustive()
+    }
+}
+
+/// A guest function bound to its argument and return types with
+/// [`MultiUseSandbox::bind_function`]
+pub struct BoundFunction<Args, Output> {
+    name: String,
+    /// Keeps its buffer across calls, so that it only grows to the size of
+    /// the largest call
+    builder: FlatBufferBuilder<'static>,
+    _types: PhantomData<fn(Args) -> Output>,
+}
+
+impl<Args: ParameterTuple, Output: SupportedReturnType> BoundFunction<Args, Output> {
+    /// Returns the name of the guest function
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Calls the guest function in `sandbox` with `args`, see
+    /// [`MultiUseSandbox::call`].
+    #[instrument(err(Debug), skip_all, fields(func_name = %self.name), parent = Span::current())]
+    pub fn call(&mut self, sandbox: &mut MultiUseSandbox, args: Args) -> Result<Output> {
+        sandbox.status.check("call a guest function")?;
+        // Reset snapshot since we are mutating the sandbox state
+        sandbox.snapshot = None;
+        maybe_time_and_emit_guest_call(&self.name, || {
+            let ret = sandbox.call_guest_function_with_builder(
+                &self.name,
+                Output::TYPE,
+                args.into_value(),
+                &mut self.builder,
+            );
+            Ok(Output::from_value(ret?)?)
+        })
+    }
+}
+
+impl<Args, Output> std::fmt::Debug for BoundFunction<Args, Output> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoundFunction")
+            .field("name", &self.name)
+            .finish()
     }
 }
 
@@ -834,13 +2818,79 @@ impl Callable for MultiUseSandbox {
         func_name: &str,
         args: impl ParameterTuple,
     ) -> Result<Output> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
+        self.status.check("call a guest function")?;
         self.call(func_name, args)
     }
 }
 
+impl Drop for MultiUseSandbox {
+    fn drop(&mut self) {
+        self.status.set(SandboxStatus::Closed);
+        self.observers.dropped(self.id);
+        // Host functions may outlive the sandbox, so the workspace is
+        // removed even if they still hold a handle to it
+        if let Some(workspace) = &self.workspace {
+            workspace.close();
+        }
+    }
+}
+
+/// A file mapped read-only into the host address space, to be mapped into
+/// the guest, which is unmapped when dropped
+#[cfg(target_os = "linux")]
+struct MappedFile {
+    base: *mut libc::c_void,
+    size: usize,
+}
+
+#[cfg(target_os = "linux")]
+impl MappedFile {
+    fn new(file: &std::fs::File, flags: MemoryRegionFlags) -> std::io::Result<Self> {
+        let page_size = page_size::get();
+        let size = (file.metadata()?.len() as usize).div_ceil(page_size) * page_size;
+        if size == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the file is empty",
+            ));
+        }
+        let mut prot = libc::PROT_READ;
+        if flags.contains(MemoryRegionFlags::EXECUTE) {
+            prot |= libc::PROT_EXEC;
+        }
+        // Safety: a new mapping is created, which does not alias any memory
+        let base = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                size,
+                prot,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self { base, size })
+    }
+}
+
+// Safety: the mapping is only read, and is owned by a single sandbox
+#[cfg(target_os = "linux")]
+unsafe impl Send for MappedFile {}
+#[cfg(target_os = "linux")]
+unsafe impl Sync for MappedFile {}
+
+#[cfg(target_os = "linux")]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        // Safety: the mapping was created by `MappedFile::new` and is no
+        // longer mapped into the guest
+        unsafe { libc::munmap(self.base, self.size) };
+    }
+}
+
 impl std::fmt::Debug for MultiUseSandbox {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("MultiUseSandbox").finish()
@@ -861,6 +2911,8 @@ mod tests {
     #[cfg(target_os = "linux")]
     use crate::mem::shared_mem::{ExclusiveSharedMemory, GuestSharedMemory, SharedMemory as _};
     use crate::sandbox::SandboxConfiguration;
+    use crate::sandbox::debug_events::DebugEvent;
+    use crate::sandbox::status::SandboxStatus;
     use crate::{GuestBinary, HyperlightError, MultiUseSandbox, Result, UninitializedSandbox};
 
     #[test]
@@ -912,6 +2964,10 @@ mod tests {
             let temp_file = std::env::temp_dir().join("test_poison_map_file.bin");
             let res = sbox.map_file_cow(&temp_file, 0x0).unwrap_err();
             assert!(matches!(res, HyperlightError::PoisonedSandbox));
+            let res = sbox
+                .map_file_region(&temp_file, 0x0, MemoryRegionFlags::READ)
+                .unwrap_err();
+            assert!(matches!(res, HyperlightError::PoisonedSandbox));
             std::fs::remove_file(&temp_file).ok(); // Clean up
         }
 
@@ -931,53 +2987,299 @@ mod tests {
         assert_eq!(res, "hello2".to_string());
         assert!(!sbox.poisoned());
 
-        // re-poison on purpose
-        let res = sbox
-            .call::<()>("guest_panic", "hello".to_string())
-            .unwrap_err();
+        // re-poison on purpose
+        let res = sbox
+            .call::<()>("guest_panic", "hello".to_string())
+            .unwrap_err();
+        assert!(
+            matches!(res, HyperlightError::GuestAborted(code, context) if code == ErrorCode::UnknownError as u8 && context.contains("hello"))
+        );
+        assert!(sbox.poisoned());
+
+        // restore to non-poisoned snapshot should work again
+        sbox.restore(snapshot.clone()).unwrap();
+        assert!(!sbox.poisoned());
+
+        // guest calls should work again
+        let res = sbox.call::<String>("Echo", "hello3".to_string()).unwrap();
+        assert_eq!(res, "hello3".to_string());
+        assert!(!sbox.poisoned());
+
+        // snapshot should work again
+        let _ = sbox.snapshot().unwrap();
+    }
+
+    #[test]
+    fn status_transitions() {
+        let path = simple_guest_as_string().unwrap();
+        let mut u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+        assert_eq!(u_sbox.status(), SandboxStatus::Created);
+
+        let handle = u_sbox.status_handle();
+        let host_handle = handle.clone();
+        u_sbox
+            .register("GetStatus", move || -> Result<i64> {
+                let running =
+                    SandboxStatus::Running("CallGivenParamlessHostFuncThatReturnsI64".to_string());
+                Ok((host_handle.get() == running) as i64)
+            })
+            .unwrap();
+        let mut sbox: MultiUseSandbox = u_sbox.evolve().unwrap();
+        assert_eq!(sbox.status(), SandboxStatus::Initialized);
+        let snapshot = sbox.snapshot().unwrap();
+
+        // the status is running while the guest calls back into the host
+        let res = sbox
+            .call::<i64>(
+                "CallGivenParamlessHostFuncThatReturnsI64",
+                "GetStatus".to_string(),
+            )
+            .unwrap();
+        assert_eq!(res, 1);
+        assert_eq!(sbox.status(), SandboxStatus::Initialized);
+
+        let _ = sbox.call::<()>("guest_panic", "hello".to_string());
+        assert_eq!(sbox.status(), SandboxStatus::Poisoned);
+
+        sbox.restore(snapshot).unwrap();
+        assert_eq!(handle.get(), SandboxStatus::Initialized);
+
+        drop(sbox);
+        assert_eq!(handle.get(), SandboxStatus::Closed);
+    }
+
+    #[test]
+    #[cfg(feature = "trace_guest")]
+    fn flush_guest_traces() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let interrupt_handle = sbox.interrupt_handle();
+
+        // Requests made while the guest is not running are deferred to the next call
+        assert!(!interrupt_handle.flush_guest_traces());
+        let res = sbox.call::<String>("Echo", "hello".to_string()).unwrap();
+        assert_eq!(res, "hello");
+
+        // Requests made during a call do not disturb it
+        let thread = thread::spawn(move || {
+            for _ in 0..10 {
+                interrupt_handle.flush_guest_traces();
+                thread::sleep(std::time::Duration::from_millis(10));
+            }
+        });
+        let res = sbox.call::<u64>("SpinForMs", 200u32).unwrap();
+        assert_eq!(res, 200);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    #[cfg(crashdump)]
+    fn core_dump_on_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_guest_core_dump(false);
+
+        let path = simple_guest_as_string().unwrap();
+        let mut u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), Some(cfg)).unwrap();
+        u_sbox.set_core_dump_on_crash(dir.path().to_string_lossy());
+        let mut sbox = u_sbox.evolve().unwrap();
+
+        sbox.call::<()>("TriggerException", ()).unwrap_err();
+
+        let dumps: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(dumps.len(), 1);
+        assert!(dumps[0].starts_with("hl_core_") && dumps[0].ends_with(".elf"));
+    }
+
+    #[test]
+    fn workspace_removed_on_drop() {
+        let path = simple_guest_as_string().unwrap();
+        let mut u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+        let workspace = u_sbox.create_workspace(16).unwrap();
+        assert!(u_sbox.create_workspace(16).is_err());
+
+        let host_workspace = workspace.clone();
+        u_sbox
+            .register("StoreText", move |text: String| {
+                host_workspace.write_file("text", text.as_bytes())
+            })
+            .unwrap();
+        let sbox = u_sbox.evolve().unwrap();
+        workspace.write_file("text", b"hello").unwrap();
+        assert_eq!(sbox.workspace().unwrap().used(), 5);
+
+        let dir = workspace.path().to_path_buf();
+        assert!(dir.exists());
+        drop(sbox);
+        assert!(!dir.exists());
+        assert!(matches!(
+            workspace.write_file("text", b"hello"),
+            Err(HyperlightError::WorkspaceClosed)
+        ));
+    }
+
+    /// Make sure input/output buffers are properly reset after guest call (with host call)
+    #[test]
+    fn host_func_error() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sandbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+        sandbox
+            .register("HostError", || -> Result<()> {
+                Err(HyperlightError::Error("hi".to_string()))
+            })
+            .unwrap();
+        let mut sandbox = sandbox.evolve().unwrap();
+
+        // will exhaust io if leaky
+        for _ in 0..1000 {
+            let result = sandbox
+                .call::<i64>(
+                    "CallGivenParamlessHostFuncThatReturnsI64",
+                    "HostError".to_string(),
+                )
+                .unwrap_err();
+
+            assert!(
+                matches!(result, HyperlightError::GuestError(code, msg) if code == ErrorCode::HostFunctionError && msg == "hi"),
+            );
+        }
+    }
+
+    #[test]
+    fn host_functions_signature() {
+        use crate::func::HostFunctions;
+
+        let host_functions = HostFunctions::new()
+            .register_host_fn("GetLong", || -> Result<i64> { Ok(42) })
+            .register_host_fn("GetInt", || -> Result<i32> { Ok(42) });
+        assert_eq!(
+            host_functions.names().collect::<Vec<_>>(),
+            ["GetLong", "GetInt"]
+        );
+        let path = simple_guest_as_string().unwrap();
+        let mut sandbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+        sandbox.register_host_functions(&host_functions).unwrap();
+        let mut sandbox = sandbox.evolve().unwrap();
+
+        let res = sandbox
+            .call::<i64>(
+                "CallGivenParamlessHostFuncThatReturnsI64",
+                "GetLong".to_string(),
+            )
+            .unwrap();
+        assert_eq!(res, 42);
+
+        // The guest expects a Long from a function that returns an Int
+        let err = sandbox
+            .call::<i64>(
+                "CallGivenParamlessHostFuncThatReturnsI64",
+                "GetInt".to_string(),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(&err, HyperlightError::GuestError(ErrorCode::HostFunctionError, msg)
+                if msg.contains("called as fn() -> Long, but it was registered as fn() -> Int")),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn register_on_live_sandbox() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sandbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let call = |sandbox: &mut MultiUseSandbox| {
+            sandbox.call::<i64>(
+                "CallGivenParamlessHostFuncThatReturnsI64",
+                "Plugin".to_string(),
+            )
+        };
+        assert!(call(&mut sandbox).is_err());
+
+        sandbox
+            .register("Plugin", || -> Result<i64> { Ok(1) })
+            .unwrap();
         assert!(
-            matches!(res, HyperlightError::GuestAborted(code, context) if code == ErrorCode::UnknownError as u8 && context.contains("hello"))
+            sandbox
+                .host_function_names()
+                .contains(&"Plugin".to_string())
         );
-        assert!(sbox.poisoned());
-
-        // restore to non-poisoned snapshot should work again
-        sbox.restore(snapshot.clone()).unwrap();
-        assert!(!sbox.poisoned());
+        assert_eq!(call(&mut sandbox).unwrap(), 1);
 
-        // guest calls should work again
-        let res = sbox.call::<String>("Echo", "hello3".to_string()).unwrap();
-        assert_eq!(res, "hello3".to_string());
-        assert!(!sbox.poisoned());
+        // Registering again replaces the function
+        sandbox
+            .register("Plugin", || -> Result<i64> { Ok(2) })
+            .unwrap();
+        assert_eq!(call(&mut sandbox).unwrap(), 2);
 
-        // snapshot should work again
-        let _ = sbox.snapshot().unwrap();
+        assert!(sandbox.unregister("Plugin"));
+        assert!(!sandbox.unregister("Plugin"));
+        assert!(
+            !sandbox
+                .host_function_names()
+                .contains(&"Plugin".to_string())
+        );
+        assert!(call(&mut sandbox).is_err());
     }
 
-    /// Make sure input/output buffers are properly reset after guest call (with host call)
     #[test]
-    fn host_func_error() {
+    fn host_function_policy() {
+        use crate::func::HostFunctionPolicy;
+
         let path = simple_guest_as_string().unwrap();
         let mut sandbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
         sandbox
-            .register("HostError", || -> Result<()> {
-                Err(HyperlightError::Error("hi".to_string()))
-            })
+            .register("Limited", || -> Result<i64> { Ok(1) })
+            .unwrap();
+        sandbox
+            .set_host_function_policy("Limited", HostFunctionPolicy::AllowedTimes(1))
             .unwrap();
         let mut sandbox = sandbox.evolve().unwrap();
+        let call = |sandbox: &mut MultiUseSandbox| {
+            sandbox.call::<i64>(
+                "CallGivenParamlessHostFuncThatReturnsI64",
+                "Limited".to_string(),
+            )
+        };
 
-        // will exhaust io if leaky
-        for _ in 0..1000 {
-            let result = sandbox
-                .call::<i64>(
-                    "CallGivenParamlessHostFuncThatReturnsI64",
-                    "HostError".to_string(),
-                )
+        // The calls are counted for each guest function call
+        assert_eq!(call(&mut sandbox).unwrap(), 1);
+        assert_eq!(call(&mut sandbox).unwrap(), 1);
+        {
+            let host_funcs = sandbox.lock_host_funcs();
+            assert!(host_funcs.call_host_function("Limited", vec![]).is_ok());
+            let err = host_funcs
+                .call_host_function("Limited", vec![])
                 .unwrap_err();
-
             assert!(
-                matches!(result, HyperlightError::GuestError(code, msg) if code == ErrorCode::HostFunctionError && msg == "hi"),
+                matches!(err, HyperlightError::HostFunctionDenied(ref name) if name == "Limited")
             );
         }
+
+        sandbox.set_host_function_policy("Limited", HostFunctionPolicy::Denied);
+        let err = call(&mut sandbox).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                HyperlightError::GuestError(ErrorCode::HostFunctionDenied, _)
+            ),
+            "{:?}",
+            err
+        );
+
+        sandbox.set_host_function_policy("Limited", HostFunctionPolicy::Allowed);
+        assert_eq!(call(&mut sandbox).unwrap(), 1);
     }
 
     #[test]
@@ -1039,6 +3341,48 @@ mod tests {
         assert_eq!(res, 0);
     }
 
+    #[test]
+    fn bound_function() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+            u_sbox.evolve()
+        }
+        .unwrap();
+
+        let mut add_to_static = sbox.bind_function::<i32, i32>("AddToStatic").unwrap();
+        assert_eq!(add_to_static.name(), "AddToStatic");
+        for i in 1..=10 {
+            assert_eq!(add_to_static.call(&mut sbox, 1).unwrap(), i);
+        }
+
+        // The state of the sandbox is persisted, as with `call`
+        let res: i32 = sbox.call("GetStatic", ()).unwrap();
+        assert_eq!(res, 10);
+
+        // Calls with varying argument sizes reuse the same buffer
+        let mut echo = sbox.bind_function::<String, String>("Echo").unwrap();
+        for len in [1000, 1, 100] {
+            let msg = "a".repeat(len);
+            assert_eq!(echo.call(&mut sbox, msg.clone()).unwrap(), msg);
+        }
+
+        // Binding checks the name and the signature against the guest
+        let res = sbox.bind_function::<i32, i32>("NoSuchFunction");
+        assert!(matches!(
+            res,
+            Err(HyperlightError::GuestFunctionNotFound(name)) if name == "NoSuchFunction"
+        ));
+        let res = sbox.bind_function::<i32, String>("Echo");
+        assert!(matches!(
+            res,
+            Err(HyperlightError::GuestFunctionSignatureMismatch(name, bound, declared))
+                if name == "Echo"
+                    && bound == "fn(Int) -> String"
+                    && declared == "fn(String) -> String"
+        ));
+    }
+
     // Tests to ensure that many (1000) function calls can be made in a call context with a small stack (24K) and heap(20K).
     // This test effectively ensures that the stack is being properly reset after each call and we are not leaking memory in the Guest.
     #[test]
@@ -1106,6 +3450,32 @@ mod tests {
         assert_eq!(res, 0);
     }
 
+    #[test]
+    fn test_debug_events() {
+        let usbox = UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_as_string().expect("Guest Binary Missing")),
+            None,
+        )
+        .unwrap();
+        let early_events = usbox.subscribe_debug_events();
+
+        let mut sbox: MultiUseSandbox = usbox.evolve().unwrap();
+        let events = sbox.subscribe_debug_events();
+
+        let _: i32 = sbox.call("PrintOutput", "hello".to_string()).unwrap();
+        let res: Result<()> = sbox.call("TriggerException", ());
+        assert!(res.is_err());
+
+        for events in [early_events, events] {
+            let events: Vec<_> = events.try_iter().collect();
+            assert_eq!(events[0], DebugEvent::GuestPrint("hello".to_string()));
+            assert!(matches!(
+                &events[1],
+                DebugEvent::Exception { message, .. } if message.contains("InvalidOpcode")
+            ));
+        }
+    }
+
     #[test]
     fn test_trigger_exception_on_guest() {
         let usbox = UninitializedSandbox::new(
@@ -1254,6 +3624,55 @@ mod tests {
         };
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_map_file_region() {
+        let mut sbox = UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_as_string().expect("Guest Binary Missing")),
+            None,
+        )
+        .unwrap()
+        .evolve()
+        .unwrap();
+
+        let expected = b"a large read-only asset";
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), expected).unwrap();
+        let guest_base = 0x1_0000_0000;
+
+        assert!(
+            sbox.map_file_region(
+                file.path(),
+                guest_base,
+                MemoryRegionFlags::READ | MemoryRegionFlags::WRITE
+            )
+            .is_err()
+        );
+        assert!(
+            sbox.map_file_region(file.path(), guest_base + 1, MemoryRegionFlags::READ)
+                .is_err()
+        );
+        let size = sbox
+            .map_file_region(file.path(), guest_base, MemoryRegionFlags::READ)
+            .unwrap();
+        assert_eq!(size, hyperlight_common::vmem::PAGE_SIZE as u64);
+
+        let actual: Vec<u8> = sbox
+            .call(
+                "ReadMappedBuffer",
+                (guest_base, expected.len() as u64, true),
+            )
+            .unwrap();
+        assert_eq!(actual, expected);
+
+        let err = sbox
+            .call::<bool>("WriteMappedBuffer", (guest_base, expected.len() as u64))
+            .unwrap_err();
+        assert!(
+            matches!(err, HyperlightError::MemoryAccessViolation(addr, ..) if addr == guest_base)
+        );
+    }
+
     #[cfg(target_os = "linux")]
     fn page_aligned_memory(src: &[u8]) -> GuestSharedMemory {
         use hyperlight_common::mem::PAGE_SIZE_USIZE;
@@ -1387,6 +3806,29 @@ mod tests {
         assert_ne!(sandbox3.id, sandbox_id);
     }
 
+    #[test]
+    fn snapshot_layout_compatibility() {
+        let new_sandbox = |cfg| {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), cfg).unwrap();
+            u_sbox.evolve().unwrap()
+        };
+        let mut sandbox = new_sandbox(None);
+        let sandbox2 = new_sandbox(None);
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_heap_size(2 * SandboxConfiguration::DEFAULT_HEAP_SIZE);
+        let sandbox3 = new_sandbox(Some(cfg));
+
+        let info = sandbox.snapshot().unwrap().layout_info();
+        info.check_build_compatible().unwrap();
+        info.check_compatible(&sandbox2.layout_info()).unwrap();
+        let err = info.check_compatible(&sandbox3.layout_info());
+        assert!(matches!(
+            err,
+            Err(HyperlightError::SnapshotLayoutMismatch(_))
+        ));
+    }
+
     /// Test that snapshot restore properly resets vCPU debug registers. This test verifies
     /// that restore() calls reset_vcpu().
     #[test]
@@ -1539,4 +3981,324 @@ mod tests {
         let start = code_gva + 4096 - 100;
         assert_gva_read_matches(&mut sbox, start, 200);
     }
+
+    /// Scan for the first bytes of the guest code, which are in the
+    /// snapshot at the address of the code
+    #[test]
+    fn guest_memory_access() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+
+        let code_gva = sbox.mem_mgr.layout.get_guest_code_address() as u64;
+        let code = sbox.read_guest_bytes(code_gva + 4090, 16).unwrap();
+        let word: u64 = sbox.read_guest_memory(code_gva + 4090).unwrap();
+        assert_eq!(word.to_le_bytes(), code[..8]);
+        assert!(sbox.write_guest_memory(code_gva, 0u64).is_err());
+        assert!(sbox.read_guest_bytes(0, 8).is_err());
+
+        let scratch_gva =
+            hyperlight_common::layout::scratch_base_gva(sbox.mem_mgr.scratch_mem.mem_size());
+        let original: u64 = sbox.read_guest_memory(scratch_gva).unwrap();
+        sbox.write_guest_memory(scratch_gva, 0x1234_5678_u64)
+            .unwrap();
+        assert_eq!(
+            sbox.read_guest_memory::<u64>(scratch_gva).unwrap(),
+            0x1234_5678
+        );
+        sbox.write_guest_memory(scratch_gva, original).unwrap();
+        assert_eq!(sbox.call::<i32>("AddToStatic", 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn guest_aslr() {
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_guest_aslr(true);
+        let new_sandbox = || -> MultiUseSandbox {
+            let path = simple_guest_as_string().unwrap();
+            UninitializedSandbox::new(GuestBinary::FilePath(path), Some(cfg))
+                .unwrap()
+                .evolve()
+                .unwrap()
+        };
+        let mut sbox = new_sandbox();
+        let other = new_sandbox();
+
+        let code_gva = sbox.mem_mgr.layout.get_guest_code_address() as u64;
+        let other_code_gva = other.mem_mgr.layout.get_guest_code_address() as u64;
+        assert_ne!(code_gva, other_code_gva);
+        assert_eq!(code_gva % 0x20_0000, 0x1000);
+        // The code is mapped at its random address only
+        assert!(sbox.read_guest_bytes(code_gva, 16).is_ok());
+        assert!(sbox.read_guest_bytes(0x1000, 16).is_err());
+
+        let snapshot = sbox.snapshot().unwrap();
+        assert_eq!(sbox.call::<i32>("AddToStatic", 5).unwrap(), 5);
+        assert_eq!(sbox.call::<i32>("StackOverflow", 2).unwrap(), 2);
+        sbox.restore(snapshot).unwrap();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 0);
+        assert_eq!(
+            sbox.mem_mgr.layout.get_guest_code_address() as u64,
+            code_gva
+        );
+    }
+
+    #[test]
+    fn set_region_permissions() {
+        let rwx = MemoryRegionFlags::READ | MemoryRegionFlags::WRITE | MemoryRegionFlags::EXECUTE;
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_guest_write_xor_execute(true);
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), Some(cfg))
+                .unwrap()
+                .evolve()
+                .unwrap();
+
+        // The guest makes the code it generates executable itself
+        assert_eq!(sbox.call::<i32>("ExecuteJitCode", ()).unwrap(), 42);
+
+        let code_gva = sbox.mem_mgr.layout.get_guest_code_address() as u64;
+        assert!(sbox.set_region_permissions(code_gva, 0x1000, rwx).is_err());
+        assert!(
+            sbox.set_region_permissions(code_gva + 1, 0x1000, MemoryRegionFlags::READ)
+                .is_err()
+        );
+        assert!(
+            sbox.set_region_permissions(code_gva, 0x1000, MemoryRegionFlags::WRITE)
+                .is_err()
+        );
+        assert!(
+            sbox.set_region_permissions(0, 0x1000, MemoryRegionFlags::READ)
+                .is_err()
+        );
+
+        // The pages of the snapshot made writable are copy-on-write
+        let snapshot = sbox.snapshot().unwrap();
+        sbox.set_region_permissions(
+            code_gva,
+            0x1000,
+            MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
+        )
+        .unwrap();
+        assert!(sbox.write_guest_bytes(code_gva, &[0]).is_err());
+        assert_eq!(sbox.call::<i32>("ExecuteJitCode", ()).unwrap(), 42);
+        sbox.restore(snapshot).unwrap();
+
+        // Without W^X, pages can be writable and executable
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let code_gva = sbox.mem_mgr.layout.get_guest_code_address() as u64;
+        sbox.set_region_permissions(code_gva, 0x1000, rwx).unwrap();
+        assert_eq!(sbox.call::<i32>("ExecuteJitCode", ()).unwrap(), 42);
+    }
+
+    #[test]
+    fn memory_stats() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let stats = sbox.memory_stats().unwrap();
+        assert_eq!(
+            stats.configured,
+            sbox.mem_mgr.shared_mem.mem_size() + sbox.mem_mgr.scratch_mem.mem_size()
+        );
+        assert!(stats.committed > 0);
+        assert!(stats.committed <= stats.configured);
+
+        // The memory is backed as the guest touches it
+        assert_eq!(sbox.call::<i32>("AddToStatic", 5).unwrap(), 5);
+        let after = sbox.memory_stats().unwrap();
+        assert_eq!(after.configured, stats.configured);
+        assert!(after.committed >= stats.committed);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn memfd_memory() {
+        use crate::sandbox::MemoryBacking;
+
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_memory_backing(MemoryBacking::Memfd { sealed: true });
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path.clone()), Some(cfg))
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let memfd = std::fs::File::from(sbox.memory_fd().unwrap().try_clone_to_owned().unwrap());
+        assert_eq!(
+            memfd.metadata().unwrap().len(),
+            sbox.mem_mgr.shared_mem.mem_size() as u64
+        );
+        assert!(sbox.scratch_memory_fd().is_some());
+
+        // Snapshots are restored into the memfd
+        let snapshot = sbox.snapshot().unwrap();
+        for _ in 0..2 {
+            assert_eq!(sbox.call::<i32>("AddToStatic", 5).unwrap(), 5);
+            sbox.restore(snapshot.clone()).unwrap();
+        }
+
+        let sbox: MultiUseSandbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        assert!(sbox.memory_fd().is_none());
+    }
+
+    #[test]
+    fn zeroize_memory() {
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_zeroize_memory(true);
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), Some(cfg))
+                .unwrap()
+                .evolve()
+                .unwrap();
+        assert!(sbox.mem_mgr.layout.zeroize_memory());
+
+        let snapshot = sbox.snapshot().unwrap();
+        for _ in 0..2 {
+            assert_eq!(sbox.call::<i32>("AddToStatic", 5).unwrap(), 5);
+            sbox.restore(snapshot.clone()).unwrap();
+        }
+        assert_eq!(sbox.call::<i32>("AddToStatic", 5).unwrap(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "mem_trace")]
+    fn trace_memory_accesses() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let counter: u64 = sbox.call("GetStaticAddress", ()).unwrap();
+        assert!(sbox.trace_memory_accesses(counter, 4).is_err());
+
+        sbox.trace_memory_accesses(counter & !0xfff, 0x1000)
+            .unwrap();
+        assert_eq!(sbox.call::<i32>("AddToStatic", 5).unwrap(), 5);
+        let accesses = sbox.take_memory_accesses();
+        assert!(
+            accesses
+                .iter()
+                .any(|access| access.gva == counter && (1..=4).contains(&access.size)),
+            "{:?}",
+            accesses
+        );
+
+        sbox.stop_tracing_memory_accesses().unwrap();
+        assert_eq!(sbox.call::<i32>("AddToStatic", 5).unwrap(), 10);
+        assert!(sbox.take_memory_accesses().is_empty());
+    }
+
+    #[test]
+    fn scan_memory() {
+        use crate::mem::layout::SandboxMemoryLayout;
+        use crate::mem::memory_region::MemoryRegionType;
+        use crate::mem::shared_mem::SharedMemory as _;
+        use crate::sandbox::memory_scan::{MemoryPattern, MemoryScanOptions};
+
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+
+        let code_gpa = sbox.mem_mgr.layout.get_guest_code_address() as u64;
+        let offset = code_gpa as usize - SandboxMemoryLayout::BASE_ADDRESS;
+        let code = sbox
+            .mem_mgr
+            .shared_mem
+            .with_exclusivity(|snapshot| snapshot.as_slice()[offset..offset + 32].to_vec())
+            .unwrap();
+        let pattern = MemoryPattern::new(code);
+
+        let snapshot = MemoryScanOptions::new().region_type(MemoryRegionType::Snapshot);
+        let matches = sbox.scan_memory(&pattern, &snapshot).unwrap();
+        assert!(
+            matches
+                .iter()
+                .any(|m| m.gpa == code_gpa && m.region_type == MemoryRegionType::Snapshot)
+        );
+
+        let range = snapshot.gpa_range(code_gpa..code_gpa + 31);
+        assert!(sbox.scan_memory(&pattern, &range).unwrap().is_empty());
+    }
+
+    #[test]
+    fn cancel_call() {
+        use crate::sandbox::CancellationToken;
+
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let token = CancellationToken::new();
+        assert_eq!(
+            sbox.call_cancellable::<i32>("AddToStatic", 5, &token)
+                .unwrap(),
+            5
+        );
+
+        let canceller = token.clone();
+        let thread = thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(100));
+            canceller.cancel();
+        });
+        let res = sbox.call_cancellable::<()>("Spin", (), &token);
+        thread.join().unwrap();
+        assert!(matches!(res, Err(HyperlightError::Cancelled)), "{:?}", res);
+        assert!(!sbox.poisoned());
+        assert_eq!(sbox.call::<i32>("AddToStatic", 1).unwrap(), 6);
+
+        // A cancelled token does not run the call
+        let res = sbox.call_cancellable::<i32>("AddToStatic", 1, &token);
+        assert!(matches!(res, Err(HyperlightError::Cancelled)));
+        assert_eq!(sbox.call::<i32>("AddToStatic", 1).unwrap(), 7);
+    }
+
+    #[test]
+    fn call_with_deadline() {
+        use std::time::Duration;
+
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        assert_eq!(
+            sbox.call_with_deadline::<i32>("AddToStatic", 5, Duration::from_secs(10))
+                .unwrap(),
+            5
+        );
+
+        let deadline = Duration::from_millis(100);
+        let res = sbox.call_with_deadline::<()>("Spin", (), deadline);
+        assert!(
+            matches!(res, Err(HyperlightError::DeadlineExceeded(d)) if d == deadline),
+            "{:?}",
+            res
+        );
+        assert!(!sbox.poisoned());
+        assert_eq!(sbox.call::<i32>("AddToStatic", 1).unwrap(), 6);
+    }
 }
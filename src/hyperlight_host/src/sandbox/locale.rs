@@ -0,0 +1,330 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::{Result, new_error};
+
+/// Where the timezone and locale data given to guests comes from
+pub trait LocaleDataSource: Send + Sync {
+    /// Returns the TZif data of the timezone `name`, such as
+    /// `Europe/Paris`, or `None` if it is not known
+    fn timezone(&self, name: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Returns the data of the locale `tag`, such as `fr-FR`, or `None`
+    /// if it is not known
+    fn locale(&self, tag: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// A [`LocaleDataSource`] reading the data from directories, where the
+/// data of a timezone or a locale is the file at its name. Names that
+/// would lead out of the directories are not known.
+#[derive(Clone, Debug, Default)]
+pub struct LocaleDataDirs {
+    timezones: Option<PathBuf>,
+    locales: Option<PathBuf>,
+}
+
+impl LocaleDataDirs {
+    /// Creates a source that knows no timezone and no locale
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a source reading the timezones from the timezone database
+    /// of the system, on the systems that have one
+    pub fn system() -> Self {
+        let mut dirs = Self::new();
+        #[cfg(unix)]
+        {
+            dirs.timezones = Some(PathBuf::from("/usr/share/zoneinfo"));
+        }
+        dirs
+    }
+
+    /// Reads the timezones from `dir`, which is laid out like
+    /// `/usr/share/zoneinfo`
+    pub fn timezones(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.timezones = Some(dir.into());
+        self
+    }
+
+    /// Reads the locales from `dir`
+    pub fn locales(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.locales = Some(dir.into());
+        self
+    }
+}
+
+/// Reads the file `name` of `dir`, if `name` does not lead out of `dir`
+fn read_data_file(dir: Option<&Path>, name: &str) -> Result<Option<Vec<u8>>> {
+    let Some(dir) = dir else {
+        return Ok(None);
+    };
+    let relative = Path::new(name);
+    let contained = relative.components().next().is_some()
+        && relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    if !contained {
+        return Ok(None);
+    }
+    let path = dir.join(relative);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    std::fs::read(&path)
+        .map(Some)
+        .map_err(|e| new_error!("Failed to read {}: {}", path.display(), e))
+}
+
+impl LocaleDataSource for LocaleDataDirs {
+    fn timezone(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        read_data_file(self.timezones.as_deref(), name)
+    }
+
+    fn locale(&self, tag: &str) -> Result<Option<Vec<u8>>> {
+        read_data_file(self.locales.as_deref(), tag)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum LocaleDataKind {
+    Timezone,
+    Locale,
+}
+
+/// The data most recently given to guests, up to a total size
+#[derive(Debug)]
+struct LocaleDataCache {
+    capacity: usize,
+    size: usize,
+    entries: HashMap<(LocaleDataKind, String), Arc<[u8]>>,
+    /// The keys of `entries`, from the oldest to the newest
+    order: VecDeque<(LocaleDataKind, String)>,
+}
+
+impl LocaleDataCache {
+    fn get(&self, key: &(LocaleDataKind, String)) -> Option<Arc<[u8]>> {
+        self.entries.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: (LocaleDataKind, String), data: Arc<[u8]>) {
+        if data.len() > self.capacity || self.entries.contains_key(&key) {
+            return;
+        }
+        while self.size + data.len() > self.capacity {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.size -= evicted.len();
+            }
+        }
+        self.size += data.len();
+        self.order.push_back(key.clone());
+        self.entries.insert(key, data);
+    }
+}
+
+/// Gives the timezone and locale data of a [`LocaleDataSource`] to the
+/// guests of the sandboxes it is registered in with
+/// [`UninitializedSandbox::provide_locale_data`](crate::UninitializedSandbox::provide_locale_data),
+/// so that guests do not have to embed it.
+///
+/// The data is cached, and clones of a provider share their cache, so a
+/// provider registered in many sandboxes reads every entry once. Entries
+/// larger than the limit of the provider are refused, they must also fit
+/// in the input buffer of the guest.
+#[derive(Clone)]
+pub struct LocaleDataProvider {
+    source: Arc<dyn LocaleDataSource>,
+    max_entry_size: usize,
+    cache: Arc<Mutex<LocaleDataCache>>,
+}
+
+impl LocaleDataProvider {
+    /// The default size of the largest entry given to guests
+    pub const DEFAULT_MAX_ENTRY_SIZE: usize = 0x2000;
+    /// The default total size of the cached entries
+    pub const DEFAULT_CACHE_SIZE: usize = 0x10_0000;
+
+    /// Creates a provider of the data of `source`, with the default limits
+    pub fn new(source: impl LocaleDataSource + 'static) -> Self {
+        Self::with_limits(
+            source,
+            Self::DEFAULT_MAX_ENTRY_SIZE,
+            Self::DEFAULT_CACHE_SIZE,
+        )
+    }
+
+    /// Creates a provider of the data of `source` that refuses entries
+    /// larger than `max_entry_size` bytes, and caches at most
+    /// `cache_size` bytes of entries
+    pub fn with_limits(
+        source: impl LocaleDataSource + 'static,
+        max_entry_size: usize,
+        cache_size: usize,
+    ) -> Self {
+        Self {
+            source: Arc::new(source),
+            max_entry_size,
+            cache: Arc::new(Mutex::new(LocaleDataCache {
+                capacity: cache_size,
+                size: 0,
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Returns the TZif data of the timezone `name`, or `None` if it is
+    /// not known
+    pub fn timezone(&self, name: &str) -> Result<Option<Arc<[u8]>>> {
+        self.get(LocaleDataKind::Timezone, name)
+    }
+
+    /// Returns the data of the locale `tag`, or `None` if it is not known
+    pub fn locale(&self, tag: &str) -> Result<Option<Arc<[u8]>>> {
+        self.get(LocaleDataKind::Locale, tag)
+    }
+
+    fn get(&self, kind: LocaleDataKind, name: &str) -> Result<Option<Arc<[u8]>>> {
+        let key = (kind, name.to_string());
+        if let Some(data) = self.lock_cache()?.get(&key) {
+            return Ok(Some(data));
+        }
+
+        // The source is read without holding the lock, so that other
+        // sandboxes are not blocked by a slow source
+        let data = match kind {
+            LocaleDataKind::Timezone => self.source.timezone(name)?,
+            LocaleDataKind::Locale => self.source.locale(name)?,
+        };
+        let Some(data) = data else {
+            return Ok(None);
+        };
+        if data.len() > self.max_entry_size {
+            return Err(new_error!(
+                "The data of {} is {} bytes long, more than the {} bytes allowed",
+                name,
+                data.len(),
+                self.max_entry_size
+            ));
+        }
+
+        let data: Arc<[u8]> = data.into();
+        self.lock_cache()?.insert(key, data.clone());
+        Ok(Some(data))
+    }
+
+    fn lock_cache(&self) -> Result<std::sync::MutexGuard<'_, LocaleDataCache>> {
+        self.cache
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+    }
+}
+
+impl std::fmt::Debug for LocaleDataProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocaleDataProvider")
+            .field("max_entry_size", &self.max_entry_size)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{LocaleDataDirs, LocaleDataProvider, LocaleDataSource};
+    use crate::Result;
+
+    /// Knows every timezone, whose data is its name repeated to `len`
+    /// bytes, and counts the reads
+    struct Generated {
+        len: usize,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl LocaleDataSource for Generated {
+        fn timezone(&self, name: &str) -> Result<Option<Vec<u8>>> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            Ok(Some(name.bytes().cycle().take(self.len).collect()))
+        }
+
+        fn locale(&self, _tag: &str) -> Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn provider_caches_and_limits() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let source = Generated {
+            len: 100,
+            reads: reads.clone(),
+        };
+        let provider = LocaleDataProvider::with_limits(source, 100, 200);
+        let shared = provider.clone();
+
+        assert_eq!(provider.timezone("UTC").unwrap().unwrap()[..3], *b"UTC");
+        assert_eq!(shared.timezone("UTC").unwrap().unwrap().len(), 100);
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+        assert!(provider.locale("fr-FR").unwrap().is_none());
+
+        // The oldest entry is evicted to make room for the third one
+        provider.timezone("Europe/Paris").unwrap();
+        provider.timezone("Asia/Tokyo").unwrap();
+        assert_eq!(reads.load(Ordering::Relaxed), 3);
+        provider.timezone("Asia/Tokyo").unwrap();
+        assert_eq!(reads.load(Ordering::Relaxed), 3);
+        provider.timezone("UTC").unwrap();
+        assert_eq!(reads.load(Ordering::Relaxed), 4);
+
+        let too_large = Generated {
+            len: 101,
+            reads: Arc::new(AtomicUsize::new(0)),
+        };
+        let provider = LocaleDataProvider::with_limits(too_large, 100, 200);
+        assert!(provider.timezone("UTC").is_err());
+    }
+
+    #[test]
+    fn dirs_stay_in_their_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let zoneinfo = root.path().join("zoneinfo");
+        std::fs::create_dir_all(zoneinfo.join("Europe")).unwrap();
+        std::fs::write(zoneinfo.join("Europe/Paris"), b"TZif").unwrap();
+        std::fs::write(root.path().join("secret"), b"secret").unwrap();
+
+        let dirs = LocaleDataDirs::new().timezones(&zoneinfo);
+        assert_eq!(dirs.timezone("Europe/Paris").unwrap().unwrap(), b"TZif");
+        assert!(dirs.timezone("Europe/Nowhere").unwrap().is_none());
+        assert!(dirs.timezone("Europe").unwrap().is_none());
+        assert!(dirs.timezone("../secret").unwrap().is_none());
+        assert!(dirs.timezone("").unwrap().is_none());
+        assert!(
+            dirs.timezone(root.path().join("secret").to_str().unwrap())
+                .unwrap()
+                .is_none()
+        );
+        assert!(dirs.locale("fr-FR").unwrap().is_none());
+    }
+}
@@ -0,0 +1,365 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::mem::memory_region::MemoryRegionType;
+use crate::{Result, new_error};
+
+/// The bytes scanned between two checks of the rate limit
+const SCAN_CHUNK_SIZE: usize = 0x10_0000;
+
+/// What [`MultiUseSandbox::scan_memory`](crate::MultiUseSandbox::scan_memory)
+/// searches guest memory for: a sequence of bytes, some of which can be
+/// wildcards, optionally only at aligned addresses.
+///
+/// Integers and floats convert into a pattern of their little-endian
+/// representation, aligned to their size:
+///
+/// ```
+/// use hyperlight_host::sandbox::memory_scan::MemoryPattern;
+///
+/// let magic = MemoryPattern::from(0xdead_beef_u32);
+/// let text = MemoryPattern::new("hello");
+/// // "h?llo", matching "hello" and "hallo"
+/// let wildcard = MemoryPattern::with_mask("hello", [0xff_u8, 0, 0xff, 0xff, 0xff])?;
+/// # Ok::<(), hyperlight_host::HyperlightError>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryPattern {
+    bytes: Vec<u8>,
+    /// The bits of each byte that have to match
+    mask: Vec<u8>,
+    align: usize,
+}
+
+impl MemoryPattern {
+    /// Creates a pattern matching exactly `bytes`, at any address
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        let bytes = bytes.into();
+        let mask = vec![0xff; bytes.len()];
+        Self {
+            bytes,
+            mask,
+            align: 1,
+        }
+    }
+
+    /// Creates a pattern where only the bits set in `mask` have to match
+    /// the corresponding bits of `bytes`, so a mask byte of 0 matches any
+    /// byte. Fails if `mask` is not as long as `bytes`.
+    pub fn with_mask(bytes: impl Into<Vec<u8>>, mask: impl Into<Vec<u8>>) -> Result<Self> {
+        let bytes = bytes.into();
+        let mask = mask.into();
+        if bytes.len() != mask.len() {
+            return Err(new_error!(
+                "The mask of a memory pattern has {} bytes, but the pattern has {}",
+                mask.len(),
+                bytes.len()
+            ));
+        }
+        Ok(Self {
+            bytes,
+            mask,
+            align: 1,
+        })
+    }
+
+    /// Only matches the pattern at guest addresses that are a multiple
+    /// of `align`
+    pub fn aligned(mut self, align: usize) -> Self {
+        self.align = align.max(1);
+        self
+    }
+
+    /// Returns the length of the pattern
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns whether the pattern is empty, in which case it matches
+    /// nothing
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn matches_at(&self, data: &[u8]) -> bool {
+        self.bytes
+            .iter()
+            .zip(&self.mask)
+            .zip(data)
+            .all(|((b, m), d)| (b ^ d) & m == 0)
+    }
+}
+
+macro_rules! impl_pattern_from_value {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for MemoryPattern {
+                fn from(value: $t) -> Self {
+                    Self::new(value.to_le_bytes()).aligned(size_of::<$t>())
+                }
+            }
+        )*
+    };
+}
+
+impl_pattern_from_value!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl From<&[u8]> for MemoryPattern {
+    fn from(bytes: &[u8]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<&str> for MemoryPattern {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+/// Where and how much [`MultiUseSandbox::scan_memory`](crate::MultiUseSandbox::scan_memory)
+/// searches. By default, all the memory of the sandbox is searched for
+/// all the matches, as fast as possible.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryScanOptions {
+    region_types: Vec<MemoryRegionType>,
+    gpa_range: Option<Range<u64>>,
+    max_matches: Option<usize>,
+    max_bytes_per_second: Option<u64>,
+}
+
+impl MemoryScanOptions {
+    /// Creates options that scan all the memory of the sandbox
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Searches the regions of the given type, which can be called
+    /// several times to search several types of regions. The memory of a
+    /// sandbox is made of a [`MemoryRegionType::Snapshot`] region, a
+    /// [`MemoryRegionType::Scratch`] region holding the memory written by
+    /// the guest, and the regions mapped into the sandbox.
+    pub fn region_type(mut self, region_type: MemoryRegionType) -> Self {
+        self.region_types.push(region_type);
+        self
+    }
+
+    /// Only returns the matches that are entirely within this range of
+    /// guest physical addresses
+    pub fn gpa_range(mut self, range: Range<u64>) -> Self {
+        self.gpa_range = Some(range);
+        self
+    }
+
+    /// Stops the scan once this many matches were found
+    pub fn max_matches(mut self, max_matches: usize) -> Self {
+        self.max_matches = Some(max_matches);
+        self
+    }
+
+    /// Limits how fast memory is scanned, so that scanning a large
+    /// sandbox does not take up a host CPU
+    pub fn max_bytes_per_second(mut self, max_bytes_per_second: u64) -> Self {
+        self.max_bytes_per_second = Some(max_bytes_per_second.max(1));
+        self
+    }
+}
+
+/// An occurrence of a [`MemoryPattern`] in guest memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryMatch {
+    /// The guest physical address of the first byte of the match
+    pub gpa: u64,
+    /// The type of the region the match is in
+    pub region_type: MemoryRegionType,
+}
+
+/// Searches the memory regions of a sandbox one after the other
+pub(crate) struct MemoryScanner<'a> {
+    pattern: &'a MemoryPattern,
+    options: &'a MemoryScanOptions,
+    matches: Vec<MemoryMatch>,
+    start: Instant,
+    scanned: u64,
+}
+
+impl<'a> MemoryScanner<'a> {
+    pub(crate) fn new(pattern: &'a MemoryPattern, options: &'a MemoryScanOptions) -> Self {
+        Self {
+            pattern,
+            options,
+            matches: Vec::new(),
+            start: Instant::now(),
+            scanned: 0,
+        }
+    }
+
+    /// Searches `data`, which is mapped at `base_gpa` in the guest
+    pub(crate) fn scan(&mut self, base_gpa: u64, data: &[u8], region_type: MemoryRegionType) {
+        if self.pattern.is_empty()
+            || self.is_done()
+            || !(self.options.region_types.is_empty()
+                || self.options.region_types.contains(&region_type))
+        {
+            return;
+        }
+
+        // Restrict the data to the requested range of addresses
+        let end_gpa = base_gpa + data.len() as u64;
+        let (start, end) = match &self.options.gpa_range {
+            Some(range) => (range.start.max(base_gpa), range.end.min(end_gpa)),
+            None => (base_gpa, end_gpa),
+        };
+        if start >= end {
+            return;
+        }
+        let data = &data[(start - base_gpa) as usize..(end - base_gpa) as usize];
+
+        let len = self.pattern.len();
+        let align = self.pattern.align as u64;
+        for chunk_start in (0..data.len()).step_by(SCAN_CHUNK_SIZE) {
+            let chunk_end = (chunk_start + SCAN_CHUNK_SIZE).min(data.len());
+            for offset in chunk_start..chunk_end {
+                let gpa = start + offset as u64;
+                if gpa % align != 0 {
+                    continue;
+                }
+                let Some(candidate) = data.get(offset..offset + len) else {
+                    break;
+                };
+                if self.pattern.matches_at(candidate) {
+                    self.matches.push(MemoryMatch { gpa, region_type });
+                    if self.is_done() {
+                        return;
+                    }
+                }
+            }
+            self.throttle((chunk_end - chunk_start) as u64);
+        }
+    }
+
+    /// Returns the matches, ordered by address
+    pub(crate) fn finish(mut self) -> Vec<MemoryMatch> {
+        self.matches.sort_by_key(|m| m.gpa);
+        self.matches
+    }
+
+    fn is_done(&self) -> bool {
+        self.options
+            .max_matches
+            .is_some_and(|max| self.matches.len() >= max)
+    }
+
+    /// Sleeps as long as needed for the scan not to exceed its rate
+    fn throttle(&mut self, bytes: u64) {
+        self.scanned += bytes;
+        let Some(rate) = self.options.max_bytes_per_second else {
+            return;
+        };
+        let expected = Duration::from_secs_f64(self.scanned as f64 / rate as f64);
+        if let Some(wait) = expected.checked_sub(self.start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{MemoryMatch, MemoryPattern, MemoryScanOptions, MemoryScanner};
+    use crate::mem::memory_region::MemoryRegionType;
+
+    fn scan(
+        pattern: &MemoryPattern,
+        options: &MemoryScanOptions,
+        regions: &[(u64, &[u8], MemoryRegionType)],
+    ) -> Vec<u64> {
+        let mut scanner = MemoryScanner::new(pattern, options);
+        for (gpa, data, region_type) in regions {
+            scanner.scan(*gpa, data, *region_type);
+        }
+        scanner.finish().iter().map(|m| m.gpa).collect()
+    }
+
+    #[test]
+    fn patterns() {
+        let data = b"hello hallo hullo\x01\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00";
+        let region = [(0x1000, &data[..], MemoryRegionType::Scratch)];
+        let options = MemoryScanOptions::new();
+
+        assert_eq!(scan(&"hello".into(), &options, &region), vec![0x1000]);
+        let wildcard = MemoryPattern::with_mask("hello", [0xff_u8, 0, 0xff, 0xff, 0xff]).unwrap();
+        assert_eq!(
+            scan(&wildcard, &options, &region),
+            vec![0x1000, 0x1006, 0x100c]
+        );
+        assert!(MemoryPattern::with_mask("hello", [0xff_u8]).is_err());
+
+        // 1u32 is at 0x1011 and 0x1018, but only the latter is aligned
+        assert_eq!(scan(&1u32.into(), &options, &region), vec![0x1018]);
+        assert_eq!(
+            scan(&MemoryPattern::new(1u32.to_le_bytes()), &options, &region),
+            vec![0x1011, 0x1018]
+        );
+        assert!(scan(&MemoryPattern::new(b""), &options, &region).is_empty());
+    }
+
+    #[test]
+    fn filters() {
+        let data = [0xaa_u8; 8];
+        let regions = [
+            (0x1000, &data[..], MemoryRegionType::Snapshot),
+            (0x8000, &data[..], MemoryRegionType::Scratch),
+        ];
+        let pattern = MemoryPattern::new([0xaa_u8, 0xaa]).aligned(4);
+
+        let all = scan(&pattern, &MemoryScanOptions::new(), &regions);
+        assert_eq!(all, vec![0x1000, 0x1004, 0x8000, 0x8004]);
+
+        let scratch = MemoryScanOptions::new().region_type(MemoryRegionType::Scratch);
+        assert_eq!(scan(&pattern, &scratch, &regions), vec![0x8000, 0x8004]);
+
+        // A match must be entirely within the range
+        let range = MemoryScanOptions::new().gpa_range(0x1002..0x8002);
+        assert_eq!(scan(&pattern, &range, &regions), vec![0x1004, 0x8000]);
+
+        let first = MemoryScanOptions::new().max_matches(1);
+        let mut scanner = MemoryScanner::new(&pattern, &first);
+        for (gpa, data, region_type) in regions {
+            scanner.scan(gpa, data, region_type);
+        }
+        assert_eq!(
+            scanner.finish(),
+            vec![MemoryMatch {
+                gpa: 0x1000,
+                region_type: MemoryRegionType::Snapshot
+            }]
+        );
+    }
+
+    #[test]
+    fn rate_limit() {
+        let data = vec![0u8; 0x1000];
+        let options = MemoryScanOptions::new().max_bytes_per_second(0x1000 * 20);
+        let start = Instant::now();
+        let regions = [(0, &data[..], MemoryRegionType::Scratch)];
+        scan(&MemoryPattern::new([1u8]), &options, &regions);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
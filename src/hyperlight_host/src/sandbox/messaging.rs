@@ -0,0 +1,356 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use hyperlight_common::messaging::MAX_MESSAGE_SIZE;
+
+use crate::{Result, new_error};
+
+/// The topics a sandbox connected to a [`MessageBus`] may publish and
+/// subscribe to.
+///
+/// By default, a sandbox can publish and subscribe to every topic.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageBusPolicy {
+    publish: Option<HashSet<String>>,
+    subscribe: Option<HashSet<String>>,
+}
+
+impl MessageBusPolicy {
+    /// Creates a policy that allows every topic
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows publishing to `topic`. Once a topic is allowed, publishing
+    /// to the other topics is denied.
+    pub fn allow_publish(mut self, topic: impl Into<String>) -> Self {
+        self.publish
+            .get_or_insert_with(HashSet::new)
+            .insert(topic.into());
+        self
+    }
+
+    /// Allows subscribing to `topic`. Once a topic is allowed,
+    /// subscribing to the other topics is denied.
+    pub fn allow_subscribe(mut self, topic: impl Into<String>) -> Self {
+        self.subscribe
+            .get_or_insert_with(HashSet::new)
+            .insert(topic.into());
+        self
+    }
+
+    fn allows(topics: &Option<HashSet<String>>, topic: &str) -> bool {
+        topics.as_ref().is_none_or(|topics| topics.contains(topic))
+    }
+}
+
+type Callback = Arc<dyn Fn(&str, &[u8]) + Send + Sync>;
+
+#[derive(Default)]
+struct Topic {
+    /// The messages not yet received by each subscriber
+    queues: HashMap<u64, VecDeque<Vec<u8>>>,
+    callbacks: Vec<Callback>,
+}
+
+#[derive(Default)]
+struct Bus {
+    topics: HashMap<String, Topic>,
+    next_subscriber: u64,
+}
+
+/// A publish/subscribe channel between the sandboxes of the process, and
+/// the host, through named topics. Sandboxes are connected to it with
+/// [`UninitializedSandbox::connect_message_bus`], and their guests use
+/// the functions of `hyperlight_guest_bin::messaging`.
+///
+/// Messages are small, at most [`MAX_MESSAGE_SIZE`] bytes, and are meant
+/// for coordination, such as telling the sandboxes to invalidate a cache
+/// or to reload their configuration. A message is not delivered back to
+/// the sandbox that published it. Each subscriber queues up to
+/// [`MessageBus::QUEUE_CAPACITY`] messages per topic, dropping the oldest
+/// ones when it does not keep up.
+///
+/// Clones share their topics.
+///
+/// [`UninitializedSandbox::connect_message_bus`]: crate::UninitializedSandbox::connect_message_bus
+#[derive(Clone, Default)]
+pub struct MessageBus {
+    inner: Arc<Mutex<Bus>>,
+}
+
+impl MessageBus {
+    /// The number of messages a subscriber queues per topic
+    pub const QUEUE_CAPACITY: usize = 256;
+
+    /// Creates a message bus without topics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes `message` to `topic` from the host
+    pub fn publish(&self, topic: &str, message: &[u8]) -> Result<()> {
+        self.publish_from(None, topic, message)
+    }
+
+    /// Subscribes the host to `topic`, the messages published from now on
+    /// being received with [`Subscription::try_recv`]
+    pub fn subscribe(&self, topic: &str) -> Result<Subscription> {
+        let subscriber = self.new_subscriber()?;
+        self.add_queue(subscriber, topic)?;
+        Ok(Subscription {
+            bus: self.clone(),
+            subscriber,
+            topic: topic.to_string(),
+        })
+    }
+
+    /// Calls `callback` with the topic and the message for every message
+    /// published to `topic` from now on. The callback runs on the thread
+    /// that publishes, which can be the thread of a sandbox, so it should
+    /// return quickly.
+    pub fn on_message(
+        &self,
+        topic: &str,
+        callback: impl Fn(&str, &[u8]) + Send + Sync + 'static,
+    ) -> Result<()> {
+        self.lock()?
+            .topics
+            .entry(topic.to_string())
+            .or_default()
+            .callbacks
+            .push(Arc::new(callback));
+        Ok(())
+    }
+
+    /// Publishes `message` to the subscribers of `topic` but `publisher`
+    pub(crate) fn publish_from(
+        &self,
+        publisher: Option<u64>,
+        topic: &str,
+        message: &[u8],
+    ) -> Result<()> {
+        if message.is_empty() {
+            return Err(new_error!(
+                "Messages published to {} cannot be empty",
+                topic
+            ));
+        }
+        if message.len() > MAX_MESSAGE_SIZE {
+            return Err(new_error!(
+                "Message of {} bytes published to {} is larger than {} bytes",
+                message.len(),
+                topic,
+                MAX_MESSAGE_SIZE
+            ));
+        }
+        let callbacks = {
+            let mut bus = self.lock()?;
+            let Some(topic_state) = bus.topics.get_mut(topic) else {
+                return Ok(());
+            };
+            for (subscriber, queue) in topic_state.queues.iter_mut() {
+                if Some(*subscriber) == publisher {
+                    continue;
+                }
+                if queue.len() == Self::QUEUE_CAPACITY {
+                    log::warn!(
+                        "Dropping the oldest message of {} for a subscriber that does not keep up",
+                        topic
+                    );
+                    queue.pop_front();
+                }
+                queue.push_back(message.to_vec());
+            }
+            topic_state.callbacks.clone()
+        };
+        // The lock is released so that callbacks can publish
+        for callback in callbacks {
+            callback(topic, message);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn new_subscriber(&self) -> Result<u64> {
+        let mut bus = self.lock()?;
+        bus.next_subscriber += 1;
+        Ok(bus.next_subscriber)
+    }
+
+    /// Starts queueing the messages of `topic` for `subscriber`
+    pub(crate) fn add_queue(&self, subscriber: u64, topic: &str) -> Result<()> {
+        self.lock()?
+            .topics
+            .entry(topic.to_string())
+            .or_default()
+            .queues
+            .entry(subscriber)
+            .or_default();
+        Ok(())
+    }
+
+    /// Returns the next message of `topic` for `subscriber`
+    pub(crate) fn pop(&self, subscriber: u64, topic: &str) -> Result<Option<Vec<u8>>> {
+        self.lock()?
+            .topics
+            .get_mut(topic)
+            .and_then(|topic| topic.queues.get_mut(&subscriber))
+            .map(VecDeque::pop_front)
+            .ok_or_else(|| new_error!("Not subscribed to {}", topic))
+    }
+
+    /// Drops the queues of `subscriber`
+    pub(crate) fn remove_subscriber(&self, subscriber: u64) {
+        if let Ok(mut bus) = self.lock() {
+            for topic in bus.topics.values_mut() {
+                topic.queues.remove(&subscriber);
+            }
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Bus>> {
+        self.inner
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+    }
+}
+
+impl std::fmt::Debug for MessageBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageBus").finish_non_exhaustive()
+    }
+}
+
+/// A subscription of the host to a topic of a [`MessageBus`], which
+/// stops receiving messages when it is dropped
+#[derive(Debug)]
+pub struct Subscription {
+    bus: MessageBus,
+    subscriber: u64,
+    topic: String,
+}
+
+impl Subscription {
+    /// Returns the next message published to the topic, or `None` if
+    /// there is none
+    pub fn try_recv(&self) -> Result<Option<Vec<u8>>> {
+        self.bus.pop(self.subscriber, &self.topic)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.bus.remove_subscriber(self.subscriber);
+    }
+}
+
+/// The connection of a sandbox to a message bus, shared by the host
+/// functions of the sandbox, which drops the queues of the sandbox when
+/// the sandbox is dropped
+pub(crate) struct BusConnection {
+    pub(crate) bus: MessageBus,
+    pub(crate) subscriber: u64,
+    pub(crate) policy: MessageBusPolicy,
+}
+
+impl BusConnection {
+    pub(crate) fn publish(&self, topic: &str, message: &[u8]) -> Result<()> {
+        if !MessageBusPolicy::allows(&self.policy.publish, topic) {
+            return Err(new_error!("Publishing to {} is not allowed", topic));
+        }
+        self.bus.publish_from(Some(self.subscriber), topic, message)
+    }
+
+    pub(crate) fn subscribe(&self, topic: &str) -> Result<()> {
+        if !MessageBusPolicy::allows(&self.policy.subscribe, topic) {
+            return Err(new_error!("Subscribing to {} is not allowed", topic));
+        }
+        self.bus.add_queue(self.subscriber, topic)
+    }
+
+    pub(crate) fn poll(&self, topic: &str) -> Result<Vec<u8>> {
+        Ok(self.bus.pop(self.subscriber, topic)?.unwrap_or_default())
+    }
+}
+
+impl Drop for BusConnection {
+    fn drop(&mut self) {
+        self.bus.remove_subscriber(self.subscriber);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{BusConnection, MessageBus, MessageBusPolicy};
+
+    #[test]
+    fn messages_reach_the_other_subscribers() {
+        let bus = MessageBus::new();
+        let host = bus.subscribe("config").unwrap();
+        let connect = |policy| BusConnection {
+            bus: bus.clone(),
+            subscriber: bus.new_subscriber().unwrap(),
+            policy,
+        };
+        let a = connect(MessageBusPolicy::new());
+        let b = connect(
+            MessageBusPolicy::new()
+                .allow_subscribe("config")
+                .allow_publish("cache"),
+        );
+        a.subscribe("config").unwrap();
+        b.subscribe("config").unwrap();
+        assert!(b.subscribe("secrets").is_err());
+
+        let called = Arc::new(AtomicUsize::new(0));
+        let counter = called.clone();
+        bus.on_message("config", move |topic, message| {
+            assert_eq!((topic, message), ("config", &b"v2"[..]));
+            counter.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+
+        a.publish("config", b"v2").unwrap();
+        assert!(b.publish("config", b"v3").is_err());
+        assert_eq!(called.load(Ordering::Relaxed), 1);
+
+        // The publisher does not receive its own message
+        assert_eq!(a.poll("config").unwrap(), b"");
+        assert_eq!(b.poll("config").unwrap(), b"v2");
+        assert_eq!(b.poll("config").unwrap(), b"");
+        assert_eq!(host.try_recv().unwrap(), Some(b"v2".to_vec()));
+        assert!(a.poll("cache").is_err());
+
+        assert!(bus.publish("config", b"").is_err());
+        assert!(bus.publish("config", &[0; 4097]).is_err());
+    }
+
+    #[test]
+    fn slow_subscribers_drop_the_oldest_messages() {
+        let bus = MessageBus::new();
+        let host = bus.subscribe("events").unwrap();
+        for i in 0..=MessageBus::QUEUE_CAPACITY as u32 {
+            bus.publish("events", &i.to_le_bytes()).unwrap();
+        }
+        assert_eq!(host.try_recv().unwrap(), Some(1u32.to_le_bytes().to_vec()));
+    }
+}
@@ -0,0 +1,329 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+use crate::HypervisorKind;
+use crate::hypervisor::virtual_machine::VmExit;
+
+/// The hardware performance counters of the guest during a guest
+/// function call, see [`MultiUseSandbox::last_call_metrics`].
+///
+/// The counters only count the events that happen while the vCPU runs
+/// the guest, not those of the host, including the host functions the
+/// guest calls.
+///
+/// [`MultiUseSandbox::last_call_metrics`]: crate::MultiUseSandbox::last_call_metrics
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SandboxMetrics {
+    /// The number of instructions the guest retired
+    pub instructions_retired: u64,
+    /// The number of CPU cycles the guest ran for
+    pub cycles: u64,
+}
+
+impl SandboxMetrics {
+    /// The average number of instructions retired per cycle, or `None`
+    /// if the guest did not run for any cycle
+    pub fn instructions_per_cycle(&self) -> Option<f64> {
+        (self.cycles != 0).then(|| self.instructions_retired as f64 / self.cycles as f64)
+    }
+}
+
+/// The exits of the vCPU of a sandbox to the host since the sandbox was
+/// created, by reason, see [`MultiUseSandbox::vm_exit_stats`].
+///
+/// Exits are expensive, so a guest that suddenly exits more often is a
+/// likely cause of a performance regression.
+///
+/// [`MultiUseSandbox::vm_exit_stats`]: crate::MultiUseSandbox::vm_exit_stats
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VmExitStats {
+    /// The exits on guest accesses to guest physical memory that is not
+    /// mapped (EPT violations on Intel, NPT faults on AMD), including
+    /// the accesses to MMIO regions
+    pub ept_violations: u64,
+    /// The exits on guest accesses to MMIO regions that the host emulated
+    pub mmio_exits: u64,
+    /// The exits on the guest halting, which ends every guest call
+    pub halt_exits: u64,
+    /// The exits on guest writes to an I/O port, which the guest uses to
+    /// call the host, log and report traces
+    pub outb_exits: u64,
+    /// The exits on guest accesses to MSRs that the hypervisor does not
+    /// virtualize
+    pub msr_exits: u64,
+    /// The other exits, such as the vCPU being interrupted
+    pub other_exits: u64,
+}
+
+impl VmExitStats {
+    /// The number of exits, whatever their reason
+    pub fn total(&self) -> u64 {
+        self.ept_violations + self.halt_exits + self.outb_exits + self.msr_exits + self.other_exits
+    }
+
+    /// Counts `exit`
+    pub(crate) fn record(&mut self, exit: &VmExit) {
+        match exit {
+            VmExit::MmioEmulated() => {
+                self.ept_violations += 1;
+                self.mmio_exits += 1;
+            }
+            VmExit::MmioRead(_) | VmExit::MmioWrite(_) => self.ept_violations += 1,
+            VmExit::Halt() => self.halt_exits += 1,
+            VmExit::IoOut(_, _) => self.outb_exits += 1,
+            VmExit::MsrAccess { .. } => self.msr_exits += 1,
+            #[cfg(gdb)]
+            VmExit::Debug { .. } => self.other_exits += 1,
+            VmExit::Cancelled() | VmExit::Unknown(_) | VmExit::Retry() => self.other_exits += 1,
+        }
+    }
+}
+
+/// The resources a sandbox used since it was created, see
+/// [`MultiUseSandbox::usage`], which are limited by its
+/// [`SandboxQuota`](super::quota::SandboxQuota).
+///
+/// Restoring a snapshot does not reset the usage, compare two readings
+/// to measure a single guest function call.
+///
+/// [`MultiUseSandbox::usage`]: crate::MultiUseSandbox::usage
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SandboxUsage {
+    /// The time the vCPU ran the guest, not counting the exits to the
+    /// host, such as the host functions the guest called
+    pub cpu_time: Duration,
+    /// The number of exits of the vCPU to the host, as counted by
+    /// [`VmExitStats::total`]
+    pub vm_exits: u64,
+    /// The number of host function calls made by the guest
+    pub host_calls: u64,
+    /// The number of bytes the host and the guest passed each other
+    /// through the input and output buffers, that is the guest and host
+    /// function calls and their results
+    pub bytes_transferred: u64,
+}
+
+/// How much of the memory of a sandbox is backed by host memory, see
+/// [`MultiUseSandbox::memory_stats`].
+///
+/// The memory of a sandbox is reserved when the sandbox is created, but
+/// on Linux a page only takes host memory once the guest or the host
+/// touches it, so a sandbox that only uses a small part of its memory
+/// costs little more than that part. On Windows, the memory is committed
+/// when the sandbox is created, and only the pages that were touched are
+/// resident.
+///
+/// [`MultiUseSandbox::memory_stats`]: crate::MultiUseSandbox::memory_stats
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MemoryStats {
+    /// The size of the memory the sandbox was configured with, in bytes
+    pub configured: usize,
+    /// The size of the pages of that memory that are resident on the
+    /// host, in bytes, including the pages still shared with the
+    /// snapshot the sandbox was created from or restored
+    pub committed: usize,
+}
+
+impl MemoryStats {
+    /// The share of the configured memory that is committed, from 0 to 1
+    pub fn committed_ratio(&self) -> f64 {
+        if self.configured == 0 {
+            return 0.0;
+        }
+        self.committed as f64 / self.configured as f64
+    }
+}
+
+/// A phase of the construction of a sandbox, see [`CreationReport`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CreationPhase {
+    /// Parsing the guest binary and loading it into the memory of the
+    /// snapshot
+    ElfLoad,
+    /// Building the page tables of the guest
+    PageTableSetup,
+    /// Allocating the shared memory of the sandbox and copying the
+    /// snapshot into it
+    MemoryAllocation,
+    /// Creating the VM and its vCPU, and mapping the memory into the VM
+    VmCreation,
+    /// Setting the initial registers and TSC of the vCPU
+    VcpuSetup,
+    /// Running the initialisation function of the guest
+    GuestInit,
+}
+
+impl Display for CreationPhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreationPhase::ElfLoad => write!(f, "ELF load"),
+            CreationPhase::PageTableSetup => write!(f, "page table setup"),
+            CreationPhase::MemoryAllocation => write!(f, "memory allocation"),
+            CreationPhase::VmCreation => write!(f, "VM creation"),
+            CreationPhase::VcpuSetup => write!(f, "vCPU setup"),
+            CreationPhase::GuestInit => write!(f, "guest initialisation"),
+        }
+    }
+}
+
+/// How long each phase of the construction of a sandbox took, see
+/// [`MultiUseSandbox::creation_report`], to find where the cold start
+/// time of a sandbox goes and to compare hypervisors.
+///
+/// Each phase is also traced as a `sandbox_creation` span.
+///
+/// [`MultiUseSandbox::creation_report`]: crate::MultiUseSandbox::creation_report
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CreationReport {
+    phases: Vec<(CreationPhase, Duration)>,
+    hypervisor: Option<HypervisorKind>,
+}
+
+impl CreationReport {
+    /// The phases, in the order they ran, with how long they took
+    pub fn phases(&self) -> &[(CreationPhase, Duration)] {
+        &self.phases
+    }
+
+    /// How long `phase` took, or `None` if it did not run, such as the
+    /// ELF load of a sandbox created from a snapshot
+    pub fn duration(&self, phase: CreationPhase) -> Option<Duration> {
+        self.phases
+            .iter()
+            .find(|(p, _)| *p == phase)
+            .map(|(_, duration)| *duration)
+    }
+
+    /// How long all the phases took
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// The hypervisor the VM was created with
+    pub fn hypervisor(&self) -> Option<HypervisorKind> {
+        self.hypervisor
+    }
+
+    /// Runs `f` as `phase`, adding how long it took to the phase
+    pub(crate) fn time<T>(&mut self, phase: CreationPhase, f: impl FnOnce() -> T) -> T {
+        let span = tracing::debug_span!("sandbox_creation", phase = %phase);
+        let start = Instant::now();
+        let ret = span.in_scope(f);
+        self.add(phase, start.elapsed());
+        ret
+    }
+
+    /// Appends the phases of `other`, which ran after the ones of `self`
+    pub(crate) fn append(&mut self, other: CreationReport) {
+        for (phase, duration) in other.phases {
+            self.add(phase, duration);
+        }
+        self.hypervisor = other.hypervisor.or(self.hypervisor);
+    }
+
+    pub(crate) fn set_hypervisor(&mut self, hypervisor: HypervisorKind) {
+        self.hypervisor = Some(hypervisor);
+    }
+
+    fn add(&mut self, phase: CreationPhase, elapsed: Duration) {
+        match self.phases.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, duration)) => *duration += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CreationPhase, CreationReport, MemoryStats, SandboxMetrics, VmExitStats};
+    use crate::hypervisor::virtual_machine::VmExit;
+
+    #[test]
+    fn instructions_per_cycle() {
+        let metrics = SandboxMetrics {
+            instructions_retired: 300,
+            cycles: 200,
+        };
+        assert_eq!(metrics.instructions_per_cycle(), Some(1.5));
+        assert_eq!(SandboxMetrics::default().instructions_per_cycle(), None);
+    }
+
+    #[test]
+    fn committed_ratio() {
+        let stats = MemoryStats {
+            configured: 0x10000,
+            committed: 0x4000,
+        };
+        assert_eq!(stats.committed_ratio(), 0.25);
+        assert_eq!(MemoryStats::default().committed_ratio(), 0.0);
+    }
+
+    #[test]
+    fn creation_report() {
+        let mut report = CreationReport::default();
+        assert_eq!(report.time(CreationPhase::ElfLoad, || 42), 42);
+        let mut vm = CreationReport::default();
+        vm.time(CreationPhase::VmCreation, || ());
+        vm.time(CreationPhase::VcpuSetup, || ());
+        vm.time(CreationPhase::VmCreation, || ());
+        report.append(vm);
+
+        let phases: Vec<_> = report.phases().iter().map(|(phase, _)| *phase).collect();
+        assert_eq!(
+            phases,
+            [
+                CreationPhase::ElfLoad,
+                CreationPhase::VmCreation,
+                CreationPhase::VcpuSetup
+            ]
+        );
+        assert_eq!(report.duration(CreationPhase::GuestInit), None);
+        assert_eq!(
+            report.total(),
+            report.phases().iter().map(|(_, duration)| *duration).sum()
+        );
+    }
+
+    #[test]
+    fn vm_exit_stats() {
+        let mut stats = VmExitStats::default();
+        for exit in [
+            VmExit::MmioEmulated(),
+            VmExit::MmioRead(0x1000),
+            VmExit::IoOut(0x100, vec![0; 4]),
+            VmExit::IoOut(0x100, vec![0; 4]),
+            VmExit::Halt(),
+            VmExit::Retry(),
+        ] {
+            stats.record(&exit);
+        }
+        assert_eq!(
+            stats,
+            VmExitStats {
+                ept_violations: 2,
+                mmio_exits: 1,
+                halt_exits: 1,
+                outb_exits: 2,
+                msr_exits: 0,
+                other_exits: 1,
+            }
+        );
+        assert_eq!(stats.total(), 6);
+    }
+}
@@ -0,0 +1,234 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::io::{Read, Write};
+
+use hyperlight_common::mem::PAGE_SIZE_USIZE;
+
+use super::SandboxConfiguration;
+use super::checkpoint_file;
+use super::snapshot::Snapshot;
+use super::snapshot_file::{Decoder, Encoder};
+use crate::HyperlightError::SnapshotSandboxMismatch;
+use crate::func::HostFunctions;
+use crate::{MultiUseSandbox, Result, new_error};
+
+/// The first bytes sent by a migration
+const MAGIC: [u8; 8] = *b"HLMIGRAT";
+/// The version of the protocol, changed on incompatible changes
+const VERSION: u32 = 1;
+
+/// A message with the pages of the sandbox changed since the previous one
+const PAGES: u8 = 0;
+/// The last message, with the state of the sandbox besides its memory
+const DONE: u8 = 1;
+
+/// Moves a running sandbox to another host or process, over a transport
+/// such as a TCP stream, with a bounded pause.
+///
+/// The memory of the sandbox is sent in rounds while it keeps running:
+/// the first call to [`precopy`](Self::precopy) sends all of it, and each
+/// next call the pages the sandbox changed since the previous round.
+/// Once a round sends few enough pages, [`finish`](Self::finish) stops the
+/// sandbox and sends the pages it changed since the last round, along
+/// with the state of its vCPU and its host functions, so the sandbox is
+/// only paused for as long as these pages take to send. The other end
+/// carries on with the sandbox with
+/// [`MultiUseSandbox::receive_migration`].
+///
+/// Sandboxes that have host memory mapped into them cannot be migrated.
+///
+/// ```no_run
+/// # use std::net::TcpStream;
+/// # use hyperlight_host::{MultiUseSandbox, Result, UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::sandbox::migration::SandboxMigration;
+/// # fn example() -> Result<()> {
+/// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+///
+/// let mut migration = SandboxMigration::new(TcpStream::connect("10.0.0.2:7000")?)?;
+/// while migration.precopy(&mut sandbox)? > 64 {
+///     sandbox.call::<()>("HandleRequest", ())?;
+/// }
+/// migration.finish(sandbox)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SandboxMigration<W: Write> {
+    transport: W,
+    /// The memory of the sandbox as last sent, which the next round is
+    /// compared to
+    sent: Vec<u8>,
+    /// The sandbox being migrated, known from the first round on
+    sandbox_id: Option<u64>,
+}
+
+impl<W: Write> SandboxMigration<W> {
+    /// Starts a migration over `transport`
+    pub fn new(mut transport: W) -> Result<Self> {
+        transport.write_all(&MAGIC)?;
+        transport.write_all(&VERSION.to_le_bytes())?;
+        Ok(Self {
+            transport,
+            sent: Vec::new(),
+            sandbox_id: None,
+        })
+    }
+
+    /// Sends the pages of `sandbox` changed since the previous round, or
+    /// all of them on the first round, returning how many were sent. The
+    /// sandbox keeps running between the rounds, which must all be made
+    /// with the same sandbox.
+    pub fn precopy(&mut self, sandbox: &mut MultiUseSandbox) -> Result<usize> {
+        let snapshot = sandbox.snapshot()?;
+        self.send_pages(&snapshot)
+    }
+
+    /// Stops `sandbox` and sends the pages it changed since the last
+    /// round, followed by the rest of its state, completing the
+    /// migration. Returns the transport.
+    pub fn finish(mut self, mut sandbox: MultiUseSandbox) -> Result<W> {
+        let snapshot = sandbox.snapshot()?;
+        self.send_pages(&snapshot)?;
+        let host_functions = sandbox.host_function_details();
+        drop(sandbox);
+
+        let mut enc = Encoder::default();
+        enc.u8(DONE);
+        enc.bytes(&checkpoint_file::encode_host_functions(host_functions)?);
+        snapshot.encode_state(&mut enc)?;
+        enc.bytes.extend_from_slice(&snapshot.hash());
+        self.send(enc)?;
+        self.transport.flush()?;
+        Ok(self.transport)
+    }
+
+    fn send_pages(&mut self, snapshot: &Snapshot) -> Result<usize> {
+        if *self.sandbox_id.get_or_insert(snapshot.sandbox_id()) != snapshot.sandbox_id() {
+            return Err(SnapshotSandboxMismatch);
+        }
+        if !snapshot.regions().is_empty() {
+            return Err(new_error!(
+                "Sandboxes with {} mapped regions cannot be migrated",
+                snapshot.regions().len()
+            ));
+        }
+        let memory = snapshot.memory();
+        let dirty: Vec<usize> = memory
+            .chunks(PAGE_SIZE_USIZE)
+            .enumerate()
+            .filter(|(i, page)| {
+                let start = i * PAGE_SIZE_USIZE;
+                self.sent.get(start..start + page.len()) != Some(*page)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut enc = Encoder::default();
+        enc.u8(PAGES);
+        enc.u64(memory.len() as u64);
+        enc.u64(dirty.len() as u64);
+        for &i in &dirty {
+            let start = i * PAGE_SIZE_USIZE;
+            let end = (start + PAGE_SIZE_USIZE).min(memory.len());
+            enc.u64(i as u64);
+            enc.bytes(&memory[start..end]);
+        }
+        self.send(enc)?;
+        self.sent.clear();
+        self.sent.extend_from_slice(memory);
+        Ok(dirty.len())
+    }
+
+    /// Sends the message written to `enc`, preceded by its length
+    fn send(&mut self, enc: Encoder) -> Result<()> {
+        self.transport
+            .write_all(&(enc.bytes.len() as u64).to_le_bytes())?;
+        self.transport.write_all(&enc.bytes)?;
+        Ok(())
+    }
+}
+
+/// Receives the sandbox sent by a [`SandboxMigration`] from `transport`,
+/// see [`MultiUseSandbox::receive_migration`]
+pub(crate) fn receive(
+    mut transport: impl Read,
+    host_functions: &HostFunctions,
+    cfg: Option<SandboxConfiguration>,
+) -> Result<MultiUseSandbox> {
+    let mut header = [0u8; 12];
+    transport.read_exact(&mut header)?;
+    let mut dec = Decoder::new(&header);
+    let magic: [u8; 8] = dec.array()?;
+    if magic != MAGIC {
+        return Err(new_error!("Not a sandbox migration"));
+    }
+    let version = dec.u32()?;
+    if version != VERSION {
+        return Err(new_error!(
+            "Sandbox migration version {} is not supported, expected {}",
+            version,
+            VERSION
+        ));
+    }
+
+    let mut memory = Vec::new();
+    loop {
+        let message = receive_message(&mut transport)?;
+        let mut dec = Decoder::new(&message);
+        match dec.u8()? {
+            PAGES => {
+                memory.resize(dec.usize()?, 0);
+                for _ in 0..dec.u64()? {
+                    let start = dec.usize()?.saturating_mul(PAGE_SIZE_USIZE);
+                    let page = dec.bytes()?;
+                    memory
+                        .get_mut(start..start.saturating_add(page.len()))
+                        .ok_or_else(|| {
+                            new_error!("Migrated page at {:#x} is out of bounds", start)
+                        })?
+                        .copy_from_slice(page);
+                }
+                dec.finish()?;
+            }
+            DONE => {
+                let saved = checkpoint_file::decode_host_functions(dec.bytes()?)?;
+                let state = Snapshot::decode_state(&mut dec)?;
+                let hash: [u8; 32] = dec.array()?;
+                dec.finish()?;
+                let snapshot = state.with_saved_memory(memory, hash)?;
+                return checkpoint_file::restore(snapshot, &saved, host_functions, cfg);
+            }
+            tag => return Err(new_error!("Invalid sandbox migration message {}", tag)),
+        }
+    }
+}
+
+/// Receives a message sent by [`SandboxMigration::send`]
+fn receive_message(transport: &mut impl Read) -> Result<Vec<u8>> {
+    let mut len = [0u8; 8];
+    transport.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    let mut message = Vec::new();
+    transport.take(len).read_to_end(&mut message)?;
+    if message.len() as u64 != len {
+        return Err(new_error!("Sandbox migration was cut short"));
+    }
+    Ok(message)
+}
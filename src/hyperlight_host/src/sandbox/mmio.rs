@@ -0,0 +1,134 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::Debug;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::{Result, new_error};
+
+/// A host callback emulating the accesses to an MMIO region.
+///
+/// It is called with the guest physical address accessed, the size of
+/// the access, whether the access is a write, and the data of the
+/// access: the bytes written by the guest, or the buffer to fill with
+/// the bytes read by the guest.
+pub type MmioHandler = dyn Fn(u64, usize, bool, &mut [u8]) + Send + Sync;
+
+/// The MMIO regions registered for a sandbox
+#[derive(Clone, Default)]
+pub(crate) struct MmioRegions {
+    regions: Vec<(Range<u64>, Arc<MmioHandler>)>,
+}
+
+impl Debug for MmioRegions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(
+                self.regions
+                    .iter()
+                    .map(|(range, _)| format_args!("{:#x}..{:#x}", range.start, range.end)),
+            )
+            .finish()
+    }
+}
+
+impl MmioRegions {
+    /// Adds a region, which must not overlap the regions already added
+    pub(crate) fn register(&mut self, range: Range<u64>, handler: Arc<MmioHandler>) -> Result<()> {
+        if range.is_empty() {
+            return Err(new_error!(
+                "MMIO region {:#x}..{:#x} is empty",
+                range.start,
+                range.end
+            ));
+        }
+        if let Some((other, _)) = self
+            .regions
+            .iter()
+            .find(|(other, _)| other.start < range.end && range.start < other.end)
+        {
+            return Err(new_error!(
+                "MMIO region {:#x}..{:#x} overlaps MMIO region {:#x}..{:#x}",
+                range.start,
+                range.end,
+                other.start,
+                other.end
+            ));
+        }
+        self.regions.push((range, handler));
+        Ok(())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Emulates an access to `addr` with the handler of the region it is
+    /// in. Returns `false` if `addr` is not in any region.
+    pub(crate) fn handle(&self, addr: u64, is_write: bool, data: &mut [u8]) -> bool {
+        match self.regions.iter().find(|(range, _)| range.contains(&addr)) {
+            Some((_, handler)) => {
+                handler(addr, data.len(), is_write, data);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn noop(_: u64, _: usize, _: bool, _: &mut [u8]) {}
+
+    #[test]
+    fn register_and_handle() {
+        let accesses = Arc::new(Mutex::new(Vec::new()));
+        let mut regions = MmioRegions::default();
+        let log = accesses.clone();
+        regions
+            .register(
+                0x1000..0x2000,
+                Arc::new(
+                    move |addr: u64, size: usize, is_write: bool, data: &mut [u8]| {
+                        log.lock().unwrap().push((addr, size, is_write));
+                        if !is_write {
+                            data.fill(0xab);
+                        }
+                    },
+                ),
+            )
+            .unwrap();
+
+        assert!(regions.register(0x1fff..0x3000, Arc::new(noop)).is_err());
+        assert!(regions.register(0x3000..0x3000, Arc::new(noop)).is_err());
+        regions.register(0x2000..0x3000, Arc::new(noop)).unwrap();
+
+        let mut data = [0; 4];
+        assert!(regions.handle(0x1ffc, false, &mut data));
+        assert_eq!(data, [0xab; 4]);
+        assert!(regions.handle(0x1000, true, &mut data[..2]));
+        assert!(!regions.handle(0x3000, false, &mut data));
+        assert_eq!(
+            *accesses.lock().unwrap(),
+            vec![(0x1ffc, 4, false), (0x1000, 2, true)]
+        );
+    }
+}
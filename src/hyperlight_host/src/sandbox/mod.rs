@@ -14,14 +14,70 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+/// Calling into sandboxes from async code
+pub mod async_sandbox;
+/// Host buffers lent to the guest for the duration of guest function
+/// calls, mapped into the guest instead of being copied
+pub mod borrowed_buffer;
+/// Cancellation of guest function calls that rolls the sandbox back
+pub mod cancellation;
+/// The snapshots taken at the checkpoints declared by the guest
+pub mod checkpoint;
+/// The on-disk format of the checkpoints of sandboxes
+pub(crate) mod checkpoint_file;
 /// Configuration needed to establish a sandbox.
 pub mod config;
+/// The timer cancelling the guest function calls that outlive their
+/// deadline
+pub(crate) mod deadline;
+/// Subscriptions to the events raised while the guest runs, for
+/// building debugging tools
+pub mod debug_events;
+/// Checking a deployment end to end with the self-test and benchmark
+/// functions of the guest
+pub mod diagnostics;
+/// Metering guest function calls by the instructions they retire
+pub mod fuel;
+/// What the guest was doing when it aborted
+pub mod guest_crash;
+/// Delivery of the guest log records to the host log sinks
+pub(crate) mod guest_log;
+/// Recording of the host function calls made by a guest, to replay them
+/// as mocks in tests
+pub mod host_call_recording;
 /// Functionality for reading, but not modifying host functions
 pub(crate) mod host_funcs;
 /// Functionality for dealing with initialized sandboxes that can
 /// call 0 or more guest functions
 pub mod initialized_multi_use;
+/// Timezone and locale data given to guests on demand
+pub mod locale;
+/// Searching the memory of a sandbox for byte patterns or values
+pub mod memory_scan;
+/// Messages published by sandboxes to named topics, for the other
+/// sandboxes of the process
+pub mod messaging;
+/// The hardware performance counters of the guest calls, and the
+/// timings of the construction of sandboxes
+pub mod metrics;
+/// Moving running sandboxes to other hosts with a bounded pause
+pub mod migration;
+/// Guest physical address ranges whose accesses are emulated by the host
+pub mod mmio;
+/// Callbacks for the events in the life of a sandbox
+pub mod observer;
 pub(crate) mod outb;
+/// Pools of initialized sandboxes reset between uses
+pub mod pool;
+/// Limits on the resources used by all the sandboxes of the process, and
+/// by each sandbox over its lifetime
+pub mod quota;
+/// Secrets given to guests, with access policies
+pub mod secrets;
+/// Sandboxes whose guest functions are called through a shared reference
+pub mod shared;
+/// Memory regions mapped into several sandboxes at the same time
+pub mod shared_region;
 /// Functionality for creating uninitialized sandboxes, manipulating them,
 /// and converting them to initialized sandboxes.
 pub mod uninitialized;
@@ -31,6 +87,15 @@ pub(crate) mod uninitialized_evolve;
 
 /// Representation of a snapshot of a `Sandbox`.
 pub mod snapshot;
+/// Chains of snapshots that store the pages changed from one to the next
+pub mod snapshot_chain;
+/// The on-disk format of snapshots
+pub(crate) mod snapshot_file;
+/// The lifecycle state of a sandbox
+pub mod status;
+/// Streams of payloads larger than the input and output buffers,
+/// transferred between the guest and the host in chunks
+pub mod streams;
 
 /// Trait used by the macros to paper over the differences between hyperlight and hyperlight-wasm
 mod callable;
@@ -39,12 +104,77 @@ mod callable;
 #[cfg(feature = "trace_guest")]
 pub(crate) mod trace;
 
+/// Re-export for the `AsyncSandbox` type
+pub use async_sandbox::AsyncSandbox;
+/// Re-export for the `BorrowedGuestBuffer` type
+pub use borrowed_buffer::BorrowedGuestBuffer;
 /// Trait used by the macros to paper over the differences between hyperlight and hyperlight-wasm
 pub use callable::Callable;
+/// Re-export for the `CancellationToken` type
+pub use cancellation::CancellationToken;
+/// Re-export for the `CpuSet` type
+pub use config::CpuSet;
+/// Re-export for the `CpuidOverride` type
+pub use config::CpuidOverride;
+/// Re-export for the `CpuidRegister` type
+pub use config::CpuidRegister;
+/// Re-export for `FaultInjection` type
+pub use config::FaultInjection;
+/// Re-export for the `GuestCheckpointPolicy` type
+pub use config::GuestCheckpointPolicy;
+/// Re-export for the `GuestLogBackpressure` type
+pub use config::GuestLogBackpressure;
+/// Re-export for the `GuestLogOverflow` type
+pub use config::GuestLogOverflow;
+/// Re-export for the `MemProfileCapture` type
+#[cfg(feature = "mem_profile")]
+pub use config::MemProfileCapture;
+/// Re-export for the `MemoryBacking` type
+pub use config::MemoryBacking;
+/// Re-export for the `MsrPolicy` type
+pub use config::MsrPolicy;
 /// Re-export for `SandboxConfiguration` type
 pub use config::SandboxConfiguration;
+/// Re-export for the `VcpuPriority` type
+pub use config::VcpuPriority;
+/// Re-export for the `GuestCrashReport` type
+pub use guest_crash::GuestCrashReport;
+/// Re-export for the `GuestPanic` type
+pub use guest_crash::GuestPanic;
+/// Re-export for the `GuestPanicLocation` type
+pub use guest_crash::GuestPanicLocation;
+/// Re-export for the `BoundFunction` type
+pub use initialized_multi_use::BoundFunction;
+/// Re-export for the `LendingSandbox` type
+#[cfg(target_os = "linux")]
+pub use initialized_multi_use::LendingSandbox;
 /// Re-export for the `MultiUseSandbox` type
 pub use initialized_multi_use::MultiUseSandbox;
+/// Re-export for the `CreationPhase` type
+pub use metrics::CreationPhase;
+/// Re-export for the `CreationReport` type
+pub use metrics::CreationReport;
+/// Re-export for the `MemoryStats` type
+pub use metrics::MemoryStats;
+/// Re-export for the `SandboxMetrics` type
+pub use metrics::SandboxMetrics;
+/// Re-export for the `SandboxUsage` type
+pub use metrics::SandboxUsage;
+/// Re-export for the `VmExitStats` type
+pub use metrics::VmExitStats;
+/// Re-export for the `PoolStats` type
+pub use pool::PoolStats;
+/// Re-export for the `PooledSandbox` type
+pub use pool::PooledSandbox;
+/// Re-export for the `SandboxPool` type
+pub use pool::SandboxPool;
+/// Re-export for the `SharedSandbox` type
+pub use shared::SharedSandbox;
+/// Re-export for the `StreamId` type
+pub use streams::StreamId;
+/// Re-export for the `MemoryAccess` type
+#[cfg(feature = "mem_trace")]
+pub use trace::MemoryAccess;
 /// Re-export for `GuestBinary` type
 pub use uninitialized::GuestBinary;
 /// Re-export for `UninitializedSandbox` type
@@ -0,0 +1,157 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
+
+use crate::HyperlightError;
+
+/// Callbacks for the events in the life of a sandbox, added with
+/// [`UninitializedSandbox::add_observer`], so that logging, metrics or
+/// policy code can follow sandboxes without changes to hyperlight.
+///
+/// Every method does nothing by default, so an observer only implements
+/// the events it cares about. The sandbox is identified by its
+/// [`id`](crate::MultiUseSandbox::id), which is known from
+/// [`created`](Self::created) on.
+///
+/// The callbacks are made on the thread using the sandbox, while it
+/// waits for them, so they should only do little work.
+///
+/// [`UninitializedSandbox::add_observer`]: crate::UninitializedSandbox::add_observer
+pub trait SandboxObserver: Send + Sync {
+    /// The VM of the sandbox was created, and the guest is about to be
+    /// initialised
+    fn created(&self, _sandbox_id: u64) {}
+
+    /// The guest was initialised, and the sandbox is ready to be called
+    fn initialized(&self, _sandbox_id: u64) {}
+
+    /// The guest function `function` is about to be called
+    fn call_started(&self, _sandbox_id: u64, _function: &str) {}
+
+    /// The call to the guest function `function` completed with `result`
+    fn call_finished(
+        &self,
+        _sandbox_id: u64,
+        _function: &str,
+        _result: std::result::Result<&ReturnValue, &HyperlightError>,
+    ) {
+    }
+
+    /// The sandbox was restored from a snapshot
+    fn restored(&self, _sandbox_id: u64) {}
+
+    /// The sandbox was dropped, or its guest failed to initialise
+    fn dropped(&self, _sandbox_id: u64) {}
+}
+
+/// The observers of a sandbox, told about its events in the order they
+/// were added
+#[derive(Clone, Default)]
+pub(crate) struct SandboxObservers(Vec<Arc<dyn SandboxObserver>>);
+
+impl SandboxObservers {
+    pub(crate) fn add(&mut self, observer: Arc<dyn SandboxObserver>) {
+        self.0.push(observer);
+    }
+
+    pub(crate) fn created(&self, sandbox_id: u64) {
+        self.0.iter().for_each(|o| o.created(sandbox_id));
+    }
+
+    pub(crate) fn initialized(&self, sandbox_id: u64) {
+        self.0.iter().for_each(|o| o.initialized(sandbox_id));
+    }
+
+    pub(crate) fn call_started(&self, sandbox_id: u64, function: &str) {
+        self.0
+            .iter()
+            .for_each(|o| o.call_started(sandbox_id, function));
+    }
+
+    pub(crate) fn call_finished(
+        &self,
+        sandbox_id: u64,
+        function: &str,
+        result: std::result::Result<&ReturnValue, &HyperlightError>,
+    ) {
+        self.0
+            .iter()
+            .for_each(|o| o.call_finished(sandbox_id, function, result));
+    }
+
+    pub(crate) fn restored(&self, sandbox_id: u64) {
+        self.0.iter().for_each(|o| o.restored(sandbox_id));
+    }
+
+    pub(crate) fn dropped(&self, sandbox_id: u64) {
+        self.0.iter().for_each(|o| o.dropped(sandbox_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
+
+    use super::{SandboxObserver, SandboxObservers};
+    use crate::HyperlightError;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl SandboxObserver for Recorder {
+        fn call_started(&self, sandbox_id: u64, function: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{sandbox_id} started {function}"));
+        }
+
+        fn call_finished(
+            &self,
+            sandbox_id: u64,
+            function: &str,
+            result: Result<&ReturnValue, &HyperlightError>,
+        ) {
+            self.0.lock().unwrap().push(format!(
+                "{sandbox_id} finished {function}: {}",
+                result.is_ok()
+            ));
+        }
+    }
+
+    #[test]
+    fn every_observer_is_told() {
+        let first = Arc::new(Recorder::default());
+        let second = Arc::new(Recorder::default());
+        let mut observers = SandboxObservers::default();
+        observers.add(first.clone());
+        observers.add(second.clone());
+
+        observers.created(1);
+        observers.call_started(1, "Echo");
+        observers.call_finished(1, "Echo", Ok(&ReturnValue::Void(())));
+        observers.dropped(1);
+
+        let expected = ["1 started Echo", "1 finished Echo: true"];
+        assert_eq!(*first.0.lock().unwrap(), expected);
+        assert_eq!(*second.0.lock().unwrap(), expected);
+    }
+}
@@ -16,15 +16,18 @@ limitations under the License.
 
 use std::sync::{Arc, Mutex};
 
+use hyperlight_common::flatbuffer_wrappers::function_call::FunctionCall;
 use hyperlight_common::flatbuffer_wrappers::function_types::{FunctionCallResult, ParameterValue};
 use hyperlight_common::flatbuffer_wrappers::guest_error::{ErrorCode, GuestError};
 use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
-use hyperlight_common::outb::{Exception, OutBAction};
-use log::{Level, Record};
+use hyperlight_common::outb::{ABORT_TERMINATOR, Exception, OutBAction};
 use tracing::{Span, instrument};
-use tracing_log::format_trace;
 
+use super::debug_events::{DebugEvent, DebugEventSink};
+use super::guest_crash::GuestPanic;
+use super::guest_log::GuestLogSink;
 use super::host_funcs::FunctionRegistry;
+use crate::HyperlightError;
 #[cfg(feature = "mem_profile")]
 use crate::hypervisor::regs::CommonRegisters;
 use crate::mem::mgr::SandboxMemoryManager;
@@ -41,6 +44,10 @@ pub enum HandleOutbError {
         code: u8,
         /// The error message from the guest
         message: String,
+        /// The exception that made the guest abort, if it reported one
+        exception: Option<Exception>,
+        /// The panic that made the guest abort, if it sent one
+        panic: Option<GuestPanic>,
     },
     #[error("Invalid outb port: {0}")]
     InvalidPort(String),
@@ -64,71 +71,15 @@ pub enum HandleOutbError {
 #[instrument(err(Debug), skip_all, parent = Span::current(), level="Trace")]
 pub(super) fn outb_log(
     mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    guest_logs: &mut GuestLogSink,
 ) -> Result<(), HandleOutbError> {
-    // This code will create either a logging record or a tracing record for the GuestLogData depending on if the host has set up a tracing subscriber.
-    // In theory as we have enabled the log feature in the Cargo.toml for tracing this should happen
-    // automatically (based on if there is tracing subscriber present) but only works if the event created using macros. (see https://github.com/tokio-rs/tracing/blob/master/tracing/src/macros.rs#L2421 )
-    // The reason that we don't want to use the tracing macros is that we want to be able to explicitly
-    // set the file and line number for the log record which is not possible with macros.
-    // This is because the file and line number come from the  guest not the call site.
-
     let log_data: GuestLogData = mgr
         .read_guest_log_data()
         .map_err(|e| HandleOutbError::ReadLogData(e.to_string()))?;
 
-    let record_level: Level = (&log_data.level).into();
-
-    // Work out if we need to log or trace
-    // this API is marked as follows but it is the easiest way to work out if we should trace or log
-
-    // Private API for internal use by tracing's macros.
-    //
-    // This function is *not* considered part of `tracing`'s public API, and has no
-    // stability guarantees. If you use it, and it breaks or disappears entirely,
-    // don't say we didn't warn you.
-
-    let should_trace = tracing_core::dispatcher::has_been_set();
-    let source_file = Some(log_data.source_file.as_str());
-    let line = Some(log_data.line);
-    let source = Some(log_data.source.as_str());
-
-    // See https://github.com/rust-lang/rust/issues/42253 for the reason this has to be done this way
-
-    if should_trace {
-        // Create a tracing event for the GuestLogData
-        // Ideally we would create tracing metadata based on the Guest Log Data
-        // but tracing derives the metadata at compile time
-        // see https://github.com/tokio-rs/tracing/issues/2419
-        // so we leave it up to the subscriber to figure out that there are logging fields present with this data
-        format_trace(
-            &Record::builder()
-                .args(format_args!("{}", log_data.message))
-                .level(record_level)
-                .target("hyperlight_guest")
-                .file(source_file)
-                .line(line)
-                .module_path(source)
-                .build(),
-        )
-        .map_err(|e| HandleOutbError::TraceFormat(e.to_string()))?;
-    } else {
-        // Create a log record for the GuestLogData
-        log::logger().log(
-            &Record::builder()
-                .args(format_args!("{}", log_data.message))
-                .level(record_level)
-                .target("hyperlight_guest")
-                .file(Some(&log_data.source_file))
-                .line(Some(log_data.line))
-                .module_path(Some(&log_data.source))
-                .build(),
-        );
-    }
-
-    Ok(())
+    guest_logs.log(log_data)
 }
 
-const ABORT_TERMINATOR: u8 = 0xFF;
 const MAX_ABORT_BUFFER_LEN: usize = 1024;
 
 fn outb_abort(
@@ -145,21 +96,30 @@ fn outb_abort(
             let guest_error_code = *buffer.first().unwrap_or(&0);
 
             let result = {
-                let message = if let Some(&maybe_exception_code) = buffer.get(1) {
-                    match Exception::try_from(maybe_exception_code) {
-                        Ok(exception) => {
-                            let extra_msg = String::from_utf8_lossy(&buffer[2..]);
-                            format!("Exception: {:?} | {}", exception, extra_msg)
+                let exception = buffer
+                    .get(1)
+                    .and_then(|&code| Exception::try_from(code).ok());
+                let mut panic = None;
+                let message = match exception {
+                    Some(exception) => {
+                        let extra_msg = String::from_utf8_lossy(&buffer[2..]);
+                        format!("Exception: {:?} | {}", exception, extra_msg)
+                    }
+                    None => {
+                        let text = buffer.get(1..).unwrap_or_default();
+                        panic = GuestPanic::parse(text);
+                        match &panic {
+                            Some(panic) => panic.to_string(),
+                            None => String::from_utf8_lossy(text).into(),
                         }
-                        Err(_) => String::from_utf8_lossy(&buffer[1..]).into(),
                     }
-                } else {
-                    String::new()
                 };
 
                 Err(HandleOutbError::GuestAborted {
                     code: guest_error_code,
                     message,
+                    exception,
+                    panic,
                 })
             };
 
@@ -172,6 +132,8 @@ fn outb_abort(
             return Err(HandleOutbError::GuestAborted {
                 code: 0,
                 message: "Guest abort buffer overflowed".into(),
+                exception: None,
+                panic: None,
             });
         }
 
@@ -180,11 +142,62 @@ fn outb_abort(
     Ok(())
 }
 
+/// Reads the host function call the guest made
+pub(crate) fn read_host_function_call(
+    mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+) -> Result<FunctionCall, HandleOutbError> {
+    mem_mgr
+        .get_host_function_call()
+        .map_err(|e| HandleOutbError::ReadHostFunctionCall(e.to_string()))
+}
+
+/// Calls the host function the guest called with `call`, returning the
+/// result to give back to the guest
+#[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+pub(crate) fn call_host_function(
+    host_funcs: &Arc<Mutex<FunctionRegistry>>,
+    debug_events: &DebugEventSink,
+    call: FunctionCall,
+) -> Result<FunctionCallResult, HandleOutbError> {
+    let name = call.function_name;
+    let args: Vec<ParameterValue> = call.parameters.unwrap_or(vec![]);
+    if name == "HostPrint"
+        && let [ParameterValue::String(msg)] = args.as_slice()
+    {
+        debug_events.emit(DebugEvent::GuestPrint(msg.clone()));
+    }
+    let host_funcs = host_funcs
+        .try_lock()
+        .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?;
+    let res = host_funcs
+        .check_signature(&name, &args, call.expected_return_type)
+        .and_then(|()| host_funcs.call_host_function(&name, args))
+        .map_err(|e| {
+            let code = match e {
+                HyperlightError::HostFunctionDenied(_) => ErrorCode::HostFunctionDenied,
+                _ => ErrorCode::HostFunctionError,
+            };
+            GuestError::new(code, e.to_string())
+        });
+    Ok(FunctionCallResult::new(res))
+}
+
+/// Gives the result of the host function call the guest made back to it
+pub(crate) fn write_host_function_result(
+    mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    result: &FunctionCallResult,
+) -> Result<(), HandleOutbError> {
+    mem_mgr
+        .write_response_from_host_function_call(result)
+        .map_err(|e| HandleOutbError::WriteHostFunctionResponse(e.to_string()))
+}
+
 /// Handles OutB operations from the guest.
 #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
 pub(crate) fn handle_outb(
     mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
-    host_funcs: &Arc<Mutex<FunctionRegistry>>,
+    debug_events: &DebugEventSink,
+    guest_logs: &mut GuestLogSink,
     port: u16,
     data: u32,
     #[cfg(feature = "mem_profile")] regs: &CommonRegisters,
@@ -194,28 +207,18 @@ pub(crate) fn handle_outb(
         .try_into()
         .map_err(|e: anyhow::Error| HandleOutbError::InvalidPort(e.to_string()))?
     {
-        OutBAction::Log => outb_log(mem_mgr),
-        OutBAction::CallFunction => {
-            let call = mem_mgr
-                .get_host_function_call()
-                .map_err(|e| HandleOutbError::ReadHostFunctionCall(e.to_string()))?;
-            let name = call.function_name.clone();
-            let args: Vec<ParameterValue> = call.parameters.unwrap_or(vec![]);
-            let res = host_funcs
-                .try_lock()
-                .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?
-                .call_host_function(&name, args)
-                .map_err(|e| GuestError::new(ErrorCode::HostFunctionError, e.to_string()));
-
-            let func_result = FunctionCallResult::new(res);
-
-            mem_mgr
-                .write_response_from_host_function_call(&func_result)
-                .map_err(|e| HandleOutbError::WriteHostFunctionResponse(e.to_string()))?;
-
-            Ok(())
-        }
-        OutBAction::Abort => outb_abort(mem_mgr, data),
+        OutBAction::Log => outb_log(mem_mgr, guest_logs),
+        // Host functions can call back into the guest, which needs the
+        // vCPU, so the calls are handled by the VM before getting here
+        OutBAction::CallFunction => Ok(()),
+        OutBAction::Abort => outb_abort(mem_mgr, data).inspect_err(|e| {
+            if let HandleOutbError::GuestAborted { code, message, .. } = e {
+                debug_events.emit(DebugEvent::Exception {
+                    code: *code,
+                    message: message.clone(),
+                });
+            }
+        }),
         OutBAction::DebugPrint => {
             let ch: char = match char::from_u32(data) {
                 Some(c) => c,
@@ -225,8 +228,16 @@ pub(crate) fn handle_outb(
             };
 
             eprint!("{}", ch);
+            debug_events.emit(DebugEvent::GuestPrint(ch.to_string()));
             Ok(())
         }
+        // The snapshot needs the state of the vCPU, so the checkpoints are
+        // handled by the VM before getting here
+        OutBAction::Checkpoint => Ok(()),
+        // Likewise for the stores to traced memory, which are reported
+        // in the registers of the vCPU
+        #[cfg(feature = "mem_trace")]
+        OutBAction::TraceMemoryAccess => Ok(()),
         #[cfg(feature = "trace_guest")]
         OutBAction::TraceBatch => Ok(()),
         #[cfg(feature = "mem_profile")]
@@ -249,6 +260,7 @@ mod tests {
     use crate::mem::mgr::SandboxMemoryManager;
     use crate::mem::shared_mem::SharedMemory;
     use crate::sandbox::SandboxConfiguration;
+    use crate::sandbox::guest_log::GuestLogSink;
     use crate::sandbox::outb::GuestLogData;
     use crate::testing::log_values::test_value_as_str;
 
@@ -288,7 +300,7 @@ mod tests {
             // We set a logger but there is no guest log data
             // in memory, so expect a log operation to fail
             let mut mgr = new_mgr();
-            assert!(outb_log(&mut mgr).is_err());
+            assert!(outb_log(&mut mgr, &mut GuestLogSink::default()).is_err());
         }
         {
             // Write a log message so outb_log will succeed.
@@ -306,7 +318,7 @@ mod tests {
                 )
                 .unwrap();
 
-            let res = outb_log(&mut mgr);
+            let res = outb_log(&mut mgr, &mut GuestLogSink::default());
             assert!(res.is_ok());
             assert_eq!(0, LOGGER.num_log_calls());
             LOGGER.clear_log_calls();
@@ -343,7 +355,7 @@ mod tests {
                     )
                     .unwrap();
 
-                outb_log(&mut mgr).unwrap();
+                outb_log(&mut mgr, &mut GuestLogSink::default()).unwrap();
 
                 LOGGER.test_log_records(|log_calls| {
                     let expected_level: Level = (&level).into();
@@ -425,7 +437,7 @@ mod tests {
                     )
                     .unwrap();
                 subscriber.clear();
-                outb_log(&mut mgr).unwrap();
+                outb_log(&mut mgr, &mut GuestLogSink::default()).unwrap();
 
                 subscriber.test_trace_records(|spans, events| {
                     let expected_level = match level {
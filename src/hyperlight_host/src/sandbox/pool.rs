@@ -0,0 +1,440 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tracing::{Span, instrument};
+
+use super::initialized_multi_use::MultiUseSandbox;
+use super::snapshot::Snapshot;
+use crate::metrics::{
+    METRIC_SANDBOX_POOL_CHECKOUTS, METRIC_SANDBOX_POOL_DISCARDED,
+    METRIC_SANDBOX_POOL_DISCARDED_LABEL_REASON,
+};
+use crate::{Result, log_then_return, new_error};
+
+/// Creates the sandboxes of a [`SandboxPool`]
+type Factory = Box<dyn Fn() -> Result<MultiUseSandbox> + Send + Sync>;
+
+/// A pool of initialized sandboxes running the same guest, which are
+/// handed out with [`checkout`](Self::checkout) and given back by
+/// dropping the returned [`PooledSandbox`].
+///
+/// Each sandbox is snapshotted once it is created, and restored to that
+/// snapshot when it is given back, so every checkout starts from the
+/// state the guest was in after initialisation. A sandbox that cannot be
+/// restored, or that was created before the pool was
+/// [`invalidate`](Self::invalidate)d, is dropped instead of being given
+/// back, and a new one is created by the next checkout that needs it.
+///
+/// The pool can be shared between threads, cloning it gives another
+/// handle to the same pool.
+///
+/// ```no_run
+/// # use hyperlight_host::{UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::sandbox::SandboxPool;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let pool = SandboxPool::new(4, || {
+///     UninitializedSandbox::new(GuestBinary::FilePath("guest.bin".into()), None)?.evolve()
+/// })?;
+///
+/// let mut sandbox = pool.checkout()?;
+/// let message: String = sandbox.call("Echo", "Hello".to_string())?;
+/// // Restores the sandbox and gives it back to the pool
+/// drop(sandbox);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SandboxPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    factory: Factory,
+    size: usize,
+    state: Mutex<PoolState>,
+    /// Signalled when a sandbox is given back, or may be created
+    available: Condvar,
+}
+
+struct PoolState {
+    idle: Vec<Entry>,
+    /// The number of sandboxes that are idle, checked out, or being
+    /// created
+    live: usize,
+    generation: u64,
+    stats: PoolStats,
+}
+
+/// A sandbox of the pool, along with the snapshot it is restored to
+struct Entry {
+    sandbox: MultiUseSandbox,
+    snapshot: Arc<Snapshot>,
+    generation: u64,
+}
+
+/// Counters of the activity of a [`SandboxPool`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The number of sandboxes the pool keeps
+    pub size: usize,
+    /// The number of sandboxes waiting to be checked out
+    pub idle: usize,
+    /// The number of sandboxes checked out
+    pub checked_out: usize,
+    /// The number of sandboxes the pool created
+    pub created: u64,
+    /// The number of checkouts
+    pub checkouts: u64,
+    /// The number of sandboxes restored and given back to the pool
+    pub resets: u64,
+    /// The number of sandboxes dropped because they could not be
+    /// restored, or because the pool was invalidated
+    pub discarded: u64,
+}
+
+impl SandboxPool {
+    /// Creates a pool of `size` sandboxes, which `factory` creates all
+    /// before this returns
+    #[instrument(err(Debug), skip(factory), parent = Span::current())]
+    pub fn new<F>(size: usize, factory: F) -> Result<Self>
+    where
+        F: Fn() -> Result<MultiUseSandbox> + Send + Sync + 'static,
+    {
+        if size == 0 {
+            log_then_return!("A sandbox pool needs at least one sandbox");
+        }
+        let pool = Self {
+            inner: Arc::new(PoolInner {
+                factory: Box::new(factory),
+                size,
+                state: Mutex::new(PoolState {
+                    idle: Vec::with_capacity(size),
+                    live: 0,
+                    generation: 0,
+                    stats: PoolStats::default(),
+                }),
+                available: Condvar::new(),
+            }),
+        };
+        for _ in 0..size {
+            let entry = pool.inner.create(0)?;
+            let mut state = pool.inner.lock();
+            state.live += 1;
+            state.idle.push(entry);
+        }
+        Ok(pool)
+    }
+
+    /// Checks a sandbox out of the pool, waiting for one to be given back
+    /// if all of them are checked out
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn checkout(&self) -> Result<PooledSandbox> {
+        self.checkout_until(None)
+            .and_then(|sandbox| sandbox.ok_or_else(|| new_error!("No sandbox was available")))
+    }
+
+    /// Checks a sandbox out of the pool, waiting at most `timeout` for one
+    /// to be given back. Returns `None` if none was given back in time.
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn checkout_timeout(&self, timeout: Duration) -> Result<Option<PooledSandbox>> {
+        self.checkout_until(Some(Instant::now() + timeout))
+    }
+
+    /// Drops the idle sandboxes, and the checked out ones when they are
+    /// given back, so that the pool is refilled with new sandboxes, for
+    /// instance once the guest binary the factory loads was updated
+    #[instrument(skip(self), parent = Span::current())]
+    pub fn invalidate(&self) {
+        let idle = {
+            let mut state = self.inner.lock();
+            state.generation += 1;
+            let idle = std::mem::take(&mut state.idle);
+            state.live -= idle.len();
+            state.stats.discarded += idle.len() as u64;
+            idle
+        };
+        for _ in &idle {
+            PoolInner::count_discarded("invalidated");
+        }
+        self.inner.available.notify_all();
+        // Dropping the sandboxes does not need the lock
+        drop(idle);
+    }
+
+    /// The activity of the pool so far
+    pub fn stats(&self) -> PoolStats {
+        let state = self.inner.lock();
+        PoolStats {
+            size: self.inner.size,
+            idle: state.idle.len(),
+            checked_out: state.live - state.idle.len(),
+            ..state.stats
+        }
+    }
+
+    fn checkout_until(&self, deadline: Option<Instant>) -> Result<Option<PooledSandbox>> {
+        let mut state = self.inner.lock();
+        loop {
+            if let Some(entry) = state.idle.pop() {
+                state.stats.checkouts += 1;
+                metrics::counter!(METRIC_SANDBOX_POOL_CHECKOUTS).increment(1);
+                return Ok(Some(PooledSandbox {
+                    entry: Some(entry),
+                    pool: self.inner.clone(),
+                }));
+            }
+            if state.live < self.inner.size {
+                // Create the sandbox without holding the lock
+                state.live += 1;
+                let generation = state.generation;
+                drop(state);
+                let entry = match self.inner.create(generation) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        self.inner.lock().live -= 1;
+                        self.inner.available.notify_one();
+                        return Err(e);
+                    }
+                };
+                let mut state = self.inner.lock();
+                state.stats.checkouts += 1;
+                metrics::counter!(METRIC_SANDBOX_POOL_CHECKOUTS).increment(1);
+                return Ok(Some(PooledSandbox {
+                    entry: Some(entry),
+                    pool: self.inner.clone(),
+                }));
+            }
+            state = match deadline {
+                None => self
+                    .inner
+                    .available
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+                Some(deadline) => {
+                    let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+                        return Ok(None);
+                    };
+                    self.inner
+                        .available
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0
+                }
+            };
+        }
+    }
+}
+
+impl std::fmt::Debug for SandboxPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SandboxPool")
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PoolInner {
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn create(&self, generation: u64) -> Result<Entry> {
+        let mut sandbox = (self.factory)()?;
+        let snapshot = sandbox.snapshot()?;
+        self.lock().stats.created += 1;
+        Ok(Entry {
+            sandbox,
+            snapshot,
+            generation,
+        })
+    }
+
+    /// Restores `entry` and gives it back to the pool, or drops it
+    fn checkin(&self, mut entry: Entry) {
+        let restored = entry.sandbox.restore(entry.snapshot.clone());
+        let reason = {
+            let mut state = self.lock();
+            let reason = match restored {
+                Err(e) => {
+                    tracing::warn!(
+                        "Dropping a pooled sandbox that could not be restored: {}",
+                        e
+                    );
+                    "restore_failed"
+                }
+                Ok(()) if entry.generation != state.generation => "invalidated",
+                Ok(()) => {
+                    state.stats.resets += 1;
+                    state.idle.push(entry);
+                    drop(state);
+                    self.available.notify_one();
+                    return;
+                }
+            };
+            state.live -= 1;
+            state.stats.discarded += 1;
+            reason
+        };
+        // A new sandbox can be created in place of this one
+        self.available.notify_one();
+        Self::count_discarded(reason);
+        // Dropping the sandbox does not need the lock
+        drop(entry);
+    }
+
+    fn count_discarded(reason: &'static str) {
+        metrics::counter!(
+            METRIC_SANDBOX_POOL_DISCARDED,
+            METRIC_SANDBOX_POOL_DISCARDED_LABEL_REASON => reason
+        )
+        .increment(1);
+    }
+}
+
+/// A sandbox checked out of a [`SandboxPool`], which is restored and
+/// given back to the pool when this is dropped
+pub struct PooledSandbox {
+    entry: Option<Entry>,
+    pool: Arc<PoolInner>,
+}
+
+impl PooledSandbox {
+    /// Drops the sandbox instead of giving it back to the pool, which
+    /// creates a new one in its place when it is needed
+    pub fn discard(mut self) {
+        if let Some(entry) = self.entry.take() {
+            {
+                let mut state = self.pool.lock();
+                state.live -= 1;
+                state.stats.discarded += 1;
+            }
+            self.pool.available.notify_one();
+            PoolInner::count_discarded("discarded");
+            drop(entry);
+        }
+    }
+}
+
+impl Deref for PooledSandbox {
+    type Target = MultiUseSandbox;
+
+    // The entry is only taken when the sandbox is dropped or discarded
+    #[allow(clippy::unwrap_used)]
+    fn deref(&self) -> &MultiUseSandbox {
+        &self.entry.as_ref().unwrap().sandbox
+    }
+}
+
+impl DerefMut for PooledSandbox {
+    #[allow(clippy::unwrap_used)]
+    fn deref_mut(&mut self) -> &mut MultiUseSandbox {
+        &mut self.entry.as_mut().unwrap().sandbox
+    }
+}
+
+impl Drop for PooledSandbox {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            self.pool.checkin(entry);
+        }
+    }
+}
+
+impl std::fmt::Debug for PooledSandbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PooledSandbox").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::{PoolStats, SandboxPool};
+    use crate::UninitializedSandbox;
+    use crate::sandbox::uninitialized::GuestBinary;
+
+    fn new_pool(size: usize) -> SandboxPool {
+        let path = simple_guest_as_string().unwrap();
+        SandboxPool::new(size, move || {
+            UninitializedSandbox::new(GuestBinary::FilePath(path.clone()), None)?.evolve()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn checkout_resets_sandboxes() {
+        let pool = new_pool(2);
+        let mut first = pool.checkout().unwrap();
+        let mut second = pool.checkout().unwrap();
+        assert_eq!(first.call::<i32>("AddToStatic", 5).unwrap(), 5);
+        assert_eq!(second.call::<i32>("AddToStatic", 7).unwrap(), 7);
+        assert!(
+            pool.checkout_timeout(Duration::from_millis(10))
+                .unwrap()
+                .is_none()
+        );
+
+        drop(first);
+        let mut third = pool.checkout().unwrap();
+        assert_eq!(third.call::<i32>("AddToStatic", 1).unwrap(), 1);
+        third.discard();
+        drop(second);
+
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                size: 2,
+                idle: 1,
+                checked_out: 0,
+                created: 2,
+                checkouts: 3,
+                resets: 2,
+                discarded: 1,
+            }
+        );
+
+        // The discarded sandbox is replaced when it is needed
+        let first = pool.checkout().unwrap();
+        let second = pool.checkout().unwrap();
+        assert_eq!(pool.stats().created, 3);
+        drop((first, second));
+    }
+
+    #[test]
+    fn invalidate_replaces_sandboxes() {
+        let pool = new_pool(2);
+        let mut sandbox = pool.checkout().unwrap();
+        pool.invalidate();
+        assert_eq!(pool.stats().idle, 0);
+        assert_eq!(sandbox.call::<i32>("AddToStatic", 5).unwrap(), 5);
+        drop(sandbox);
+
+        let stats = pool.stats();
+        assert_eq!((stats.idle, stats.discarded), (0, 2));
+        let mut sandbox = pool.checkout().unwrap();
+        assert_eq!(sandbox.call::<i32>("AddToStatic", 5).unwrap(), 5);
+        assert_eq!(pool.stats().created, 3);
+    }
+}
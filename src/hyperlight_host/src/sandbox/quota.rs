@@ -0,0 +1,451 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use super::metrics::SandboxUsage;
+use crate::{HyperlightError, Result};
+
+/// Limits on the resources used by all the sandboxes of the process
+/// together.
+///
+/// Sandboxes are admitted against the quota set with [`set_process_quota`]:
+/// creating an [`UninitializedSandbox`](crate::UninitializedSandbox) reserves
+/// its memory, evolving it into a [`MultiUseSandbox`](crate::MultiUseSandbox)
+/// reserves a vCPU thread, and each guest function call counts as a
+/// concurrent call while it runs. When a reservation would exceed the quota,
+/// the operation fails with [`HyperlightError::ProcessQuotaExceeded`] instead
+/// of committing more resources. Reservations are released when the sandbox
+/// is dropped, or when the call returns.
+///
+/// No resource is limited by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProcessQuota {
+    max_memory: Option<usize>,
+    max_vcpu_threads: Option<usize>,
+    max_concurrent_calls: Option<usize>,
+}
+
+impl ProcessQuota {
+    /// A quota that does not limit any resource
+    pub const fn new() -> Self {
+        Self {
+            max_memory: None,
+            max_vcpu_threads: None,
+            max_concurrent_calls: None,
+        }
+    }
+
+    /// Limits the total size in bytes of the memory of all the sandboxes,
+    /// or lifts the limit if `None`
+    pub fn set_max_memory(&mut self, bytes: Option<usize>) {
+        self.max_memory = bytes;
+    }
+
+    /// Limits the number of sandboxes with a vCPU thread, that is the
+    /// number of evolved sandboxes, or lifts the limit if `None`
+    pub fn set_max_vcpu_threads(&mut self, count: Option<usize>) {
+        self.max_vcpu_threads = count;
+    }
+
+    /// Limits the number of guest function calls running at the same time
+    /// across all the sandboxes, or lifts the limit if `None`
+    pub fn set_max_concurrent_calls(&mut self, count: Option<usize>) {
+        self.max_concurrent_calls = count;
+    }
+
+    /// Returns the limit on `resource`, if any
+    pub fn get(&self, resource: QuotaResource) -> Option<usize> {
+        match resource {
+            QuotaResource::Memory => self.max_memory,
+            QuotaResource::VcpuThreads => self.max_vcpu_threads,
+            QuotaResource::ConcurrentCalls => self.max_concurrent_calls,
+        }
+    }
+}
+
+/// A resource limited by a [`ProcessQuota`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaResource {
+    /// The memory of the sandboxes, in bytes
+    Memory,
+    /// The vCPU threads of the evolved sandboxes
+    VcpuThreads,
+    /// The guest function calls in progress
+    ConcurrentCalls,
+}
+
+impl Display for QuotaResource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaResource::Memory => write!(f, "sandbox memory"),
+            QuotaResource::VcpuThreads => write!(f, "vCPU threads"),
+            QuotaResource::ConcurrentCalls => write!(f, "concurrent guest calls"),
+        }
+    }
+}
+
+/// The resources reserved by all the sandboxes of the process
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    /// The total size in bytes of the memory of the sandboxes
+    pub memory: usize,
+    /// The number of evolved sandboxes
+    pub vcpu_threads: usize,
+    /// The number of guest function calls in progress
+    pub concurrent_calls: usize,
+}
+
+impl QuotaUsage {
+    fn get_mut(&mut self, resource: QuotaResource) -> &mut usize {
+        match resource {
+            QuotaResource::Memory => &mut self.memory,
+            QuotaResource::VcpuThreads => &mut self.vcpu_threads,
+            QuotaResource::ConcurrentCalls => &mut self.concurrent_calls,
+        }
+    }
+}
+
+/// The quota of the process along with the resources reserved against it,
+/// but for the concurrent calls, which are counted by [`CallCounter`]
+#[derive(Debug)]
+struct QuotaLedger {
+    quota: ProcessQuota,
+    usage: QuotaUsage,
+}
+
+impl QuotaLedger {
+    const fn new() -> Self {
+        Self {
+            quota: ProcessQuota::new(),
+            usage: QuotaUsage {
+                memory: 0,
+                vcpu_threads: 0,
+                concurrent_calls: 0,
+            },
+        }
+    }
+
+    fn reserve(&mut self, resource: QuotaResource, amount: usize) -> Result<()> {
+        let used = self.usage.get_mut(resource);
+        let requested = used.saturating_add(amount);
+        if let Some(limit) = self.quota.get(resource)
+            && requested > limit
+        {
+            return Err(HyperlightError::ProcessQuotaExceeded(resource, limit));
+        }
+        *used = requested;
+        Ok(())
+    }
+
+    fn release(&mut self, resource: QuotaResource, amount: usize) {
+        let used = self.usage.get_mut(resource);
+        *used = used.saturating_sub(amount);
+    }
+}
+
+/// The guest function calls in progress, counted without a lock since
+/// every guest function call reserves one
+#[derive(Debug)]
+struct CallCounter {
+    running: AtomicUsize,
+    /// The quota on the calls, `usize::MAX` if they are not limited
+    limit: AtomicUsize,
+}
+
+impl CallCounter {
+    const fn new() -> Self {
+        Self {
+            running: AtomicUsize::new(0),
+            limit: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    fn set_limit(&self, limit: Option<usize>) {
+        self.limit
+            .store(limit.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    fn reserve(&self, amount: usize) -> Result<()> {
+        let limit = self.limit.load(Ordering::Relaxed);
+        self.running
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+                let requested = running.saturating_add(amount);
+                (requested <= limit).then_some(requested)
+            })
+            .map_err(|_| {
+                HyperlightError::ProcessQuotaExceeded(QuotaResource::ConcurrentCalls, limit)
+            })?;
+        Ok(())
+    }
+
+    fn release(&self, amount: usize) {
+        let _ = self
+            .running
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+                Some(running.saturating_sub(amount))
+            });
+    }
+}
+
+static LEDGER: Mutex<QuotaLedger> = Mutex::new(QuotaLedger::new());
+
+static CALLS: CallCounter = CallCounter::new();
+
+fn with_ledger<T>(f: impl FnOnce(&mut QuotaLedger) -> T) -> T {
+    // The lock is never held across code that could panic, so the ledger
+    // is always consistent
+    f(&mut LEDGER.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Sets the quota the sandboxes of the process are admitted against.
+///
+/// Resources already reserved are kept even if they exceed the new quota,
+/// only later reservations are refused.
+pub fn set_process_quota(quota: ProcessQuota) {
+    with_ledger(|ledger| {
+        ledger.quota = quota;
+        CALLS.set_limit(quota.max_concurrent_calls);
+    });
+}
+
+/// Returns the quota the sandboxes of the process are admitted against
+pub fn process_quota() -> ProcessQuota {
+    with_ledger(|ledger| ledger.quota)
+}
+
+/// Returns the resources currently reserved by the sandboxes of the process
+pub fn process_quota_usage() -> QuotaUsage {
+    QuotaUsage {
+        concurrent_calls: CALLS.running.load(Ordering::Acquire),
+        ..with_ledger(|ledger| ledger.usage)
+    }
+}
+
+/// An amount of a resource reserved against the process quota, released
+/// when dropped
+#[derive(Debug)]
+pub(crate) struct QuotaReservation {
+    resource: QuotaResource,
+    amount: usize,
+}
+
+impl QuotaReservation {
+    /// Reserves `amount` of `resource`, or fails with
+    /// [`HyperlightError::ProcessQuotaExceeded`] if that would exceed the
+    /// process quota
+    pub(crate) fn reserve(resource: QuotaResource, amount: usize) -> Result<Self> {
+        match resource {
+            QuotaResource::ConcurrentCalls => CALLS.reserve(amount)?,
+            _ => with_ledger(|ledger| ledger.reserve(resource, amount))?,
+        }
+        Ok(Self { resource, amount })
+    }
+}
+
+impl Drop for QuotaReservation {
+    fn drop(&mut self) {
+        match self.resource {
+            QuotaResource::ConcurrentCalls => CALLS.release(self.amount),
+            _ => with_ledger(|ledger| ledger.release(self.resource, self.amount)),
+        }
+    }
+}
+
+/// Limits on the resources a single sandbox uses over its lifetime, see
+/// [`MultiUseSandbox::set_usage_quota`].
+///
+/// The resources are counted by [`SandboxUsage`]. Once the sandbox has
+/// used up its quota of a resource, guest function calls fail with
+/// [`HyperlightError::SandboxQuotaExceeded`] without running. A call that
+/// uses up the quota while it runs is stopped at the next exit of the
+/// vCPU to the host, which poisons the sandbox. A guest that does not
+/// exit, for instance because it spins, is only stopped by the CPU time
+/// budget of each call, see
+/// [`SandboxConfiguration::set_guest_cpu_time_budget`].
+///
+/// No resource is limited by default.
+///
+/// [`MultiUseSandbox::set_usage_quota`]: crate::MultiUseSandbox::set_usage_quota
+/// [`SandboxConfiguration::set_guest_cpu_time_budget`]: crate::sandbox::SandboxConfiguration::set_guest_cpu_time_budget
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SandboxQuota {
+    max_cpu_time: Option<Duration>,
+    max_vm_exits: Option<u64>,
+    max_host_calls: Option<u64>,
+    max_bytes_transferred: Option<u64>,
+}
+
+impl SandboxQuota {
+    /// A quota that does not limit any resource
+    pub const fn new() -> Self {
+        Self {
+            max_cpu_time: None,
+            max_vm_exits: None,
+            max_host_calls: None,
+            max_bytes_transferred: None,
+        }
+    }
+
+    /// Limits the time the vCPU of the sandbox runs the guest, or lifts
+    /// the limit if `None`
+    pub fn set_max_cpu_time(&mut self, time: Option<Duration>) {
+        self.max_cpu_time = time;
+    }
+
+    /// Limits the number of exits of the vCPU to the host, or lifts the
+    /// limit if `None`
+    pub fn set_max_vm_exits(&mut self, count: Option<u64>) {
+        self.max_vm_exits = count;
+    }
+
+    /// Limits the number of host function calls made by the guest, or
+    /// lifts the limit if `None`
+    pub fn set_max_host_calls(&mut self, count: Option<u64>) {
+        self.max_host_calls = count;
+    }
+
+    /// Limits the number of bytes the host and the guest pass each other
+    /// through the input and output buffers, or lifts the limit if `None`
+    pub fn set_max_bytes_transferred(&mut self, bytes: Option<u64>) {
+        self.max_bytes_transferred = bytes;
+    }
+
+    /// Returns the first resource of which `usage` has used up the quota,
+    /// if any
+    pub(crate) fn exceeded(&self, usage: &SandboxUsage) -> Option<SandboxQuotaResource> {
+        let exceeded = |used: u64, limit: Option<u64>| limit.is_some_and(|limit| used >= limit);
+        if self
+            .max_cpu_time
+            .is_some_and(|limit| usage.cpu_time >= limit)
+        {
+            Some(SandboxQuotaResource::CpuTime)
+        } else if exceeded(usage.vm_exits, self.max_vm_exits) {
+            Some(SandboxQuotaResource::VmExits)
+        } else if exceeded(usage.host_calls, self.max_host_calls) {
+            Some(SandboxQuotaResource::HostCalls)
+        } else if exceeded(usage.bytes_transferred, self.max_bytes_transferred) {
+            Some(SandboxQuotaResource::BytesTransferred)
+        } else {
+            None
+        }
+    }
+}
+
+/// A resource limited by a [`SandboxQuota`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SandboxQuotaResource {
+    /// The time the vCPU runs the guest
+    CpuTime,
+    /// The exits of the vCPU to the host
+    VmExits,
+    /// The host function calls made by the guest
+    HostCalls,
+    /// The bytes passed through the input and output buffers
+    BytesTransferred,
+}
+
+impl Display for SandboxQuotaResource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxQuotaResource::CpuTime => write!(f, "guest CPU time"),
+            SandboxQuotaResource::VmExits => write!(f, "vCPU exits"),
+            SandboxQuotaResource::HostCalls => write!(f, "host function calls"),
+            SandboxQuotaResource::BytesTransferred => write!(f, "bytes transferred"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CallCounter, QuotaLedger, QuotaResource, SandboxQuota, SandboxQuotaResource};
+    use crate::HyperlightError;
+    use crate::sandbox::metrics::SandboxUsage;
+
+    #[test]
+    fn ledger_admits_within_quota() {
+        let mut ledger = QuotaLedger::new();
+        ledger.quota.set_max_memory(Some(0x3000));
+        ledger.quota.set_max_vcpu_threads(Some(1));
+
+        ledger.reserve(QuotaResource::Memory, 0x2000).unwrap();
+        let err = ledger.reserve(QuotaResource::Memory, 0x2000).unwrap_err();
+        assert!(matches!(
+            err,
+            HyperlightError::ProcessQuotaExceeded(QuotaResource::Memory, 0x3000)
+        ));
+        assert_eq!(ledger.usage.memory, 0x2000);
+
+        ledger.release(QuotaResource::Memory, 0x2000);
+        ledger.reserve(QuotaResource::Memory, 0x3000).unwrap();
+
+        ledger.reserve(QuotaResource::VcpuThreads, 1).unwrap();
+        assert!(ledger.reserve(QuotaResource::VcpuThreads, 1).is_err());
+
+        // Calls are not limited by this quota
+        for _ in 0..100 {
+            ledger.reserve(QuotaResource::ConcurrentCalls, 1).unwrap();
+        }
+        assert_eq!(ledger.usage.concurrent_calls, 100);
+    }
+
+    #[test]
+    fn call_counter_admits_within_quota() {
+        let calls = CallCounter::new();
+        for _ in 0..100 {
+            calls.reserve(1).unwrap();
+        }
+
+        calls.set_limit(Some(101));
+        calls.reserve(1).unwrap();
+        let err = calls.reserve(1).unwrap_err();
+        assert!(matches!(
+            err,
+            HyperlightError::ProcessQuotaExceeded(QuotaResource::ConcurrentCalls, 101)
+        ));
+
+        calls.release(1);
+        calls.reserve(1).unwrap();
+        calls.set_limit(None);
+        calls.reserve(1).unwrap();
+    }
+
+    #[test]
+    fn sandbox_quota_is_used_up() {
+        let mut quota = SandboxQuota::new();
+        let mut usage = SandboxUsage {
+            host_calls: 10,
+            ..Default::default()
+        };
+        assert_eq!(quota.exceeded(&usage), None);
+
+        quota.set_max_host_calls(Some(11));
+        quota.set_max_cpu_time(Some(Duration::from_secs(1)));
+        assert_eq!(quota.exceeded(&usage), None);
+        usage.host_calls = 11;
+        assert_eq!(
+            quota.exceeded(&usage),
+            Some(SandboxQuotaResource::HostCalls)
+        );
+        usage.cpu_time = Duration::from_secs(2);
+        assert_eq!(quota.exceeded(&usage), Some(SandboxQuotaResource::CpuTime));
+    }
+}
@@ -0,0 +1,214 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::{Result, new_error};
+
+/// Who may read a secret given to guests, and how many times.
+///
+/// By default, a secret can be read any number of times, from any guest
+/// function.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SecretPolicy {
+    functions: Option<HashSet<String>>,
+    one_time: bool,
+}
+
+impl SecretPolicy {
+    /// Creates a policy that allows every read
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows reading the secret while the guest function `name` is
+    /// called. Once a function is allowed, reads made while other
+    /// functions are called, or while the guest is initialised, are
+    /// denied.
+    pub fn allow_function(mut self, name: impl Into<String>) -> Self {
+        self.functions
+            .get_or_insert_with(HashSet::new)
+            .insert(name.into());
+        self
+    }
+
+    /// Removes the secret once it has been read, so that it can only be
+    /// read once, by a single sandbox
+    pub fn one_time(mut self) -> Self {
+        self.one_time = true;
+        self
+    }
+
+    fn allows(&self, function: Option<&str>) -> bool {
+        match (&self.functions, function) {
+            (None, _) => true,
+            (Some(functions), Some(function)) => functions.contains(function),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+struct Secret {
+    value: Vec<u8>,
+    policy: SecretPolicy,
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        // Do not leave the value behind in freed memory
+        self.value.fill(0);
+    }
+}
+
+/// The secrets given to the guests of the sandboxes they are registered
+/// in with
+/// [`UninitializedSandbox::provide_secrets`](crate::UninitializedSandbox::provide_secrets),
+/// so that credentials do not have to be embedded in guest binaries.
+///
+/// Every read is logged with the `hyperlight_host::secrets` target,
+/// along with the guest function that made it and whether it was
+/// allowed. Values are never logged.
+///
+/// Clones share their secrets, so a secret inserted or removed after the
+/// secrets are registered in a sandbox is seen by its guest.
+#[derive(Clone, Default)]
+pub struct Secrets {
+    entries: Arc<Mutex<HashMap<String, Secret>>>,
+}
+
+impl Secrets {
+    /// Creates an empty set of secrets
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts the secret `name`, replacing any secret of the same name
+    pub fn insert(
+        &self,
+        name: impl Into<String>,
+        value: impl Into<Vec<u8>>,
+        policy: SecretPolicy,
+    ) -> Result<()> {
+        self.lock()?.insert(
+            name.into(),
+            Secret {
+                value: value.into(),
+                policy,
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes the secret `name`, returning whether there was one
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        Ok(self.lock()?.remove(name).is_some())
+    }
+
+    /// Returns whether there is a secret `name`
+    pub fn contains(&self, name: &str) -> Result<bool> {
+        Ok(self.lock()?.contains_key(name))
+    }
+
+    /// Returns the value of the secret `name` if its policy allows the
+    /// guest function `function` to read it, `None` standing for the
+    /// initialisation of the guest
+    pub(crate) fn read(&self, name: &str, function: Option<&str>) -> Result<Vec<u8>> {
+        let mut entries = self.lock()?;
+        let Some(secret) = entries.get(name) else {
+            log::warn!(
+                target: "hyperlight_host::secrets",
+                "Denied read of unknown secret {} from {}",
+                name,
+                function.unwrap_or("guest initialisation")
+            );
+            return Err(new_error!("Unknown secret {}", name));
+        };
+        if !secret.policy.allows(function) {
+            log::warn!(
+                target: "hyperlight_host::secrets",
+                "Denied read of secret {} from {}",
+                name,
+                function.unwrap_or("guest initialisation")
+            );
+            return Err(new_error!("Reading secret {} is not allowed", name));
+        }
+
+        log::info!(
+            target: "hyperlight_host::secrets",
+            "Read of secret {} from {}",
+            name,
+            function.unwrap_or("guest initialisation")
+        );
+        let value = secret.value.clone();
+        if secret.policy.one_time {
+            entries.remove(name);
+        }
+        Ok(value)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, Secret>>> {
+        self.entries
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+    }
+}
+
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Secrets").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SecretPolicy, Secrets};
+
+    #[test]
+    fn policies_are_enforced() {
+        let secrets = Secrets::new();
+        secrets
+            .insert("token", b"abc".to_vec(), SecretPolicy::new())
+            .unwrap();
+        secrets
+            .insert(
+                "key",
+                b"def".to_vec(),
+                SecretPolicy::new().allow_function("Connect"),
+            )
+            .unwrap();
+        secrets
+            .insert("nonce", b"ghi".to_vec(), SecretPolicy::new().one_time())
+            .unwrap();
+
+        assert_eq!(secrets.read("token", None).unwrap(), b"abc");
+        assert_eq!(secrets.read("token", Some("Echo")).unwrap(), b"abc");
+        assert!(secrets.read("missing", Some("Echo")).is_err());
+
+        assert!(secrets.read("key", None).is_err());
+        assert!(secrets.read("key", Some("Echo")).is_err());
+        assert_eq!(secrets.read("key", Some("Connect")).unwrap(), b"def");
+
+        // Clones share the secrets, including their reads
+        let shared = secrets.clone();
+        assert_eq!(shared.read("nonce", Some("Echo")).unwrap(), b"ghi");
+        assert!(secrets.read("nonce", Some("Echo")).is_err());
+        assert!(!secrets.contains("nonce").unwrap());
+
+        assert!(secrets.remove("token").unwrap());
+        assert!(shared.read("token", None).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+use hyperlight_common::func::{ParameterTuple, SupportedReturnType};
+
+use super::initialized_multi_use::MultiUseSandbox;
+use super::status::{SandboxStatus, SandboxStatusHandle};
+use crate::hypervisor::InterruptHandle;
+use crate::{Result, new_error};
+
+/// A [`MultiUseSandbox`] whose guest functions are called through a
+/// shared reference, so that it can be put behind an [`Arc`] and used
+/// from the handlers of a server without wrapping it in a `Mutex`.
+///
+/// Calls run one at a time: a call made while another one is running
+/// waits for it to complete, or fails right away with
+/// [`try_call`](Self::try_call). The state of the sandbox can be queried
+/// with [`status`](Self::status) and [`poisoned`](Self::poisoned) at any
+/// time, including while a call runs.
+///
+/// A panic in a host function, or in the closure given to
+/// [`with_sandbox`](Self::with_sandbox), does not make the sandbox
+/// unusable the way it would poison a `Mutex`. The sandbox is left in the
+/// state it was in when the panic happened, such as
+/// [`SandboxStatus::Running`] with the name of the guest function that
+/// was called, so later calls fail with an error saying why, until the
+/// sandbox is restored from a snapshot.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::sandbox::shared::SharedSandbox;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+/// let sandbox = Arc::new(SharedSandbox::new(sandbox));
+///
+/// let handler = sandbox.clone();
+/// std::thread::spawn(move || handler.call::<String>("Echo", "Hello".to_string()));
+/// let message: String = sandbox.call("Echo", "World".to_string())?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SharedSandbox {
+    sandbox: Mutex<MultiUseSandbox>,
+    status: SandboxStatusHandle,
+    interrupt_handle: Arc<dyn InterruptHandle>,
+}
+
+impl SharedSandbox {
+    /// Wraps `sandbox` to be called through a shared reference
+    pub fn new(sandbox: MultiUseSandbox) -> Self {
+        Self {
+            status: sandbox.status_handle(),
+            interrupt_handle: sandbox.interrupt_handle(),
+            sandbox: Mutex::new(sandbox),
+        }
+    }
+
+    /// Calls the guest function `func_name` with `args`, as
+    /// [`MultiUseSandbox::call`] does, once the call running, if any,
+    /// completes
+    pub fn call<Output: SupportedReturnType>(
+        &self,
+        func_name: &str,
+        args: impl ParameterTuple,
+    ) -> Result<Output> {
+        self.lock().call(func_name, args)
+    }
+
+    /// Calls the guest function `func_name` with `args`, as
+    /// [`call`](Self::call) does, unless another call is running, in
+    /// which case it fails without waiting
+    pub fn try_call<Output: SupportedReturnType>(
+        &self,
+        func_name: &str,
+        args: impl ParameterTuple,
+    ) -> Result<Output> {
+        let mut sandbox = match self.sandbox.try_lock() {
+            Ok(sandbox) => sandbox,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                return Err(new_error!(
+                    "Cannot call {} while the sandbox is busy, its status is {}",
+                    func_name,
+                    self.status.get()
+                ));
+            }
+        };
+        sandbox.call(func_name, args)
+    }
+
+    /// Runs `f` with the sandbox once the call running, if any,
+    /// completes, for instance to take or restore a snapshot between
+    /// guest calls
+    pub fn with_sandbox<T>(&self, f: impl FnOnce(&mut MultiUseSandbox) -> T) -> T {
+        f(&mut self.lock())
+    }
+
+    /// Returns the current lifecycle state of the sandbox, without
+    /// waiting for the call running to complete
+    pub fn status(&self) -> SandboxStatus {
+        self.status.get()
+    }
+
+    /// Whether the sandbox is poisoned, see [`MultiUseSandbox::poisoned`]
+    pub fn poisoned(&self) -> bool {
+        self.status.get() == SandboxStatus::Poisoned
+    }
+
+    /// The handle to interrupt the running guest call, see
+    /// [`MultiUseSandbox::interrupt_handle`]
+    pub fn interrupt_handle(&self) -> Arc<dyn InterruptHandle> {
+        self.interrupt_handle.clone()
+    }
+
+    /// Gives the sandbox back
+    pub fn into_inner(self) -> MultiUseSandbox {
+        self.sandbox
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MultiUseSandbox> {
+        // The status of the sandbox tells whether a panic left it unusable
+        self.sandbox
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl std::fmt::Debug for SharedSandbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedSandbox")
+            .field("status", &self.status.get())
+            .finish_non_exhaustive()
+    }
+}
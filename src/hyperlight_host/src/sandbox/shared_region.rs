@@ -0,0 +1,116 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use hyperlight_common::mem::PAGE_SIZE_USIZE;
+
+use crate::mem::shared_mem::{ExclusiveSharedMemory, HostSharedMemory, SharedMemory};
+use crate::{Result, new_error};
+
+/// A named region of memory that can be mapped into several sandboxes at
+/// the same time with [`MultiUseSandbox::map_shared_region`], read-write
+/// in some of them and read-only in the others, so that the guests
+/// exchange data, for instance in a producer/consumer pipeline, without a
+/// call to the host for every message.
+///
+/// The region is not part of the snapshots of the sandboxes it is mapped
+/// into: restoring a sandbox maps it again but does not roll back what
+/// was written to it. The guests are responsible for synchronising their
+/// accesses, for instance with atomic operations on a header of the
+/// region.
+///
+/// Clones share the region, which is freed when the last clone and the
+/// last sandbox it is mapped into are dropped.
+///
+/// [`MultiUseSandbox::map_shared_region`]: crate::MultiUseSandbox::map_shared_region
+#[derive(Clone)]
+pub struct SharedRegion {
+    name: Arc<str>,
+    memory: HostSharedMemory,
+}
+
+impl SharedRegion {
+    /// Creates a zeroed region of at least `size` bytes, rounded up to a
+    /// page, named `name` in the errors and logs
+    pub fn new(name: &str, size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(new_error!("The shared region {} cannot be empty", name));
+        }
+        let size = size
+            .checked_next_multiple_of(PAGE_SIZE_USIZE)
+            .ok_or_else(|| new_error!("The shared region {} is too large", name))?;
+        let (memory, _) = ExclusiveSharedMemory::new(size)?.build();
+        Ok(Self {
+            name: name.into(),
+            memory,
+        })
+    }
+
+    /// The name of the region
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The size of the region in bytes
+    pub fn size(&self) -> usize {
+        self.memory.mem_size()
+    }
+
+    /// Reads `buf.len()` bytes of the region at `offset` from the host
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.memory.copy_to_slice(buf, offset)
+    }
+
+    /// Writes `data` to the region at `offset` from the host
+    pub fn write(&self, offset: usize, data: &[u8]) -> Result<()> {
+        self.memory.copy_from_slice(data, offset)
+    }
+
+    /// The address of the region in the host
+    pub(crate) fn base_addr(&self) -> usize {
+        self.memory.base_addr()
+    }
+}
+
+impl std::fmt::Debug for SharedRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedRegion")
+            .field("name", &self.name)
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedRegion;
+
+    #[test]
+    fn clones_share_the_region() {
+        let region = SharedRegion::new("pipeline", 100).unwrap();
+        assert_eq!(region.size(), 0x1000);
+        assert_eq!(region.name(), "pipeline");
+        assert!(SharedRegion::new("empty", 0).is_err());
+
+        let clone = region.clone();
+        clone.write(0xff0, b"hello").unwrap();
+        let mut buf = [0; 5];
+        region.read(0xff0, &mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+        assert!(region.write(0xffc, b"hello").is_err());
+    }
+}
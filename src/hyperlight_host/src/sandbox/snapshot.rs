@@ -14,6 +14,11 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+#[cfg(target_os = "linux")]
+use std::fs::File;
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use hyperlight_common::layout::{scratch_base_gpa, scratch_base_gva};
@@ -21,15 +26,19 @@ use hyperlight_common::vmem::{self, BasicMapping, CowMapping, Mapping, MappingKi
 use tracing::{Span, instrument};
 
 use crate::HyperlightError::MemoryRegionSizeMismatch;
-use crate::Result;
+#[cfg(gdb)]
+use crate::hypervisor::gdb::arch::HwBreakpointRegs;
 use crate::hypervisor::regs::CommonSpecialRegisters;
 use crate::mem::exe::LoadInfo;
-use crate::mem::layout::SandboxMemoryLayout;
+use crate::mem::layout::{SandboxLayoutInfo, SandboxMemoryLayout};
 use crate::mem::memory_region::MemoryRegion;
 use crate::mem::mgr::GuestPageTableBuffer;
 use crate::mem::shared_mem::{ExclusiveSharedMemory, SharedMemory};
 use crate::sandbox::SandboxConfiguration;
+use crate::sandbox::metrics::{CreationPhase, CreationReport};
+use crate::sandbox::snapshot_file::{self, Decoder, Encoder};
 use crate::sandbox::uninitialized::{GuestBinary, GuestEnvironment};
+use crate::{Result, new_error};
 
 pub(super) static SANDBOX_CONFIGURATION_COUNTER: AtomicU64 = AtomicU64::new(0);
 
@@ -100,8 +109,21 @@ pub struct Snapshot {
     /// tables are relocated during snapshot.
     sregs: Option<CommonSpecialRegisters>,
 
+    /// The hardware breakpoints the debugger had programmed in DR0-DR3
+    /// and DR7 when the snapshot was taken, if a debugger is attached to
+    /// the sandbox, so that they survive restoring the snapshot
+    #[cfg(gdb)]
+    hw_breakpoints: Option<HwBreakpointRegs>,
+
     /// The next action that should be performed on this snapshot
     entrypoint: NextAction,
+
+    /// A sealed memfd holding a copy of `memory`, created the first
+    /// time the snapshot is restored, so that restoring can map it
+    /// copy-on-write over the sandbox memory instead of copying it.
+    /// None if the memfd could not be created.
+    #[cfg(target_os = "linux")]
+    file: OnceLock<Option<File>>,
 }
 impl core::convert::AsRef<Snapshot> for Snapshot {
     fn as_ref(&self) -> &Self {
@@ -174,12 +196,38 @@ fn hash(memory: &[u8], regions: &[MemoryRegion]) -> Result<[u8; 32]> {
     Ok(hasher.finalize().into())
 }
 
-pub(crate) fn access_gpa<'a>(
-    snap: &'a ExclusiveSharedMemory,
-    scratch: &'a ExclusiveSharedMemory,
+/// Creates a memfd holding `memory`, sealed so that its contents can
+/// never change while sandbox memory is mapped over it
+#[cfg(target_os = "linux")]
+fn sealed_memfd(memory: &[u8]) -> Result<File> {
+    use std::io::Write;
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    let fd = unsafe {
+        libc::memfd_create(
+            c"hyperlight-snapshot".as_ptr(),
+            libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    // Safety: the descriptor was just created and is owned by nothing else
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(memory)?;
+    let seals = libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(file)
+}
+
+pub(crate) fn access_gpa<'a, S: SharedMemory>(
+    snap: &'a S,
+    scratch: &'a S,
     scratch_size: usize,
     gpa: u64,
-) -> Option<(&'a ExclusiveSharedMemory, usize)> {
+) -> Option<(&'a S, usize)> {
     let scratch_base = scratch_base_gpa(scratch_size);
     if gpa >= scratch_base && gpa < scratch_base + scratch_size as u64 {
         Some((scratch, (gpa - scratch_base) as usize))
@@ -331,6 +379,61 @@ fn map_specials(pt_buf: &GuestPageTableBuffer, scratch_size: usize) {
     unsafe { vmem::map(pt_buf, mapping) };
 }
 
+/// Maps the code region page by page with the permissions of the
+/// segments of the guest binary covering each page, failing if a page
+/// would be both writable and executable
+#[cfg(feature = "init-paging")]
+fn map_code_write_xor_execute(
+    pt_buf: &GuestPageTableBuffer,
+    layout: &SandboxMemoryLayout,
+    region: &std::ops::Range<usize>,
+    segments: &[(
+        std::ops::Range<usize>,
+        crate::mem::memory_region::MemoryRegionFlags,
+    )],
+) -> Result<()> {
+    use crate::mem::memory_region::MemoryRegionFlags;
+
+    // The segments are relative to the load address of the binary
+    let code_offset = layout.get_guest_code_offset();
+    for gpa in region.clone().step_by(PAGE_SIZE) {
+        let start = (gpa - region.start).saturating_sub(code_offset);
+        let end = (gpa - region.start + PAGE_SIZE).saturating_sub(code_offset);
+        let flags = segments
+            .iter()
+            .filter(|(segment, _)| segment.start < end && start < segment.end)
+            .fold(MemoryRegionFlags::READ, |flags, (_, segment_flags)| {
+                flags | *segment_flags
+            });
+        if flags.contains(MemoryRegionFlags::WRITE | MemoryRegionFlags::EXECUTE) {
+            return Err(new_error!(
+                "The page at offset {:#x} of the guest binary is both writable and executable",
+                start
+            ));
+        }
+        let kind = if flags.contains(MemoryRegionFlags::WRITE) {
+            MappingKind::Cow(CowMapping {
+                readable: true,
+                executable: false,
+            })
+        } else {
+            MappingKind::Basic(BasicMapping {
+                readable: true,
+                writable: false,
+                executable: flags.contains(MemoryRegionFlags::EXECUTE),
+            })
+        };
+        let mapping = Mapping {
+            phys_base: gpa as u64,
+            virt_base: (gpa + layout.gva_slide()) as u64,
+            len: PAGE_SIZE as u64,
+            kind,
+        };
+        unsafe { vmem::map(pt_buf, mapping) };
+    }
+    Ok(())
+}
+
 impl Snapshot {
     /// Create a new snapshot from the guest binary identified by `env`. With the configuration
     /// specified in `cfg`.
@@ -338,53 +441,99 @@ impl Snapshot {
         env: impl Into<GuestEnvironment<'a, 'b>>,
         cfg: SandboxConfiguration,
     ) -> Result<Self> {
-        let env = env.into();
-        let mut bin = env.guest_binary;
-        bin.canonicalize()?;
-        let blob = env.init_data;
-
-        use crate::mem::exe::ExeInfo;
-        let exe_info = match bin {
-            GuestBinary::FilePath(bin_path_str) => ExeInfo::from_file(&bin_path_str)?,
-            GuestBinary::Buffer(buffer) => ExeInfo::from_buf(buffer)?,
-        };
-
-        let guest_blob_size = blob.as_ref().map(|b| b.data.len()).unwrap_or(0);
-        let guest_blob_mem_flags = blob.as_ref().map(|b| b.permissions);
-
-        #[cfg_attr(not(feature = "init-paging"), allow(unused_mut))]
-        let mut layout = crate::mem::layout::SandboxMemoryLayout::new(
-            cfg,
-            exe_info.loaded_size(),
-            guest_blob_size,
-            guest_blob_mem_flags,
-        )?;
-
-        let load_addr = layout.get_guest_code_address() as u64;
-        let entrypoint_offset: u64 = exe_info.entrypoint().into();
+        Self::from_env_timed(env, cfg, &mut CreationReport::default())
+    }
 
-        let mut memory = vec![0; layout.get_memory_size()?];
+    /// Like [`Self::from_env`], timing the ELF load and the page table
+    /// setup in `report`
+    pub(crate) fn from_env_timed<'a, 'b>(
+        env: impl Into<GuestEnvironment<'a, 'b>>,
+        mut cfg: SandboxConfiguration,
+        report: &mut CreationReport,
+    ) -> Result<Self> {
+        let env = env.into();
+        #[cfg_attr(not(feature = "init-paging"), allow(unused_mut, unused_variables))]
+        let (mut layout, mut memory, load_info, load_addr, entrypoint_offset, segments) = report
+            .time(CreationPhase::ElfLoad, || -> Result<_> {
+                let mut bin = env.guest_binary;
+                bin.canonicalize()?;
+                let blob = env.init_data;
+
+                use crate::mem::exe::ExeInfo;
+                let exe_info = match bin {
+                    GuestBinary::FilePath(bin_path_str) => ExeInfo::from_file(&bin_path_str)?,
+                    GuestBinary::Buffer(buffer) => ExeInfo::from_buf(buffer)?,
+                };
+                if let Some(guest_config) = exe_info.guest_config() {
+                    cfg.apply_guest_binary_config(guest_config)?;
+                }
 
-        let load_info = exe_info.load(
-            load_addr.try_into()?,
-            &mut memory[layout.get_guest_code_offset()..],
-        )?;
+                let guest_blob_size = blob.as_ref().map(|b| b.data.len()).unwrap_or(0);
+                let guest_blob_mem_flags = blob.as_ref().map(|b| b.permissions);
+
+                let mut layout = crate::mem::layout::SandboxMemoryLayout::new(
+                    cfg,
+                    exe_info.loaded_size(),
+                    guest_blob_size,
+                    guest_blob_mem_flags,
+                )?;
+                if cfg.get_guest_aslr() {
+                    layout.randomize();
+                }
 
-        blob.map(|x| layout.write_init_data(&mut memory, x.data))
-            .transpose()?;
+                let load_addr = layout.get_guest_code_address() as u64;
+                let entrypoint_offset: u64 = exe_info.entrypoint().into();
+
+                let mut memory = vec![0; layout.get_memory_size()?];
+
+                // The permissions of the segments are only needed to keep
+                // the writable and executable pages apart
+                let segments = layout.write_xor_execute().then(|| exe_info.segments());
+
+                let load_info = exe_info.load(
+                    load_addr.try_into()?,
+                    &mut memory[layout.get_guest_code_offset()..],
+                )?;
+                layout.write_image_info(&mut memory, &load_info.image)?;
+
+                blob.map(|x| layout.write_init_data(&mut memory, x.data))
+                    .transpose()?;
+                Ok((
+                    layout,
+                    memory,
+                    load_info,
+                    load_addr,
+                    entrypoint_offset,
+                    segments,
+                ))
+            })?;
 
         #[cfg(feature = "init-paging")]
-        {
+        report.time(CreationPhase::PageTableSetup, || -> Result<()> {
             // Set up page table entries for the snapshot
             let pt_buf = GuestPageTableBuffer::new(layout.get_pt_base_gpa() as usize);
 
-            use crate::mem::memory_region::{GuestMemoryRegion, MemoryRegionFlags};
+            use crate::mem::memory_region::{
+                GuestMemoryRegion, MemoryRegionFlags, MemoryRegionType,
+            };
 
             // 1. Map the (ideally readonly) pages of snapshot data
             for rgn in layout.get_memory_regions_::<GuestMemoryRegion>(())?.iter() {
-                let readable = rgn.flags.contains(MemoryRegionFlags::READ);
-                let executable = rgn.flags.contains(MemoryRegionFlags::EXECUTE);
-                let writable = rgn.flags.contains(MemoryRegionFlags::WRITE);
+                // Guard pages are left unmapped
+                if rgn.flags == MemoryRegionFlags::NONE {
+                    continue;
+                }
+                if let (Some(segments), MemoryRegionType::Code) = (&segments, rgn.region_type) {
+                    map_code_write_xor_execute(&pt_buf, &layout, &rgn.guest_region, segments)?;
+                    continue;
+                }
+                let mut flags = rgn.flags;
+                if layout.write_xor_execute() && flags.contains(MemoryRegionFlags::WRITE) {
+                    flags.remove(MemoryRegionFlags::EXECUTE);
+                }
+                let readable = flags.contains(MemoryRegionFlags::READ);
+                let executable = flags.contains(MemoryRegionFlags::EXECUTE);
+                let writable = flags.contains(MemoryRegionFlags::WRITE);
                 let kind = if writable {
                     MappingKind::Cow(CowMapping {
                         readable,
@@ -399,11 +548,18 @@ impl Snapshot {
                 };
                 let mapping = Mapping {
                     phys_base: rgn.guest_region.start as u64,
-                    virt_base: rgn.guest_region.start as u64,
+                    virt_base: (rgn.guest_region.start + layout.gva_slide()) as u64,
                     len: rgn.guest_region.len() as u64,
                     kind,
                 };
-                unsafe { vmem::map(&pt_buf, mapping) };
+                if cfg.get_guest_large_pages() {
+                    // Only basic mappings can use large pages, the
+                    // copy-on-write ones automatically fall back to
+                    // small pages
+                    unsafe { vmem::map_with_large_pages(&pt_buf, mapping) };
+                } else {
+                    unsafe { vmem::map(&pt_buf, mapping) };
+                }
             }
 
             // 2. Map the special mappings
@@ -412,7 +568,8 @@ impl Snapshot {
             let pt_bytes = pt_buf.into_bytes();
             layout.set_pt_size(pt_bytes.len())?;
             memory.extend(&pt_bytes);
-        };
+            Ok(())
+        })?;
 
         let exn_stack_top_gva = hyperlight_common::layout::MAX_GVA as u64
             - hyperlight_common::layout::SCRATCH_TOP_EXN_STACK_OFFSET
@@ -430,7 +587,11 @@ impl Snapshot {
             hash,
             stack_top_gva: exn_stack_top_gva,
             sregs: None,
+            #[cfg(gdb)]
+            hw_breakpoints: None,
             entrypoint: NextAction::Initialise(load_addr + entrypoint_offset),
+            #[cfg(target_os = "linux")]
+            file: OnceLock::new(),
         })
     }
 
@@ -515,7 +676,11 @@ impl Snapshot {
             hash,
             stack_top_gva,
             sregs: Some(sregs),
+            #[cfg(gdb)]
+            hw_breakpoints: None,
             entrypoint,
+            #[cfg(target_os = "linux")]
+            file: OnceLock::new(),
         })
     }
 
@@ -529,6 +694,11 @@ impl Snapshot {
         &self.regions
     }
 
+    /// The hash of the memory and the regions of this snapshot
+    pub(crate) fn hash(&self) -> [u8; 32] {
+        self.hash
+    }
+
     /// Return the size of the snapshot in bytes.
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn mem_size(&self) -> usize {
@@ -541,6 +711,196 @@ impl Snapshot {
         &self.memory
     }
 
+    /// Return a sealed memfd holding the memory contents of the
+    /// snapshot, creating it on the first call. Returns None if it
+    /// could not be created, in which case the snapshot is restored by
+    /// copying its memory.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn file(&self) -> Option<&File> {
+        self.file
+            .get_or_init(|| match sealed_memfd(&self.memory) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    tracing::warn!("Failed to create the memfd of a snapshot: {:?}", e);
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    /// A snapshot of the same sandbox state as this one, holding
+    /// `memory`, which must be the memory of this snapshot rebuilt
+    /// elsewhere, such as by a [`SnapshotChain`]
+    ///
+    /// [`SnapshotChain`]: super::snapshot_chain::SnapshotChain
+    pub(crate) fn with_memory(&self, memory: Vec<u8>) -> Snapshot {
+        Snapshot {
+            sandbox_id: self.sandbox_id,
+            layout: self.layout,
+            memory,
+            regions: self.regions.clone(),
+            load_info: self.load_info.clone(),
+            hash: self.hash,
+            stack_top_gva: self.stack_top_gva,
+            sregs: self.sregs,
+            #[cfg(gdb)]
+            hw_breakpoints: self.hw_breakpoints,
+            entrypoint: self.entrypoint,
+            #[cfg(target_os = "linux")]
+            file: OnceLock::new(),
+        }
+    }
+
+    /// Saves the snapshot to the file at `path`, so that sandboxes can
+    /// be created from it by other processes or on other hosts with
+    /// [`UninitializedSandbox::from_snapshot_file`].
+    ///
+    /// Snapshots that have host memory mapped into the guest cannot be
+    /// saved.
+    ///
+    /// [`UninitializedSandbox::from_snapshot_file`]: crate::UninitializedSandbox::from_snapshot_file
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut enc = Encoder::default();
+        self.encode(&mut enc)?;
+        std::fs::write(path, enc.bytes)?;
+        Ok(())
+    }
+
+    /// Writes the snapshot to `enc` in the format of [`save`](Self::save)
+    pub(crate) fn encode(&self, enc: &mut Encoder) -> Result<()> {
+        self.encode_state(enc)?;
+        enc.bytes.extend_from_slice(&self.hash);
+        enc.bytes(&self.memory);
+        Ok(())
+    }
+
+    /// Writes everything of the snapshot but its hash and its memory to
+    /// `enc`, for the memory to be sent apart from it
+    pub(crate) fn encode_state(&self, enc: &mut Encoder) -> Result<()> {
+        if !self.regions.is_empty() {
+            return Err(new_error!(
+                "Snapshots with {} mapped regions cannot be saved",
+                self.regions.len()
+            ));
+        }
+        enc.bytes.extend_from_slice(&snapshot_file::MAGIC);
+        enc.u32(snapshot_file::VERSION);
+        enc.bytes(self.layout.info().to_json().as_bytes());
+        self.layout.encode(enc);
+        enc.u64(self.stack_top_gva);
+        match self.entrypoint {
+            NextAction::Initialise(addr) => {
+                enc.u8(0);
+                enc.u64(addr);
+            }
+            NextAction::Call(addr) => {
+                enc.u8(1);
+                enc.u64(addr);
+            }
+            #[cfg(test)]
+            NextAction::None => {
+                enc.u8(2);
+                enc.u64(0);
+            }
+        }
+        enc.u8(self.sregs.is_some() as u8);
+        if let Some(sregs) = &self.sregs {
+            enc.sregs(sregs);
+        }
+        Ok(())
+    }
+
+    /// Loads a snapshot saved with [`save`](Self::save).
+    ///
+    /// Fails if the file is not a snapshot, if it is corrupted, or if
+    /// it was saved by a build of hyperlight that lays out the sandbox
+    /// memory differently. The loaded snapshot can only be restored
+    /// into sandboxes created from it.
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn load(path: impl AsRef<Path>) -> Result<Snapshot> {
+        let bytes = std::fs::read(path)?;
+        let mut dec = Decoder::new(&bytes);
+        let snapshot = Self::decode(&mut dec)?;
+        dec.finish()?;
+        Ok(snapshot)
+    }
+
+    /// Reads a snapshot written by [`encode`](Self::encode) from `dec`
+    pub(crate) fn decode(dec: &mut Decoder<'_>) -> Result<Snapshot> {
+        let state = Self::decode_state(dec)?;
+        let saved_hash: [u8; 32] = dec.array()?;
+        let memory = dec.bytes()?.to_vec();
+        state.with_saved_memory(memory, saved_hash)
+    }
+
+    /// Reads what [`encode_state`](Self::encode_state) wrote from `dec`,
+    /// returning a snapshot without memory, to be given the memory sent
+    /// apart from it with [`with_saved_memory`](Self::with_saved_memory)
+    pub(crate) fn decode_state(dec: &mut Decoder<'_>) -> Result<Snapshot> {
+        let magic: [u8; 8] = dec.array()?;
+        if magic != snapshot_file::MAGIC {
+            return Err(new_error!("Not a snapshot file"));
+        }
+        let version = dec.u32()?;
+        if version != snapshot_file::VERSION {
+            return Err(new_error!(
+                "Snapshot file version {} is not supported, expected {}",
+                version,
+                snapshot_file::VERSION
+            ));
+        }
+        let saved_info = std::str::from_utf8(dec.bytes()?)
+            .map_err(|e| new_error!("Invalid snapshot layout: {}", e))?;
+        let saved_info = SandboxLayoutInfo::from_json(saved_info)?;
+        let layout = SandboxMemoryLayout::decode(dec)?;
+        saved_info.check_compatible(&layout.info())?;
+        let stack_top_gva = dec.u64()?;
+        let entrypoint = match (dec.u8()?, dec.u64()?) {
+            (0, addr) => NextAction::Initialise(addr),
+            (1, addr) => NextAction::Call(addr),
+            (tag, _) => return Err(new_error!("Invalid snapshot entrypoint {}", tag)),
+        };
+        let sregs = match dec.u8()? {
+            0 => None,
+            _ => Some(dec.sregs()?),
+        };
+        Ok(Self {
+            sandbox_id: SANDBOX_CONFIGURATION_COUNTER.fetch_add(1, Ordering::Relaxed),
+            layout,
+            memory: Vec::new(),
+            regions: Vec::new(),
+            load_info: LoadInfo::dummy(),
+            hash: [0; 32],
+            stack_top_gva,
+            sregs,
+            #[cfg(gdb)]
+            hw_breakpoints: None,
+            entrypoint,
+            #[cfg(target_os = "linux")]
+            file: OnceLock::new(),
+        })
+    }
+
+    /// This snapshot, read by [`decode_state`](Self::decode_state),
+    /// holding `memory`, which must hash to the `saved_hash` of the
+    /// snapshot it was saved from
+    pub(crate) fn with_saved_memory(
+        self,
+        memory: Vec<u8>,
+        saved_hash: [u8; 32],
+    ) -> Result<Snapshot> {
+        let hash = hash(&memory, &self.regions)?;
+        if hash != saved_hash {
+            return Err(new_error!("Snapshot file is corrupted"));
+        }
+        Ok(Self {
+            memory,
+            hash,
+            ..self
+        })
+    }
+
     /// Return a copy of the load info for the exe in the snapshot
     pub(crate) fn load_info(&self) -> LoadInfo {
         self.load_info.clone()
@@ -550,6 +910,14 @@ impl Snapshot {
         &self.layout
     }
 
+    /// Describes the memory layout of the snapshot, so that it can be
+    /// checked for compatibility before the snapshot state is restored
+    /// by another build of hyperlight or into another sandbox, see
+    /// [`SandboxLayoutInfo::check_compatible`].
+    pub fn layout_info(&self) -> SandboxLayoutInfo {
+        self.layout.info()
+    }
+
     pub(crate) fn root_pt_gpa(&self) -> u64 {
         self.layout.get_pt_base_gpa()
     }
@@ -570,6 +938,29 @@ impl Snapshot {
     pub(crate) fn entrypoint(&self) -> NextAction {
         self.entrypoint
     }
+
+    /// Returns the hardware breakpoints programmed by the debugger when
+    /// the snapshot was taken, if a debugger was attached
+    #[cfg(gdb)]
+    pub(crate) fn hw_breakpoints(&self) -> Option<&HwBreakpointRegs> {
+        self.hw_breakpoints.as_ref()
+    }
+
+    /// Stores the hardware breakpoints programmed by the debugger
+    #[cfg(gdb)]
+    pub(crate) fn with_hw_breakpoints(mut self, hw_breakpoints: Option<HwBreakpointRegs>) -> Self {
+        self.hw_breakpoints = hw_breakpoints;
+        self
+    }
+}
+
+impl std::fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("sandbox_id", &self.sandbox_id)
+            .field("mem_size", &self.memory.len())
+            .finish_non_exhaustive()
+    }
 }
 
 impl PartialEq for Snapshot {
@@ -661,6 +1052,134 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn restore_copies_dirty_pages() {
+        let (mut mgr, pt_base) = make_simple_pt_mems();
+        mgr.shared_mem
+            .copy_from_slice(&[b'a'; PAGE_SIZE], 0)
+            .unwrap();
+        let snapshot = super::Snapshot::new(
+            &mut mgr.shared_mem,
+            &mut mgr.scratch_mem,
+            0,
+            mgr.layout,
+            LoadInfo::dummy(),
+            Vec::new(),
+            pt_base,
+            0,
+            default_sregs(),
+            super::NextAction::None,
+        )
+        .unwrap();
+        mgr.restore_snapshot(&snapshot).unwrap();
+
+        // Nothing was written since the restore
+        assert_eq!(mgr.shared_mem.restore_dirty_pages(&snapshot).unwrap(), 0);
+
+        mgr.shared_mem.write::<u8>(1, b'b').unwrap();
+        mgr.restore_snapshot(&snapshot).unwrap();
+        mgr.shared_mem
+            .with_exclusivity(|e| assert_eq!(e.as_slice(), snapshot.memory()))
+            .unwrap();
+
+        mgr.shared_mem.write::<u8>(PAGE_SIZE + 1, 0xff).unwrap();
+        assert_eq!(mgr.shared_mem.restore_dirty_pages(&snapshot).unwrap(), 1);
+    }
+
+    #[test]
+    fn restore_does_not_write_the_snapshot() {
+        let (mut mgr, pt_base) = make_simple_pt_mems();
+        let snapshot = |mgr: &mut SandboxMemoryManager<HostSharedMemory>, fill: u8| {
+            mgr.shared_mem
+                .copy_from_slice(&[fill; PAGE_SIZE], 0)
+                .unwrap();
+            super::Snapshot::new(
+                &mut mgr.shared_mem,
+                &mut mgr.scratch_mem,
+                0,
+                mgr.layout,
+                LoadInfo::dummy(),
+                Vec::new(),
+                pt_base,
+                0,
+                default_sregs(),
+                super::NextAction::None,
+            )
+            .unwrap()
+        };
+        let snapshot_a = snapshot(&mut mgr, 0xAA);
+        let snapshot_b = snapshot(&mut mgr, 0xBB);
+
+        for _ in 0..2 {
+            mgr.restore_snapshot(&snapshot_a).unwrap();
+            mgr.shared_mem.fill(0xCC, 0, PAGE_SIZE).unwrap();
+            mgr.restore_snapshot(&snapshot_b).unwrap();
+            mgr.shared_mem
+                .with_exclusivity(|e| assert_eq!(e.as_slice(), snapshot_b.memory()))
+                .unwrap();
+        }
+        mgr.restore_snapshot(&snapshot_a).unwrap();
+        mgr.shared_mem
+            .with_exclusivity(|e| assert_eq!(e.as_slice(), snapshot_a.memory()))
+            .unwrap();
+        assert_eq!(snapshot_a.memory()[0], 0xAA);
+
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::fs::FileExt;
+
+            let mut page = [0u8; PAGE_SIZE];
+            snapshot_a
+                .file()
+                .unwrap()
+                .read_exact_at(&mut page, 0)
+                .unwrap();
+            assert_eq!(page, [0xAA; PAGE_SIZE]);
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let (mut mgr, pt_base) = make_simple_pt_mems();
+        mgr.shared_mem
+            .copy_from_slice(&[b'a'; PAGE_SIZE], 0)
+            .unwrap();
+        let mut sregs = default_sregs();
+        sregs.cr0 = 0x8000_0011;
+        let snapshot = super::Snapshot::new(
+            &mut mgr.shared_mem,
+            &mut mgr.scratch_mem,
+            0,
+            mgr.layout,
+            LoadInfo::dummy(),
+            Vec::new(),
+            pt_base,
+            0x2000,
+            sregs,
+            super::NextAction::Call(0x1234),
+        )
+        .unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        snapshot.save(file.path()).unwrap();
+
+        let loaded = super::Snapshot::load(file.path()).unwrap();
+        assert!(loaded == snapshot);
+        assert_ne!(loaded.sandbox_id(), snapshot.sandbox_id());
+        assert_eq!(loaded.memory(), snapshot.memory());
+        assert_eq!(loaded.layout_info(), snapshot.layout_info());
+        assert_eq!(loaded.stack_top_gva(), 0x2000);
+        assert_eq!(loaded.sregs(), Some(&sregs));
+        assert!(matches!(
+            loaded.entrypoint(),
+            super::NextAction::Call(0x1234)
+        ));
+
+        // Truncated files are not loaded
+        let bytes = std::fs::read(file.path()).unwrap();
+        std::fs::write(file.path(), &bytes[..bytes.len() - 1]).unwrap();
+        assert!(super::Snapshot::load(file.path()).is_err());
+    }
+
     #[test]
     fn snapshot_mem_size() {
         let (mut mgr, pt_base) = make_simple_pt_mems();
@@ -0,0 +1,151 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use hyperlight_common::mem::PAGE_SIZE_USIZE;
+
+use super::snapshot::Snapshot;
+use crate::HyperlightError::SnapshotSandboxMismatch;
+use crate::{Result, new_error};
+
+/// The position of a snapshot in a [`SnapshotChain`], the base snapshot
+/// being the first one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SnapshotId(usize);
+
+/// The pages of a snapshot that differ from the previous snapshot of the
+/// chain
+struct Layer {
+    /// The snapshot without its memory
+    state: Snapshot,
+    mem_size: usize,
+    pages: Vec<(usize, Box<[u8]>)>,
+}
+
+/// A base snapshot followed by the snapshots later taken from the same
+/// sandbox, each of them only storing the pages that changed since the
+/// previous one. This lets a sandbox keep many points to roll back to,
+/// such as one per request it served, for little more memory than a
+/// single snapshot.
+///
+/// The sandbox is rolled back to a point of the chain with
+/// [`MultiUseSandbox::restore_to`], which rebuilds the snapshot by
+/// applying the pages of the chain up to that point.
+///
+/// [`MultiUseSandbox::restore_to`]: crate::MultiUseSandbox::restore_to
+pub struct SnapshotChain {
+    base: Arc<Snapshot>,
+    layers: Vec<Layer>,
+    /// The memory of the last snapshot of the chain, which the next
+    /// snapshot is compared to
+    top: Vec<u8>,
+}
+
+impl SnapshotChain {
+    /// Creates a chain starting at `base`
+    pub fn new(base: Arc<Snapshot>) -> Self {
+        Self {
+            top: base.memory().to_vec(),
+            base,
+            layers: Vec::new(),
+        }
+    }
+
+    /// The id of the base snapshot
+    pub fn base_id(&self) -> SnapshotId {
+        SnapshotId(0)
+    }
+
+    /// The id of the last snapshot of the chain
+    pub fn last_id(&self) -> SnapshotId {
+        SnapshotId(self.layers.len())
+    }
+
+    /// Appends `snapshot`, which must be taken from the sandbox of the
+    /// base snapshot, storing the pages that changed since the last
+    /// snapshot of the chain
+    pub fn push(&mut self, snapshot: &Snapshot) -> Result<SnapshotId> {
+        if snapshot.sandbox_id() != self.base.sandbox_id() {
+            return Err(SnapshotSandboxMismatch);
+        }
+        let memory = snapshot.memory();
+        let pages = memory
+            .chunks(PAGE_SIZE_USIZE)
+            .enumerate()
+            .filter(|(i, page)| {
+                let start = i * PAGE_SIZE_USIZE;
+                self.top.get(start..start + page.len()) != Some(*page)
+            })
+            .map(|(i, page)| (i, Box::from(page)))
+            .collect();
+        self.layers.push(Layer {
+            state: snapshot.with_memory(Vec::new()),
+            mem_size: memory.len(),
+            pages,
+        });
+        self.top.clear();
+        self.top.extend_from_slice(memory);
+        Ok(self.last_id())
+    }
+
+    /// Rebuilds the snapshot `id` of the chain
+    pub fn snapshot(&self, id: SnapshotId) -> Result<Arc<Snapshot>> {
+        if id.0 == 0 {
+            return Ok(self.base.clone());
+        }
+        let layers = self
+            .layers
+            .get(..id.0)
+            .ok_or_else(|| new_error!("Snapshot {:?} is not in the chain", id))?;
+        let mut memory = self.base.memory().to_vec();
+        for layer in layers {
+            memory.resize(layer.mem_size, 0);
+            for (i, page) in &layer.pages {
+                let start = i * PAGE_SIZE_USIZE;
+                memory[start..start + page.len()].copy_from_slice(page);
+            }
+        }
+        Ok(Arc::new(layers[id.0 - 1].state.with_memory(memory)))
+    }
+
+    /// Drops the snapshots taken after `id`, so that the next snapshot
+    /// pushed follows it
+    pub fn truncate(&mut self, id: SnapshotId) -> Result<()> {
+        if id > self.last_id() {
+            return Err(new_error!("Snapshot {:?} is not in the chain", id));
+        }
+        if id < self.last_id() {
+            self.top = self.snapshot(id)?.memory().to_vec();
+            self.layers.truncate(id.0);
+        }
+        Ok(())
+    }
+
+    /// The number of pages stored for the snapshots after the base one
+    pub fn stored_pages(&self) -> usize {
+        self.layers.iter().map(|layer| layer.pages.len()).sum()
+    }
+}
+
+impl std::fmt::Debug for SnapshotChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnapshotChain")
+            .field("snapshots", &(self.layers.len() + 1))
+            .field("stored_pages", &self.stored_pages())
+            .finish_non_exhaustive()
+    }
+}
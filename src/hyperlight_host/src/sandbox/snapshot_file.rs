@@ -0,0 +1,269 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The on-disk format of the snapshots saved with
+//! [`Snapshot::save`](super::snapshot::Snapshot::save).
+//!
+//! A snapshot file starts with [`MAGIC`] and the [`VERSION`] of the
+//! format, followed by the version of Hyperlight that saved it, since the
+//! layout of the sandbox memory depends on it, and then by the fields of
+//! the snapshot, little-endian, ending with its memory.
+
+use crate::hypervisor::regs::{CommonSegmentRegister, CommonSpecialRegisters, CommonTableRegister};
+use crate::{Result, new_error};
+
+/// The first bytes of a snapshot file
+pub(crate) const MAGIC: [u8; 8] = *b"HLSNAPSH";
+/// The version of the format, changed on incompatible changes
+pub(crate) const VERSION: u32 = 4;
+
+/// Writes the fields of a snapshot file
+#[derive(Default)]
+pub(crate) struct Encoder {
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn option_u64(&mut self, value: Option<u64>) {
+        self.u8(value.is_some() as u8);
+        self.u64(value.unwrap_or_default());
+    }
+
+    /// Writes `bytes` prefixed by their length
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn sregs(&mut self, sregs: &CommonSpecialRegisters) {
+        for segment in [
+            &sregs.cs, &sregs.ds, &sregs.es, &sregs.fs, &sregs.gs, &sregs.ss, &sregs.tr, &sregs.ldt,
+        ] {
+            self.u64(segment.base);
+            self.u32(segment.limit);
+            self.u16(segment.selector);
+            for field in [
+                segment.type_,
+                segment.present,
+                segment.dpl,
+                segment.db,
+                segment.s,
+                segment.l,
+                segment.g,
+                segment.avl,
+                segment.unusable,
+            ] {
+                self.u8(field);
+            }
+        }
+        for table in [&sregs.gdt, &sregs.idt] {
+            self.u64(table.base);
+            self.u16(table.limit);
+        }
+        for register in [
+            sregs.cr0,
+            sregs.cr2,
+            sregs.cr3,
+            sregs.cr4,
+            sregs.cr8,
+            sregs.efer,
+            sregs.apic_base,
+        ] {
+            self.u64(register);
+        }
+        for word in sregs.interrupt_bitmap {
+            self.u64(word);
+        }
+    }
+}
+
+/// Reads the fields of a snapshot file
+pub(crate) struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let (head, tail) = self
+            .bytes
+            .split_first_chunk::<N>()
+            .ok_or_else(|| new_error!("Snapshot file is truncated"))?;
+        self.bytes = tail;
+        Ok(*head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub(crate) fn usize(&mut self) -> Result<usize> {
+        Ok(usize::try_from(self.u64()?)?)
+    }
+
+    pub(crate) fn option_u64(&mut self) -> Result<Option<u64>> {
+        let present = self.u8()? != 0;
+        let value = self.u64()?;
+        Ok(present.then_some(value))
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        self.take()
+    }
+
+    /// Reads bytes written by [`Encoder::bytes`]
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.usize()?;
+        if len > self.bytes.len() {
+            return Err(new_error!("Snapshot file is truncated"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn sregs(&mut self) -> Result<CommonSpecialRegisters> {
+        let mut segment = || -> Result<CommonSegmentRegister> {
+            Ok(CommonSegmentRegister {
+                base: self.u64()?,
+                limit: self.u32()?,
+                selector: self.u16()?,
+                type_: self.u8()?,
+                present: self.u8()?,
+                dpl: self.u8()?,
+                db: self.u8()?,
+                s: self.u8()?,
+                l: self.u8()?,
+                g: self.u8()?,
+                avl: self.u8()?,
+                unusable: self.u8()?,
+                padding: 0,
+            })
+        };
+        let (cs, ds, es, fs, gs, ss, tr, ldt) = (
+            segment()?,
+            segment()?,
+            segment()?,
+            segment()?,
+            segment()?,
+            segment()?,
+            segment()?,
+            segment()?,
+        );
+        let mut table = || -> Result<CommonTableRegister> {
+            Ok(CommonTableRegister {
+                base: self.u64()?,
+                limit: self.u16()?,
+            })
+        };
+        let (gdt, idt) = (table()?, table()?);
+        Ok(CommonSpecialRegisters {
+            cs,
+            ds,
+            es,
+            fs,
+            gs,
+            ss,
+            tr,
+            ldt,
+            gdt,
+            idt,
+            cr0: self.u64()?,
+            cr2: self.u64()?,
+            cr3: self.u64()?,
+            cr4: self.u64()?,
+            cr8: self.u64()?,
+            efer: self.u64()?,
+            apic_base: self.u64()?,
+            interrupt_bitmap: [self.u64()?, self.u64()?, self.u64()?, self.u64()?],
+        })
+    }
+
+    /// Fails if there are bytes left
+    pub(crate) fn finish(self) -> Result<()> {
+        if !self.bytes.is_empty() {
+            return Err(new_error!(
+                "Snapshot file has {} unexpected trailing bytes",
+                self.bytes.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, Encoder};
+    use crate::hypervisor::regs::CommonSpecialRegisters;
+
+    #[test]
+    fn round_trip() {
+        let mut sregs = CommonSpecialRegisters::default();
+        sregs.cs.l = 1;
+        sregs.cs.selector = 8;
+        sregs.idt.base = 0xffff_8000_0000_0000;
+        sregs.cr3 = 0x1000;
+        sregs.interrupt_bitmap[3] = 1;
+
+        let mut enc = Encoder::default();
+        enc.u32(42);
+        enc.option_u64(None);
+        enc.bytes(b"memory");
+        enc.sregs(&sregs);
+
+        let mut dec = Decoder::new(&enc.bytes);
+        assert_eq!(dec.u32().unwrap(), 42);
+        assert_eq!(dec.option_u64().unwrap(), None);
+        assert_eq!(dec.bytes().unwrap(), b"memory");
+        assert_eq!(dec.sregs().unwrap(), sregs);
+        dec.finish().unwrap();
+
+        let mut dec = Decoder::new(&enc.bytes[..10]);
+        dec.u32().unwrap();
+        assert!(dec.option_u64().is_err());
+    }
+}
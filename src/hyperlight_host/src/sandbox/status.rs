@@ -0,0 +1,132 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{HyperlightError, Result};
+
+/// The lifecycle state of a sandbox.
+///
+/// A sandbox starts [`Created`](Self::Created) as an
+/// [`UninitializedSandbox`](crate::UninitializedSandbox), becomes
+/// [`Initialized`](Self::Initialized) once evolved into a
+/// [`MultiUseSandbox`](crate::MultiUseSandbox), and is
+/// [`Closed`](Self::Closed) once dropped. In between, it moves to
+/// [`Running`](Self::Running) for the duration of each guest function call,
+/// and to [`ResettingSnapshot`](Self::ResettingSnapshot) while a snapshot
+/// is restored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SandboxStatus {
+    /// The guest has not been initialized yet
+    Created,
+    /// The guest is initialized and ready to be called
+    Initialized,
+    /// The guest function with the given name is being called
+    Running(String),
+    /// A snapshot is being restored. A sandbox is left in this state if
+    /// restoring failed partway through, and must then be restored again
+    /// before it can be used.
+    ResettingSnapshot,
+    /// The guest did not run to completion, see
+    /// [`HyperlightError::PoisonedSandbox`]. The sandbox must be restored
+    /// from a snapshot before it can be used.
+    Poisoned,
+    /// The sandbox has been dropped
+    Closed,
+}
+
+impl Display for SandboxStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxStatus::Created => write!(f, "created"),
+            SandboxStatus::Initialized => write!(f, "initialized"),
+            SandboxStatus::Running(function_name) => {
+                write!(f, "running guest function {}", function_name)
+            }
+            SandboxStatus::ResettingSnapshot => write!(f, "resetting to a snapshot"),
+            SandboxStatus::Poisoned => write!(f, "poisoned"),
+            SandboxStatus::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+/// A handle to query the [`SandboxStatus`] of a sandbox, which stays
+/// valid as the sandbox evolves and after it is dropped, so that it can
+/// be used from other threads or from host functions.
+#[derive(Clone, Debug)]
+pub struct SandboxStatusHandle(Arc<Mutex<SandboxStatus>>);
+
+impl SandboxStatusHandle {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(Mutex::new(SandboxStatus::Created)))
+    }
+
+    /// Returns the current status of the sandbox
+    pub fn get(&self) -> SandboxStatus {
+        // The lock is never held across code that could panic, so its
+        // content is always consistent
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub(crate) fn set(&self, status: SandboxStatus) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = status;
+    }
+
+    /// Returns an error unless the sandbox is
+    /// [`Initialized`](SandboxStatus::Initialized), in which case
+    /// `operation` can be carried out
+    pub(crate) fn check(&self, operation: &'static str) -> Result<()> {
+        match self.get() {
+            SandboxStatus::Initialized => Ok(()),
+            SandboxStatus::Poisoned => Err(HyperlightError::PoisonedSandbox),
+            status => Err(HyperlightError::InvalidSandboxState(operation, status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SandboxStatus, SandboxStatusHandle};
+    use crate::HyperlightError;
+
+    #[test]
+    fn check_only_allows_initialized() {
+        let handle = SandboxStatusHandle::new();
+        let err = handle.check("call a guest function").unwrap_err();
+        assert!(matches!(
+            err,
+            HyperlightError::InvalidSandboxState(_, SandboxStatus::Created)
+        ));
+
+        handle.set(SandboxStatus::Initialized);
+        handle.check("call a guest function").unwrap();
+
+        handle.set(SandboxStatus::Poisoned);
+        let err = handle.check("call a guest function").unwrap_err();
+        assert!(matches!(err, HyperlightError::PoisonedSandbox));
+
+        handle.set(SandboxStatus::Running("Echo".to_string()));
+        let err = handle.check("take a snapshot").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot take a snapshot while the sandbox is running guest function Echo"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use hyperlight_common::stream::STREAM_CHUNK_SIZE;
+
+use crate::{Result, new_error};
+
+/// Identifies a stream opened with [`MultiUseSandbox::open_input_stream`]
+/// or [`MultiUseSandbox::open_output_stream`]. The guest is given the
+/// `u32` of the stream, usually as an argument of a guest function call.
+///
+/// [`MultiUseSandbox::open_input_stream`]: crate::MultiUseSandbox::open_input_stream
+/// [`MultiUseSandbox::open_output_stream`]: crate::MultiUseSandbox::open_output_stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StreamId(u32);
+
+impl StreamId {
+    /// The value the guest identifies the stream with
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+enum Stream {
+    Input(Box<dyn Read + Send>),
+    Output(Box<dyn Write + Send>),
+}
+
+#[derive(Default)]
+struct StreamTable {
+    streams: HashMap<u32, Stream>,
+    next_id: u32,
+}
+
+/// The streams of a sandbox, shared with the host functions the guest
+/// reads and writes them through
+#[derive(Clone, Default)]
+pub(crate) struct Streams {
+    table: Arc<Mutex<StreamTable>>,
+}
+
+impl Streams {
+    fn open(&self, stream: Stream) -> Result<StreamId> {
+        let mut table = self.lock()?;
+        let id = table.next_id;
+        table.next_id = table.next_id.wrapping_add(1);
+        table.streams.insert(id, stream);
+        Ok(StreamId(id))
+    }
+
+    pub(crate) fn open_input(&self, reader: impl Read + Send + 'static) -> Result<StreamId> {
+        self.open(Stream::Input(Box::new(reader)))
+    }
+
+    pub(crate) fn open_output(&self, writer: impl Write + Send + 'static) -> Result<StreamId> {
+        self.open(Stream::Output(Box::new(writer)))
+    }
+
+    /// Removes the stream `id`, flushing it if it is an output stream
+    pub(crate) fn close(&self, id: StreamId) -> Result<()> {
+        let stream = self
+            .lock()?
+            .streams
+            .remove(&id.0)
+            .ok_or_else(|| new_error!("Stream {} is not open", id.0))?;
+        if let Stream::Output(mut writer) = stream {
+            writer
+                .flush()
+                .map_err(|e| new_error!("Failed to flush stream {}: {}", id.0, e))?;
+        }
+        Ok(())
+    }
+
+    /// Reads up to `max_len` bytes, capped to [`STREAM_CHUNK_SIZE`], from
+    /// the input stream `id`. The chunk is only empty at the end of the
+    /// stream.
+    pub(crate) fn read(&self, id: u32, max_len: u32) -> Result<Vec<u8>> {
+        let mut table = self.lock()?;
+        let Some(Stream::Input(reader)) = table.streams.get_mut(&id) else {
+            return Err(new_error!("Stream {} is not an open input stream", id));
+        };
+        let mut chunk = vec![0; (max_len as usize).min(STREAM_CHUNK_SIZE)];
+        let mut len = 0;
+        while len < chunk.len() {
+            match reader.read(&mut chunk[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(new_error!("Failed to read stream {}: {}", id, e)),
+            }
+        }
+        chunk.truncate(len);
+        Ok(chunk)
+    }
+
+    /// Writes `data` to the output stream `id`
+    pub(crate) fn write(&self, id: u32, data: &[u8]) -> Result<()> {
+        if data.len() > STREAM_CHUNK_SIZE {
+            return Err(new_error!(
+                "Chunk of {} bytes written to stream {} is larger than {} bytes",
+                data.len(),
+                id,
+                STREAM_CHUNK_SIZE
+            ));
+        }
+        let mut table = self.lock()?;
+        let Some(Stream::Output(writer)) = table.streams.get_mut(&id) else {
+            return Err(new_error!("Stream {} is not an open output stream", id));
+        };
+        writer
+            .write_all(data)
+            .map_err(|e| new_error!("Failed to write stream {}: {}", id, e))
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, StreamTable>> {
+        self.table
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+    }
+}
+
+impl std::fmt::Debug for Streams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Streams").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    use hyperlight_common::stream::STREAM_CHUNK_SIZE;
+
+    use super::Streams;
+
+    /// A writer the test can look at once the stream is closed
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn chunks_are_read_and_written() {
+        let streams = Streams::default();
+        let payload: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        let input = streams.open_input(Cursor::new(payload.clone())).unwrap();
+        let buffer = SharedBuffer::default();
+        let output = streams.open_output(buffer.clone()).unwrap();
+        assert_ne!(input, output);
+
+        loop {
+            let chunk = streams.read(input.as_u32(), u32::MAX).unwrap();
+            if chunk.is_empty() {
+                break;
+            }
+            assert!(chunk.len() <= STREAM_CHUNK_SIZE);
+            streams.write(output.as_u32(), &chunk).unwrap();
+        }
+        streams.close(input).unwrap();
+        streams.close(output).unwrap();
+        assert_eq!(*buffer.0.lock().unwrap(), payload);
+
+        // The streams are gone once closed, and cannot be used the other
+        // way around
+        assert!(streams.read(input.as_u32(), 1).is_err());
+        assert!(streams.close(output).is_err());
+        let input = streams.open_input(Cursor::new(vec![1])).unwrap();
+        assert!(streams.write(input.as_u32(), &[1]).is_err());
+        assert!(
+            streams
+                .write(input.as_u32(), &vec![0; STREAM_CHUNK_SIZE + 1])
+                .is_err()
+        );
+    }
+}
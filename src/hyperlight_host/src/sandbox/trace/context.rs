@@ -27,7 +27,7 @@ use opentelemetry::{Context, KeyValue, global};
 use tracing::span::{EnteredSpan, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::hypervisor::regs::CommonRegisters;
+use crate::hypervisor::arch::GuestRegisters;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::HostSharedMemory;
 use crate::{Result, new_error};
@@ -50,13 +50,12 @@ impl EventsBatch {
     /// * `mem_mgr` - The sandbox memory manager with access to shared and scratch memory
     /// * `root_pt` - The root page table physical address (CR3) for GVA translation
     fn from_regs(
-        regs: &CommonRegisters,
+        regs: &impl GuestRegisters,
         mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
         root_pt: u64,
     ) -> Result<Self> {
-        let magic_no = regs.r8;
-        let trace_data_gva = regs.r9;
-        let trace_data_len = regs.r10 as usize;
+        let (magic_no, trace_data_gva, trace_data_len) = regs.trace_batch_args();
+        let trace_data_len = trace_data_len as usize;
 
         // Validate the magic number to ensure the guest is providing trace data
         if magic_no != OutBAction::TraceBatch as u64 {
@@ -102,7 +101,8 @@ pub struct TraceContext {
     /// **NOTE**: This is only used until the TSC frequency is calculated, when the first
     /// records are received.
     start_tsc: Option<u64>,
-    /// The frequency of the timestamp counter.
+    /// The frequency of the timestamp counter, reported by the guest or
+    /// the configuration, or measured.
     tsc_freq: Option<u64>,
     current_parent_ctx: Option<Context>,
 }
@@ -199,13 +199,13 @@ impl TraceContext {
     }
 
     /// Check if the registers indicate that there is trace data to be handled.
-    pub fn has_trace_data(&self, regs: &CommonRegisters) -> bool {
-        regs.r8 == OutBAction::TraceBatch as u64
+    pub fn has_trace_data(&self, regs: &impl GuestRegisters) -> bool {
+        regs.trace_batch_args().0 == OutBAction::TraceBatch as u64
     }
 
     pub fn handle_trace(
         &mut self,
-        regs: &CommonRegisters,
+        regs: &impl GuestRegisters,
         mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
         root_pt: u64,
     ) -> Result<()> {
@@ -224,9 +224,13 @@ impl TraceContext {
         // Process each event
         for ev in events.into_iter() {
             match ev {
-                GuestEvent::GuestStart { tsc } => {
+                GuestEvent::GuestStart { tsc, tsc_freq } => {
                     // Move to GuestStart
-                    if self.tsc_freq.is_none() {
+                    // Use the frequency the guest reports, which accounts for
+                    // a configured guest TSC frequency, and measure it otherwise
+                    if tsc_freq != 0 {
+                        self.tsc_freq = Some(tsc_freq);
+                    } else if self.tsc_freq.is_none() {
                         self.calculate_tsc_freq()?;
                     }
                     self.start_tsc = Some(tsc);
@@ -368,6 +372,12 @@ impl TraceContext {
         self.current_parent_ctx = Some(ctx);
     }
 
+    /// Use `tsc_freq`, the frequency the guest TSC was configured to run at,
+    /// instead of measuring it when the guest does not report it
+    pub fn set_tsc_freq(&mut self, tsc_freq: u64) {
+        self.tsc_freq = Some(tsc_freq);
+    }
+
     pub fn new_host_trace(&mut self, ctx: Context) {
         let span = tracing::info_span!("call-to-host");
         let _ = span.set_parent(ctx);
@@ -470,6 +480,23 @@ mod tests {
         assert!(trace_ctx.host_spans.len() == 1);
     }
 
+    /// Test that the TSC frequency reported by the guest is used to
+    /// convert its timestamps.
+    #[test]
+    fn test_guest_trace_reported_tsc_freq() {
+        let mut trace_ctx = create_dummy_trace_context();
+
+        let events = vec![GuestEvent::GuestStart {
+            tsc: 1000,
+            tsc_freq: 1_000_000_000,
+        }];
+
+        let res = trace_ctx.handle_trace_impl(events);
+        assert!(res.is_ok());
+        assert_eq!(trace_ctx.tsc_freq, Some(1_000_000_000));
+        assert_eq!(trace_ctx.start_tsc, Some(1000));
+    }
+
     /// Test handling a batch with one span and no events.
     /// The span is not closed.
     #[test]
@@ -477,7 +504,10 @@ mod tests {
         let mut trace_ctx = create_dummy_trace_context();
 
         let events = vec![
-            GuestEvent::GuestStart { tsc: 1000 },
+            GuestEvent::GuestStart {
+                tsc: 1000,
+                tsc_freq: 0,
+            },
             create_open_span(1, None, "test-span", "test-target", 2000, vec![]),
         ];
 
@@ -495,7 +525,10 @@ mod tests {
         let mut trace_ctx = create_dummy_trace_context();
 
         let events = vec![
-            GuestEvent::GuestStart { tsc: 1000 },
+            GuestEvent::GuestStart {
+                tsc: 1000,
+                tsc_freq: 0,
+            },
             create_open_span(1, None, "test-span", "test-target", 2000, vec![]),
             create_close_span(1, 2500),
         ];
@@ -515,7 +548,10 @@ mod tests {
         let mut trace_ctx = create_dummy_trace_context();
 
         let events = vec![
-            GuestEvent::GuestStart { tsc: 1000 },
+            GuestEvent::GuestStart {
+                tsc: 1000,
+                tsc_freq: 0,
+            },
             create_open_span(1, None, "test-span", "test-target", 2000, vec![]),
             create_log_event(1, 2500, "test-event", vec![]),
         ];
@@ -534,7 +570,10 @@ mod tests {
         let mut trace_ctx = create_dummy_trace_context();
 
         let events = vec![
-            GuestEvent::GuestStart { tsc: 1000 },
+            GuestEvent::GuestStart {
+                tsc: 1000,
+                tsc_freq: 0,
+            },
             create_open_span(1, None, "parent-span", "test-target", 2000, vec![]),
             create_open_span(2, Some(1), "child-span", "test-target", 2500, vec![]),
         ];
@@ -553,7 +592,10 @@ mod tests {
         let mut trace_ctx = create_dummy_trace_context();
 
         let events = vec![
-            GuestEvent::GuestStart { tsc: 1000 },
+            GuestEvent::GuestStart {
+                tsc: 1000,
+                tsc_freq: 0,
+            },
             create_open_span(1, None, "parent-span", "test-target", 2000, vec![]),
             create_open_span(2, Some(1), "child-span", "test-target", 2500, vec![]),
             create_close_span(2, 3000),
@@ -576,7 +618,10 @@ mod tests {
         let mut trace_ctx = create_dummy_trace_context();
 
         let events = vec![
-            GuestEvent::GuestStart { tsc: 1000 },
+            GuestEvent::GuestStart {
+                tsc: 1000,
+                tsc_freq: 0,
+            },
             create_open_span(1, None, "parent-span", "test-target", 2000, vec![]),
             create_open_span(2, Some(1), "child-span", "test-target", 2500, vec![]),
             create_close_span(2, 3000),
@@ -619,7 +664,10 @@ mod tests {
         trace_ctx.start_instant = Some(Instant::now());
 
         let events = vec![
-            GuestEvent::GuestStart { tsc: 1000 },
+            GuestEvent::GuestStart {
+                tsc: 1000,
+                tsc_freq: 0,
+            },
             create_open_span(1, None, "span", "target", 1500, vec![]),
         ];
 
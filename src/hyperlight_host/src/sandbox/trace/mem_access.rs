@@ -0,0 +1,127 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+/// A store of the guest to a range of its memory traced with
+/// [`MultiUseSandbox::trace_memory_accesses`]
+///
+/// [`MultiUseSandbox::trace_memory_accesses`]: crate::MultiUseSandbox::trace_memory_accesses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The guest virtual address the store faulted on
+    pub gva: u64,
+    /// The guest virtual address of the instruction that made the store
+    pub rip: u64,
+    /// The number of bytes from `gva` up to the last byte the store
+    /// changed, which is less than the size of the store if it wrote
+    /// the values already there, 0 if it changed nothing
+    pub size: u32,
+}
+
+/// The most bytes a single store of the guest can change, with AVX-512
+const MAX_STORE_SIZE: usize = 64;
+
+/// The stores of the guest to its traced memory, in the order they were
+/// made, until they are taken by the host
+#[derive(Debug, Default)]
+pub(crate) struct MemoryAccessLog {
+    accesses: Vec<MemoryAccess>,
+    /// The store the guest is single-stepping, with the bytes at its
+    /// address before it
+    pending: Option<(MemoryAccess, Vec<u8>)>,
+}
+
+impl MemoryAccessLog {
+    /// The number of bytes at `gva` compared before and after a store to
+    /// find out its size, which stay in the page of `gva`
+    pub(crate) fn compared_len(gva: u64) -> usize {
+        let page_size = hyperlight_common::vmem::PAGE_SIZE;
+        (page_size - gva as usize % page_size).min(MAX_STORE_SIZE)
+    }
+
+    /// Starts recording the store of the instruction at `rip` to `gva`,
+    /// where the bytes are `before`
+    pub(crate) fn begin(&mut self, gva: u64, rip: u64, before: Vec<u8>) {
+        let access = MemoryAccess { gva, rip, size: 0 };
+        self.pending = Some((access, before));
+    }
+
+    /// The address of the store being recorded, if any
+    pub(crate) fn pending_gva(&self) -> Option<u64> {
+        self.pending.as_ref().map(|(access, _)| access.gva)
+    }
+
+    /// Records the store started with [`Self::begin`], now that the bytes
+    /// at its address are `after`, and emits it as a tracing event
+    pub(crate) fn finish(&mut self, after: &[u8]) {
+        let Some((mut access, before)) = self.pending.take() else {
+            return;
+        };
+        access.size = before
+            .iter()
+            .zip(after)
+            .rposition(|(before, after)| before != after)
+            .map_or(0, |last| last as u32 + 1);
+        tracing::debug!(
+            gva = format_args!("{:#x}", access.gva),
+            rip = format_args!("{:#x}", access.rip),
+            size = access.size,
+            "guest store to traced memory"
+        );
+        self.accesses.push(access);
+    }
+
+    /// Takes the stores recorded so far
+    pub(crate) fn take(&mut self) -> Vec<MemoryAccess> {
+        std::mem::take(&mut self.accesses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryAccess, MemoryAccessLog};
+
+    #[test]
+    fn stores_are_sized_by_the_bytes_they_change() {
+        assert_eq!(MemoryAccessLog::compared_len(0x2000), 64);
+        assert_eq!(MemoryAccessLog::compared_len(0x2ffc), 4);
+
+        let mut log = MemoryAccessLog::default();
+        log.begin(0x2000, 0x1234, vec![1, 2, 3, 4]);
+        assert_eq!(log.pending_gva(), Some(0x2000));
+        log.finish(&[1, 5, 3, 4]);
+        // The same value written again changes nothing
+        log.begin(0x2008, 0x1238, vec![1, 2]);
+        log.finish(&[1, 2]);
+        assert_eq!(log.pending_gva(), None);
+
+        assert_eq!(
+            log.take(),
+            vec![
+                MemoryAccess {
+                    gva: 0x2000,
+                    rip: 0x1234,
+                    size: 2,
+                },
+                MemoryAccess {
+                    gva: 0x2008,
+                    rip: 0x1238,
+                    size: 0,
+                },
+            ]
+        );
+        assert!(log.take().is_empty());
+    }
+}
@@ -19,7 +19,7 @@ use std::sync::{Arc, Mutex};
 use fallible_iterator::FallibleIterator;
 use framehop::Unwinder;
 
-use crate::hypervisor::regs::CommonRegisters;
+use crate::hypervisor::arch::GuestRegisters;
 use crate::mem::layout::SandboxMemoryLayout;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::HostSharedMemory;
@@ -92,7 +92,7 @@ impl MemTraceInfo {
 
     fn unwind(
         &self,
-        regs: &CommonRegisters,
+        regs: &impl GuestRegisters,
         mem_mgr: &SandboxMemoryManager<HostSharedMemory>,
     ) -> Result<Vec<u64>> {
         let mut read_stack = |addr| {
@@ -106,8 +106,8 @@ impl MemTraceInfo {
             .try_lock()
             .map_err(|e| new_error!("could not lock unwinder cache {}\n", e))?;
         let iter = self.unwinder.iter_frames(
-            regs.rip,
-            framehop::x86_64::UnwindRegsX86_64::new(regs.rip, regs.rsp, regs.rbp),
+            regs.pc(),
+            framehop::x86_64::UnwindRegsX86_64::new(regs.pc(), regs.sp(), regs.fp()),
             &mut *cache,
             &mut read_stack,
         );
@@ -145,7 +145,7 @@ impl MemTraceInfo {
 
     fn handle_trace(
         &self,
-        regs: &CommonRegisters,
+        regs: &impl GuestRegisters,
         mem_mgr: &SandboxMemoryManager<HostSharedMemory>,
         trace_identifier: TraceFrameType,
     ) -> std::result::Result<(), HandleOutbError> {
@@ -153,8 +153,7 @@ impl MemTraceInfo {
             return Ok(());
         };
 
-        let amt = regs.rax;
-        let ptr = regs.rcx;
+        let (amt, ptr) = regs.mem_profile_args();
 
         match trace_identifier {
             TraceFrameType::MemAlloc => self
@@ -177,7 +176,7 @@ impl MemTraceInfo {
     #[inline(always)]
     pub(crate) fn handle_trace_mem_alloc(
         &self,
-        regs: &CommonRegisters,
+        regs: &impl GuestRegisters,
         mem_mgr: &SandboxMemoryManager<HostSharedMemory>,
     ) -> std::result::Result<(), HandleOutbError> {
         self.handle_trace(regs, mem_mgr, TraceFrameType::MemAlloc)
@@ -186,7 +185,7 @@ impl MemTraceInfo {
     #[inline(always)]
     pub(crate) fn handle_trace_mem_free(
         &self,
-        regs: &CommonRegisters,
+        regs: &impl GuestRegisters,
         mem_mgr: &SandboxMemoryManager<HostSharedMemory>,
     ) -> std::result::Result<(), HandleOutbError> {
         self.handle_trace(regs, mem_mgr, TraceFrameType::MemFree)
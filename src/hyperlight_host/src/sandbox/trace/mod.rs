@@ -23,3 +23,11 @@ pub(crate) use context::TraceContext;
 mod mem_profile;
 #[cfg(feature = "mem_profile")]
 pub(crate) use mem_profile::MemTraceInfo;
+
+/// Tracing of the stores of the guest to ranges of its memory.
+#[cfg(feature = "mem_trace")]
+mod mem_access;
+#[cfg(feature = "mem_trace")]
+pub use mem_access::MemoryAccess;
+#[cfg(feature = "mem_trace")]
+pub(crate) use mem_access::MemoryAccessLog;
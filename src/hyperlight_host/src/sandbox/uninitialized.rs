@@ -19,16 +19,41 @@ use std::option::Option;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
+use hyperlight_common::diagnostics::HOST_ECHO_FUNCTION;
+use hyperlight_common::locale::{HOST_LOCALE_FUNCTION, HOST_TIMEZONE_FUNCTION};
+use hyperlight_common::messaging::{
+    HOST_POLL_FUNCTION, HOST_PUBLISH_FUNCTION, HOST_SUBSCRIBE_FUNCTION,
+};
+use hyperlight_common::secrets::HOST_SECRET_FUNCTION;
 use tracing::{Span, instrument};
 use tracing_core::LevelFilter;
 
-use super::host_funcs::{FunctionRegistry, default_writer_func};
+use super::debug_events::{DebugEventSink, DebugEvents};
+use super::diagnostics::host_echo;
+use super::host_call_recording::{HostCallFixture, HostCallMode, HostCallRecorder};
+use super::host_funcs::{FunctionEntry, FunctionRegistry, default_writer_func};
+use super::locale::LocaleDataProvider;
+use super::messaging::{BusConnection, MessageBus, MessageBusPolicy};
+use super::metrics::{CreationPhase, CreationReport};
+use super::mmio::MmioRegions;
+use super::observer::{SandboxObserver, SandboxObservers};
+use super::quota::{QuotaReservation, QuotaResource};
+use super::secrets::Secrets;
 use super::snapshot::Snapshot;
+use super::status::{SandboxStatus, SandboxStatusHandle};
 use super::uninitialized_evolve::evolve_impl_multi_use;
-use crate::func::host_functions::{HostFunction, register_host_function};
+use crate::func::host_functions::{
+    HostFunction, HostFunctionPolicy, HostFunctions, register_host_function,
+};
+use crate::func::middleware::HostCallMiddleware;
+use crate::func::workspace::Workspace;
 use crate::func::{ParameterTuple, SupportedReturnType};
+#[cfg(gdb)]
+use crate::hypervisor::gdb::monitor::MonitorCommands;
+use crate::hypervisor::regs::CommonSpecialRegisters;
 #[cfg(feature = "build-metadata")]
 use crate::log_build_details;
+use crate::mem::layout::SandboxMemoryLayout;
 use crate::mem::memory_region::{DEFAULT_GUEST_BLOB_MEM_FLAGS, MemoryRegionFlags};
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::ExclusiveSharedMemory;
@@ -38,19 +63,34 @@ use crate::{MultiUseSandbox, Result, new_error};
 #[cfg(any(crashdump, gdb))]
 #[derive(Clone, Debug, Default)]
 pub(crate) struct SandboxRuntimeConfig {
-    #[cfg(crashdump)]
+    /// The path of the guest binary, if it was loaded from a file. Used
+    /// for core dumps and reported to gdb so it can load the symbols.
+    #[cfg(any(crashdump, gdb))]
     pub(crate) binary_path: Option<String>,
     #[cfg(gdb)]
     pub(crate) debug_info: Option<super::config::DebugInfo>,
+    /// The monitor commands registered by the host, answered by the VM
+    /// when gdb sends them
+    #[cfg(gdb)]
+    pub(crate) monitor_commands: MonitorCommands,
     #[cfg(crashdump)]
     pub(crate) guest_core_dump: bool,
+    /// Where core dumps and crash reports are placed, which takes
+    /// priority over the `HYPERLIGHT_CORE_DUMP_DIR` environment variable
+    #[cfg(crashdump)]
+    pub(crate) core_dump_dir: Option<String>,
+    #[cfg(crashdump)]
+    pub(crate) guest_crash_report: bool,
+    /// The configuration of the sandbox, included in crash reports
+    #[cfg(crashdump)]
+    pub(crate) sandbox_config: SandboxConfiguration,
     /// The original entry point address of the loaded guest binary
     /// (load_addr + ELF entry offset). Used for AT_ENTRY in core dumps
     /// so GDB can compute the correct load offset for PIE binaries.
     ///
     /// `None` until resolved from the snapshot's `NextAction::Initialise`
     /// in `set_up_hypervisor_partition`.
-    #[cfg(crashdump)]
+    #[cfg(any(crashdump, gdb))]
     pub(crate) entry_point: Option<u64>,
 }
 
@@ -78,6 +118,26 @@ pub struct UninitializedSandbox {
     // This is needed to convey the stack pointer between the snapshot
     // and the HyperlightVm creation
     pub(crate) stack_top_gva: u64,
+    /// The special registers of the vCPU the snapshot was taken from, set
+    /// on the new vCPU, since the guest already loaded its own GDT and
+    /// IDT. None if the guest has not been initialised yet.
+    pub(crate) sregs: Option<CommonSpecialRegisters>,
+    /// Subscriptions to the debug events, carried over to the VM
+    pub(crate) debug_events: DebugEventSink,
+    /// The MMIO regions emulated by the host, carried over to the VM
+    pub(crate) mmio_regions: MmioRegions,
+    /// The status of the sandbox, carried over to the `MultiUseSandbox`
+    pub(crate) status: SandboxStatusHandle,
+    /// The workspace of the sandbox, carried over to the `MultiUseSandbox`
+    pub(crate) workspace: Option<Workspace>,
+    /// The memory of the sandbox reserved against the process quota,
+    /// carried over to the `MultiUseSandbox`
+    pub(crate) memory_reservation: QuotaReservation,
+    /// The timings of the construction of the sandbox so far, completed
+    /// by `evolve`
+    pub(crate) creation_report: CreationReport,
+    /// The observers of the sandbox, carried over to the `MultiUseSandbox`
+    pub(crate) observers: SandboxObservers,
 }
 
 impl Debug for UninitializedSandbox {
@@ -157,6 +217,13 @@ impl<'a, 'b> GuestEnvironment<'a, 'b> {
             init_data: init_data.map(GuestBlob::from),
         }
     }
+
+    /// Sets the initialization data, which the guest can decode into a
+    /// typed configuration in a `#[guest_init]` function.
+    pub fn with_init_data(mut self, init_data: &'b [u8]) -> Self {
+        self.init_data = Some(GuestBlob::from(init_data));
+        self
+    }
 }
 
 impl<'a> From<GuestBinary<'a>> for GuestEnvironment<'a, '_> {
@@ -175,10 +242,10 @@ impl UninitializedSandbox {
     // `InterruptHandler` on Linux.
     //
     // This is ok for now as this is not a public function
-    fn from_snapshot(
+    pub(super) fn from_snapshot(
         snapshot: Arc<Snapshot>,
         cfg: Option<SandboxConfiguration>,
-        #[cfg(crashdump)] binary_path: Option<String>,
+        #[cfg(any(crashdump, gdb))] binary_path: Option<String>,
     ) -> Result<Self> {
         #[cfg(feature = "build-metadata")]
         log_build_details();
@@ -194,29 +261,48 @@ impl UninitializedSandbox {
             #[cfg(crashdump)]
             let guest_core_dump = sandbox_cfg.get_guest_core_dump();
 
+            #[cfg(crashdump)]
+            let guest_crash_report = sandbox_cfg.get_guest_crash_report();
+
             #[cfg(gdb)]
             let debug_info = sandbox_cfg.get_guest_debug_info();
 
             SandboxRuntimeConfig {
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 binary_path,
                 #[cfg(gdb)]
                 debug_info,
+                #[cfg(gdb)]
+                monitor_commands: MonitorCommands::default(),
                 #[cfg(crashdump)]
                 guest_core_dump,
+                #[cfg(crashdump)]
+                core_dump_dir: None,
+                #[cfg(crashdump)]
+                guest_crash_report,
+                #[cfg(crashdump)]
+                sandbox_config: sandbox_cfg,
                 // entry_point is set later in set_up_hypervisor_partition
                 // once the entrypoint is resolved from the snapshot
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 entry_point: None,
             }
         };
 
-        let mut mem_mgr_wrapper =
-            SandboxMemoryManager::<ExclusiveSharedMemory>::from_snapshot(snapshot.as_ref())?;
+        let memory_reservation =
+            QuotaReservation::reserve(QuotaResource::Memory, snapshot.mem_size())?;
 
-        mem_mgr_wrapper.write_memory_layout()?;
+        let mut creation_report = CreationReport::default();
+        let mem_mgr_wrapper = creation_report.time(CreationPhase::MemoryAllocation, || {
+            let mut mem_mgr_wrapper =
+                SandboxMemoryManager::<ExclusiveSharedMemory>::from_snapshot(snapshot.as_ref())?;
+            mem_mgr_wrapper.write_memory_layout()?;
+            Ok::<_, crate::HyperlightError>(mem_mgr_wrapper)
+        })?;
 
-        let host_funcs = Arc::new(Mutex::new(FunctionRegistry::default()));
+        let mut host_funcs = FunctionRegistry::default();
+        host_funcs.set_fail_every(sandbox_cfg.get_host_call_fail_every());
+        let host_funcs = Arc::new(Mutex::new(host_funcs));
 
         let mut sandbox = Self {
             host_funcs,
@@ -227,10 +313,19 @@ impl UninitializedSandbox {
             rt_cfg,
             load_info: snapshot.load_info(),
             stack_top_gva: snapshot.stack_top_gva(),
+            sregs: snapshot.sregs().copied(),
+            debug_events: DebugEventSink::default(),
+            mmio_regions: MmioRegions::default(),
+            status: SandboxStatusHandle::new(),
+            workspace: None,
+            memory_reservation,
+            creation_report,
+            observers: SandboxObservers::default(),
         };
 
         // If we were passed a writer for host print register it otherwise use the default.
         sandbox.register_print(default_writer_func)?;
+        sandbox.register(HOST_ECHO_FUNCTION, host_echo)?;
 
         crate::debug!("Sandbox created:  {:#?}", sandbox);
 
@@ -252,19 +347,62 @@ impl UninitializedSandbox {
         env: impl Into<GuestEnvironment<'a, 'b>>,
         cfg: Option<SandboxConfiguration>,
     ) -> Result<Self> {
-        let cfg = cfg.unwrap_or_default();
+        let mut cfg = cfg.unwrap_or_default();
         let env = env.into();
-        #[cfg(crashdump)]
+        #[cfg(any(crashdump, gdb))]
         let binary_path = match &env.guest_binary {
             GuestBinary::FilePath(path) => Some(path.clone()),
             GuestBinary::Buffer(_) => None,
         };
-        let snapshot = Snapshot::from_env(env, cfg)?;
-        Self::from_snapshot(
+        let mut creation_report = CreationReport::default();
+        let snapshot = Snapshot::from_env_timed(env, cfg, &mut creation_report)?;
+        // The configuration embedded in the guest binary can raise the
+        // memory sizes, keep the sandbox configuration in line with them
+        let layout = snapshot.layout().info();
+        cfg.set_heap_size(layout.heap_size);
+        cfg.set_scratch_size(layout.scratch_size as usize);
+        let mut sandbox = Self::from_snapshot(
             Arc::new(snapshot),
             Some(cfg),
-            #[cfg(crashdump)]
+            #[cfg(any(crashdump, gdb))]
             binary_path,
+        )?;
+        creation_report.append(std::mem::take(&mut sandbox.creation_report));
+        sandbox.creation_report = creation_report;
+        Ok(sandbox)
+    }
+
+    /// Creates a new uninitialized sandbox from a snapshot saved with
+    /// [`Snapshot::save`] or [`MultiUseSandbox::save_snapshot`], for
+    /// example a pre-warmed sandbox shipped from another process or
+    /// host.
+    ///
+    /// The memory sizes of `cfg` are replaced by those of the snapshot.
+    #[instrument(err(Debug), skip(path, cfg), parent = Span::current())]
+    pub fn from_snapshot_file(
+        path: impl AsRef<Path>,
+        cfg: Option<SandboxConfiguration>,
+    ) -> Result<Self> {
+        Self::from_loaded_snapshot(Snapshot::load(path)?, cfg)
+    }
+
+    /// Creates a new uninitialized sandbox from a snapshot loaded from a
+    /// file, whose memory sizes replace those of `cfg`
+    pub(super) fn from_loaded_snapshot(
+        snapshot: Snapshot,
+        cfg: Option<SandboxConfiguration>,
+    ) -> Result<Self> {
+        let mut cfg = cfg.unwrap_or_default();
+        let layout = snapshot.layout().info();
+        cfg.set_heap_size(layout.heap_size);
+        cfg.set_scratch_size(layout.scratch_size as usize);
+        cfg.set_input_data_size(layout.input_data_size as usize);
+        cfg.set_output_data_size(layout.output_data_size as usize);
+        Self::from_snapshot(
+            Arc::new(snapshot),
+            Some(cfg),
+            #[cfg(any(crashdump, gdb))]
+            None,
         )
     }
 
@@ -286,6 +424,254 @@ impl UninitializedSandbox {
         self.max_guest_log_level = Some(log_level);
     }
 
+    /// Makes the sandbox write an ELF core dump of the guest to `dir`
+    /// whenever the guest crashes, for instance because of a page fault,
+    /// a general protection fault or an abort.
+    ///
+    /// This enables core dumps even if they were disabled with
+    /// [`SandboxConfiguration::set_guest_core_dump`], and `dir` takes
+    /// priority over the `HYPERLIGHT_CORE_DUMP_DIR` environment variable.
+    /// Crash reports, see [`SandboxConfiguration::set_guest_crash_report`],
+    /// are placed in `dir` too.
+    #[cfg(crashdump)]
+    pub fn set_core_dump_on_crash(&mut self, dir: impl Into<String>) {
+        self.rt_cfg.guest_core_dump = true;
+        self.rt_cfg.core_dump_dir = Some(dir.into());
+    }
+
+    /// Registers a command that can be issued from a gdb session attached
+    /// to the sandbox with `monitor <name> [args]`.
+    ///
+    /// `handler` is given the arguments that follow the name, and returns
+    /// the text to print in gdb. It runs while the guest is stopped in the
+    /// debugger. `monitor help` lists the registered commands along with
+    /// the built-in ones, which cannot be replaced.
+    #[cfg(gdb)]
+    pub fn register_gdb_monitor_command(
+        &mut self,
+        name: impl Into<String>,
+        help: impl Into<String>,
+        handler: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Result<()> {
+        self.rt_cfg
+            .monitor_commands
+            .register(name.into(), help.into(), Arc::new(handler))
+    }
+
+    /// Creates the [`Workspace`] of the sandbox, an empty directory where
+    /// its host functions can store up to `quota` bytes of files on
+    /// behalf of the guest.
+    ///
+    /// The directory is removed when the sandbox is dropped. A sandbox has
+    /// at most one workspace.
+    pub fn create_workspace(&mut self, quota: u64) -> Result<Workspace> {
+        if self.workspace.is_some() {
+            return Err(new_error!("The sandbox already has a workspace"));
+        }
+        let workspace = Workspace::new(quota)?;
+        self.workspace = Some(workspace.clone());
+        Ok(workspace)
+    }
+
+    /// Subscribes to the [`DebugEvent`](crate::sandbox::debug_events::DebugEvent)s
+    /// raised while the guest runs, including during [`evolve`](Self::evolve).
+    ///
+    /// The subscription carries over to the [`MultiUseSandbox`] this
+    /// sandbox evolves into.
+    pub fn subscribe_debug_events(&self) -> DebugEvents {
+        self.debug_events.subscribe()
+    }
+
+    /// Registers the guest physical addresses in `range` as an MMIO
+    /// region, whose accesses by the guest are emulated by `handler`
+    /// instead of failing the guest call. See
+    /// [`MmioHandler`](crate::sandbox::mmio::MmioHandler) for the
+    /// arguments `handler` is called with.
+    ///
+    /// The region must lie between the memory of the sandbox and its
+    /// scratch region, and the guest must map it in its page tables
+    /// before accessing it. Memory mapped into the sandbox later at the
+    /// same addresses takes precedence over the region.
+    ///
+    /// Only KVM supports MMIO regions: on other hypervisors,
+    /// [`evolve`](Self::evolve) fails if any region is registered.
+    pub fn register_mmio_region(
+        &mut self,
+        range: std::ops::Range<u64>,
+        handler: impl Fn(u64, usize, bool, &mut [u8]) + Send + Sync + 'static,
+    ) -> Result<()> {
+        let memory_end =
+            (SandboxMemoryLayout::BASE_ADDRESS + self.mgr.layout.get_memory_size()?) as u64;
+        let scratch_base =
+            hyperlight_common::layout::scratch_base_gpa(self.mgr.layout.get_scratch_size());
+        if range.start < memory_end || range.end > scratch_base {
+            return Err(new_error!(
+                "MMIO region {:#x}..{:#x} is not between the sandbox memory, which ends at {:#x}, and the scratch region, which starts at {:#x}",
+                range.start,
+                range.end,
+                memory_end,
+                scratch_base
+            ));
+        }
+        self.mmio_regions.register(range, Arc::new(handler))
+    }
+
+    /// Adds `observer`, which is told about the events in the life of the
+    /// [`MultiUseSandbox`] this sandbox evolves into, starting with its
+    /// creation by [`evolve`](Self::evolve). Observers are told about
+    /// each event in the order they were added.
+    pub fn add_observer(&mut self, observer: Arc<dyn SandboxObserver>) {
+        self.observers.add(observer);
+    }
+
+    /// Returns the status of the sandbox, which is always
+    /// [`SandboxStatus::Created`] until it is evolved.
+    pub fn status(&self) -> SandboxStatus {
+        self.status.get()
+    }
+
+    /// Returns a handle to query the status of this sandbox, and of the
+    /// [`MultiUseSandbox`] it evolves into, from other threads.
+    pub fn status_handle(&self) -> SandboxStatusHandle {
+        self.status.clone()
+    }
+
+    /// Records the host function calls made by the guest from now on,
+    /// including the ones made by the [`MultiUseSandbox`] this sandbox
+    /// evolves into.
+    ///
+    /// The returned recorder gives a [`HostCallFixture`] that can be
+    /// replayed with [`replay_host_calls`](Self::replay_host_calls).
+    pub fn record_host_calls(&mut self) -> Result<HostCallRecorder> {
+        let (mode, recorder) = HostCallMode::record();
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_mode(mode);
+        Ok(recorder)
+    }
+
+    /// Sets whether the guest may call the host function `name`, see
+    /// [`HostFunctionPolicy`]. Host functions are allowed unless a policy
+    /// is set for them.
+    pub fn set_host_function_policy(
+        &mut self,
+        name: &str,
+        policy: HostFunctionPolicy,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_policy(name, policy);
+        Ok(())
+    }
+
+    /// Wraps the host function calls made by the guest in `middleware`,
+    /// inside the layers added before, including the calls made by the
+    /// [`MultiUseSandbox`] this sandbox evolves into. See
+    /// [`HostCallMiddleware`].
+    pub fn add_host_call_middleware(
+        &mut self,
+        middleware: impl HostCallMiddleware + 'static,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .add_middleware(Arc::new(middleware));
+        Ok(())
+    }
+
+    /// Answers the host function calls made by the guest from now on with
+    /// the results recorded in `fixture`, without calling the registered
+    /// host functions.
+    ///
+    /// The calls must be made in the order they were recorded, with the
+    /// same arguments; any other call fails.
+    pub fn replay_host_calls(&mut self, fixture: HostCallFixture) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_mode(HostCallMode::replay(fixture));
+        Ok(())
+    }
+
+    /// Registers the host functions guests call to get timezone and
+    /// locale data, see [`hyperlight_common::locale`], which answer with
+    /// the data of `provider`.
+    ///
+    /// Registering the same provider in many sandboxes shares its cache.
+    pub fn provide_locale_data(&mut self, provider: &LocaleDataProvider) -> Result<()> {
+        let timezones = provider.clone();
+        self.register(
+            HOST_TIMEZONE_FUNCTION,
+            move |name: String| -> Result<Vec<u8>> {
+                Ok(timezones
+                    .timezone(&name)?
+                    .map(|data| data.to_vec())
+                    .unwrap_or_default())
+            },
+        )?;
+        let locales = provider.clone();
+        self.register(
+            HOST_LOCALE_FUNCTION,
+            move |tag: String| -> Result<Vec<u8>> {
+                Ok(locales
+                    .locale(&tag)?
+                    .map(|data| data.to_vec())
+                    .unwrap_or_default())
+            },
+        )
+    }
+
+    /// Registers the host function guests call to get secrets, see
+    /// [`hyperlight_common::secrets`], which answers with the secrets of
+    /// `secrets` their policies allow the guest to read.
+    ///
+    /// The policies are checked against the guest function being called
+    /// when the guest asks for a secret.
+    pub fn provide_secrets(&mut self, secrets: &Secrets) -> Result<()> {
+        let secrets = secrets.clone();
+        let status = self.status.clone();
+        self.register(
+            HOST_SECRET_FUNCTION,
+            move |name: String| -> Result<Vec<u8>> {
+                let function = match status.get() {
+                    SandboxStatus::Running(function) => Some(function),
+                    _ => None,
+                };
+                secrets.read(&name, function.as_deref())
+            },
+        )
+    }
+
+    /// Connects the sandbox to `bus`, registering the host functions
+    /// guests call to publish and receive messages, see
+    /// [`hyperlight_common::messaging`]. `policy` restricts the topics
+    /// the guest can publish and subscribe to.
+    pub fn connect_message_bus(
+        &mut self,
+        bus: &MessageBus,
+        policy: MessageBusPolicy,
+    ) -> Result<()> {
+        let connection = Arc::new(BusConnection {
+            bus: bus.clone(),
+            subscriber: bus.new_subscriber()?,
+            policy,
+        });
+        let publisher = connection.clone();
+        self.register(
+            HOST_PUBLISH_FUNCTION,
+            move |topic: String, message: Vec<u8>| publisher.publish(&topic, &message),
+        )?;
+        let subscriber = connection.clone();
+        self.register(HOST_SUBSCRIBE_FUNCTION, move |topic: String| {
+            subscriber.subscribe(&topic)
+        })?;
+        self.register(HOST_POLL_FUNCTION, move |topic: String| {
+            connection.poll(&topic)
+        })
+    }
+
     /// Registers a host function that the guest can call.
     pub fn register<Args: ParameterTuple, Output: SupportedReturnType>(
         &mut self,
@@ -295,6 +681,62 @@ impl UninitializedSandbox {
         register_host_function(host_func, self, name.as_ref())
     }
 
+    /// Registers a host function that the guest can call, and that can
+    /// call back into the guest with
+    /// [`call_guest`](crate::func::callbacks::call_guest) before it
+    /// returns.
+    ///
+    /// The function runs on a thread of its own, while the thread that
+    /// called the guest function runs the guest functions it calls back.
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::func::callbacks::call_guest;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?;
+    /// sandbox.register_with_callbacks("WaitForEvents", |count: i32| {
+    ///     for event in 0..count {
+    ///         call_guest::<()>("OnEvent", (event,))?;
+    ///     }
+    ///     Ok(count)
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_with_callbacks<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: impl AsRef<str>,
+        host_func: impl Into<HostFunction<Output, Args>>,
+    ) -> Result<()> {
+        let entry = FunctionEntry {
+            function: host_func.into().into(),
+            parameter_types: Args::TYPE,
+            return_type: Output::TYPE,
+            check_signature: false,
+            allows_callbacks: true,
+        };
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .register_host_function(name.as_ref().to_string(), entry)
+    }
+
+    /// Registers all the host functions of `host_functions`, which can
+    /// be registered on several sandboxes.
+    pub fn register_host_functions(&mut self, host_functions: &HostFunctions) -> Result<()> {
+        let mut hfs = self
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        for (name, entry) in host_functions.entries() {
+            hfs.register_host_function(name.clone(), entry.clone())?;
+        }
+        Ok(())
+    }
+
     /// Registers the special "HostPrint" function for guest printing.
     ///
     /// This overrides the default behavior of writing to stdout.
@@ -363,6 +805,23 @@ mod tests {
         assert_eq!(res, buffer.to_vec());
     }
 
+    #[test]
+    fn test_guest_init_receives_init_data() {
+        let binary_path = simple_guest_as_string().unwrap();
+        let config = b"log_level=debug";
+        let guest_env =
+            GuestEnvironment::from(GuestBinary::FilePath(binary_path)).with_init_data(config);
+
+        let uninitialized_sandbox = UninitializedSandbox::new(guest_env, None).unwrap();
+        let mut sandbox: MultiUseSandbox = uninitialized_sandbox.evolve().unwrap();
+
+        let res = sandbox
+            .call::<Vec<u8>>("GetInitData", ())
+            .expect("Failed to call GetInitData");
+
+        assert_eq!(res, config.to_vec());
+    }
+
     #[test]
     fn test_new_sandbox() {
         // Guest Binary exists at path
@@ -523,6 +982,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_host_call_middleware() {
+        use std::sync::Mutex;
+
+        use crate::func::Next;
+
+        let mut usbox = UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_as_string().expect("Guest Binary Missing")),
+            None,
+        )
+        .unwrap();
+        usbox.register("add", |a: i32, b: i32| Ok(a + b)).unwrap();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let audit = seen.clone();
+        // The outer layer sees the calls the inner layers rewrite or veto
+        usbox
+            .add_host_call_middleware(
+                move |name: &str, args: Vec<ParameterValue>, next: Next<'_>| {
+                    audit.lock().unwrap().push(name.to_string());
+                    next.run(name, args)
+                },
+            )
+            .unwrap();
+        usbox
+            .add_host_call_middleware(|name: &str, args: Vec<ParameterValue>, next: Next<'_>| {
+                match name {
+                    "forbidden" => Err(new_error!("{} is not allowed", name)),
+                    "double" => next.run("add", [args.clone(), args].concat()),
+                    _ => next.run(name, args),
+                }
+            })
+            .unwrap();
+        let sandbox: MultiUseSandbox = usbox.evolve().unwrap();
+        let host_funcs = sandbox.host_funcs.try_lock().unwrap();
+
+        let res = host_funcs
+            .call_host_function("add", vec![ParameterValue::Int(1), ParameterValue::Int(2)])
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(3));
+        let res = host_funcs
+            .call_host_function("double", vec![ParameterValue::Int(4)])
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(8));
+        let res = host_funcs.call_host_function("forbidden", vec![]);
+        assert!(res.unwrap_err().to_string().contains("not allowed"));
+
+        assert_eq!(*seen.lock().unwrap(), ["add", "double", "forbidden"]);
+    }
+
     #[test]
     fn test_host_print() {
         // writer as a FnMut closure mutating a captured variable and then trying to access the captured variable
@@ -1071,7 +1580,7 @@ mod tests {
             let sandbox1 = UninitializedSandbox::from_snapshot(
                 snapshot.clone(),
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create first sandbox from snapshot");
@@ -1080,7 +1589,7 @@ mod tests {
             let sandbox2 = UninitializedSandbox::from_snapshot(
                 snapshot.clone(),
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create second sandbox from snapshot");
@@ -1105,7 +1614,7 @@ mod tests {
             let sandbox = UninitializedSandbox::from_snapshot(
                 snapshot,
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create sandbox from snapshot with custom heap");
@@ -1128,7 +1637,7 @@ mod tests {
             let sandbox = UninitializedSandbox::from_snapshot(
                 snapshot,
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create sandbox from snapshot with custom stack");
@@ -1152,7 +1661,7 @@ mod tests {
             let sandbox = UninitializedSandbox::from_snapshot(
                 snapshot,
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create sandbox from snapshot with custom buffers");
@@ -1179,21 +1688,21 @@ mod tests {
             let sandbox1 = UninitializedSandbox::from_snapshot(
                 snapshot.clone(),
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create sandbox1 from fully customized snapshot");
             let sandbox2 = UninitializedSandbox::from_snapshot(
                 snapshot.clone(),
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create sandbox2 from fully customized snapshot");
             let sandbox3 = UninitializedSandbox::from_snapshot(
                 snapshot.clone(),
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create sandbox3 from fully customized snapshot");
@@ -1215,7 +1724,7 @@ mod tests {
             let sandbox = UninitializedSandbox::from_snapshot(
                 snapshot,
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 None,
             )
             .expect("Failed to create sandbox from buffer-based snapshot");
@@ -1234,7 +1743,7 @@ mod tests {
             let mut sandbox = UninitializedSandbox::from_snapshot(
                 snapshot,
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create sandbox from snapshot");
@@ -1276,7 +1785,7 @@ mod tests {
             let sandbox = UninitializedSandbox::from_snapshot(
                 snapshot,
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create sandbox from snapshot with init data");
@@ -1294,7 +1803,7 @@ mod tests {
             let orig_sandbox = UninitializedSandbox::from_snapshot(
                 orig_snapshot,
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create orig_sandbox");
@@ -1307,7 +1816,7 @@ mod tests {
             let new_sandbox = UninitializedSandbox::from_snapshot(
                 new_snapshot,
                 None,
-                #[cfg(crashdump)]
+                #[cfg(any(crashdump, gdb))]
                 Some(binary_path.clone()),
             )
             .expect("Failed to create new_sandbox");
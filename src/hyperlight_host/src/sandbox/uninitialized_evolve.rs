@@ -14,12 +14,15 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 #[cfg(gdb)]
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 
 use rand::RngExt;
 use tracing::{Span, instrument};
 
 use super::SandboxConfiguration;
+use super::metrics::{CreationPhase, CreationReport};
+use super::quota::{QuotaReservation, QuotaResource};
 #[cfg(any(crashdump, gdb))]
 use super::uninitialized::SandboxRuntimeConfig;
 use crate::hypervisor::hyperlight_vm::{HyperlightVm, HyperlightVmError};
@@ -27,8 +30,6 @@ use crate::mem::exe::LoadInfo;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::ptr::RawPtr;
 use crate::mem::shared_mem::GuestSharedMemory;
-#[cfg(gdb)]
-use crate::sandbox::config::DebugInfo;
 #[cfg(feature = "mem_profile")]
 use crate::sandbox::trace::MemTraceInfo;
 #[cfg(target_os = "linux")]
@@ -37,7 +38,9 @@ use crate::{MultiUseSandbox, Result, UninitializedSandbox};
 
 #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
 pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<MultiUseSandbox> {
+    let vcpu_reservation = QuotaReservation::reserve(QuotaResource::VcpuThreads, 1)?;
     let (mut hshm, gshm) = u_sbox.mgr.build()?;
+    let mut creation_report = u_sbox.creation_report;
     let mut vm = set_up_hypervisor_partition(
         gshm,
         &u_sbox.config,
@@ -45,7 +48,17 @@ pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<Mult
         #[cfg(any(crashdump, gdb))]
         u_sbox.rt_cfg,
         u_sbox.load_info,
+        &mut creation_report,
     )?;
+    if let Some(sregs) = &u_sbox.sregs {
+        vm.reset_vcpu(hshm.layout.get_pt_base_gpa(), sregs)
+            .map_err(HyperlightVmError::Restore)?;
+    }
+    vm.set_debug_events(u_sbox.debug_events);
+    vm.set_mmio_regions(u_sbox.mmio_regions)
+        .map_err(HyperlightVmError::Create)?;
+    let id = super::snapshot::SANDBOX_CONFIGURATION_COUNTER.fetch_add(1, Ordering::Relaxed);
+    u_sbox.observers.created(id);
 
     let seed = {
         let mut rng = rand::rng();
@@ -64,25 +77,39 @@ pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<Mult
     #[cfg(target_os = "linux")]
     setup_signal_handlers(&u_sbox.config)?;
 
-    vm.initialise(
-        peb_addr,
-        seed,
-        page_size,
-        &mut hshm,
-        &u_sbox.host_funcs,
-        u_sbox.max_guest_log_level,
-        #[cfg(gdb)]
-        dbg_mem_access_hdl,
-    )
-    .map_err(HyperlightVmError::Initialize)?;
+    creation_report
+        .time(CreationPhase::GuestInit, || {
+            vm.initialise(
+                peb_addr,
+                seed,
+                page_size,
+                &mut hshm,
+                &u_sbox.host_funcs,
+                u_sbox.max_guest_log_level,
+                #[cfg(gdb)]
+                dbg_mem_access_hdl,
+            )
+        })
+        .map_err(|e| {
+            u_sbox.observers.dropped(id);
+            HyperlightVmError::Initialize(e)
+        })?;
 
     #[cfg(gdb)]
     let dbg_mem_wrapper = Arc::new(Mutex::new(hshm.clone()));
 
     Ok(MultiUseSandbox::from_uninit(
+        id,
         u_sbox.host_funcs,
         hshm,
         vm,
+        u_sbox.status,
+        u_sbox.workspace,
+        vec![u_sbox.memory_reservation, vcpu_reservation],
+        creation_report,
+        u_sbox.config,
+        u_sbox.max_guest_log_level,
+        u_sbox.observers,
         #[cfg(gdb)]
         dbg_mem_wrapper,
     ))
@@ -94,18 +121,22 @@ pub(crate) fn set_up_hypervisor_partition(
     stack_top_gva: u64,
     #[cfg(any(crashdump, gdb))] rt_cfg: SandboxRuntimeConfig,
     _load_info: LoadInfo,
+    creation_report: &mut CreationReport,
 ) -> Result<HyperlightVm> {
     // Create gdb thread if gdb is enabled and the configuration is provided
     #[cfg(gdb)]
-    let gdb_conn = if let Some(DebugInfo { port }) = rt_cfg.debug_info {
+    let gdb_conn = if let Some(debug_info) = rt_cfg.debug_info {
         use crate::hypervisor::gdb::create_gdb_thread;
 
-        let gdb_conn = create_gdb_thread(port);
+        let gdb_conn = create_gdb_thread(debug_info.into());
 
         // in case the gdb thread creation fails, we still want to continue
         // without gdb
         match gdb_conn {
-            Ok(gdb_conn) => Some(gdb_conn),
+            Ok((gdb_conn, port)) => {
+                log::info!("Waiting for a gdb client to connect on port {}", port);
+                Some(gdb_conn)
+            }
             Err(e) => {
                 log::error!("Could not create gdb connection: {:#}", e);
 
@@ -119,11 +150,12 @@ pub(crate) fn set_up_hypervisor_partition(
     #[cfg(feature = "mem_profile")]
     let trace_info = MemTraceInfo::new(_load_info.info)?;
 
-    // Store the original entry point address in the runtime config for core dumps.
+    // Store the original entry point address in the runtime config for core dumps
+    // and for the gdb auxiliary vector.
     // This is needed because `entrypoint` transitions from `Initialise(addr)` to
     // `Call(dispatch_addr)` after guest initialisation, losing the original value
     // that GDB needs to compute the PIE binary's load offset.
-    #[cfg(crashdump)]
+    #[cfg(any(crashdump, gdb))]
     let rt_cfg = {
         let mut rt_cfg = rt_cfg;
         if let crate::sandbox::snapshot::NextAction::Initialise(addr) = mgr.entrypoint {
@@ -141,10 +173,11 @@ pub(crate) fn set_up_hypervisor_partition(
         config,
         #[cfg(gdb)]
         gdb_conn,
-        #[cfg(crashdump)]
+        #[cfg(any(crashdump, gdb))]
         rt_cfg,
         #[cfg(feature = "mem_profile")]
         trace_info,
+        creation_report,
     )
     .map_err(HyperlightVmError::Create)?)
 }
@@ -0,0 +1,135 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::{Display, Formatter};
+
+use crate::hypervisor::virtual_machine::{HypervisorType, get_available_hypervisor};
+
+/// When this environment variable is set to `1`, tests written with
+/// [`hyperlight_test!`](crate::hyperlight_test) fail instead of being
+/// skipped if no hypervisor is available, so that a misconfigured CI
+/// machine does not silently skip them
+pub const REQUIRE_HYPERVISOR_ENV: &str = "HYPERLIGHT_TEST_REQUIRE_HYPERVISOR";
+
+/// A hypervisor backend sandboxes can run on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Backend {
+    /// The Linux Kernel-based Virtual Machine
+    Kvm,
+    /// The Microsoft Hypervisor, through `/dev/mshv`
+    Mshv,
+    /// The Windows Hypervisor Platform
+    Whp,
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Kvm => write!(f, "KVM"),
+            Backend::Mshv => write!(f, "MSHV"),
+            Backend::Whp => write!(f, "WHP"),
+        }
+    }
+}
+
+impl From<HypervisorType> for Backend {
+    fn from(hypervisor: HypervisorType) -> Self {
+        match hypervisor {
+            #[cfg(kvm)]
+            HypervisorType::Kvm => Backend::Kvm,
+            #[cfg(mshv3)]
+            HypervisorType::Mshv => Backend::Mshv,
+            #[cfg(target_os = "windows")]
+            HypervisorType::Whp => Backend::Whp,
+        }
+    }
+}
+
+/// Returns the backend sandboxes run on in the current environment, or
+/// `None` if no hypervisor this build of Hyperlight supports is usable,
+/// in which case creating a sandbox fails
+pub fn available_backend() -> Option<Backend> {
+    get_available_hypervisor().map(Backend::from)
+}
+
+/// Returns whether the test `test_name` can run, which is when a
+/// hypervisor is available. Otherwise the test is reported as skipped on
+/// stderr and `false` is returned, unless [`REQUIRE_HYPERVISOR_ENV`] is
+/// set, in which case this panics.
+#[allow(clippy::panic)]
+pub fn hypervisor_or_skip(test_name: &str) -> bool {
+    if available_backend().is_some() {
+        return true;
+    }
+    if std::env::var(REQUIRE_HYPERVISOR_ENV).is_ok_and(|v| v == "1") {
+        panic!(
+            "{}: no hypervisor is available and {} is set",
+            test_name, REQUIRE_HYPERVISOR_ENV
+        );
+    }
+    eprintln!("skipping {}: no hypervisor is available", test_name);
+    false
+}
+
+/// Declares a test that needs a hypervisor to run, which is skipped when
+/// there is none, see [`hypervisor_or_skip`](crate::test_support::hypervisor_or_skip).
+///
+/// The test is written like a regular test function, and can have other
+/// attributes such as `#[should_panic]` or `#[ignore]`.
+///
+/// ```
+/// use hyperlight_host::hyperlight_test;
+///
+/// hyperlight_test! {
+///     fn guest_runs() {
+///         // Create and call a sandbox here
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! hyperlight_test {
+    ($(#[$attr:meta])* fn $name:ident() $body:block) => {
+        $(#[$attr])*
+        #[test]
+        fn $name() {
+            if !$crate::test_support::hypervisor_or_skip(stringify!($name)) {
+                return;
+            }
+            $body
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{available_backend, hypervisor_or_skip};
+    use crate::is_hypervisor_present;
+
+    #[test]
+    fn backend_matches_hypervisor_presence() {
+        assert_eq!(available_backend().is_some(), is_hypervisor_present());
+        if is_hypervisor_present() {
+            assert!(hypervisor_or_skip("backend_matches_hypervisor_presence"));
+        }
+    }
+
+    crate::hyperlight_test! {
+        fn hyperlight_test_runs_with_a_hypervisor() {
+            assert!(is_hypervisor_present());
+        }
+    }
+}
@@ -14,15 +14,39 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 #![allow(clippy::disallowed_macros)]
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Barrier};
 use std::thread;
 use std::time::Duration;
 
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterType, ParameterValue, ReturnType, ReturnValue,
+};
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::interrupt::FIRST_HOST_INTERRUPT_VECTOR;
 use hyperlight_common::log_level::GuestLogFilter;
-use hyperlight_host::sandbox::SandboxConfiguration;
-use hyperlight_host::{HyperlightError, MultiUseSandbox};
+use hyperlight_host::func::HostFunctions;
+use hyperlight_host::func::callbacks::{MAX_CALLBACK_DEPTH, call_guest};
+use hyperlight_host::func::host_io;
+use hyperlight_host::sandbox::fuel::FuelDecision;
+use hyperlight_host::sandbox::host_call_recording::{
+    GuestCallDivergence, GuestCallRecord, HostCallFixture,
+};
+use hyperlight_host::sandbox::locale::{LocaleDataDirs, LocaleDataProvider};
+use hyperlight_host::sandbox::messaging::{MessageBus, MessageBusPolicy};
+use hyperlight_host::sandbox::migration::SandboxMigration;
+use hyperlight_host::sandbox::observer::SandboxObserver;
+use hyperlight_host::sandbox::quota::{SandboxQuota, SandboxQuotaResource};
+use hyperlight_host::sandbox::secrets::{SecretPolicy, Secrets};
+#[cfg(target_os = "linux")]
+use hyperlight_host::sandbox::shared_region::SharedRegion;
+use hyperlight_host::sandbox::snapshot_chain::SnapshotChain;
+use hyperlight_host::sandbox::status::SandboxStatus;
+use hyperlight_host::sandbox::{
+    CpuSet, CreationPhase, FaultInjection, GuestCheckpointPolicy, MsrPolicy, SandboxConfiguration,
+    SharedSandbox,
+};
+use hyperlight_host::{HyperlightError, HypervisorKind, MultiUseSandbox, UninitializedSandbox};
 use hyperlight_testing::simplelogger::{LOGGER, SimpleLogger};
 use serial_test::serial;
 use tracing_core::LevelFilter;
@@ -76,6 +100,44 @@ fn interrupt_host_call() {
     });
 }
 
+// Host functions using the cancellation-aware helpers stop waiting as soon as the call is interrupted
+#[test]
+fn interrupt_host_call_using_host_io() {
+    with_rust_uninit_sandbox(|mut usbox| {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+
+        let spin = move || {
+            barrier2.wait();
+            let res = host_io::sleep(Duration::from_secs(60));
+            assert!(host_io::is_cancelled());
+            res
+        };
+
+        usbox.register("Spin", spin).unwrap();
+
+        let mut sandbox: MultiUseSandbox = usbox.evolve().unwrap();
+        let interrupt_handle = sandbox.interrupt_handle();
+
+        let thread = thread::spawn(move || {
+            barrier.wait(); // wait for the host function to be entered
+            interrupt_handle.kill();
+        });
+
+        let start = std::time::Instant::now();
+        let result = sandbox.call::<i32>("CallHostSpin", ()).unwrap_err();
+        assert!(
+            matches!(&result, HyperlightError::ExecutionCanceledByHost()),
+            "unexpected error: {result:?}"
+        );
+        assert!(start.elapsed() < Duration::from_secs(30));
+        // Outside of a host function call nothing is cancelled
+        assert!(!host_io::is_cancelled());
+
+        thread.join().unwrap();
+    });
+}
+
 /// Makes sure a running guest call can be interrupted by the host
 #[test]
 fn interrupt_in_progress_guest_call() {
@@ -344,6 +406,252 @@ fn interrupt_custom_signal_no_and_retry_delay() {
     });
 }
 
+/// Makes sure a guest call is stopped once it has used up its CPU time budget
+#[test]
+#[cfg(target_os = "linux")]
+fn guest_cpu_time_budget() {
+    let budget = Duration::from_millis(100);
+    let mut config = SandboxConfiguration::default();
+    config.set_guest_cpu_time_budget(Some(budget));
+
+    with_rust_sandbox_cfg(config, |mut sbox1| {
+        let snapshot = sbox1.snapshot().unwrap();
+        sbox1.call::<String>("Echo", "hello".to_string()).unwrap();
+
+        let res = sbox1.call::<i32>("Spin", ()).unwrap_err();
+        assert!(
+            matches!(&res, HyperlightError::ExecutionBudgetExceeded(b) if *b == budget),
+            "unexpected error: {res:?}"
+        );
+        assert!(sbox1.poisoned());
+
+        // The budget applies to each call separately
+        sbox1.restore(snapshot).unwrap();
+        sbox1.call::<String>("Echo", "hello".to_string()).unwrap();
+    });
+}
+
+/// Makes sure the instructions and cycles of the guest are counted when asked to
+#[test]
+#[cfg(target_os = "linux")]
+fn guest_perf_counters() {
+    let mut config = SandboxConfiguration::default();
+    config.set_guest_perf_counters(true);
+
+    with_rust_sandbox_cfg(config, |mut sbox1| {
+        assert_eq!(sbox1.last_call_metrics(), None);
+        if let Err(e) = sbox1.call::<String>("Echo", "hello".to_string()) {
+            // The host does not give access to its performance counters
+            assert!(
+                e.to_string().contains("Failed to count the guest events"),
+                "unexpected error: {e:?}"
+            );
+            return;
+        }
+        if hyperlight_host::test_support::available_backend()
+            != Some(hyperlight_host::test_support::Backend::Kvm)
+        {
+            assert_eq!(sbox1.last_call_metrics(), None);
+            return;
+        }
+        let metrics = sbox1.last_call_metrics().unwrap();
+        assert!(metrics.instructions_retired > 0, "{metrics:?}");
+        assert!(metrics.cycles > 0, "{metrics:?}");
+    });
+}
+
+/// Guests are paused once they used up their fuel, and refuelled or
+/// stopped by the host
+#[test]
+#[cfg(target_os = "linux")]
+fn guest_fuel() {
+    with_rust_sandbox(|mut sbox1| {
+        if hyperlight_host::test_support::available_backend()
+            != Some(hyperlight_host::test_support::Backend::Kvm)
+        {
+            assert!(sbox1.set_fuel(Some(1_000_000)).is_err());
+            return;
+        }
+        let snapshot = sbox1.snapshot().unwrap();
+        sbox1.set_fuel(Some(1_000_000_000)).unwrap();
+        if let Err(e) = sbox1.call::<i32>("AddToStatic", 1) {
+            // The host does not give access to its performance counters
+            assert!(
+                e.to_string().contains("Failed to count the guest events"),
+                "unexpected error: {e:?}"
+            );
+            return;
+        }
+        assert!(sbox1.fuel().unwrap() < 1_000_000_000);
+
+        let refuels = Arc::new(AtomicUsize::new(0));
+        let counter = refuels.clone();
+        sbox1.on_fuel_exhausted(move || {
+            if counter.fetch_add(1, Ordering::Relaxed) < 3 {
+                FuelDecision::Refuel(100_000)
+            } else {
+                FuelDecision::Abort
+            }
+        });
+        sbox1.set_fuel(Some(100_000)).unwrap();
+        let res = sbox1.call::<()>("Spin", ());
+        assert!(
+            matches!(res, Err(HyperlightError::FuelExhausted)),
+            "unexpected error: {res:?}"
+        );
+        assert_eq!(refuels.load(Ordering::Relaxed), 4);
+        assert!(sbox1.poisoned());
+
+        // Without fuel left, calls are stopped before the guest runs
+        sbox1.restore(snapshot.clone()).unwrap();
+        assert_eq!(sbox1.fuel(), Some(0));
+        sbox1.on_fuel_exhausted(|| FuelDecision::Abort);
+        assert!(matches!(
+            sbox1.call::<i32>("GetStatic", ()),
+            Err(HyperlightError::FuelExhausted)
+        ));
+        sbox1.restore(snapshot).unwrap();
+        sbox1.set_fuel(None).unwrap();
+        assert_eq!(sbox1.call::<i32>("GetStatic", ()).unwrap(), 0);
+    });
+}
+
+/// Makes sure the exits of the vCPU are counted by reason
+#[test]
+fn vm_exit_stats() {
+    with_rust_sandbox(|mut sbox1| {
+        let before = sbox1.vm_exit_stats();
+        sbox1
+            .call::<i32>("PrintOutput", "hello".to_string())
+            .unwrap();
+        let after = sbox1.vm_exit_stats();
+
+        // Every call ends with the guest halting, and printing calls the host
+        assert_eq!(after.halt_exits, before.halt_exits + 1);
+        assert!(after.outb_exits > before.outb_exits, "{after:?}");
+        assert!(after.total() > before.total());
+    });
+}
+
+/// Makes sure every phase of the construction of a sandbox is timed
+#[test]
+fn creation_report() {
+    with_rust_sandbox(|sbox| {
+        let report = sbox.creation_report();
+        for phase in [
+            CreationPhase::ElfLoad,
+            CreationPhase::MemoryAllocation,
+            CreationPhase::VmCreation,
+            CreationPhase::VcpuSetup,
+            CreationPhase::GuestInit,
+        ] {
+            assert!(report.duration(phase).is_some(), "{phase} is missing");
+        }
+        assert!(report.total() >= report.duration(CreationPhase::GuestInit).unwrap());
+        assert!(hyperlight_host::available_backends().contains(&report.hypervisor().unwrap()));
+    });
+}
+
+/// Makes sure the guest accesses to an MMIO region are emulated by its handler
+#[test]
+#[cfg(target_os = "linux")]
+fn mmio_region() {
+    let reg = 0x1_0000_0008;
+    let value = Arc::new(std::sync::Mutex::new(0u32));
+    let accesses = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let mut uninit = new_rust_uninit_sandbox();
+    let (device, log) = (value.clone(), accesses.clone());
+    uninit
+        .register_mmio_region(
+            0x1_0000_0000..0x1_0000_1000,
+            move |addr, size, is_write, data| {
+                log.lock().unwrap().push((addr, size, is_write));
+                let mut device = device.lock().unwrap();
+                if is_write {
+                    *device = u32::from_le_bytes(data.try_into().unwrap()) + 1;
+                } else {
+                    data.copy_from_slice(&device.to_le_bytes());
+                }
+            },
+        )
+        .unwrap();
+    if hyperlight_host::test_support::available_backend()
+        != Some(hyperlight_host::test_support::Backend::Kvm)
+    {
+        assert!(uninit.evolve().is_err());
+        return;
+    }
+    let mut sbox = uninit.evolve().unwrap();
+
+    let res = sbox.call::<u32>("MmioWriteRead", (reg, 41u32)).unwrap();
+    assert_eq!(res, 42);
+    assert_eq!(*value.lock().unwrap(), 42);
+    assert_eq!(
+        *accesses.lock().unwrap(),
+        vec![(reg, 4, true), (reg, 4, false)]
+    );
+
+    // Accesses outside of the region still fail the call
+    let res = sbox.call::<u32>("MmioWriteRead", (0x1_0000_1000u64, 1u32));
+    assert!(res.is_err());
+}
+
+/// Makes sure the guest reads its TSC with the configured offset
+#[test]
+fn guest_tsc_offset() {
+    let offset = 1 << 62;
+    let mut cfg = SandboxConfiguration::default();
+    cfg.set_guest_tsc_offset(Some(offset));
+    with_rust_sandbox_cfg(cfg, |mut sbox| {
+        let tsc = sbox.call::<u64>("ReadTsc", ()).unwrap();
+        assert!(tsc >= offset as u64);
+    });
+
+    // Only KVM scales the TSC
+    let mut cfg = SandboxConfiguration::default();
+    cfg.set_guest_tsc_frequency(Some(1_000_000));
+    if hyperlight_host::test_support::available_backend()
+        != Some(hyperlight_host::test_support::Backend::Kvm)
+    {
+        let uninit = hyperlight_host::UninitializedSandbox::new(
+            hyperlight_host::GuestBinary::FilePath(
+                hyperlight_testing::simple_guest_as_string().unwrap(),
+            ),
+            Some(cfg),
+        )
+        .unwrap();
+        assert!(uninit.evolve().is_err());
+    }
+}
+
+/// Makes sure the interrupts injected by the host reach the handler the guest installed
+#[test]
+fn inject_interrupt() {
+    let mut sbox = new_rust_sandbox();
+    assert!(matches!(
+        sbox.inject_interrupt(FIRST_HOST_INTERRUPT_VECTOR - 1),
+        Err(HyperlightError::InvalidInterruptVector(_))
+    ));
+
+    // Injected from another thread while the guest function runs
+    let vector = FIRST_HOST_INTERRUPT_VECTOR + 1;
+    let interrupt_handle = sbox.interrupt_handle();
+    let thread = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        interrupt_handle.inject_interrupt(vector);
+    });
+    let res = sbox.call::<u32>("WaitForInterrupt", vector as u32).unwrap();
+    assert_eq!(res, vector as u32);
+    thread.join().unwrap();
+
+    // Injected before the call, and delivered once the guest enables interrupts
+    let vector = FIRST_HOST_INTERRUPT_VECTOR + 2;
+    sbox.inject_interrupt(vector).unwrap();
+    let res = sbox.call::<u32>("WaitForInterrupt", vector as u32).unwrap();
+    assert_eq!(res, vector as u32);
+}
+
 #[test]
 fn interrupt_spamming_host_call() {
     with_rust_uninit_sandbox(|mut uninit| {
@@ -391,6 +699,18 @@ fn print_four_args_c_guest() {
     });
 }
 
+#[test]
+fn static_init_c_guest() {
+    with_c_sandbox(|mut sbox1| {
+        // The constructor ran and the thread-local variable starts from
+        // its initial value
+        let res = sbox1.call::<i32>("GetStaticInitState", ());
+        assert!(matches!(&res, Ok(42)), "unexpected result: {res:?}");
+        let res = sbox1.call::<i32>("GetStaticInitState", ());
+        assert!(matches!(&res, Ok(43)), "unexpected result: {res:?}");
+    });
+}
+
 // Checks that guest can abort with a specific code.
 #[test]
 fn guest_abort() {
@@ -419,6 +739,32 @@ fn guest_abort_with_context1() {
     });
 }
 
+/// The crash report of a guest that aborted describes what it was doing
+#[test]
+fn guest_abort_crash_report() {
+    with_rust_sandbox(|mut sbox| {
+        sbox.call::<String>("Echo", "No crash".to_string()).unwrap();
+        assert!(sbox.crash_report().is_none());
+
+        let res = sbox
+            .call::<()>("GuestAbortWithMessage", (25_i32, "Oh no".to_string()))
+            .unwrap_err();
+        assert!(
+            matches!(&res, HyperlightError::GuestAborted(25, _)),
+            "unexpected error: {res:?}"
+        );
+        let report = sbox.crash_report().expect("report of the abort").clone();
+        assert_eq!(report.code, 25);
+        assert_eq!(report.message, "Oh no");
+        assert!(report.exception.is_none());
+        let registers = report.registers.expect("registers of the aborted guest");
+        assert_ne!(registers.rsp, 0);
+        assert!(!report.stack.is_empty());
+        assert_eq!(report.frames.first(), Some(&registers.rip));
+        assert!(report.to_string().contains("Oh no"));
+    });
+}
+
 #[test]
 fn guest_abort_with_context2() {
     with_all_sandboxes(|mut sbox1| {
@@ -494,6 +840,15 @@ fn guest_panic() {
             matches!(&res, HyperlightError::GuestAborted(code, context) if *code == ErrorCode::UnknownError as u8 && context.contains("\nError... error...")),
             "unexpected error: {res:?}"
         );
+
+        // The panic handler sends the message and where the guest
+        // panicked apart from each other
+        let report = sbox1.crash_report().expect("report of the abort");
+        let panic = report.panic.clone().expect("panic of the guest");
+        assert_eq!(panic.message, "Error... error...");
+        let location = panic.location.expect("location of the panic");
+        assert!(location.file.ends_with("main.rs"), "{location}");
+        assert!(location.line > 0);
     });
 }
 
@@ -563,6 +918,79 @@ fn guest_malloc_abort() {
     });
 }
 
+// checks that the allocation failures injected by the host are deterministic
+#[test]
+fn guest_alloc_fault_injection() {
+    let mut cfg = SandboxConfiguration::default();
+    cfg.set_fault_injection(FaultInjection {
+        guest_alloc_byte_budget: 0x4000,
+        ..Default::default()
+    });
+    with_rust_sandbox_cfg(cfg, |mut sbox| {
+        let snapshot = sbox.snapshot().unwrap();
+
+        for _ in 0..2 {
+            // a vector that fits in the heap but not in the budget
+            let err = sbox.call::<i32>("CallMalloc", 0x8000).unwrap_err();
+            assert!(
+                matches!(
+                    &err,
+                    HyperlightError::GuestAborted(code, msg) if *code == ErrorCode::UnknownError as u8 && msg.contains("memory allocation of ")
+                ),
+                "unexpected error: {err:?}"
+            );
+
+            // restoring the snapshot also restores the allocation count
+            sbox.restore(snapshot.clone()).unwrap();
+            assert_eq!(sbox.call::<i32>("CallMalloc", 0x100).unwrap(), 0x100);
+            sbox.restore(snapshot.clone()).unwrap();
+        }
+    });
+
+    let mut cfg = SandboxConfiguration::default();
+    cfg.set_fault_injection(FaultInjection {
+        guest_alloc_fail_every: 1,
+        ..Default::default()
+    });
+    with_rust_sandbox_cfg(cfg, |mut sbox| {
+        let err = sbox.call::<i32>("CallMalloc", 0x10).unwrap_err();
+        assert!(
+            matches!(&err, HyperlightError::GuestAborted(..)),
+            "unexpected error: {err:?}"
+        );
+    });
+}
+
+// checks that the host function call failures injected by the host reach the guest
+#[test]
+fn host_call_fault_injection() {
+    let mut cfg = SandboxConfiguration::default();
+    cfg.set_fault_injection(FaultInjection {
+        host_call_fail_every: 2,
+        ..Default::default()
+    });
+    with_rust_sandbox_cfg(cfg, |mut sbox| {
+        let snapshot = sbox.snapshot().unwrap();
+
+        for call in 1..=4 {
+            let res = sbox.call::<i32>("PrintOutput", "hello\n".to_string());
+            if call % 2 == 0 {
+                assert!(
+                    matches!(
+                        &res,
+                        Err(HyperlightError::GuestAborted(_, msg)) if msg.contains("Injected failure of host function call")
+                    ),
+                    "unexpected result: {res:?}"
+                );
+                // the guest panicked, but the host keeps counting the calls
+                sbox.restore(snapshot.clone()).unwrap();
+            } else {
+                assert_eq!(res.unwrap(), 6);
+            }
+        }
+    });
+}
+
 /// Test that executing an OUT instruction with an invalid port causes an error and poisons the sandbox.
 #[test]
 fn guest_outb_with_invalid_port_poisons_sandbox() {
@@ -732,6 +1160,59 @@ fn recursive_stack_allocate_overflow() {
     });
 }
 
+/// Touching the guard pages below a limited stack is reported as a
+/// stack overflow and poisons the sandbox
+#[test]
+fn stack_guard_pages() {
+    let mut config = SandboxConfiguration::default();
+    config.set_max_stack_size(0x10000);
+
+    with_rust_sandbox_cfg(config, |mut sbox| {
+        sbox.call::<i32>("StackOverflow", 2_i32).unwrap();
+
+        let res = sbox.call::<()>("InfiniteRecursion", ()).unwrap_err();
+        assert!(
+            matches!(&res, HyperlightError::GuestStackOverflow(_)),
+            "unexpected error: {res:?}"
+        );
+        assert!(sbox.poisoned());
+    });
+}
+
+/// The guard pages around the heap are taken out of the heap size
+#[test]
+fn heap_guard_pages() {
+    let mut config = SandboxConfiguration::default();
+    config.set_heap_guard_pages(2);
+
+    with_rust_sandbox_cfg(config, |mut sbox| {
+        assert_eq!(sbox.call::<i32>("CallMalloc", 0x1000).unwrap(), 0x1000);
+        let err = sbox
+            .call::<i32>("CallMalloc", SandboxConfiguration::DEFAULT_HEAP_SIZE as i32)
+            .unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                HyperlightError::GuestAborted(code, msg) if *code == ErrorCode::UnknownError as u8 && msg.contains("memory allocation of ")
+            ),
+            "unexpected error: {err:?}"
+        );
+    });
+
+    let mut config = SandboxConfiguration::default();
+    config.set_heap_size(0x4000);
+    config.set_heap_guard_pages(2);
+    assert!(
+        UninitializedSandbox::new(
+            hyperlight_host::GuestBinary::FilePath(
+                hyperlight_testing::simple_guest_as_string().unwrap(),
+            ),
+            Some(config),
+        )
+        .is_err()
+    );
+}
+
 // Check that log messages are emitted correctly from the guest
 // This test is ignored as it sets a logger and therefore maybe impacted by other tests running concurrently
 // or it may impact other tests.
@@ -908,6 +1389,101 @@ fn test_if_guest_is_able_to_get_string_return_values_from_host() {
     });
 }
 
+/// Tests that the host function calls recorded in one run can be replayed
+/// in another without calling the host functions
+#[test]
+fn host_call_record_and_replay() {
+    let fixture_file = tempfile::NamedTempFile::new().unwrap();
+
+    with_c_uninit_sandbox(|mut sbox| {
+        sbox.register("HostAddStrings", |a: String| {
+            a + ", string added by Host Function"
+        })
+        .unwrap();
+        let recorder = sbox.record_host_calls().unwrap();
+        let mut sbox = sbox.evolve().unwrap();
+        let res = sbox
+            .call::<String>("GuestRetrievesStringValue", ())
+            .unwrap();
+        assert_eq!(res, "Guest Function, string added by Host Function");
+
+        let fixture = recorder.fixture();
+        assert_eq!(fixture.calls().len(), 1);
+        fixture.save(fixture_file.path()).unwrap();
+    });
+
+    with_c_uninit_sandbox(|mut sbox| {
+        sbox.register("HostAddStrings", |_: String| -> String {
+            panic!("the host function must not be called when replaying")
+        })
+        .unwrap();
+        let fixture = HostCallFixture::load(fixture_file.path()).unwrap();
+        sbox.replay_host_calls(fixture).unwrap();
+        let mut sbox = sbox.evolve().unwrap();
+        let res = sbox
+            .call::<String>("GuestRetrievesStringValue", ())
+            .unwrap();
+        assert_eq!(res, "Guest Function, string added by Host Function");
+
+        // The fixture holds a single call
+        assert!(
+            sbox.call::<String>("GuestRetrievesStringValue", ())
+                .is_err()
+        );
+    });
+}
+
+/// Tests that a recorded guest call is reproduced in a fresh sandbox, and
+/// that the replay reports where the guest departs from the record
+#[test]
+fn guest_call_record_and_replay() {
+    let record_file = tempfile::NamedTempFile::new().unwrap();
+
+    with_rust_uninit_sandbox(|mut sbox| {
+        sbox.register("HostAdd", |a: i32, b: i32| a + b).unwrap();
+        let mut sbox = sbox.evolve().unwrap();
+        let (res, record) = sbox.record_call(
+            "Add",
+            ReturnType::Int,
+            vec![ParameterValue::Int(2), ParameterValue::Int(3)],
+        );
+        assert_eq!(res.unwrap(), ReturnValue::Int(5));
+        assert_eq!(record.host_calls.calls().len(), 1);
+        record.save(record_file.path()).unwrap();
+    });
+
+    let record = GuestCallRecord::load(record_file.path()).unwrap();
+    with_rust_uninit_sandbox(|mut sbox| {
+        sbox.register("HostAdd", |_: i32, _: i32| -> i32 {
+            panic!("the host function must not be called when replaying")
+        })
+        .unwrap();
+        let mut sbox = sbox.evolve().unwrap();
+        assert_eq!(sbox.replay_call(&record), None);
+
+        // The guest returns what the host function answered
+        let mut altered = record.clone();
+        let mut host_calls = altered.host_calls.calls().to_vec();
+        host_calls[0].result = Ok(ReturnValue::Int(6));
+        altered.host_calls = HostCallFixture::new(host_calls);
+        assert_eq!(
+            sbox.replay_call(&altered),
+            Some(GuestCallDivergence::Result {
+                expected: Ok(ReturnValue::Int(5)),
+                actual: Ok(ReturnValue::Int(6)),
+            })
+        );
+
+        // The guest calls the host function with different arguments
+        let mut altered = record.clone();
+        altered.args = vec![ParameterValue::Int(1), ParameterValue::Int(3)];
+        assert!(matches!(
+            sbox.replay_call(&altered),
+            Some(GuestCallDivergence::HostCall { index: 0, .. })
+        ));
+    });
+}
+
 /// Test that validates interrupt behavior with random kill timing under concurrent load
 /// Uses a pool of 100 sandboxes, 100 threads, and 500 iterations per thread.
 /// Randomly decides to kill some calls at random times during execution.
@@ -1768,3 +2344,869 @@ fn interrupt_cancel_delete_race() {
         handle.join().unwrap();
     }
 }
+
+/// Every guest provides the self-test and benchmark functions
+#[test]
+fn run_diagnostics() {
+    with_all_sandboxes(|mut sandbox| {
+        let report = sandbox.run_diagnostics(10).unwrap();
+        assert!(report.self_test.contains("host call round trip: ok"));
+        assert!(report.guest_call > Duration::ZERO);
+        assert_eq!(report.guest_timings.iterations, 10);
+        assert!(report.guest_alloc_cycles() > 0);
+        assert!(report.host_call_cycles() > 0);
+    });
+}
+
+/// The functions registered by a Rust guest can be listed with their
+/// signatures
+#[test]
+fn guest_functions() {
+    with_rust_sandbox(|mut sandbox| {
+        let functions = sandbox.guest_functions().unwrap();
+        let echo = functions.iter().find(|f| f.name == "Echo").unwrap();
+        assert_eq!(echo.parameter_types, [ParameterType::String]);
+        assert_eq!(echo.return_type, ReturnType::String);
+        assert!(echo.matches::<(String,), String>());
+        assert!(!echo.matches::<(i32,), String>());
+        assert!(functions.iter().any(|f| f.name == "AddToStatic"));
+        assert!(!functions.iter().any(|f| f.name.starts_with("__hl_")));
+        assert!(functions.windows(2).all(|w| w[0].name < w[1].name));
+    });
+}
+
+/// Payloads larger than the input and output buffers are streamed
+/// through the guest in chunks
+#[test]
+fn stream_copy() {
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    with_rust_sandbox(|mut sandbox| {
+        let payload: Vec<u8> = (0..1 << 20).map(|i: u32| (i % 251) as u8).collect();
+        let input = sandbox
+            .open_input_stream(std::io::Cursor::new(payload.clone()))
+            .unwrap();
+        let buffer = SharedBuffer::default();
+        let output = sandbox.open_output_stream(buffer.clone()).unwrap();
+
+        let copied: u64 = sandbox
+            .call("StreamCopy", (input.as_u32(), output.as_u32()))
+            .unwrap();
+        assert_eq!(copied, payload.len() as u64);
+        sandbox.close_stream(input).unwrap();
+        sandbox.close_stream(output).unwrap();
+        assert!(*buffer.0.lock().unwrap() == payload);
+
+        // The guest fails to use a closed stream
+        assert!(
+            sandbox
+                .call::<u64>("StreamCopy", (input.as_u32(), output.as_u32()))
+                .is_err()
+        );
+    });
+}
+
+/// A buffer lent to the guest is read and written in place, and is not
+/// mapped again by restoring a snapshot taken while it was lent
+#[test]
+#[cfg(target_os = "linux")]
+fn borrowed_buffer() {
+    use std::alloc::{Layout, alloc_zeroed, dealloc};
+
+    use hyperlight_host::sandbox::BorrowedGuestBuffer;
+
+    let layout = Layout::from_size_align(4 << 20, 0x1000).unwrap();
+    // Safety: the layout is not zero sized, and the buffer is only used
+    // until it is deallocated at the end of the test
+    let ptr = unsafe { alloc_zeroed(layout) };
+    assert!(!ptr.is_null());
+    let buffer = unsafe { std::slice::from_raw_parts_mut(ptr, layout.size()) };
+    let len = buffer.len() as u64;
+
+    with_rust_sandbox(|mut sandbox| {
+        buffer.fill(1);
+        let sum: u64 = sandbox
+            .with_borrowed_buffer(
+                BorrowedGuestBuffer::read_only(buffer).unwrap(),
+                |sandbox, address| sandbox.call("SumBorrowedBuffer", (address, len)),
+            )
+            .unwrap();
+        assert_eq!(sum, len);
+
+        let snapshot = sandbox
+            .with_borrowed_buffer(
+                BorrowedGuestBuffer::read_write(buffer).unwrap(),
+                |sandbox, address| {
+                    sandbox.call::<()>("FillBorrowedBuffer", (address, len, 3))?;
+                    sandbox.snapshot()
+                },
+            )
+            .unwrap();
+        assert!(buffer.iter().all(|&b| b == 3));
+
+        // Writing to a read-only buffer fails
+        let err = sandbox
+            .with_borrowed_buffer(
+                BorrowedGuestBuffer::read_only(buffer).unwrap(),
+                |sandbox, address| sandbox.call::<()>("FillBorrowedBuffer", (address, len, 4)),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(err, HyperlightError::MemoryAccessViolation(..)),
+            "{err:?}"
+        );
+        assert!(buffer.iter().all(|&b| b == 3));
+
+        // The buffer was given back, so the guest cannot reach it anymore
+        sandbox.restore(snapshot.clone()).unwrap();
+        assert!(
+            sandbox
+                .call::<u64>("SumBorrowedBuffer", (0x4_0000_0000u64, len))
+                .is_err()
+        );
+
+        // The buffer is given back even when the closure panics
+        sandbox.restore(snapshot).unwrap();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            sandbox.with_borrowed_buffer(
+                BorrowedGuestBuffer::read_only(buffer).unwrap(),
+                |_, _| -> hyperlight_host::Result<()> { panic!("the closure panicked") },
+            )
+        }));
+        assert!(res.is_err());
+        assert!(
+            sandbox
+                .call::<u64>("SumBorrowedBuffer", (0x4_0000_0000u64, len))
+                .is_err()
+        );
+    });
+
+    // Safety: the buffer was allocated with this layout
+    unsafe { dealloc(ptr, layout) };
+}
+
+/// Host functions registered with callbacks call back into the guest
+/// function waiting for them, which can call them again, up to a limit
+#[test]
+fn host_function_callbacks() {
+    with_rust_uninit_sandbox(|mut usbox| {
+        usbox
+            .register_with_callbacks("HostEmitEvents", |count: i32| {
+                if count <= 0 {
+                    return Ok(0);
+                }
+                call_guest::<i32>("AddToStatic", (count,))?;
+                let rest = call_guest::<i32>("CallHostWithCallbacks", (count - 1,))?;
+                Ok(count + rest)
+            })
+            .unwrap();
+        usbox
+            .register("HostAdd", |a: i32, b: i32| {
+                call_guest::<i32>("AddToStatic", (a + b,))
+            })
+            .unwrap();
+        let mut sandbox: MultiUseSandbox = usbox.evolve().unwrap();
+
+        let res: i32 = sandbox.call("CallHostWithCallbacks", 3).unwrap();
+        assert_eq!(res, 6);
+        let counter: i32 = sandbox.call("GetStatic", ()).unwrap();
+        assert_eq!(counter, 6);
+
+        // The guest can still be called once the callbacks are done
+        let res: String = sandbox.call("Echo", "after".to_string()).unwrap();
+        assert_eq!(res, "after");
+
+        // Callbacks nesting deeper than the limit fail the call
+        let depth = MAX_CALLBACK_DEPTH as i32 + 1;
+        assert!(sandbox.call::<i32>("CallHostWithCallbacks", depth).is_err());
+
+        // Host functions registered without callbacks cannot call back
+        assert!(sandbox.call::<i32>("Add", (1, 2)).is_err());
+    });
+}
+
+/// Queued calls run back to back in the guest, each with its own result
+#[test]
+fn call_queue() {
+    with_rust_sandbox(|mut sandbox| {
+        assert!(sandbox.run_queued_calls().unwrap().is_empty());
+
+        sandbox.queue_call::<i32>("AddToStatic", 5);
+        sandbox.queue_call::<String>("Echo", "queued".to_string());
+        sandbox.queue_call::<i32>("FunctionThatDoesNotExist", ());
+        sandbox.queue_call::<i32>("AddToStatic", 10);
+        assert_eq!(sandbox.queued_calls(), 4);
+
+        let results = sandbox.run_queued_calls().unwrap();
+        assert_eq!(sandbox.queued_calls(), 0);
+        assert_eq!(results.len(), 4);
+        assert!(matches!(results[0], Ok(ReturnValue::Int(5))));
+        assert!(matches!(&results[1], Ok(ReturnValue::String(s)) if s == "queued"));
+        assert!(matches!(
+            results[2],
+            Err(HyperlightError::GuestError(
+                ErrorCode::GuestFunctionNotFound,
+                _
+            ))
+        ));
+        assert!(matches!(results[3], Ok(ReturnValue::Int(15))));
+
+        // The sandbox is called as usual after the queue ran
+        let counter: i32 = sandbox.call("GetStatic", ()).unwrap();
+        assert_eq!(counter, 15);
+    });
+}
+
+/// The accesses to an MSR no hypervisor implements follow the MSR policy
+#[test]
+fn msr_policy() {
+    const UNKNOWN_MSR: u32 = 0xdead_0000;
+
+    let mut cfg = SandboxConfiguration::default();
+    cfg.set_msr_policy(MsrPolicy::Deny);
+    with_rust_sandbox_cfg(cfg, |mut sbox| {
+        let err = sbox.call::<u64>("ReadMsr", UNKNOWN_MSR).unwrap_err();
+        assert!(
+            matches!(err, HyperlightError::MsrAccessDenied(UNKNOWN_MSR, false)),
+            "unexpected error {:?}",
+            err
+        );
+        assert!(sbox.poisoned());
+    });
+
+    cfg.set_msr_policy(MsrPolicy::EmulateAsZero);
+    with_rust_sandbox_cfg(cfg, |mut sbox| {
+        sbox.call::<()>("WriteMsr", (UNKNOWN_MSR, 0x1234_u64))
+            .unwrap();
+        assert_eq!(sbox.call::<u64>("ReadMsr", UNKNOWN_MSR).unwrap(), 0);
+    });
+}
+
+/// A call that fails after a checkpoint can be rolled back to it
+#[test]
+fn guest_checkpoints() {
+    let mut cfg = SandboxConfiguration::default();
+    cfg.set_guest_checkpoint_policy(GuestCheckpointPolicy::Always);
+    with_rust_sandbox_cfg(cfg, |mut sbox| {
+        assert!(sbox.last_checkpoint().is_none());
+        sbox.call::<i32>("AddToStaticCheckpointAndFail", 5)
+            .unwrap_err();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 15);
+
+        let checkpoint = sbox.last_checkpoint().unwrap();
+        assert_eq!(checkpoint.name(), "added");
+        sbox.restore(checkpoint.snapshot()).unwrap();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+    });
+
+    // Checkpoints are ignored by default
+    with_rust_sandbox(|mut sbox| {
+        sbox.call::<i32>("AddToStaticCheckpointAndFail", 5)
+            .unwrap_err();
+        assert!(sbox.last_checkpoint().is_none());
+    });
+}
+
+/// A sandbox is restored to any snapshot of a chain
+#[test]
+fn snapshot_chain() {
+    with_rust_sandbox(|mut sbox| {
+        let mut chain = SnapshotChain::new(sbox.snapshot().unwrap());
+        let mut ids = vec![chain.base_id()];
+        for _ in 0..3 {
+            sbox.call::<i32>("AddToStatic", 1).unwrap();
+            ids.push(chain.push(&sbox.snapshot().unwrap()).unwrap());
+        }
+        // Only the pages that changed are stored
+        let pages = sbox.snapshot().unwrap().layout_info().memory_size as usize / 4096;
+        assert!(chain.stored_pages() < 3 * pages);
+
+        for (value, id) in ids.iter().enumerate().rev() {
+            sbox.restore_to(&chain, *id).unwrap();
+            assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), value as i32);
+        }
+
+        chain.truncate(ids[1]).unwrap();
+        sbox.restore_to(&chain, ids[1]).unwrap();
+        assert!(sbox.restore_to(&chain, ids[2]).is_err());
+    });
+
+    // Snapshots of other sandboxes cannot be pushed
+    let mut chain = SnapshotChain::new(new_rust_sandbox().snapshot().unwrap());
+    let res = chain.push(&new_rust_sandbox().snapshot().unwrap());
+    assert!(matches!(res, Err(HyperlightError::SnapshotSandboxMismatch)));
+}
+
+/// Sandboxes are created from snapshots saved to files
+#[test]
+fn snapshot_file() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    with_rust_sandbox(|mut sbox| {
+        sbox.call::<i32>("AddToStatic", 5).unwrap();
+        sbox.save_snapshot(file.path()).unwrap();
+    });
+
+    let mut sbox = UninitializedSandbox::from_snapshot_file(file.path(), None)
+        .unwrap()
+        .evolve()
+        .unwrap();
+    assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+    sbox.call::<i32>("AddToStatic", 1).unwrap();
+    assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 6);
+
+    // Corrupted snapshots are not loaded
+    let mut bytes = std::fs::read(file.path()).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    std::fs::write(file.path(), &bytes).unwrap();
+    assert!(UninitializedSandbox::from_snapshot_file(file.path(), None).is_err());
+    std::fs::write(file.path(), b"not a snapshot").unwrap();
+    assert!(UninitializedSandbox::from_snapshot_file(file.path(), None).is_err());
+}
+
+/// Sandboxes are carried on from checkpoints, with the host functions
+/// they had when the checkpoint was taken
+#[test]
+fn checkpoint_file() {
+    let host_functions = HostFunctions::new().register_host_fn(
+        "HostAdd",
+        |a: i32, b: i32| -> hyperlight_host::Result<i32> { Ok(a + b) },
+    );
+    let file = tempfile::NamedTempFile::new().unwrap();
+    with_rust_uninit_sandbox(|mut usbox| {
+        usbox.register_host_functions(&host_functions).unwrap();
+        let mut sbox = usbox.evolve().unwrap();
+        sbox.call::<i32>("AddToStatic", 5).unwrap();
+        sbox.checkpoint(file.path()).unwrap();
+    });
+
+    let mut sbox = MultiUseSandbox::from_checkpoint(file.path(), &host_functions, None).unwrap();
+    assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+    assert_eq!(sbox.call::<i32>("Add", (1, 2)).unwrap(), 3);
+
+    // The host functions must be registered again, with the same
+    // signatures
+    let res = MultiUseSandbox::from_checkpoint(file.path(), &HostFunctions::new(), None);
+    assert!(res.is_err());
+    let changed = HostFunctions::new().register_host_fn(
+        "HostAdd",
+        |a: i64, b: i64| -> hyperlight_host::Result<i64> { Ok(a + b) },
+    );
+    assert!(MultiUseSandbox::from_checkpoint(file.path(), &changed, None).is_err());
+
+    // Snapshot files are not checkpoints
+    sbox.save_snapshot(file.path()).unwrap();
+    assert!(MultiUseSandbox::from_checkpoint(file.path(), &host_functions, None).is_err());
+}
+
+/// Running sandboxes are migrated by sending the pages they change between
+/// rounds, and carry on in their state on the other end
+#[test]
+fn live_migration() {
+    let mut transport = Vec::new();
+    with_rust_sandbox(|mut sbox| {
+        let mut migration = SandboxMigration::new(&mut transport).unwrap();
+        sbox.call::<i32>("AddToStatic", 5).unwrap();
+        let first = migration.precopy(&mut sbox).unwrap();
+        sbox.call::<i32>("AddToStatic", 1).unwrap();
+        let second = migration.precopy(&mut sbox).unwrap();
+        assert!(second > 0 && second < first, "{second} of {first} pages");
+        sbox.call::<i32>("AddToStatic", 1).unwrap();
+        migration.finish(sbox).unwrap();
+    });
+
+    let mut sbox =
+        MultiUseSandbox::receive_migration(transport.as_slice(), &HostFunctions::new(), None)
+            .unwrap();
+    assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 7);
+
+    // Migrations cut short are not received
+    let truncated = &transport[..transport.len() - 1];
+    assert!(MultiUseSandbox::receive_migration(truncated, &HostFunctions::new(), None).is_err());
+}
+
+/// Reloading the guest of a sandbox starts it afresh and keeps its host
+/// functions
+#[test]
+fn reload_guest() {
+    with_rust_uninit_sandbox(|mut usbox| {
+        usbox.register("HostAdd", |a: i32, b: i32| a + b).unwrap();
+        let mut sbox = usbox.evolve().unwrap();
+        sbox.call::<i32>("AddToStatic", 5).unwrap();
+        let snapshot = sbox.snapshot().unwrap();
+
+        let path = hyperlight_testing::simple_guest_as_string().unwrap();
+        sbox.reload_guest(hyperlight_host::GuestBinary::FilePath(path))
+            .unwrap();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 0);
+        assert_eq!(sbox.call::<i32>("Add", (1, 2)).unwrap(), 3);
+        assert!(matches!(
+            sbox.restore(snapshot),
+            Err(HyperlightError::SnapshotSandboxMismatch)
+        ));
+
+        // A guest that fails to load leaves the previous one in place
+        sbox.call::<i32>("AddToStatic", 1).unwrap();
+        let res = sbox.reload_guest(hyperlight_host::GuestBinary::FilePath(
+            "does-not-exist".into(),
+        ));
+        assert!(res.is_err());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 1);
+    });
+}
+
+/// The resources used by a sandbox accumulate across calls, and calls
+/// fail once the sandbox used up its quota
+#[test]
+fn usage_quota() {
+    with_rust_uninit_sandbox(|mut usbox| {
+        usbox.register("HostAdd", |a: i32, b: i32| a + b).unwrap();
+        let mut sbox = usbox.evolve().unwrap();
+        let snapshot = sbox.snapshot().unwrap();
+
+        let before = sbox.usage();
+        assert_eq!(sbox.call::<i32>("Add", (1, 2)).unwrap(), 3);
+        let after = sbox.usage();
+        assert_eq!(after.host_calls, before.host_calls + 1);
+        assert!(after.vm_exits > before.vm_exits);
+        assert!(after.cpu_time > before.cpu_time);
+        assert!(after.bytes_transferred > before.bytes_transferred);
+
+        let mut quota = SandboxQuota::new();
+        quota.set_max_host_calls(Some(after.host_calls + 1));
+        sbox.set_usage_quota(quota);
+
+        // The call that uses up the quota is stopped
+        let res = sbox.call::<i32>("Add", (1, 2));
+        assert!(
+            matches!(
+                res,
+                Err(HyperlightError::SandboxQuotaExceeded(
+                    SandboxQuotaResource::HostCalls
+                ))
+            ),
+            "{:?}",
+            res
+        );
+        assert!(sbox.poisoned());
+
+        // Restoring the sandbox does not give back its quota
+        sbox.restore(snapshot).unwrap();
+        let res = sbox.call::<i32>("GetStatic", ());
+        assert!(matches!(
+            res,
+            Err(HyperlightError::SandboxQuotaExceeded(
+                SandboxQuotaResource::HostCalls
+            ))
+        ));
+        assert!(!sbox.poisoned());
+
+        sbox.set_usage_quota(SandboxQuota::new());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 0);
+    });
+}
+
+#[test]
+fn restore_points() {
+    with_rust_sandbox(|mut sbox| {
+        sbox.call::<i32>("AddToStatic", 5).unwrap();
+        sbox.create_restore_point("five").unwrap();
+        sbox.call::<i32>("AddToStatic", 10).unwrap();
+        sbox.create_restore_point("fifteen").unwrap();
+
+        // A failed call in the middle of a transaction is rolled back,
+        // along with the calls made before it
+        sbox.call::<i32>("AddToStatic", 1).unwrap();
+        let res = sbox.call::<()>("guest_panic", "transaction failed".to_string());
+        assert!(res.is_err());
+        assert!(sbox.poisoned());
+        sbox.rollback_to("fifteen").unwrap();
+        assert!(!sbox.poisoned());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 15);
+
+        // Restore points are kept after rolling back to them
+        sbox.rollback_to("five").unwrap();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+        sbox.rollback_to("fifteen").unwrap();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 15);
+
+        let mut labels: Vec<_> = sbox.restore_points().collect();
+        labels.sort();
+        assert_eq!(labels, ["fifteen", "five"]);
+        assert!(sbox.remove_restore_point("five"));
+        assert!(!sbox.remove_restore_point("five"));
+        assert!(sbox.rollback_to("five").is_err());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 15);
+    });
+}
+
+/// Sandboxes cloned from a live sandbox start in its state, with its host
+/// functions, and then diverge from it
+#[test]
+fn clone_sandbox() {
+    with_rust_uninit_sandbox(|mut usbox| {
+        usbox.register("HostAdd", |a: i32, b: i32| a + b).unwrap();
+        let mut parent = usbox.evolve().unwrap();
+        parent.call::<i32>("AddToStatic", 5).unwrap();
+
+        let mut child = parent.clone_sandbox().unwrap();
+        let mut sibling = parent.clone_sandbox().unwrap();
+        assert_eq!(child.call::<i32>("GetStatic", ()).unwrap(), 5);
+        child.call::<i32>("AddToStatic", 1).unwrap();
+        assert_eq!(child.call::<i32>("GetStatic", ()).unwrap(), 6);
+        assert_eq!(sibling.call::<i32>("GetStatic", ()).unwrap(), 5);
+        assert_eq!(parent.call::<i32>("GetStatic", ()).unwrap(), 5);
+        assert_eq!(child.call::<i32>("Add", (2, 3)).unwrap(), 5);
+
+        // Snapshots of the child only restore into the child
+        let snapshot = child.snapshot().unwrap();
+        assert!(matches!(
+            parent.restore(snapshot.clone()),
+            Err(HyperlightError::SnapshotSandboxMismatch)
+        ));
+        child.call::<i32>("AddToStatic", 1).unwrap();
+        child.restore(snapshot).unwrap();
+        assert_eq!(child.call::<i32>("GetStatic", ()).unwrap(), 6);
+    });
+}
+
+/// Sandboxes backed by huge pages run, snapshot, restore and clone like the
+/// other sandboxes
+#[test]
+fn host_huge_pages() {
+    let mut config = SandboxConfiguration::default();
+    config.set_host_huge_pages(true);
+
+    with_rust_sandbox_cfg(config, |mut sbox| {
+        let snapshot = sbox.snapshot().unwrap();
+        sbox.call::<i32>("AddToStatic", 5).unwrap();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+        let mut child = sbox.clone_sandbox().unwrap();
+        assert_eq!(child.call::<i32>("GetStatic", ()).unwrap(), 5);
+
+        sbox.restore(snapshot).unwrap();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 0);
+    });
+}
+
+/// A region shared by two sandboxes carries what one guest writes to the
+/// other, and to the host, and is not rolled back by restores
+#[test]
+#[cfg(target_os = "linux")]
+fn shared_region() {
+    use hyperlight_host::mem::memory_region::MemoryRegionFlags;
+
+    let region = SharedRegion::new("pipeline", 0x1000).unwrap();
+    let (producer_base, consumer_base) = (0x1_0000_0000u64, 0x2_0000_0000u64);
+    let mut producer = new_rust_sandbox();
+    let mut consumer = new_rust_sandbox();
+    producer
+        .map_shared_region(
+            &region,
+            producer_base,
+            MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
+        )
+        .unwrap();
+    consumer
+        .map_shared_region(&region, consumer_base, MemoryRegionFlags::READ)
+        .unwrap();
+    let snapshot = producer.snapshot().unwrap();
+
+    assert!(
+        producer
+            .call::<bool>("WriteMappedBuffer", (producer_base, 1u64))
+            .unwrap()
+    );
+    let read = consumer
+        .call::<Vec<u8>>("ReadMappedBuffer", (consumer_base, 1u64, true))
+        .unwrap();
+    assert_eq!(read, [0x42]);
+    let mut buf = [0; 1];
+    region.read(0, &mut buf).unwrap();
+    assert_eq!(buf, [0x42]);
+
+    region.write(1, b"hi").unwrap();
+    producer.restore(snapshot).unwrap();
+    let read = producer
+        .call::<Vec<u8>>("ReadMappedBuffer", (producer_base, 3u64, true))
+        .unwrap();
+    assert_eq!(read, b"\x42hi");
+
+    let err = consumer
+        .call::<bool>("WriteMappedBuffer", (consumer_base, 1u64))
+        .unwrap_err();
+    assert!(
+        matches!(err, HyperlightError::MemoryAccessViolation(addr, ..) if addr == consumer_base)
+    );
+}
+
+/// Guests get the timezones of the host on demand
+#[test]
+fn locale_data() {
+    let zoneinfo = tempfile::tempdir().unwrap();
+    std::fs::create_dir(zoneinfo.path().join("Europe")).unwrap();
+    std::fs::write(zoneinfo.path().join("Europe/Paris"), b"TZif2").unwrap();
+    let provider = LocaleDataProvider::new(LocaleDataDirs::new().timezones(zoneinfo.path()));
+
+    with_rust_uninit_sandbox(|mut usbox| {
+        usbox.provide_locale_data(&provider).unwrap();
+        let mut sbox = usbox.evolve().unwrap();
+        let data: Vec<u8> = sbox
+            .call("GetTimezoneData", "Europe/Paris".to_string())
+            .unwrap();
+        assert_eq!(data, b"TZif2");
+        let data: Vec<u8> = sbox
+            .call("GetTimezoneData", "Mars/Olympus_Mons".to_string())
+            .unwrap();
+        assert!(data.is_empty());
+    });
+
+    // Without a provider, the guest is told the data is not available
+    with_rust_sandbox(|mut sbox| {
+        let res = sbox.call::<Vec<u8>>("GetTimezoneData", "Europe/Paris".to_string());
+        assert!(res.is_err());
+    });
+}
+
+/// Guests read the secrets the host gives them, as their policies allow
+#[test]
+fn secrets() {
+    let secrets = Secrets::new();
+    secrets
+        .insert("token", b"hunter2".to_vec(), SecretPolicy::new().one_time())
+        .unwrap();
+    secrets
+        .insert(
+            "key",
+            b"key".to_vec(),
+            SecretPolicy::new().allow_function("Connect"),
+        )
+        .unwrap();
+
+    with_rust_uninit_sandbox(|mut usbox| {
+        usbox.provide_secrets(&secrets).unwrap();
+        let mut sbox = usbox.evolve().unwrap();
+        let token: Vec<u8> = sbox.call("GetSecret", "token".to_string()).unwrap();
+        assert_eq!(token, b"hunter2");
+        // The token could only be read once
+        let res = sbox.call::<Vec<u8>>("GetSecret", "token".to_string());
+        assert!(res.is_err());
+        // The key can only be read by another guest function
+        let res = sbox.call::<Vec<u8>>("GetSecret", "key".to_string());
+        assert!(res.is_err());
+        assert!(!sbox.poisoned());
+    });
+}
+
+/// Sandboxes exchange messages through a message bus, as their policies allow
+#[test]
+fn message_bus() {
+    let bus = MessageBus::new();
+    let host = bus.subscribe("config").unwrap();
+
+    let connect = |policy| {
+        let mut usbox = hyperlight_host::UninitializedSandbox::new(
+            hyperlight_host::GuestBinary::FilePath(
+                hyperlight_testing::simple_guest_as_string().unwrap(),
+            ),
+            None,
+        )
+        .unwrap();
+        usbox.connect_message_bus(&bus, policy).unwrap();
+        usbox.evolve().unwrap()
+    };
+    let mut publisher = connect(MessageBusPolicy::new().allow_publish("config"));
+    let mut subscriber = connect(MessageBusPolicy::new().allow_subscribe("config"));
+
+    subscriber
+        .call::<()>("Subscribe", "config".to_string())
+        .unwrap();
+    publisher
+        .call::<()>("Publish", ("config".to_string(), b"v2".to_vec()))
+        .unwrap();
+    let message: Vec<u8> = subscriber.call("Poll", "config".to_string()).unwrap();
+    assert_eq!(message, b"v2");
+    let message: Vec<u8> = subscriber.call("Poll", "config".to_string()).unwrap();
+    assert!(message.is_empty());
+    assert_eq!(host.try_recv().unwrap(), Some(b"v2".to_vec()));
+
+    // The policies restrict the topics
+    let res = subscriber.call::<()>("Publish", ("config".to_string(), b"v3".to_vec()));
+    assert!(res.is_err());
+    let res = publisher.call::<()>("Subscribe", "config".to_string());
+    assert!(res.is_err());
+}
+
+/// Sandboxes run on the requested hypervisor, and fail to evolve with a
+/// clear error if it is not available
+#[test]
+fn explicit_hypervisor() {
+    let backends = hyperlight_host::available_backends();
+    for kind in [
+        HypervisorKind::Kvm,
+        HypervisorKind::Mshv,
+        HypervisorKind::Whp,
+    ] {
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_hypervisor(kind);
+        let usbox = hyperlight_host::UninitializedSandbox::new(
+            hyperlight_host::GuestBinary::FilePath(
+                hyperlight_testing::simple_guest_as_string().unwrap(),
+            ),
+            Some(cfg),
+        )
+        .unwrap();
+        match usbox.evolve() {
+            Ok(mut sbox) => {
+                assert!(backends.contains(&kind));
+                let res: String = sbox.call("Echo", "hello".to_string()).unwrap();
+                assert_eq!(res, "hello");
+            }
+            Err(err) => {
+                assert!(!backends.contains(&kind));
+                assert!(err.to_string().contains("was requested"), "{}", err);
+            }
+        }
+    }
+}
+
+/// Guests run on a vCPU pinned to the configured host CPUs
+#[test]
+fn vcpu_affinity() {
+    let mut cfg = SandboxConfiguration::default();
+    // Every CPU, since the test may be restricted to any of them
+    cfg.set_vcpu_affinity(Some(CpuSet::from_cpus(0..CpuSet::MAX_CPUS).unwrap()));
+    with_rust_sandbox_cfg(cfg, |mut sbox| {
+        let res: String = sbox.call("Echo", "pinned".to_string()).unwrap();
+        assert_eq!(res, "pinned");
+    });
+}
+
+#[test]
+fn shared_sandbox() {
+    with_rust_uninit_sandbox(|mut usbox| {
+        let entered = Arc::new(Barrier::new(2));
+        let release = Arc::new(Barrier::new(2));
+        let (host_entered, host_release) = (entered.clone(), release.clone());
+        usbox
+            .register("HostAdd", move |a: i32, b: i32| {
+                if a < 0 {
+                    host_entered.wait();
+                    host_release.wait();
+                }
+                a + b
+            })
+            .unwrap();
+        let sbox = Arc::new(SharedSandbox::new(usbox.evolve().unwrap()));
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let sbox = sbox.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        sbox.call::<i32>("AddToStatic", 1).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in threads {
+            handle.join().unwrap();
+        }
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 40);
+
+        // A call made while another one runs fails without waiting
+        let busy = sbox.clone();
+        let call = thread::spawn(move || busy.call::<i32>("Add", (-1, 2)));
+        entered.wait();
+        assert!(matches!(sbox.status(), SandboxStatus::Running(_)));
+        assert!(sbox.try_call::<i32>("GetStatic", ()).is_err());
+        release.wait();
+        assert_eq!(call.join().unwrap().unwrap(), 1);
+        assert_eq!(sbox.try_call::<i32>("GetStatic", ()).unwrap(), 40);
+
+        let snapshot = sbox.with_sandbox(|sandbox| sandbox.snapshot()).unwrap();
+        sbox.call::<i32>("AddToStatic", 2).unwrap();
+        sbox.with_sandbox(|sandbox| sandbox.restore(snapshot))
+            .unwrap();
+        let mut sandbox = Arc::try_unwrap(sbox).unwrap().into_inner();
+        assert_eq!(sandbox.call::<i32>("GetStatic", ()).unwrap(), 40);
+    });
+}
+
+#[test]
+fn sandbox_observer() {
+    #[derive(Default)]
+    struct Events(std::sync::Mutex<Vec<String>>);
+
+    impl SandboxObserver for Events {
+        fn created(&self, _: u64) {
+            self.0.lock().unwrap().push("created".to_string());
+        }
+
+        fn initialized(&self, _: u64) {
+            self.0.lock().unwrap().push("initialized".to_string());
+        }
+
+        fn call_started(&self, _: u64, function: &str) {
+            self.0.lock().unwrap().push(format!("started {function}"));
+        }
+
+        fn call_finished(
+            &self,
+            _: u64,
+            function: &str,
+            result: Result<&ReturnValue, &HyperlightError>,
+        ) {
+            let outcome = if result.is_ok() { "ok" } else { "failed" };
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("finished {function}: {outcome}"));
+        }
+
+        fn restored(&self, _: u64) {
+            self.0.lock().unwrap().push("restored".to_string());
+        }
+
+        fn dropped(&self, _: u64) {
+            self.0.lock().unwrap().push("dropped".to_string());
+        }
+    }
+
+    with_rust_uninit_sandbox(|mut usbox| {
+        let events = Arc::new(Events::default());
+        usbox.add_observer(events.clone());
+        let mut sbox = usbox.evolve().unwrap();
+        let snapshot = sbox.snapshot().unwrap();
+        sbox.call::<String>("Echo", "hello".to_string()).unwrap();
+        assert!(sbox.call::<()>("NoSuchFunction", ()).is_err());
+        sbox.restore(snapshot).unwrap();
+        drop(sbox);
+
+        assert_eq!(
+            *events.0.lock().unwrap(),
+            [
+                "created",
+                "initialized",
+                "started Echo",
+                "finished Echo: ok",
+                "started NoSuchFunction",
+                "finished NoSuchFunction: failed",
+                "restored",
+                "dropped",
+            ]
+        );
+    });
+}
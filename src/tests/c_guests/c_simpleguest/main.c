@@ -207,6 +207,19 @@ int set_static(void) {
   return length;
 }
 
+static int constructed = 0;
+static __thread int thread_counter = 42;
+
+__attribute__((constructor)) static void construct(void) {
+  constructed = 1;
+}
+
+int get_static_init_state(void) {
+  // Thread-local variables and static constructors are set up by the
+  // guest runtime before hyperlight_main
+  return constructed ? thread_counter++ : -1;
+}
+
 hl_Vec *get_size_prefixed_buffer(const hl_FunctionCall* params) {
   hl_Vec input = params->parameters[0].value.VecBytes;
   return hl_flatbuffer_result_from_Bytes(input.data, input.len);
@@ -354,6 +367,7 @@ HYPERLIGHT_WRAP_FUNCTION(print_eleven_args, Int, 11, String, Int, Long, String,
 HYPERLIGHT_WRAP_FUNCTION(echo_float, Float, 1, Float)
 HYPERLIGHT_WRAP_FUNCTION(echo_double, Double, 1, Double)
 HYPERLIGHT_WRAP_FUNCTION(set_static, Int, 0)
+HYPERLIGHT_WRAP_FUNCTION(get_static_init_state, Int, 0)
 // HYPERLIGHT_WRAP_FUNCTION(get_size_prefixed_buffer, Int, 1, VecBytes) is not valid for functions that return VecBytes
 HYPERLIGHT_WRAP_FUNCTION(guest_abort_with_msg, Int, 2, Int, String)
 HYPERLIGHT_WRAP_FUNCTION(guest_abort_with_code, Int, 1, Int)
@@ -393,6 +407,7 @@ void hyperlight_main(void)
     HYPERLIGHT_REGISTER_FUNCTION("EchoFloat", echo_float);
     HYPERLIGHT_REGISTER_FUNCTION("EchoDouble", echo_double);
     HYPERLIGHT_REGISTER_FUNCTION("SetStatic", set_static);
+    HYPERLIGHT_REGISTER_FUNCTION("GetStaticInitState", get_static_init_state);
     // HYPERLIGHT_REGISTER_FUNCTION macro does not work for functions that return VecBytes,
     // so we use hl_register_function_definition directly
     hl_register_function_definition("GetSizePrefixedBuffer", get_size_prefixed_buffer, 1, (hl_ParameterType[]){hl_ParameterType_VecBytes}, hl_ReturnType_VecBytes);
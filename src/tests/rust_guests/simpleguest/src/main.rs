@@ -42,18 +42,24 @@ use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::flatbuffer_wrappers::guest_log_level::LogLevel;
 use hyperlight_common::flatbuffer_wrappers::util::get_flatbuffer_result;
 use hyperlight_common::log_level::GuestLogFilter;
+use hyperlight_common::stream::STREAM_CHUNK_SIZE;
 use hyperlight_common::vmem::{BasicMapping, MappingKind};
 use hyperlight_guest::error::{HyperlightGuestError, Result};
 use hyperlight_guest::exit::{abort_with_code, abort_with_code_and_message};
 use hyperlight_guest_bin::exception::arch::{Context, ExceptionInfo};
 use hyperlight_guest_bin::guest_function::definition::{GuestFunc, GuestFunctionDefinition};
+use hyperlight_guest_bin::borrowed_buffer::{borrowed_buffer, borrowed_buffer_mut};
 use hyperlight_guest_bin::guest_function::register::register_function;
 use hyperlight_guest_bin::host_comm::{
-    call_host_function, call_host_function_without_returning_result, get_host_return_value_raw,
-    print_output_with_host_print, read_n_bytes_from_user_memory,
+    call_host_function, call_host_function_without_returning_result, checkpoint,
+    get_host_return_value_raw, print_output_with_host_print, read_n_bytes_from_user_memory,
 };
+use hyperlight_guest_bin::locale::timezone_data;
 use hyperlight_guest_bin::memory::malloc;
-use hyperlight_guest_bin::{GUEST_HANDLE, guest_function, guest_logger, host_function};
+use hyperlight_guest_bin::messaging;
+use hyperlight_guest_bin::secrets::get_secret;
+use hyperlight_guest_bin::stream::{HostStreamReader, HostStreamWriter};
+use hyperlight_guest_bin::{GUEST_HANDLE, guest_function, guest_init, guest_logger, host_function};
 use log::{LevelFilter, error};
 use tracing::{Span, instrument};
 
@@ -514,6 +520,11 @@ fn get_static() -> i32 {
     unsafe { COUNTER }
 }
 
+#[guest_function("GetStaticAddress")]
+fn get_static_address() -> u64 {
+    (&raw const COUNTER) as u64
+}
+
 #[guest_function("AddToStaticAndFail")]
 fn add_to_static_and_fail() -> Result<i32> {
     unsafe { COUNTER += 10 };
@@ -523,6 +534,17 @@ fn add_to_static_and_fail() -> Result<i32> {
     ))
 }
 
+#[guest_function("AddToStaticCheckpointAndFail")]
+fn add_to_static_checkpoint_and_fail(i: i32) -> Result<i32> {
+    unsafe { COUNTER += i };
+    checkpoint("added")?;
+    unsafe { COUNTER += 10 };
+    Err(HyperlightGuestError::new(
+        ErrorCode::GuestError,
+        "Crash on purpose".to_string(),
+    ))
+}
+
 #[guest_function("24K_in_8K_out")]
 fn twenty_four_k_in_eight_k_out(input: Vec<u8>) -> Vec<u8> {
     assert!(input.len() == 24 * 1024, "Input must be 24K bytes");
@@ -552,6 +574,70 @@ fn get_dr0() -> u64 {
     value
 }
 
+#[guest_function("ReadMsr")]
+fn read_msr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    unsafe { core::arch::asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high) };
+    ((high as u64) << 32) | low as u64
+}
+
+#[guest_function("WriteMsr")]
+fn write_msr(msr: u32, value: u64) {
+    unsafe {
+        core::arch::asm!(
+            "wrmsr",
+            in("ecx") msr,
+            in("eax") value as u32,
+            in("edx") (value >> 32) as u32
+        )
+    };
+}
+
+#[guest_function("GetTimezoneData")]
+fn get_timezone_data(name: String) -> Result<Vec<u8>> {
+    Ok(timezone_data(&name)?.unwrap_or_default())
+}
+
+#[guest_function("GetSecret")]
+fn get_secret_value(name: String) -> Result<Vec<u8>> {
+    get_secret(&name)
+}
+
+#[guest_function("Publish")]
+fn publish(topic: String, message: Vec<u8>) -> Result<()> {
+    messaging::publish(&topic, &message)
+}
+
+#[guest_function("Subscribe")]
+fn subscribe(topic: String) -> Result<()> {
+    messaging::subscribe(&topic)
+}
+
+#[guest_function("Poll")]
+fn poll(topic: String) -> Result<Vec<u8>> {
+    Ok(messaging::poll(&topic)?.unwrap_or_default())
+}
+
+#[guest_function("StreamCopy")]
+fn stream_copy(input: u32, output: u32) -> Result<u64> {
+    let mut reader = HostStreamReader::new(input);
+    let mut writer = HostStreamWriter::new(output);
+    let mut copied = 0;
+    while let Some(chunk) = reader.next_chunk(STREAM_CHUNK_SIZE)? {
+        writer.write_all(&chunk)?;
+        copied += chunk.len() as u64;
+    }
+    Ok(copied)
+}
+
+#[guest_function("CallHostWithCallbacks")]
+fn call_host_with_callbacks(count: i32) -> Result<i32> {
+    #[host_function("HostEmitEvents")]
+    fn host_emit_events(count: i32) -> Result<i32>;
+
+    host_emit_events(count)
+}
+
 #[guest_function("Add")]
 fn add(a: i32, b: i32) -> Result<i32> {
     #[host_function("HostAdd")]
@@ -582,6 +668,21 @@ fn read_from_user_memory(num: u64, expected: Vec<u8>) -> Result<Vec<u8>> {
     Ok(bytes)
 }
 
+static mut INIT_DATA: Vec<u8> = Vec::new();
+
+#[guest_init]
+fn init(config: Vec<u8>) {
+    unsafe { INIT_DATA = config };
+}
+
+#[guest_function("GetInitData")]
+fn get_init_data() -> Vec<u8> {
+    #[allow(static_mut_refs)]
+    unsafe {
+        INIT_DATA.clone()
+    }
+}
+
 #[guest_function("ReadMappedBuffer")]
 fn read_mapped_buffer(base: u64, len: u64, do_map: bool) -> Vec<u8> {
     let base = base as usize as *const u8;
@@ -615,6 +716,20 @@ fn check_mapped_buffer(base: u64) -> bool {
         .is_some()
 }
 
+#[guest_function("SumBorrowedBuffer")]
+fn sum_borrowed_buffer(address: u64, len: u64) -> u64 {
+    // Safety: the host lent the buffer for this call
+    let buffer = unsafe { borrowed_buffer(address, len) };
+    buffer.iter().map(|&b| b as u64).sum()
+}
+
+#[guest_function("FillBorrowedBuffer")]
+fn fill_borrowed_buffer(address: u64, len: u64, value: i32) {
+    // Safety: the host lent the buffer for this call
+    let buffer = unsafe { borrowed_buffer_mut(address, len) };
+    buffer.fill(value as u8);
+}
+
 #[guest_function("WriteMappedBuffer")]
 fn write_mapped_buffer(base: u64, len: u64) -> bool {
     let base = base as usize as *mut u8;
@@ -643,6 +758,78 @@ fn write_mapped_buffer(base: u64, len: u64) -> bool {
     true
 }
 
+#[guest_function("ExecuteJitCode")]
+fn execute_jit_code() -> Result<i32> {
+    use hyperlight_guest_bin::paging::{Protection, protect};
+
+    // mov eax, 42; ret
+    const CODE: [u8; 6] = [0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3];
+
+    let layout = Layout::from_size_align(4096, 4096).unwrap();
+    let page = unsafe { alloc::alloc::alloc_zeroed(layout) };
+    if page.is_null() {
+        return Err(HyperlightGuestError::new(
+            ErrorCode::MallocFailed,
+            "Failed to allocate the code page".to_string(),
+        ));
+    }
+    unsafe { core::ptr::copy_nonoverlapping(CODE.as_ptr(), page, CODE.len()) };
+    unsafe { protect(page, 4096, Protection::ReadExecute)? };
+    let code: extern "C" fn() -> i32 = unsafe { core::mem::transmute(page) };
+    let result = code();
+    // The allocator writes to the page when it is freed
+    unsafe { protect(page, 4096, Protection::ReadWrite)? };
+    unsafe { alloc::alloc::dealloc(page, layout) };
+    Ok(result)
+}
+
+#[guest_function("MmioWriteRead")]
+fn mmio_write_read(addr: u64, value: u32) -> u32 {
+    let page = addr & !0xfff;
+    let reg = addr as usize as *mut u32;
+
+    unsafe {
+        hyperlight_guest_bin::paging::map_region(
+            page as _,
+            page as _,
+            4096,
+            MappingKind::Basic(BasicMapping {
+                readable: true,
+                writable: true,
+                executable: false,
+            }),
+        );
+        hyperlight_guest_bin::paging::barrier::first_valid_same_ctx();
+
+        reg.write_volatile(value);
+        reg.read_volatile()
+    }
+}
+
+#[guest_function("ReadTsc")]
+fn read_tsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+static LAST_INTERRUPT: AtomicU64 = AtomicU64::new(0);
+
+fn record_interrupt(vector: u8) {
+    LAST_INTERRUPT.store(vector as u64, Ordering::Release);
+}
+
+// Installs a handler for the interrupt `vector`, and spins until the host injects it
+#[guest_function("WaitForInterrupt")]
+fn wait_for_interrupt(vector: u32) -> Result<u32> {
+    LAST_INTERRUPT.store(0, Ordering::Release);
+    hyperlight_guest_bin::interrupt::register_handler(vector as u8, record_interrupt)?;
+    // Interrupts are only enabled by the next call once a handler is installed
+    unsafe { core::arch::asm!("sti", options(nomem, nostack)) };
+    while LAST_INTERRUPT.load(Ordering::Acquire) == 0 {
+        core::hint::spin_loop();
+    }
+    Ok(LAST_INTERRUPT.load(Ordering::Acquire) as u32)
+}
+
 #[guest_function("ExecMappedBuffer")]
 fn exec_mapped_buffer(base: u64, len: u64) -> bool {
     let base = base as usize as *mut u8;